@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compares decoding a `Message` with decoding a `MessageView` of the same response, requires
+//!  nightly: `cargo bench`
+
+#![feature(test)]
+
+extern crate test;
+extern crate trust_dns;
+
+use std::net::Ipv4Addr;
+
+use test::Bencher;
+
+use trust_dns::op::{Message, MessageType, MessageView, Query};
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns::rr::rdata::MX;
+use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+
+/// A response with a query, answers, name servers and additionals, typical of a busy server
+fn response() -> Vec<u8> {
+  let origin = Name::parse("example.com.", None).unwrap();
+  let name = |label: &str| Name::parse(label, Some(&origin)).unwrap();
+  let record = |name: Name, rr_type: RecordType, rdata: RData| {
+    Record::new().name(name).rr_type(rr_type).dns_class(DNSClass::IN).ttl(86400).rdata(rdata).clone()
+  };
+
+  let mut message = Message::new();
+  message.id(10).message_type(MessageType::Response);
+  message.add_query(Query::new().name(origin.clone()).query_type(RecordType::MX).query_class(DNSClass::IN).clone());
+  for (index, host) in ["mail1", "mail2", "mail3", "mail4"].iter().enumerate() {
+    message.add_answer(record(origin.clone(), RecordType::MX, RData::MX(MX::new(index as u16 * 10, name(host)))));
+    message.add_additional(record(name(host), RecordType::A, RData::A(Ipv4Addr::new(192, 0, 2, index as u8))));
+  }
+  for ns in ["ns1", "ns2"].iter() {
+    message.add_name_server(record(origin.clone(), RecordType::NS, RData::NS(name(ns))));
+  }
+
+  let mut buffer: Vec<u8> = Vec::with_capacity(512);
+  message.emit(&mut BinEncoder::new(&mut buffer)).unwrap();
+  buffer
+}
+
+#[bench]
+fn bench_message_read(b: &mut Bencher) {
+  let buffer = response();
+  b.iter(|| Message::read(&mut BinDecoder::new(&buffer)).unwrap());
+}
+
+#[bench]
+fn bench_message_view_read(b: &mut Bencher) {
+  let buffer = response();
+  b.iter(|| MessageView::read(&buffer).unwrap().get_answers().len());
+}
+
+/// what a server needs to route a request, the query
+#[bench]
+fn bench_message_view_query(b: &mut Bencher) {
+  let buffer = response();
+  let origin = Name::parse("example.com.", None).unwrap();
+  b.iter(|| MessageView::read(&buffer).unwrap().get_queries()[0].get_name().eq_name(&origin));
+}
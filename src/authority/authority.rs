@@ -13,18 +13,27 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::rc::Rc;
 
 use chrono::UTC;
+use data_encoding::base32hex;
 use openssl::crypto::pkey::Role;
 
-use ::authority::{Journal, RRSet, UpdateResult, ZoneType};
+use ::authority::{Journal, QueryPolicy, RRSet, SerialPolicy, TransferPolicy, UpdatePolicy, UpdateResult, ZoneType};
+use ::client::ZoneDiff;
 use ::error::{PersistenceErrorKind, PersistenceResult};
 use ::op::{Message, UpdateMessage, ResponseCode, Query};
 use ::rr::{DNSClass, Name, RData, Record, RecordType};
-use ::rr::rdata::{NSEC, SIG};
-use ::rr::dnssec::Signer;
+use ::rr::rdata::{NSEC, NSEC3, NSEC3PARAM, SIG, compare_serial};
+use ::rr::dnssec::{Signer, TsigKey, split_tsig};
+use ::serialize::txt::Writer;
 
 /// Accessor key for RRSets in the Authority.
 #[derive(Eq, PartialEq, Debug, Hash, Clone)]
@@ -74,12 +83,20 @@ pub struct Authority {
   records: BTreeMap<RrKey, RRSet>,
   zone_type: ZoneType,
   allow_update: bool,
+  update_policy: UpdatePolicy,
+  transfer_policy: TransferPolicy,
+  query_policy: QueryPolicy,
+  serial_policy: SerialPolicy,
   // Private key mapped to the Record of the DNSKey
   //  TODO: these private_keys should be stored securely. Ideally, we have keys only stored per
   //   server instance, but that requires requesting updates from the parent zone, which may or
   //   may not support dynamic updates to register the new key... Trust-DNS will provide support
   //   for this, in some form, perhaps alternate root zones...
   secure_keys: Vec<Signer>,
+  // the primaries of a secondary zone, and if one has notified of a change
+  primaries: Vec<SocketAddr>,
+  notified: bool,
+  also_notify: Vec<SocketAddr>,
 }
 
 impl Authority {
@@ -98,7 +115,9 @@ impl Authority {
   /// The new `Authority`.
   pub fn new(origin: Name, records: BTreeMap<RrKey, RRSet>, zone_type: ZoneType, allow_update: bool) -> Authority {
     Authority{ origin: origin, class: DNSClass::IN,  journal: None, records: records, zone_type: zone_type,
-      allow_update: allow_update, secure_keys: Vec::new() }
+      allow_update: allow_update, update_policy: UpdatePolicy::new(), transfer_policy: TransferPolicy::new(),
+      query_policy: QueryPolicy::new(), serial_policy: SerialPolicy::default(), secure_keys: Vec::new(),
+      primaries: Vec::new(), notified: false, also_notify: Vec::new() }
   }
 
   /// By adding a secure key, this will implicitly enable dnssec for the zone.
@@ -117,6 +136,51 @@ impl Authority {
     self.secure_keys.push(signer);
   }
 
+  /// Denies the existence of names with NSEC3 records, hashed with the parameters, rather than
+  ///  NSEC records, once the zone is signed. The NSEC3PARAM is added at the origin.
+  pub fn set_nsec3param(&mut self, param: NSEC3PARAM) {
+    let mut record = Record::with(self.origin.clone(), RecordType::NSEC3PARAM, self.get_minimum_ttl());
+    record.rdata(RData::NSEC3PARAM(param));
+
+    // there is only one NSEC3PARAM
+    self.records.remove(&RrKey::new(&self.origin, RecordType::NSEC3PARAM));
+
+    let serial = self.get_serial();
+    self.upsert(record, serial);
+  }
+
+  /// Replaces the records of the zone with those of the reloaded zone, e.g. read again from its
+  ///  changed zone file. The keys and journal of this zone are kept, the keys are added back to
+  ///  the new records and the new records are persisted to the journal.
+  ///
+  /// # Arguments
+  ///
+  /// * `zone` - the reloaded zone, only its records are used
+  pub fn reload(&mut self, zone: Authority) -> PersistenceResult<()> {
+    // there is no diff of a reload, IXFR falls back to AXFR for the older serials
+    if let Some(journal) = self.journal.as_ref() {
+      try!(journal.clear_diffs());
+    }
+
+    let nsec3param: Option<NSEC3PARAM> = self.get_nsec3param().cloned();
+    self.records = zone.records;
+
+    let secure_keys = mem::replace(&mut self.secure_keys, Vec::new());
+    for signer in secure_keys {
+      self.add_secure_key(signer);
+    }
+
+    // the reloaded zone is signed as the zone was
+    if let Some(nsec3param) = nsec3param {
+      if self.get_nsec3param().is_none() { self.set_nsec3param(nsec3param); }
+    }
+    if !self.secure_keys.is_empty() {
+      self.sign_zone();
+    }
+
+    self.persist_to_journal()
+  }
+
   /// Recovers the zone from a Journal, returns an error on failure to recover the zone.
   ///
   /// # Arguments
@@ -155,19 +219,103 @@ impl Authority {
 
       info!("persisting zone to journal at SOA.serial: {}", serial);
 
-      // TODO: THIS NEEDS TO BE IN A TRANSACTION!!!
-      try!(journal.insert_record(serial, Record::new().rr_type(RecordType::AXFR)));
+      // the zone is written whole or not at all, a partial dump would lose records on recovery
+      try!(journal.transaction(|| {
+        try!(journal.insert_record(serial, Record::new().rr_type(RecordType::AXFR)));
 
-      for rr_set in self.records.values() {
-        // TODO: should we preserve rr_sets or not?
-        for record in rr_set.iter() {
-          try!(journal.insert_record(serial, record));
+        for rr_set in self.records.values() {
+          // TODO: should we preserve rr_sets or not?
+          for record in rr_set.iter() {
+            try!(journal.insert_record(serial, record));
+          }
         }
+
+        Ok(())
+      }));
+    }
+
+    Ok(())
+  }
+
+  /// Replays the diffs of the updates in the journal onto the zone, once it has been loaded from
+  ///  its zone file, the checkpoint which the diffs start from. The RRSIG, NSEC and NSEC3 records
+  ///  of the diffs are skipped, the zone is signed again once its keys have been added.
+  ///
+  /// If the serial of the zone file is newer than that of the journal, i.e. the zone file was
+  ///  edited, the journal is truncated.
+  ///
+  /// # Return value
+  ///
+  /// The number of diffs which were replayed, an error if the diffs do not follow on from the
+  ///  serial of the zone
+  pub fn replay_journal(&mut self) -> PersistenceResult<usize> {
+    fn is_signature(record: &Record) -> bool {
+      match record.get_rr_type() {
+        RecordType::RRSIG | RecordType::SIG | RecordType::NSEC | RecordType::NSEC3 => true,
+        _ => false,
+      }
+    }
+
+    let serial = self.get_serial();
+    let (diffs, last_serial) = {
+      let journal = if let Some(journal) = self.journal.as_ref() { journal } else { return Ok(0) };
+      let last_serial = if let Some(last_serial) = try!(journal.select_last_serial()) { last_serial } else { return Ok(0) };
+
+      match try!(journal.select_diffs(serial, last_serial)) {
+        Some(diffs) => (diffs, last_serial),
+        None if compare_serial(serial, last_serial) == Some(Ordering::Greater) => {
+          warn!("zone {} at serial {} is newer than its journal at serial {}, truncating the journal", self.origin, serial, last_serial);
+          try!(journal.truncate());
+          return Ok(0)
+        },
+        None => return Err(PersistenceErrorKind::OutOfSync(serial, last_serial).into()),
       }
+    };
+
+    if diffs.is_empty() { return Ok(0) }
+
+    let diffs: Vec<ZoneDiff> = diffs.into_iter().map(|diff| {
+      ZoneDiff{ from_soa: diff.from_soa,
+                to_soa: diff.to_soa,
+                deleted: diff.deleted.into_iter().filter(|rr| !is_signature(rr)).collect(),
+                added: diff.added.into_iter().filter(|rr| !is_signature(rr)).collect() }
+    }).collect();
 
-      // TODO: COMMIT THE TRANSACTION!!!
+    if !self.apply_diffs(&diffs) {
+      return Err(PersistenceErrorKind::OutOfSync(serial, last_serial).into())
     }
 
+    info!("replayed {} diffs of zone {} from the journal, to serial {}", diffs.len(), self.origin, self.get_serial());
+    Ok(diffs.len())
+  }
+
+  /// Writes the zone to its zone file and truncates its journal, e.g. periodically, so that the
+  ///  journal only holds the updates since the zone file was written, which bounds its size. The
+  ///  RRSIG, NSEC and NSEC3 records are not written, the zone is signed again when it is loaded.
+  ///  Any `$INCLUDE`s of the zone file are replaced by the records.
+  ///
+  /// The zone file is only replaced once the zone has been written completely, if that fails the
+  ///  journal is left as it is. Does nothing if the zone has no journal.
+  ///
+  /// # Arguments
+  ///
+  /// * `zone_file` - the zone file the zone was loaded from, see `replay_journal()`
+  pub fn checkpoint(&self, zone_file: &Path) -> PersistenceResult<()> {
+    let journal = if let Some(journal) = self.journal.as_ref() { journal } else { return Ok(()) };
+    let new_file = zone_file.with_extension(".tmp");
+
+    {
+      let mut writer = BufWriter::new(try!(File::create(&new_file)));
+      try!(Writer::new().signatures(false).write(self, &mut writer));
+      try!(writer.flush());
+      try!(writer.get_ref().sync_all());
+    }
+
+    // the journal is only truncated once the zone file holds its updates
+    try!(fs::rename(&new_file, zone_file));
+    try!(journal.truncate());
+
+    info!("checkpointed zone {} at serial {} to {:?}", self.origin, self.get_serial(), zone_file);
     Ok(())
   }
 
@@ -179,6 +327,63 @@ impl Authority {
     self.journal.as_ref()
   }
 
+  /// Sets the authorization policy for dynamic updates, see `UpdatePolicy`. The policy only
+  ///  applies if updates are allowed for the zone.
+  pub fn set_update_policy(&mut self, update_policy: UpdatePolicy) {
+    self.update_policy = update_policy;
+  }
+
+  pub fn get_update_policy(&self) -> &UpdatePolicy {
+    &self.update_policy
+  }
+
+  /// Sets which clients may transfer the zone, see `TransferPolicy`
+  pub fn set_transfer_policy(&mut self, transfer_policy: TransferPolicy) {
+    self.transfer_policy = transfer_policy;
+  }
+
+  pub fn get_transfer_policy(&self) -> &TransferPolicy {
+    &self.transfer_policy
+  }
+
+  /// Sets which clients may query the zone, see `QueryPolicy`
+  pub fn set_query_policy(&mut self, query_policy: QueryPolicy) {
+    self.query_policy = query_policy;
+  }
+
+  pub fn get_query_policy(&self) -> &QueryPolicy {
+    &self.query_policy
+  }
+
+  /// How the serial is incremented when the zone changes, by default it is incremented by one
+  pub fn set_serial_policy(&mut self, serial_policy: SerialPolicy) {
+    self.serial_policy = serial_policy;
+  }
+
+  pub fn get_serial_policy(&self) -> SerialPolicy {
+    self.serial_policy
+  }
+
+  /// Sets the primaries of a secondary, `ZoneType::Slave`, zone. The zone is transferred from
+  ///  these, and only these may NOTIFY the zone of changes.
+  pub fn set_primaries(&mut self, primaries: Vec<SocketAddr>) {
+    self.primaries = primaries;
+  }
+
+  pub fn get_primaries(&self) -> &[SocketAddr] {
+    &self.primaries
+  }
+
+  /// Secondaries to NOTIFY of changes to the zone, in addition to the name servers of the zone,
+  ///  see `ZoneNotifier`
+  pub fn set_also_notify(&mut self, also_notify: Vec<SocketAddr>) {
+    self.also_notify = also_notify;
+  }
+
+  pub fn get_also_notify(&self) -> &[SocketAddr] {
+    &self.also_notify
+  }
+
   #[cfg(test)]
   pub fn set_allow_update(&mut self, allow_update: bool) {
     self.allow_update = allow_update;
@@ -193,6 +398,10 @@ impl Authority {
     &self.origin
   }
 
+  pub fn get_class(&self) -> DNSClass {
+    self.class
+  }
+
   pub fn get_zone_type(&self) -> ZoneType {
     self.zone_type
   }
@@ -217,6 +426,34 @@ impl Authority {
     self.lookup(&self.origin, RecordType::SOA, is_secure)
   }
 
+  /// Returns the SOA for the authority section of a negative response, the TTL of which is the
+  ///  lesser of its TTL and the minimum field of the SOA
+  ///
+  /// [RFC 2308](https://tools.ietf.org/html/rfc2308), Negative Caching of DNS Queries, March 1998
+  ///
+  /// ```text
+  /// 3 - Negative Answers from Authoritative Servers
+  ///
+  ///    Name servers authoritative for a zone MUST include the SOA record of
+  ///    the zone in the authority section of the response when reporting an
+  ///    NXDOMAIN or indicating that no data of the requested type exists.
+  ///    This is required so that the response may be cached.  The TTL of this
+  ///    record is set from the minimum of the MINIMUM field of the SOA record
+  ///    and the TTL of the SOA itself, and indicates how long a resolver may
+  ///    cache the negative answer.
+  /// ```
+  pub fn get_soa_negative(&self, is_secure: bool) -> Vec<Record> {
+    let minimum = self.get_minimum_ttl();
+    self.get_soa_secure(is_secure).into_iter()
+                                  .map(|record| {
+                                    let mut record = record.clone();
+                                    let ttl = ::std::cmp::min(record.get_ttl(), minimum);
+                                    record.ttl(ttl);
+                                    record
+                                  })
+                                  .collect()
+  }
+
   pub fn get_minimum_ttl(&self) -> u32 {
     self.get_soa().map_or(0, |soa| if let &RData::SOA(ref rdata) = soa.get_rdata() { rdata.get_minimum() } else { 0 })
   }
@@ -245,8 +482,9 @@ impl Authority {
     };
 
     let serial = if let &mut RData::SOA(ref mut soa_rdata) = soa.get_rdata_mut() {
-      soa_rdata.increment_serial();
-      soa_rdata.get_serial()
+      let serial = self.serial_policy.next_serial(soa_rdata.get_serial(), UTC::now());
+      soa_rdata.set_serial(serial);
+      serial
     } else {
       panic!("This was not an SOA record"); // valid panic, never should happen
     };
@@ -342,6 +580,7 @@ impl Authority {
     //      for rrset in temp
     //           if (zone_rrset<rrset.name, rrset.type> != rrset)
     //                return (NXRRSET)
+    let mut required_rrsets: HashMap<RrKey, Vec<&Record>> = HashMap::new();
     for require in pre_requisites {
       if require.get_ttl() != 0 {
         debug!("ttl must be 0 for: {:?}", require);
@@ -402,22 +641,27 @@ impl Authority {
             return Err(ResponseCode::FormErr);
           }
         ,
-        class @ _ if class == self.class =>
+        class @ _ if class == self.class => {
           // zone     rrset    rr       RRset exists (value dependent)
-          if self.lookup(require.get_name(), require.get_rr_type(), false)
-                 .iter()
-                 .filter(|rr| *rr == &require)
-                 .next()
-                 .is_none() {
-            return Err(ResponseCode::NXRRSet);
-          } else {
-            continue;
-          }
-          ,
+          //  the rrsets are compared once all the prerequisites have been collected
+          required_rrsets.entry(RrKey::new(require.get_name(), require.get_rr_type()))
+                         .or_insert(Vec::new())
+                         .push(require);
+        },
         _ => return Err(ResponseCode::FormErr),
       }
     }
 
+    // each rrset must be exactly matched by the zone, same members, no more, no less
+    for (key, required) in required_rrsets {
+      let rr_set = self.lookup(&key.name, key.record_type, false);
+      if rr_set.len() != required.len() ||
+         !rr_set.iter().all(|rr| required.contains(rr)) ||
+         !required.iter().all(|rr| rr_set.contains(rr)) {
+        return Err(ResponseCode::NXRRSet);
+      }
+    }
+
     // if we didn't bail everything checked out...
     Ok(())
   }
@@ -444,7 +688,7 @@ impl Authority {
   ///   and restore the zone to its original state before answering the
   ///   requestor.
   /// ```
-  fn authorize(&self, update_message: &Message) -> UpdateResult<()> {
+  fn authorize(&self, update_message: &Message, src: Option<&IpAddr>, request_bytes: Option<&[u8]>) -> UpdateResult<()> {
     // 3.3.3 - Pseudocode for Permission Checking
     //
     //      if (security policy exists)
@@ -460,49 +704,131 @@ impl Authority {
       return Err(ResponseCode::Refused)
     }
 
-    // verify sig0, currently the only authorization that is accepted.
-    let sig0s: &[Record] = update_message.get_sig0();
-    debug!("authorizing with: {:?}", sig0s);
-    if !sig0s.is_empty() && sig0s.iter()
-            .filter_map(|sig0| if let &RData::SIG(ref sig) = sig0.get_rdata() { Some(sig) } else { None })
-            .any(|sig| {
-              let name = sig.get_signer_name();
-              let keys = self.lookup(name, RecordType::KEY, false);
-              debug!("found keys {:?}", keys);
-              keys.iter()
-                  .filter_map(|rr_set| if let &RData::KEY(ref key) = rr_set.get_rdata() { Some(key) } else { None })
-                  .any(|key| {
-                    let pkey = key.get_algorithm().public_key_from_vec(key.get_public_key());
-                    if let Err(error) = pkey {
-                      warn!("public key {:?} of {} could not be used: {}", key, name, error);
-                      return false
-                    }
-
-                    let pkey = pkey.unwrap();
-                    if pkey.can(Role::Verify) {
-                      let signer: Signer = Signer::new_verifier(*key.get_algorithm(), pkey, sig.get_signer_name().clone());
-
-                      if signer.verify_message(update_message, sig.get_sig()) {
-                        info!("verified sig: {:?} with key: {:?}", sig, key);
-                        true
-                      } else {
-                        debug!("did not verify sig: {:?} with key: {:?}", sig, key);
-                        false
-                      }
-                    } else {
-                      warn!("{}: can not be used to verify", name);
-                      false
-                    }
-                  })
-            }) {
-      return Ok(());
-    } else {
-      warn!("no sig0 matched registered records: id {}", update_message.get_id());
+    if !self.update_policy.is_source_allowed(src) {
+      warn!("update from {:?} is not permitted: {}", src, self.origin);
+      return Err(ResponseCode::Refused)
     }
 
+    // the key which signed the update, sig0 against the KEYs of the zone, or tsig against the
+    //  keys of the update policy
+    let signer: Option<Name> = self.verify_sig0(update_message)
+                                   .or_else(|| request_bytes.and_then(|bytes| self.verify_tsig(bytes)));
+
     // getting here, we will always default to rejecting the request
     //  the code will only ever explcitly return authrorized actions.
-    Err(ResponseCode::Refused)
+    match signer.as_ref() {
+      Some(signer) if !self.update_policy.is_key_allowed(signer) => {
+        warn!("key {} is not permitted to update: {}", signer, self.origin);
+        Err(ResponseCode::Refused)
+      },
+      None if !self.update_policy.is_unsigned_allowed() => {
+        warn!("no sig0 or tsig matched registered keys: id {}", update_message.get_id());
+        Err(ResponseCode::Refused)
+      },
+      signer if !self.update_policy.is_update_allowed(signer, update_message.get_updates()) => {
+        warn!("update by {:?} is not permitted by the grants: {}", signer, self.origin);
+        Err(ResponseCode::Refused)
+      },
+      _ => Ok(()),
+    }
+  }
+
+  /// verifies the sig0 against the KEY records of the zone, returning the name of the signer
+  fn verify_sig0(&self, update_message: &Message) -> Option<Name> {
+    let sig0s: &[Record] = update_message.get_sig0();
+    debug!("authorizing with: {:?}", sig0s);
+    sig0s.iter()
+         .filter_map(|sig0| if let &RData::SIG(ref sig) = sig0.get_rdata() { Some(sig) } else { None })
+         .find(|sig| {
+           let name = sig.get_signer_name();
+           let keys = self.lookup(name, RecordType::KEY, false);
+           debug!("found keys {:?}", keys);
+           keys.iter()
+               .filter_map(|rr_set| if let &RData::KEY(ref key) = rr_set.get_rdata() { Some(key) } else { None })
+               .any(|key| {
+                 let pkey = key.get_algorithm().public_key_from_vec(key.get_public_key());
+                 if let Err(error) = pkey {
+                   warn!("public key {:?} of {} could not be used: {}", key, name, error);
+                   return false
+                 }
+
+                 let pkey = pkey.unwrap();
+                 if pkey.can(Role::Verify) {
+                   let signer: Signer = Signer::new_verifier(*key.get_algorithm(), pkey, sig.get_signer_name().clone());
+
+                   if signer.verify_message(update_message, sig.get_sig()) {
+                     info!("verified sig: {:?} with key: {:?}", sig, key);
+                     true
+                   } else {
+                     debug!("did not verify sig: {:?} with key: {:?}", sig, key);
+                     false
+                   }
+                 } else {
+                   warn!("{}: can not be used to verify", name);
+                   false
+                 }
+               })
+         })
+         .map(|sig| sig.get_signer_name().clone())
+  }
+
+  /// verifies the tsig of the serialized request against the keys of the update policy,
+  ///  returning the name of the key
+  fn verify_tsig(&self, request_bytes: &[u8]) -> Option<Name> {
+    Self::verify_tsig_with(self.update_policy.get_tsig_keys(), request_bytes).map(|(key, _)| key.get_name().clone())
+  }
+
+  /// verifies the tsig of the serialized request against the keys, returning the key and the MAC
+  ///  of the request, which is needed to sign the response
+  fn verify_tsig_with<'k>(keys: &'k [TsigKey], request_bytes: &[u8]) -> Option<(&'k TsigKey, Vec<u8>)> {
+    if keys.is_empty() { return None }
+
+    let (unsigned, record) = match split_tsig(request_bytes) {
+      Ok(Some(split)) => split,
+      Ok(None) => return None,
+      Err(error) => { warn!("could not read the tsig of the request: {}", error); return None },
+    };
+
+    let tsig = if let &RData::TSIG(ref tsig) = record.get_rdata() { tsig } else { return None };
+    let key: &TsigKey = match keys.iter()
+                                  .find(|key| key.get_name() == record.get_name() && &key.get_algorithm().to_name() == tsig.get_algorithm()) {
+      Some(key) => key,
+      None => { warn!("no tsig key for: {} {}", record.get_name(), tsig.get_algorithm()); return None },
+    };
+
+    if !TsigKey::is_time_valid(tsig, UTC::now().timestamp() as u64) {
+      warn!("tsig of {} is outside of the time window: {}", key.get_name(), tsig.get_time_signed());
+      return None
+    }
+
+    match key.verify(None, &unsigned, tsig, false) {
+      Ok(true) => { info!("verified tsig with key: {}", key.get_name()); Some((key, tsig.get_mac().to_vec())) },
+      Ok(false) => { warn!("tsig did not verify with key: {}", key.get_name()); None },
+      Err(error) => { warn!("could not verify tsig with key {}: {}", key.get_name(), error); None },
+    }
+  }
+
+  /// Checks the transfer policy of the zone for an AXFR request
+  ///
+  /// # Arguments
+  ///
+  /// * `src` - the address of the client, if known
+  /// * `request_bytes` - the request as it was received, for verifying its TSIG
+  ///
+  /// # Return value
+  ///
+  /// The key the request was signed with and the MAC of the request, with which the response
+  ///  must be signed, or Refused if the client may not transfer the zone.
+  pub fn authorize_transfer(&self, src: Option<&IpAddr>, request_bytes: Option<&[u8]>) -> UpdateResult<Option<(TsigKey, Vec<u8>)>> {
+    let signer = request_bytes.and_then(|bytes| Self::verify_tsig_with(self.transfer_policy.get_tsig_keys(), bytes))
+                              .map(|(key, mac)| (key.clone(), mac));
+
+    if self.transfer_policy.is_open() || signer.is_some() || self.transfer_policy.is_source_allowed(src) {
+      Ok(signer)
+    } else {
+      warn!("transfer to {:?} is not permitted: {}", src, self.origin);
+      Err(ResponseCode::Refused)
+    }
   }
 
   /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -674,6 +1000,12 @@ impl Authority {
           //  Update RR and a non-CNAME Zone RRset or vice versa, ignore the CNAME
           //  Update RR, otherwise replace the CNAME Zone RR with the CNAME Update
           //  RR.
+          //
+          //  the DNSSEC records of RFC 4035 may coexist with a CNAME
+          if self.is_cname_conflict(rr) {
+            info!("ignoring update conflicting with CNAME: {:?}", rr);
+            continue;
+          }
 
           // zone     rrset    rr       Add to an RRset
           info!("upserting record: {:?}", rr);
@@ -697,7 +1029,7 @@ impl Authority {
               info!("deleting all records at name (not SOA or NS at origin): {:?}", rr.get_name());
               let to_delete = self.records.keys().filter(|k| !((k.record_type == RecordType::SOA ||
                                                                 k.record_type == RecordType::NS) &&
-                                                               k.name != self.origin))
+                                                               k.name == self.origin))
                                                  .filter(|k| &k.name == rr.get_name())
                                                  .cloned()
                                                  .collect::<Vec<RrKey>>();
@@ -769,6 +1101,102 @@ impl Authority {
     records.insert(record, serial)
   }
 
+  /// removes the record with the same rdata from its rrset, true if it was removed
+  fn remove(&mut self, record: &Record, serial: u32) -> bool {
+    self.records.get_mut(&RrKey::new(record.get_name(), record.get_rr_type()))
+                .map_or(false, |rr_set| rr_set.remove(record, serial))
+  }
+
+  /// true if adding the record would put a CNAME beside other data at its name
+  fn is_cname_conflict(&self, record: &Record) -> bool {
+    fn is_dnssec(record_type: RecordType) -> bool {
+      match record_type {
+        RecordType::RRSIG | RecordType::SIG | RecordType::NSEC | RecordType::NSEC3 => true,
+        _ => false,
+      }
+    }
+
+    let record_type = record.get_rr_type();
+    if is_dnssec(record_type) { return false }
+
+    self.records.keys()
+                .filter(|k| &k.name == record.get_name())
+                .filter(|k| !is_dnssec(k.record_type))
+                .any(|k| (record_type == RecordType::CNAME) != (k.record_type == RecordType::CNAME))
+  }
+
+  /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+  ///
+  /// ```text
+  /// 3.10. If a slave receives a NOTIFY request from a host that is not a
+  ///    known master for the zone containing the QNAME, it should ignore the
+  ///    request and produce an error message in its operations log.
+  ///
+  /// 3.11. The only defined NOTIFY event at this time is that the SOA RR
+  ///    has changed.  Upon completion of a NOTIFY transaction, the slave
+  ///    should behave as though the zone's REFRESH interval had expired.
+  /// ```
+  ///
+  /// Marks a secondary zone to be refreshed, see `take_notified()`.
+  ///
+  /// # Arguments
+  ///
+  /// * `src` - the address the NOTIFY was received from, it must be one of the primaries
+  pub fn notify(&mut self, src: Option<&IpAddr>) -> UpdateResult<()> {
+    if self.zone_type != ZoneType::Slave {
+      warn!("notify of zone which is not a secondary: {}", self.origin);
+      return Err(ResponseCode::NotAuth)
+    }
+
+    if !src.map_or(false, |src| self.primaries.iter().any(|primary| &primary.ip() == src)) {
+      warn!("notify of {} from {:?}, which is not a primary of the zone", self.origin, src);
+      return Err(ResponseCode::Refused)
+    }
+
+    info!("notified of change to zone: {}", self.origin);
+    self.notified = true;
+    Ok(())
+  }
+
+  /// Returns true if a primary has notified of a change since the last call, and clears the flag
+  pub fn take_notified(&mut self) -> bool {
+    mem::replace(&mut self.notified, false)
+  }
+
+  /// Applies the diffs of an incremental transfer from a primary, in order.
+  ///
+  /// Returns false, and leaves the zone unchanged, if the diffs do not start at the serial of
+  ///  the zone.
+  pub fn apply_diffs(&mut self, diffs: &[ZoneDiff]) -> bool {
+    let from_serial = diffs.first().and_then(|diff| if let &RData::SOA(ref soa) = diff.from_soa.get_rdata() { Some(soa.get_serial()) } else { None });
+    if from_serial != Some(self.get_serial()) {
+      warn!("diffs of {} do not start at serial {}: {:?}", self.origin, self.get_serial(), from_serial);
+      return false
+    }
+
+    for diff in diffs {
+      let serial = if let &RData::SOA(ref soa) = diff.to_soa.get_rdata() { soa.get_serial() } else { self.get_serial() };
+
+      // the last NS of a name is not removed, it is retried once the additions are made
+      let retry: Vec<&Record> = diff.deleted.iter()
+                                    .filter(|rr| rr.get_rr_type() != RecordType::SOA)
+                                    .filter(|rr| !self.remove(rr, serial))
+                                    .collect();
+
+      for record in diff.added.iter().filter(|rr| rr.get_rr_type() != RecordType::SOA) {
+        self.upsert(record.clone(), serial);
+      }
+
+      for record in retry {
+        self.remove(record, serial);
+      }
+
+      self.upsert(diff.to_soa.clone(), serial);
+    }
+
+    true
+  }
+
   /// Takes the UpdateMessage, extracts the Records, and applies the changes to the record set.
   ///
   /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -827,12 +1255,130 @@ impl Authority {
   /// true if any of additions, updates or deletes were made to the zone, false otherwise. Err is
   ///  returned in the case of bad data, etc.
   pub fn update(&mut self, update: &Message) -> UpdateResult<bool> {
+    self.update_from(update, None, None)
+  }
+
+  /// Performs the update, see `update()`, authorizing it with the source address and serialized
+  ///  form of the request, which are required for network restrictions and TSIG respectively.
+  ///
+  /// # Arguments
+  ///
+  /// * `update` - the update message
+  /// * `src` - the address the update was received from
+  /// * `request_bytes` - the update message as it was received
+  pub fn update_from(&mut self, update: &Message, src: Option<&IpAddr>, request_bytes: Option<&[u8]>) -> UpdateResult<bool> {
     // the spec says to authorize after prereqs, seems better to auth first.
-    try!(self.authorize(update));
+    try!(self.authorize(update, src, request_bytes));
     try!(self.verify_prerequisites(update.get_pre_requisites()));
     try!(self.pre_scan(update.get_updates()));
 
-    self.update_records(update.get_updates(), true)
+    // the zone before the update, the diff is journaled for IXFR
+    let before: Option<BTreeSet<Record>> = if self.journal.is_some() { Some(self.all_records()) } else { None };
+    let updated = try!(self.update_records(update.get_updates(), true));
+
+    if let (true, Some(before)) = (updated, before) {
+      self.persist_diff(before);
+    }
+
+    Ok(updated)
+  }
+
+  /// every record of the zone, including the RRSIGs
+  fn all_records(&self) -> BTreeSet<Record> {
+    self.records.values().flat_map(|rr_set| rr_set.get_records(true)).cloned().collect()
+  }
+
+  /// writes the diff between the zone before the update and the current zone to the journal
+  fn persist_diff(&self, before: BTreeSet<Record>) {
+    let journal = if let Some(journal) = self.journal.as_ref() { journal } else { return };
+    let after = self.all_records();
+
+    let old_soa: Option<Record> = before.iter().find(|rr| rr.get_rr_type() == RecordType::SOA).cloned();
+    let new_soa: Option<Record> = self.get_soa().cloned();
+
+    if let (Some(old_soa), Some(new_soa)) = (old_soa, new_soa) {
+      let deleted: Vec<Record> = before.difference(&after).filter(|rr| rr.get_rr_type() != RecordType::SOA).cloned().collect();
+      let added: Vec<Record> = after.difference(&before).filter(|rr| rr.get_rr_type() != RecordType::SOA).cloned().collect();
+
+      // without the diff, the history is broken and IXFR falls back to AXFR
+      if let Err(error) = journal.insert_diff(&ZoneDiff{ from_soa: old_soa, to_soa: new_soa, deleted: deleted, added: added }) {
+        error!("could not persist the diff of the update: {}", error);
+      }
+    }
+  }
+
+  /// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer in DNS, August 1996
+  ///
+  /// ```text
+  /// 4. Response Format
+  ///
+  ///    If incremental zone transfer is available, one or more difference
+  ///    sequences is returned.  The list of difference sequences is preceded
+  ///    and followed by a copy of the server's current version of the SOA.
+  ///
+  ///    Each difference sequence represents one update to the zone (one SOA
+  ///    serial change) consisting of deleted RRs and added RRs.  The first RR
+  ///    of the deleted RRs is the older SOA RR and the first RR of the added
+  ///    RRs is the newer SOA RR.
+  ///
+  ///    ...
+  ///
+  ///    If incremental zone transfer is not available, the entire zone is
+  ///    returned.  The first and the last RR of the response is the SOA
+  ///    record of the zone.  I.e. the behavior is the same as an AXFR
+  ///    response except the query type is IXFR.
+  ///
+  ///    If the server's SOA serial is the same as, or older than, the
+  ///    client's, only the server's current SOA is returned.
+  /// ```
+  ///
+  /// The differences are read from the history of updates in the journal, if it does not go back
+  ///  to the serial of the client the entire zone is returned.
+  ///
+  /// # Arguments
+  ///
+  /// * `serial` - the serial of the zone at the client, from the SOA of the request
+  ///
+  /// # Return value
+  ///
+  /// The records of the response, empty if the zone may not be transferred.
+  pub fn ixfr(&self, serial: u32) -> Vec<Record> {
+    match self.get_zone_type() {
+      ZoneType::Master | ZoneType::Slave => (),
+      _ => return vec![],
+    }
+
+    let soa: Record = if let Some(soa) = self.get_soa() { soa.clone() } else { return vec![] };
+    let current_serial = self.get_serial();
+    // the client is up to date, or newer by RFC 1982 serial arithmetic
+    match compare_serial(serial, current_serial) {
+      Some(Ordering::Equal) | Some(Ordering::Greater) => return vec![soa],
+      _ => (),
+    }
+
+    let diffs: Option<Vec<ZoneDiff>> = match self.journal.as_ref().map(|journal| journal.select_diffs(serial, current_serial)) {
+      Some(Ok(diffs)) => diffs,
+      Some(Err(error)) => { error!("could not read the diffs from the journal: {}", error); None },
+      None => None,
+    };
+
+    if let Some(diffs) = diffs {
+      debug!("ixfr of {} from {} to {} in {} diffs", self.origin, serial, current_serial, diffs.len());
+      let mut records: Vec<Record> = vec![soa.clone()];
+      for diff in diffs {
+        records.push(diff.from_soa);
+        records.extend(diff.deleted);
+        records.push(diff.to_soa);
+        records.extend(diff.added);
+      }
+      records.push(soa);
+      records
+    } else {
+      debug!("no history of {} from {}, falling back to axfr", self.origin, serial);
+      let mut query = Query::new();
+      query.name(self.origin.clone()).query_type(RecordType::AXFR);
+      self.search(&query, true).into_iter().cloned().collect()
+    }
   }
 
   /// Using the specified query, perform a lookup against this zone.
@@ -879,6 +1425,102 @@ impl Authority {
     query_result
   }
 
+  /// Synthesizes the answer to the query from a wildcard, for names which do not exist in the zone
+  ///
+  /// The source of synthesis is the wildcard, `*`, immediately below the closest encloser, the
+  ///  nearest ancestor of the name which exists, and the synthesized records are owned by the
+  ///  queried name. Nothing is synthesized below a delegation.
+  ///
+  /// [RFC 4592](https://tools.ietf.org/html/rfc4592), The Role of Wildcards, July 2006
+  ///
+  /// ```text
+  /// 3.3.1.  Query Matching
+  ///
+  ///    The closest encloser is the node in the zone's tree of existing
+  ///    domain names that has the most labels matching the query name
+  ///    (consecutively, counting from the root label downward).
+  ///    ...
+  ///    The source of synthesis is the wildcard domain name that is
+  ///    "*." prepended to the closest encloser.
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `query` - the query which was not answered by `search()`
+  /// * `is_secure` - if true, the RRSIGs of the wildcard are synthesized as well
+  ///
+  /// # Return value
+  ///
+  /// The synthesized records, empty if the name exists or there is no matching wildcard.
+  pub fn wildcard_search(&self, query: &Query, is_secure: bool) -> Vec<Record> {
+    let name: &Name = query.get_name();
+    match query.get_query_type() {
+      RecordType::SOA | RecordType::AXFR | RecordType::IXFR => return vec![],
+      _ => (),
+    }
+
+    let wildcard: Name = if let Some(wildcard) = self.find_wildcard(name) { wildcard } else { return vec![] };
+    self.lookup(&wildcard, query.get_query_type(), is_secure).into_iter()
+                                                             .map(|record| { let mut record = record.clone(); record.name(name.clone()); record })
+                                                             .collect()
+  }
+
+  /// Finds the wildcard which is the source of synthesis for the name, i.e. `*.` the closest
+  ///  encloser of a name which does not exist in the zone
+  ///
+  /// # Return value
+  ///
+  /// The wildcard, None if the name exists, is not in the zone, is delegated or there is no such
+  ///  wildcard.
+  pub fn find_wildcard(&self, name: &Name) -> Option<Name> {
+    if *name == self.origin || !self.origin.zone_of(name) || self.contains_name(name) { return None }
+
+    let encloser: Name = self.closest_encloser(name);
+
+    // the wildcard of a delegated zone belongs to the child
+    if self.find_zone_cut(&encloser).is_some() { return None }
+
+    let wildcard: Name = encloser.prepend_label(Rc::new("*".to_string()));
+    if self.contains_name(&wildcard) { Some(wildcard) } else { None }
+  }
+
+  /// the longest ancestor of the name, or the name itself, which exists in the zone, the name
+  ///  must be in the zone
+  fn closest_encloser(&self, name: &Name) -> Name {
+    let mut encloser: Name = name.clone();
+    while encloser != self.origin && !self.contains_name(&encloser) {
+      encloser = encloser.base_name();
+    }
+
+    encloser
+  }
+
+  /// Finds the delegation of the name to a child zone, i.e. the topmost name at or above the name,
+  ///  below the origin, which owns NS records
+  ///
+  /// # Return value
+  ///
+  /// The owner of the NS records, None if the name is not delegated or is not in the zone.
+  pub fn find_zone_cut(&self, name: &Name) -> Option<Name> {
+    if !self.origin.zone_of(name) { return None }
+
+    let mut zone_cut: Option<Name> = None;
+    let mut ancestor: Name = name.clone();
+    while ancestor != self.origin && !ancestor.is_root() {
+      if !self.lookup(&ancestor, RecordType::NS, false).is_empty() { zone_cut = Some(ancestor.clone()); }
+      ancestor = ancestor.base_name();
+    }
+
+    zone_cut
+  }
+
+  /// true if the name owns records, or is an empty non-terminal, i.e. names below it own records
+  pub fn contains_name(&self, name: &Name) -> bool {
+    // in the canonical order the names below a name follow it, so only the first key at or after
+    //  the name needs to be checked, NULL is the lowest record type
+    self.records.range(RrKey::new(name, RecordType::NULL)..).next().map_or(false, |(key, _)| name.zone_of(&key.name))
+  }
+
   /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
   ///
   /// # Arguments
@@ -920,16 +1562,113 @@ impl Authority {
   ///
   /// # Arguments
   ///
-  /// * `name` - given this name (i.e. the lookup name), return the NSEC record that is less than
-  ///            this
+  /// * `name` - given this name (i.e. the lookup name), return the NSEC record that matches it,
+  ///            or the one which covers it, i.e. the last NSEC record that is less than this
   /// * `is_secure` - if true then it will return RRSIG records as well
   pub fn get_nsec_records(&self, name: &Name, is_secure: bool) -> Vec<&Record> {
     self.records.values().filter(|rr_set| rr_set.get_record_type() == RecordType::NSEC)
-                         .skip_while(|rr_set| name < rr_set.get_name())
-                         .next()
+                         .take_while(|rr_set| rr_set.get_name() <= name)
+                         .last()
                          .map_or(vec![], |rr_set| rr_set.get_records(is_secure).into_iter().collect())
   }
 
+  /// Return the NSEC3 records which match, or cover, the hash of the name, the NSEC3 records
+  ///  are hashed with the NSEC3PARAM of the zone
+  ///
+  /// [RFC 5155](https://tools.ietf.org/html/rfc5155), NSEC3, March 2008
+  ///
+  /// ```text
+  /// 7.2.1.  Closest Encloser Proof
+  ///
+  ///    For some NSEC3 responses, a proof of the closest encloser is
+  ///    required.  This is a proof that some ancestor of the QNAME is the
+  ///    closest encloser of QNAME.
+  /// ```
+  pub fn get_nsec3_records(&self, name: &Name, is_secure: bool) -> Vec<&Record> {
+    let param: &NSEC3PARAM = if let Some(param) = self.get_nsec3param() { param } else { return vec![] };
+    let hash = param.get_hash_algorithm().hash(param.get_salt(), name, param.get_iterations());
+    let hash_label: String = base32hex::encode(&hash).to_lowercase();
+
+    // the hashed owners, each sorted by its hash, the last before the hash covers it, if none
+    //  precede it the last NSEC3 wraps around to the first and covers it
+    let nsec3s: Vec<(String, &RRSet)> = self.records.values()
+                                                    .filter(|rr_set| rr_set.get_record_type() == RecordType::NSEC3 && rr_set.get_name().num_labels() > 0)
+                                                    .map(|rr_set| (rr_set.get_name()[0].to_lowercase(), rr_set))
+                                                    .collect();
+
+    nsec3s.iter().filter(|&&(ref owner, _)| *owner <= hash_label)
+                 .max_by_key(|&&(ref owner, _)| owner.clone())
+                 .or_else(|| nsec3s.iter().max_by_key(|&&(ref owner, _)| owner.clone()))
+                 .map_or(vec![], |&(_, rr_set)| rr_set.get_records(is_secure).into_iter().collect())
+  }
+
+  /// the NSEC3PARAM at the origin, if the zone is signed with NSEC3
+  fn get_nsec3param(&self) -> Option<&NSEC3PARAM> {
+    self.lookup(&self.origin, RecordType::NSEC3PARAM, false).into_iter()
+                                                            .filter_map(|rr| if let &RData::NSEC3PARAM(ref param) = rr.get_rdata() { Some(param) } else { None })
+                                                            .next()
+  }
+
+  /// The proof that the name, or the records of the query type at it, do not exist, for the
+  ///  authority section of a negative response when the DO bit is set. NSEC3 is used if the zone
+  ///  has an NSEC3PARAM, otherwise NSEC.
+  ///
+  /// If the name exists, the NSEC or NSEC3 of the name proves that it has no records of the type.
+  ///  Otherwise, the proof shows that the name does not exist, and that the wildcard at the closest
+  ///  encloser either does not exist, or has no records of the type.
+  ///
+  /// [RFC 4035](https://tools.ietf.org/html/rfc4035), DNSSEC Protocol Modifications, March 2005
+  ///
+  /// ```text
+  /// 3.1.3.2.  Including NSEC RRs: Name Error Response
+  ///
+  ///    If the zone does not contain any RRsets matching <SNAME, SCLASS>
+  ///    either exactly or via wildcard name expansion, then the name server
+  ///    MUST include the following NSEC RRs in the Authority section, along
+  ///    with their associated RRSIG RRs:
+  ///
+  ///    o  An NSEC RR proving that there is no exact match for
+  ///       <SNAME, SCLASS>.
+  ///
+  ///    o  An NSEC RR proving that the zone contains no RRsets that would
+  ///       match <SNAME, SCLASS> via wildcard name expansion.
+  /// ```
+  pub fn get_denial(&self, name: &Name, is_secure: bool) -> Vec<&Record> {
+    if !self.origin.zone_of(name) { return vec![] }
+
+    let encloser: Name = self.closest_encloser(name);
+    let wildcard: Name = encloser.prepend_label(Rc::new("*".to_string()));
+    let exists = encloser == *name;
+
+    let mut denial: Vec<&Record> = Vec::new();
+    if self.get_nsec3param().is_some() {
+      if exists {
+        denial.append(&mut self.get_nsec3_records(name, is_secure));
+      } else {
+        // the next closer name is the ancestor of the name one label below the closest encloser
+        let mut next_closer: Name = name.clone();
+        while next_closer.base_name() != encloser {
+          next_closer = next_closer.base_name();
+        }
+
+        denial.append(&mut self.get_nsec3_records(&encloser, is_secure));
+        denial.append(&mut self.get_nsec3_records(&next_closer, is_secure));
+        denial.append(&mut self.get_nsec3_records(&wildcard, is_secure));
+      }
+    } else {
+      denial.append(&mut self.get_nsec_records(name, is_secure));
+      if !exists { denial.append(&mut self.get_nsec_records(&wildcard, is_secure)); }
+    }
+
+    // the same record may prove more than one name
+    let mut unique: Vec<&Record> = Vec::with_capacity(denial.len());
+    for record in denial {
+      if !unique.contains(&record) { unique.push(record); }
+    }
+
+    unique
+  }
+
   /// (Re)generates the nsec records, increments the serial number nad signs the zone
   pub fn secure_zone(&mut self) {
     // TODO: only call nsec_zone after adds/deletes
@@ -941,10 +1680,18 @@ impl Authority {
     self.increment_soa_serial();
 
     // TODO: should we auto sign here? or maybe up a level...
-    self.sign_zone();
+    self.sign_rrsets();
   }
 
-  /// Creates all nsec records needed for the zone, replaces any existing records.
+  /// Generates the NSEC, or NSEC3, records and signs the zone with its keys, without incrementing
+  ///  the serial, i.e. once a loaded zone has had its keys added, before it is served.
+  pub fn sign_zone(&mut self) {
+    self.nsec_zone();
+    self.sign_rrsets();
+  }
+
+  /// Creates all nsec records needed for the zone, replaces any existing records. If the zone
+  ///  has an NSEC3PARAM NSEC3 records are created instead.
   fn nsec_zone(&mut self) {
     // only create nsec records for secure zones
     if self.secure_keys.is_empty() { return }
@@ -952,7 +1699,7 @@ impl Authority {
 
     // first remove all existing nsec records
     let delete_keys: Vec<RrKey> = self.records.keys()
-                                              .filter(|k| k.record_type == RecordType::NSEC)
+                                              .filter(|k| k.record_type == RecordType::NSEC || k.record_type == RecordType::NSEC3)
                                               .cloned()
                                               .collect();
 
@@ -960,6 +1707,11 @@ impl Authority {
       self.records.remove(&key);
     }
 
+    if let Some(param) = self.get_nsec3param().cloned() {
+      self.nsec3_zone(&param);
+      return
+    }
+
     // now go through and generate the nsec records
     let ttl = self.get_minimum_ttl();
     let serial = self.get_serial();
@@ -1000,17 +1752,56 @@ impl Authority {
     }
   }
 
-  /// Signs any records in the zone that have serial numbers greater than or equal to `serial`
-  fn sign_zone(&mut self) {
+  /// Creates the NSEC3 records of the zone, hashed with the parameters of its NSEC3PARAM, one for
+  ///  each name, including the empty non-terminals, in the order of their hashes.
+  fn nsec3_zone(&mut self, param: &NSEC3PARAM) {
+    let ttl = self.get_minimum_ttl();
+    let serial = self.get_serial();
+
+    let mut names: BTreeMap<Name, Vec<RecordType>> = BTreeMap::new();
+    for key in self.records.keys() {
+      names.entry(key.name.clone()).or_insert_with(Vec::new).push(key.record_type);
+
+      // the empty non-terminals between the name and the origin
+      let mut name = key.name.base_name();
+      while name != self.origin && self.origin.zone_of(&name) {
+        names.entry(name.clone()).or_insert_with(Vec::new);
+        name = name.base_name();
+      }
+    }
+
+    let mut hashes: Vec<(Vec<u8>, Vec<RecordType>)> = names.into_iter().map(|(name, mut types)| {
+      // the records of each name which is not empty are signed
+      if !types.is_empty() { types.push(RecordType::RRSIG); }
+      (param.get_hash_algorithm().hash(param.get_salt(), &name, param.get_iterations()), types)
+    }).collect();
+    hashes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut records: Vec<Record> = Vec::with_capacity(hashes.len());
+    for (i, &(ref hash, ref types)) in hashes.iter().enumerate() {
+      // the next hashed owner of the last wraps around to the first
+      let next_hash: &Vec<u8> = &hashes[(i + 1) % hashes.len()].0;
+      let owner = Name::parse(&base32hex::encode(hash).to_lowercase(), Some(&self.origin)).expect("hash is not a label"); // valid panic, never should happen
+
+      let mut record = Record::with(owner, RecordType::NSEC3, ttl);
+      record.rdata(RData::NSEC3(NSEC3::new(param.get_hash_algorithm(), false, param.get_iterations(), param.get_salt().to_vec(),
+                                           next_hash.clone(), types.clone())));
+      records.push(record);
+    }
+
+    for record in records {
+      self.upsert(record, serial);
+    }
+  }
+
+  /// Signs each of the rrsets of the zone, replacing any existing signatures
+  fn sign_rrsets(&mut self) {
     debug!("signing zone: {}", self.origin);
     let inception = UTC::now();
     let zone_ttl = self.get_minimum_ttl();
 
-    for rr_set in self.records.iter_mut().filter_map(|(_, rr_set)| {
-      // do not sign zone DNSKEY's that's the job of the parent zone
-      if rr_set.get_record_type() == RecordType::DNSKEY { return None }
-      rr_set.get_rrsigs().is_empty();
-      Some(rr_set) } ) {
+    // the DNSKEYs are signed by themselves, the parent zone signs the DS
+    for rr_set in self.records.values_mut() {
 
       debug!("signing rr_set: {}", rr_set.get_name());
       rr_set.clear_rrsigs();
@@ -1065,9 +1856,13 @@ impl Authority {
 #[cfg(test)]
 pub mod authority_tests {
   use std::collections::BTreeMap;
-  use std::net::{Ipv4Addr,Ipv6Addr};
+  use std::net::{IpAddr,Ipv4Addr,Ipv6Addr};
+
+  use chrono::UTC;
 
-  use ::authority::ZoneType;
+  use ::authority::{Grant, GrantName, UpdatePolicy, ZoneType};
+  use ::rr::dnssec::{TsigAlgorithm, TsigKey};
+  use ::serialize::binary::{BinEncoder, BinSerializable};
   use ::rr::*;
   use ::rr::rdata::{ NULL, SOA, TXT };
   use ::op::*;
@@ -1208,16 +2003,85 @@ pub mod authority_tests {
 
   #[test]
   fn test_authorize() {
-    let authority: Authority = create_example();
+    let mut authority: Authority = create_example();
 
     let mut message = Message::new();
     message.id(10).message_type(MessageType::Query).op_code(OpCode::Update);
 
-    assert_eq!(authority.authorize(&message), Err(ResponseCode::Refused));
+    assert_eq!(authority.authorize(&message, None, None), Err(ResponseCode::Refused));
+
+    // unsigned updates must be allowed by the policy
+    authority.set_allow_update(true);
+    assert_eq!(authority.authorize(&message, None, None), Err(ResponseCode::Refused));
+
+    let mut policy = UpdatePolicy::new();
+    policy.allow_network("192.0.2.0/24".parse().unwrap()).allow_unsigned(true);
+    authority.set_update_policy(policy);
 
-    // TODO: this will nee to be more complex as additional policies are added
-    // authority.set_allow_update(true);
-    // assert!(authority.authorize(&message).is_ok());
+    assert!(authority.authorize(&message, Some(&IpAddr::V4(Ipv4Addr::new(192,0,2,1))), None).is_ok());
+    assert_eq!(authority.authorize(&message, Some(&IpAddr::V4(Ipv4Addr::new(198,51,100,1))), None), Err(ResponseCode::Refused));
+    assert_eq!(authority.authorize(&message, None, None), Err(ResponseCode::Refused));
+  }
+
+  #[test]
+  fn test_authorize_tsig() {
+    let mut authority: Authority = create_example();
+    authority.set_allow_update(true);
+
+    let key_name = Name::parse("www.example.com.", None).unwrap();
+    let key = TsigKey::new(key_name.clone(), TsigAlgorithm::HmacSha256, b"www secret".to_vec());
+
+    // the key may only update its own address
+    let mut policy = UpdatePolicy::new();
+    policy.tsig_key(key.clone()).grant(Grant::new(key_name.clone(), GrantName::SelfName, vec![RecordType::A]));
+    authority.set_update_policy(policy);
+
+    let update = |name: &Name, rr_type: RecordType, key: Option<&TsigKey>| -> (Message, Vec<u8>) {
+      let mut record = Record::with(name.clone(), rr_type, 86400);
+      record.rdata(if rr_type == RecordType::A { RData::A(Ipv4Addr::new(192,0,2,1)) } else { RData::AAAA(Ipv6Addr::new(0x2001,0xdb8,0,0,0,0,0,1)) });
+
+      let mut message = Message::new();
+      message.id(10).message_type(MessageType::Query).op_code(OpCode::Update);
+      let mut zone = Query::new();
+      zone.name(Name::parse("example.com.", None).unwrap()).query_type(RecordType::SOA);
+      message.add_zone(zone);
+      message.add_update(record);
+
+      let mut bytes: Vec<u8> = Vec::new();
+      {
+        let mut encoder = BinEncoder::new(&mut bytes);
+        message.emit(&mut encoder).unwrap();
+      }
+
+      match key {
+        Some(key) => (message, key.sign(&bytes, None, UTC::now().timestamp() as u64).unwrap().0),
+        None => (message, bytes),
+      }
+    };
+
+    let (message, bytes) = update(&key_name, RecordType::A, Some(&key));
+    assert!(authority.authorize(&message, None, Some(&bytes)).is_ok());
+
+    let (message, bytes) = update(&key_name, RecordType::AAAA, Some(&key));
+    assert_eq!(authority.authorize(&message, None, Some(&bytes)), Err(ResponseCode::Refused));
+
+    let (message, bytes) = update(&Name::parse("ns.example.com.", None).unwrap(), RecordType::A, Some(&key));
+    assert_eq!(authority.authorize(&message, None, Some(&bytes)), Err(ResponseCode::Refused));
+
+    let (message, bytes) = update(&key_name, RecordType::A, None);
+    assert_eq!(authority.authorize(&message, None, Some(&bytes)), Err(ResponseCode::Refused));
+
+    let wrong_key = TsigKey::new(key_name.clone(), TsigAlgorithm::HmacSha256, b"not the secret".to_vec());
+    let (message, bytes) = update(&key_name, RecordType::A, Some(&wrong_key));
+    assert_eq!(authority.authorize(&message, None, Some(&bytes)), Err(ResponseCode::Refused));
+
+    // the key must also be one of the required keys
+    let mut policy = UpdatePolicy::new();
+    policy.tsig_key(key.clone()).require_key_name(Name::parse("other.example.com.", None).unwrap());
+    authority.set_update_policy(policy);
+
+    let (message, bytes) = update(&key_name, RecordType::A, Some(&key));
+    assert_eq!(authority.authorize(&message, None, Some(&bytes)), Err(ResponseCode::Refused));
   }
 
   #[test]
@@ -1256,6 +2120,15 @@ pub mod authority_tests {
     assert_eq!(authority.verify_prerequisites(&[Record::new().name(not_in_zone.clone()).ttl(0).dns_class(DNSClass::IN).rr_type(RecordType::A).rdata(RData::A(Ipv4Addr::new(93,184,216,24))).clone()]), Err(ResponseCode::NXRRSet));
     // wrong IP
     assert_eq!(authority.verify_prerequisites(&[Record::new().name(authority.get_origin().clone()).ttl(0).dns_class(DNSClass::IN).rr_type(RecordType::A).rdata(RData::A(Ipv4Addr::new(93,184,216,24))).clone()]), Err(ResponseCode::NXRRSet));
+
+    // the rrset must match exactly, no more, no less
+    let origin = authority.get_origin().clone();
+    let serial = authority.get_serial();
+    let first = Record::new().name(origin.clone()).ttl(0).dns_class(DNSClass::IN).rr_type(RecordType::A).rdata(RData::A(Ipv4Addr::new(93,184,216,34))).clone();
+    let second = Record::new().name(origin.clone()).ttl(0).dns_class(DNSClass::IN).rr_type(RecordType::A).rdata(RData::A(Ipv4Addr::new(93,184,216,35))).clone();
+    authority.upsert(second.clone().ttl(86400).clone(), serial);
+    assert_eq!(authority.verify_prerequisites(&[first.clone()]), Err(ResponseCode::NXRRSet));
+    assert!(authority.verify_prerequisites(&[first.clone(), second.clone()]).is_ok());
   }
 
   #[test]
@@ -1385,6 +2258,26 @@ pub mod authority_tests {
     assert!(authority.update_records(del_record, true).expect("update failed"));
     assert!(authority.lookup(&www_name, RecordType::ANY, false).is_empty());
     assert_eq!(serial + 6, authority.get_serial());
+
+    // a CNAME can not be added beside other data, nor other data beside a CNAME
+    let cname_record = &[Record::new().name(authority.get_origin().clone()).ttl(86400).rr_type(RecordType::CNAME).dns_class(DNSClass::IN).rdata(RData::CNAME(www_name.clone())).clone()];
+    assert!(!authority.update_records(cname_record, true).expect("update failed"));
+    assert!(authority.lookup(authority.get_origin(), RecordType::CNAME, false).is_empty());
+
+    let alias_name = Name::new().label("alias").label("example").label("com");
+    let alias_record = &[Record::new().name(alias_name.clone()).ttl(86400).rr_type(RecordType::CNAME).dns_class(DNSClass::IN).rdata(RData::CNAME(www_name.clone())).clone()];
+    assert!(authority.update_records(alias_record, true).expect("update failed"));
+    let alias_a_record = &[Record::new().name(alias_name.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,0,0,1))).clone()];
+    assert!(!authority.update_records(alias_a_record, true).expect("update failed"));
+    assert!(authority.lookup(&alias_name, RecordType::A, false).is_empty());
+
+    //  ANY      ANY      empty    Delete all RRsets from a name, but not the SOA and NS of the zone
+    let origin = authority.get_origin().clone();
+    let del_record = &[Record::new().name(origin.clone()).ttl(0).rr_type(RecordType::ANY).dns_class(DNSClass::ANY).rdata(RData::NULL(NULL::new())).clone()];
+    assert!(authority.update_records(del_record, true).expect("update failed"));
+    assert!(authority.lookup(&origin, RecordType::A, false).is_empty());
+    assert!(!authority.lookup(&origin, RecordType::SOA, false).is_empty());
+    assert!(!authority.lookup(&origin, RecordType::NS, false).is_empty());
   }
 
   #[test]
@@ -1412,6 +2305,52 @@ pub mod authority_tests {
     }
   }
 
+  #[test]
+  fn test_sign_zone_nsec3() {
+    use std::collections::BTreeSet;
+    use chrono::Duration;
+    use data_encoding::base32hex;
+    use openssl::crypto::pkey::PKey;
+    use ::rr::dnssec::{Algorithm, Nsec3HashAlgorithm, Signer};
+    use ::rr::rdata::NSEC3PARAM;
+
+    let mut authority: Authority = create_example();
+    let names: BTreeSet<Name> = authority.get_records().keys().map(|key| key.name.clone()).collect();
+
+    let mut pkey = PKey::new();
+    pkey.gen(512);
+    let signer = Signer::new(Algorithm::RSASHA256, pkey, authority.get_origin().clone(), Duration::weeks(1));
+    authority.add_secure_key(signer);
+    authority.set_nsec3param(NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, false, 2, vec![0xAA, 0xBB]));
+
+    let serial = authority.get_serial();
+    authority.sign_zone();
+    assert_eq!(authority.get_serial(), serial);
+    assert!(authority.get_records().keys().all(|key| key.record_type != RecordType::NSEC));
+
+    // one NSEC3 for each name, each the next of another
+    let nsec3s: Vec<&Record> = authority.get_records().values()
+                                        .filter(|rr_set| rr_set.get_record_type() == RecordType::NSEC3)
+                                        .flat_map(|rr_set| rr_set.iter())
+                                        .collect();
+    assert_eq!(nsec3s.len(), names.len());
+    let owners: Vec<String> = nsec3s.iter().map(|nsec3| nsec3.get_name()[0].to_lowercase()).collect();
+    for nsec3 in &nsec3s {
+      if let &RData::NSEC3(ref rdata) = nsec3.get_rdata() {
+        assert!(owners.contains(&base32hex::encode(rdata.get_next_hashed_owner_name()).to_lowercase()));
+      } else {
+        panic!("not an NSEC3: {:?}", nsec3);
+      }
+    }
+
+    // the DNSKEYs are signed, and the names which do not exist are denied with the NSEC3s
+    let origin = authority.get_origin().clone();
+    assert!(authority.lookup(&origin, RecordType::DNSKEY, true).iter().any(|r| r.get_rr_type() == RecordType::RRSIG));
+    let denial = authority.get_denial(&Name::parse("zzz.example.com.", None).unwrap(), true);
+    assert!(!denial.is_empty());
+    assert!(denial.iter().all(|r| r.get_rr_type() == RecordType::NSEC3 || r.get_rr_type() == RecordType::RRSIG));
+  }
+
   #[test]
   fn test_get_nsec() {
     let name = Name::new().label("zzz").label("example").label("com");
@@ -1424,6 +2363,25 @@ pub mod authority_tests {
     }
   }
 
+  #[test]
+  fn test_get_denial() {
+    let authority: Authority = create_secure_example();
+    let name = |s: &str| Name::parse(s, None).unwrap();
+    let nsec_owners = |records: Vec<&Record>| -> Vec<Name> {
+      records.into_iter().filter(|r| r.get_rr_type() == RecordType::NSEC).map(|r| r.get_name().clone()).collect()
+    };
+
+    // the name exists, its own NSEC proves the type does not
+    assert_eq!(nsec_owners(authority.get_denial(&name("www.example.com."), true)), vec![name("www.example.com.")]);
+
+    // the covering NSEC for the name, and the wildcard, which is covered by the same NSEC here
+    assert_eq!(nsec_owners(authority.get_denial(&name("aaa.example.com."), true)), vec![name("example.com.")]);
+    assert_eq!(nsec_owners(authority.get_denial(&name("zzz.example.com."), true)), vec![name("www.example.com."), name("example.com.")]);
+
+    // out of the zone
+    assert!(authority.get_denial(&name("www.example.net."), true).is_empty());
+  }
+
   #[test]
   fn test_journal() {
     use std::net::Ipv4Addr;
@@ -1467,6 +2425,162 @@ pub mod authority_tests {
     assert!(delete_rrset.is_empty());
   }
 
+  #[test]
+  fn test_serial_policy() {
+    use ::authority::SerialPolicy;
+
+    let mut authority = create_example();
+    assert_eq!(authority.get_serial_policy(), SerialPolicy::Increment);
+    authority.secure_zone();
+    assert_eq!(authority.get_serial(), 2015082404);
+
+    // the serial of the example is already date based, it is now that of today
+    authority.set_serial_policy(SerialPolicy::Date);
+    authority.secure_zone();
+    assert!(authority.get_serial() / 100 > 20150824);
+    assert_eq!(authority.get_serial() % 100, 0);
+
+    let serial = authority.get_serial();
+    authority.secure_zone();
+    assert_eq!(authority.get_serial(), serial + 1);
+  }
+
+  #[test]
+  fn test_apply_diffs() {
+    use std::net::Ipv4Addr;
+    use ::client::ZoneDiff;
+
+    let mut authority = create_example();
+    let from_soa = authority.get_soa().unwrap().clone();
+    let mut to_soa = from_soa.clone();
+    if let &mut RData::SOA(ref mut soa) = to_soa.get_rdata_mut() { soa.increment_serial(); }
+
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    let deleted = Record::new().name(www.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(93,184,216,34))).clone();
+    let added = Record::new().name(new_name.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,11,12,13))).clone();
+
+    let diffs = vec![ZoneDiff{ from_soa: from_soa.clone(), to_soa: to_soa.clone(), deleted: vec![deleted], added: vec![added.clone()] }];
+    assert!(authority.apply_diffs(&diffs));
+
+    assert!(authority.lookup(&www, RecordType::A, false).is_empty());
+    assert_eq!(authority.lookup(&new_name, RecordType::A, false), vec![&added]);
+    assert_eq!(authority.get_soa(), Some(&to_soa));
+
+    // the diffs no longer start at the serial of the zone
+    assert!(!authority.apply_diffs(&diffs));
+  }
+
+  #[test]
+  fn test_wildcard_search() {
+    let mut authority = create_example();
+    let origin = authority.get_origin().clone();
+    let name = |s: &str| Name::parse(s, Some(&origin)).unwrap();
+    let a = |n: &Name, address: Ipv4Addr| Record::new().name(n.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(address)).clone();
+
+    authority.upsert(a(&name("*"), Ipv4Addr::new(192,0,2,1)), 0);
+    authority.upsert(a(&name("x.ent"), Ipv4Addr::new(192,0,2,2)), 0);
+    authority.upsert(Record::new().name(name("sub")).ttl(86400).rr_type(RecordType::NS).dns_class(DNSClass::IN).rdata(RData::NS(name("ns.sub"))).clone(), 0);
+    authority.upsert(a(&name("ns.sub"), Ipv4Addr::new(192,0,2,53)), 0);
+
+    let search = |n: &Name, rtype: RecordType| {
+      let mut query = Query::new();
+      query.name(n.clone()).query_type(rtype);
+      authority.wildcard_search(&query, false)
+    };
+
+    // synthesized, with the queried name as the owner
+    assert_eq!(search(&name("other"), RecordType::A), vec![a(&name("other"), Ipv4Addr::new(192,0,2,1))]);
+    assert_eq!(search(&name("deep.other"), RecordType::A), vec![a(&name("deep.other"), Ipv4Addr::new(192,0,2,1))]);
+
+    // the wildcard has no records of the type
+    assert!(search(&name("other"), RecordType::MX).is_empty());
+
+    // names which exist are not synthesized, including empty non-terminals
+    assert!(search(&name("www"), RecordType::MX).is_empty());
+    assert!(search(&name("ent"), RecordType::A).is_empty());
+
+    // the closest encloser is ent, which has no wildcard
+    assert!(search(&name("y.ent"), RecordType::A).is_empty());
+
+    // the closest encloser of a.*.example.com. is *.example.com., the wildcard itself
+    assert!(search(&name("*"), RecordType::A).is_empty());
+    assert!(search(&name("a.*"), RecordType::A).is_empty());
+
+    // never below a delegation
+    assert!(search(&name("x.sub"), RecordType::A).is_empty());
+    assert!(search(&name("x.ns.sub"), RecordType::A).is_empty());
+
+    // outside of the zone
+    assert!(search(&Name::parse("other.example.net.", None).unwrap(), RecordType::A).is_empty());
+
+    assert!(authority.contains_name(&name("ent")));
+    assert!(authority.contains_name(&name("x.ent")));
+    assert!(authority.contains_name(&origin));
+    assert!(!authority.contains_name(&name("en")));
+    assert!(!authority.contains_name(&name("y.ent")));
+    assert!(!authority.contains_name(&name("zzz")));
+  }
+
+  #[test]
+  fn test_ixfr() {
+    use std::net::Ipv4Addr;
+    use rusqlite::Connection;
+    use ::authority::Journal;
+
+    let conn = Connection::open_in_memory().expect("could not create in memory DB");
+    let mut journal = Journal::new(conn).unwrap();
+    journal.schema_up().unwrap();
+
+    let mut authority = create_example();
+    authority.set_allow_update(true);
+    let mut policy = UpdatePolicy::new();
+    policy.allow_unsigned(true);
+    authority.set_update_policy(policy);
+    authority.journal(journal);
+    authority.persist_to_journal().unwrap();
+
+    let serial = authority.get_serial();
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    let new_record = Record::new().name(new_name.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,11,12,13))).clone();
+    let delete_record = Record::new().name(Name::parse("www.example.com.", None).unwrap()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(93,184,216,34))).clone();
+
+    let mut message = Message::new();
+    message.add_update(new_record.clone());
+    message.add_update(delete_record.clone().dns_class(DNSClass::NONE).ttl(0).clone());
+    assert!(authority.update(&message).unwrap());
+
+    let mut message = Message::new();
+    message.add_update(new_record.clone().rdata(RData::A(Ipv4Addr::new(10,11,12,14))).clone());
+    assert!(authority.update(&message).unwrap());
+    assert_eq!(authority.get_serial(), serial + 2);
+
+    // current soa, (old soa, deleted, new soa, added) for each update, current soa
+    let ixfr = authority.ixfr(serial);
+    let soa = authority.get_soa().unwrap().clone();
+    let types: Vec<RecordType> = ixfr.iter().map(|rr| rr.get_rr_type()).collect();
+    assert_eq!(types, vec![RecordType::SOA,
+                           RecordType::SOA, RecordType::A, RecordType::SOA, RecordType::A,
+                           RecordType::SOA, RecordType::SOA, RecordType::A,
+                           RecordType::SOA]);
+    assert_eq!(ixfr[0], soa);
+    assert_eq!(ixfr[2], delete_record);
+    assert_eq!(ixfr[4], new_record);
+    assert_eq!(ixfr[8], soa);
+
+    // only the last update
+    assert_eq!(authority.ixfr(serial + 1).len(), 5);
+
+    // up to date
+    assert_eq!(authority.ixfr(serial + 2), vec![soa.clone()]);
+
+    // no history, the entire zone
+    let axfr = authority.ixfr(serial - 1);
+    assert_eq!(axfr.first(), Some(&soa));
+    assert_eq!(axfr.last(), Some(&soa));
+    assert!(axfr.contains(&new_record.clone().rdata(RData::A(Ipv4Addr::new(10,11,12,14))).clone()));
+  }
+
   #[test]
   fn test_recovery() {
     use rusqlite::Connection;
@@ -1507,4 +2621,95 @@ pub mod authority_tests {
       })
     }));
   }
+
+  #[test]
+  fn test_journal_file() {
+    use std::env;
+    use std::fs;
+    use std::net::Ipv4Addr;
+    use ::authority::Journal;
+
+    let path = env::temp_dir().join("trust_dns_test_journal_file.jrnl");
+    fs::remove_file(&path).ok();
+
+    // a new journal file is created with the current schema
+    let mut authority = create_example();
+    authority.journal(Journal::from_file(&path).expect("new journal"));
+    authority.persist_to_journal().unwrap();
+
+    let new_name = Name::new().label("new").label("example").label("com");
+    let new_record = Record::new().name(new_name.clone()).rdata(RData::A(Ipv4Addr::new(10,11,12,13))).clone();
+    authority.update_records(&[new_record.clone()], true).unwrap();
+    drop(authority);
+
+    // the accepted update survives the restart
+    let journal = Journal::from_file(&path).expect("existing journal");
+    let mut recovered_authority = Authority::new(Name::new().label("example").label("com"), BTreeMap::new(), ZoneType::Master, true);
+    recovered_authority.recover_with_journal(&journal).expect("recovery");
+
+    assert_eq!(recovered_authority.lookup(&new_name, RecordType::A, false), vec![&new_record]);
+    assert!(recovered_authority.get_soa().is_some());
+
+    fs::remove_file(&path).ok();
+  }
+  #[test]
+  fn test_replay_journal_and_checkpoint() {
+    use std::env;
+    use std::fs;
+    use std::net::Ipv4Addr;
+    use ::authority::Journal;
+    use ::serialize::txt::Parser;
+
+    let dir = env::temp_dir().join("trust_dns_test_checkpoint");
+    fs::create_dir_all(&dir).unwrap();
+    let zone_file = dir.join("example.com.zone");
+    let journal_file = dir.join("example.com.jrnl");
+    fs::remove_file(&journal_file).ok();
+
+    let load = || -> Authority {
+      let mut authority = Parser::parse_path(&zone_file, None, ZoneType::Master, true).expect("zone file");
+      authority.journal(Journal::from_file(&journal_file).expect("journal"));
+      let mut policy = UpdatePolicy::new();
+      policy.allow_unsigned(true);
+      authority.set_update_policy(policy);
+      authority
+    };
+
+    // the zone file is written at the checkpoint
+    let mut authority = create_example();
+    authority.journal(Journal::from_file(&journal_file).expect("new journal"));
+    authority.checkpoint(&zone_file).unwrap();
+    drop(authority);
+
+    let mut authority = load();
+    assert_eq!(authority.replay_journal().unwrap(), 0);
+    let serial = authority.get_serial();
+
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    let new_record = Record::new().name(new_name.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,11,12,13))).clone();
+    let mut message = Message::new();
+    message.add_update(new_record.clone());
+    assert!(authority.update(&message).unwrap());
+    drop(authority);
+
+    // the update is replayed onto the zone file
+    let mut authority = load();
+    assert!(authority.lookup(&new_name, RecordType::A, false).is_empty());
+    assert_eq!(authority.replay_journal().unwrap(), 1);
+    assert_eq!(authority.lookup(&new_name, RecordType::A, false), vec![&new_record]);
+    assert_eq!(authority.get_serial(), serial + 1);
+
+    // the zone file holds the update once it is checkpointed, the journal is empty
+    authority.checkpoint(&zone_file).unwrap();
+    assert_eq!(authority.get_journal().unwrap().select_last_serial().unwrap(), None);
+    drop(authority);
+
+    let mut authority = load();
+    assert_eq!(authority.replay_journal().unwrap(), 0);
+    assert_eq!(authority.lookup(&new_name, RecordType::A, false), vec![&new_record]);
+    assert_eq!(authority.get_serial(), serial + 1);
+
+    fs::remove_file(&zone_file).ok();
+    fs::remove_file(&journal_file).ok();
+  }
 }
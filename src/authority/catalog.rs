@@ -17,20 +17,143 @@
 //  should be the only "front-end" for lookups, where if that misses, then we go to the catalog
 //  then, if requested, do a recursive lookup... i.e. the catalog would only point to files.
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
 
-use ::rr::{Name, RecordType};
-use ::authority::{Authority, ZoneType};
+use ::error::{EncodeResult, PersistenceResult};
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+use ::authority::{Authority, ChaosZones, Forwarder, Network, QueryPolicy, ResponsePolicyZone, Transfer, Upstream, ZoneType};
+use ::authority::forwarder::answer_from;
 use ::op::{Edns, Message, MessageType, OpCode, Query, UpdateMessage, ResponseCode};
+use ::rr::rdata::opt::{EdnsCode, EdnsOption};
+use ::serialize::binary::{BinEncoder, BinSerializable};
+
+/// the most CNAMEs which are followed within a zone for an answer
+const MAX_CNAME_CHAIN: usize = 16;
+/// the version of EDNS which is supported
+const EDNS_VERSION: u8 = 0;
+/// the default UDP payload size advertised to EDNS clients
+const DEFAULT_MAX_PAYLOAD: u16 = 4096;
 
 /// Set of authorities, zones, available to this server.
+///
+/// Different versions of the zones can be served to different clients with views, i.e. split
+///  horizon, each view is itself a `Catalog`. The requests of clients which match a view are
+///  handled by the view, the others by this catalog.
+///
+/// ```rust,ignore
+/// // internal clients see the internal version of the zone
+/// let internal = Catalog::new();
+/// internal.upsert(origin.clone(), internal_authority);
+///
+/// let mut catalog = Catalog::new();
+/// catalog.upsert(origin, public_authority);
+/// catalog.add_view(vec!["10.0.0.0/8".parse().unwrap()], internal);
+/// ```
+///
+/// Zones can be added and removed while the server is running, with `upsert()` and `remove()`;
+///  requests in flight finish with the zones they found.
 pub struct Catalog {
-  authorities: HashMap<Name, RwLock<Authority>>,
+  authorities: RwLock<HashMap<Name, Arc<RwLock<Authority>>>>,
+  query_policy: QueryPolicy,
+  views: Vec<(Vec<Network>, Catalog)>,
+  forwarders: Vec<Forwarder>,
+  recursor: Option<Box<Upstream>>,
+  response_policies: Vec<ResponsePolicyZone>,
+  chaos_zones: ChaosZones,
+  max_payload: u16,
+  nsid: Option<Vec<u8>>,
 }
 
 impl Catalog {
   pub fn new() -> Self {
-    Catalog{ authorities: HashMap::new() }
+    Catalog{ authorities: RwLock::new(HashMap::new()), query_policy: QueryPolicy::new(), views: Vec::new(), forwarders: Vec::new(),
+             recursor: None, response_policies: Vec::new(), chaos_zones: ChaosZones::new(),
+             max_payload: DEFAULT_MAX_PAYLOAD, nsid: None }
+  }
+
+  /// Adds a view, which handles the requests of clients in any of the networks. Views are matched
+  ///  in the order they were added.
+  pub fn add_view(&mut self, match_clients: Vec<Network>, view: Catalog) {
+    self.views.push((match_clients, view));
+  }
+
+  /// The views in the order they are matched, with the networks of the clients they match
+  pub fn get_views(&self) -> &[(Vec<Network>, Catalog)] {
+    &self.views
+  }
+
+  /// Forwards the queries for names in the domain of the forwarder which are not in any of the
+  ///  zones, if the client may query recursively, see `QueryPolicy::is_recursion_allowed()`. The
+  ///  forwarder of the closest domain is used.
+  pub fn add_forwarder(&mut self, forwarder: Forwarder) {
+    self.forwarders.push(forwarder);
+  }
+
+  /// Resolves the queries for names which are neither in any of the zones, nor forwarded,
+  ///  recursively with the recursor, e.g. a `Recursor`. Only queries with RD set, from clients
+  ///  which may query recursively, are resolved, see `QueryPolicy::is_recursion_allowed()`; the
+  ///  responses to those clients have RA set.
+  pub fn set_recursor(&mut self, recursor: Option<Box<Upstream>>) {
+    self.recursor = recursor;
+  }
+
+  /// Rewrites the responses to queries with the rules of the policy zone, see
+  ///  `ResponsePolicyZone`. The first policy zone, in the order they were added, with a rule which
+  ///  matches rewrites the response.
+  pub fn add_response_policy(&mut self, response_policy: ResponsePolicyZone) {
+    self.response_policies.push(response_policy);
+  }
+
+  /// The answers to the CHAOS class queries which identify the server, e.g. `version.bind`, by
+  ///  default they are all refused
+  pub fn set_chaos_zones(&mut self, chaos_zones: ChaosZones) {
+    self.chaos_zones = chaos_zones;
+  }
+
+  pub fn get_chaos_zones(&self) -> &ChaosZones {
+    &self.chaos_zones
+  }
+
+  /// The UDP payload size advertised in the OPT of the responses to EDNS clients, UDP responses
+  ///  are no larger than this nor the payload size of the client, default 4096
+  pub fn set_max_payload(&mut self, max_payload: u16) {
+    self.max_payload = if max_payload < 512 { 512 } else { max_payload };
+  }
+
+  pub fn get_max_payload(&self) -> u16 {
+    self.max_payload
+  }
+
+  /// The name server identifier which is sent to EDNS clients which ask for it, see
+  ///  [RFC 5001](https://tools.ietf.org/html/rfc5001), by default none is sent
+  pub fn set_nsid(&mut self, nsid: Option<Vec<u8>>) {
+    self.nsid = nsid;
+  }
+
+  /// the forwarder of the closest domain to the name
+  fn find_forwarder(&self, name: &Name) -> Option<&Forwarder> {
+    self.forwarders.iter()
+                   .filter(|forwarder| forwarder.get_domain().zone_of(name))
+                   .max_by_key(|forwarder| forwarder.get_domain().num_labels())
+  }
+
+  /// the view which the client matches, if any, requests without a source match no view
+  fn find_view(&self, src: Option<&IpAddr>) -> Option<&Catalog> {
+    let src: &IpAddr = if let Some(src) = src { src } else { return None };
+    self.views.iter()
+              .find(|&&(ref networks, _)| networks.iter().any(|network| network.contains(src)))
+              .map(|&(_, ref view)| view)
+  }
+
+  /// Sets which clients may query any of the zones, the zones may restrict them further, see
+  ///  `Authority::set_query_policy()`
+  pub fn set_query_policy(&mut self, query_policy: QueryPolicy) {
+    self.query_policy = query_policy;
+  }
+
+  pub fn get_query_policy(&self) -> &QueryPolicy {
+    &self.query_policy
   }
 
   /// Determine's what needs to happen given the type of request, i.e. Query or Update.
@@ -39,37 +162,54 @@ impl Catalog {
   ///
   /// * `request` - the requested action to perform.
   pub fn handle_request(&self, request: &Message) -> Message {
+    self.handle_request_with(request, None, None)
+  }
+
+  /// Handles the request as `handle_request()`, with the address it was received from and its
+  ///  serialized form, which are needed to authorize updates, see `UpdatePolicy`.
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the requested action to perform.
+  /// * `src` - the address of the client.
+  /// * `request_bytes` - the request as it was received, the `request` was decoded from these.
+  pub fn handle_request_from(&self, request: &Message, src: SocketAddr, request_bytes: &[u8]) -> Message {
+    self.handle_request_with(request, Some(src.ip()), Some(request_bytes))
+  }
+
+  fn handle_request_with(&self, request: &Message, src: Option<IpAddr>, request_bytes: Option<&[u8]>) -> Message {
+    if let Some(view) = self.find_view(src.as_ref()) {
+      return view.handle_request_with(request, src, request_bytes)
+    }
+
     info!("id: {} type: {:?} op_code: {:?}", request.get_id(), request.get_message_type(), request.get_op_code());
     debug!("request: {:?}", request);
 
     let mut resp_edns_opt: Option<Edns> = None;
 
-    // check if it's edns
+    // only the requests with an OPT are answered with one, RFC 6891
     if let Some(req_edns) = request.get_edns() {
-      let mut response = Message::new();
-      response.id(request.get_id());
-
       let mut resp_edns: Edns = Edns::new();
-
-      // check our version against the request
-      // TODO: what version are we?
-      let our_version = 0;
-      resp_edns.set_dnssec_ok(true);
-      resp_edns.set_max_payload( if req_edns.get_max_payload() < 512 { 512 } else { req_edns.get_max_payload() } );
-      resp_edns.set_version(our_version);
-
-      if req_edns.get_version() > our_version {
-        warn!("request edns version greater than {}: {}", our_version, req_edns.get_version());
-        response.response_code(ResponseCode::BADVERS);
+      resp_edns.set_version(EDNS_VERSION);
+      resp_edns.set_max_payload(self.max_payload);
+      // the DO bit is copied from the request, RFC 3225
+      resp_edns.set_dnssec_ok(req_edns.is_dnssec_ok());
+
+      if req_edns.get_version() > EDNS_VERSION {
+        warn!("request edns version greater than {}: {}", EDNS_VERSION, req_edns.get_version());
+        let mut response = Self::error_msg(request.get_id(), request.get_op_code(), ResponseCode::BADVERS);
+        resp_edns.set_rcode_high(ResponseCode::BADVERS.high());
         response.set_edns(resp_edns);
         return response
       }
 
-      // TODO: inform of supported DNSSec protocols...
+      // the options which are supported are answered, others are ignored
+      if let (Some(_), Some(nsid)) = (req_edns.get_option(&EdnsCode::NSID), self.nsid.as_ref()) {
+        resp_edns.set_option(EdnsOption::Unknown(u16::from(EdnsCode::NSID), nsid.clone()));
+      }
+
       // TODO: add padding for private key hashing, need better knowledge of the length of the
       //   response.
-      // resp_edns.set_option()
-
       resp_edns_opt = Some(resp_edns);
     }
 
@@ -78,18 +218,36 @@ impl Catalog {
       //  especially for recursive lookups
       MessageType::Query => {
         match request.get_op_code() {
+          OpCode::Query if !self.query_policy.is_query_allowed(src.as_ref()) => {
+            info!("refusing query from: {:?}", src);
+            let mut response = Self::error_msg(request.get_id(), request.get_op_code(), ResponseCode::Refused);
+            response.add_all_queries(request.get_queries());
+            response
+          },
+          // transfers over UDP are truncated by the UdpHandler, and over TCP are split into messages
+          //  by the server, this answers the whole zone to callers of the catalog itself
+          OpCode::Query if Self::is_transfer(request) => {
+            let response = self.transfer(request, src.as_ref(), request_bytes).into_response();
+            debug!("transfer response: {} records", response.get_answers().len());
+            response
+          },
           OpCode::Query => {
-            let response = self.lookup(&request);
+            let response = self.lookup_from(&request, src.as_ref());
             debug!("query response: {:?}", response);
             response
             // TODO, handle recursion here or in the catalog?
             // recursive queries should be cached.
           },
           OpCode::Update => {
-            let response = self.update(request);
+            let response = self.update_from(request, src.as_ref(), request_bytes);
             debug!("update response: {:?}", response);
             response
-          }
+          },
+          OpCode::Notify => {
+            let response = self.notify(request, src.as_ref());
+            debug!("notify response: {:?}", response);
+            response
+          },
           c @ _ => {
             error!("unimplemented op_code: {:?}", c);
             Self::error_msg(request.get_id(), request.get_op_code(), ResponseCode::NotImp)
@@ -123,8 +281,78 @@ impl Catalog {
     return message;
   }
 
-  pub fn upsert(&mut self, name: Name, authority: Authority) {
-    self.authorities.insert(name, RwLock::new(authority));
+  /// Adds the zone to the catalog, replacing any zone with the same origin. This may be called
+  ///  while the catalog is serving requests.
+  pub fn upsert(&self, name: Name, authority: Authority) {
+    let mut authorities = self.authorities.write().unwrap(); // poison errors should panic...
+    if authorities.insert(name.clone(), Arc::new(RwLock::new(authority))).is_some() {
+      info!("replaced zone: {}", name);
+    }
+  }
+
+  /// Removes the zone with exactly this origin from the catalog, its names are no longer answered
+  ///  for. This may be called while the catalog is serving requests.
+  ///
+  /// Returns false if the zone is not in the catalog.
+  pub fn remove(&self, origin: &Name) -> bool {
+    let mut authorities = self.authorities.write().unwrap(); // poison errors should panic...
+    if authorities.remove(origin).is_some() {
+      info!("removed zone: {}", origin);
+      true
+    } else {
+      false
+    }
+  }
+
+  /// The zone with exactly this origin
+  pub fn get(&self, origin: &Name) -> Option<Arc<RwLock<Authority>>> {
+    self.authorities.read().unwrap().get(origin).cloned() // poison errors should panic...
+  }
+
+  /// The wildcard which the answers for the name are synthesized from, in the view of the client,
+  ///  see `Authority::find_wildcard()`
+  pub fn find_wildcard(&self, name: &Name, src: Option<&IpAddr>) -> Option<Name> {
+    if let Some(view) = self.find_view(src) { return view.find_wildcard(name, src) }
+
+    let authority = if let Some(authority) = self.find_auth_recurse(name) { authority } else { return None };
+    let authority = authority.read().unwrap(); // poison errors should panic...
+    authority.find_wildcard(name)
+  }
+
+  /// The origins of all the zones in the catalog
+  pub fn origins(&self) -> Vec<Name> {
+    self.authorities.read().unwrap().keys().cloned().collect() // poison errors should panic...
+  }
+
+  /// Flushes the journals of the zones, including those of the views, see `Journal::flush()`,
+  ///  failures are logged
+  pub fn flush_journals(&self) {
+    for (origin, authority) in self.authorities.read().unwrap().iter() { // poison errors should panic...
+      if let Some(journal) = authority.read().unwrap().get_journal() { // poison errors should panic...
+        journal.flush().unwrap_or_else(|e| error!("could not flush the journal of {}: {}", origin, e));
+      }
+    }
+
+    for &(_, ref view) in &self.views {
+      view.flush_journals();
+    }
+  }
+
+  /// Swaps the records of a zone in the catalog for those of the reloaded zone. Lookups in flight
+  ///  finish with the previous records, later lookups see the new ones.
+  ///
+  /// Returns false if the zone is not in the catalog.
+  pub fn reload(&self, zone: Authority) -> PersistenceResult<bool> {
+    let origin = zone.get_origin().clone();
+    match self.get(&origin) {
+      Some(authority) => {
+        let mut authority = authority.write().unwrap(); // poison errors should panic...
+        try!(authority.reload(zone));
+        info!("reloaded zone: {}", origin);
+        Ok(true)
+      },
+      None => Ok(false),
+    }
   }
 
   /// Update the zone given the Update request.
@@ -176,6 +404,18 @@ impl Catalog {
   ///
   /// * `request` - an update message
   pub fn update(&self, update: &Message) -> Message {
+    self.update_from(update, None, None)
+  }
+
+  /// Update the zone, see `update()`, authorizing the update with the address and serialized form
+  ///  of the request.
+  ///
+  /// # Arguments
+  ///
+  /// * `update` - an update message
+  /// * `src` - the address the update was received from
+  /// * `request_bytes` - the update as it was received
+  pub fn update_from(&self, update: &Message, src: Option<&IpAddr>, request_bytes: Option<&[u8]>) -> Message {
     let mut response: Message = Message::new();
     response.id(update.get_id());
     response.op_code(OpCode::Update);
@@ -194,8 +434,14 @@ impl Catalog {
       return response;
     }
 
-    if let Some(authority) = self.find_auth_recurse(zones[0].get_name()) {
+    // 3.1.1 the ZNAME and ZCLASS must be one of our zones exactly, not a name within one
+    if let Some(authority) = self.get(zones[0].get_name()) {
       let mut authority = authority.write().unwrap(); // poison errors should panic...
+      if zones[0].get_query_class() != authority.get_class() {
+        response.response_code(ResponseCode::NotAuth);
+        return response;
+      }
+
       match authority.get_zone_type() {
         ZoneType::Slave => {
           error!("slave forwarding for update not yet implemented");
@@ -203,7 +449,7 @@ impl Catalog {
           return response;
         },
         ZoneType::Master => {
-          let update_result = authority.update(update);
+          let update_result = authority.update_from(update, src, request_bytes);
           match update_result {
             // successful update
             Ok(..) => { response.response_code(ResponseCode::NoError); },
@@ -217,36 +463,195 @@ impl Catalog {
         }
       }
     } else {
-      response.response_code(ResponseCode::NXDomain);
+      response.response_code(ResponseCode::NotAuth);
       response
     }
   }
 
+  /// true if the request is for a zone transfer, AXFR, see `transfer()`
+  pub fn is_transfer(request: &Message) -> bool {
+    request.get_op_code() == OpCode::Query &&
+      request.get_queries().iter().any(|query| query.get_query_type() == RecordType::AXFR)
+  }
+
+  /// Answers an AXFR request with the entire zone, if the `TransferPolicy` of the zone permits
+  ///  the client. Over TCP the response should be split into multiple messages, see
+  ///  `Transfer::to_bytes()`.
+  ///
+  /// [RFC 5936](https://tools.ietf.org/html/rfc5936), DNS Zone Transfer Protocol (AXFR), June 2010
+  ///
+  /// ```text
+  /// 2.2.1.  Header Values
+  ///
+  ///    RCODE     "NoError" or an error code; see Section 2.2.
+  ///
+  ///    QDCOUNT   MUST be 1 in the first message;
+  ///              MUST be 0 or 1 in all following messages.
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the AXFR request
+  /// * `src` - the address the request was received from
+  /// * `request_bytes` - the request as it was received, for verifying its TSIG
+  pub fn transfer(&self, request: &Message, src: Option<&IpAddr>, request_bytes: Option<&[u8]>) -> Transfer {
+    if let Some(view) = self.find_view(src) {
+      return view.transfer(request, src, request_bytes)
+    }
+
+    let mut response: Message = Message::new();
+    response.id(request.get_id());
+    response.op_code(OpCode::Query);
+    response.message_type(MessageType::Response);
+    response.add_all_queries(request.get_queries());
+
+    let queries: &[Query] = request.get_queries();
+    if queries.len() != 1 || queries[0].get_query_type() != RecordType::AXFR {
+      response.response_code(ResponseCode::FormErr);
+      return Transfer::new(response, None);
+    }
+
+    let authority = if let Some(authority) = self.get(queries[0].get_name()) { authority } else {
+      response.response_code(ResponseCode::NotAuth);
+      return Transfer::new(response, None);
+    };
+
+    if !self.query_policy.is_query_allowed(src) {
+      response.response_code(ResponseCode::Refused);
+      return Transfer::new(response, None);
+    }
+
+    let authority = authority.read().unwrap(); // poison errors should panic...
+    let signer = match authority.authorize_transfer(src, request_bytes) {
+      Ok(signer) => signer,
+      Err(response_code) => {
+        response.response_code(response_code);
+        return Transfer::new(response, None);
+      },
+    };
+
+    // the zone is transferred with its DNSSEC records
+    let records = authority.search(&queries[0], true);
+    if records.is_empty() {
+      response.response_code(ResponseCode::Refused);
+    } else {
+      response.response_code(ResponseCode::NoError);
+      response.authoritative(true);
+      response.add_all_answers(&records);
+    }
+
+    Transfer::new(response, signer)
+  }
+
+  /// Handles a NOTIFY from the primary of a secondary zone, the zone is refreshed on the next poll
+  ///  of the `ZoneRefresher`.
+  ///
+  /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+  ///
+  /// ```text
+  /// 3.7. A NOTIFY request has QDCOUNT>0, ANCOUNT>=0, AUCOUNT>=0,
+  ///    ADCOUNT>=0.  If ANCOUNT>0, then the answer section represents an
+  ///    unsecure hint at the new RRset for this <QNAME,QCLASS,QTYPE>.  A
+  ///    slave receiving such a hint is free to treat equivilence of this
+  ///    answer section with its local data as a "no further work needs to
+  ///    be done" indication.  If ANCOUNT=0, or ANCOUNT>0 and the answer
+  ///    section differs from the slave's local data, then the slave should
+  ///    query its known masters to retrieve the new data.
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the NOTIFY request
+  /// * `src` - the address the request was received from
+  pub fn notify(&self, request: &Message, src: Option<&IpAddr>) -> Message {
+    let mut response: Message = Message::new();
+    response.id(request.get_id());
+    response.op_code(OpCode::Notify);
+    response.message_type(MessageType::Response);
+    response.add_all_queries(request.get_queries());
+
+    let queries: &[Query] = request.get_queries();
+    if queries.len() != 1 || queries[0].get_query_type() != RecordType::SOA {
+      response.response_code(ResponseCode::FormErr);
+      return response;
+    }
+
+    let authority = if let Some(authority) = self.get(queries[0].get_name()) { authority } else {
+      response.response_code(ResponseCode::NotAuth);
+      return response;
+    };
+
+    // the SOA hint in the answers is not trusted, the refresh checks the serial at the primary
+    let mut authority = authority.write().unwrap(); // poison errors should panic...
+    match authority.notify(src) {
+      Ok(()) => { response.response_code(ResponseCode::NoError).authoritative(true); },
+      Err(response_code) => { response.response_code(response_code); },
+    }
+
+    response
+  }
+
   /// Given the requested query, lookup and return any matching results.
   ///
   /// # Arguments
   ///
   /// * `request` - the query message.
   pub fn lookup(&self, request: &Message) -> Message {
+    self.lookup_from(request, None)
+  }
+
+  /// Looks up the answers as `lookup()`, refusing the queries for zones which the client may not
+  ///  query, see `Authority::get_query_policy()`
+  pub fn lookup_from(&self, request: &Message, src: Option<&IpAddr>) -> Message {
     let mut response: Message = Message::new();
     response.id(request.get_id());
     response.op_code(OpCode::Query);
     response.message_type(MessageType::Response);
     response.add_all_queries(request.get_queries());
+    response.recursion_desired(request.is_recursion_desired());
+
+    let is_recursion_allowed = self.query_policy.is_recursion_allowed(src);
+    if self.recursor.is_some() && is_recursion_allowed { response.recursion_available(true); }
 
     // TODO: the spec is very unclear on what to do with multiple queries
     //  we will search for each, in the future, maybe make this threaded to respond even faster.
     for query in request.get_queries() {
+      if query.get_query_class() == DNSClass::CH {
+        self.chaos_zones.answer(query, &mut response);
+        continue;
+      }
+
       if let Some(ref_authority) = self.find_auth_recurse(query.get_name()) {
         let authority = &ref_authority.read().unwrap(); // poison errors should panic
         debug!("found authority: {:?}", authority.get_origin());
         let is_dnssec = request.get_edns().map_or(false, |edns|edns.is_dnssec_ok());
 
-        let records = authority.search(query, is_dnssec);
+        if !authority.get_query_policy().is_query_allowed(src) {
+          info!("refusing query for {} from: {:?}", authority.get_origin(), src);
+          response.response_code(ResponseCode::Refused);
+          continue;
+        }
+
+        if query.get_query_type() == RecordType::IXFR {
+          Self::ixfr(request, query, authority, &mut response);
+          continue;
+        }
+
+        // the parent is authoritative for the DS records of the delegation itself
+        if let Some(zone_cut) = authority.find_zone_cut(query.get_name()) {
+          if query.get_query_type() != RecordType::DS || zone_cut != *query.get_name() {
+            Self::referral(&zone_cut, authority, is_dnssec, &mut response);
+            continue;
+          }
+        }
+
+        let records: Vec<Record> = Self::find_answers(authority, query, is_dnssec);
         if !records.is_empty() {
           response.response_code(ResponseCode::NoError);
           response.authoritative(true);
-          response.add_all_answers(&records);
+          for record in records {
+            response.add_answer(record);
+          }
 
           // get the NS records
           let ns = authority.get_ns(is_dnssec);
@@ -254,21 +659,39 @@ impl Catalog {
           else {
             response.add_all_name_servers(&ns);
           }
-        } else {
-          if is_dnssec {
-            // get NSEC records
-            response.add_all_name_servers(&authority.get_nsec_records(query.get_name(), is_dnssec));
-          }
 
-          // in the not found case it's standard to return the SOA in the authority section
-          response.response_code(ResponseCode::NXDomain);
+          Self::additional_section(authority, is_dnssec, request.get_max_payload(), &mut response);
+        } else {
+          // a name which exists, including empty non-terminals and names matched by a wildcard,
+          //  but has no records of the type is NODATA, otherwise the name does not exist
+          let exists = authority.contains_name(query.get_name()) || authority.find_wildcard(query.get_name()).is_some();
+          response.response_code(if exists { ResponseCode::NoError } else { ResponseCode::NXDomain });
+          response.authoritative(true);
 
-          let soa = authority.get_soa_secure(is_dnssec);
+          // in the negative case the SOA in the authority section allows the answer to be cached
+          let soa = authority.get_soa_negative(is_dnssec);
           if soa.is_empty() { warn!("there is no SOA record for: {:?}", authority.get_origin()); }
-          else {
-            response.add_all_name_servers(&soa);
+          for record in soa {
+            response.add_name_server(record);
+          }
+
+          if is_dnssec {
+            // get the NSEC or NSEC3 records which prove the denial
+            response.add_all_name_servers(&authority.get_denial(query.get_name(), is_dnssec));
           }
         }
+      } else if let Some(forwarder) = self.find_forwarder(query.get_name()) {
+        if request.is_recursion_desired() && is_recursion_allowed {
+          forwarder.forward(query, &mut response);
+        } else {
+          response.response_code(ResponseCode::Refused);
+        }
+      } else if let Some(ref recursor) = self.recursor {
+        if request.is_recursion_desired() && is_recursion_allowed {
+          answer_from(&**recursor, query, &mut response);
+        } else {
+          response.response_code(ResponseCode::Refused);
+        }
       } else {
         // we found nothing.
         response.response_code(ResponseCode::NXDomain);
@@ -277,12 +700,212 @@ impl Catalog {
 
     // TODO a lot of things do a recursive query for non-A or AAAA records, and return those in
     //  additional
+    if response.get_response_code() == ResponseCode::Refused { return response }
+    if let Some(query) = request.get_queries().first() {
+      for response_policy in &self.response_policies {
+        if let Some(action) = response_policy.check(query, &response) {
+          info!("policy zone {} matched {} {:?}: {:?}", response_policy.get_origin(), query.get_name(), query.get_query_type(), action);
+          return response_policy.rewrite(action, request, &response).unwrap_or(response)
+        }
+      }
+    }
+
     response
   }
 
+  /// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer in DNS, August 1996
+  ///
+  /// ```text
+  /// 3. Query Format
+  ///
+  ///    The IXFR query packet format is the same as that of a normal DNS
+  ///    query, but with the query type being IXFR and the authority section
+  ///    containing the SOA record of client's version of the zone.
+  /// ```
+  fn ixfr(request: &Message, query: &Query, authority: &Authority, response: &mut Message) {
+    if query.get_name() != authority.get_origin() {
+      response.response_code(ResponseCode::NotAuth);
+      return
+    }
+
+    let serial: Option<u32> = request.get_name_servers()
+                                     .iter()
+                                     .filter_map(|rr| if let &RData::SOA(ref soa) = rr.get_rdata() { Some(soa.get_serial()) } else { None })
+                                     .next();
+
+    let serial = if let Some(serial) = serial { serial } else {
+      warn!("ixfr request without an SOA: {}", request.get_id());
+      response.response_code(ResponseCode::FormErr);
+      return
+    };
+
+    let records = authority.ixfr(serial);
+    if records.is_empty() {
+      response.response_code(ResponseCode::Refused);
+      return
+    }
+
+    response.response_code(ResponseCode::NoError);
+    response.authoritative(true);
+    for record in records {
+      response.add_answer(record);
+    }
+  }
+
+  /// The answers to the query, following any chain of CNAMEs to targets within the zone
+  ///
+  /// [RFC 1034](https://tools.ietf.org/html/rfc1034), Domain Concepts and Facilities, November 1987
+  ///
+  /// ```text
+  /// 4.3.2. Algorithm
+  ///
+  ///       If the data at the node is a CNAME, and QTYPE doesn't
+  ///       match CNAME, copy the CNAME RR into the answer section
+  ///       of the response, change QNAME to the canonical name in
+  ///       the CNAME RR, and go back to step 1.
+  /// ```
+  fn find_answers(authority: &Authority, query: &Query, is_dnssec: bool) -> Vec<Record> {
+    let mut answers: Vec<Record> = Self::find_records(authority, query, is_dnssec);
+    match query.get_query_type() {
+      RecordType::CNAME | RecordType::ANY | RecordType::AXFR => return answers,
+      _ if !answers.is_empty() => return answers,
+      _ => (),
+    }
+
+    let mut visited: Vec<Name> = Vec::new();
+    let mut name: Name = query.get_name().clone();
+    while visited.len() < MAX_CNAME_CHAIN {
+      if visited.contains(&name) {
+        warn!("cname loop at {} for: {}", name, query.get_name());
+        break
+      }
+      visited.push(name.clone());
+
+      let mut cname_query = Query::new();
+      cname_query.name(name.clone()).query_type(RecordType::CNAME).query_class(query.get_query_class());
+      let cnames: Vec<Record> = Self::find_records(authority, &cname_query, is_dnssec);
+      let target: Name = match cnames.iter().filter_map(|rr| if let &RData::CNAME(ref target) = rr.get_rdata() { Some(target.clone()) } else { None }).next() {
+        Some(target) => target,
+        None => break,
+      };
+      answers.extend(cnames);
+
+      // targets outside of the zone, or delegated from it, are left to the resolver
+      if !authority.get_origin().zone_of(&target) || authority.find_zone_cut(&target).is_some() { break }
+
+      let mut target_query = Query::new();
+      target_query.name(target.clone()).query_type(query.get_query_type()).query_class(query.get_query_class());
+      let records: Vec<Record> = Self::find_records(authority, &target_query, is_dnssec);
+      if !records.is_empty() {
+        answers.extend(records);
+        break
+      }
+
+      name = target;
+    }
+
+    answers
+  }
+
+  /// the records of the zone matching the query, or synthesized from a wildcard
+  fn find_records(authority: &Authority, query: &Query, is_dnssec: bool) -> Vec<Record> {
+    let records: Vec<Record> = authority.search(query, is_dnssec).into_iter().cloned().collect();
+    if records.is_empty() { authority.wildcard_search(query, is_dnssec) } else { records }
+  }
+
+  /// Adds the addresses in the zone of the names in the answer and authority sections, e.g. the
+  ///  exchange of an MX, to the additional section, as long as the response fits in max_payload.
+  ///
+  /// [RFC 1034](https://tools.ietf.org/html/rfc1034), Domain Concepts and Facilities, November 1987
+  ///
+  /// ```text
+  /// 3.7. Queries
+  ///
+  ///    Additional      Records that relate to the query, but are not
+  ///                    strictly answers for the question.
+  /// ```
+  fn additional_section(authority: &Authority, is_dnssec: bool, max_payload: u16, response: &mut Message) {
+    let targets: Vec<Name> = response.get_answers().iter()
+                                     .chain(response.get_name_servers().iter())
+                                     .filter_map(|rr| match *rr.get_rdata() {
+                                       RData::MX(ref mx) => Some(mx.get_exchange().clone()),
+                                       RData::SRV(ref srv) => Some(srv.get_target().clone()),
+                                       RData::NS(ref name) | RData::CNAME(ref name) => Some(name.clone()),
+                                       _ => None,
+                                     })
+                                     .collect();
+
+    let mut size: usize = match encoded_len(response) {
+      Ok(size) => size,
+      Err(error) => { warn!("could not encode the response: {}", error); return },
+    };
+
+    for target in targets {
+      for rtype in &[RecordType::A, RecordType::AAAA] {
+        for record in authority.lookup(&target, *rtype, is_dnssec) {
+          if response.get_answers().contains(record) || response.get_additional().contains(record) { continue }
+
+          let record_size = match encoded_len(record) {
+            Ok(record_size) => record_size,
+            Err(..) => continue,
+          };
+
+          // names are compressed in the response, so this overestimates its size
+          if size + record_size > max_payload as usize { return }
+          size += record_size;
+          response.add_additional(record.clone());
+        }
+      }
+    }
+  }
+
+  /// [RFC 1034](https://tools.ietf.org/html/rfc1034), Domain Concepts and Facilities, November 1987
+  ///
+  /// ```text
+  /// 4.3.2. Algorithm
+  ///
+  ///    b. If a match would take us out of the authoritative data,
+  ///       we have a referral.  This happens when we encounter a
+  ///       node with NS RRs marking cuts along the bottom of a
+  ///       zone.
+  ///
+  ///       Copy the NS RRs for the subzone into the authority
+  ///       section of the reply.  Put whatever addresses are
+  ///       available into the additional section, using glue RRs
+  ///       if the addresses are not available from authoritative
+  ///       data or the cache.  Go to step 4.
+  /// ```
+  fn referral(zone_cut: &Name, authority: &Authority, is_dnssec: bool, response: &mut Message) {
+    let ns = authority.lookup(zone_cut, RecordType::NS, false);
+
+    // a referral is not an authoritative answer
+    response.response_code(ResponseCode::NoError);
+    response.authoritative(false);
+    response.add_all_name_servers(&ns);
+
+    // the signed DS records, or their absence, show if the child is signed
+    if is_dnssec {
+      let ds = authority.lookup(zone_cut, RecordType::DS, true);
+      if ds.is_empty() {
+        response.add_all_name_servers(&authority.get_nsec_records(zone_cut, true));
+      } else {
+        response.add_all_name_servers(&ds);
+      }
+    }
+
+    // the glue, the addresses of the name servers which are in the zone
+    for name_server in ns.iter().filter_map(|rr| if let &RData::NS(ref name) = rr.get_rdata() { Some(name) } else { None }) {
+      for rtype in &[RecordType::A, RecordType::AAAA] {
+        for glue in authority.lookup(name_server, *rtype, false) {
+          response.add_additional(glue.clone());
+        }
+      }
+    }
+  }
+
   /// recursively searches the catalog for a matching auhtority.
-  fn find_auth_recurse(&self, name: &Name) -> Option<&RwLock<Authority>> {
-    let authority = self.authorities.get(name);
+  fn find_auth_recurse(&self, name: &Name) -> Option<Arc<RwLock<Authority>>> {
+    let authority = self.get(name);
     if authority.is_some() { return authority; }
     else {
       let name = name.base_name();
@@ -295,6 +918,16 @@ impl Catalog {
   }
 }
 
+/// the length of the serialized message or record
+fn encoded_len<B: BinSerializable<B>>(serializable: &B) -> EncodeResult<usize> {
+  let mut buf: Vec<u8> = Vec::with_capacity(512);
+  {
+    let mut encoder = BinEncoder::new(&mut buf);
+    try!(serializable.emit(&mut encoder));
+  }
+  Ok(buf.len())
+}
+
 #[cfg(test)]
 mod catalog_tests {
   use std::net::*;
@@ -333,7 +966,7 @@ mod catalog_tests {
     let origin = example.get_origin().clone();
     let test_origin = test.get_origin().clone();
 
-    let mut catalog: Catalog = Catalog::new();
+    let catalog: Catalog = Catalog::new();
     catalog.upsert(origin.clone(), example);
     catalog.upsert(test_origin.clone(), test);
 
@@ -382,12 +1015,118 @@ mod catalog_tests {
     assert_eq!(answers.first().unwrap().get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
   }
 
+  #[test]
+  fn test_referral() {
+    let mut test = create_test();
+    let origin = test.get_origin().clone();
+    let sub = Name::parse("sub.test.com.", None).unwrap();
+    let ns_sub = Name::parse("ns.sub.test.com.", None).unwrap();
+    let ns = Record::new().name(sub.clone()).ttl(86400).rr_type(RecordType::NS).dns_class(DNSClass::IN).rdata(RData::NS(ns_sub.clone())).clone();
+    let glue = Record::new().name(ns_sub.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(192,0,2,53))).clone();
+    test.upsert(ns.clone(), 0);
+    test.upsert(glue.clone(), 0);
+
+    let catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    for name in &["www.sub.test.com.", "sub.test.com.", "ns.sub.test.com."] {
+      let mut query: Query = Query::new();
+      query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+      let mut question: Message = Message::new();
+      question.add_query(query);
+
+      let result: Message = catalog.lookup(&question);
+      assert_eq!(result.get_response_code(), ResponseCode::NoError);
+      assert!(!result.is_authoritative());
+      assert!(result.get_answers().is_empty());
+      assert_eq!(result.get_name_servers(), &[ns.clone()]);
+      assert_eq!(result.get_additional(), &[glue.clone()]);
+    }
+
+    // names beside the delegation are answered
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.test.com.", None).unwrap()).query_type(RecordType::A);
+    let mut question: Message = Message::new();
+    question.add_query(query);
+    let result: Message = catalog.lookup(&question);
+    assert!(result.is_authoritative());
+    assert!(!result.get_answers().is_empty());
+  }
+
+  #[test]
+  fn test_additional_section() {
+    use ::rr::rdata::MX;
+
+    let mut test = create_test();
+    let origin = test.get_origin().clone();
+    let mail = Name::parse("mail.test.com.", None).unwrap();
+    let mx = Record::new().name(origin.clone()).ttl(86400).rr_type(RecordType::MX).dns_class(DNSClass::IN).rdata(RData::MX(MX::new(10, mail.clone()))).clone();
+    let address = Record::new().name(mail.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(192,0,2,25))).clone();
+    test.upsert(mx.clone(), 0);
+    test.upsert(address.clone(), 0);
+
+    let catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::MX);
+    let mut question: Message = Message::new();
+    question.add_query(query);
+
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.get_answers(), &[mx]);
+    assert_eq!(result.get_additional(), &[address]);
+  }
+
+  #[test]
+  fn test_cname_chain() {
+    let mut test = create_test();
+    let origin = test.get_origin().clone();
+    let name = |s: &str| Name::parse(s, Some(&origin)).unwrap();
+    let cname = |from: &Name, to: &Name| Record::new().name(from.clone()).ttl(86400).rr_type(RecordType::CNAME).dns_class(DNSClass::IN).rdata(RData::CNAME(to.clone())).clone();
+
+    let external = Name::parse("www.example.net.", None).unwrap();
+    let chain = vec![cname(&name("alias"), &name("alias2")), cname(&name("alias2"), &name("www"))];
+    let loops = vec![cname(&name("loop1"), &name("loop2")), cname(&name("loop2"), &name("loop1"))];
+    for record in chain.iter().chain(loops.iter()) {
+      test.upsert(record.clone(), 0);
+    }
+    test.upsert(cname(&name("out"), &external), 0);
+
+    let catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    let lookup = |n: &Name, rtype: RecordType| {
+      let mut query: Query = Query::new();
+      query.name(n.clone()).query_type(rtype);
+      let mut question: Message = Message::new();
+      question.add_query(query);
+      catalog.lookup(&question)
+    };
+
+    let result = lookup(&name("alias"), RecordType::A);
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert_eq!(&result.get_answers()[..2], &chain[..]);
+    assert_eq!(result.get_answers()[2].get_rdata(), &RData::A(Ipv4Addr::new(94,184,216,34)));
+
+    // the CNAME itself is not followed
+    assert_eq!(lookup(&name("alias"), RecordType::CNAME).get_answers(), &chain[..1]);
+
+    // the loop is cut, with each CNAME once
+    assert_eq!(lookup(&name("loop1"), RecordType::A).get_answers(), &loops[..]);
+
+    // out of the zone, the resolver follows the target
+    let result = lookup(&name("out"), RecordType::A);
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert_eq!(result.get_answers(), &[cname(&name("out"), &external)]);
+  }
+
   #[test]
   fn test_catalog_nx_soa() {
     let example = create_example();
     let origin = example.get_origin().clone();
 
-    let mut catalog: Catalog = Catalog::new();
+    let catalog: Catalog = Catalog::new();
     catalog.upsert(origin.clone(), example);
 
     let mut question: Message = Message::new();
@@ -409,13 +1148,50 @@ mod catalog_tests {
     assert_eq!(ns.first().unwrap().get_rdata(), &RData::SOA(SOA::new(Name::parse("sns.dns.icann.org.", None).unwrap(), Name::parse("noc.dns.icann.org.", None).unwrap(), 2015082403, 7200, 3600, 1209600, 3600 )));
   }
 
+  #[test]
+  fn test_negative_responses() {
+    let mut test = create_test();
+    let origin = test.get_origin().clone();
+
+    // the negative TTL is the lesser of the SOA TTL and minimum
+    test.upsert(Record::new().name(origin.clone()).ttl(3600).rr_type(RecordType::SOA).dns_class(DNSClass::IN).rdata(RData::SOA(SOA::new(Name::parse("sns.dns.icann.org.", None).unwrap(), Name::parse("noc.dns.icann.org.", None).unwrap(), 2015082404, 7200, 3600, 1209600, 300 ))).clone(), 0);
+
+    // b.test.com. is an empty non-terminal
+    test.upsert(Record::new().name(Name::parse("a.b.test.com.", None).unwrap()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(192,0,2,1))).clone(), 0);
+
+    let catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    let lookup = |n: &str, rtype: RecordType| {
+      let mut query: Query = Query::new();
+      query.name(Name::parse(n, None).unwrap()).query_type(rtype);
+      let mut question: Message = Message::new();
+      question.add_query(query);
+      catalog.lookup(&question)
+    };
+
+    for &(name, rtype, code) in &[("nx.test.com.", RecordType::A, ResponseCode::NXDomain),
+                                  ("b.test.com.", RecordType::A, ResponseCode::NoError),
+                                  ("www.test.com.", RecordType::MX, ResponseCode::NoError)] {
+      let result = lookup(name, rtype);
+      assert_eq!(result.get_response_code(), code);
+      assert!(result.is_authoritative());
+      assert!(result.get_answers().is_empty());
+
+      let ns: &[Record] = result.get_name_servers();
+      assert_eq!(ns.len(), 1);
+      assert_eq!(ns[0].get_rr_type(), RecordType::SOA);
+      assert_eq!(ns[0].get_ttl(), 300);
+    }
+  }
+
   #[test]
   fn test_axfr() {
     let test = create_test();
     let origin = test.get_origin().clone();
     let soa = Record::new().name(origin.clone()).ttl(3600).rr_type(RecordType::SOA).dns_class(DNSClass::IN).rdata(RData::SOA(SOA::new(Name::parse("sns.dns.icann.org.", None).unwrap(), Name::parse("noc.dns.icann.org.", None).unwrap(), 2015082403, 7200, 3600, 1209600, 3600 ))).clone();
 
-    let mut catalog: Catalog = Catalog::new();
+    let catalog: Catalog = Catalog::new();
     catalog.upsert(origin.clone(), test);
 
     let mut query: Query = Query::new();
@@ -449,4 +1225,388 @@ mod catalog_tests {
 
     assert_eq!(expected_set, answers);
   }
+
+  #[test]
+  fn test_transfer_policy() {
+    use ::authority::TransferPolicy;
+
+    let mut test = create_test();
+    let origin = test.get_origin().clone();
+    let mut policy = TransferPolicy::new();
+    policy.allow_network("192.0.2.0/24".parse().unwrap());
+    test.set_transfer_policy(policy);
+
+    let catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::AXFR);
+    let mut question: Message = Message::new();
+    question.add_query(query);
+    assert!(Catalog::is_transfer(&question));
+
+    let allowed = catalog.transfer(&question, Some(&IpAddr::V4(Ipv4Addr::new(192,0,2,1))), None);
+    assert_eq!(allowed.get_response().get_response_code(), ResponseCode::NoError);
+    assert_eq!(allowed.get_response().get_answers().first().unwrap().get_rr_type(), RecordType::SOA);
+    assert_eq!(allowed.get_response().get_answers().last().unwrap().get_rr_type(), RecordType::SOA);
+    assert!(!allowed.is_signed());
+
+    let refused = catalog.transfer(&question, Some(&IpAddr::V4(Ipv4Addr::new(198,51,100,1))), None);
+    assert_eq!(refused.get_response().get_response_code(), ResponseCode::Refused);
+    assert!(refused.get_response().get_answers().is_empty());
+
+    // an unknown source is refused through the request handler as well
+    let response = catalog.handle_request(&question);
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+
+    // only the zone itself can be transferred
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.test.com.", None).unwrap()).query_type(RecordType::AXFR);
+    let mut question: Message = Message::new();
+    question.add_query(query);
+    let response = catalog.transfer(&question, Some(&IpAddr::V4(Ipv4Addr::new(192,0,2,1))), None);
+    assert_eq!(response.get_response().get_response_code(), ResponseCode::NotAuth);
+  }
+
+  #[test]
+  fn test_query_policy() {
+    use ::authority::QueryPolicy;
+
+    let mut test = create_test();
+    let origin = test.get_origin().clone();
+    let mut policy = QueryPolicy::new();
+    policy.allow_network("192.0.2.0/24".parse().unwrap());
+    test.set_query_policy(policy);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.test.com.", None).unwrap()).query_type(RecordType::A);
+    let mut question: Message = Message::new();
+    question.message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    let query_from = |catalog: &Catalog, src: [u8; 4]| catalog.handle_request_with(&question, Some(IpAddr::V4(Ipv4Addr::new(src[0], src[1], src[2], src[3]))), None);
+
+    // the zone refuses clients outside of its networks
+    assert_eq!(query_from(&catalog, [192,0,2,1]).get_response_code(), ResponseCode::NoError);
+    assert_eq!(query_from(&catalog, [198,51,100,1]).get_response_code(), ResponseCode::Refused);
+    assert!(query_from(&catalog, [198,51,100,1]).get_answers().is_empty());
+
+    // the catalog refuses them for all zones
+    let mut policy = QueryPolicy::new();
+    policy.deny_network("192.0.2.128/25".parse().unwrap());
+    catalog.set_query_policy(policy);
+    assert_eq!(query_from(&catalog, [192,0,2,1]).get_response_code(), ResponseCode::NoError);
+    assert_eq!(query_from(&catalog, [192,0,2,129]).get_response_code(), ResponseCode::Refused);
+  }
+
+  #[test]
+  fn test_views() {
+    let public = create_test();
+    let origin = public.get_origin().clone();
+    let www = Name::parse("www.test.com.", None).unwrap();
+
+    let mut internal = create_test();
+    internal.upsert(Record::new().name(www.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,0,0,1))).clone(), 0);
+    let view: Catalog = Catalog::new();
+    view.upsert(origin.clone(), internal);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), public);
+    catalog.add_view(vec!["10.0.0.0/8".parse().unwrap()], view);
+
+    let mut query: Query = Query::new();
+    query.name(www.clone()).query_type(RecordType::A);
+    let mut question: Message = Message::new();
+    question.message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    let addresses = |src: Option<IpAddr>| -> Vec<RData> {
+      catalog.handle_request_with(&question, src, None).get_answers().iter().map(|r| r.get_rdata().clone()).collect()
+    };
+
+    assert!(addresses(Some(IpAddr::V4(Ipv4Addr::new(10,1,2,3)))).contains(&RData::A(Ipv4Addr::new(10,0,0,1))));
+    assert_eq!(addresses(Some(IpAddr::V4(Ipv4Addr::new(192,0,2,1)))), vec![RData::A(Ipv4Addr::new(94,184,216,34))]);
+    assert_eq!(addresses(None), vec![RData::A(Ipv4Addr::new(94,184,216,34))]);
+  }
+
+  #[test]
+  fn test_forwarder() {
+    use ::authority::{Forwarder, QueryPolicy, Upstream};
+    use ::error::ClientResult;
+
+    struct Echo;
+    impl Upstream for Echo {
+      fn lookup(&self, query: Query) -> ClientResult<Message> {
+        let mut answer = Message::new();
+        answer.response_code(ResponseCode::NoError)
+              .add_answer(Record::new().name(query.get_name().clone()).ttl(300).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(192,0,2,80))).clone());
+        Ok(answer)
+      }
+    }
+
+    let test = create_test();
+    let origin = test.get_origin().clone();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.add_forwarder(Forwarder::new(Name::parse("example.net.", None).unwrap(), Box::new(Echo)));
+
+    let mut policy = QueryPolicy::new();
+    policy.allow_recursion_network("192.0.2.0/24".parse().unwrap());
+    catalog.set_query_policy(policy);
+
+    let question = |name: &str, recursion_desired: bool| {
+      let mut query: Query = Query::new();
+      query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+      let mut question: Message = Message::new();
+      question.message_type(MessageType::Query).op_code(OpCode::Query).recursion_desired(recursion_desired).add_query(query);
+      question
+    };
+    let client = Some(IpAddr::V4(Ipv4Addr::new(192,0,2,1)));
+
+    let response = catalog.handle_request_with(&question("www.example.net.", true), client, None);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_recursion_available());
+    assert!(!response.is_authoritative());
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(192,0,2,80)));
+
+    // the zones take precedence
+    let response = catalog.handle_request_with(&question("www.test.com.", true), client, None);
+    assert!(response.is_authoritative());
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(94,184,216,34)));
+
+    // recursion must be desired, and allowed
+    let response = catalog.handle_request_with(&question("www.example.net.", false), client, None);
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    let response = catalog.handle_request_with(&question("www.example.net.", true), Some(IpAddr::V4(Ipv4Addr::new(198,51,100,1))), None);
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+
+    // other names are not forwarded
+    let response = catalog.handle_request_with(&question("www.example.org.", true), client, None);
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+  }
+
+  #[test]
+  fn test_recursor() {
+    use ::authority::{QueryPolicy, Upstream};
+    use ::error::{ClientErrorKind, ClientResult};
+
+    struct Unreachable;
+    impl Upstream for Unreachable {
+      fn lookup(&self, _: Query) -> ClientResult<Message> {
+        Err(ClientErrorKind::Message("no route to the root servers").into())
+      }
+    }
+
+    let test = create_test();
+    let origin = test.get_origin().clone();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.set_recursor(Some(Box::new(Unreachable)));
+
+    let mut policy = QueryPolicy::new();
+    policy.allow_recursion_network("192.0.2.0/24".parse().unwrap());
+    catalog.set_query_policy(policy);
+
+    let question = |name: &str| {
+      let mut query: Query = Query::new();
+      query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+      let mut question: Message = Message::new();
+      question.message_type(MessageType::Query).op_code(OpCode::Query).recursion_desired(true).add_query(query);
+      question
+    };
+    let client = Some(IpAddr::V4(Ipv4Addr::new(192,0,2,1)));
+    let other = Some(IpAddr::V4(Ipv4Addr::new(198,51,100,1)));
+
+    // the authoritative zones are answered without the recursor, with RA for allowed clients
+    let response = catalog.handle_request_with(&question("www.test.com."), client, None);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_authoritative() && response.is_recursion_available() && response.is_recursion_desired());
+    assert!(!catalog.handle_request_with(&question("www.test.com."), other, None).is_recursion_available());
+
+    // the failure of the recursor is a SERVFAIL
+    let response = catalog.handle_request_with(&question("www.example.org."), client, None);
+    assert_eq!(response.get_response_code(), ResponseCode::ServFail);
+    assert!(response.is_recursion_available());
+
+    let response = catalog.handle_request_with(&question("www.example.org."), other, None);
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(!response.is_recursion_available());
+  }
+
+  #[test]
+  fn test_update_zone_section() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let mut update: Message = Message::new();
+    update.op_code(OpCode::Update);
+
+    // a name within the zone is not the zone
+    let mut zone: Query = Query::new();
+    zone.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::SOA);
+    update.add_zone(zone);
+    assert_eq!(catalog.update(&update).get_response_code(), ResponseCode::NotAuth);
+
+    // nor is another class
+    let mut update: Message = Message::new();
+    let mut zone: Query = Query::new();
+    zone.name(origin.clone()).query_type(RecordType::SOA).query_class(DNSClass::CH);
+    update.add_zone(zone);
+    assert_eq!(catalog.update(&update).get_response_code(), ResponseCode::NotAuth);
+
+    // updates of the zone are refused, as it does not allow them
+    let mut update: Message = Message::new();
+    let mut zone: Query = Query::new();
+    zone.name(origin.clone()).query_type(RecordType::SOA);
+    update.add_zone(zone);
+    assert_eq!(catalog.update(&update).get_response_code(), ResponseCode::Refused);
+  }
+
+  #[test]
+  fn test_notify() {
+    let origin: Name = Name::parse("example.com.", None).unwrap();
+    let primary: SocketAddr = "192.0.2.1:53".parse().unwrap();
+
+    let mut secondary = Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Slave, false);
+    secondary.set_primaries(vec![primary]);
+
+    let catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), secondary);
+    catalog.upsert(Name::parse("test.com.", None).unwrap(), create_test());
+
+    let notify = |name: &Name| {
+      let mut query: Query = Query::new();
+      query.name(name.clone()).query_type(RecordType::SOA);
+      let mut request: Message = Message::new();
+      request.id(7).op_code(OpCode::Notify).add_query(query);
+      request
+    };
+
+    // only the primary may notify
+    let response = catalog.notify(&notify(&origin), Some(&"192.0.2.2".parse().unwrap()));
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(!catalog.get(&origin).unwrap().write().unwrap().take_notified());
+
+    let response = catalog.notify(&notify(&origin), Some(&primary.ip()));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_id(), 7);
+    assert_eq!(response.get_op_code(), OpCode::Notify);
+    assert_eq!(response.get_message_type(), MessageType::Response);
+    assert!(response.is_authoritative());
+    assert_eq!(response.get_queries().len(), 1);
+    assert!(catalog.get(&origin).unwrap().write().unwrap().take_notified());
+    assert!(!catalog.get(&origin).unwrap().write().unwrap().take_notified());
+
+    // the primary zone is not a secondary, nor is an unknown zone
+    let response = catalog.notify(&notify(&Name::parse("test.com.", None).unwrap()), Some(&primary.ip()));
+    assert_eq!(response.get_response_code(), ResponseCode::NotAuth);
+    let response = catalog.notify(&notify(&Name::parse("example.net.", None).unwrap()), Some(&primary.ip()));
+    assert_eq!(response.get_response_code(), ResponseCode::NotAuth);
+  }
+
+  #[test]
+  fn test_upsert_remove() {
+    let origin: Name = Name::parse("test.com.", None).unwrap();
+    let www: Name = Name::parse("www.test.com.", None).unwrap();
+
+    let catalog: Catalog = Catalog::new();
+
+    let mut query: Query = Query::new();
+    query.name(www.clone()).query_type(RecordType::A);
+    let mut question: Message = Message::new();
+    question.message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    assert_eq!(catalog.lookup(&question).get_response_code(), ResponseCode::NXDomain);
+
+    // a zone added while serving is answered for
+    catalog.upsert(origin.clone(), create_test());
+    let held = catalog.get(&origin).unwrap();
+    let response = catalog.lookup(&question);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_authoritative());
+    assert_eq!(response.get_answers().len(), 1);
+
+    // and no longer once removed, the zone held by a request in flight is still readable
+    assert!(catalog.remove(&origin));
+    assert!(!catalog.remove(&origin));
+    assert!(catalog.get(&origin).is_none());
+    assert!(catalog.origins().is_empty());
+    assert_eq!(catalog.lookup(&question).get_response_code(), ResponseCode::NXDomain);
+    assert_eq!(held.read().unwrap().get_origin(), &origin);
+  }
+  #[test]
+  fn test_edns() {
+    use ::rr::rdata::opt::{EdnsCode, EdnsOption};
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(Name::parse("test.com.", None).unwrap(), create_test());
+    catalog.set_max_payload(1232);
+    catalog.set_nsid(Some(b"ns1".to_vec()));
+
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.test.com.", None).unwrap()).query_type(RecordType::A);
+    let mut question: Message = Message::new();
+    question.message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    // no OPT is sent to clients without EDNS
+    assert!(catalog.handle_request(&question).get_edns().is_none());
+
+    let mut edns: Edns = Edns::new();
+    edns.set_max_payload(4096);
+    edns.set_option(EdnsOption::Unknown(u16::from(EdnsCode::NSID), vec![]));
+    question.set_edns(edns.clone());
+
+    let response = catalog.handle_request(&question);
+    let resp_edns = response.get_edns().unwrap();
+    assert_eq!(resp_edns.get_version(), 0);
+    assert_eq!(resp_edns.get_max_payload(), 1232);
+    assert!(!resp_edns.is_dnssec_ok());
+    assert_eq!(resp_edns.get_option(&EdnsCode::NSID), Some(&EdnsOption::Unknown(u16::from(EdnsCode::NSID), b"ns1".to_vec())));
+
+    // unsupported versions are answered with BADVERS
+    edns.set_version(1);
+    question.set_edns(edns);
+    let response = catalog.handle_request(&question);
+    // 16 is shared with BADSIG
+    assert_eq!(u16::from(response.get_response_code()), u16::from(ResponseCode::BADVERS));
+    assert_eq!(response.get_message_type(), MessageType::Response);
+    assert_eq!(response.get_edns().unwrap().get_version(), 0);
+  }
+
+  #[test]
+  fn test_response_policy() {
+    use ::authority::ResponsePolicyZone;
+
+    let rpz_origin: Name = Name::parse("rpz.", None).unwrap();
+    let mut policy_zone = Authority::new(rpz_origin.clone(), BTreeMap::new(), ZoneType::Master, false);
+    policy_zone.upsert(Record::new().name(rpz_origin.clone()).ttl(3600).rr_type(RecordType::SOA).dns_class(DNSClass::IN).rdata(RData::SOA(SOA::new(Name::parse("localhost.", None).unwrap(), Name::parse("root.localhost.", None).unwrap(), 1, 3600, 600, 86400, 60))).clone(), 1);
+    policy_zone.upsert(Record::new().name(Name::parse("www.test.com.rpz.", None).unwrap()).ttl(60).rr_type(RecordType::CNAME).dns_class(DNSClass::IN).rdata(RData::CNAME(Name::root())).clone(), 1);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(Name::parse("test.com.", None).unwrap(), create_test());
+    catalog.add_response_policy(ResponsePolicyZone::new(policy_zone));
+
+    let question = |name: &str| {
+      let mut query: Query = Query::new();
+      query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+      let mut question: Message = Message::new();
+      question.id(5).message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+      question
+    };
+
+    let response = catalog.lookup(&question("www.test.com."));
+    assert_eq!(response.get_id(), 5);
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+    assert!(response.get_answers().is_empty());
+    assert_eq!(response.get_name_servers()[0].get_name(), &rpz_origin);
+
+    // other names are answered from the zone
+    let response = catalog.lookup(&question("test.com."));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers().len(), 1);
+  }
 }
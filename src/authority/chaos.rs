@@ -0,0 +1,150 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use ::op::{Message, Query, ResponseCode};
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+use ::rr::rdata::TXT;
+
+/// The built-in CHAOS class zones, which identify the server, e.g. the instance of an anycast
+///  address which answered.
+///
+/// ```text
+/// version.bind.   CH TXT "0.7.3"
+/// version.server. CH TXT "0.7.3"
+/// hostname.bind.  CH TXT "ns1.example.com"
+/// id.server.      CH TXT "ns1.example.com"
+/// ```
+///
+/// Each answer is disabled when it is None, queries for it, and for other CHAOS names, are
+///  answered with REFUSED. By default all of them are disabled.
+///
+/// ```rust,ignore
+/// let mut chaos = ChaosZones::new();
+/// chaos.version(Some(trust_dns::version().into())).hostname(Some("ns1.example.com".to_string()));
+/// catalog.set_chaos_zones(chaos);
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct ChaosZones {
+  version: Option<String>,
+  hostname: Option<String>,
+  id: Option<String>,
+}
+
+impl ChaosZones {
+  pub fn new() -> Self {
+    ChaosZones::default()
+  }
+
+  /// The answer to `version.bind` and `version.server`
+  pub fn version(&mut self, version: Option<String>) -> &mut Self {
+    self.version = version;
+    self
+  }
+
+  /// The answer to `hostname.bind`
+  pub fn hostname(&mut self, hostname: Option<String>) -> &mut Self {
+    self.hostname = hostname;
+    self
+  }
+
+  /// The answer to `id.server`, by default that of `hostname.bind`
+  pub fn id(&mut self, id: Option<String>) -> &mut Self {
+    self.id = id;
+    self
+  }
+
+  pub fn get_version(&self) -> Option<&str> { self.version.as_ref().map(|s| s as &str) }
+  pub fn get_hostname(&self) -> Option<&str> { self.hostname.as_ref().map(|s| s as &str) }
+  pub fn get_id(&self) -> Option<&str> { self.id.as_ref().or(self.hostname.as_ref()).map(|s| s as &str) }
+
+  /// Answers the CHAOS class query, the answer is authoritative
+  pub fn answer(&self, query: &Query, response: &mut Message) {
+    let name = query.get_name().to_lowercase();
+    let text: Option<&str> = if name == Self::name("version", "bind") || name == Self::name("version", "server") {
+      self.get_version()
+    } else if name == Self::name("hostname", "bind") {
+      self.get_hostname()
+    } else if name == Self::name("id", "server") {
+      self.get_id()
+    } else {
+      None
+    };
+
+    let text: &str = if let Some(text) = text { text } else {
+      debug!("refusing CHAOS query for: {}", query.get_name());
+      response.response_code(ResponseCode::Refused);
+      return
+    };
+
+    response.response_code(ResponseCode::NoError);
+    response.authoritative(true);
+
+    match query.get_query_type() {
+      RecordType::TXT | RecordType::ANY => {
+        response.add_answer(Record::new().name(query.get_name().clone()).ttl(0).rr_type(RecordType::TXT).dns_class(DNSClass::CH)
+                                         .rdata(RData::TXT(TXT::new(vec![text.to_string()]))).clone());
+      },
+      _ => (),
+    }
+  }
+
+  fn name(label: &'static str, domain: &'static str) -> Name {
+    Name::new().label(label).label(domain)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use ::op::{Message, Query, ResponseCode};
+  use ::rr::{DNSClass, Name, RData, RecordType};
+  use ::rr::rdata::TXT;
+  use super::*;
+
+  fn answer(chaos: &ChaosZones, name: &str, query_type: RecordType) -> Message {
+    let mut query = Query::new();
+    query.name(Name::parse(name, None).unwrap()).query_type(query_type).query_class(DNSClass::CH);
+
+    let mut response = Message::new();
+    chaos.answer(&query, &mut response);
+    response
+  }
+
+  #[test]
+  fn test_answer() {
+    let mut chaos = ChaosZones::new();
+    assert_eq!(answer(&chaos, "version.bind.", RecordType::TXT).get_response_code(), ResponseCode::Refused);
+
+    chaos.version(Some("0.7.3".to_string())).hostname(Some("ns1.example.com".to_string()));
+
+    let response = answer(&chaos, "VERSION.bind.", RecordType::TXT);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_authoritative());
+    assert_eq!(response.get_answers()[0].get_dns_class(), DNSClass::CH);
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::TXT(TXT::new(vec!["0.7.3".to_string()])));
+
+    // id.server is the hostname unless set
+    let response = answer(&chaos, "id.server.", RecordType::TXT);
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::TXT(TXT::new(vec!["ns1.example.com".to_string()])));
+    chaos.id(Some("anycast-1".to_string()));
+    let response = answer(&chaos, "id.server.", RecordType::ANY);
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::TXT(TXT::new(vec!["anycast-1".to_string()])));
+
+    // no data of other types, and other names are refused
+    let response = answer(&chaos, "hostname.bind.", RecordType::A);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.get_answers().is_empty());
+    assert_eq!(answer(&chaos, "authors.bind.", RecordType::TXT).get_response_code(), ResponseCode::Refused);
+  }
+}
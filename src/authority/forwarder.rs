@@ -0,0 +1,111 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::net::SocketAddr;
+
+use ::client::{Client, ClientConnection, NameServerPool, SelectionStrategy, SharedCache};
+use ::error::*;
+use ::op::{Message, Query, ResponseCode};
+use ::resolver::{Connector, Recursor};
+use ::rr::Name;
+use ::serialize::binary::DecodeMode;
+use ::udp::UdpClientConnection;
+
+/// The resolvers which queries are forwarded to, e.g. a `Client`, or which resolve them
+///  recursively, e.g. a `Recursor`
+pub trait Upstream {
+  /// Resolves the query, returning the response of the upstream resolver
+  fn lookup(&self, query: Query) -> ClientResult<Message>;
+}
+
+impl<C: ClientConnection> Upstream for Client<C> {
+  fn lookup(&self, query: Query) -> ClientResult<Message> {
+    Client::lookup(self, query)
+  }
+}
+
+impl<T: Connector> Upstream for Recursor<T> {
+  fn lookup(&self, query: Query) -> ClientResult<Message> {
+    self.resolve(query.get_name(), query.get_query_class(), query.get_query_type())
+  }
+}
+
+/// Forwards the queries for the names of a domain, which are not in any zone of the `Catalog`, to
+///  upstream resolvers, making the server a forwarding proxy. Forwarding the root domain forwards
+///  all such queries.
+///
+/// The answers are not authoritative, and have RA set. If the upstream resolvers fail, the query
+///  is answered with SERVFAIL.
+///
+/// ```rust,ignore
+/// let cache = SharedCache::new(ResponseCache::new(4096));
+/// let forwarder = Forwarder::with_name_servers(Name::root(), &["192.0.2.53:53".parse().unwrap()], cache).unwrap();
+/// catalog.add_forwarder(forwarder);
+/// ```
+pub struct Forwarder {
+  domain: Name,
+  upstream: Box<Upstream>,
+}
+
+impl Forwarder {
+  /// # Arguments
+  ///
+  /// * `domain` - the names at and below this are forwarded
+  /// * `upstream` - resolves the forwarded queries
+  pub fn new(domain: Name, upstream: Box<Upstream>) -> Self {
+    Forwarder{ domain: domain, upstream: upstream }
+  }
+
+  /// Forwards the queries to the name servers over UDP, in order, caching their answers. The
+  ///  records of the answers which can not be decoded are skipped, the rest are forwarded.
+  pub fn with_name_servers(domain: Name, name_servers: &[SocketAddr], cache: SharedCache) -> ClientResult<Self> {
+    let mut connections: Vec<UdpClientConnection> = Vec::with_capacity(name_servers.len());
+    for name_server in name_servers {
+      connections.push(try!(UdpClientConnection::new(*name_server)));
+    }
+
+    let mut client = Client::new(NameServerPool::new(connections, SelectionStrategy::Ordered));
+    client.set_shared_cache(Some(cache));
+    client.set_decode_mode(DecodeMode::Lenient);
+    Ok(Self::new(domain, Box::new(client)))
+  }
+
+  pub fn get_domain(&self) -> &Name { &self.domain }
+
+  /// Adds the answer of the upstream resolvers for the query to the response
+  pub fn forward(&self, query: &Query, response: &mut Message) {
+    answer_from(&*self.upstream, query, response)
+  }
+}
+
+/// Adds the answer of the upstream for the query to the response, which is answered with SERVFAIL
+///  if the upstream fails
+pub fn answer_from(upstream: &Upstream, query: &Query, response: &mut Message) {
+  response.recursion_available(true);
+
+  match upstream.lookup(query.clone()) {
+    Ok(answer) => {
+      debug!("resolved {} {:?} upstream: {:?}", query.get_name(), query.get_query_type(), answer.get_response_code());
+      response.response_code(answer.get_response_code());
+      for record in answer.get_answers() { response.add_answer(record.clone()); }
+      for record in answer.get_name_servers() { response.add_name_server(record.clone()); }
+      for record in answer.get_additional() { response.add_additional(record.clone()); }
+    },
+    Err(error) => {
+      warn!("could not resolve {} {:?} upstream: {}", query.get_name(), query.get_query_type(), error);
+      response.response_code(ResponseCode::ServFail);
+    },
+  }
+}
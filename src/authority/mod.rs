@@ -25,14 +25,30 @@ pub enum ZoneType { Master, Slave, Hint, Forward }
 
 mod authority;
 mod catalog;
+mod chaos;
+mod forwarder;
 mod persistence;
+mod query_policy;
+mod response_policy;
 mod rr_set;
+mod serial_policy;
+mod transfer;
+mod update_policy;
+mod validation;
 
 pub use self::authority::Authority;
 pub use self::authority::RrKey;
 pub use self::catalog::Catalog;
+pub use self::chaos::ChaosZones;
+pub use self::forwarder::{Forwarder, Upstream};
 pub use self::rr_set::RRSet;
 pub use self::persistence::Journal;
+pub use self::serial_policy::SerialPolicy;
+pub use self::query_policy::QueryPolicy;
+pub use self::response_policy::{PolicyAction, ResponsePolicyZone};
+pub use self::transfer::{MAX_TRANSFER_MESSAGE_SIZE, Transfer, TransferPolicy};
+pub use self::update_policy::{Grant, GrantName, Network, UpdatePolicy};
+pub use self::validation::ZoneProblem;
 
 #[cfg(test)]
 pub use self::authority::authority_tests;
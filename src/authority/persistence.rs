@@ -12,12 +12,13 @@ use rusqlite;
 use rusqlite::Connection;
 use rusqlite::SqliteError;
 
-use ::error::PersistenceErrorKind;
+use ::error::{DecodeResult, PersistenceErrorKind};
 use ::error::PersistenceResult;
-use ::rr::Record;
+use ::client::ZoneDiff;
+use ::rr::{Record, RData};
 use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
 
-const CURRENT_VERSION: i64 = 1;
+const CURRENT_VERSION: i64 = 2;
 
 /// The Journal is the audit log of all changes to a zone after initial creation.
 pub struct Journal {
@@ -31,8 +32,11 @@ impl Journal {
     Ok(Journal { conn: conn, version: try!(version) })
   }
 
+  /// Opens the journal at the path, creating it if necessary, and brings its schema up to date
   pub fn from_file(journal_file: &Path) -> PersistenceResult<Journal> {
-    Self::new(try!(Connection::open(journal_file)))
+    let mut journal = try!(Self::new(try!(Connection::open(journal_file))));
+    try!(journal.schema_up());
+    Ok(journal)
   }
 
   /// gets the current schema version of the journal
@@ -79,16 +83,191 @@ impl Journal {
     Ok(())
   }
 
-  /// Inserts a set of records into the Journal, a convenience method for insert_record
+  /// Inserts a set of records into the Journal, all or none of the records are inserted
   pub fn insert_records(&self, soa_serial: u32, records: &[Record]) -> PersistenceResult<()> {
-    // TODO: NEED TRANSACTION HERE
-    for record in records {
-      try!(self.insert_record(soa_serial, record));
+    self.transaction(|| {
+      for record in records {
+        try!(self.insert_record(soa_serial, record));
+      }
+
+      Ok(())
+    })
+  }
+
+  /// Runs the inserts of f in a single transaction, which is rolled back if f fails. This is also
+  ///  far faster than a transaction per insert when writing an entire zone.
+  pub fn transaction<F: FnOnce() -> PersistenceResult<()>>(&self, f: F) -> PersistenceResult<()> {
+    try!(self.conn.execute_batch("BEGIN TRANSACTION"));
+
+    match f() {
+      Ok(()) => {
+        try!(self.conn.execute_batch("COMMIT TRANSACTION"));
+        Ok(())
+      },
+      Err(err) => {
+        self.conn.execute_batch("ROLLBACK TRANSACTION").unwrap_or_else(|e| error!("could not roll back journal: {}", e));
+        Err(err)
+      },
     }
+  }
+
+  /// Writes the changes in the write-ahead log of the journal, if it is in WAL mode, into the
+  ///  journal file, e.g. before shutting down. The changes are already durable, this only means
+  ///  that the journal is a single file.
+  pub fn flush(&self) -> PersistenceResult<()> {
+    try!(self.conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)"));
+    Ok(())
+  }
+
+  /// Inserts the diff of an update to the zone, the history of diffs is used to answer IXFR
+  ///  requests
+  pub fn insert_diff(&self, diff: &ZoneDiff) -> PersistenceResult<()> {
+    assert!(self.version == CURRENT_VERSION, "schema version mismatch, schema_up() resolves this");
+
+    let old_soa: Vec<u8> = try!(Self::emit_records(&[&diff.from_soa]));
+    let deleted: Vec<u8> = try!(Self::emit_records(&diff.deleted.iter().collect::<Vec<&Record>>()));
+    let new_soa: Vec<u8> = try!(Self::emit_records(&[&diff.to_soa]));
+    let added: Vec<u8> = try!(Self::emit_records(&diff.added.iter().collect::<Vec<&Record>>()));
+
+    let timestamp = time::get_time();
+    let old_serial: i64 = soa_serial(&diff.from_soa) as i64;
+    let new_serial: i64 = soa_serial(&diff.to_soa) as i64;
+
+    let count = try!(self.conn.execute("INSERT
+                                          INTO diffs (old_serial, new_serial, timestamp, old_soa, deleted, new_soa, added)
+                                          VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                                          &[&old_serial, &new_serial, &timestamp, &old_soa, &deleted, &new_soa, &added]));
+    //
+    if count != 1 {
+      return Err(PersistenceErrorKind::WrongInsertCount(count, 1).into());
+    };
 
     Ok(())
   }
 
+  /// Removes the history of diffs, e.g. after the zone has been replaced, IXFR requests are then
+  ///  answered with the full zone.
+  pub fn clear_diffs(&self) -> PersistenceResult<()> {
+    assert!(self.version == CURRENT_VERSION, "schema version mismatch, schema_up() resolves this");
+
+    try!(self.conn.execute("DELETE FROM diffs", &[]));
+    Ok(())
+  }
+
+  /// The serial the newest diff takes the zone to, None if there are no diffs
+  pub fn select_last_serial(&self) -> PersistenceResult<Option<u32>> {
+    assert!(self.version == CURRENT_VERSION, "schema version mismatch, schema_up() resolves this");
+
+    let mut stmt = try!(self.conn.prepare("SELECT new_serial
+                                            FROM diffs
+                                            ORDER BY _rowid_ DESC
+                                            LIMIT 1"));
+
+    let serial_opt: Option<Result<i64, SqliteError>> = try!(stmt.query_map(&[], |row| row.get(0))).next();
+    match serial_opt {
+      Some(Ok(serial)) => Ok(Some(serial as u32)),
+      Some(Err(err)) => return Err(try!(Err(err))),
+      None => Ok(None),
+    }
+  }
+
+  /// Removes all of the records and diffs, once the zone has been written to its zone file at a
+  ///  checkpoint, which bounds the size of the journal. IXFR requests for the older serials are
+  ///  then answered with the full zone.
+  pub fn truncate(&self) -> PersistenceResult<()> {
+    assert!(self.version == CURRENT_VERSION, "schema version mismatch, schema_up() resolves this");
+
+    self.transaction(|| {
+      try!(self.conn.execute("DELETE FROM records", &[]));
+      try!(self.conn.execute("DELETE FROM diffs", &[]));
+      Ok(())
+    })
+  }
+
+  /// Selects the diffs which take the zone from the old serial to the new serial, in order.
+  ///
+  /// Returns None if the history of the journal does not go back to the old serial.
+  ///
+  /// # Arguments
+  ///
+  /// * `old_serial` - the serial of the zone the diffs start from, e.g. that of the secondary
+  /// * `new_serial` - the serial of the zone the diffs end at, the current serial of the zone
+  pub fn select_diffs(&self, old_serial: u32, new_serial: u32) -> PersistenceResult<Option<Vec<ZoneDiff>>> {
+    assert!(self.version == CURRENT_VERSION, "schema version mismatch, schema_up() resolves this");
+
+    // the history is followed back from the newest diff
+    let mut stmt = try!(self.conn.prepare("SELECT old_soa, deleted, new_soa, added
+                                            FROM diffs
+                                            ORDER BY _rowid_ DESC"));
+
+    let rows = try!(stmt.query_and_then(&[], |row| -> Result<ZoneDiff, SqliteError> {
+      let old_soa: Vec<u8> = try!(row.get_checked(0));
+      let deleted: Vec<u8> = try!(row.get_checked(1));
+      let new_soa: Vec<u8> = try!(row.get_checked(2));
+      let added: Vec<u8> = try!(row.get_checked(3));
+
+      let mut records: Vec<Vec<Record>> = Vec::with_capacity(4);
+      for bytes in &[old_soa, deleted, new_soa, added] {
+        match Self::read_records(bytes) {
+          Ok(read) => records.push(read),
+          Err(decode_error) => return Err(rusqlite::Error::InvalidParameterName(format!("could not decode: {}", decode_error))),
+        }
+      }
+
+      let added = records.pop().unwrap();
+      let new_soa = records.pop().unwrap().pop();
+      let deleted = records.pop().unwrap();
+      let old_soa = records.pop().unwrap().pop();
+
+      match (old_soa, new_soa) {
+        (Some(old_soa), Some(new_soa)) => Ok(ZoneDiff{ from_soa: old_soa, to_soa: new_soa, deleted: deleted, added: added }),
+        _ => Err(rusqlite::Error::InvalidParameterName("diff is missing its SOA".to_string())),
+      }
+    }));
+
+    let mut diffs: Vec<ZoneDiff> = Vec::new();
+    let mut serial = new_serial;
+    for diff in rows {
+      if serial == old_serial { break }
+
+      let diff = try!(diff);
+      if soa_serial(&diff.to_soa) != serial {
+        // the zone was changed without a diff, the history is broken
+        return Ok(None)
+      }
+
+      serial = soa_serial(&diff.from_soa);
+      diffs.push(diff);
+    }
+
+    if serial != old_serial { return Ok(None) }
+
+    diffs.reverse();
+    Ok(Some(diffs))
+  }
+
+  fn emit_records(records: &[&Record]) -> PersistenceResult<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(512);
+    {
+      let mut encoder = BinEncoder::new(&mut bytes);
+      for record in records {
+        try!(record.emit(&mut encoder));
+      }
+    }
+
+    Ok(bytes)
+  }
+
+  fn read_records(bytes: &[u8]) -> DecodeResult<Vec<Record>> {
+    let mut decoder = BinDecoder::new(bytes);
+    let mut records: Vec<Record> = Vec::new();
+    while decoder.len() > 0 {
+      records.push(try!(Record::read(&mut decoder)));
+    }
+
+    Ok(records)
+  }
+
   /// Selects a record from the given row_id.
   ///
   /// This allows for the entire set of records to be iterated through, by starting at 0, and
@@ -178,6 +357,7 @@ impl Journal {
       match self.version + 1 {
         0 => self.version = try!(self.init_up()),
         1 => self.version = try!(self.records_up()),
+        2 => self.version = try!(self.diffs_up()),
         _ => panic!("incorrect version somewhere"), // valid panic, non-recoverable state
       }
 
@@ -217,6 +397,25 @@ impl Journal {
 
     Ok(1)
   }
+
+  /// adds the diffs table, the history of changes to the zone between serials, for IXFR
+  fn diffs_up(&self) -> PersistenceResult<i64> {
+    try!(self.conn.execute("CREATE TABLE diffs (
+                                          old_serial     INTEGER NOT NULL,
+                                          new_serial     INTEGER NOT NULL,
+                                          timestamp      TEXT NOT NULL,
+                                          old_soa        BLOB NOT NULL,
+                                          deleted        BLOB NOT NULL,
+                                          new_soa        BLOB NOT NULL,
+                                          added          BLOB NOT NULL
+                                        )", &[]));
+
+    Ok(2)
+  }
+}
+
+fn soa_serial(soa: &Record) -> u32 {
+  if let &RData::SOA(ref soa) = soa.get_rdata() { soa.get_serial() } else { 0 }
 }
 
 pub struct JournalIter<'j> {
@@ -267,6 +466,35 @@ fn test_init_journal() {
   assert_eq!(Journal::select_schema_version(&journal.conn).unwrap(), CURRENT_VERSION);
 }
 
+#[test]
+fn test_flush() {
+  let (_, journal) = create_test_journal();
+
+  // not in WAL mode, there is nothing to checkpoint
+  journal.flush().unwrap();
+  assert_eq!(journal.iter().count(), 2);
+}
+
+#[test]
+fn test_truncate() {
+  use ::rr::{Name, RecordType};
+  use ::rr::rdata::SOA;
+
+  let (_, journal) = create_test_journal();
+  assert_eq!(journal.select_last_serial().unwrap(), None);
+
+  let soa = |serial: u32| -> Record {
+    Record::new().name(Name::new()).rr_type(RecordType::SOA).rdata(RData::SOA(SOA::new(Name::new(), Name::new(), serial, 1, 2, 3, 4))).clone()
+  };
+  journal.insert_diff(&ZoneDiff{ from_soa: soa(1), to_soa: soa(2), deleted: vec![], added: vec![] }).unwrap();
+  journal.insert_diff(&ZoneDiff{ from_soa: soa(2), to_soa: soa(3), deleted: vec![], added: vec![] }).unwrap();
+  assert_eq!(journal.select_last_serial().unwrap(), Some(3));
+
+  journal.truncate().unwrap();
+  assert_eq!(journal.iter().count(), 0);
+  assert_eq!(journal.select_last_serial().unwrap(), None);
+}
+
 #[cfg(test)]
 fn create_test_journal() -> (Record, Journal) {
   use std::net::Ipv4Addr;
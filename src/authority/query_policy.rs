@@ -0,0 +1,114 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::net::IpAddr;
+
+use ::authority::Network;
+
+/// Which clients may query, and which may have their queries answered recursively. Disallowed
+///  clients are answered with REFUSED.
+///
+/// A policy is applied by the `Catalog` to all queries, by an `Authority` to the queries for its
+///  zone, and by the `Server` to the queries received on a listener.
+///
+/// The default policy permits queries from any client, and recursion for no network clients. A
+///  client in one of the denied networks is never permitted. Once networks are allowed, the client
+///  must be in one of them. Requests which were not received from the network, i.e. without a
+///  source address, are always permitted to query, and to query recursively.
+#[derive(Default, Clone, Debug)]
+pub struct QueryPolicy {
+  allow: Vec<Network>,
+  deny: Vec<Network>,
+  allow_recursion: Vec<Network>,
+}
+
+impl QueryPolicy {
+  pub fn new() -> Self {
+    QueryPolicy::default()
+  }
+
+  /// Permits queries from the network, once any are allowed all others are refused
+  pub fn allow_network(&mut self, network: Network) -> &mut Self {
+    self.allow.push(network);
+    self
+  }
+
+  /// Refuses queries from the network, even if it is within an allowed network
+  pub fn deny_network(&mut self, network: Network) -> &mut Self {
+    self.deny.push(network);
+    self
+  }
+
+  /// Permits recursive queries from the network, which must also be permitted to query
+  pub fn allow_recursion_network(&mut self, network: Network) -> &mut Self {
+    self.allow_recursion.push(network);
+    self
+  }
+
+  pub fn get_allowed_networks(&self) -> &[Network] { &self.allow }
+  pub fn get_denied_networks(&self) -> &[Network] { &self.deny }
+  pub fn get_recursion_networks(&self) -> &[Network] { &self.allow_recursion }
+
+  /// true if the client may query
+  pub fn is_query_allowed(&self, src: Option<&IpAddr>) -> bool {
+    let src: &IpAddr = if let Some(src) = src { src } else { return true };
+    if self.deny.iter().any(|network| network.contains(src)) { return false }
+
+    self.allow.is_empty() || self.allow.iter().any(|network| network.contains(src))
+  }
+
+  /// true if the client may query recursively
+  pub fn is_recursion_allowed(&self, src: Option<&IpAddr>) -> bool {
+    let src: &IpAddr = if let Some(src) = src { src } else { return true };
+
+    self.is_query_allowed(Some(src)) && self.allow_recursion.iter().any(|network| network.contains(src))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::{IpAddr, Ipv4Addr};
+
+  use ::authority::Network;
+  use super::*;
+
+  #[test]
+  fn test_query_policy() {
+    let client = IpAddr::V4(Ipv4Addr::new(192,0,2,1));
+    let other = IpAddr::V4(Ipv4Addr::new(198,51,100,1));
+
+    let mut policy = QueryPolicy::new();
+    assert!(policy.is_query_allowed(Some(&client)));
+    assert!(policy.is_query_allowed(None));
+    assert!(!policy.is_recursion_allowed(Some(&client)));
+
+    policy.allow_network("192.0.2.0/24".parse::<Network>().unwrap());
+    assert!(policy.is_query_allowed(Some(&client)));
+    assert!(!policy.is_query_allowed(Some(&other)));
+    assert!(policy.is_query_allowed(None));
+
+    // deny takes precedence
+    policy.deny_network("192.0.2.1/32".parse::<Network>().unwrap());
+    assert!(!policy.is_query_allowed(Some(&client)));
+    assert!(policy.is_query_allowed(Some(&IpAddr::V4(Ipv4Addr::new(192,0,2,2)))));
+
+    // recursion needs the client to be allowed to query
+    policy.allow_recursion_network("0.0.0.0/0".parse::<Network>().unwrap());
+    assert!(!policy.is_recursion_allowed(Some(&client)));
+    assert!(!policy.is_recursion_allowed(Some(&other)));
+    assert!(policy.is_recursion_allowed(Some(&IpAddr::V4(Ipv4Addr::new(192,0,2,2)))));
+    assert!(policy.is_recursion_allowed(None));
+  }
+}
@@ -0,0 +1,361 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::rc::Rc;
+
+use ::authority::{Authority, Network};
+use ::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use ::rr::{Name, RData, Record, RecordType};
+
+/// the subdomain of the policy zone with the response IP triggers
+const RPZ_IP: &'static str = "rpz-ip";
+/// the subdomain of the policy zone with the name server name triggers
+const RPZ_NSDNAME: &'static str = "rpz-nsdname";
+/// the target of a CNAME which answers with the unmodified response
+const RPZ_PASSTHRU: &'static str = "rpz-passthru";
+
+/// What a rule of a `ResponsePolicyZone` does to the response
+#[derive(Debug, PartialEq, Clone)]
+pub enum PolicyAction {
+  /// answer that the name does not exist, `CNAME .`
+  NxDomain,
+  /// answer that the name has no records of the type, `CNAME *.`
+  NoData,
+  /// answer with the unmodified response, `CNAME rpz-passthru.`
+  Passthru,
+  /// answer with the records of the rule, owned by the query name, e.g. a `CNAME` to a walled
+  ///  garden or local `A` records
+  LocalData(Vec<Record>),
+}
+
+/// A Response Policy Zone (RPZ), a zone of rules which rewrite the responses of the server, e.g. to
+///  block the names of malware as a DNS firewall.
+///
+/// [DNS Response Policy Zones](https://tools.ietf.org/html/draft-vixie-dnsop-dns-rpz-00)
+///
+/// ```text
+/// bad.example.com.rpz.               CNAME .                   ; NXDOMAIN
+/// *.bad.example.com.rpz.             CNAME *.                  ; NODATA
+/// ads.example.com.rpz.               CNAME walled.garden.      ; forced CNAME
+/// www.example.net.rpz.               A     192.0.2.1           ; local data
+/// ok.example.com.rpz.                CNAME rpz-passthru.       ; unmodified
+/// 24.0.2.0.192.rpz-ip.rpz.           CNAME .                   ; answers in 192.0.2.0/24
+/// ns.bad.example.rpz-nsdname.rpz.    CNAME .                   ; delegated to ns.bad.example.
+/// ```
+///
+/// The rule is found by the query name, exactly or by a wildcard, then by the addresses of the A
+///  and AAAA records of the answers, the longest prefix matching, then by the names of the NS
+///  records of the response. The policy zone is loaded and may be refreshed like any other zone.
+///
+/// ```rust,ignore
+/// let policy_zone = Parser::parse_path(&path, Some(origin), ZoneType::Master, false).unwrap();
+/// catalog.add_response_policy(ResponsePolicyZone::new(policy_zone));
+/// ```
+pub struct ResponsePolicyZone {
+  authority: Authority,
+  ip_triggers: Vec<(Network, Name)>,
+}
+
+impl ResponsePolicyZone {
+  /// # Arguments
+  ///
+  /// * `authority` - the policy zone, its origin is stripped from the owners of the rules
+  pub fn new(authority: Authority) -> Self {
+    let rpz_ip = authority.get_origin().prepend_label(Rc::new(RPZ_IP.to_string()));
+    let ip_triggers: Vec<(Network, Name)> = authority.get_records().values()
+                                                     .map(|rr_set| rr_set.get_name())
+                                                     .filter(|name| rpz_ip.zone_of(name) && **name != rpz_ip)
+                                                     .filter_map(|name| Self::parse_ip_trigger(name, &rpz_ip).map(|network| (network, name.clone())))
+                                                     .collect();
+
+    ResponsePolicyZone{ authority: authority, ip_triggers: ip_triggers }
+  }
+
+  pub fn get_origin(&self) -> &Name { self.authority.get_origin() }
+  pub fn get_authority(&self) -> &Authority { &self.authority }
+
+  /// The action of the rule which matches the query or the response it would be answered with,
+  ///  None if no rule matches
+  pub fn check(&self, query: &Query, response: &Message) -> Option<PolicyAction> {
+    if let Some(rule) = self.name_rule(query.get_name(), None) {
+      return Some(self.action(&rule, query))
+    }
+
+    let mut ip_rule: Option<&(Network, Name)> = None;
+    for record in response.get_answers() {
+      let address: IpAddr = match *record.get_rdata() {
+        RData::A(address) => IpAddr::V4(address),
+        RData::AAAA(address) => IpAddr::V6(address),
+        _ => continue,
+      };
+
+      for trigger in self.ip_triggers.iter().filter(|&&(ref network, _)| network.contains(&address)) {
+        if ip_rule.map_or(true, |rule| rule.0.get_prefix_len() < trigger.0.get_prefix_len()) { ip_rule = Some(trigger); }
+      }
+    }
+
+    if let Some(&(_, ref rule)) = ip_rule {
+      return Some(self.action(rule, query))
+    }
+
+    for record in response.get_answers().iter().chain(response.get_name_servers()) {
+      if let RData::NS(ref ns) = *record.get_rdata() {
+        if let Some(rule) = self.name_rule(ns, Some(RPZ_NSDNAME)) {
+          return Some(self.action(&rule, query))
+        }
+      }
+    }
+
+    None
+  }
+
+  /// The response rewritten by the action, the policy zone's SOA allows negative answers to be
+  ///  cached. None for `PolicyAction::Passthru`.
+  pub fn rewrite(&self, action: PolicyAction, request: &Message, response: &Message) -> Option<Message> {
+    let mut rewritten: Message = Message::new();
+    rewritten.id(response.get_id());
+    rewritten.op_code(OpCode::Query);
+    rewritten.message_type(MessageType::Response);
+    rewritten.add_all_queries(response.get_queries());
+    rewritten.recursion_desired(request.is_recursion_desired());
+    rewritten.recursion_available(response.is_recursion_available());
+
+    let response_code = match action {
+      PolicyAction::Passthru => return None,
+      PolicyAction::NxDomain => ResponseCode::NXDomain,
+      PolicyAction::NoData => ResponseCode::NoError,
+      PolicyAction::LocalData(records) => {
+        for record in records {
+          rewritten.add_answer(record);
+        }
+        ResponseCode::NoError
+      },
+    };
+
+    rewritten.response_code(response_code);
+    if rewritten.get_answers().is_empty() {
+      for soa in self.authority.get_soa_negative(false) {
+        rewritten.add_name_server(soa);
+      }
+    }
+
+    Some(rewritten)
+  }
+
+  /// the owner of the rule for the name, exactly or by the closest wildcard, within the
+  ///  subdomain of the policy zone
+  fn name_rule(&self, name: &Name, subdomain: Option<&str>) -> Option<Name> {
+    let mut suffix = self.authority.get_origin().clone();
+    if let Some(subdomain) = subdomain { suffix = suffix.prepend_label(Rc::new(subdomain.to_string())); }
+
+    let trigger = |name: &Name| -> Name {
+      let mut trigger = name.clone();
+      trigger.append(&suffix);
+      trigger
+    };
+
+    let exact = trigger(name);
+    if self.has_rule(&exact) { return Some(exact) }
+
+    let mut parent = name.clone();
+    while !parent.is_root() {
+      parent = parent.base_name();
+      let wildcard = trigger(&parent.prepend_label(Rc::new("*".to_string())));
+      if self.has_rule(&wildcard) { return Some(wildcard) }
+    }
+
+    None
+  }
+
+  /// true if there are records at the name, empty non-terminals are not rules
+  fn has_rule(&self, name: &Name) -> bool {
+    !self.authority.lookup(name, RecordType::ANY, false).is_empty()
+  }
+
+  /// the action of the records of the rule for the query
+  fn action(&self, rule: &Name, query: &Query) -> PolicyAction {
+    if let Some(cname) = self.authority.lookup(rule, RecordType::CNAME, false).first() {
+      let target: &Name = if let RData::CNAME(ref target) = *cname.get_rdata() { target } else { return PolicyAction::Passthru };
+
+      if target.is_root() { return PolicyAction::NxDomain }
+      if target.label_count() == 1 && target.is_wildcard() { return PolicyAction::NoData }
+      if target.label_count() == 1 && target[0].to_lowercase() == RPZ_PASSTHRU { return PolicyAction::Passthru }
+
+      // a wildcard target is the query name in the target domain
+      let target: Name = if target.is_wildcard() {
+        let mut target_name = query.get_name().clone();
+        target_name.append(&target.base_name());
+        target_name
+      } else {
+        target.clone()
+      };
+
+      let mut cname: Record = (*cname).clone();
+      cname.name(query.get_name().clone()).rdata(RData::CNAME(target));
+      return PolicyAction::LocalData(vec![cname])
+    }
+
+    let records: Vec<Record> = self.authority.lookup(rule, query.get_query_type(), false)
+                                             .into_iter()
+                                             .map(|record| { let mut record = record.clone(); record.name(query.get_name().clone()); record })
+                                             .collect();
+    if records.is_empty() { PolicyAction::NoData } else { PolicyAction::LocalData(records) }
+  }
+
+  /// the network of the owner of a response IP trigger, e.g. `24.0.2.0.192.rpz-ip` is
+  ///  192.0.2.0/24, and `48.zz.db8.2001.rpz-ip` is 2001:db8::/48
+  fn parse_ip_trigger(name: &Name, rpz_ip: &Name) -> Option<Network> {
+    let num_labels = name.label_count() - rpz_ip.label_count();
+    let labels: Vec<String> = (0..num_labels).map(|i| name[i].to_lowercase()).collect();
+    let (prefix_len, address) = match labels.split_first() {
+      Some((prefix_len, address)) => (prefix_len, address),
+      None => return None,
+    };
+
+    let prefix_len: u8 = if let Ok(prefix_len) = prefix_len.parse() { prefix_len } else { return None };
+    let address: Vec<&str> = address.iter().rev().map(|label| label.as_str()).collect();
+
+    let octets: Vec<u8> = address.iter().filter_map(|octet| octet.parse().ok()).collect();
+    if address.len() == 4 && octets.len() == 4 {
+      return Some(Network::new(IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])), prefix_len))
+    }
+
+    // zz is the longest run of zero words, as :: in the usual notation
+    let mut words: Vec<u16> = Vec::with_capacity(8);
+    for word in &address {
+      if *word == "zz" {
+        if address.len() > 8 { return None }
+        for _ in 0..(9 - address.len()) { words.push(0); }
+      } else if let Ok(word) = u16::from_str_radix(word, 16) {
+        words.push(word);
+      } else {
+        return None
+      }
+    }
+
+    if words.len() != 8 { return None }
+    Some(Network::new(IpAddr::V6(Ipv6Addr::new(words[0], words[1], words[2], words[3], words[4], words[5], words[6], words[7])), prefix_len))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+  use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+  use ::authority::{Authority, ZoneType};
+  use ::op::{Message, Query, ResponseCode};
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::rr::rdata::SOA;
+  use super::*;
+
+  fn create_policy_zone() -> ResponsePolicyZone {
+    let origin = Name::parse("rpz.", None).unwrap();
+    let mut authority = Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Master, false);
+    authority.upsert(Record::new().name(origin.clone()).ttl(3600).rr_type(RecordType::SOA).dns_class(DNSClass::IN).rdata(RData::SOA(SOA::new(Name::parse("localhost.", None).unwrap(), Name::parse("root.localhost.", None).unwrap(), 1, 3600, 600, 86400, 60))).clone(), 1);
+
+    let cname = |owner: &str, target: &str| Record::new().name(Name::parse(owner, Some(&origin)).unwrap()).ttl(60).rr_type(RecordType::CNAME).dns_class(DNSClass::IN).rdata(RData::CNAME(Name::parse(target, None).unwrap())).clone();
+    authority.upsert(cname("bad.example.com", "."), 1);
+    authority.upsert(cname("*.nodata.example.com", "*."), 1);
+    authority.upsert(cname("ads.example.com", "walled.garden."), 1);
+    authority.upsert(cname("ok.ads.example.com", "rpz-passthru."), 1);
+    authority.upsert(cname("24.0.2.0.192.rpz-ip", "."), 1);
+    authority.upsert(cname("48.zz.db8.2001.rpz-ip", "*."), 1);
+    authority.upsert(cname("ns.bad.example.rpz-nsdname", "."), 1);
+    authority.upsert(Record::new().name(Name::parse("www.example.net", Some(&origin)).unwrap()).ttl(60).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,0,0,1))).clone(), 1);
+
+    ResponsePolicyZone::new(authority)
+  }
+
+  fn query(name: &str, query_type: RecordType) -> Query {
+    let mut query = Query::new();
+    query.name(Name::parse(name, None).unwrap()).query_type(query_type);
+    query
+  }
+
+  #[test]
+  fn test_parse_ip_trigger() {
+    let rpz_ip = Name::parse("rpz-ip.rpz.", None).unwrap();
+    let parse = |name: &str| ResponsePolicyZone::parse_ip_trigger(&Name::parse(name, None).unwrap(), &rpz_ip);
+
+    assert_eq!(parse("24.0.2.0.192.rpz-ip.rpz.").map(|n| (n.get_address(), n.get_prefix_len())),
+               Some((IpAddr::V4(Ipv4Addr::new(192,0,2,0)), 24)));
+    assert_eq!(parse("48.zz.db8.2001.rpz-ip.rpz.").map(|n| (n.get_address(), n.get_prefix_len())),
+               Some((IpAddr::V6(Ipv6Addr::new(0x2001,0xdb8,0,0,0,0,0,0)), 48)));
+    assert_eq!(parse("128.1.zz.db8.2001.rpz-ip.rpz.").map(|n| (n.get_address(), n.get_prefix_len())),
+               Some((IpAddr::V6(Ipv6Addr::new(0x2001,0xdb8,0,0,0,0,0,1)), 128)));
+    assert!(parse("24.0.2.300.rpz-ip.rpz.").is_none());
+    assert!(parse("bad.0.2.0.192.rpz-ip.rpz.").is_none());
+  }
+
+  #[test]
+  fn test_check() {
+    let rpz = create_policy_zone();
+    let empty = Message::new();
+
+    assert_eq!(rpz.check(&query("bad.example.com.", RecordType::A), &empty), Some(PolicyAction::NxDomain));
+    assert_eq!(rpz.check(&query("www.nodata.example.com.", RecordType::A), &empty), Some(PolicyAction::NoData));
+    assert_eq!(rpz.check(&query("nodata.example.com.", RecordType::A), &empty), None);
+    assert_eq!(rpz.check(&query("ok.ads.example.com.", RecordType::A), &empty), Some(PolicyAction::Passthru));
+    assert_eq!(rpz.check(&query("www.example.org.", RecordType::A), &empty), None);
+
+    match rpz.check(&query("ads.example.com.", RecordType::A), &empty) {
+      Some(PolicyAction::LocalData(records)) => {
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].get_name(), &Name::parse("ads.example.com.", None).unwrap());
+        assert_eq!(records[0].get_rdata(), &RData::CNAME(Name::parse("walled.garden.", None).unwrap()));
+      },
+      action => panic!("unexpected action: {:?}", action),
+    }
+
+    match rpz.check(&query("www.example.net.", RecordType::A), &empty) {
+      Some(PolicyAction::LocalData(records)) => assert_eq!(records[0].get_rdata(), &RData::A(Ipv4Addr::new(10,0,0,1))),
+      action => panic!("unexpected action: {:?}", action),
+    }
+    assert_eq!(rpz.check(&query("www.example.net.", RecordType::AAAA), &empty), Some(PolicyAction::NoData));
+
+    // by the addresses of the answers
+    let name = Name::parse("www.example.org.", None).unwrap();
+    let mut response = Message::new();
+    response.add_answer(Record::new().name(name.clone()).ttl(60).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(192,0,2,1))).clone());
+    assert_eq!(rpz.check(&query("www.example.org.", RecordType::A), &response), Some(PolicyAction::NxDomain));
+
+    let mut response = Message::new();
+    response.add_answer(Record::new().name(name.clone()).ttl(60).rr_type(RecordType::AAAA).dns_class(DNSClass::IN).rdata(RData::AAAA(Ipv6Addr::new(0x2001,0xdb8,0,1,0,0,0,1))).clone());
+    assert_eq!(rpz.check(&query("www.example.org.", RecordType::AAAA), &response), Some(PolicyAction::NoData));
+
+    // by the names of the name servers
+    let mut response = Message::new();
+    response.add_name_server(Record::new().name(Name::parse("example.org.", None).unwrap()).ttl(60).rr_type(RecordType::NS).dns_class(DNSClass::IN).rdata(RData::NS(Name::parse("ns.bad.example.", None).unwrap())).clone());
+    assert_eq!(rpz.check(&query("www.example.org.", RecordType::A), &response), Some(PolicyAction::NxDomain));
+  }
+
+  #[test]
+  fn test_rewrite() {
+    let rpz = create_policy_zone();
+    let mut request = Message::new();
+    request.id(10).recursion_desired(true).add_query(query("bad.example.com.", RecordType::A));
+
+    let rewritten = rpz.rewrite(PolicyAction::NxDomain, &request, &request).unwrap();
+    assert_eq!(rewritten.get_id(), 10);
+    assert_eq!(rewritten.get_response_code(), ResponseCode::NXDomain);
+    assert!(rewritten.is_recursion_desired());
+    assert_eq!(rewritten.get_queries().len(), 1);
+    assert_eq!(rewritten.get_name_servers()[0].get_rr_type(), RecordType::SOA);
+
+    assert!(rpz.rewrite(PolicyAction::Passthru, &request, &request).is_none());
+  }
+}
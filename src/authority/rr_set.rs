@@ -4,9 +4,11 @@
 // http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
+use  std::cmp::Ordering;
 use  std::slice::Iter;
 
 use ::rr::{Name, Record, RecordType, RData};
+use ::rr::rdata::compare_serial;
 
 /// Set of resource records associated to a name and type
 #[derive(Debug, PartialEq)]
@@ -157,7 +159,8 @@ impl RRSet {
           match soa_record.get_rdata() {
             &RData::SOA(ref existing_soa) => {
               if let &RData::SOA(ref new_soa) = record.get_rdata() {
-                if new_soa.get_serial() <= existing_soa.get_serial() {
+                // the serials wrap around, see RFC 1982
+                if compare_serial(new_soa.get_serial(), existing_soa.get_serial()) != Some(Ordering::Greater) {
                   info!("update ignored serial out of data: {:?} <= {:?}", new_soa, existing_soa);
                   return false;
                 }
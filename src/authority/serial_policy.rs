@@ -0,0 +1,84 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::cmp::Ordering;
+
+use chrono::{Datelike, DateTime, UTC};
+
+use ::rr::rdata::compare_serial;
+
+/// How the serial of the SOA of a zone is incremented when the zone changes, e.g. after a dynamic
+///  update and the re-signing of the zone.
+///
+/// The next serial is always greater than the current one by the serial arithmetic of RFC 1982,
+///  if the serial of the policy is not, e.g. as a date based serial was already incremented 100
+///  times on the same day, or a zone file had a greater serial, the current serial is incremented
+///  by one instead.
+#[derive(RustcDecodable, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SerialPolicy {
+  /// the serial is incremented by one, wrapping around to 0
+  Increment,
+  /// the serial is the date in the form YYYYMMDDnn, where nn counts the changes of the day
+  Date,
+  /// the serial is the unix timestamp in seconds
+  UnixTime,
+}
+
+impl SerialPolicy {
+  /// The serial following the current serial at the time `now`
+  pub fn next_serial(&self, serial: u32, now: DateTime<UTC>) -> u32 {
+    let next = match *self {
+      SerialPolicy::Increment => return serial.wrapping_add(1),
+      SerialPolicy::Date => (now.year() as u32 * 10_000 + now.month() * 100 + now.day()) * 100,
+      SerialPolicy::UnixTime => now.timestamp() as u32,
+    };
+
+    if compare_serial(next, serial) == Some(Ordering::Greater) { next } else { serial.wrapping_add(1) }
+  }
+}
+
+impl Default for SerialPolicy {
+  fn default() -> Self {
+    SerialPolicy::Increment
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::{TimeZone, UTC};
+
+  use super::*;
+
+  #[test]
+  fn test_next_serial() {
+    let now = UTC.ymd(2016, 8, 20).and_hms(10, 0, 0);
+
+    assert_eq!(SerialPolicy::Increment.next_serial(7, now), 8);
+    assert_eq!(SerialPolicy::Increment.next_serial(u32::max_value(), now), 0);
+
+    assert_eq!(SerialPolicy::Date.next_serial(7, now), 2016082000);
+    assert_eq!(SerialPolicy::Date.next_serial(2016082000, now), 2016082001);
+    assert_eq!(SerialPolicy::Date.next_serial(2016082099, now), 2016082100);
+    assert_eq!(SerialPolicy::Date.next_serial(2016081905, now), 2016082000);
+
+    assert_eq!(SerialPolicy::UnixTime.next_serial(7, now), 1471687200);
+    assert_eq!(SerialPolicy::UnixTime.next_serial(1471687200, now), 1471687201);
+
+    // a serial which is already greater is only incremented, serials less than 2^31 before the
+    //  next serial, wrapping around, are lesser
+    assert_eq!(SerialPolicy::UnixTime.next_serial(3_500_000_000, now), 3_500_000_001);
+    assert_eq!(SerialPolicy::Date.next_serial(u32::max_value(), now), 2016082000);
+  }
+}
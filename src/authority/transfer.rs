@@ -0,0 +1,247 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::net::IpAddr;
+
+use chrono::UTC;
+
+use ::authority::Network;
+use ::error::*;
+use ::op::Message;
+use ::rr::dnssec::TsigKey;
+use ::serialize::binary::{BinEncoder, BinSerializable};
+
+/// The largest message of a zone transfer, leaving room for the TSIG below the 65535 byte limit
+///  of TCP messages
+pub const MAX_TRANSFER_MESSAGE_SIZE: usize = 16384;
+
+/// Which clients may transfer a zone with AXFR, checked before the zone is transferred
+///
+/// The default policy permits any client. Once networks or TSIG keys are configured, the client
+///  must either be in one of the networks, or sign the request with one of the keys.
+#[derive(Default)]
+pub struct TransferPolicy {
+  networks: Vec<Network>,
+  tsig_keys: Vec<TsigKey>,
+}
+
+impl TransferPolicy {
+  pub fn new() -> Self {
+    TransferPolicy::default()
+  }
+
+  /// Permits transfers to the network
+  pub fn allow_network(&mut self, network: Network) -> &mut Self {
+    self.networks.push(network);
+    self
+  }
+
+  /// Permits transfers which are signed with the TSIG key, the responses are signed with it
+  pub fn tsig_key(&mut self, key: TsigKey) -> &mut Self {
+    self.tsig_keys.push(key);
+    self
+  }
+
+  pub fn get_tsig_keys(&self) -> &[TsigKey] { &self.tsig_keys }
+
+  /// true if no networks or keys are configured, i.e. any client may transfer the zone
+  pub fn is_open(&self) -> bool {
+    self.networks.is_empty() && self.tsig_keys.is_empty()
+  }
+
+  /// true if the address is in one of the permitted networks
+  pub fn is_source_allowed(&self, src: Option<&IpAddr>) -> bool {
+    match src {
+      Some(src) => self.networks.iter().any(|network| network.contains(src)),
+      None => false,
+    }
+  }
+}
+
+/// The response to an AXFR request, see `Catalog::transfer()`
+///
+/// Over TCP the zone is sent as a stream of messages, each at most `MAX_TRANSFER_MESSAGE_SIZE`,
+///  with the first and last records being the SOA.
+///
+/// [RFC 5936](https://tools.ietf.org/html/rfc5936), DNS Zone Transfer Protocol (AXFR), June 2010
+///
+/// ```text
+/// 2.2.  AXFR Response
+///
+///    The AXFR response will consist of 0 or more messages.
+///    ...
+///    An AXFR response that is transferring the zone's contents
+///    will consist of a series (which could be a series of
+///    length 1) of DNS messages.
+/// ```
+pub struct Transfer {
+  response: Message,
+  signer: Option<(TsigKey, Vec<u8>)>,
+}
+
+impl Transfer {
+  /// # Arguments
+  ///
+  /// * `response` - the response with the entire zone in its answers, or an error
+  /// * `signer` - the key the request was signed with, and the MAC of the request
+  pub fn new(response: Message, signer: Option<(TsigKey, Vec<u8>)>) -> Self {
+    Transfer{ response: response, signer: signer }
+  }
+
+  /// The response as a single message, e.g. for UDP where it will be truncated
+  pub fn get_response(&self) -> &Message { &self.response }
+  pub fn into_response(self) -> Message { self.response }
+  pub fn is_signed(&self) -> bool { self.signer.is_some() }
+
+  /// Splits the response into messages of at most max_size bytes, each with the question and as
+  ///  many of the answers as fit. A single record larger than max_size is sent on its own.
+  pub fn to_messages(&self, max_size: usize) -> EncodeResult<Vec<Message>> {
+    let header_len = try!(Self::encoded_len(&Self::next_message(&self.response)));
+
+    let mut messages: Vec<Message> = Vec::new();
+    let mut message = Self::next_message(&self.response);
+    let mut message_len = header_len;
+    for record in self.response.get_answers() {
+      let record_len = {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+          let mut encoder = BinEncoder::new(&mut buf);
+          try!(record.emit(&mut encoder));
+        }
+        buf.len()
+      };
+
+      // names are compressed in the message, so the sum of the records is an upper bound
+      if !message.get_answers().is_empty() && message_len + record_len > max_size {
+        messages.push(message);
+        message = Self::next_message(&self.response);
+        message_len = header_len;
+      }
+
+      message.add_answer(record.clone());
+      message_len += record_len;
+    }
+
+    messages.push(message);
+    Ok(messages)
+  }
+
+  /// Serializes the messages of the transfer, signing each of them if the request was signed
+  pub fn to_bytes(&self, max_size: usize) -> EncodeResult<Vec<Vec<u8>>> {
+    let mut prior_mac: Option<Vec<u8>> = self.signer.as_ref().map(|&(_, ref request_mac)| request_mac.clone());
+    let mut first = true;
+    let now = UTC::now().timestamp() as u64;
+
+    let mut buffers: Vec<Vec<u8>> = Vec::new();
+    for message in try!(self.to_messages(max_size)) {
+      let mut buf: Vec<u8> = Vec::with_capacity(max_size);
+      {
+        let mut encoder = BinEncoder::new(&mut buf);
+        try!(message.emit(&mut encoder));
+      }
+
+      if let Some((ref key, _)) = self.signer {
+        let mac = prior_mac.take().expect("the request was signed");
+        let (signed, mac) = if first { try!(key.sign(&buf, Some(&mac), now)) } else { try!(key.sign_subsequent(&buf, &mac, now)) };
+
+        buf = signed;
+        prior_mac = Some(mac);
+      }
+
+      first = false;
+      buffers.push(buf);
+    }
+
+    Ok(buffers)
+  }
+
+  /// a message with the header and question of the response
+  fn next_message(response: &Message) -> Message {
+    let mut message = Message::new();
+    message.id(response.get_id())
+           .message_type(response.get_message_type())
+           .op_code(response.get_op_code())
+           .authoritative(response.is_authoritative())
+           .response_code(response.get_response_code())
+           .add_all_queries(response.get_queries());
+    message
+  }
+
+  fn encoded_len(message: &Message) -> EncodeResult<usize> {
+    let mut buf: Vec<u8> = Vec::new();
+    {
+      let mut encoder = BinEncoder::new(&mut buf);
+      try!(message.emit(&mut encoder));
+    }
+    Ok(buf.len())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::{IpAddr, Ipv4Addr};
+
+  use ::authority::Network;
+  use ::authority::authority_tests::create_example;
+  use ::op::{Message, MessageType, OpCode, Query, ResponseCode};
+  use ::rr::{Name, Record, RecordType};
+  use ::rr::dnssec::{TsigAlgorithm, TsigKey, split_tsig};
+  use super::*;
+
+  #[test]
+  fn test_transfer_policy() {
+    let mut policy = TransferPolicy::new();
+    assert!(policy.is_open());
+
+    policy.allow_network("192.0.2.0/24".parse::<Network>().unwrap());
+    assert!(!policy.is_open());
+    assert!(policy.is_source_allowed(Some(&IpAddr::V4(Ipv4Addr::new(192,0,2,1)))));
+    assert!(!policy.is_source_allowed(Some(&IpAddr::V4(Ipv4Addr::new(192,0,3,1)))));
+    assert!(!policy.is_source_allowed(None));
+  }
+
+  #[test]
+  fn test_to_messages() {
+    let authority = create_example();
+    let mut query = Query::new();
+    query.name(authority.get_origin().clone()).query_type(RecordType::AXFR);
+
+    let mut response = Message::new();
+    response.id(10).message_type(MessageType::Response).op_code(OpCode::Query).response_code(ResponseCode::NoError).add_query(query);
+    let records: Vec<Record> = authority.search(&response.get_queries()[0], false).into_iter().cloned().collect();
+    for record in records.iter() {
+      response.add_answer(record.clone());
+    }
+
+    let transfer = Transfer::new(response, None);
+    assert_eq!(transfer.to_messages(MAX_TRANSFER_MESSAGE_SIZE).unwrap().len(), 1);
+
+    // small messages split the zone, but keep every record in order
+    let messages = transfer.to_messages(128).unwrap();
+    assert!(messages.len() > 1);
+    assert!(messages.iter().all(|message| message.get_id() == 10 && message.get_queries().len() == 1));
+    let streamed: Vec<Record> = messages.iter().flat_map(|message| message.get_answers().iter().cloned()).collect();
+    assert_eq!(streamed, records);
+
+    assert_eq!(transfer.to_bytes(128).unwrap().len(), messages.len());
+
+    // each message is signed
+    let key = TsigKey::new(Name::parse("xfr-key.", None).unwrap(), TsigAlgorithm::HmacSha256, b"0123456789abcdef".to_vec());
+    let transfer = Transfer::new(transfer.into_response(), Some((key, vec![1, 2, 3])));
+    for buf in transfer.to_bytes(128).unwrap() {
+      assert!(split_tsig(&buf).unwrap().is_some());
+    }
+  }
+}
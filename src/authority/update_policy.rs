@@ -0,0 +1,312 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ::error::*;
+use ::rr::{Name, Record, RecordType};
+use ::rr::dnssec::TsigKey;
+
+/// A network in CIDR notation, e.g. `192.0.2.0/24` or `2001:db8::/32`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Network {
+  address: IpAddr,
+  prefix_len: u8,
+}
+
+impl Network {
+  /// # Arguments
+  ///
+  /// * `address` - the address of the network, bits past the prefix are ignored
+  /// * `prefix_len` - the number of leading bits of the address which must match, at most 32 for
+  ///                  IPv4 and 128 for IPv6
+  pub fn new(address: IpAddr, prefix_len: u8) -> Self {
+    let max_len = match address { IpAddr::V4(..) => 32, IpAddr::V6(..) => 128 };
+    Network{ address: address, prefix_len: if prefix_len > max_len { max_len } else { prefix_len } }
+  }
+
+  pub fn get_address(&self) -> IpAddr { self.address }
+  pub fn get_prefix_len(&self) -> u8 { self.prefix_len }
+
+  /// true if the address is in this network, IPv4 addresses are never in IPv6 networks
+  pub fn contains(&self, address: &IpAddr) -> bool {
+    let (network, address): (Vec<u8>, Vec<u8>) = match (self.address, *address) {
+      (IpAddr::V4(network), IpAddr::V4(address)) => (network.octets().to_vec(), address.octets().to_vec()),
+      (IpAddr::V6(network), IpAddr::V6(address)) => (network.octets().to_vec(), address.octets().to_vec()),
+      _ => return false,
+    };
+
+    let full_bytes = self.prefix_len as usize / 8;
+    let remaining_bits = self.prefix_len % 8;
+
+    if network[..full_bytes] != address[..full_bytes] { return false }
+    if remaining_bits == 0 { return true }
+
+    let mask: u8 = 0xFF << (8 - remaining_bits);
+    network[full_bytes] & mask == address[full_bytes] & mask
+  }
+}
+
+impl FromStr for Network {
+  type Err = ParseError;
+
+  /// parses `address/prefix_len`, a bare address is a network of only that address
+  fn from_str(s: &str) -> ParseResult<Self> {
+    let mut parts = s.splitn(2, '/');
+    let address: IpAddr = try!(parts.next().unwrap_or("").parse().map_err(|_| ParseErrorKind::Msg(format!("bad network address: {}", s))));
+
+    let prefix_len: u8 = match parts.next() {
+      Some(prefix_len) => try!(prefix_len.parse()),
+      None => match address { IpAddr::V4(..) => 32, IpAddr::V6(..) => 128 },
+    };
+
+    let max_len = match address { IpAddr::V4(..) => 32, IpAddr::V6(..) => 128 };
+    if prefix_len > max_len {
+      return Err(ParseErrorKind::Msg(format!("prefix length is longer than the address: {}", s)).into())
+    }
+
+    Ok(Network::new(address, prefix_len))
+  }
+}
+
+/// The names a `Grant` permits to be updated
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GrantName {
+  /// only the name of the signing key itself, e.g. a host updating its own address
+  SelfName,
+  /// exactly this name
+  Name(Name),
+  /// this name and any name below it
+  Subdomain(Name),
+}
+
+/// Permits the holder of a key to update records of some names and types
+///
+/// ```rust,ignore
+/// // the key host1.example.com. may only update its own A and AAAA records
+/// Grant::new(host1, GrantName::SelfName, vec![RecordType::A, RecordType::AAAA]);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Grant {
+  signer: Name,
+  name: GrantName,
+  types: Vec<RecordType>,
+}
+
+impl Grant {
+  /// # Arguments
+  ///
+  /// * `signer` - the name of the TSIG or SIG(0) key which signed the update
+  /// * `name` - the names the key may update
+  /// * `types` - the types the key may update, all types if empty
+  pub fn new(signer: Name, name: GrantName, types: Vec<RecordType>) -> Self {
+    Grant{ signer: signer, name: name, types: types }
+  }
+
+  pub fn get_signer(&self) -> &Name { &self.signer }
+  pub fn get_name(&self) -> &GrantName { &self.name }
+  pub fn get_types(&self) -> &[RecordType] { &self.types }
+
+  /// true if this grant permits the signer to make the update
+  pub fn permits(&self, signer: &Name, update: &Record) -> bool {
+    if &self.signer != signer { return false }
+
+    let name_permitted = match self.name {
+      GrantName::SelfName => update.get_name() == signer,
+      GrantName::Name(ref name) => update.get_name() == name,
+      GrantName::Subdomain(ref name) => name.zone_of(update.get_name()),
+    };
+
+    // deleting all the rrsets of a name requires a grant of all types
+    let type_permitted = self.types.is_empty() ||
+                         (update.get_rr_type() != RecordType::ANY && self.types.contains(&update.get_rr_type()));
+
+    name_permitted && type_permitted
+  }
+}
+
+/// The authorization policy for dynamic updates of a zone, checked before any update is applied
+///
+/// The default policy requires updates to be signed with SIG(0) by a KEY in the zone, and then
+///  permits any update. Each part of the policy only further restricts updates:
+///
+/// * `networks` - the update must come from one of these networks, any source if empty
+/// * `tsig_keys` - updates may also be signed with these TSIG keys
+/// * `key_names` - the update must be signed by one of these keys, any verified key if empty
+/// * `grants` - each record of the update must be permitted by a grant for its signer, any
+///              record if empty
+///
+/// Unsigned updates are refused unless `allow_unsigned` is set, in which case they are restricted
+///  by the networks. Grants are of signers, so once any grant is added unsigned updates are
+///  refused, even if `allow_unsigned` is set.
+#[derive(Default)]
+pub struct UpdatePolicy {
+  networks: Vec<Network>,
+  allow_unsigned: bool,
+  tsig_keys: Vec<TsigKey>,
+  key_names: Vec<Name>,
+  grants: Vec<Grant>,
+}
+
+impl UpdatePolicy {
+  pub fn new() -> Self {
+    UpdatePolicy::default()
+  }
+
+  /// Permits updates from the network
+  pub fn allow_network(&mut self, network: Network) -> &mut Self {
+    self.networks.push(network);
+    self
+  }
+
+  /// If true, updates which are not signed are accepted from the permitted networks
+  pub fn allow_unsigned(&mut self, allow_unsigned: bool) -> &mut Self {
+    self.allow_unsigned = allow_unsigned;
+    self
+  }
+
+  /// Accepts updates signed with the TSIG key
+  pub fn tsig_key(&mut self, key: TsigKey) -> &mut Self {
+    self.tsig_keys.push(key);
+    self
+  }
+
+  /// Requires updates to be signed by the named TSIG or SIG(0) key, or one of the other required keys
+  pub fn require_key_name(&mut self, key_name: Name) -> &mut Self {
+    self.key_names.push(key_name);
+    self
+  }
+
+  /// Adds a grant, once any are added each record of an update must be permitted by one, and
+  ///  unsigned updates are refused
+  pub fn grant(&mut self, grant: Grant) -> &mut Self {
+    self.grants.push(grant);
+    self
+  }
+
+  pub fn get_tsig_keys(&self) -> &[TsigKey] { &self.tsig_keys }
+  pub fn is_unsigned_allowed(&self) -> bool { self.allow_unsigned }
+
+  /// true if updates are permitted from the address, if the address is not known updates are
+  ///  only permitted when no networks are configured
+  pub fn is_source_allowed(&self, src: Option<&IpAddr>) -> bool {
+    if self.networks.is_empty() { return true }
+
+    match src {
+      Some(src) => self.networks.iter().any(|network| network.contains(src)),
+      None => false,
+    }
+  }
+
+  /// true if the key, which verified the update, is one of the required keys
+  pub fn is_key_allowed(&self, key_name: &Name) -> bool {
+    self.key_names.is_empty() || self.key_names.contains(key_name)
+  }
+
+  /// true if the signer may make each of the updates, `None` if the update was unsigned
+  pub fn is_update_allowed(&self, signer: Option<&Name>, updates: &[Record]) -> bool {
+    if self.grants.is_empty() { return true }
+
+    match signer {
+      Some(signer) => updates.iter().all(|update| self.grants.iter().any(|grant| grant.permits(signer, update))),
+      None => false,
+    }
+  }
+}
+
+#[test]
+fn test_network() {
+  use std::net::{Ipv4Addr, Ipv6Addr};
+
+  let network: Network = "192.0.2.0/24".parse().unwrap();
+  assert!(network.contains(&IpAddr::V4(Ipv4Addr::new(192,0,2,1))));
+  assert!(!network.contains(&IpAddr::V4(Ipv4Addr::new(192,0,3,1))));
+  assert!(!network.contains(&IpAddr::V6(Ipv6Addr::new(0,0,0,0,0,0xFFFF,0xC000,0x0201))));
+
+  let network: Network = "10.0.0.0/9".parse().unwrap();
+  assert!(network.contains(&IpAddr::V4(Ipv4Addr::new(10,127,0,1))));
+  assert!(!network.contains(&IpAddr::V4(Ipv4Addr::new(10,128,0,1))));
+
+  let network: Network = "2001:db8::/32".parse().unwrap();
+  assert!(network.contains(&IpAddr::V6(Ipv6Addr::new(0x2001,0xdb8,1,0,0,0,0,1))));
+  assert!(!network.contains(&IpAddr::V6(Ipv6Addr::new(0x2001,0xdb9,0,0,0,0,0,1))));
+
+  let network: Network = "192.0.2.1".parse().unwrap();
+  assert_eq!(network.get_prefix_len(), 32);
+  assert!(network.contains(&IpAddr::V4(Ipv4Addr::new(192,0,2,1))));
+  assert!(!network.contains(&IpAddr::V4(Ipv4Addr::new(192,0,2,2))));
+
+  assert!("0.0.0.0/0".parse::<Network>().unwrap().contains(&IpAddr::V4(Ipv4Addr::new(203,0,113,1))));
+  assert!("192.0.2.0/33".parse::<Network>().is_err());
+  assert!("192.0.2/24".parse::<Network>().is_err());
+}
+
+#[test]
+fn test_grant() {
+  use std::net::Ipv4Addr;
+  use ::rr::RData;
+
+  let origin = Name::parse("example.com.", None).unwrap();
+  let host1 = Name::parse("host1.example.com.", None).unwrap();
+  let host2 = Name::parse("host2.example.com.", None).unwrap();
+
+  let mut update = Record::new();
+  update.name(host1.clone()).rr_type(RecordType::A).ttl(86400).rdata(RData::A(Ipv4Addr::new(192,0,2,1)));
+
+  let grant = Grant::new(host1.clone(), GrantName::SelfName, vec![RecordType::A]);
+  assert!(grant.permits(&host1, &update));
+  assert!(!grant.permits(&host2, &update));
+
+  update.name(host2.clone());
+  assert!(!grant.permits(&host1, &update));
+
+  update.name(host1.clone()).rr_type(RecordType::ANY);
+  assert!(!grant.permits(&host1, &update));
+
+  let grant = Grant::new(host1.clone(), GrantName::Subdomain(origin.clone()), vec![]);
+  assert!(grant.permits(&host1, &update));
+  update.name(host2.clone()).rr_type(RecordType::A);
+  assert!(grant.permits(&host1, &update));
+
+  let mut policy = UpdatePolicy::new();
+  assert!(policy.is_update_allowed(None, &[update.clone()]));
+  policy.grant(Grant::new(host2.clone(), GrantName::Name(host2.clone()), vec![RecordType::A]));
+  assert!(policy.is_update_allowed(Some(&host2), &[update.clone()]));
+  assert!(!policy.is_update_allowed(Some(&host1), &[update.clone()]));
+  assert!(!policy.is_update_allowed(None, &[update.clone()]));
+}
+
+#[test]
+fn test_unsigned() {
+  use std::net::Ipv4Addr;
+  use ::rr::RData;
+
+  let host1 = Name::parse("host1.example.com.", None).unwrap();
+  let mut update = Record::new();
+  update.name(host1.clone()).rr_type(RecordType::A).ttl(86400).rdata(RData::A(Ipv4Addr::new(192,0,2,1)));
+
+  let mut policy = UpdatePolicy::new();
+  policy.allow_unsigned(true).allow_network("192.0.2.0/24".parse().unwrap());
+  assert!(policy.is_unsigned_allowed());
+  assert!(policy.is_source_allowed(Some(&IpAddr::V4(Ipv4Addr::new(192,0,2,53)))));
+  assert!(!policy.is_source_allowed(Some(&IpAddr::V4(Ipv4Addr::new(192,0,3,53)))));
+  assert!(policy.is_update_allowed(None, &[update.clone()]));
+
+  // grants are of signers, an unsigned update has none
+  policy.grant(Grant::new(host1.clone(), GrantName::SelfName, vec![]));
+  assert!(!policy.is_update_allowed(None, &[update.clone()]));
+  assert!(policy.is_update_allowed(Some(&host1), &[update.clone()]));
+}
@@ -0,0 +1,201 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fmt;
+
+use ::authority::{Authority, RrKey};
+use ::rr::{Name, RData, RecordType};
+
+/// A problem with the records of a zone, see `Authority::validate()`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ZoneProblem {
+  /// there is no SOA at the origin
+  MissingSoa,
+  /// there is more than one SOA at the origin
+  MultipleSoa(usize),
+  /// an SOA at a name other than the origin
+  SoaNotAtApex(Name),
+  /// there are no NS records at the origin
+  MissingNs,
+  /// the target of an NS record, which is in the zone, has neither A nor AAAA records
+  NsTargetWithoutAddress { name: Name, target: Name },
+  /// a CNAME at a name which has other records, other than DNSSEC records
+  CnameAndOtherData(Name),
+  /// records at a name which is not in the zone
+  OutOfZone(Name, RecordType),
+  /// records at or below a delegation, other than the delegation itself, its DS, and the glue
+  ///  addresses of its name servers, which are occluded by the delegation
+  BelowDelegation { name: Name, record_type: RecordType, zone_cut: Name },
+}
+
+impl fmt::Display for ZoneProblem {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      ZoneProblem::MissingSoa => write!(f, "no SOA at the origin"),
+      ZoneProblem::MultipleSoa(count) => write!(f, "{} SOA records at the origin", count),
+      ZoneProblem::SoaNotAtApex(ref name) => write!(f, "SOA at {} which is not the origin", name),
+      ZoneProblem::MissingNs => write!(f, "no NS records at the origin"),
+      ZoneProblem::NsTargetWithoutAddress { ref name, ref target } => write!(f, "NS {} of {} has no A or AAAA records", target, name),
+      ZoneProblem::CnameAndOtherData(ref name) => write!(f, "CNAME at {} beside other records", name),
+      ZoneProblem::OutOfZone(ref name, record_type) => write!(f, "{:?} at {} is not in the zone", record_type, name),
+      ZoneProblem::BelowDelegation { ref name, record_type, ref zone_cut } => {
+        write!(f, "{:?} at {} is occluded by the delegation of {}", record_type, name, zone_cut)
+      },
+    }
+  }
+}
+
+/// the records which may be beside a CNAME, and anywhere in the zone
+fn is_dnssec(record_type: RecordType) -> bool {
+  match record_type {
+    RecordType::RRSIG | RecordType::SIG | RecordType::NSEC | RecordType::NSEC3 => true,
+    _ => false,
+  }
+}
+
+impl Authority {
+  /// Checks the records of the zone for the problems which would have it answer wrongly, e.g.
+  ///  after loading it from its zone file and before it is served.
+  ///
+  /// # Return value
+  ///
+  /// The problems which were found, those of the origin first, then those of each name in order,
+  ///  empty if there are none
+  pub fn validate(&self) -> Vec<ZoneProblem> {
+    let origin: &Name = self.get_origin();
+    let mut problems: Vec<ZoneProblem> = Vec::new();
+
+    match self.get_records().get(&RrKey::new(origin, RecordType::SOA)).map_or(0, |rr_set| rr_set.iter().count()) {
+      0 => problems.push(ZoneProblem::MissingSoa),
+      1 => (),
+      count => problems.push(ZoneProblem::MultipleSoa(count)),
+    }
+
+    if self.get_records().get(&RrKey::new(origin, RecordType::NS)).map_or(true, |rr_set| rr_set.is_empty()) {
+      problems.push(ZoneProblem::MissingNs);
+    }
+
+    // the name servers of the delegations, their addresses are glue
+    let mut glue: Vec<&Name> = Vec::new();
+    for rr_set in self.get_records().values().filter(|rr_set| rr_set.get_record_type() == RecordType::NS && rr_set.get_name() != origin) {
+      for record in rr_set.iter() {
+        if let &RData::NS(ref target) = record.get_rdata() { glue.push(target); }
+      }
+    }
+
+    for rr_set in self.get_records().values().filter(|rr_set| !rr_set.is_empty()) {
+      let name: &Name = rr_set.get_name();
+      let record_type: RecordType = rr_set.get_record_type();
+
+      if !origin.zone_of(name) {
+        problems.push(ZoneProblem::OutOfZone(name.clone(), record_type));
+        continue
+      }
+
+      if record_type == RecordType::SOA && name != origin {
+        problems.push(ZoneProblem::SoaNotAtApex(name.clone()));
+      }
+
+      if record_type == RecordType::CNAME {
+        let other_data = self.get_records().values()
+                                           .filter(|other| other.get_name() == name && !other.is_empty())
+                                           .any(|other| other.get_record_type() != RecordType::CNAME && !is_dnssec(other.get_record_type()));
+        if other_data { problems.push(ZoneProblem::CnameAndOtherData(name.clone())); }
+      }
+
+      if let Some(zone_cut) = self.find_zone_cut(name) {
+        let delegated = if name == &zone_cut {
+          match record_type { RecordType::NS | RecordType::DS => false, _ => !is_dnssec(record_type) }
+        } else {
+          match record_type { RecordType::A | RecordType::AAAA => !glue.contains(&name), _ => !is_dnssec(record_type) }
+        };
+
+        if delegated {
+          problems.push(ZoneProblem::BelowDelegation{ name: name.clone(), record_type: record_type, zone_cut: zone_cut });
+        }
+      }
+
+      if record_type == RecordType::NS {
+        for record in rr_set.iter() {
+          let target: &Name = if let &RData::NS(ref target) = record.get_rdata() { target } else { continue };
+          if !origin.zone_of(target) { continue }
+
+          let has_address = [RecordType::A, RecordType::AAAA].iter().any(|address_type| {
+            self.get_records().get(&RrKey::new(target, *address_type)).map_or(false, |rr_set| !rr_set.is_empty())
+          });
+          if !has_address {
+            problems.push(ZoneProblem::NsTargetWithoutAddress{ name: name.clone(), target: target.clone() });
+          }
+        }
+      }
+    }
+
+    problems
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+  use std::net::Ipv4Addr;
+
+  use ::authority::{Authority, ZoneType};
+  use ::authority::authority_tests::create_example;
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::rr::rdata::{MX, TXT};
+  use super::*;
+
+  fn record(name: &str, record_type: RecordType, rdata: RData) -> Record {
+    Record::new().name(Name::parse(name, None).unwrap()).ttl(3600).rr_type(record_type).dns_class(DNSClass::IN).rdata(rdata).clone()
+  }
+
+  #[test]
+  fn test_validate_soa_and_ns() {
+    let authority = Authority::new(Name::parse("example.com.", None).unwrap(), BTreeMap::new(), ZoneType::Master, false);
+    assert_eq!(authority.validate(), vec![ZoneProblem::MissingSoa, ZoneProblem::MissingNs]);
+  }
+
+  #[test]
+  fn test_validate() {
+    let mut authority = create_example();
+    assert_eq!(authority.validate(), vec![]);
+
+    let name = |s: &str| Name::parse(s, None).unwrap();
+    authority.upsert(record("alias.example.com.", RecordType::CNAME, RData::CNAME(name("www.example.com."))), 0);
+    authority.upsert(record("alias.example.com.", RecordType::TXT, RData::TXT(TXT::new(vec!["beside".to_string()]))), 0);
+    authority.upsert(record("sub.example.com.", RecordType::NS, RData::NS(name("ns.sub.example.com."))), 0);
+    authority.upsert(record("sub.example.com.", RecordType::MX, RData::MX(MX::new(10, name("mail.example.com.")))), 0);
+    authority.upsert(record("host.sub.example.com.", RecordType::A, RData::A(Ipv4Addr::new(192,0,2,1))), 0);
+    authority.upsert(record("www.example.net.", RecordType::A, RData::A(Ipv4Addr::new(192,0,2,2))), 0);
+
+    let problems = authority.validate();
+    for problem in &[ZoneProblem::CnameAndOtherData(name("alias.example.com.")),
+                     ZoneProblem::BelowDelegation{ name: name("host.sub.example.com."), record_type: RecordType::A, zone_cut: name("sub.example.com.") },
+                     ZoneProblem::BelowDelegation{ name: name("sub.example.com."), record_type: RecordType::MX, zone_cut: name("sub.example.com.") },
+                     ZoneProblem::NsTargetWithoutAddress{ name: name("sub.example.com."), target: name("ns.sub.example.com.") },
+                     ZoneProblem::OutOfZone(name("www.example.net."), RecordType::A)] {
+      assert!(problems.contains(problem), "{} not in {:?}", problem, problems);
+    }
+    assert_eq!(problems.len(), 5);
+
+    // the glue of the delegation is not occluded
+    authority.upsert(record("ns.sub.example.com.", RecordType::A, RData::A(Ipv4Addr::new(192,0,2,53))), 0);
+    assert!(!authority.validate().iter().any(|problem| match *problem {
+      ZoneProblem::NsTargetWithoutAddress{ .. } => true,
+      ZoneProblem::BelowDelegation{ ref name, .. } => name == &Name::parse("ns.sub.example.com.", None).unwrap(),
+      _ => false,
+    }));
+  }
+}
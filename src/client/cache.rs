@@ -0,0 +1,868 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! In memory cache of query responses, for use by the `Client`
+
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use ::op::ResponseCode;
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct CacheKey {
+  name: Name,
+  dns_class: DNSClass,
+  record_type: Option<RecordType>, // None for NXDOMAIN, which applies to all types
+}
+
+#[derive(Debug)]
+enum CachedValue {
+  Records(Vec<Record>),
+  // NXDOMAIN or NODATA, with the SOA from the authority section
+  Negative(ResponseCode, Record),
+}
+
+/// the default upper bound on the time an entry is held, one week
+const DEFAULT_MAX_TTL: u32 = 604800;
+/// the default remaining percentage of the TTL at which popular entries are prefetched
+const DEFAULT_PREFETCH_PERCENT: u8 = 10;
+/// the TTL of stale records, as recommended by RFC 8767
+const DEFAULT_STALE_TTL: u32 = 30;
+
+#[derive(Debug)]
+struct CacheEntry {
+  value: CachedValue,
+  inserted: Instant,
+  ttl: u32,
+  // the time after which the entry may no longer be served, its key in the expiries
+  dead_at: Instant,
+  // the tick of the insertion, distinguishes entries which die at the same instant
+  id: u64,
+  // the tick of the last use, its key in the recency
+  last_used: u64,
+  size: usize,
+  hits: u64,
+  prefetching: bool,
+}
+
+impl CacheEntry {
+  fn is_expired(&self, now: Instant) -> bool {
+    now.duration_since(self.inserted) >= Duration::from_secs(self.ttl as u64)
+  }
+
+  /// true once the entry may no longer be served, even as stale
+  fn is_dead(&self, now: Instant, max_stale: u32) -> bool {
+    now.duration_since(self.inserted) >= Duration::from_secs(self.ttl as u64 + max_stale as u64)
+  }
+
+  fn is_records(&self) -> bool {
+    if let CachedValue::Records(..) = self.value { true } else { false }
+  }
+}
+
+/// Counters of the cache's activity, see `ResponseCache::get_stats()`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+  entries: usize,
+  memory: usize,
+  hits: u64,
+  negative_hits: u64,
+  misses: u64,
+  insertions: u64,
+  evictions: u64,
+  expirations: u64,
+  prefetches: u64,
+  stale_hits: u64,
+}
+
+impl CacheStats {
+  /// the number of entries currently held, this may include expired entries
+  pub fn get_entries(&self) -> usize { self.entries }
+  /// the estimated number of bytes held by the entries
+  pub fn get_memory(&self) -> usize { self.memory }
+  /// calls to `get()` which were answered from the cache
+  pub fn get_hits(&self) -> u64 { self.hits }
+  /// calls to `get_negative()` which were answered from the cache
+  pub fn get_negative_hits(&self) -> u64 { self.negative_hits }
+  /// calls to `get()` which found no unexpired entry
+  pub fn get_misses(&self) -> u64 { self.misses }
+  /// positive and negative entries stored
+  pub fn get_insertions(&self) -> u64 { self.insertions }
+  /// unexpired entries removed to make room for new ones
+  pub fn get_evictions(&self) -> u64 { self.evictions }
+  /// entries removed after their TTL passed
+  pub fn get_expirations(&self) -> u64 { self.expirations }
+  /// popular entries which were queued to be refreshed before they expire
+  pub fn get_prefetches(&self) -> u64 { self.prefetches }
+  /// calls to `get_stale()` which were answered with expired records
+  pub fn get_stale_hits(&self) -> u64 { self.stale_hits }
+}
+
+/// A cache of answers, keyed by (name, class, type).
+///
+/// Positive entries are held for the minimum TTL of their records. Negative entries, NXDOMAIN and
+///  NODATA, are held for the negative TTL of RFC 2308. Either TTL is clamped to the range of
+///  `min_ttl()` and `max_ttl()`. When the cache is full, by entry count or by the estimated memory
+///  of the entries, the least recently used entry is evicted.
+///
+/// The cache is not synchronized, wrap it in a `SharedCache` to use it from multiple clients.
+#[derive(Debug)]
+pub struct ResponseCache {
+  entries: HashMap<CacheKey, CacheEntry>,
+  // the keys by last use, the first is the least recently used
+  recency: BTreeMap<u64, CacheKey>,
+  // the keys by the time they may no longer be served, the first dies first
+  expiries: BTreeMap<(Instant, u64), CacheKey>,
+  max_entries: usize,
+  max_memory: Option<usize>,
+  min_ttl: u32,
+  max_ttl: u32,
+  tick: u64,
+  stats: CacheStats,
+  prefetch_hits: Option<u64>,
+  prefetch_percent: u8,
+  prefetches: Vec<CacheKey>,
+  serve_stale: Option<u32>,
+  stale_ttl: u32,
+}
+
+impl ResponseCache {
+  /// Creates a new cache which will hold at most `max_entries` RRSets
+  pub fn new(max_entries: usize) -> Self {
+    assert!(max_entries > 0, "the cache must allow at least one entry");
+    ResponseCache{ entries: HashMap::new(), recency: BTreeMap::new(), expiries: BTreeMap::new(), max_entries: max_entries,
+                   max_memory: None, min_ttl: 0, max_ttl: DEFAULT_MAX_TTL, tick: 0, stats: CacheStats::default(), prefetch_hits: None,
+                   prefetch_percent: DEFAULT_PREFETCH_PERCENT, prefetches: vec![], serve_stale: None,
+                   stale_ttl: DEFAULT_STALE_TTL }
+  }
+
+  /// Limits the estimated memory of the entries in bytes, the estimate includes the names and the
+  ///  record structures, but not all of the record data. None, the default, only limits the entry
+  ///  count.
+  pub fn max_memory(&mut self, max_memory: Option<usize>) -> &mut Self {
+    self.max_memory = max_memory;
+    self
+  }
+
+  /// Entries are held for at least this many seconds, even if their TTL is lower. Records with a
+  ///  TTL of zero are still never cached. Defaults to 0.
+  pub fn min_ttl(&mut self, min_ttl: u32) -> &mut Self {
+    self.min_ttl = min_ttl;
+    self
+  }
+
+  /// Entries are held for at most this many seconds, even if their TTL is higher. Defaults to one
+  ///  week.
+  pub fn max_ttl(&mut self, max_ttl: u32) -> &mut Self {
+    self.max_ttl = max_ttl;
+    self
+  }
+
+  /// Entries with at least this many hits are queued to be refreshed when they are hit within the
+  ///  last `prefetch_percent()` of their TTL, so that popular names are refreshed before they
+  ///  expire, see `take_prefetches()`. None, the default, disables prefetching.
+  pub fn prefetch(&mut self, min_hits: Option<u64>) -> &mut Self {
+    self.prefetch_hits = min_hits;
+    self
+  }
+
+  /// The remaining percentage of the TTL within which a hit queues a popular entry for refresh,
+  ///  defaults to 10
+  pub fn prefetch_percent(&mut self, prefetch_percent: u8) -> &mut Self {
+    self.prefetch_percent = cmp::min(prefetch_percent, 100);
+    self
+  }
+
+  /// Keeps positive entries for this many seconds after they expire, so that they may be served
+  ///  when the server can not be reached, see `get_stale()`. None, the default, drops the entries
+  ///  when they expire. [RFC 8767](https://tools.ietf.org/html/rfc8767) suggests one to three days.
+  ///
+  /// ```text
+  /// 4.  Standards Action
+  ///
+  ///    Answers from authoritative servers that have a DNS response code of
+  ///    either NOERROR (RCODE 0) or NXDOMAIN (RCODE 3) and the Authoritative
+  ///    Answers (AA) bit set MUST be considered to have refreshed the data at
+  ///    the resolver.  Answers from authoritative servers that have any other
+  ///    response code SHOULD be considered a failure to refresh the data and
+  ///    therefore leave any previous state intact.
+  /// ```
+  pub fn serve_stale(&mut self, max_stale: Option<u32>) -> &mut Self {
+    self.serve_stale = max_stale;
+    self
+  }
+
+  /// The TTL of the records served stale, defaults to 30 seconds
+  pub fn stale_ttl(&mut self, stale_ttl: u32) -> &mut Self {
+    self.stale_ttl = stale_ttl;
+    self
+  }
+
+  pub fn get_max_entries(&self) -> usize { self.max_entries }
+  pub fn get_max_memory(&self) -> Option<usize> { self.max_memory }
+  pub fn get_min_ttl(&self) -> u32 { self.min_ttl }
+  pub fn get_max_ttl(&self) -> u32 { self.max_ttl }
+  pub fn get_prefetch(&self) -> Option<u64> { self.prefetch_hits }
+  pub fn get_prefetch_percent(&self) -> u8 { self.prefetch_percent }
+  pub fn get_serve_stale(&self) -> Option<u32> { self.serve_stale }
+  pub fn get_stale_ttl(&self) -> u32 { self.stale_ttl }
+
+  /// The counters of the cache's activity, along with its current size
+  pub fn get_stats(&self) -> CacheStats {
+    let mut stats = self.stats;
+    stats.entries = self.entries.len();
+    stats
+  }
+
+  /// the number of entries in the cache, this may include expired entries
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Returns the cached records, with the TTLs reduced by the time spent in the cache, or None
+  ///  if there is no unexpired positive entry.
+  pub fn get(&mut self, name: &Name, dns_class: DNSClass, record_type: RecordType) -> Option<Vec<Record>> {
+    let found = match self.lookup(CacheKey{ name: name.clone(), dns_class: dns_class, record_type: Some(record_type) }) {
+      Some((&CachedValue::Records(ref records), elapsed)) => Some(records.iter().map(|r| reduce_ttl(r, elapsed)).collect()),
+      _ => None,
+    };
+
+    if found.is_some() { self.stats.hits += 1 } else { self.stats.misses += 1 }
+    found
+  }
+
+  /// Returns the cached negative response for the query, NXDOMAIN or NODATA (NoError), along with
+  ///  the SOA record from the original response, its TTL reduced by the time spent in the cache.
+  pub fn get_negative(&mut self, name: &Name, dns_class: DNSClass, record_type: RecordType) -> Option<(ResponseCode, Record)> {
+    // NXDOMAIN is for all types at the name
+    for key_type in &[None, Some(record_type)] {
+      let found = match self.lookup(CacheKey{ name: name.clone(), dns_class: dns_class, record_type: *key_type }) {
+        Some((&CachedValue::Negative(response_code, ref soa), elapsed)) => Some((response_code, reduce_ttl(soa, elapsed))),
+        _ => None,
+      };
+
+      if found.is_some() {
+        self.stats.negative_hits += 1;
+        return found
+      }
+    }
+
+    None
+  }
+
+  /// returns the value and the number of seconds it has been cached
+  fn lookup(&mut self, key: CacheKey) -> Option<(&CachedValue, u32)> {
+    let now = Instant::now();
+
+    let (expired, dead) = match self.entries.get(&key) {
+      Some(entry) => (entry.is_expired(now), entry.is_dead(now, self.max_stale(entry))),
+      None => return None,
+    };
+
+    if dead {
+      self.remove(&key);
+      self.stats.expirations += 1;
+      return None
+    }
+
+    // kept only to be served stale
+    if expired { return None }
+
+    self.tick += 1;
+    let entry = self.entries.get_mut(&key).expect("entry was just found");
+    self.recency.remove(&entry.last_used);
+    self.recency.insert(self.tick, key.clone());
+    entry.last_used = self.tick;
+    entry.hits += 1;
+
+    let elapsed = now.duration_since(entry.inserted).as_secs() as u32;
+
+    if let Some(min_hits) = self.prefetch_hits {
+      let is_records = entry.is_records();
+      let remaining = entry.ttl.saturating_sub(elapsed) as u64;
+
+      if is_records && !entry.prefetching && entry.hits >= min_hits && remaining * 100 <= entry.ttl as u64 * self.prefetch_percent as u64 {
+        debug!("queueing prefetch: {:?}", key);
+        entry.prefetching = true;
+        self.stats.prefetches += 1;
+        self.prefetches.push(key);
+      }
+    }
+
+    Some((&entry.value, elapsed))
+  }
+
+  /// Returns the expired records of the query, with the stale TTL, if they expired within the
+  ///  `serve_stale()` period. This is for answering when the server could not be reached, or
+  ///  failed, after `get()` found no unexpired records.
+  pub fn get_stale(&mut self, name: &Name, dns_class: DNSClass, record_type: RecordType) -> Option<Vec<Record>> {
+    let max_stale = match self.serve_stale {
+      Some(max_stale) => max_stale,
+      None => return None,
+    };
+
+    let now = Instant::now();
+    let key = CacheKey{ name: name.clone(), dns_class: dns_class, record_type: Some(record_type) };
+    let records: Vec<Record> = match self.entries.get(&key) {
+      Some(entry) if !entry.is_dead(now, max_stale) => match entry.value {
+        CachedValue::Records(ref records) => records.iter().map(|r| {
+          let mut record = r.clone();
+          record.ttl(cmp::min(self.stale_ttl, r.get_ttl()));
+          record
+        }).collect(),
+        _ => return None,
+      },
+      _ => return None,
+    };
+
+    self.stats.stale_hits += 1;
+    Some(records)
+  }
+
+  /// the time the entry is kept after it expires
+  fn max_stale(&self, entry: &CacheEntry) -> u32 {
+    if entry.is_records() { self.serve_stale.unwrap_or(0) } else { 0 }
+  }
+
+  /// Returns the popular entries which should be refreshed, and clears the queue. The refreshed
+  ///  records replace the entries with `insert()`, see `Client::refresh()`.
+  pub fn take_prefetches(&mut self) -> Vec<(Name, DNSClass, RecordType)> {
+    self.prefetches.drain(..).filter_map(|key| key.record_type.map(|record_type| (key.name, key.dns_class, record_type))).collect()
+  }
+
+  /// true if there are entries queued to be prefetched
+  pub fn has_prefetches(&self) -> bool {
+    !self.prefetches.is_empty()
+  }
+
+  /// Stores the records, which should all belong to the same RRSet. The entry lives for the
+  ///  minimum TTL of the records, clamped to `min_ttl()` and `max_ttl()`, the TTLs of the records
+  ///  are set to that of the entry. Records with a TTL of zero are not cached.
+  pub fn insert(&mut self, name: &Name, dns_class: DNSClass, record_type: RecordType, mut records: Vec<Record>) {
+    let ttl = match records.iter().map(|r| r.get_ttl()).min() {
+      Some(0) | None => return,
+      Some(ttl) => self.clamp_ttl(ttl),
+    };
+
+    for record in &mut records {
+      record.ttl(ttl);
+    }
+
+    self.insert_entry(CacheKey{ name: name.clone(), dns_class: dns_class, record_type: Some(record_type) }, CachedValue::Records(records), ttl);
+  }
+
+  /// Stores a negative response, per [RFC 2308](https://tools.ietf.org/html/rfc2308), the SOA must
+  ///  be from the authority section of the response.
+  ///
+  /// ```text
+  /// 5 - Caching Negative Answers
+  ///
+  ///    Like normal answers negative answers have a time to live (TTL).  As
+  ///    there is no record in the answer section to which this TTL can be
+  ///    applied, the TTL must be carried by another method.  This is done by
+  ///    including the SOA record from the zone in the authority section of
+  ///    the reply.  When the authoritative server creates this record its TTL
+  ///    is taken from the minimum of the SOA.MINIMUM field and SOA's TTL.
+  ///
+  ///    A negative answer that resulted from a name error (NXDOMAIN) should
+  ///    be cached such that it can be retrieved and returned in response to
+  ///    another query for the same <QNAME, QCLASS> that resulted in the
+  ///    cached negative response.
+  ///
+  ///    A negative answer that resulted from a no data error (NODATA) should
+  ///    be cached such that it can be retrieved and returned in response to
+  ///    another query for the same <QNAME, QTYPE, QCLASS> that resulted in
+  ///    the cached negative response.
+  /// ```
+  pub fn insert_negative(&mut self, name: &Name, dns_class: DNSClass, record_type: RecordType, response_code: ResponseCode, mut soa: Record) {
+    let ttl = match negative_ttl(&soa) {
+      Some(0) | None => return,
+      Some(ttl) => self.clamp_ttl(ttl),
+    };
+    soa.ttl(ttl);
+
+    let key_type = match response_code {
+      ResponseCode::NXDomain => None,
+      ResponseCode::NoError => Some(record_type),
+      _ => return,
+    };
+
+    self.insert_entry(CacheKey{ name: name.clone(), dns_class: dns_class, record_type: key_type }, CachedValue::Negative(response_code, soa), ttl);
+  }
+
+  fn clamp_ttl(&self, ttl: u32) -> u32 {
+    cmp::min(cmp::max(ttl, self.min_ttl), self.max_ttl)
+  }
+
+  fn insert_entry(&mut self, key: CacheKey, value: CachedValue, ttl: u32) {
+    let size = estimate_size(&key, &value);
+    self.remove(&key);
+
+    while !self.entries.is_empty() && (self.entries.len() >= self.max_entries || self.is_over_memory(size)) {
+      self.evict();
+    }
+
+    let now = Instant::now();
+    let max_stale = if let CachedValue::Records(..) = value { self.serve_stale.unwrap_or(0) } else { 0 };
+    let dead_at = now + Duration::from_secs(ttl as u64 + max_stale as u64);
+
+    self.tick += 1;
+    self.stats.insertions += 1;
+    self.stats.memory += size;
+    self.recency.insert(self.tick, key.clone());
+    self.expiries.insert((dead_at, self.tick), key.clone());
+    self.entries.insert(key, CacheEntry{ value: value, inserted: now, ttl: ttl, dead_at: dead_at, id: self.tick,
+                                         last_used: self.tick, size: size, hits: 0, prefetching: false });
+  }
+
+  /// true if adding an entry of `size` bytes would exceed the memory limit
+  fn is_over_memory(&self, size: usize) -> bool {
+    self.max_memory.map_or(false, |max| self.stats.memory + size > max)
+  }
+
+  fn remove(&mut self, key: &CacheKey) {
+    if let Some(entry) = self.entries.remove(key) {
+      self.stats.memory -= entry.size;
+      self.recency.remove(&entry.last_used);
+      self.expiries.remove(&(entry.dead_at, entry.id));
+    }
+  }
+
+  /// Removes all entries for the name, of any type or class
+  pub fn flush_name(&mut self, name: &Name) {
+    let keys: Vec<CacheKey> = self.entries.keys().filter(|k| k.name == *name).cloned().collect();
+    for key in keys {
+      self.remove(&key);
+    }
+  }
+
+  /// Removes all entries, the counters of the statistics are kept
+  pub fn clear(&mut self) {
+    self.entries.clear();
+    self.recency.clear();
+    self.expiries.clear();
+    self.stats.memory = 0;
+  }
+
+  /// drops the expired entries which may not be served stale, or if none, the least recently used.
+  ///  Both are found from the front of the ordered indexes, so that a full cache is not scanned on
+  ///  every insert.
+  fn evict(&mut self) {
+    let now = Instant::now();
+    let mut expired = false;
+
+    while let Some(key) = self.first_dead(now) {
+      self.stats.expirations += 1;
+      self.remove(&key);
+      expired = true;
+    }
+
+    if expired { return }
+
+    let lru: Option<CacheKey> = self.recency.values().next().cloned();
+    if let Some(key) = lru {
+      debug!("evicting from cache: {:?}", key);
+      self.stats.evictions += 1;
+      self.remove(&key);
+    }
+  }
+
+  /// the key of the first entry to die, if it may no longer be served
+  fn first_dead(&self, now: Instant) -> Option<CacheKey> {
+    let key = match self.expiries.values().next() {
+      Some(key) => key,
+      None => return None,
+    };
+
+    match self.entries.get(key) {
+      Some(entry) if entry.is_dead(now, self.max_stale(entry)) => Some(key.clone()),
+      _ => None,
+    }
+  }
+}
+
+/// A `ResponseCache` which may be shared between clients, and resolvers, across threads. Clones
+///  refer to the same cache.
+///
+/// Prefetching is done by a client of its own, on a background thread which waits for the popular
+///  entries to refresh, see `wait_for_prefetch()`, so that the callers are always answered from
+///  the cache.
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+/// use trust_dns::client::{ResponseCache, SharedCache};
+/// use trust_dns::resolver::{Resolver, ResolverConfig};
+///
+/// let mut cache = ResponseCache::new(4096);
+/// cache.prefetch(Some(10));
+/// let cache = SharedCache::new(cache);
+///
+/// let config = ResolverConfig::from_system().unwrap();
+/// let resolver = Resolver::with_cache(config.clone(), cache.clone()).unwrap();
+/// let prefetcher = Resolver::with_cache(config, cache.clone()).unwrap();
+///
+/// thread::spawn(move || loop {
+///   if cache.wait_for_prefetch(Duration::from_secs(60)) { prefetcher.prefetch(); }
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedCache {
+  cache: Arc<Mutex<ResponseCache>>,
+  prefetch_ready: Arc<Condvar>,
+}
+
+impl SharedCache {
+  pub fn new(cache: ResponseCache) -> Self {
+    SharedCache{ cache: Arc::new(Mutex::new(cache)), prefetch_ready: Arc::new(Condvar::new()) }
+  }
+
+  /// see `ResponseCache::get()`, threads waiting in `wait_for_prefetch()` are woken if the hit
+  ///  queued the entry to be prefetched
+  pub fn get(&self, name: &Name, dns_class: DNSClass, record_type: RecordType) -> Option<Vec<Record>> {
+    let mut cache = self.lock();
+    let records = cache.get(name, dns_class, record_type);
+    if cache.has_prefetches() { self.prefetch_ready.notify_all(); }
+    records
+  }
+
+  /// see `ResponseCache::get_negative()`
+  pub fn get_negative(&self, name: &Name, dns_class: DNSClass, record_type: RecordType) -> Option<(ResponseCode, Record)> {
+    self.lock().get_negative(name, dns_class, record_type)
+  }
+
+  /// see `ResponseCache::get_stale()`
+  pub fn get_stale(&self, name: &Name, dns_class: DNSClass, record_type: RecordType) -> Option<Vec<Record>> {
+    self.lock().get_stale(name, dns_class, record_type)
+  }
+
+  /// see `ResponseCache::take_prefetches()`
+  pub fn take_prefetches(&self) -> Vec<(Name, DNSClass, RecordType)> {
+    self.lock().take_prefetches()
+  }
+
+  /// Blocks until there are entries to prefetch, or the timeout passes, returns true if there are
+  ///  entries to prefetch
+  pub fn wait_for_prefetch(&self, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut cache = self.lock();
+
+    while !cache.has_prefetches() {
+      let now = Instant::now();
+      if now >= deadline { return false }
+
+      cache = match self.prefetch_ready.wait_timeout(cache, deadline - now) {
+        Ok((cache, _)) => cache,
+        Err(poisoned) => poisoned.into_inner().0,
+      };
+    }
+
+    true
+  }
+
+  /// Locks the cache for exclusive access. A panic while the cache was locked does not leave it in
+  ///  an inconsistent state, so the lock is taken even if it is poisoned.
+  pub fn lock(&self) -> MutexGuard<ResponseCache> {
+    self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  /// see `ResponseCache::get_stats()`
+  pub fn get_stats(&self) -> CacheStats {
+    self.lock().get_stats()
+  }
+}
+
+impl From<ResponseCache> for SharedCache {
+  fn from(cache: ResponseCache) -> Self {
+    SharedCache::new(cache)
+  }
+}
+
+/// the negative TTL of RFC 2308, the minimum of the SOA record's TTL and its MINIMUM field
+fn negative_ttl(soa: &Record) -> Option<u32> {
+  if let RData::SOA(ref rdata) = *soa.get_rdata() {
+    Some(cmp::min(soa.get_ttl(), rdata.get_minimum()))
+  } else {
+    None
+  }
+}
+
+/// a rough estimate of the bytes held by an entry, the names and fixed size structures
+fn estimate_size(key: &CacheKey, value: &CachedValue) -> usize {
+  let record_size = |record: &Record| mem::size_of::<Record>() + record.get_name().len();
+  let records = match *value {
+    CachedValue::Records(ref records) => records.iter().fold(0, |sum, record| sum + record_size(record)),
+    CachedValue::Negative(_, ref soa) => record_size(soa),
+  };
+
+  mem::size_of::<CacheKey>() + mem::size_of::<CacheEntry>() + key.name.len() + records
+}
+
+fn reduce_ttl(record: &Record, elapsed: u32) -> Record {
+  let mut record = record.clone();
+  let ttl = record.get_ttl().saturating_sub(elapsed);
+  record.ttl(ttl);
+  record
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::Ipv4Addr;
+
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  use super::{ResponseCache, SharedCache};
+  use ::op::ResponseCode;
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::rr::rdata::SOA;
+
+  fn a_record(name: &Name, ttl: u32) -> Record {
+    let mut record = Record::with(name.clone(), RecordType::A, ttl);
+    record.rdata(RData::A(Ipv4Addr::new(127,0,0,1)));
+    record
+  }
+
+  #[test]
+  fn test_get_insert() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut cache = ResponseCache::new(10);
+
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_none());
+    cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 300)]);
+
+    let records = cache.get(&name, DNSClass::IN, RecordType::A).expect("should be cached");
+    assert_eq!(records, vec![a_record(&name, 300)]);
+    assert!(records[0].get_ttl() <= 300);
+    assert!(cache.get(&name, DNSClass::IN, RecordType::AAAA).is_none());
+
+    // zero ttls are never cached
+    cache.insert(&name, DNSClass::IN, RecordType::AAAA, vec![a_record(&name, 0)]);
+    assert!(cache.get(&name, DNSClass::IN, RecordType::AAAA).is_none());
+
+    cache.flush_name(&name);
+    assert!(cache.is_empty());
+  }
+
+  #[test]
+  fn test_lru() {
+    let first = Name::parse("first.example.com.", None).unwrap();
+    let second = Name::parse("second.example.com.", None).unwrap();
+    let third = Name::parse("third.example.com.", None).unwrap();
+    let mut cache = ResponseCache::new(2);
+
+    cache.insert(&first, DNSClass::IN, RecordType::A, vec![a_record(&first, 300)]);
+    cache.insert(&second, DNSClass::IN, RecordType::A, vec![a_record(&second, 300)]);
+
+    // touch first, making second the lru
+    assert!(cache.get(&first, DNSClass::IN, RecordType::A).is_some());
+    cache.insert(&third, DNSClass::IN, RecordType::A, vec![a_record(&third, 300)]);
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&first, DNSClass::IN, RecordType::A).is_some());
+    assert!(cache.get(&second, DNSClass::IN, RecordType::A).is_none());
+    assert!(cache.get(&third, DNSClass::IN, RecordType::A).is_some());
+  }
+
+  #[test]
+  fn test_eviction_indexes() {
+    let mut cache = ResponseCache::new(10);
+    let first = Name::parse("first.example.com.", None).unwrap();
+    cache.insert(&first, DNSClass::IN, RecordType::A, vec![a_record(&first, 300)]);
+
+    for i in 0..100 {
+      let name = Name::parse(&format!("host{}.example.com.", i), None).unwrap();
+      cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 300)]);
+      // keep first the most recently used
+      assert!(cache.get(&first, DNSClass::IN, RecordType::A).is_some());
+    }
+
+    assert_eq!(cache.len(), 10);
+    assert_eq!(cache.recency.len(), 10);
+    assert_eq!(cache.expiries.len(), 10);
+    assert_eq!(cache.get_stats().get_evictions(), 91);
+
+    // an entry which may no longer be served is dropped before the least recently used, first is
+    //  the first to die but the most recently used
+    for entry in cache.entries.values_mut().filter(|e| e.id == 1) { entry.inserted = Instant::now() - Duration::from_secs(400); }
+
+    let lru = Name::parse("host90.example.com.", None).unwrap();
+    let last = Name::parse("last.example.com.", None).unwrap();
+    cache.insert(&last, DNSClass::IN, RecordType::A, vec![a_record(&last, 300)]);
+    assert_eq!(cache.len(), 10);
+    assert!(cache.get(&first, DNSClass::IN, RecordType::A).is_none());
+    assert!(cache.get(&lru, DNSClass::IN, RecordType::A).is_some());
+    assert_eq!(cache.get_stats().get_evictions(), 91);
+    assert_eq!(cache.get_stats().get_expirations(), 1);
+  }
+
+  #[test]
+  fn test_negative() {
+    let origin = Name::parse("example.com.", None).unwrap();
+    let nx = Name::parse("nx.example.com.", None).unwrap();
+    let www = Name::parse("www.example.com.", None).unwrap();
+
+    let mut soa = Record::with(origin.clone(), RecordType::SOA, 3600);
+    soa.rdata(RData::SOA(SOA::new(origin.clone(), origin.clone(), 1, 2, 3, 4, 60)));
+
+    let mut cache = ResponseCache::new(10);
+    cache.insert_negative(&nx, DNSClass::IN, RecordType::A, ResponseCode::NXDomain, soa.clone());
+    cache.insert_negative(&www, DNSClass::IN, RecordType::MX, ResponseCode::NoError, soa.clone());
+
+    // NXDOMAIN applies to all types
+    let (response_code, cached_soa) = cache.get_negative(&nx, DNSClass::IN, RecordType::AAAA).expect("should be cached");
+    assert_eq!(response_code, ResponseCode::NXDomain);
+    assert!(cached_soa.get_ttl() <= 60);
+
+    // NODATA only to the type
+    assert_eq!(cache.get_negative(&www, DNSClass::IN, RecordType::MX).unwrap().0, ResponseCode::NoError);
+    assert!(cache.get_negative(&www, DNSClass::IN, RecordType::A).is_none());
+    assert!(cache.get(&www, DNSClass::IN, RecordType::MX).is_none());
+  }
+
+  #[test]
+  fn test_ttl_clamping() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut cache = ResponseCache::new(10);
+    cache.min_ttl(60).max_ttl(600);
+
+    cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 5)]);
+    let ttl = cache.get(&name, DNSClass::IN, RecordType::A).unwrap()[0].get_ttl();
+    assert!(ttl > 5 && ttl <= 60);
+
+    cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 86400)]);
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).unwrap()[0].get_ttl() <= 600);
+
+    // zero is still never cached
+    cache.insert(&name, DNSClass::IN, RecordType::AAAA, vec![a_record(&name, 0)]);
+    assert!(cache.get(&name, DNSClass::IN, RecordType::AAAA).is_none());
+  }
+
+  #[test]
+  fn test_max_memory() {
+    let first = Name::parse("first.example.com.", None).unwrap();
+    let second = Name::parse("second.example.com.", None).unwrap();
+    let mut cache = ResponseCache::new(10);
+
+    cache.insert(&first, DNSClass::IN, RecordType::A, vec![a_record(&first, 300)]);
+    let one_entry = cache.get_stats().get_memory();
+    assert!(one_entry > 0);
+
+    // room for only a single entry
+    cache.max_memory(Some(one_entry + 1));
+    cache.insert(&second, DNSClass::IN, RecordType::A, vec![a_record(&second, 300)]);
+
+    assert_eq!(cache.len(), 1);
+    assert!(cache.get(&second, DNSClass::IN, RecordType::A).is_some());
+    assert!(cache.get_stats().get_memory() <= one_entry + 1);
+    assert_eq!(cache.get_stats().get_evictions(), 1);
+
+    cache.clear();
+    assert_eq!(cache.get_stats().get_memory(), 0);
+  }
+
+  #[test]
+  fn test_stats() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut cache = ResponseCache::new(10);
+
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_none());
+    cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 300)]);
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_some());
+
+    let stats = cache.get_stats();
+    assert_eq!(stats.get_entries(), 1);
+    assert_eq!(stats.get_hits(), 1);
+    assert_eq!(stats.get_misses(), 1);
+    assert_eq!(stats.get_insertions(), 1);
+    assert_eq!(stats.get_evictions(), 0);
+  }
+
+  #[test]
+  fn test_shared_cache() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let shared = SharedCache::new(ResponseCache::new(10));
+
+    let other = shared.clone();
+    let record_name = name.clone();
+    thread::spawn(move || {
+      other.lock().insert(&record_name, DNSClass::IN, RecordType::A, vec![a_record(&record_name, 300)]);
+    }).join().unwrap();
+
+    assert!(shared.lock().get(&name, DNSClass::IN, RecordType::A).is_some());
+    assert_eq!(shared.get_stats().get_hits(), 1);
+  }
+
+  #[test]
+  fn test_prefetch() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut cache = ResponseCache::new(10);
+    cache.prefetch(Some(2)).prefetch_percent(100);
+
+    cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 300)]);
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_some());
+    assert!(!cache.has_prefetches());
+
+    // the second hit makes the entry popular, within the whole of the TTL
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_some());
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_some());
+    assert_eq!(cache.take_prefetches(), vec![(name.clone(), DNSClass::IN, RecordType::A)]);
+    assert!(cache.take_prefetches().is_empty());
+    assert_eq!(cache.get_stats().get_prefetches(), 1);
+
+    // refreshing the entry allows it to be prefetched again
+    cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 300)]);
+    cache.get(&name, DNSClass::IN, RecordType::A);
+    cache.get(&name, DNSClass::IN, RecordType::A);
+    assert_eq!(cache.take_prefetches().len(), 1);
+
+    let shared = SharedCache::new(ResponseCache::new(10));
+    assert!(!shared.wait_for_prefetch(::std::time::Duration::from_millis(10)));
+  }
+
+  #[test]
+  fn test_serve_stale() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut cache = ResponseCache::new(10);
+    assert!(cache.get_stale(&name, DNSClass::IN, RecordType::A).is_none());
+
+    cache.serve_stale(Some(3600));
+    cache.insert(&name, DNSClass::IN, RecordType::A, vec![a_record(&name, 300)]);
+
+    // expire the entry, within the stale period
+    for entry in cache.entries.values_mut() { entry.inserted = Instant::now() - Duration::from_secs(400); }
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_none());
+    assert_eq!(cache.len(), 1);
+
+    let stale = cache.get_stale(&name, DNSClass::IN, RecordType::A).expect("should be served stale");
+    assert_eq!(stale[0].get_ttl(), 30);
+    assert_eq!(cache.get_stats().get_stale_hits(), 1);
+
+    // past the stale period the entry is dropped
+    for entry in cache.entries.values_mut() { entry.inserted = Instant::now() - Duration::from_secs(4000); }
+    assert!(cache.get_stale(&name, DNSClass::IN, RecordType::A).is_none());
+    assert!(cache.get(&name, DNSClass::IN, RecordType::A).is_none());
+    assert!(cache.is_empty());
+  }
+}
@@ -0,0 +1,61 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The CHAOS class TXT queries used for identifying servers, see `Client::chaos_query()`
+
+use ::rr::Name;
+
+/// The standard CH TXT diagnostic names, these identify the software and the instance of a
+///  server, e.g. which member of an anycast group answered.
+///
+/// [RFC 4892, Requirements for a Mechanism Identifying a Name Server Instance, June 2007](https://tools.ietf.org/html/rfc4892#section-2.1)
+///
+/// ```text
+/// Historically, the name "hostname.bind" has been used for this
+/// purpose, and "id.server" has since been proposed as the
+/// standardized name, a server MAY answer either.
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChaosQuery {
+  /// `version.bind.`, the version of the server software
+  VersionBind,
+  /// `hostname.bind.`, the host name of the server instance
+  HostnameBind,
+  /// `id.server.`, the identity of the server instance, RFC 4892
+  IdServer,
+  /// `version.server.`, the version of the server software, RFC 4892
+  VersionServer,
+}
+
+impl ChaosQuery {
+  /// The name to query with class CH and type TXT
+  pub fn to_name(&self) -> Name {
+    let (label, domain) = match *self {
+      ChaosQuery::VersionBind => ("version", "bind"),
+      ChaosQuery::HostnameBind => ("hostname", "bind"),
+      ChaosQuery::IdServer => ("id", "server"),
+      ChaosQuery::VersionServer => ("version", "server"),
+    };
+
+    Name::with_labels(vec![label.to_string(), domain.to_string()])
+  }
+}
+
+#[test]
+fn test_to_name() {
+  assert_eq!(ChaosQuery::VersionBind.to_name(), Name::parse("version.bind.", None).unwrap());
+  assert_eq!(ChaosQuery::IdServer.to_name(), Name::parse("id.server.", None).unwrap());
+}
@@ -13,8 +13,12 @@
 // limitations under the License.
 
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
+use std::net::IpAddr;
 use std::sync::Arc as Rc;
+use std::thread;
+use std::time::Instant;
 
 use chrono::UTC;
 use data_encoding::base32hex;
@@ -22,13 +26,18 @@ use openssl::crypto::pkey::Role;
 use rand;
 
 use ::error::*;
+use ::resolver::{Hosts, Overrides};
 use ::rr::{DNSClass, RecordType, Record, RData};
-use ::rr::rdata::NULL;
+use ::rr::rdata::{NULL, SRV, compare_serial};
 use ::rr::domain;
 use ::rr::dnssec::{Signer, TrustAnchor};
-use ::op::{ Message, MessageType, OpCode, Query, Edns, ResponseCode, UpdateMessage };
+use ::op::{ Message, MessageBuilder, MessageType, OpCode, Query, Edns, ResponseCode, UpdateMessage };
 use ::serialize::binary::*;
-use ::client::ClientConnection;
+use ::client::{AddressOrder, ChaosQuery, ClientConnection, Dns64, LookupIpStrategy, MailExchange, MessageInterceptor, QueryFlags, QueryLimiter, QueryObserver, ResponseCache, RetryPolicy, SharedCache, ServiceLookup};
+use ::client::mail_exchange;
+
+/// the CNAME chain depth followed by lookups which require the final address, e.g. `lookup_ip()`
+const DEFAULT_CNAME_DEPTH: usize = 8;
 
 /// The Client is abstracted over either trust_dns::tcp::TcpClientConnection or
 ///  trust_dns::udp::UdpClientConnection, usage of TCP or UDP is up to the user. Some DNS servers
@@ -36,6 +45,19 @@ use ::client::ClientConnection;
 pub struct Client<C: ClientConnection> {
   client_connection: RefCell<C>,
   trust_anchor: TrustAnchor,
+  retry_policy: RetryPolicy,
+  case_randomization: bool,
+  validating: bool,
+  cache: Option<SharedCache>,
+  dns64: Option<Dns64>,
+  max_cname_depth: usize,
+  observer: Option<Rc<QueryObserver>>,
+  interceptors: Vec<Rc<MessageInterceptor>>,
+  query_flags: QueryFlags,
+  hosts: RefCell<Option<Hosts>>,
+  overrides: Option<Overrides>,
+  limiter: Option<QueryLimiter>,
+  decode_mode: DecodeMode,
 }
 
 impl<C: ClientConnection> Client<C> {
@@ -46,7 +68,20 @@ impl<C: ClientConnection> Client<C> {
   /// * `client_connection` - the client_connection to use for all communication
   pub fn new(client_connection: C) -> Client<C> {
     Client{ client_connection: RefCell::new(client_connection),
-            trust_anchor: TrustAnchor::default() }
+            trust_anchor: TrustAnchor::default(),
+            retry_policy: RetryPolicy::default(),
+            case_randomization: false,
+            validating: false,
+            cache: None,
+            dns64: None,
+            max_cname_depth: 0,
+            observer: None,
+            interceptors: vec![],
+            query_flags: QueryFlags::default(),
+            hosts: RefCell::new(None),
+            overrides: None,
+            limiter: None,
+            decode_mode: DecodeMode::Strict }
   }
 
   /// This variant allows for the trust_anchor to be replaced
@@ -58,7 +93,183 @@ impl<C: ClientConnection> Client<C> {
   ///                    root public_key.
   pub fn with_trust_anchor(client_connection: C, trust_anchor: TrustAnchor) -> Client<C> {
     Client{ client_connection: RefCell::new(client_connection),
-            trust_anchor: trust_anchor }
+            trust_anchor: trust_anchor,
+            retry_policy: RetryPolicy::default(),
+            case_randomization: false,
+            validating: false,
+            cache: None,
+            dns64: None,
+            max_cname_depth: 0,
+            observer: None,
+            interceptors: vec![],
+            query_flags: QueryFlags::default(),
+            hosts: RefCell::new(None),
+            overrides: None,
+            limiter: None,
+            decode_mode: DecodeMode::Strict }
+  }
+
+  /// Sets the policy for retrying failed requests, by default requests are not retried.
+  ///
+  /// Timeouts, io errors and SERVFAIL or REFUSED responses are retried, other response codes such
+  ///  as NXDOMAIN are returned immediately.
+  pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+    self.retry_policy = retry_policy;
+  }
+
+  pub fn get_retry_policy(&self) -> &RetryPolicy {
+    &self.retry_policy
+  }
+
+  /// Sets the RD, CD and AD flags of the queries sent by this client, the default is
+  ///  `QueryFlags::stub()`, recursion desired. `secure_query()` always sets the AD flag and clears
+  ///  CD, as it validates the responses.
+  pub fn set_query_flags(&mut self, query_flags: QueryFlags) {
+    self.query_flags = query_flags;
+  }
+
+  pub fn get_query_flags(&self) -> &QueryFlags {
+    &self.query_flags
+  }
+
+  /// Enables 0x20 encoding, the case of the letters in the query name is randomized and the
+  ///  response must echo the name back exactly, otherwise it is rejected as a possible spoof.
+  ///  This is disabled by default, as some servers do not preserve the case of the query.
+  pub fn set_case_randomization(&mut self, case_randomization: bool) {
+    self.case_randomization = case_randomization;
+  }
+
+  pub fn is_case_randomization(&self) -> bool {
+    self.case_randomization
+  }
+
+  /// When enabled, the responses of `query()`, and the lookups built on it, are validated as with
+  ///  `secure_query()` against the trust anchor of the client, the queries set the DO bit. A
+  ///  response which fails validation is an error of `ClientErrorKind::Bogus`, which carries the
+  ///  reason, in place of the SERVFAIL of a validating recursive server. Names in the hosts are not
+  ///  validated. Default is false.
+  pub fn set_validating(&mut self, validating: bool) {
+    self.validating = validating;
+  }
+
+  pub fn is_validating(&self) -> bool {
+    self.validating
+  }
+
+  /// Enables caching of answers to `query()`, repeat queries are answered from the cache until
+  ///  the TTL of the records expires. NXDOMAIN and NODATA responses are cached for their negative
+  ///  TTL, see `Message::get_negative_ttl()`. `None`, the default, disables caching.
+  pub fn set_cache(&mut self, cache: Option<ResponseCache>) {
+    self.cache = cache.map(SharedCache::new);
+  }
+
+  /// Like `set_cache()`, but the cache may also be used by other clients, e.g. those of
+  ///  different resolvers in the same process.
+  pub fn set_shared_cache(&mut self, cache: Option<SharedCache>) {
+    self.cache = cache;
+  }
+
+  /// The cache of this client, if caching is enabled, e.g. to inspect its statistics
+  pub fn get_cache(&self) -> Option<&SharedCache> {
+    self.cache.as_ref()
+  }
+
+  /// Enables DNS64, RFC 6147, for the IPv6-only clients of a NAT64. An IN AAAA query of a name
+  ///  without AAAA records, other than excluded ones, is answered with AAAA records synthesized from
+  ///  the A records of the name, see `Dns64`. `None`, the default, disables synthesis.
+  pub fn set_dns64(&mut self, dns64: Option<Dns64>) {
+    self.dns64 = dns64;
+  }
+
+  pub fn get_dns64(&self) -> Option<&Dns64> {
+    self.dns64.as_ref()
+  }
+
+  /// When greater than zero, `query()` will follow CNAME chains which do not contain the final
+  ///  answer, re-querying the canonical name up to `max_cname_depth` times. The returned
+  ///  response contains the full chain along with the final records. Default is 0, disabled.
+  pub fn set_max_cname_depth(&mut self, max_cname_depth: usize) {
+    self.max_cname_depth = max_cname_depth;
+  }
+
+  /// Sets the observer which receives an event for each request sent, response received and
+  ///  retry, e.g. to export metrics. Responses answered from the cache are not sent, and so are
+  ///  not observed. Default is None.
+  pub fn set_observer(&mut self, observer: Option<Rc<QueryObserver>>) {
+    self.observer = observer;
+  }
+
+  /// Adds an interceptor, which is invoked with each request sent and response received by this
+  ///  client, see `MessageInterceptor`.
+  pub fn add_interceptor(&mut self, interceptor: Rc<MessageInterceptor>) {
+    self.interceptors.push(interceptor);
+  }
+
+  /// Sets the hosts which answer A and AAAA queries, and reverse lookups, before the name server
+  ///  is queried. If the hosts are watched, see `Hosts::set_watch()`, changes to the file are
+  ///  picked up. `None`, the default, disables the hosts.
+  pub fn set_hosts(&mut self, hosts: Option<Hosts>) {
+    self.hosts = RefCell::new(hosts);
+  }
+
+  /// Sets the static answers of names, which are used before the hosts, cache and name server,
+  ///  for queries of the IN class. `None`, the default, disables the overrides.
+  pub fn set_overrides(&mut self, overrides: Option<Overrides>) {
+    self.overrides = overrides;
+  }
+
+  pub fn get_overrides(&self) -> Option<&Overrides> {
+    self.overrides.as_ref()
+  }
+
+  /// Bounds the number of requests in flight, each request waits for a slot of the limiter before
+  ///  it is sent, see `QueryLimiter`. The limiter may be shared by many clients, e.g. one per
+  ///  thread, to bound the queries of the process. `None`, the default, is unlimited.
+  pub fn set_limiter(&mut self, limiter: Option<QueryLimiter>) {
+    self.limiter = limiter;
+  }
+
+  pub fn get_limiter(&self) -> Option<&QueryLimiter> {
+    self.limiter.as_ref()
+  }
+
+  /// Sets how responses are decoded, with `DecodeMode::Lenient` the records of a response which
+  ///  can not be decoded are skipped, rather than failing the request. Default is Strict.
+  pub fn set_decode_mode(&mut self, decode_mode: DecodeMode) {
+    self.decode_mode = decode_mode;
+  }
+
+  pub fn get_decode_mode(&self) -> DecodeMode {
+    self.decode_mode
+  }
+
+  /// Refreshes the popular entries of the cache which are about to expire, see
+  ///  `ResponseCache::prefetch()`. Returns the number of entries which were refreshed.
+  ///
+  /// This is meant to be called from a client of its own, sharing the cache, on a background
+  ///  thread, see `SharedCache::wait_for_prefetch()`.
+  pub fn prefetch(&self) -> usize {
+    let prefetches = match self.cache {
+      Some(ref cache) => cache.take_prefetches(),
+      None => return 0,
+    };
+
+    prefetches.iter().filter(|&&(ref name, query_class, query_type)| {
+      match self.refresh(name, query_class, query_type) {
+        Ok(..) => true,
+        Err(error) => {
+          debug!("prefetch of {} {:?} failed: {}", name, query_type, error);
+          false
+        },
+      }
+    }).count()
+  }
+
+  /// Removes all cached records for the name
+  pub fn flush_cache(&self, name: &domain::Name) {
+    if let Some(ref cache) = self.cache {
+      cache.lock().flush_name(name);
+    }
   }
 
   /// DNSSec validating query, this will return an error if the requested records can not be
@@ -89,69 +300,75 @@ impl<C: ClientConnection> Client<C> {
     //  the RRSIG is signed by the DNSKEY, the DNSKEY is signed by the DS record in the Parent
     //  zone. The key_tag is the DS record is assigned to the DNSKEY.
     let record_response = try!(self.inner_query(query_name, query_class, query_type, true));
-    {
-      // TODO, would iterators be more efficient to pass around?
-      let rrsigs: Vec<&Record> = record_response.get_answers().iter()
-                                                .chain(record_response.get_name_servers())
-                                                .filter(|rr| rr.get_rr_type() == RecordType::RRSIG).collect();
+    try!(self.validate_response(query_name, query_class, query_type, &record_response));
 
-      if rrsigs.is_empty() {
-        return Err(ClientErrorKind::NoRRSIG.into());
-      }
+    // getting here means that we looped through all records with validation
+    Ok(record_response)
+  }
 
-      // group the record sets by name and type
-      let mut rrset_types: HashSet<(domain::Name, RecordType)> = HashSet::new();
-      for rrset in record_response.get_answers().iter()
-                                  .chain(record_response.get_name_servers())
-                                  .filter(|rr| rr.get_rr_type() != RecordType::RRSIG)
-                                  .map(|rr| (rr.get_name().clone(), rr.get_rr_type())) {
-        rrset_types.insert(rrset);
-      }
+  /// Validates all of the records of the response to the query, and the proof of non-existence
+  ///  if there are no answers, see `secure_query()`
+  fn validate_response(&self, query_name: &domain::Name, query_class: DNSClass, query_type: RecordType, record_response: &Message) -> ClientResult<()> {
+    // TODO, would iterators be more efficient to pass around?
+    let rrsigs: Vec<&Record> = record_response.get_answers().iter()
+                                              .chain(record_response.get_name_servers())
+                                              .filter(|rr| rr.get_rr_type() == RecordType::RRSIG).collect();
+
+    if rrsigs.is_empty() {
+      return Err(ClientErrorKind::NoRRSIG.into());
+    }
 
-      // verify all returned rrsets
-      for &(ref name, rrset_type) in rrset_types.iter() {
-        let rrset: Vec<&Record> = record_response.get_answers().iter()
-                                                 .chain(record_response.get_name_servers())
-                                                 .filter(|rr| rr.get_rr_type() == rrset_type && rr.get_name() == name).collect();
+    // group the record sets by name and type
+    let mut rrset_types: HashSet<(domain::Name, RecordType)> = HashSet::new();
+    for rrset in record_response.get_answers().iter()
+                                .chain(record_response.get_name_servers())
+                                .filter(|rr| rr.get_rr_type() != RecordType::RRSIG)
+                                .map(|rr| (rr.get_name().clone(), rr.get_rr_type())) {
+      rrset_types.insert(rrset);
+    }
 
-        // '. DNSKEY' -> 'com. DS' -> 'com. DNSKEY' -> 'examle.com. DS' -> 'example.com. DNSKEY'
-        // 'com. DS' is signed by '. DNSKEY' which produces 'com. RRSIG', all are in the same zone, '.'
-        //  the '.' DNSKEY is signed by the well known root certificate.
-        // TODO fix rrsigs clone()
-        let proof = try!(self.recursive_query_verify(&name, rrset, rrsigs.clone(), rrset_type, query_class));
+    // verify all returned rrsets
+    for &(ref name, rrset_type) in rrset_types.iter() {
+      let rrset: Vec<&Record> = record_response.get_answers().iter()
+                                               .chain(record_response.get_name_servers())
+                                               .filter(|rr| rr.get_rr_type() == rrset_type && rr.get_name() == name).collect();
 
-        // TODO return this, also make a prettier print
-        debug!("proved existance through for {}:{:?}: {:?}", name, rrset_type, proof);
-      }
+      // '. DNSKEY' -> 'com. DS' -> 'com. DNSKEY' -> 'examle.com. DS' -> 'example.com. DNSKEY'
+      // 'com. DS' is signed by '. DNSKEY' which produces 'com. RRSIG', all are in the same zone, '.'
+      //  the '.' DNSKEY is signed by the well known root certificate.
+      // TODO fix rrsigs clone()
+      let proof = try!(self.recursive_query_verify(&name, rrset, rrsigs.clone(), rrset_type, query_class));
 
-      // at this point all records are validated, but if there are NSEC records present,
-      //  then it's a negative confirmation...
-      if record_response.get_response_code() == ResponseCode::NXDomain ||
-         record_response.get_answers().is_empty() {
-        let mut validated_nx = false;
-        for &(_, rrset_type) in rrset_types.iter() {
-          match rrset_type {
-            rt @ RecordType::NSEC => {
-              try!(self.verify_nsec(query_name, query_type, query_class,
-                 record_response.get_name_servers().iter().filter(|rr| rr.get_rr_type() == rt).collect()));
-              validated_nx = true;
-            },
-            rt @ RecordType::NSEC3 => {
-              try!(self.verify_nsec3(query_name, query_type, query_class,
-                record_response.get_name_servers().iter().filter(|rr| rr.get_rr_type() == RecordType::SOA).next(),
-                record_response.get_name_servers().iter().filter(|rr| rr.get_rr_type() == rt).collect()));
-              validated_nx = true;
-            },
-            _ => (),
-          }
-        }
+      // TODO return this, also make a prettier print
+      debug!("proved existance through for {}:{:?}: {:?}", name, rrset_type, proof);
+    }
 
-        if !validated_nx { return Err(ClientErrorKind::Message("no nsec(3) records to validate nxdomain").into()) }
+    // at this point all records are validated, but if there are NSEC records present,
+    //  then it's a negative confirmation...
+    if record_response.get_response_code() == ResponseCode::NXDomain ||
+       record_response.get_answers().is_empty() {
+      let mut validated_nx = false;
+      for &(_, rrset_type) in rrset_types.iter() {
+        match rrset_type {
+          rt @ RecordType::NSEC => {
+            try!(self.verify_nsec(query_name, query_type, query_class,
+               record_response.get_name_servers().iter().filter(|rr| rr.get_rr_type() == rt).collect()));
+            validated_nx = true;
+          },
+          rt @ RecordType::NSEC3 => {
+            try!(self.verify_nsec3(query_name, query_type, query_class,
+              record_response.get_name_servers().iter().filter(|rr| rr.get_rr_type() == RecordType::SOA).next(),
+              record_response.get_name_servers().iter().filter(|rr| rr.get_rr_type() == rt).collect()));
+            validated_nx = true;
+          },
+          _ => (),
+        }
       }
+
+      if !validated_nx { return Err(ClientErrorKind::Message("no nsec(3) records to validate nxdomain").into()) }
     }
 
-    // getting here means that we looped through all records with validation
-    Ok(record_response)
+    Ok(())
   }
 
   /// Verifies a record set against the supplied signatures, looking up the DNSKey chain.
@@ -473,8 +690,8 @@ impl<C: ClientConnection> Client<C> {
 
   /// A *classic* DNS query, i.e. does not perform and DNSSec operations
   ///
-  /// *Note* As of now, this will not recurse on PTR or CNAME record responses, that is up to
-  ///        the caller.
+  /// *Note* This will only follow CNAME chains if enabled with `set_max_cname_depth()`, and
+  ///        never recurse on PTR record responses, that is up to the caller.
   ///
   /// # Arguments
   ///
@@ -482,154 +699,791 @@ impl<C: ClientConnection> Client<C> {
   /// * `query_class` - most likely this should always be DNSClass::IN
   /// * `query_type` - record type to lookup
   pub fn query(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
-    self.inner_query(name, query_class, query_type, false)
+    let response = try!(self.cached_query(name, query_class, query_type));
+
+    match query_type {
+      RecordType::CNAME | RecordType::ANY | RecordType::AXFR | RecordType::IXFR => Ok(response),
+      _ if self.max_cname_depth == 0 => Ok(response),
+      _ => self.follow_cnames(name, query_class, query_type, response, self.max_cname_depth),
+    }
   }
 
-  fn inner_query(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType, secure: bool) -> ClientResult<Message> {
-    debug!("querying: {} {:?}", name, query_type);
+  /// Queries each of the names in turn, returning the first response with answers, e.g. for the
+  ///  names of a search list, see `ResolverConfig::search_names()`. If no name has answers, the
+  ///  response of the last name is returned. Errors are returned immediately, as the remaining
+  ///  names would likely fail in the same way.
+  ///
+  /// # Arguments
+  ///
+  /// * `names` - the names to query, in order
+  /// * `query_class` - most likely this should always be DNSClass::IN
+  /// * `query_type` - record type to lookup
+  pub fn query_first(&self, names: &[domain::Name], query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
+    let mut last: Option<Message> = None;
 
-    // build the message
-    let mut message: Message = Message::new();
-    let id: u16 = rand::random();
-    // TODO make recursion a parameter
-    message.id(id).message_type(MessageType::Query).op_code(OpCode::Query).recursion_desired(true);
+    for name in names {
+      let response = try!(self.query(name, query_class, query_type));
+      if response.get_response_code() == ResponseCode::NoError && !response.get_answers().is_empty() {
+        return Ok(response)
+      }
 
-    // Extended dns
-    let mut edns: Edns = Edns::new();
+      debug!("no answers for {}: {:?}", name, response.get_response_code());
+      last = Some(response);
+    }
 
-    if secure {
-      edns.set_dnssec_ok(true);
-      message.authentic_data(true);
-      message.checking_disabled(false);
+    last.ok_or(ClientErrorKind::Message("no names to query").into())
+  }
+
+  /// follows the CNAME chain in the response, requerying for the end of the chain if the final
+  ///  answer was not included.
+  fn follow_cnames(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType, mut response: Message, max_depth: usize) -> ClientResult<Message> {
+    let mut visited: HashSet<domain::Name> = HashSet::new();
+    let mut current: domain::Name = name.clone();
+    let mut depth: usize = 0;
+
+    loop {
+      // walk the chain as far as the answers allow
+      loop {
+        if !visited.insert(current.clone()) { return Err(ClientErrorKind::CnameLoop(current).into()) }
+
+        let answered = response.get_answers().iter().any(|r| r.get_rr_type() == query_type && r.get_name() == &current);
+        if answered { return Ok(response) }
+
+        let next = response.get_answers().iter().filter(|r| r.get_name() == &current).filter_map(|r| {
+          if let RData::CNAME(ref target) = *r.get_rdata() { Some(target.clone()) } else { None }
+        }).next();
+
+        match next {
+          Some(target) => current = target,
+          None => break,
+        }
+      }
+
+      // no cname was followed, nothing more to look for
+      if &current == name { return Ok(response) }
+
+      depth += 1;
+      if depth > max_depth { return Err(ClientErrorKind::CnameDepthExceeded(max_depth).into()) }
+
+      debug!("following cname chain to: {}", current);
+      let next_response = try!(self.cached_query(&current, query_class, query_type));
+
+      // visited will be checked again from the start of this name
+      visited.remove(&current);
+      for answer in next_response.get_answers() {
+        if !response.get_answers().contains(answer) { response.add_answer(answer.clone()); }
+      }
+
+      // a negative answer for the canonical name is the answer for the query
+      if next_response.get_answers().is_empty() {
+        response.response_code(next_response.get_response_code());
+        for ns in next_response.get_name_servers() { response.add_name_server(ns.clone()); }
+        return Ok(response)
+      }
     }
+  }
 
-    edns.set_max_payload(1500);
-    edns.set_version(0);
+  fn cached_query(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
+    if let Some(ref dns64) = self.dns64 {
+      if query_class == DNSClass::IN && query_type == RecordType::AAAA {
+        return self.dns64_query(dns64, name)
+      }
+    }
 
-    message.set_edns(edns);
+    self.hosts_or_cached_query(name, query_class, query_type)
+  }
 
-    // add the query
-    let mut query: Query = Query::new();
-    query.name(name.clone()).query_class(query_class).query_type(query_type);
-    message.add_query(query);
+  /// RFC 6147, the AAAA response is returned unless it has no usable AAAA records, in which case
+  ///  AAAA records are synthesized from the A records of the name
+  fn dns64_query(&self, dns64: &Dns64, name: &domain::Name) -> ClientResult<Message> {
+    let response = try!(self.hosts_or_cached_query(name, DNSClass::IN, RecordType::AAAA));
+
+    // 5.1.2, a response other than NOERROR or NXDOMAIN is treated as no records, NXDOMAIN isn't
+    //  synthesized
+    if response.get_response_code() == ResponseCode::NXDomain { return Ok(response) }
+
+    let usable = response.get_answers().iter().any(|r| match *r.get_rdata() {
+      RData::AAAA(ref addr) => !dns64.is_excluded_aaaa(addr),
+      _ => false,
+    });
+    if usable && response.get_response_code() == ResponseCode::NoError { return Ok(response) }
+
+    let a_response = try!(self.hosts_or_cached_query(name, DNSClass::IN, RecordType::A));
+    if a_response.get_response_code() != ResponseCode::NoError { return Ok(response) }
+
+    // 5.1.7, the CNAMEs of the chain are kept, the TTL is that of the A record
+    let mut answers: Vec<Record> = vec![];
+    for record in a_response.get_answers() {
+      match *record.get_rdata() {
+        RData::A(ref addr) if !dns64.is_excluded_ipv4(addr) => {
+          let mut aaaa = record.clone();
+          aaaa.rr_type(RecordType::AAAA).rdata(RData::AAAA(dns64.synthesize(addr)));
+          answers.push(aaaa);
+        },
+        RData::CNAME(..) => answers.push(record.clone()),
+        _ => (),
+      }
+    }
 
-    self.send_message(&message)
+    if !answers.iter().any(|r| r.get_rr_type() == RecordType::AAAA) { return Ok(response) }
+
+    debug!("dns64 synthesized: {}", name);
+    let mut synthesized = Self::cached_response(name, DNSClass::IN, RecordType::AAAA, ResponseCode::NoError);
+    for answer in answers { synthesized.add_answer(answer); }
+    Ok(synthesized)
   }
 
-  /// Sends a record to create on the server, this will fail if the record exists (atomicity
-  ///  depends on the server)
-  ///
-  /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
-  ///
-  /// ```text
-  ///  2.4.3 - RRset Does Not Exist
-  ///
-  ///   No RRs with a specified NAME and TYPE (in the zone and class denoted
-  ///   by the Zone Section) can exist.
-  ///
-  ///   For this prerequisite, a requestor adds to the section a single RR
-  ///   whose NAME and TYPE are equal to that of the RRset whose nonexistence
-  ///   is required.  The RDLENGTH of this record is zero (0), and RDATA
-  ///   field is therefore empty.  CLASS must be specified as NONE in order
-  ///   to distinguish this condition from a valid RR whose RDLENGTH is
-  ///   naturally zero (0) (for example, the NULL RR).  TTL must be specified
-  ///   as zero (0).
-  ///
-  /// 2.5.1 - Add To An RRset
-  ///
-  ///    RRs are added to the Update Section whose NAME, TYPE, TTL, RDLENGTH
-  ///    and RDATA are those being added, and CLASS is the same as the zone
-  ///    class.  Any duplicate RRs will be silently ignored by the primary
-  ///    master.
-  /// ```
+  fn hosts_or_cached_query(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
+    if let Some((response_code, answers)) = self.overrides.as_ref().and_then(|o| if query_class == DNSClass::IN { o.lookup(name, query_type) } else { None }) {
+      debug!("override hit: {} {:?}: {:?}", name, query_type, response_code);
+      let mut response = Self::cached_response(name, query_class, query_type, response_code);
+      for answer in answers { response.add_answer(answer); }
+      return Ok(response)
+    }
+
+    if let Some(response) = self.hosts_query(name, query_class, query_type) {
+      debug!("hosts hit: {} {:?}", name, query_type);
+      return Ok(response)
+    }
+
+    if let Some(answers) = self.cache.as_ref().and_then(|c| c.get(name, query_class, query_type)) {
+      debug!("cache hit: {} {:?}", name, query_type);
+      let mut response = Self::cached_response(name, query_class, query_type, ResponseCode::NoError);
+      for answer in answers { response.add_answer(answer); }
+      return Ok(response)
+    }
+
+    if let Some((response_code, soa)) = self.cache.as_ref().and_then(|c| c.get_negative(name, query_class, query_type)) {
+      debug!("negative cache hit: {} {:?}: {:?}", name, query_type, response_code);
+      let mut response = Self::cached_response(name, query_class, query_type, response_code);
+      response.add_name_server(soa);
+      return Ok(response)
+    }
+
+    let result = self.refresh(name, query_class, query_type);
+
+    // RFC 8767, if the server can't be reached or fails, expired records may be served
+    let failed = match result {
+      Ok(ref response) => response.get_response_code() == ResponseCode::ServFail,
+      Err(..) => true,
+    };
+
+    if failed {
+      if let Some(answers) = self.cache.as_ref().and_then(|c| c.get_stale(name, query_class, query_type)) {
+        debug!("serving stale: {} {:?}", name, query_type);
+        let mut response = Self::cached_response(name, query_class, query_type, ResponseCode::NoError);
+        for answer in answers { response.add_answer(answer); }
+        return Ok(response)
+      }
+    }
+
+    result
+  }
+
+  /// Queries the server, bypassing the cache and hosts, the response replaces the cached entry of
+  ///  the query. Used to prefetch popular entries, see `prefetch()`.
   ///
   /// # Arguments
   ///
-  /// * `record` - the name of the record to create
-  /// * `zone_origin` - the zone name to update, i.e. SOA name
-  /// * `signer` - the signer, with private key, to use to sign the request
-  ///
-  /// The update must go to a zone authority (i.e. the server used in the ClientConnection)
-  pub fn create(&self,
-                record: Record,
-                zone_origin: domain::Name,
-                signer: &Signer) -> ClientResult<Message> {
-    assert!(zone_origin.zone_of(record.get_name()));
+  /// * `name` - the label to lookup
+  /// * `query_class` - most likely this should always be DNSClass::IN
+  /// * `query_type` - record type to lookup
+  pub fn refresh(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
+    let response = try!(self.inner_query(name, query_class, query_type, self.validating));
+    if self.validating {
+      if let Err(error) = self.validate_response(name, query_class, query_type, &response) {
+        debug!("validation failed: {} {:?}: {}", name, query_type, error);
+        return Err(ClientErrorKind::Bogus(name.clone(), error.to_string()).into())
+      }
+    }
 
-    // for updates, the query section is used for the zone
-    let mut zone: Query = Query::new();
-    zone.name(zone_origin).query_class(record.get_dns_class()).query_type(RecordType::SOA);
+    if let Some(ref cache) = self.cache {
+      let mut cache = cache.lock();
+      if response.get_response_code() == ResponseCode::NoError && !response.get_answers().is_empty() {
+        cache.insert(name, query_class, query_type, response.get_answers().to_vec());
+      } else if response.get_negative_ttl().is_some() {
+        // the negative ttl is of the SOA, a response without one is not cached
+        let soa = response.get_name_servers().iter().find(|r| if let RData::SOA(..) = *r.get_rdata() { true } else { false });
+        if let Some(soa) = soa {
+          cache.insert_negative(name, query_class, query_type, response.get_response_code(), soa.clone());
+        }
+      }
+    }
 
-    // build the message
-    let mut message: Message = Message::new();
-    message.id(rand::random()).message_type(MessageType::Query).op_code(OpCode::Update).recursion_desired(false);
-    message.add_zone(zone);
+    Ok(response)
+  }
 
-    let mut prerequisite = Record::with(record.get_name().clone(), record.get_rr_type(), 0);
-    prerequisite.dns_class(DNSClass::NONE);
-    message.add_pre_requisite(prerequisite);
-    message.add_update(record);
+  /// answers A and AAAA queries from the hosts, None if the hosts do not have the name, a name in
+  ///  the hosts without addresses of the type has no data
+  fn hosts_query(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType) -> Option<Message> {
+    if query_class != DNSClass::IN { return None }
+    if query_type != RecordType::A && query_type != RecordType::AAAA { return None }
 
-    // Extended dns
-    let mut edns: Edns = Edns::new();
+    let mut hosts = self.hosts.borrow_mut();
+    let hosts = match *hosts {
+      Some(ref mut hosts) => hosts,
+      None => return None,
+    };
 
-    // if secure {
-    //   edns.set_dnssec_ok(true);
-    //   message.authentic_data(true);
-    //   message.checking_disabled(false);
-    // }
+    hosts.refresh();
+    hosts.lookup(name, query_type).map(|addrs| {
+      let mut response = Self::cached_response(name, query_class, query_type, ResponseCode::NoError);
+      for addr in addrs {
+        let rdata = match addr {
+          IpAddr::V4(ip) => RData::A(ip),
+          IpAddr::V6(ip) => RData::AAAA(ip),
+        };
+        response.add_answer(Record::new().name(name.clone()).ttl(0).rr_type(query_type).dns_class(query_class).rdata(rdata).clone());
+      }
+      response
+    })
+  }
 
-    edns.set_max_payload(1500);
-    edns.set_version(0);
+  /// builds a response for the cached records
+  fn cached_response(name: &domain::Name, query_class: DNSClass, query_type: RecordType, response_code: ResponseCode) -> Message {
+    let mut query: Query = Query::new();
+    query.name(name.clone()).query_class(query_class).query_type(query_type);
 
-    message.set_edns(edns);
+    let mut message: Message = Message::new();
+    message.id(rand::random()).message_type(MessageType::Response).op_code(OpCode::Query)
+           .recursion_desired(true).recursion_available(true).response_code(response_code).add_query(query);
 
-    // after all other updates to the message, sign it.
-    message.sign(signer, UTC::now().timestamp() as u32);
+    message
+  }
 
-    self.send_message(&message)
+  /// Performs a query for the specified `Query`, the message id is assigned by the client.
+  ///
+  /// *Note* As of now, this will not recurse on PTR or CNAME record responses, that is up to
+  ///        the caller.
+  ///
+  /// # Arguments
+  ///
+  /// * `query` - the name, class and type to lookup
+  pub fn lookup(&self, query: Query) -> ClientResult<Message> {
+    let query_flags = self.query_flags;
+    self.lookup_with_flags(query, query_flags)
   }
 
-  /// Appends a record to an existing rrset, optionally require the rrset to exis (atomicity
-  ///  depends on the server)
+  /// Like `lookup()`, with the header flags of this request instead of those of the client
   ///
-  /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+  /// # Arguments
   ///
-  /// ```text
-  /// 2.4.1 - RRset Exists (Value Independent)
+  /// * `query` - the query to send
+  /// * `query_flags` - the RD, CD and AD flags for the request
+  pub fn lookup_with_flags(&self, query: Query, query_flags: QueryFlags) -> ClientResult<Message> {
+    let message = self.build_query_message(query, false, query_flags);
+    self.send_query(&message)
+  }
+
+  /// Sends an arbitrary `Message` to the server, returning the decoded response.
   ///
-  ///   At least one RR with a specified NAME and TYPE (in the zone and class
-  ///   specified in the Zone Section) must exist.
+  /// The message id is always replaced by a random id, the response is checked to match it.
   ///
-  ///   For this prerequisite, a requestor adds to the section a single RR
-  ///   whose NAME and TYPE are equal to that of the zone RRset whose
-  ///   existence is required.  RDLENGTH is zero and RDATA is therefore
-  ///   empty.  CLASS must be specified as ANY to differentiate this
-  ///   condition from that of an actual RR whose RDLENGTH is naturally zero
-  ///   (0) (e.g., NULL).  TTL is specified as zero (0).
+  /// # Arguments
   ///
-  /// 2.5.1 - Add To An RRset
+  /// * `message` - the message to send, e.g. a query with custom flags or EDNS options
+  pub fn send(&self, mut message: Message) -> ClientResult<Message> {
+    message.id(rand::random());
+    self.send_message(&message)
+  }
+
+  /// Looks up the IPv4 and IPv6 addresses of the name, following any CNAME chain. The addresses
+  ///  are deduplicated and returned in the specified order.
   ///
-  ///    RRs are added to the Update Section whose NAME, TYPE, TTL, RDLENGTH
-  ///    and RDATA are those being added, and CLASS is the same as the zone
-  ///    class.  Any duplicate RRs will be silently ignored by the primary
-  ///    master.
-  /// ```
+  /// An error is only returned if neither of the A or AAAA queries succeeded, a name without any
+  ///  addresses returns an empty list.
   ///
   /// # Arguments
   ///
-  /// * `record` - the record to append to an RRSet
-  /// * `zone_origin` - the zone name to update, i.e. SOA name
-  /// * `must_exist` - if true, the request will fail if the record does not exist
-  /// * `signer` - the signer, with private key, to use to sign the request
+  /// * `name` - the name to lookup
+  /// * `order` - the order of the address families in the result
+  pub fn lookup_ip(&self, name: &domain::Name, order: AddressOrder) -> ClientResult<Vec<IpAddr>> {
+    self.lookup_ip_with_strategy(name, LookupIpStrategy::Ipv4AndIpv6, order)
+  }
+
+  /// Like `lookup_ip()`, only the address families of the strategy are queried. With a fallback
+  ///  strategy, e.g. `Ipv6ThenIpv4`, the second family is only queried if the first has no
+  ///  addresses, or its query failed.
   ///
-  /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
-  /// the rrset does not exist and must_exist is false, then the RRSet will be created.
+  /// # Arguments
+  ///
+  /// * `name` - the name to lookup
+  /// * `strategy` - the address families to query
+  /// * `order` - the order of the address families in the result
+  pub fn lookup_ip_with_strategy(&self, name: &domain::Name, strategy: LookupIpStrategy, order: AddressOrder) -> ClientResult<Vec<IpAddr>> {
+    match strategy {
+      LookupIpStrategy::Ipv4Only => self.lookup_addresses(name, RecordType::A).map(|ipv4| order.merge(ipv4, vec![])),
+      LookupIpStrategy::Ipv6Only => self.lookup_addresses(name, RecordType::AAAA).map(|ipv6| order.merge(vec![], ipv6)),
+      LookupIpStrategy::Ipv4AndIpv6 => self.lookup_both(name, order),
+      LookupIpStrategy::Ipv6ThenIpv4 => self.lookup_either(name, RecordType::AAAA, RecordType::A).map(|addrs| order.merge(vec![], addrs)),
+      LookupIpStrategy::Ipv4ThenIpv6 => self.lookup_either(name, RecordType::A, RecordType::AAAA).map(|addrs| order.merge(addrs, vec![])),
+    }
+  }
+
+  /// the addresses of the first type, or else of the second
+  fn lookup_either(&self, name: &domain::Name, first: RecordType, second: RecordType) -> ClientResult<Vec<IpAddr>> {
+    let first_result = self.lookup_addresses(name, first);
+    match first_result {
+      Ok(ref addrs) if !addrs.is_empty() => return Ok(addrs.clone()),
+      Err(ref error) => warn!("{:?} lookup of {} failed: {}", first, name, error),
+      _ => (),
+    }
+
+    match (first_result, self.lookup_addresses(name, second)) {
+      (_, Ok(addrs)) => Ok(addrs),
+      (Ok(addrs), Err(error)) => {
+        warn!("{:?} lookup of {} failed: {}", second, name, error);
+        Ok(addrs)
+      },
+      (Err(_), Err(error)) => Err(error),
+    }
+  }
+
+  fn lookup_both(&self, name: &domain::Name, order: AddressOrder) -> ClientResult<Vec<IpAddr>> {
+    let ipv4 = self.lookup_addresses(name, RecordType::A);
+    let ipv6 = self.lookup_addresses(name, RecordType::AAAA);
+
+    match (ipv4, ipv6) {
+      (Ok(ipv4), Ok(ipv6)) => Ok(order.merge(ipv4, ipv6)),
+      (Ok(ipv4), Err(error)) => {
+        warn!("AAAA lookup of {} failed: {}", name, error);
+        Ok(order.merge(ipv4, vec![]))
+      },
+      (Err(error), Ok(ipv6)) => {
+        warn!("A lookup of {} failed: {}", name, error);
+        Ok(order.merge(vec![], ipv6))
+      },
+      (Err(error), Err(_)) => Err(error),
+    }
+  }
+
+  /// Looks up the names of the address, i.e. the PTR records of the in-addr.arpa. or ip6.arpa.
+  ///  name for the address. CNAMEs are followed, as used for classless delegation of in-addr.arpa.,
+  ///  [RFC 2317](https://tools.ietf.org/html/rfc2317).
+  ///
+  /// # Arguments
+  ///
+  /// * `addr` - the address to lookup
+  pub fn reverse_lookup(&self, addr: IpAddr) -> ClientResult<Vec<domain::Name>> {
+    if let Some(ref mut hosts) = *self.hosts.borrow_mut() {
+      hosts.refresh();
+      if let Some(names) = hosts.reverse_lookup(&addr) { return Ok(names.to_vec()) }
+    }
+
+    let name = domain::Name::from(addr);
+    let max_depth = if self.max_cname_depth == 0 { DEFAULT_CNAME_DEPTH } else { self.max_cname_depth };
+
+    let response = try!(self.cached_query(&name, DNSClass::IN, RecordType::PTR));
+    let response = try!(self.follow_cnames(&name, DNSClass::IN, RecordType::PTR, response, max_depth));
+
+    Ok(response.get_answers().iter().filter_map(|record| {
+      if let RData::PTR(ref target) = *record.get_rdata() { Some(target.clone()) } else { None }
+    }).collect())
+  }
+
+  /// Looks up the mail exchanges of the name, sorted by preference. Exchanges of equal preference
+  ///  are returned in a random order, see `sort_by_preference()`.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - the domain to lookup
+  /// * `resolve_addresses` - if true, the addresses of each exchange are resolved, using the
+  ///                         additional section of the response when present
+  pub fn mx_lookup(&self, name: &domain::Name, resolve_addresses: bool) -> ClientResult<Vec<MailExchange>> {
+    let max_depth = if self.max_cname_depth == 0 { DEFAULT_CNAME_DEPTH } else { self.max_cname_depth };
+
+    let response = try!(self.cached_query(name, DNSClass::IN, RecordType::MX));
+    let response = try!(self.follow_cnames(name, DNSClass::IN, RecordType::MX, response, max_depth));
+
+    let mut exchanges: Vec<MailExchange> = response.get_answers().iter().filter_map(|record| {
+      if let RData::MX(ref mx) = *record.get_rdata() { Some(MailExchange::from(mx)) } else { None }
+    }).collect();
+
+    mail_exchange::sort_by_preference(&mut exchanges);
+
+    if resolve_addresses {
+      for exchange in exchanges.iter_mut() {
+        let mut addresses: Vec<IpAddr> = response.get_additional().iter()
+          .filter(|record| record.get_name() == exchange.get_exchange())
+          .filter_map(|record| match *record.get_rdata() {
+            RData::A(ip) => Some(IpAddr::V4(ip)),
+            RData::AAAA(ip) => Some(IpAddr::V6(ip)),
+            _ => None,
+          }).collect();
+
+        if addresses.is_empty() {
+          addresses = match self.lookup_ip(exchange.get_exchange(), AddressOrder::default()) {
+            Ok(addresses) => addresses,
+            Err(error) => {
+              warn!("could not resolve mail exchange {}: {}", exchange.get_exchange(), error);
+              vec![]
+            },
+          };
+        }
+
+        exchange.set_addresses(addresses);
+      }
+    }
+
+    Ok(exchanges)
+  }
+
+  /// Looks up the SRV records of the service, `_service._protocol.name`, use
+  ///  `ServiceLookup::selection()` to order the targets for connection attempts.
+  ///
+  /// [RFC 2782](https://tools.ietf.org/html/rfc2782), DNS SRV RR, February 2000
+  ///
+  /// # Arguments
+  ///
+  /// * `service` - the symbolic name of the service, without the leading underscore, e.g. `ldap`
+  /// * `protocol` - the protocol, without the leading underscore, e.g. `tcp`
+  /// * `name` - the domain of the service
+  pub fn srv_lookup(&self, service: &str, protocol: &str, name: &domain::Name) -> ClientResult<ServiceLookup> {
+    let srv_name = name.prepend_label(Rc::new(format!("_{}", protocol)))
+                       .prepend_label(Rc::new(format!("_{}", service)));
+    let max_depth = if self.max_cname_depth == 0 { DEFAULT_CNAME_DEPTH } else { self.max_cname_depth };
+
+    let response = try!(self.cached_query(&srv_name, DNSClass::IN, RecordType::SRV));
+    let response = try!(self.follow_cnames(&srv_name, DNSClass::IN, RecordType::SRV, response, max_depth));
+
+    let records: Vec<SRV> = response.get_answers().iter().filter_map(|record| {
+      if let RData::SRV(ref srv) = *record.get_rdata() { Some(srv.clone()) } else { None }
+    }).collect();
+
+    Ok(ServiceLookup::new(srv_name, records))
+  }
+
+  /// Queries the CH TXT diagnostic name of the server, e.g. `version.bind.`, returning the text
+  ///  of each TXT record. The response is never cached, as it identifies the instance answering.
+  ///
+  /// # Arguments
+  ///
+  /// * `chaos_query` - the name to query
+  pub fn chaos_query(&self, chaos_query: ChaosQuery) -> ClientResult<Vec<String>> {
+    let response = try!(self.inner_query(&chaos_query.to_name(), DNSClass::CH, RecordType::TXT, false));
+    if response.get_response_code() != ResponseCode::NoError { return Err(error_response(&response)) }
+
+    Ok(response.get_answers().iter().filter_map(|record| {
+      if let RData::TXT(ref txt) = *record.get_rdata() { Some(txt.get_txt_data().concat()) } else { None }
+    }).collect())
+  }
+
+  /// returns the addresses of the A or AAAA records for the name
+  fn lookup_addresses(&self, name: &domain::Name, query_type: RecordType) -> ClientResult<Vec<IpAddr>> {
+    let max_depth = if self.max_cname_depth == 0 { DEFAULT_CNAME_DEPTH } else { self.max_cname_depth };
+
+    let response = try!(self.cached_query(name, DNSClass::IN, query_type));
+    let response = try!(self.follow_cnames(name, DNSClass::IN, query_type, response, max_depth));
+
+    Ok(response.get_answers().iter().filter_map(|record| {
+      match *record.get_rdata() {
+        RData::A(ip) => Some(IpAddr::V4(ip)),
+        RData::AAAA(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+      }
+    }).collect())
+  }
+
+  fn inner_query(&self, name: &domain::Name, query_class: DNSClass, query_type: RecordType, secure: bool) -> ClientResult<Message> {
+    // add the query
+    let mut query: Query = Query::new();
+    query.name(name.clone()).query_class(query_class).query_type(query_type);
+
+    let message = self.build_query_message(query, secure, self.query_flags);
+    self.send_query(&message)
+  }
+
+  fn build_query_message(&self, mut query: Query, secure: bool, query_flags: QueryFlags) -> Message {
+    debug!("querying: {} {:?}", query.get_name(), query.get_query_type());
+
+    if self.case_randomization {
+      let name = query.get_name().randomize_label_case();
+      query.name(name);
+    }
+
+    // build the message
+    let mut message: Message = Message::new();
+    let id: u16 = rand::random();
+    message.id(id).message_type(MessageType::Query).op_code(OpCode::Query);
+    query_flags.apply(&mut message);
+
+    // Extended dns
+    let mut edns: Edns = Edns::new();
+
+    if secure {
+      edns.set_dnssec_ok(true);
+      message.authentic_data(true);
+      message.checking_disabled(false);
+    }
+
+    edns.set_max_payload(1500);
+    edns.set_version(0);
+
+    message.set_edns(edns);
+    message.add_query(query);
+
+    message
+  }
+
+  /// Performs a full zone transfer, AXFR, of the zone. The records are returned by the iterator as
+  ///  each message of the transfer is received, so the entire zone never needs to be in memory.
+  ///
+  /// The iterator yields the starting SOA record, followed by the remaining records of the zone.
+  ///  The trailing SOA, which marks the end of the transfer, is not returned.
+  ///
+  /// [RFC 5936](https://tools.ietf.org/html/rfc5936), DNS Zone Transfer Protocol (AXFR), June 2010
+  ///
+  /// ```text
+  /// 2.2.  AXFR Response
+  ///
+  ///    The AXFR server MUST begin an AXFR response by sending the SOA RR of
+  ///    the zone, and it MUST end it by sending the same SOA RR.
+  /// ```
+  ///
+  /// *Note* the transfer requires a connection which supports multiple message responses, i.e.
+  ///        TcpClientConnection.
+  ///
+  /// # Arguments
+  ///
+  /// * `zone_origin` - the zone to transfer
+  /// * `dns_class` - the class of the zone, most likely DNSClass::IN
+  pub fn zone_transfer(&self, zone_origin: &domain::Name, dns_class: DNSClass) -> ClientResult<ZoneTransfer<C>> {
+    let mut query: Query = Query::new();
+    query.name(zone_origin.clone()).query_class(dns_class).query_type(RecordType::AXFR);
+
+    let mut message: Message = Message::new();
+    message.id(rand::random()).message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    let response = try!(self.send_message(&message));
+    let stream = try!(TransferStream::new(self, response));
+
+    Ok(ZoneTransfer{ stream: stream, soa: None, done: false })
+  }
+
+  /// Performs an incremental zone transfer, IXFR, of the zone from the version of the `soa`.
+  ///
+  /// If the server does not support IXFR, i.e. it responds with an error or no records, a full
+  ///  zone transfer is performed instead and `IncrementalTransfer::Full` is returned.
+  ///
+  /// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer in DNS, August 1996
+  ///
+  /// ```text
+  /// 3. Query Format
+  ///
+  ///    The IXFR query packet format is the same as that of a normal DNS
+  ///    query, but with the query type being IXFR and the authority section
+  ///    containing the SOA record of client's version of the zone.
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `zone_origin` - the zone to transfer
+  /// * `dns_class` - the class of the zone, most likely DNSClass::IN
+  /// * `soa` - the SOA of the version of the zone held by the client
+  pub fn incremental_transfer(&self, zone_origin: &domain::Name, dns_class: DNSClass, soa: Record) -> ClientResult<IncrementalTransfer> {
+    let current_serial = match soa_serial(&soa) {
+      Some(serial) => serial,
+      None => return Err(ClientErrorKind::Message("an SOA record is required for IXFR").into()),
+    };
+
+    let mut query: Query = Query::new();
+    query.name(zone_origin.clone()).query_class(dns_class).query_type(RecordType::IXFR);
+
+    let mut message: Message = Message::new();
+    message.id(rand::random()).message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+    message.add_name_server(soa);
+
+    let response = try!(self.send_message(&message));
+
+    if response.get_response_code() != ResponseCode::NoError || response.get_answers().is_empty() {
+      debug!("IXFR not available for {}: {:?}, falling back to AXFR", zone_origin, response.get_response_code());
+      return self.full_transfer(zone_origin, dns_class)
+    }
+
+    // a single SOA is returned when the zone is up to date
+    if response.get_answers().len() == 1 {
+      if let Some(serial) = soa_serial(&response.get_answers()[0]) {
+        if compare_serial(serial, current_serial) != Some(Ordering::Greater) {
+          return Ok(IncrementalTransfer::UpToDate(response.get_answers()[0].clone()))
+        }
+      }
+    }
+
+    parse_ixfr(try!(TransferStream::new(self, response)))
+  }
+
+  /// collects a full zone transfer
+  fn full_transfer(&self, zone_origin: &domain::Name, dns_class: DNSClass) -> ClientResult<IncrementalTransfer> {
+    let mut records: Vec<Record> = try!(try!(self.zone_transfer(zone_origin, dns_class)).collect());
+    if records.is_empty() { return Err(ClientErrorKind::Message("zone transfer contained no records").into()) }
+
+    let soa = records.remove(0);
+    Ok(IncrementalTransfer::Full{ soa: soa, records: records })
+  }
+
+  /// Sends a NOTIFY for the zone to the server, informing a secondary that the zone has changed.
+  ///
+  /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+  ///
+  /// ```text
+  /// 3.7. A NOTIFY request has QDCOUNT>0, ANCOUNT>=0, AUCOUNT>=0,
+  ///      ADCOUNT>=0.  If ANCOUNT>0, then the answer section represents an
+  ///      unsecure hint at the new RRset for this <QNAME,QCLASS,QTYPE>.  A
+  ///      slave receiving such a hint is free to treat equivilence of this
+  ///      answer section with its local data as a "no further work needs to
+  ///      be done" indication.
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `zone_origin` - the zone which has changed
+  /// * `dns_class` - the class of the zone, most likely DNSClass::IN
+  /// * `soa` - optionally the new SOA of the zone, sent as a hint in the answer section
+  pub fn notify(&self, zone_origin: &domain::Name, dns_class: DNSClass, soa: Option<Record>) -> ClientResult<Message> {
+    let mut builder = MessageBuilder::notify(zone_origin.clone(), dns_class);
+    if let Some(soa) = soa { builder.answer(soa); }
+
+    self.send_message(&builder.build())
+  }
+
+  /// Sends a NOTIFY for the zone to each of the secondaries, see `notify()`. The responses are
+  ///  returned in the same order as the secondaries, a failure to notify one secondary does not
+  ///  stop the others from being notified.
+  ///
+  /// # Arguments
+  ///
+  /// * `secondaries` - connections to each of the secondaries to notify
+  /// * `zone_origin` - the zone which has changed
+  /// * `dns_class` - the class of the zone, most likely DNSClass::IN
+  /// * `soa` - optionally the new SOA of the zone, sent as a hint in the answer section
+  pub fn notify_all<I>(secondaries: I, zone_origin: &domain::Name, dns_class: DNSClass, soa: Option<Record>) -> Vec<ClientResult<Message>>
+  where I: IntoIterator<Item=C> {
+    secondaries.into_iter()
+               .map(|connection| {
+                 let client = Client::new(connection);
+                 let result = client.notify(zone_origin, dns_class, soa.clone());
+                 if let Err(ref error) = result { warn!("notify of {} failed: {}", zone_origin, error); }
+                 result
+               })
+               .collect()
+  }
+
+  /// Sends a record to create on the server, this will fail if the record exists (atomicity
+  ///  depends on the server)
+  ///
+  /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+  ///
+  /// ```text
+  ///  2.4.3 - RRset Does Not Exist
+  ///
+  ///   No RRs with a specified NAME and TYPE (in the zone and class denoted
+  ///   by the Zone Section) can exist.
+  ///
+  ///   For this prerequisite, a requestor adds to the section a single RR
+  ///   whose NAME and TYPE are equal to that of the RRset whose nonexistence
+  ///   is required.  The RDLENGTH of this record is zero (0), and RDATA
+  ///   field is therefore empty.  CLASS must be specified as NONE in order
+  ///   to distinguish this condition from a valid RR whose RDLENGTH is
+  ///   naturally zero (0) (for example, the NULL RR).  TTL must be specified
+  ///   as zero (0).
+  ///
+  /// 2.5.1 - Add To An RRset
+  ///
+  ///    RRs are added to the Update Section whose NAME, TYPE, TTL, RDLENGTH
+  ///    and RDATA are those being added, and CLASS is the same as the zone
+  ///    class.  Any duplicate RRs will be silently ignored by the primary
+  ///    master.
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `record` - the name of the record to create
+  /// * `zone_origin` - the zone name to update, i.e. SOA name
+  /// * `signer` - the signer, with private key, to use to sign the request with SIG(0), if None
+  ///              the update is sent unsigned
+  ///
+  /// The update must go to a zone authority (i.e. the server used in the ClientConnection)
+  pub fn create(&self,
+                record: Record,
+                zone_origin: domain::Name,
+                signer: Option<&Signer>) -> ClientResult<Message> {
+    assert!(zone_origin.zone_of(record.get_name()));
+
+    // for updates, the query section is used for the zone
+    let mut zone: Query = Query::new();
+    zone.name(zone_origin).query_class(record.get_dns_class()).query_type(RecordType::SOA);
+
+    // build the message
+    let mut message: Message = Message::new();
+    message.id(rand::random()).message_type(MessageType::Query).op_code(OpCode::Update).recursion_desired(false);
+    message.add_zone(zone);
+
+    let mut prerequisite = Record::with(record.get_name().clone(), record.get_rr_type(), 0);
+    prerequisite.dns_class(DNSClass::NONE);
+    message.add_pre_requisite(prerequisite);
+    message.add_update(record);
+
+    // Extended dns
+    let mut edns: Edns = Edns::new();
+
+    // if secure {
+    //   edns.set_dnssec_ok(true);
+    //   message.authentic_data(true);
+    //   message.checking_disabled(false);
+    // }
+
+    edns.set_max_payload(1500);
+    edns.set_version(0);
+
+    message.set_edns(edns);
+
+    // after all other updates to the message, sign it.
+    Self::sign_update(&mut message, signer);
+
+    self.send_message(&message)
+  }
+
+  /// Appends a record to an existing rrset, optionally require the rrset to exis (atomicity
+  ///  depends on the server)
+  ///
+  /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+  ///
+  /// ```text
+  /// 2.4.1 - RRset Exists (Value Independent)
+  ///
+  ///   At least one RR with a specified NAME and TYPE (in the zone and class
+  ///   specified in the Zone Section) must exist.
+  ///
+  ///   For this prerequisite, a requestor adds to the section a single RR
+  ///   whose NAME and TYPE are equal to that of the zone RRset whose
+  ///   existence is required.  RDLENGTH is zero and RDATA is therefore
+  ///   empty.  CLASS must be specified as ANY to differentiate this
+  ///   condition from that of an actual RR whose RDLENGTH is naturally zero
+  ///   (0) (e.g., NULL).  TTL is specified as zero (0).
+  ///
+  /// 2.5.1 - Add To An RRset
+  ///
+  ///    RRs are added to the Update Section whose NAME, TYPE, TTL, RDLENGTH
+  ///    and RDATA are those being added, and CLASS is the same as the zone
+  ///    class.  Any duplicate RRs will be silently ignored by the primary
+  ///    master.
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `record` - the record to append to an RRSet
+  /// * `zone_origin` - the zone name to update, i.e. SOA name
+  /// * `must_exist` - if true, the request will fail if the record does not exist
+  /// * `signer` - the signer, with private key, to use to sign the request with SIG(0), if None
+  ///              the update is sent unsigned
+  ///
+  /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
+  /// the rrset does not exist and must_exist is false, then the RRSet will be created.
   pub fn append(&self,
                 record: Record,
                 zone_origin: domain::Name,
                 must_exist: bool,
-                signer: &Signer) -> ClientResult<Message> {
+                signer: Option<&Signer>) -> ClientResult<Message> {
     assert!(zone_origin.zone_of(record.get_name()));
 
     // for updates, the query section is used for the zone
@@ -664,7 +1518,7 @@ impl<C: ClientConnection> Client<C> {
     message.set_edns(edns);
 
     // after all other updates to the message, sign it.
-    message.sign(signer, UTC::now().timestamp() as u32);
+    Self::sign_update(&mut message, signer);
 
     self.send_message(&message)
   }
@@ -708,14 +1562,15 @@ impl<C: ClientConnection> Client<C> {
   /// * `current` - the current current which must exist for the swap to complete
   /// * `new` - the new record with which to replace the current record
   /// * `zone_origin` - the zone name to update, i.e. SOA name
-  /// * `signer` - the signer, with private key, to use to sign the request
+  /// * `signer` - the signer, with private key, to use to sign the request with SIG(0), if None
+  ///              the update is sent unsigned
   ///
   /// The update must go to a zone authority (i.e. the server used in the ClientConnection).
   pub fn compare_and_swap(&self,
                           current: Record,
                           new: Record,
                           zone_origin: domain::Name,
-                          signer: &Signer) -> ClientResult<Message> {
+                          signer: Option<&Signer>) -> ClientResult<Message> {
     assert!(zone_origin.zone_of(current.get_name()));
     assert!(zone_origin.zone_of(new.get_name()));
 
@@ -759,7 +1614,7 @@ impl<C: ClientConnection> Client<C> {
     message.set_edns(edns);
 
     // after all other updates to the message, sign it.
-    message.sign(signer, UTC::now().timestamp() as u32);
+    Self::sign_update(&mut message, signer);
 
     self.send_message(&message)
   }
@@ -796,14 +1651,15 @@ impl<C: ClientConnection> Client<C> {
   /// * `record` - the record to delete from a RRSet, the name, type and rdata must match the
   ///              record to delete
   /// * `zone_origin` - the zone name to update, i.e. SOA name
-  /// * `signer` - the signer, with private key, to use to sign the request
+  /// * `signer` - the signer, with private key, to use to sign the request with SIG(0), if None
+  ///              the update is sent unsigned
   ///
   /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
   /// the rrset does not exist and must_exist is false, then the RRSet will be deleted.
   pub fn delete_by_rdata(&self,
                          mut record: Record,
                          zone_origin: domain::Name,
-                         signer: &Signer) -> ClientResult<Message> {
+                         signer: Option<&Signer>) -> ClientResult<Message> {
     assert!(zone_origin.zone_of(record.get_name()));
 
     // for updates, the query section is used for the zone
@@ -836,7 +1692,7 @@ impl<C: ClientConnection> Client<C> {
     message.set_edns(edns);
 
     // after all other updates to the message, sign it.
-    message.sign(signer, UTC::now().timestamp() as u32);
+    Self::sign_update(&mut message, signer);
 
     self.send_message(&message)
   }
@@ -873,14 +1729,15 @@ impl<C: ClientConnection> Client<C> {
   /// * `record` - the record to delete from a RRSet, the name, and type must match the
   ///              record set to delete
   /// * `zone_origin` - the zone name to update, i.e. SOA name
-  /// * `signer` - the signer, with private key, to use to sign the request
+  /// * `signer` - the signer, with private key, to use to sign the request with SIG(0), if None
+  ///              the update is sent unsigned
   ///
   /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
   /// the rrset does not exist and must_exist is false, then the RRSet will be deleted.
   pub fn delete_rrset(&self,
                       mut record: Record,
                       zone_origin: domain::Name,
-                      signer: &Signer) -> ClientResult<Message> {
+                      signer: Option<&Signer>) -> ClientResult<Message> {
     assert!(zone_origin.zone_of(record.get_name()));
 
     // for updates, the query section is used for the zone
@@ -915,7 +1772,7 @@ impl<C: ClientConnection> Client<C> {
     message.set_edns(edns);
 
     // after all other updates to the message, sign it.
-    message.sign(signer, UTC::now().timestamp() as u32);
+    Self::sign_update(&mut message, signer);
 
     self.send_message(&message)
   }
@@ -940,7 +1797,8 @@ impl<C: ClientConnection> Client<C> {
   /// * `name_of_records` - the name of all the record sets to delete
   /// * `zone_origin` - the zone name to update, i.e. SOA name
   /// * `dns_class` - the class of the SOA
-  /// * `signer` - the signer, with private key, to use to sign the request
+  /// * `signer` - the signer, with private key, to use to sign the request with SIG(0), if None
+  ///              the update is sent unsigned
   ///
   /// The update must go to a zone authority (i.e. the server used in the ClientConnection). This
   /// operation attempts to delete all resource record sets the the specified name reguardless of
@@ -949,7 +1807,7 @@ impl<C: ClientConnection> Client<C> {
                     name_of_records: domain::Name,
                     zone_origin: domain::Name,
                     dns_class: DNSClass,
-                    signer: &Signer) -> ClientResult<Message> {
+                    signer: Option<&Signer>) -> ClientResult<Message> {
     assert!(zone_origin.zone_of(&name_of_records));
 
     // for updates, the query section is used for the zone
@@ -986,36 +1844,322 @@ impl<C: ClientConnection> Client<C> {
     message.set_edns(edns);
 
     // after all other updates to the message, sign it.
-    message.sign(signer, UTC::now().timestamp() as u32);
+    Self::sign_update(&mut message, signer);
 
     self.send_message(&message)
   }
 
-  /// Sends a message to the server for which this client was defined
-  ///
-  /// # Arguments
-  ///
-  /// * `message` - the message to deliver
-  fn send_message(&self, message: &Message) -> ClientResult<Message> {
-    // get the message bytes and send the query
-    let mut buffer: Vec<u8> = Vec::with_capacity(512);
-    {
+  /// signs the update with SIG(0), this must be called after all other changes to the message
+  fn sign_update(message: &mut Message, signer: Option<&Signer>) {
+    if let Some(signer) = signer {
+      message.sign(signer, UTC::now().timestamp() as u32);
+    }
+  }
+
+  /// Sends the query message, verifying the 0x20 encoding of the response if enabled
+  fn send_query(&self, message: &Message) -> ClientResult<Message> {
+    let response = try!(self.send_message(message));
+
+    if self.case_randomization {
+      let echoed = response.get_queries().iter().zip(message.get_queries().iter())
+                           .all(|(r, q)| r.get_name().eq_case(q.get_name()));
+
+      if !echoed || response.get_queries().len() != message.get_queries().len() {
+        warn!("response did not echo the query name case: {:?}", response.get_queries());
+        return Err(ClientErrorKind::Message("response query name case does not match the request").into())
+      }
+    }
+
+    Ok(response)
+  }
+
+  /// Sends a message to the server for which this client was defined, retrying according to the
+  ///  RetryPolicy
+  ///
+  /// # Arguments
+  ///
+  /// * `message` - the message to deliver
+  fn send_message(&self, message: &Message) -> ClientResult<Message> {
+    let attempts = self.retry_policy.get_attempts();
+
+    let mut attempt: usize = 0;
+    loop {
+      let last_attempt = attempt + 1 >= attempts;
+      let result = self.send_message_once(message);
+
+      let retry = match result {
+        Ok(ref response) => RetryPolicy::is_retryable_response(response.get_response_code()),
+        Err(ref error) => RetryPolicy::is_retryable_error(error),
+      };
+
+      if !retry || last_attempt { return result }
+
+      let backoff = self.retry_policy.backoff(attempt);
+      debug!("retrying request {} after {:?}, attempt {} of {}", message.get_id(), backoff, attempt + 2, attempts);
+      thread::sleep(backoff);
+
+      if self.retry_policy.is_switch_upstream() {
+        self.client_connection.borrow_mut().next_upstream();
+      }
+
+      attempt += 1;
+      if let Some(ref observer) = self.observer { observer.on_retry(message, attempt + 1); }
+    }
+  }
+
+  fn send_message_once(&self, message: &Message) -> ClientResult<Message> {
+    if self.interceptors.is_empty() { return self.observed_exchange(message) }
+
+    let mut request = message.clone();
+    for interceptor in self.interceptors.iter() {
+      try!(interceptor.on_request(&mut request));
+    }
+
+    let mut response = try!(self.observed_exchange(&request));
+    for interceptor in self.interceptors.iter().rev() {
+      try!(interceptor.on_response(&request, &mut response));
+    }
+
+    Ok(response)
+  }
+
+  /// exchanges the message, reporting the events to the observer
+  fn observed_exchange(&self, message: &Message) -> ClientResult<Message> {
+    let observer = match self.observer {
+      Some(ref observer) => observer,
+      None => return self.exchange(message),
+    };
+
+    let transport = self.client_connection.borrow().transport();
+    observer.on_send(message, transport);
+
+    let start = Instant::now();
+    let result = self.exchange(message);
+    match result {
+      Ok(ref response) => observer.on_response(message, response, start.elapsed(), transport),
+      Err(ref error) => observer.on_error(message, error, start.elapsed(), transport),
+    }
+
+    result
+  }
+
+  /// sends the message over the connection and validates that the response is to the request
+  fn exchange(&self, message: &Message) -> ClientResult<Message> {
+    // get the message bytes and send the query
+    let mut buffer: Vec<u8> = Vec::with_capacity(512);
+    {
       let mut encoder = BinEncoder::new(&mut buffer);
       try!(message.emit(&mut encoder));
     }
 
+    // the slot is held until the response is received
+    let _permit = match self.limiter {
+      Some(ref limiter) => Some(try!(limiter.acquire())),
+      None => None,
+    };
+
     // send the message and get the response from the connection.
     let resp_buffer = try!(self.client_connection.borrow_mut().send(buffer));
 
-    let mut decoder = BinDecoder::new(&resp_buffer);
+    let mut decoder = BinDecoder::with_mode(&resp_buffer, self.decode_mode);
     let response = try!(Message::read(&mut decoder));
 
     if response.get_id() != message.get_id() { return Err(ClientErrorKind::IncorrectMessageId(response.get_id(), message.get_id()).into()); }
+    if !response.is_response_to(message) { return Err(ClientErrorKind::Message("response does not match the request").into()); }
 
     Ok(response)
   }
 }
 
+/// The records of a multiple message response, e.g. AXFR or IXFR, the next message is received
+///  from the connection as needed. This never ends, the caller must detect the end of the stream.
+struct TransferStream<'a, C: ClientConnection + 'a> {
+  client: &'a Client<C>,
+  id: u16,
+  records: VecDeque<Record>,
+}
+
+impl<'a, C: ClientConnection> TransferStream<'a, C> {
+  fn new(client: &'a Client<C>, response: Message) -> ClientResult<Self> {
+    let mut stream = TransferStream{ client: client, id: response.get_id(), records: VecDeque::new() };
+    try!(stream.push_response(response));
+    Ok(stream)
+  }
+
+  fn push_response(&mut self, response: Message) -> ClientResult<()> {
+    if response.get_id() != self.id { return Err(ClientErrorKind::IncorrectMessageId(response.get_id(), self.id).into()) }
+    if response.get_response_code() != ResponseCode::NoError { return Err(error_response(&response)) }
+    if response.get_answers().is_empty() { return Err(ClientErrorKind::Message("zone transfer message contained no records").into()) }
+
+    self.records.extend(response.get_answers().iter().cloned());
+    Ok(())
+  }
+
+  fn receive(&mut self) -> ClientResult<()> {
+    let buffer = try!(self.client.client_connection.borrow_mut().receive());
+    let response = try!(Message::read(&mut BinDecoder::new(&buffer)));
+    self.push_response(response)
+  }
+}
+
+impl<'a, C: ClientConnection> Iterator for TransferStream<'a, C> {
+  type Item = ClientResult<Record>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while self.records.is_empty() {
+      if let Err(e) = self.receive() { return Some(Err(e)) }
+    }
+
+    self.records.pop_front().map(Ok)
+  }
+}
+
+/// Iterator over the records of a zone transfer, see `Client::zone_transfer()`
+pub struct ZoneTransfer<'a, C: ClientConnection + 'a> {
+  stream: TransferStream<'a, C>,
+  soa: Option<Record>,
+  done: bool,
+}
+
+impl<'a, C: ClientConnection> Iterator for ZoneTransfer<'a, C> {
+  type Item = ClientResult<Record>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done { return None }
+
+    let record = match self.stream.next() {
+      Some(Ok(record)) => record,
+      Some(Err(e)) => {
+        self.done = true;
+        return Some(Err(e))
+      },
+      None => return None,
+    };
+
+    let is_soa = record.get_rr_type() == RecordType::SOA;
+
+    if self.soa.is_none() {
+      if !is_soa {
+        self.done = true;
+        return Some(Err(ClientErrorKind::Message("zone transfer did not start with an SOA").into()))
+      }
+
+      self.soa = Some(record.clone());
+      return Some(Ok(record))
+    }
+
+    if is_soa {
+      // the closing SOA, the transfer is complete
+      debug!("zone transfer complete: {}", record.get_name());
+      self.done = true;
+      return None
+    }
+
+    Some(Ok(record))
+  }
+}
+
+/// The differences between two versions of a zone, from an IXFR
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneDiff {
+  /// the SOA of the zone before the change
+  pub from_soa: Record,
+  /// the SOA of the zone after the change
+  pub to_soa: Record,
+  /// records removed from the zone
+  pub deleted: Vec<Record>,
+  /// records added to the zone
+  pub added: Vec<Record>,
+}
+
+/// The result of an incremental zone transfer, see `Client::incremental_transfer()`
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncrementalTransfer {
+  /// The zone has not changed since the requested serial, the current SOA is returned
+  UpToDate(Record),
+  /// The sequence of changes, in order, which bring the zone up to the SOA
+  Incremental { soa: Record, diffs: Vec<ZoneDiff> },
+  /// The server responded with the full zone, or IXFR was refused and AXFR was used
+  Full { soa: Record, records: Vec<Record> },
+}
+
+/// the error of a failed response, NXDOMAIN responses with an SOA carry their negative TTL
+fn error_response(response: &Message) -> ClientError {
+  match response.get_negative_ttl() {
+    Some(negative_ttl) => ClientErrorKind::NegativeResponse(response.get_response_code(), negative_ttl).into(),
+    None => ClientErrorKind::ErrorResponse(response.get_response_code()).into(),
+  }
+}
+
+fn soa_serial(record: &Record) -> Option<u32> {
+  if let RData::SOA(ref soa) = *record.get_rdata() { Some(soa.get_serial()) } else { None }
+}
+
+/// Parses the records of an IXFR response, which may be either the incremental or the AXFR format
+///
+/// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer in DNS, August 1996
+///
+/// ```text
+/// 4. Response Format
+///
+///    If incremental zone transfer is available, one or more difference
+///    sequences is returned.  The list of difference sequences is preceded
+///    and followed by a copy of the server's current version of the SOA.
+///
+///    Each difference sequence represents one update to the zone (one SOA
+///    serial change) consisting of deleted RRs and added RRs.  The first RR
+///    of the deleted RRs is the older SOA RR and the first RR of the added
+///    RRs is the newer SOA RR.
+///
+///    If incremental zone transfer is not available, the entire zone is
+///    returned.  The first and the last RR of the response is the SOA
+///    record of the zone.  I.e. the behavior is the same as an AXFR
+///    response except the query type is IXFR.
+/// ```
+fn parse_ixfr<I: Iterator<Item=ClientResult<Record>>>(mut records: I) -> ClientResult<IncrementalTransfer> {
+  let mut next = || -> ClientResult<Record> {
+    records.next().unwrap_or_else(|| Err(ClientErrorKind::Message("incremental transfer ended early").into()))
+  };
+
+  let soa = try!(next());
+  let serial = match soa_serial(&soa) {
+    Some(serial) => serial,
+    None => return Err(ClientErrorKind::Message("incremental transfer did not start with an SOA").into()),
+  };
+
+  let second = try!(next());
+  if soa_serial(&second).is_none() {
+    // AXFR format, read through to the closing SOA
+    let (mut zone, _) = try!(read_until_soa(&mut next));
+    zone.insert(0, second);
+
+    return Ok(IncrementalTransfer::Full{ soa: soa, records: zone })
+  }
+
+  // incremental format, a sequence of deletes and adds each preceded by an SOA
+  let mut diffs: Vec<ZoneDiff> = Vec::new();
+  let mut from_soa = second;
+  while soa_serial(&from_soa) != Some(serial) {
+    let (deleted, to_soa) = try!(read_until_soa(&mut next));
+    let (added, following) = try!(read_until_soa(&mut next));
+
+    diffs.push(ZoneDiff{ from_soa: from_soa, to_soa: to_soa, deleted: deleted, added: added });
+    from_soa = following;
+  }
+
+  Ok(IncrementalTransfer::Incremental{ soa: soa, diffs: diffs })
+}
+
+/// reads records up to the next SOA, returning the records and the SOA
+fn read_until_soa<F: FnMut() -> ClientResult<Record>>(next: &mut F) -> ClientResult<(Vec<Record>, Record)> {
+  let mut records = Vec::new();
+  loop {
+    let record = try!(next());
+    if record.get_rr_type() == RecordType::SOA { return Ok((records, record)) }
+    records.push(record);
+  }
+}
+
 #[cfg(test)]
 mod test {
   use std::net::*;
@@ -1025,8 +2169,10 @@ mod test {
 
   use ::authority::Catalog;
   use ::authority::authority_tests::{create_example, create_secure_example};
-  use ::client::{Client, ClientConnection, TestClientConnection};
-  use ::op::ResponseCode;
+  use ::client::{AddressOrder, Client, ClientConnection, MessageInterceptor, QueryObserver, TestClientConnection, Transport};
+  use ::error::*;
+  use ::op::{Edns, Message, MessageType, OpCode, ResponseCode};
+  use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
   use ::rr::{DNSClass, Record, RecordType, domain, RData};
   use ::rr::dnssec::{Algorithm, Signer, TrustAnchor};
   use ::tcp::TcpClientConnection;
@@ -1035,7 +2181,7 @@ mod test {
   #[test]
   fn test_query_nonet() {
     let authority = create_example();
-    let mut catalog = Catalog::new();
+    let catalog = Catalog::new();
     catalog.upsert(authority.get_origin().clone(), authority);
 
     let client = Client::new(TestClientConnection::new(&catalog));
@@ -1063,6 +2209,546 @@ mod test {
     test_query(client);
   }
 
+  #[test]
+  fn test_lookup_and_send_nonet() {
+    use ::op::Query;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let client = Client::new(TestClientConnection::new(&catalog));
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+
+    let mut query = Query::new();
+    query.name(name.clone()).query_class(DNSClass::IN).query_type(RecordType::A);
+
+    let response = client.lookup(query.clone()).expect("lookup failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+
+    let mut message = Message::new();
+    message.message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    let response = client.send(message).expect("send failed");
+    assert_eq!(response.get_answers()[0].get_name(), &name);
+  }
+
+  /// records the events of the observer
+  #[derive(Default)]
+  struct RecordingObserver {
+    events: ::std::sync::Mutex<Vec<String>>,
+  }
+
+  impl QueryObserver for RecordingObserver {
+    fn on_send(&self, _: &Message, transport: Transport) {
+      self.events.lock().unwrap().push(format!("send {:?}", transport));
+    }
+
+    fn on_retry(&self, _: &Message, attempt: usize) {
+      self.events.lock().unwrap().push(format!("retry {}", attempt));
+    }
+
+    fn on_response(&self, _: &Message, response: &Message, _: ::std::time::Duration, _: Transport) {
+      self.events.lock().unwrap().push(format!("response {:?}", response.get_response_code()));
+    }
+  }
+
+  /// adds EDNS to requests, recording the max payload of the request each response is to
+  #[derive(Default)]
+  struct EdnsInterceptor {
+    payloads: ::std::sync::Mutex<Vec<Option<u16>>>,
+  }
+
+  impl MessageInterceptor for EdnsInterceptor {
+    fn on_request(&self, request: &mut Message) -> ClientResult<()> {
+      let mut edns = Edns::new();
+      edns.set_max_payload(1232);
+      request.set_edns(edns);
+      Ok(())
+    }
+
+    fn on_response(&self, request: &Message, _: &mut Message) -> ClientResult<()> {
+      self.payloads.lock().unwrap().push(request.get_edns().map(|e| e.get_max_payload()));
+      Ok(())
+    }
+  }
+
+  /// rejects NXDOMAIN responses
+  struct RejectInterceptor;
+
+  impl MessageInterceptor for RejectInterceptor {
+    fn on_response(&self, _: &Message, response: &mut Message) -> ClientResult<()> {
+      if response.get_response_code() == ResponseCode::NXDomain {
+        return Err(ClientErrorKind::Message("rejected").into())
+      }
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_interceptors_nonet() {
+    use std::sync::Arc;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    let edns = Arc::new(EdnsInterceptor::default());
+    client.add_interceptor(edns.clone());
+    client.add_interceptor(Arc::new(RejectInterceptor));
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    let response = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers().len(), 1);
+    assert_eq!(*edns.payloads.lock().unwrap(), vec![Some(1232)]);
+
+    // the rejection is returned, the edns interceptor runs after it for responses and is skipped
+    let name = domain::Name::parse("nx.example.com.", None).unwrap();
+    let error = client.query(&name, DNSClass::IN, RecordType::A).unwrap_err();
+    match *error.kind() {
+      ClientErrorKind::Message(msg) => assert_eq!(msg, "rejected"),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+    assert_eq!(edns.payloads.lock().unwrap().len(), 1);
+  }
+
+  /// records the RD, CD and AD flags of the requests
+  #[derive(Default)]
+  struct FlagsInterceptor {
+    flags: ::std::sync::Mutex<Vec<(bool, bool, bool)>>,
+  }
+
+  impl MessageInterceptor for FlagsInterceptor {
+    fn on_request(&self, request: &mut Message) -> ClientResult<()> {
+      self.flags.lock().unwrap().push((request.is_recursion_desired(), request.is_checking_disabled(), request.is_authentic_data()));
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_query_flags_nonet() {
+    use std::sync::Arc;
+    use ::client::QueryFlags;
+    use ::op::Query;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    let interceptor = Arc::new(FlagsInterceptor::default());
+    client.add_interceptor(interceptor.clone());
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+
+    client.set_query_flags(QueryFlags::validating());
+    client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+
+    let mut query = Query::new();
+    query.name(name.clone()).query_class(DNSClass::IN).query_type(RecordType::A);
+    let mut flags = QueryFlags::iterative();
+    flags.authentic_data(true);
+    client.lookup_with_flags(query, flags).expect("lookup failed");
+
+    assert_eq!(*interceptor.flags.lock().unwrap(),
+               vec![(true, false, false), (true, true, false), (false, false, true)]);
+  }
+
+  #[test]
+  fn test_observer_nonet() {
+    use std::sync::Arc;
+    use ::client::RetryPolicy;
+
+    let mut catalog = Catalog::new();
+    let (mut client, _, origin) = create_sig0_ready_client(&mut catalog);
+
+    let observer = Arc::new(RecordingObserver::default());
+    client.set_observer(Some(observer.clone()));
+    let mut retry_policy = RetryPolicy::new(2);
+    retry_policy.initial_backoff(::std::time::Duration::from_millis(0)).jitter(false);
+    client.set_retry_policy(retry_policy);
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(*observer.events.lock().unwrap(), vec!["send Unknown", "response NoError"]);
+    observer.events.lock().unwrap().clear();
+
+    // unsigned updates are refused, which is retried
+    let mut record = Record::with(name, RecordType::A, 300);
+    record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
+    client.create(record, origin, None).expect("create failed");
+    assert_eq!(*observer.events.lock().unwrap(),
+               vec!["send Unknown", "response Refused", "retry 2", "send Unknown", "response Refused"]);
+  }
+
+  #[test]
+  fn test_cache_nonet() {
+    use ::client::ResponseCache;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let empty = Catalog::new();
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    client.set_cache(Some(ResponseCache::new(10)));
+    assert!(client.query(&name, DNSClass::IN, RecordType::A).is_ok());
+
+    // swap in an empty catalog, the next answer can only come from the cache
+    client.client_connection = ::std::cell::RefCell::new(TestClientConnection::new(&empty));
+    let response = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+  }
+
+  /// a server which can't be reached
+  #[derive(Debug)]
+  struct FailingConnection;
+
+  impl ClientConnection for FailingConnection {
+    fn send(&mut self, _: Vec<u8>) -> ClientResult<Vec<u8>> {
+      Err(ClientErrorKind::Message("unreachable").into())
+    }
+  }
+
+  #[test]
+  fn test_serve_stale_nonet() {
+    use ::client::ResponseCache;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut cache = ResponseCache::new(10);
+    cache.serve_stale(Some(3600)).max_ttl(1);
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    client.set_cache(Some(cache));
+    client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+
+    // the cache is shared with a client whose server is unreachable, once the entry has expired
+    ::std::thread::sleep(::std::time::Duration::from_millis(1100));
+    let mut broken = Client::new(FailingConnection);
+    broken.set_shared_cache(client.get_cache().cloned());
+    broken.set_retry_policy(::client::RetryPolicy::new(1));
+
+    let response = broken.query(&name, DNSClass::IN, RecordType::A).expect("stale answer expected");
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+    assert_eq!(response.get_answers()[0].get_ttl(), 1);
+  }
+
+  #[test]
+  fn test_prefetch_nonet() {
+    use ::client::ResponseCache;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut cache = ResponseCache::new(10);
+    cache.prefetch(Some(1)).prefetch_percent(100);
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    client.set_cache(Some(cache));
+    assert_eq!(client.prefetch(), 0);
+
+    client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+
+    assert_eq!(client.prefetch(), 1);
+    assert_eq!(client.prefetch(), 0);
+    assert_eq!(client.get_cache().unwrap().get_stats().get_insertions(), 2);
+  }
+
+  /// answers with the records at the query name, of the query type or CNAME, never chasing chains
+  #[derive(Debug)]
+  struct CnameConnection {
+    records: Vec<Record>,
+  }
+
+  impl ClientConnection for CnameConnection {
+    fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+      let request = try!(Message::read(&mut BinDecoder::new(&bytes)));
+      let query = request.get_queries()[0].clone();
+
+      let mut response = Message::new();
+      response.id(request.get_id()).message_type(MessageType::Response).op_code(OpCode::Query).add_query(query.clone());
+      for record in self.records.iter().filter(|r| r.get_name() == query.get_name() && (r.get_rr_type() == query.get_query_type() || r.get_rr_type() == RecordType::CNAME)) {
+        response.add_answer(record.clone());
+      }
+
+      let mut buffer = Vec::new();
+      try!(response.emit(&mut BinEncoder::new(&mut buffer)));
+      Ok(buffer)
+    }
+  }
+
+  fn cname(name: &domain::Name, target: &domain::Name) -> Record {
+    Record::new().name(name.clone()).ttl(86400).rr_type(RecordType::CNAME).dns_class(DNSClass::IN).rdata(RData::CNAME(target.clone())).clone()
+  }
+
+  #[test]
+  fn test_dns64() {
+    let v4only = domain::Name::parse("v4only.example.com.", None).unwrap();
+    let private = domain::Name::parse("private.example.com.", None).unwrap();
+    let mapped = domain::Name::parse("mapped.example.com.", None).unwrap();
+    let dual = domain::Name::parse("dual.example.com.", None).unwrap();
+    let alias = domain::Name::parse("alias.example.com.", None).unwrap();
+    let a = |name: &domain::Name, addr: Ipv4Addr| Record::new().name(name.clone()).ttl(300).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(addr)).clone();
+    let aaaa = |name: &domain::Name, addr: Ipv6Addr| Record::new().name(name.clone()).ttl(300).rr_type(RecordType::AAAA).dns_class(DNSClass::IN).rdata(RData::AAAA(addr)).clone();
+
+    let records = vec![a(&v4only, Ipv4Addr::new(192,0,2,33)),
+                       a(&private, Ipv4Addr::new(10,0,0,1)),
+                       a(&mapped, Ipv4Addr::new(192,0,2,34)), aaaa(&mapped, "::ffff:192.0.2.34".parse().unwrap()),
+                       a(&dual, Ipv4Addr::new(192,0,2,35)), aaaa(&dual, "2001:db8::35".parse().unwrap()),
+                       cname(&alias, &v4only)];
+    let mut client = Client::new(CnameConnection{ records: records });
+    client.set_dns64(Some(::client::Dns64::well_known()));
+
+    let response = client.query(&v4only, DNSClass::IN, RecordType::AAAA).expect("query failed");
+    assert_eq!(response.get_answers(), &[aaaa(&v4only, "64:ff9b::c000:221".parse().unwrap())]);
+
+    // mapped addresses are excluded, and so synthesized over
+    let response = client.query(&mapped, DNSClass::IN, RecordType::AAAA).expect("query failed");
+    assert_eq!(response.get_answers(), &[aaaa(&mapped, "64:ff9b::c000:222".parse().unwrap())]);
+
+    // real AAAA records are returned as is
+    let response = client.query(&dual, DNSClass::IN, RecordType::AAAA).expect("query failed");
+    assert_eq!(response.get_answers(), &[aaaa(&dual, "2001:db8::35".parse().unwrap())]);
+
+    // the well-known prefix can't represent private addresses
+    let response = client.query(&private, DNSClass::IN, RecordType::AAAA).expect("query failed");
+    assert!(response.get_answers().is_empty());
+
+    // the chain is kept
+    client.set_max_cname_depth(1);
+    let response = client.query(&alias, DNSClass::IN, RecordType::AAAA).expect("query failed");
+    assert_eq!(response.get_answers(), &[cname(&alias, &v4only), aaaa(&v4only, "64:ff9b::c000:221".parse().unwrap())]);
+
+    // A queries are untouched
+    let response = client.query(&v4only, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers(), &[a(&v4only, Ipv4Addr::new(192,0,2,33))]);
+  }
+
+  #[test]
+  fn test_lookup_ip_strategy() {
+    use ::client::LookupIpStrategy;
+
+    let v4only = domain::Name::parse("v4only.example.com.", None).unwrap();
+    let dual = domain::Name::parse("dual.example.com.", None).unwrap();
+    let v4 = Ipv4Addr::new(192,0,2,1);
+    let v6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let records = vec![Record::new().name(v4only.clone()).ttl(300).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(v4)).clone(),
+                       Record::new().name(dual.clone()).ttl(300).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(v4)).clone(),
+                       Record::new().name(dual.clone()).ttl(300).rr_type(RecordType::AAAA).dns_class(DNSClass::IN).rdata(RData::AAAA(v6)).clone()];
+    let client = Client::new(CnameConnection{ records: records });
+    let lookup = |name: &domain::Name, strategy: LookupIpStrategy| client.lookup_ip_with_strategy(name, strategy, AddressOrder::Ipv4First).expect("lookup failed");
+
+    assert_eq!(lookup(&dual, LookupIpStrategy::Ipv4Only), vec![IpAddr::V4(v4)]);
+    assert_eq!(lookup(&dual, LookupIpStrategy::Ipv6Only), vec![IpAddr::V6(v6)]);
+    assert_eq!(lookup(&dual, LookupIpStrategy::Ipv4AndIpv6), vec![IpAddr::V4(v4), IpAddr::V6(v6)]);
+    assert_eq!(lookup(&dual, LookupIpStrategy::Ipv6ThenIpv4), vec![IpAddr::V6(v6)]);
+    assert_eq!(lookup(&dual, LookupIpStrategy::Ipv4ThenIpv6), vec![IpAddr::V4(v4)]);
+
+    assert!(lookup(&v4only, LookupIpStrategy::Ipv6Only).is_empty());
+    assert_eq!(lookup(&v4only, LookupIpStrategy::Ipv6ThenIpv4), vec![IpAddr::V4(v4)]);
+  }
+
+  #[test]
+  fn test_follow_cname() {
+    let alias = domain::Name::parse("alias.example.com.", None).unwrap();
+    let alias2 = domain::Name::parse("alias2.example.com.", None).unwrap();
+    let www = domain::Name::parse("www.example.com.", None).unwrap();
+    let address = Record::new().name(www.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(93,184,216,34))).clone();
+
+    let records = vec![cname(&alias, &alias2), cname(&alias2, &www), address.clone()];
+    let mut client = Client::new(CnameConnection{ records: records });
+
+    // disabled by default
+    let response = client.query(&alias, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers(), &[cname(&alias, &alias2)]);
+
+    client.set_max_cname_depth(2);
+    let response = client.query(&alias, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers(), &[cname(&alias, &alias2), cname(&alias2, &www), address]);
+
+    client.set_max_cname_depth(1);
+    assert!(client.query(&alias, DNSClass::IN, RecordType::A).is_err());
+  }
+
+  #[test]
+  fn test_chaos_query() {
+    use ::client::ChaosQuery;
+    use ::rr::rdata::TXT;
+
+    let version = Record::new().name(ChaosQuery::VersionBind.to_name()).ttl(0).rr_type(RecordType::TXT).dns_class(DNSClass::CH)
+                               .rdata(RData::TXT(TXT::new(vec!["trust-".to_string(), "dns".to_string()]))).clone();
+    let client = Client::new(CnameConnection{ records: vec![version] });
+
+    assert_eq!(client.chaos_query(ChaosQuery::VersionBind).expect("query failed"), vec!["trust-dns".to_string()]);
+    assert!(client.chaos_query(ChaosQuery::IdServer).expect("query failed").is_empty());
+  }
+
+  /// answers NXDOMAIN, with the SOA in the authority section if there is one
+  #[derive(Debug)]
+  struct NxDomainConnection {
+    soa: Option<Record>,
+  }
+
+  impl ClientConnection for NxDomainConnection {
+    fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+      let request = try!(Message::read(&mut BinDecoder::new(&bytes)));
+
+      let mut response = Message::new();
+      response.id(request.get_id()).message_type(MessageType::Response).op_code(OpCode::Query)
+              .response_code(ResponseCode::NXDomain).add_all_queries(request.get_queries());
+      if let Some(ref soa) = self.soa { response.add_name_server(soa.clone()); }
+
+      let mut buffer = Vec::new();
+      try!(response.emit(&mut BinEncoder::new(&mut buffer)));
+      Ok(buffer)
+    }
+  }
+
+  #[test]
+  fn test_negative_response() {
+    use ::client::{ChaosQuery, ResponseCache};
+    use ::rr::rdata::SOA;
+
+    let origin = domain::Name::parse("example.com.", None).unwrap();
+    let soa = Record::new().name(origin.clone()).ttl(3600).rr_type(RecordType::SOA).dns_class(DNSClass::IN)
+                           .rdata(RData::SOA(SOA::new(origin.clone(), origin.clone(), 1, 2, 3, 4, 300))).clone();
+
+    let client = Client::new(NxDomainConnection{ soa: Some(soa) });
+    match *client.chaos_query(ChaosQuery::VersionBind).unwrap_err().kind() {
+      ClientErrorKind::NegativeResponse(ResponseCode::NXDomain, 300) => (),
+      ref kind => panic!("expected a negative response: {:?}", kind),
+    }
+
+    // without an SOA there is no negative ttl, and nothing is cached
+    let mut client = Client::new(NxDomainConnection{ soa: None });
+    client.set_cache(Some(ResponseCache::new(10)));
+    match *client.chaos_query(ChaosQuery::VersionBind).unwrap_err().kind() {
+      ClientErrorKind::ErrorResponse(ResponseCode::NXDomain) => (),
+      ref kind => panic!("expected an error response: {:?}", kind),
+    }
+
+    let name = domain::Name::parse("nx.example.com.", None).unwrap();
+    let response = client.refresh(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+    assert_eq!(response.get_negative_ttl(), None);
+    assert_eq!(client.get_cache().unwrap().get_stats().get_insertions(), 0);
+  }
+
+  #[test]
+  fn test_follow_cname_loop() {
+    let loop1 = domain::Name::parse("loop1.example.com.", None).unwrap();
+    let loop2 = domain::Name::parse("loop2.example.com.", None).unwrap();
+
+    let mut client = Client::new(CnameConnection{ records: vec![cname(&loop1, &loop2), cname(&loop2, &loop1)] });
+    client.set_max_cname_depth(10);
+
+    let error = client.query(&loop1, DNSClass::IN, RecordType::A).unwrap_err();
+    match *error.kind() {
+      ClientErrorKind::CnameLoop(..) => (),
+      ref kind => panic!("expected cname loop: {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_zone_transfer_nonet() {
+    let authority = create_example();
+    let origin = authority.get_origin().clone();
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), authority);
+
+    let client = Client::new(TestClientConnection::new(&catalog));
+    let records: Vec<Record> = client.zone_transfer(&origin, DNSClass::IN).expect("transfer failed")
+                                     .collect::<ClientResult<Vec<Record>>>().expect("transfer failed");
+
+    assert_eq!(records.first().unwrap().get_rr_type(), RecordType::SOA);
+    assert_eq!(records.iter().filter(|r| r.get_rr_type() == RecordType::SOA).count(), 1);
+    assert!(records.iter().any(|r| r.get_rr_type() == RecordType::A && r.get_name() == &domain::Name::parse("www.example.com.", None).unwrap()));
+  }
+
+  #[test]
+  fn test_incremental_transfer_fallback_nonet() {
+    use super::IncrementalTransfer;
+
+    let authority = create_example();
+    let origin = authority.get_origin().clone();
+    let soa = authority.get_soa().unwrap().clone();
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), authority);
+
+    // the catalog does not support IXFR, so this will fall back to AXFR
+    let client = Client::new(TestClientConnection::new(&catalog));
+    match client.incremental_transfer(&origin, DNSClass::IN, soa.clone()).expect("transfer failed") {
+      IncrementalTransfer::Full{ soa: got_soa, records } => {
+        assert_eq!(got_soa, soa);
+        assert!(!records.is_empty());
+      },
+      other => panic!("expected full transfer: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_parse_ixfr() {
+    use ::rr::rdata::SOA;
+    use super::{parse_ixfr, IncrementalTransfer};
+
+    let origin = domain::Name::parse("example.com.", None).unwrap();
+    let soa = |serial: u32| Record::new().name(origin.clone()).ttl(3600).rr_type(RecordType::SOA).dns_class(DNSClass::IN)
+                                         .rdata(RData::SOA(SOA::new(origin.clone(), origin.clone(), serial, 1, 1, 1, 1))).clone();
+    let a = |last: u8| Record::new().name(origin.clone()).ttl(3600).rr_type(RecordType::A).dns_class(DNSClass::IN)
+                                    .rdata(RData::A(Ipv4Addr::new(10,0,0,last))).clone();
+
+    // serial 1 -> 2 -> 3
+    let records = vec![soa(3), soa(1), a(1), soa(2), a(2), soa(2), soa(3), a(3), soa(3)];
+    match parse_ixfr(records.into_iter().map(Ok)).expect("parse failed") {
+      IncrementalTransfer::Incremental{ soa: got_soa, diffs } => {
+        assert_eq!(got_soa, soa(3));
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].deleted, vec![a(1)]);
+        assert_eq!(diffs[0].added, vec![a(2)]);
+        assert!(diffs[1].deleted.is_empty());
+        assert_eq!(diffs[1].added, vec![a(3)]);
+      },
+      other => panic!("expected incremental: {:?}", other),
+    }
+
+    // axfr format
+    let records = vec![soa(3), a(1), a(2), soa(3)];
+    match parse_ixfr(records.into_iter().map(Ok)).expect("parse failed") {
+      IncrementalTransfer::Full{ records, .. } => assert_eq!(records, vec![a(1), a(2)]),
+      other => panic!("expected full: {:?}", other),
+    }
+
+    // truncated
+    assert!(parse_ixfr(vec![soa(3), soa(1), a(1)].into_iter().map(Ok)).is_err());
+  }
+
+  #[test]
+  fn test_case_randomization_nonet() {
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    client.set_case_randomization(true);
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    let response = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_queries()[0].get_name(), &name);
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+  }
+
   #[cfg(test)]
   fn test_query<C: ClientConnection>(client: Client<C>) {
     use std::cmp::Ordering;
@@ -1099,7 +2785,7 @@ mod test {
       signers.first().expect("expected a key in the authority").get_public_key()
     };
 
-    let mut catalog = Catalog::new();
+    let catalog = Catalog::new();
     catalog.upsert(authority.get_origin().clone(), authority);
 
     let mut trust_anchor = TrustAnchor::new();
@@ -1110,6 +2796,31 @@ mod test {
     test_secure_query_example(client);
   }
 
+  #[test]
+  fn test_validating_nonet() {
+    let authority = create_secure_example();
+    let public_key = authority.get_secure_keys().first().expect("expected a key in the authority").get_public_key();
+
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut trust_anchor = TrustAnchor::new();
+    trust_anchor.insert_trust_anchor(public_key);
+
+    let name = domain::Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
+    let mut client = Client::with_trust_anchor(TestClientConnection::new(&catalog), trust_anchor);
+    client.set_validating(true);
+    assert!(!client.lookup_ip(&name, AddressOrder::Ipv4First).expect("lookup failed").is_empty());
+
+    // the zone's key is not trusted
+    let mut client = Client::with_trust_anchor(TestClientConnection::new(&catalog), TrustAnchor::new());
+    client.set_validating(true);
+    match *client.query(&name, DNSClass::IN, RecordType::A).unwrap_err().kind() {
+      ClientErrorKind::Bogus(ref bogus, _) => assert_eq!(bogus, &name),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+  }
+
   #[test]
   #[ignore]
   fn test_secure_query_example_udp() {
@@ -1213,7 +2924,7 @@ mod test {
       signers.first().expect("expected a key in the authority").get_public_key()
     };
 
-    let mut catalog = Catalog::new();
+    let catalog = Catalog::new();
     catalog.upsert(authority.get_origin().clone(), authority);
 
     let mut trust_anchor = TrustAnchor::new();
@@ -1338,6 +3049,199 @@ mod test {
     (client, signer, origin)
   }
 
+  #[test]
+  fn test_lookup_ip_nonet() {
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let client = Client::new(TestClientConnection::new(&catalog));
+    let name = domain::Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
+
+    let v4 = IpAddr::V4(Ipv4Addr::new(93,184,216,34));
+    let v6 = IpAddr::V6(Ipv6Addr::new(0x2606,0x2800,0x220,0x1,0x248,0x1893,0x25c8,0x1946));
+    assert_eq!(client.lookup_ip(&name, AddressOrder::Ipv4First).expect("lookup failed"), vec![v4, v6]);
+    assert_eq!(client.lookup_ip(&name, AddressOrder::Ipv6First).expect("lookup failed"), vec![v6, v4]);
+
+    let name = domain::Name::with_labels(vec!["nothere".to_string(), "example".to_string(), "com".to_string()]);
+    assert!(client.lookup_ip(&name, AddressOrder::Ipv4First).expect("lookup failed").is_empty());
+  }
+
+  #[test]
+  fn test_hosts_nonet() {
+    use ::resolver::Hosts;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    client.set_hosts(Some(Hosts::read(&b"192.0.2.1 www.example.com"[..]).unwrap()));
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    let addrs = client.lookup_ip(&name, AddressOrder::Ipv4First).expect("lookup failed");
+    assert_eq!(addrs, vec![IpAddr::V4(Ipv4Addr::new(192,0,2,1))]);
+
+    let names = client.reverse_lookup(IpAddr::V4(Ipv4Addr::new(192,0,2,1))).expect("reverse lookup failed");
+    assert_eq!(names, vec![name]);
+
+    // names which are not in the hosts are queried
+    let name = domain::Name::parse("example.com.", None).unwrap();
+    let response = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+  }
+
+  #[test]
+  fn test_overrides_nonet() {
+    use ::resolver::Overrides;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let www = domain::Name::parse("www.example.com.", None).unwrap();
+    let address = Record::new().name(www.clone()).ttl(60).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,0,0,1))).clone();
+    let mut overrides = Overrides::new();
+    overrides.insert(address.clone()).block(domain::Name::parse("example.com.", None).unwrap().trim_to(1));
+
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    client.set_overrides(Some(overrides));
+
+    // the block of com. is less specific, but takes precedence
+    let response = client.query(&www, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+
+    let mut overrides = Overrides::new();
+    overrides.insert(address.clone());
+    client.set_overrides(Some(overrides));
+
+    let response = client.query(&www, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers(), &[address]);
+
+    // the override is authoritative for the name
+    let response = client.query(&www, DNSClass::IN, RecordType::AAAA).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.get_answers().is_empty());
+
+    // other names are queried
+    let name = domain::Name::parse("example.com.", None).unwrap();
+    let response = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+  }
+
+  #[test]
+  fn test_limiter_nonet() {
+    use ::client::QueryLimiter;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let limiter = QueryLimiter::new(1, ::std::time::Duration::from_millis(10));
+    let mut client = Client::new(TestClientConnection::new(&catalog));
+    client.set_limiter(Some(limiter.clone()));
+
+    let name = domain::Name::parse("www.example.com.", None).unwrap();
+    let permit = limiter.acquire().unwrap();
+    assert!(client.query(&name, DNSClass::IN, RecordType::A).is_err());
+
+    drop(permit);
+    assert!(client.query(&name, DNSClass::IN, RecordType::A).is_ok());
+    assert_eq!(limiter.get_in_flight(), 0);
+  }
+
+  #[test]
+  fn test_query_first_nonet() {
+    use ::resolver::ResolverConfig;
+
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+    let client = Client::new(TestClientConnection::new(&catalog));
+
+    let mut config = ResolverConfig::new();
+    config.search(vec![domain::Name::parse("example.com.", None).unwrap()]);
+
+    let names = config.search_names("www").unwrap();
+    let response = client.query_first(&names, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_queries()[0].get_name(), &domain::Name::parse("www.example.com.", None).unwrap());
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+
+    let names = config.search_names("nx").unwrap();
+    let response = client.query_first(&names, DNSClass::IN, RecordType::A).expect("query failed");
+    assert!(response.get_answers().is_empty());
+  }
+
+  #[test]
+  fn test_mx_lookup_nonet() {
+    use ::rr::rdata::MX;
+
+    let mut authority = create_example();
+    let origin = authority.get_origin().clone();
+    let www = domain::Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
+    let mut mx = Record::with(origin.clone(), RecordType::MX, 86400);
+    mx.rdata(RData::MX(MX::new(10, www.clone())));
+    authority.upsert(mx, 0);
+
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), authority);
+    let client = Client::new(TestClientConnection::new(&catalog));
+
+    let exchanges = client.mx_lookup(&origin, false).expect("lookup failed");
+    assert_eq!(exchanges.len(), 1);
+    assert_eq!(exchanges[0].get_preference(), 10);
+    assert_eq!(exchanges[0].get_exchange(), &www);
+    assert!(exchanges[0].get_addresses().is_empty());
+
+    let exchanges = client.mx_lookup(&origin, true).expect("lookup failed");
+    assert_eq!(exchanges[0].get_addresses(), &[IpAddr::V4(Ipv4Addr::new(93,184,216,34)),
+                                              IpAddr::V6(Ipv6Addr::new(0x2606,0x2800,0x220,0x1,0x248,0x1893,0x25c8,0x1946))]);
+  }
+
+  #[test]
+  fn test_srv_lookup_nonet() {
+    use ::rr::rdata::SRV;
+
+    let mut authority = create_example();
+    let origin = authority.get_origin().clone();
+    let www = domain::Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
+    let srv_name = domain::Name::with_labels(vec!["_ldap".to_string(), "_tcp".to_string(), "example".to_string(), "com".to_string()]);
+    let mut srv = Record::with(srv_name.clone(), RecordType::SRV, 86400);
+    srv.rdata(RData::SRV(SRV::new(0, 5, 389, www.clone())));
+    authority.upsert(srv, 0);
+
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), authority);
+    let client = Client::new(TestClientConnection::new(&catalog));
+
+    let lookup = client.srv_lookup("ldap", "tcp", &origin).expect("lookup failed");
+    assert_eq!(lookup.get_name(), &srv_name);
+    assert_eq!(lookup.get_records(), &[SRV::new(0, 5, 389, www.clone())]);
+    assert_eq!(lookup.selection().next().unwrap().get_target(), &www);
+
+    assert!(client.srv_lookup("ldap", "udp", &origin).expect("lookup failed").is_empty());
+  }
+
+  #[test]
+  fn test_notify_nonet() {
+    let authority = create_example();
+    let origin = authority.get_origin().clone();
+    let soa = authority.get_soa().unwrap().clone();
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), authority);
+
+    let client = Client::new(TestClientConnection::new(&catalog));
+    let response = client.notify(&origin, DNSClass::IN, Some(soa.clone())).expect("notify failed");
+    assert_eq!(response.get_op_code(), OpCode::Notify);
+    // the Catalog does not yet accept NOTIFY
+    assert_eq!(response.get_response_code(), ResponseCode::NotImp);
+
+    let responses = Client::notify_all(vec![TestClientConnection::new(&catalog), TestClientConnection::new(&catalog)],
+                                       &origin, DNSClass::IN, None);
+    assert_eq!(responses.len(), 2);
+    assert!(responses.iter().all(|r| r.as_ref().unwrap().get_op_code() == OpCode::Notify));
+  }
+
   #[test]
   fn test_create() {
     let mut catalog = Catalog::new();
@@ -1350,7 +3254,7 @@ mod test {
     record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
 
 
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
     let result = client.query(record.get_name(), record.get_dns_class(), record.get_rr_type()).expect("query failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
@@ -1359,18 +3263,35 @@ mod test {
 
     // trying to create again should error
     // TODO: it would be cool to make this
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::YXRRSet);
 
     // will fail if already set and not the same value.
     let mut record = record.clone();
     record.rdata(RData::A(Ipv4Addr::new(101,11,101,11)));
 
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::YXRRSet);
 
   }
 
+  #[test]
+  fn test_create_unsigned() {
+    let mut catalog = Catalog::new();
+    let (client, _, origin) = create_sig0_ready_client(&mut catalog);
+
+    let mut record = Record::with(domain::Name::with_labels(vec!["new".to_string(), "example".to_string(), "com".to_string()]),
+                                  RecordType::A,
+                                  Duration::minutes(5).num_seconds() as u32);
+    record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
+
+    // the authority only accepts SIG(0) authorized updates
+    let result = client.create(record.clone(), origin.clone(), None).expect("create failed");
+    assert_eq!(result.get_response_code(), ResponseCode::Refused);
+    let result = client.query(record.get_name(), record.get_dns_class(), record.get_rr_type()).expect("query failed");
+    assert_eq!(result.get_answers().len(), 0);
+  }
+
   #[test]
   fn test_append() {
     let mut catalog = Catalog::new();
@@ -1383,11 +3304,11 @@ mod test {
     record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
 
     // first check the must_exist option
-    let result = client.append(record.clone(), origin.clone(), true, &signer).expect("append failed");
+    let result = client.append(record.clone(), origin.clone(), true, Some(&signer)).expect("append failed");
     assert_eq!(result.get_response_code(), ResponseCode::NXRRSet);
 
     // next append to a non-existent RRset
-    let result = client.append(record.clone(), origin.clone(), false, &signer).expect("append failed");
+    let result = client.append(record.clone(), origin.clone(), false, Some(&signer)).expect("append failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     // verify record contents
@@ -1400,7 +3321,7 @@ mod test {
     let mut record = record.clone();
     record.rdata(RData::A(Ipv4Addr::new(101,11,101,11)));
 
-    let result = client.append(record.clone(), origin.clone(), true, &signer).expect("create failed");
+    let result = client.append(record.clone(), origin.clone(), true, Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let result = client.query(record.get_name(), record.get_dns_class(), record.get_rr_type()).expect("query failed");
@@ -1411,7 +3332,7 @@ mod test {
     assert!(result.get_answers().iter().any(|rr| if let &RData::A(ref ip) = rr.get_rdata() { *ip ==  Ipv4Addr::new(101,11,101,11) } else { false }));
 
     // show that appending the same thing again is ok, but doesn't add any records
-    let result = client.append(record.clone(), origin.clone(), true, &signer).expect("create failed");
+    let result = client.append(record.clone(), origin.clone(), true, Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let result = client.query(record.get_name(), record.get_dns_class(), record.get_rr_type()).expect("query failed");
@@ -1430,14 +3351,14 @@ mod test {
                                   Duration::minutes(5).num_seconds() as u32);
     record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
 
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let current = record;
     let mut new = current.clone();
     new.rdata(RData::A(Ipv4Addr::new(101,11,101,11)));
 
-    let result = client.compare_and_swap(current.clone(), new.clone(), origin.clone(), &signer).expect("compare_and_swap failed");
+    let result = client.compare_and_swap(current.clone(), new.clone(), origin.clone(), Some(&signer)).expect("compare_and_swap failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let result = client.query(new.get_name(), new.get_dns_class(), new.get_rr_type()).expect("query failed");
@@ -1449,7 +3370,7 @@ mod test {
     let mut new = new;
     new.rdata(RData::A(Ipv4Addr::new(102,12,102,12)));
 
-    let result = client.compare_and_swap(current, new.clone(), origin.clone(), &signer).expect("compare_and_swap failed");
+    let result = client.compare_and_swap(current, new.clone(), origin.clone(), Some(&signer)).expect("compare_and_swap failed");
     assert_eq!(result.get_response_code(), ResponseCode::NXRRSet);
 
     let result = client.query(new.get_name(), new.get_dns_class(), new.get_rr_type()).expect("query failed");
@@ -1470,20 +3391,20 @@ mod test {
     record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
 
     // first check the must_exist option
-    let result = client.delete_by_rdata(record.clone(), origin.clone(), &signer).expect("delete failed");
+    let result = client.delete_by_rdata(record.clone(), origin.clone(), Some(&signer)).expect("delete failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     // next create to a non-existent RRset
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let mut record = record.clone();
     record.rdata(RData::A(Ipv4Addr::new(101,11,101,11)));
-    let result = client.append(record.clone(), origin.clone(), true, &signer).expect("create failed");
+    let result = client.append(record.clone(), origin.clone(), true, Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     // verify record contents
-    let result = client.delete_by_rdata(record.clone(), origin.clone(), &signer).expect("delete failed");
+    let result = client.delete_by_rdata(record.clone(), origin.clone(), Some(&signer)).expect("delete failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let result = client.query(record.get_name(), record.get_dns_class(), record.get_rr_type()).expect("query failed");
@@ -1504,20 +3425,20 @@ mod test {
     record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
 
     // first check the must_exist option
-    let result = client.delete_rrset(record.clone(), origin.clone(), &signer).expect("delete failed");
+    let result = client.delete_rrset(record.clone(), origin.clone(), Some(&signer)).expect("delete failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     // next create to a non-existent RRset
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let mut record = record.clone();
     record.rdata(RData::A(Ipv4Addr::new(101,11,101,11)));
-    let result = client.append(record.clone(), origin.clone(), true, &signer).expect("create failed");
+    let result = client.append(record.clone(), origin.clone(), true, Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     // verify record contents
-    let result = client.delete_rrset(record.clone(), origin.clone(), &signer).expect("delete failed");
+    let result = client.delete_rrset(record.clone(), origin.clone(), Some(&signer)).expect("delete failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let result = client.query(record.get_name(), record.get_dns_class(), record.get_rr_type()).expect("query failed");
@@ -1537,21 +3458,21 @@ mod test {
     record.rdata(RData::A(Ipv4Addr::new(100,10,100,10)));
 
     // first check the must_exist option
-    let result = client.delete_all(record.get_name().clone(), origin.clone(), DNSClass::IN, &signer).expect("delete failed");
+    let result = client.delete_all(record.get_name().clone(), origin.clone(), DNSClass::IN, Some(&signer)).expect("delete failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     // next create to a non-existent RRset
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let mut record = record.clone();
     record.rr_type(RecordType::AAAA);
     record.rdata(RData::AAAA(Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)));
-    let result = client.create(record.clone(), origin.clone(), &signer).expect("create failed");
+    let result = client.create(record.clone(), origin.clone(), Some(&signer)).expect("create failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     // verify record contents
-    let result = client.delete_all(record.get_name().clone(), origin.clone(), DNSClass::IN, &signer).expect("delete failed");
+    let result = client.delete_all(record.get_name().clone(), origin.clone(), DNSClass::IN, Some(&signer)).expect("delete failed");
     assert_eq!(result.get_response_code(), ResponseCode::NoError);
 
     let result = client.query(record.get_name(), record.get_dns_class(), RecordType::A).expect("query failed");
@@ -17,6 +17,7 @@
 use std::fmt::Debug;
 
 use ::error::*;
+use ::client::Transport;
 
 /// Trait for client connections
 pub trait ClientConnection: Sized+Debug {
@@ -27,6 +28,19 @@ pub trait ClientConnection: Sized+Debug {
   /// * `bytes` - the serialized Message
   fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>>;
   // TODO: split connect, send and read...
+
+  /// Receives the next message from the server, without sending a request. This is used for
+  ///  responses which span multiple messages, e.g. AXFR, which is only possible over TCP.
+  fn receive(&mut self) -> ClientResult<Vec<u8>> {
+    Err(ClientErrorKind::Message("multiple message responses are not supported by this connection").into())
+  }
+
+  /// Switches to the next upstream name server, if the connection has more than one. This is
+  ///  called by the `Client` between retries when the `RetryPolicy` requests it.
+  fn next_upstream(&mut self) {}
+
+  /// The transport used by this connection, reported to the `QueryObserver` of the `Client`
+  fn transport(&self) -> Transport { Transport::Unknown }
 }
 
 #[cfg(test)]
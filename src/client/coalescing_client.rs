@@ -0,0 +1,245 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A Client which can be shared between threads, coalescing identical in-flight queries
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+
+use ::error::*;
+use ::client::{Client, ClientConnection};
+use ::op::Message;
+use ::rr::{DNSClass, Name, RecordType};
+
+type QueryKey = (Name, DNSClass, RecordType);
+
+/// The result of an outstanding query, shared by all the callers waiting on it
+struct InFlight {
+  // errors are not Clone, waiters receive the description of the error
+  result: Mutex<Option<Result<Message, String>>>,
+  ready: Condvar,
+}
+
+impl InFlight {
+  fn new() -> Self {
+    InFlight{ result: Mutex::new(None), ready: Condvar::new() }
+  }
+
+  fn complete(&self, result: Result<Message, String>) {
+    let mut guard = self.result.lock().expect("in flight lock poisoned");
+    *guard = Some(result);
+    self.ready.notify_all();
+  }
+
+  fn wait(&self) -> Result<Message, String> {
+    let mut guard = self.result.lock().expect("in flight lock poisoned");
+    while guard.is_none() {
+      guard = self.ready.wait(guard).expect("in flight lock poisoned");
+    }
+
+    guard.as_ref().expect("result was not set").clone()
+  }
+}
+
+/// Removes the in flight query once the leading caller is done with it, even if it panics, so that
+///  the waiting callers are always released.
+struct Leader<'a> {
+  in_flight_queries: &'a Mutex<HashMap<QueryKey, Arc<InFlight>>>,
+  key: QueryKey,
+  in_flight: Arc<InFlight>,
+  completed: bool,
+}
+
+impl<'a> Leader<'a> {
+  fn complete(mut self, result: &ClientResult<Message>) {
+    let shared = match *result {
+      Ok(ref message) => Ok(message.clone()),
+      Err(ref error) => Err(error.to_string()),
+    };
+
+    self.remove();
+    self.in_flight.complete(shared);
+    self.completed = true;
+  }
+
+  fn remove(&self) {
+    if let Ok(mut in_flight_queries) = self.in_flight_queries.lock() {
+      in_flight_queries.remove(&self.key);
+    }
+  }
+}
+
+impl<'a> Drop for Leader<'a> {
+  fn drop(&mut self) {
+    if !self.completed {
+      self.remove();
+      self.in_flight.complete(Err("query was abandoned".to_string()));
+    }
+  }
+}
+
+/// Wraps a `Client` so that it may be shared between threads, if a query is made for the same
+///  name, class and type as a query which is already outstanding, the caller waits for the
+///  outstanding query and receives a copy of its response, rather than sending another request.
+///
+/// Requests are sent one at a time over the underlying `Client`.
+pub struct CoalescingClient<C: ClientConnection> {
+  client: Mutex<Client<C>>,
+  in_flight_queries: Mutex<HashMap<QueryKey, Arc<InFlight>>>,
+}
+
+impl<C: ClientConnection> CoalescingClient<C> {
+  /// Creates a new coalescing client
+  ///
+  /// # Arguments
+  ///
+  /// * `client` - the client over which the queries are sent
+  pub fn new(client: Client<C>) -> Self {
+    CoalescingClient{ client: Mutex::new(client), in_flight_queries: Mutex::new(HashMap::new()) }
+  }
+
+  /// Returns the wrapped client
+  pub fn into_inner(self) -> Client<C> {
+    self.client.into_inner().expect("client lock poisoned")
+  }
+
+  /// The number of distinct queries currently outstanding
+  pub fn in_flight_count(&self) -> usize {
+    self.in_flight_queries.lock().expect("in flight lock poisoned").len()
+  }
+
+  /// Performs a query, see `Client::query()`. If an identical query is already outstanding, this
+  ///  waits for its response instead of sending a new request.
+  ///
+  /// If the shared query fails, the waiting callers receive `ClientErrorKind::Coalesced` with the
+  ///  description of the original error.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - the label to lookup
+  /// * `query_class` - most likely this should always be DNSClass::IN
+  /// * `query_type` - record type to lookup
+  pub fn query(&self, name: &Name, query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
+    let key: QueryKey = (name.clone(), query_class, query_type);
+
+    let (in_flight, leader) = {
+      let mut in_flight_queries = self.in_flight_queries.lock().expect("in flight lock poisoned");
+      let existing = in_flight_queries.get(&key).cloned();
+
+      match existing {
+        Some(in_flight) => (in_flight, false),
+        None => {
+          let in_flight = Arc::new(InFlight::new());
+          in_flight_queries.insert(key.clone(), in_flight.clone());
+          (in_flight, true)
+        },
+      }
+    };
+
+    if !leader {
+      debug!("attaching to in flight query: {} {:?} {:?}", name, query_class, query_type);
+      return in_flight.wait().map_err(|error| ClientErrorKind::Coalesced(error).into())
+    }
+
+    let leader = Leader{ in_flight_queries: &self.in_flight_queries, key: key, in_flight: in_flight, completed: false };
+    let result = self.client.lock().expect("client lock poisoned").query(name, query_class, query_type);
+    leader.complete(&result);
+
+    result
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fmt;
+  use std::net::Ipv4Addr;
+  use std::sync::{Arc, Barrier};
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::thread;
+  use std::time::Duration;
+
+  use ::client::{Client, ClientConnection};
+  use ::error::*;
+  use ::op::{Message, MessageType};
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+  use super::CoalescingClient;
+
+  /// answers every query with an A record, slowly, counting the requests
+  struct SlowConnection {
+    sent: Arc<AtomicUsize>,
+  }
+
+  impl ClientConnection for SlowConnection {
+    fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+      self.sent.fetch_add(1, Ordering::SeqCst);
+      thread::sleep(Duration::from_millis(200));
+
+      let request = try!(Message::read(&mut BinDecoder::new(&bytes)));
+      let mut response = Message::new();
+      response.id(request.get_id()).message_type(MessageType::Response).add_all_queries(request.get_queries());
+
+      let mut record = Record::with(request.get_queries()[0].get_name().clone(), RecordType::A, 86400);
+      record.rdata(RData::A(Ipv4Addr::new(93,184,216,34)));
+      response.add_answer(record);
+
+      let mut buf = Vec::with_capacity(512);
+      {
+        let mut encoder = BinEncoder::new(&mut buf);
+        try!(response.emit(&mut encoder));
+      }
+
+      Ok(buf)
+    }
+  }
+
+  impl fmt::Debug for SlowConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "SlowConnection")
+    }
+  }
+
+  #[test]
+  fn test_coalesce() {
+    let sent = Arc::new(AtomicUsize::new(0));
+    let client = Arc::new(CoalescingClient::new(Client::new(SlowConnection{ sent: sent.clone() })));
+    let barrier = Arc::new(Barrier::new(4));
+    let name = Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
+
+    let threads: Vec<_> = (0..4).map(|_| {
+      let client = client.clone();
+      let barrier = barrier.clone();
+      let name = name.clone();
+
+      thread::spawn(move || {
+        barrier.wait();
+        client.query(&name, DNSClass::IN, RecordType::A).expect("query failed")
+      })
+    }).collect();
+
+    for thread in threads {
+      let response = thread.join().expect("thread panicked");
+      assert_eq!(response.get_answers().len(), 1);
+    }
+
+    assert_eq!(sent.load(Ordering::SeqCst), 1);
+    assert_eq!(client.in_flight_count(), 0);
+
+    // once complete, the next query is sent
+    client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(sent.load(Ordering::SeqCst), 2);
+  }
+}
@@ -0,0 +1,258 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! DNS Cookies for the `Client`, with persistence of the cookie state
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::SocketAddr;
+use std::str;
+use std::sync::{Arc, Mutex};
+
+use openssl::crypto::hash;
+use openssl::crypto::hmac;
+use rand::{self, Rng};
+
+use ::client::MessageInterceptor;
+use ::error::*;
+use ::op::{Edns, Message};
+use ::rr::rdata::opt::{EdnsCode, EdnsOption};
+
+const CLIENT_COOKIE_LEN: usize = 8;
+const MIN_SERVER_COOKIE_LEN: usize = 8;
+const MAX_SERVER_COOKIE_LEN: usize = 32;
+
+/// The state of [RFC 7873](https://tools.ietf.org/html/rfc7873) DNS Cookies, the client secret
+///  and the server cookies learned from each server.
+///
+/// The state can be saved and loaded, so that a short lived process presents the same client
+///  cookie, and the server cookie it was previously issued, on its first request to a server.
+///  The saved state contains the client secret and must be kept private.
+///
+/// ```text
+/// 4.1.  The Client Cookie
+///
+///    The Client Cookie SHOULD be a pseudorandom function of the Client IP
+///    Address, the Server IP Address, and a secret quantity known only to
+///    the client.  This Client Secret SHOULD have at least 64 bits of
+///    entropy [RFC4086] and be changed periodically.
+/// ```
+///
+/// The client address is not known before the request is sent, so the client cookie is derived
+///  from the client secret and the server address.
+pub struct CookieJar {
+  client_secret: Vec<u8>,
+  server_cookies: Mutex<HashMap<SocketAddr, Vec<u8>>>,
+}
+
+impl CookieJar {
+  /// A new jar, with a random client secret and no server cookies
+  pub fn new() -> Self {
+    let mut client_secret = vec![0; 16];
+    rand::thread_rng().fill_bytes(&mut client_secret);
+    CookieJar{ client_secret: client_secret, server_cookies: Mutex::new(HashMap::new()) }
+  }
+
+  /// Loads a jar previously written with `save()`
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - the saved state, e.g. a `File`
+  pub fn load<R: Read>(reader: R) -> ClientResult<Self> {
+    let mut client_secret: Option<Vec<u8>> = None;
+    let mut server_cookies: HashMap<SocketAddr, Vec<u8>> = HashMap::new();
+
+    for line in BufReader::new(reader).lines() {
+      let line = try!(line);
+      let fields: Vec<&str> = line.split_whitespace().collect();
+
+      match (fields.len(), fields.first()) {
+        (0, _) => (),
+        (2, Some(&"secret")) => client_secret = Some(try!(from_hex(fields[1]))),
+        (3, Some(&"server")) => {
+          let address: SocketAddr = try!(fields[1].parse().map_err(|_| ClientError::from(ClientErrorKind::Message("invalid server address in cookie jar"))));
+          server_cookies.insert(address, try!(from_hex(fields[2])));
+        },
+        _ => return Err(ClientErrorKind::Message("invalid line in cookie jar").into()),
+      }
+    }
+
+    match client_secret {
+      Some(client_secret) => Ok(CookieJar{ client_secret: client_secret, server_cookies: Mutex::new(server_cookies) }),
+      None => Err(ClientErrorKind::Message("cookie jar has no client secret").into()),
+    }
+  }
+
+  /// Writes the client secret and the server cookies, one per line
+  ///
+  /// # Arguments
+  ///
+  /// * `writer` - the destination for the state, e.g. a `File`
+  pub fn save<W: Write>(&self, mut writer: W) -> ClientResult<()> {
+    try!(writeln!(writer, "secret {}", to_hex(&self.client_secret)));
+    for (address, cookie) in self.server_cookies.lock().unwrap().iter() {
+      try!(writeln!(writer, "server {} {}", address, to_hex(cookie)));
+    }
+    Ok(())
+  }
+
+  /// The client cookie presented to the server
+  pub fn client_cookie(&self, server: &SocketAddr) -> Vec<u8> {
+    let mut cookie = hmac::hmac(hash::Type::SHA256, &self.client_secret, server.to_string().as_bytes());
+    cookie.truncate(CLIENT_COOKIE_LEN);
+    cookie
+  }
+
+  /// The last server cookie received from the server, if any
+  pub fn get_server_cookie(&self, server: &SocketAddr) -> Option<Vec<u8>> {
+    self.server_cookies.lock().unwrap().get(server).cloned()
+  }
+
+  fn set_server_cookie(&self, server: SocketAddr, cookie: Vec<u8>) {
+    self.server_cookies.lock().unwrap().insert(server, cookie);
+  }
+}
+
+/// Adds the COOKIE option to each request, and learns the server cookie from the responses.
+///  Responses with a cookie option which does not echo the client cookie are rejected as
+///  possibly spoofed, responses without a cookie option are accepted, as the server may not
+///  support cookies.
+///
+/// The `Client` does not know the address of the server it sends to, so each interceptor is
+///  for one server, the jar may be shared across them, see `Client::add_interceptor()`.
+pub struct CookieInterceptor {
+  jar: Arc<CookieJar>,
+  server: SocketAddr,
+}
+
+impl CookieInterceptor {
+  /// # Arguments
+  ///
+  /// * `jar` - the cookie state, which may be shared with other interceptors and saved
+  /// * `server` - the address of the server the `Client` is connected to
+  pub fn new(jar: Arc<CookieJar>, server: SocketAddr) -> Self {
+    CookieInterceptor{ jar: jar, server: server }
+  }
+}
+
+impl MessageInterceptor for CookieInterceptor {
+  fn on_request(&self, request: &mut Message) -> ClientResult<()> {
+    let mut cookie = self.jar.client_cookie(&self.server);
+    if let Some(server_cookie) = self.jar.get_server_cookie(&self.server) {
+      cookie.extend_from_slice(&server_cookie);
+    }
+
+    let mut edns = request.get_edns().cloned().unwrap_or_else(Edns::new);
+    edns.set_option(EdnsOption::Unknown(u16::from(EdnsCode::Cookie), cookie));
+    request.set_edns(edns);
+    Ok(())
+  }
+
+  fn on_response(&self, _: &Message, response: &mut Message) -> ClientResult<()> {
+    let cookie = match response.get_edns().and_then(|edns| edns.get_option(&EdnsCode::Cookie)) {
+      Some(&EdnsOption::Unknown(_, ref cookie)) => cookie.clone(),
+      _ => return Ok(()),
+    };
+
+    if cookie.len() < CLIENT_COOKIE_LEN + MIN_SERVER_COOKIE_LEN || cookie.len() > CLIENT_COOKIE_LEN + MAX_SERVER_COOKIE_LEN {
+      return Err(ClientErrorKind::Message("malformed cookie in response").into())
+    }
+
+    if cookie[..CLIENT_COOKIE_LEN] != self.jar.client_cookie(&self.server)[..] {
+      warn!("response client cookie does not match, from: {}", self.server);
+      return Err(ClientErrorKind::Message("response client cookie does not match the request").into())
+    }
+
+    self.jar.set_server_cookie(self.server, cookie[CLIENT_COOKIE_LEN..].to_vec());
+    Ok(())
+  }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> ClientResult<Vec<u8>> {
+  if hex.len() % 2 != 0 { return Err(ClientErrorKind::Message("invalid hex in cookie jar").into()) }
+
+  hex.as_bytes().chunks(2).map(|pair| {
+    str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                        .ok_or(ClientError::from(ClientErrorKind::Message("invalid hex in cookie jar")))
+  }).collect()
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use ::client::MessageInterceptor;
+  use ::op::{Edns, Message};
+  use ::rr::rdata::opt::{EdnsCode, EdnsOption};
+  use super::*;
+
+  fn response_with_cookie(cookie: Vec<u8>) -> Message {
+    let mut edns = Edns::new();
+    edns.set_option(EdnsOption::Unknown(10, cookie));
+    let mut response = Message::new();
+    response.set_edns(edns);
+    response
+  }
+
+  #[test]
+  fn test_cookie_exchange() {
+    let jar = Arc::new(CookieJar::new());
+    let server = "192.0.2.1:53".parse().unwrap();
+    let interceptor = CookieInterceptor::new(jar.clone(), server);
+
+    let mut request = Message::new();
+    interceptor.on_request(&mut request).unwrap();
+    let client_cookie = match request.get_edns().unwrap().get_option(&EdnsCode::Cookie) {
+      Some(&EdnsOption::Unknown(10, ref cookie)) => cookie.clone(),
+      option => panic!("unexpected option: {:?}", option),
+    };
+    assert_eq!(client_cookie.len(), 8);
+
+    let mut cookie = client_cookie.clone();
+    cookie.extend_from_slice(&[1,2,3,4,5,6,7,8]);
+    interceptor.on_response(&request, &mut response_with_cookie(cookie.clone())).unwrap();
+    assert_eq!(jar.get_server_cookie(&server), Some(vec![1,2,3,4,5,6,7,8]));
+
+    // the server cookie is now sent with the request
+    let mut request = Message::new();
+    interceptor.on_request(&mut request).unwrap();
+    assert_eq!(request.get_edns().unwrap().get_option(&EdnsCode::Cookie), Some(&EdnsOption::Unknown(10, cookie)));
+
+    // a response which does not echo the client cookie is rejected
+    let spoofed = vec![0,0,0,0,0,0,0,0,1,2,3,4,5,6,7,8];
+    assert!(interceptor.on_response(&request, &mut response_with_cookie(spoofed)).is_err());
+  }
+
+  #[test]
+  fn test_save_load() {
+    let jar = CookieJar::new();
+    let server = "192.0.2.1:53".parse().unwrap();
+    jar.set_server_cookie(server, vec![1,2,3,4,5,6,7,8,0xab]);
+
+    let mut saved = Vec::new();
+    jar.save(&mut saved).unwrap();
+
+    let loaded = CookieJar::load(&saved[..]).unwrap();
+    assert_eq!(loaded.client_cookie(&server), jar.client_cookie(&server));
+    assert_eq!(loaded.get_server_cookie(&server), Some(vec![1,2,3,4,5,6,7,8,0xab]));
+
+    assert!(CookieJar::load(&b"server 192.0.2.1:53 0102"[..]).is_err());
+  }
+}
@@ -0,0 +1,166 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! DNS64, synthesis of AAAA records from A records for IPv6-only clients behind a NAT64
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use ::error::*;
+
+/// The prefix lengths of RFC 6052
+const PREFIX_LENGTHS: [u8; 6] = [32, 40, 48, 56, 64, 96];
+
+/// The NAT64 prefix and exclusions of [RFC 6147](https://tools.ietf.org/html/rfc6147), see
+///  `Client::set_dns64()`.
+///
+/// When an AAAA query has no answer, the A records of the name are queried, and an AAAA record is
+///  synthesized for each of their addresses by embedding it in the NAT64 prefix, as described in
+///  [RFC 6052](https://tools.ietf.org/html/rfc6052#section-2.2).
+///
+/// ```text
+/// 5.1.4.  Exclusion of AAAA RRs
+///
+///    The DNS64 MAY perform the AAAA RR exclusion procedure.  ...
+///    By default, the DNS64 SHOULD exclude any AAAA RR whose address is
+///    a mapped IPv4 address in the ::ffff/96 prefix.
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dns64 {
+  prefix: Ipv6Addr,
+  prefix_len: u8,
+  exclude_aaaa: Vec<(Ipv6Addr, u8)>,
+  exclude_ipv4: Vec<(Ipv4Addr, u8)>,
+}
+
+impl Dns64 {
+  /// Creates a DNS64 configuration of a network specific prefix, AAAA records of IPv4-mapped
+  ///  addresses are excluded.
+  ///
+  /// # Arguments
+  ///
+  /// * `prefix` - the NAT64 prefix, the bits after the prefix length are ignored
+  /// * `prefix_len` - one of 32, 40, 48, 56, 64 or 96
+  pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> ClientResult<Self> {
+    if !PREFIX_LENGTHS.contains(&prefix_len) {
+      return Err(ClientErrorKind::Msg(format!("invalid NAT64 prefix length: {}", prefix_len)).into())
+    }
+
+    Ok(Dns64{ prefix: prefix,
+              prefix_len: prefix_len,
+              exclude_aaaa: vec![(Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96)],
+              exclude_ipv4: vec![] })
+  }
+
+  /// The well-known prefix, `64:ff9b::/96`. Addresses which are not global are excluded, per
+  ///  [RFC 6052](https://tools.ietf.org/html/rfc6052#section-3.1), the well-known prefix MUST NOT
+  ///  be used to represent non-global IPv4 addresses.
+  pub fn well_known() -> Self {
+    let mut dns64 = Self::new(Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), 96).expect("the well-known prefix is valid");
+    dns64.exclude_ipv4(Ipv4Addr::new(0,0,0,0), 8)
+         .exclude_ipv4(Ipv4Addr::new(10,0,0,0), 8)
+         .exclude_ipv4(Ipv4Addr::new(100,64,0,0), 10)
+         .exclude_ipv4(Ipv4Addr::new(127,0,0,0), 8)
+         .exclude_ipv4(Ipv4Addr::new(169,254,0,0), 16)
+         .exclude_ipv4(Ipv4Addr::new(172,16,0,0), 12)
+         .exclude_ipv4(Ipv4Addr::new(192,168,0,0), 16);
+    dns64
+  }
+
+  /// AAAA records with addresses in the prefix are treated as if they were not there, e.g. those
+  ///  of networks which can't be reached from the clients
+  pub fn exclude_aaaa(&mut self, prefix: Ipv6Addr, prefix_len: u8) -> &mut Self {
+    self.exclude_aaaa.push((prefix, prefix_len));
+    self
+  }
+
+  /// A records with addresses in the network are not synthesized
+  pub fn exclude_ipv4(&mut self, network: Ipv4Addr, prefix_len: u8) -> &mut Self {
+    self.exclude_ipv4.push((network, prefix_len));
+    self
+  }
+
+  pub fn get_prefix(&self) -> Ipv6Addr { self.prefix }
+  pub fn get_prefix_len(&self) -> u8 { self.prefix_len }
+  pub fn get_exclude_aaaa(&self) -> &[(Ipv6Addr, u8)] { &self.exclude_aaaa }
+  pub fn get_exclude_ipv4(&self) -> &[(Ipv4Addr, u8)] { &self.exclude_ipv4 }
+
+  /// true if the AAAA address is excluded, and so should not prevent synthesis
+  pub fn is_excluded_aaaa(&self, addr: &Ipv6Addr) -> bool {
+    self.exclude_aaaa.iter().any(|&(ref prefix, len)| matches(&addr.octets(), &prefix.octets(), len))
+  }
+
+  /// true if no AAAA record should be synthesized for the address
+  pub fn is_excluded_ipv4(&self, addr: &Ipv4Addr) -> bool {
+    self.exclude_ipv4.iter().any(|&(ref network, len)| matches(&addr.octets(), &network.octets(), len))
+  }
+
+  /// Embeds the IPv4 address in the prefix, bits 64 to 71 are always zero
+  pub fn synthesize(&self, addr: &Ipv4Addr) -> Ipv6Addr {
+    let mut octets = [0u8; 16];
+    let prefix = self.prefix.octets();
+    let prefix_octets = self.prefix_len as usize / 8;
+    octets[..prefix_octets].copy_from_slice(&prefix[..prefix_octets]);
+
+    let mut index = prefix_octets;
+    for octet in addr.octets().iter() {
+      if index == 8 { index += 1 }
+      octets[index] = *octet;
+      index += 1;
+    }
+
+    Ipv6Addr::from(octets)
+  }
+}
+
+/// true if the first `len` bits of the address are those of the network
+fn matches(addr: &[u8], network: &[u8], len: u8) -> bool {
+  let len = len as usize;
+  let whole = len / 8;
+  if addr[..whole] != network[..whole] { return false }
+
+  let bits = len % 8;
+  if bits == 0 { return true }
+
+  let mask = 0xffu8 << (8 - bits);
+  addr[whole] & mask == network[whole] & mask
+}
+
+#[test]
+fn test_synthesize() {
+  // the examples of RFC 6052, section 2.4
+  let addr = Ipv4Addr::new(192,0,2,33);
+  let synthesize = |prefix: &str, len: u8| Dns64::new(prefix.parse().unwrap(), len).unwrap().synthesize(&addr);
+
+  assert_eq!(synthesize("2001:db8::", 32), "2001:db8:c000:221::".parse::<Ipv6Addr>().unwrap());
+  assert_eq!(synthesize("2001:db8:100::", 40), "2001:db8:1c0:2:21::".parse::<Ipv6Addr>().unwrap());
+  assert_eq!(synthesize("2001:db8:122::", 48), "2001:db8:122:c000:2:2100::".parse::<Ipv6Addr>().unwrap());
+  assert_eq!(synthesize("2001:db8:122:300::", 56), "2001:db8:122:3c0:0:221::".parse::<Ipv6Addr>().unwrap());
+  assert_eq!(synthesize("2001:db8:122:344::", 64), "2001:db8:122:344:c0:2:2100:0".parse::<Ipv6Addr>().unwrap());
+  assert_eq!(synthesize("2001:db8:122:344::", 96), "2001:db8:122:344::c000:221".parse::<Ipv6Addr>().unwrap());
+  assert_eq!(Dns64::well_known().synthesize(&addr), "64:ff9b::c000:221".parse::<Ipv6Addr>().unwrap());
+
+  assert!(Dns64::new("2001:db8::".parse().unwrap(), 33).is_err());
+}
+
+#[test]
+fn test_exclusions() {
+  let dns64 = Dns64::well_known();
+  assert!(dns64.is_excluded_aaaa(&"::ffff:192.0.2.1".parse().unwrap()));
+  assert!(!dns64.is_excluded_aaaa(&"2001:db8::1".parse().unwrap()));
+  assert!(dns64.is_excluded_ipv4(&Ipv4Addr::new(172,20,0,1)));
+  assert!(!dns64.is_excluded_ipv4(&Ipv4Addr::new(172,32,0,1)));
+  assert!(!dns64.is_excluded_ipv4(&Ipv4Addr::new(192,0,2,33)));
+}
@@ -0,0 +1,53 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Middleware for the `Client`, interceptors may inspect, modify or reject messages
+
+use ::error::*;
+use ::op::Message;
+
+/// Invoked with each request before it is sent and with each response before it is returned to
+///  the caller, added with `Client::add_interceptor()`.
+///
+/// Interceptors run in the order they were added for requests, and in the reverse order for
+///  responses, like layers of middleware. Both methods default to passing the message through
+///  unchanged. Returning an error rejects the message, the error is returned from the `Client`
+///  request, and is only retried if `RetryPolicy::is_retryable_error()` is true for it.
+///
+/// Requests are intercepted after they are signed, so an interceptor which modifies a SIG(0)
+///  signed update will invalidate its signature.
+pub trait MessageInterceptor: Send + Sync {
+  /// Called with the request before it is sent, on each attempt
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the message to be sent, it may be modified, e.g. to add EDNS options
+  fn on_request(&self, request: &mut Message) -> ClientResult<()> {
+    let _ = request;
+    Ok(())
+  }
+
+  /// Called with each response, after it has been validated as the response to the request
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the message which was sent, after all interceptors modified it
+  /// * `response` - the response from the server, which may be modified
+  fn on_response(&self, request: &Message, response: &mut Message) -> ClientResult<()> {
+    let _ = (request, response);
+    Ok(())
+  }
+}
@@ -0,0 +1,286 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Ordering of the addresses returned from `Client::lookup_ip()`
+
+use std::cmp::Ordering;
+use std::net::{IpAddr, Ipv6Addr, UdpSocket};
+
+/// The order in which the addresses of each family are returned from `Client::lookup_ip()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressOrder {
+  /// All IPv4 addresses, followed by all IPv6 addresses
+  Ipv4First,
+  /// All IPv6 addresses, followed by all IPv4 addresses
+  Ipv6First,
+  /// Sorted by the destination address selection of
+  ///  [RFC 6724](https://tools.ietf.org/html/rfc6724#section-6), with the source address the
+  ///  system would use to reach each address, see `sort_rfc6724()`
+  Rfc6724,
+}
+
+/// The address families queried by `Client::lookup_ip_with_strategy()`, e.g. an IPv6-only host
+///  has no use for IPv4 addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookupIpStrategy {
+  /// Only A records are queried
+  Ipv4Only,
+  /// Only AAAA records are queried
+  Ipv6Only,
+  /// Both A and AAAA records are queried, and merged in the `AddressOrder`
+  Ipv4AndIpv6,
+  /// AAAA records are queried, A records only if there are no IPv6 addresses
+  Ipv6ThenIpv4,
+  /// A records are queried, AAAA records only if there are no IPv4 addresses
+  Ipv4ThenIpv6,
+}
+
+impl Default for LookupIpStrategy {
+  fn default() -> Self { LookupIpStrategy::Ipv4AndIpv6 }
+}
+
+impl Default for AddressOrder {
+  fn default() -> Self { AddressOrder::Ipv4First }
+}
+
+impl AddressOrder {
+  /// Merges the addresses of each family into a single list in this order, duplicates are removed
+  ///  keeping the first occurrence.
+  pub fn merge(&self, ipv4: Vec<IpAddr>, ipv6: Vec<IpAddr>) -> Vec<IpAddr> {
+    let (first, second) = match *self {
+      AddressOrder::Ipv4First => (ipv4, ipv6),
+      AddressOrder::Ipv6First => (ipv6, ipv4),
+      AddressOrder::Rfc6724 => {
+        let mut addresses = AddressOrder::Ipv6First.merge(ipv4, ipv6);
+        sort_rfc6724(&mut addresses, source_address);
+        return addresses
+      },
+    };
+
+    let mut addresses: Vec<IpAddr> = Vec::with_capacity(first.len() + second.len());
+    for addr in first.into_iter().chain(second.into_iter()) {
+      if !addresses.contains(&addr) { addresses.push(addr); }
+    }
+
+    addresses
+  }
+}
+
+/// Sorts the addresses by the destination address selection rules of RFC 6724, the best address
+///  first. Rules 3, 4 and 7, deprecated and home addresses and native transport, aren't applied,
+///  otherwise addresses which compare equal keep their order.
+///
+/// ```text
+/// Rule 1:  Avoid unusable destinations.
+/// Rule 2:  Prefer matching scope.
+/// Rule 5:  Prefer matching label.
+/// Rule 6:  Prefer higher precedence.
+/// Rule 8:  Prefer smaller scope.
+/// Rule 9:  Use longest matching prefix.
+/// Rule 10:  Otherwise, leave the order unchanged.
+/// ```
+///
+/// # Arguments
+///
+/// * `addresses` - the destination addresses to sort
+/// * `source_for` - the source address used to reach a destination, `None` if it is unreachable
+pub fn sort_rfc6724<F>(addresses: &mut Vec<IpAddr>, source_for: F) where F: Fn(&IpAddr) -> Option<IpAddr> {
+  let mut destinations: Vec<(IpAddr, Option<IpAddr>)> = addresses.iter().map(|addr| (*addr, source_for(addr))).collect();
+  destinations.sort_by(|a, b| compare(a, b));
+
+  addresses.clear();
+  addresses.extend(destinations.into_iter().map(|(addr, _)| addr));
+}
+
+/// The source address of the route to the address, found by connecting a UDP socket, which sends
+///  no packets
+fn source_address(addr: &IpAddr) -> Option<IpAddr> {
+  let bind = match *addr {
+    IpAddr::V4(..) => "0.0.0.0:0",
+    IpAddr::V6(..) => "[::]:0",
+  };
+
+  let socket = match UdpSocket::bind(bind) {
+    Ok(socket) => socket,
+    Err(..) => return None,
+  };
+
+  if socket.connect((*addr, 53)).is_err() { return None }
+  socket.local_addr().ok().map(|local| local.ip())
+}
+
+/// Less is better
+fn compare(&(ref da, ref sa): &(IpAddr, Option<IpAddr>), &(ref db, ref sb): &(IpAddr, Option<IpAddr>)) -> Ordering {
+  let (sa, sb) = match (*sa, *sb) {
+    (Some(sa), Some(sb)) => (sa, sb),
+    // rule 1
+    (Some(..), None) => return Ordering::Less,
+    (None, Some(..)) => return Ordering::Greater,
+    (None, None) => return Ordering::Equal,
+  };
+
+  let (da, db, sa, sb) = (to_ipv6(da), to_ipv6(db), to_ipv6(&sa), to_ipv6(&sb));
+
+  // rule 2, prefer matching scope
+  let scope_a = scope(&da) == scope(&sa);
+  let scope_b = scope(&db) == scope(&sb);
+  if scope_a != scope_b { return if scope_a { Ordering::Less } else { Ordering::Greater } }
+
+  // rule 5, prefer matching label
+  let (policy_da, policy_db) = (policy(&da), policy(&db));
+  let label_a = policy_da.1 == policy(&sa).1;
+  let label_b = policy_db.1 == policy(&sb).1;
+  if label_a != label_b { return if label_a { Ordering::Less } else { Ordering::Greater } }
+
+  // rule 6, prefer higher precedence
+  if policy_da.0 != policy_db.0 { return policy_db.0.cmp(&policy_da.0) }
+
+  // rule 8, prefer smaller scope
+  let (scope_da, scope_db) = (scope(&da), scope(&db));
+  if scope_da != scope_db { return scope_da.cmp(&scope_db) }
+
+  // rule 9, longest matching prefix, only of addresses of the same family
+  if is_ipv4(&da) == is_ipv4(&db) {
+    let (prefix_a, prefix_b) = if is_ipv4(&da) {
+      (common_prefix(&da.octets()[12..], &sa.octets()[12..], 32), common_prefix(&db.octets()[12..], &sb.octets()[12..], 32))
+    } else {
+      (common_prefix(&da.octets(), &sa.octets(), 64), common_prefix(&db.octets(), &sb.octets(), 64))
+    };
+    if prefix_a != prefix_b { return prefix_b.cmp(&prefix_a) }
+  }
+
+  // rule 10
+  Ordering::Equal
+}
+
+/// IPv4 addresses are treated as IPv4-mapped IPv6 addresses, section 2.1
+fn to_ipv6(addr: &IpAddr) -> Ipv6Addr {
+  match *addr {
+    IpAddr::V4(ref v4) => {
+      let o = v4.octets();
+      Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, (o[0] as u16) << 8 | o[1] as u16, (o[2] as u16) << 8 | o[3] as u16)
+    },
+    IpAddr::V6(v6) => v6,
+  }
+}
+
+fn is_ipv4(addr: &Ipv6Addr) -> bool {
+  matches_prefix(addr, &Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96)
+}
+
+/// The scope of the address, section 3.1, 0x2 link-local, 0x5 site-local, 0xe global
+fn scope(addr: &Ipv6Addr) -> u8 {
+  let octets = addr.octets();
+  if is_ipv4(addr) {
+    // 127/8 and 169.254/16 are link-local, section 3.2
+    return if octets[12] == 127 || (octets[12] == 169 && octets[13] == 254) { 0x2 } else { 0xe }
+  }
+
+  if octets[0] == 0xff { return octets[1] & 0x0f }
+  if *addr == Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1) { return 0x2 }
+  if matches_prefix(addr, &Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10) { return 0x2 }
+  if matches_prefix(addr, &Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 0), 10) { return 0x5 }
+  0xe
+}
+
+/// The (precedence, label) of the longest matching prefix of the default policy table, section 2.1
+fn policy(addr: &Ipv6Addr) -> (u8, u8) {
+  let table: [(Ipv6Addr, u8, u8, u8); 9] = [
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 128, 50, 0),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96, 35, 4),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 96, 1, 3),
+    (Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 0), 32, 5, 5),
+    (Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 0), 16, 30, 2),
+    (Ipv6Addr::new(0x3ffe, 0, 0, 0, 0, 0, 0, 0), 16, 1, 12),
+    (Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 0), 10, 1, 11),
+    (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7, 3, 13),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 40, 1),
+  ];
+
+  // ordered by prefix length, the first match is the longest
+  table.iter()
+       .find(|&&(ref prefix, len, _, _)| matches_prefix(addr, prefix, len))
+       .map(|&(_, _, precedence, label)| (precedence, label))
+       .expect("::/0 matches all addresses")
+}
+
+fn matches_prefix(addr: &Ipv6Addr, prefix: &Ipv6Addr, len: u8) -> bool {
+  common_prefix(&addr.octets(), &prefix.octets(), len) == len
+}
+
+/// The number of leading bits in common, at most `max`
+fn common_prefix(a: &[u8], b: &[u8], max: u8) -> u8 {
+  let mut bits = 0u8;
+  for (x, y) in a.iter().zip(b.iter()) {
+    let diff = x ^ y;
+    if diff != 0 {
+      bits += diff.leading_zeros() as u8;
+      break
+    }
+    bits += 8;
+  }
+
+  if bits > max { max } else { bits }
+}
+
+#[test]
+fn test_merge() {
+  let v4: IpAddr = "93.184.216.34".parse().unwrap();
+  let v6: IpAddr = "2606:2800:220:1:248:1893:25c8:1946".parse().unwrap();
+
+  assert_eq!(AddressOrder::Ipv4First.merge(vec![v4, v4], vec![v6]), vec![v4, v6]);
+  assert_eq!(AddressOrder::Ipv6First.merge(vec![v4], vec![v6, v6]), vec![v6, v4]);
+}
+
+#[test]
+fn test_sort_rfc6724() {
+  // the examples of RFC 6724, section 10.2
+  fn sorted(destinations: &[&str], sources: &[(&str, &str)]) -> Vec<IpAddr> {
+    let mut addresses: Vec<IpAddr> = destinations.iter().map(|d| d.parse().unwrap()).collect();
+    let sources: Vec<(IpAddr, IpAddr)> = sources.iter().map(|&(d, s)| (d.parse().unwrap(), s.parse().unwrap())).collect();
+    sort_rfc6724(&mut addresses, |addr| sources.iter().find(|&&(d, _)| d == *addr).map(|&(_, s)| s));
+    addresses
+  }
+  fn addrs(addresses: &[&str]) -> Vec<IpAddr> { addresses.iter().map(|a| a.parse().unwrap()).collect() }
+
+  // prefer matching scope
+  assert_eq!(sorted(&["2001:db8:1::1", "198.51.100.121"], &[("2001:db8:1::1", "2001:db8:1::2"), ("198.51.100.121", "169.254.13.78")]),
+             addrs(&["2001:db8:1::1", "198.51.100.121"]));
+  assert_eq!(sorted(&["2001:db8:1::1", "198.51.100.121"], &[("2001:db8:1::1", "fe80::1"), ("198.51.100.121", "198.51.100.117")]),
+             addrs(&["198.51.100.121", "2001:db8:1::1"]));
+
+  // prefer higher precedence
+  assert_eq!(sorted(&["2001:db8:1::1", "10.1.2.3"], &[("2001:db8:1::1", "2001:db8:1::2"), ("10.1.2.3", "10.1.2.4")]),
+             addrs(&["2001:db8:1::1", "10.1.2.3"]));
+
+  // prefer smaller scope
+  assert_eq!(sorted(&["2001:db8:1::1", "fe80::1"], &[("2001:db8:1::1", "2001:db8:1::2"), ("fe80::1", "fe80::2")]),
+             addrs(&["fe80::1", "2001:db8:1::1"]));
+
+  // longest matching prefix
+  assert_eq!(sorted(&["2001:db8:1::1", "2001:db8:3ffe::1"], &[("2001:db8:1::1", "2001:db8:1::2"), ("2001:db8:3ffe::1", "2001:db8:3f44::2")]),
+             addrs(&["2001:db8:1::1", "2001:db8:3ffe::1"]));
+
+  // prefer matching label
+  assert_eq!(sorted(&["2002:c633:6401::1", "2001:db8:1::1"], &[("2002:c633:6401::1", "2002:c633:6401::2"), ("2001:db8:1::1", "2002:c633:6401::2")]),
+             addrs(&["2002:c633:6401::1", "2001:db8:1::1"]));
+  assert_eq!(sorted(&["2002:c633:6401::1", "2001:db8:1::1"], &[("2002:c633:6401::1", "2002:c633:6401::2"), ("2001:db8:1::1", "2001:db8:1::2")]),
+             addrs(&["2001:db8:1::1", "2002:c633:6401::1"]));
+
+  // avoid unusable destinations
+  assert_eq!(sorted(&["2001:db8:1::1", "198.51.100.121"], &[("198.51.100.121", "198.51.100.117")]),
+             addrs(&["198.51.100.121", "2001:db8:1::1"]));
+}
@@ -0,0 +1,93 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Mail exchanges returned from `Client::mx_lookup()`
+
+use std::net::IpAddr;
+
+use rand::{self, Rng};
+
+use ::rr::Name;
+use ::rr::rdata::MX;
+
+/// A mail exchange for a domain, along with its addresses if they were resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailExchange {
+  preference: u16,
+  exchange: Name,
+  addresses: Vec<IpAddr>,
+}
+
+impl MailExchange {
+  pub fn new(preference: u16, exchange: Name) -> Self {
+    MailExchange{ preference: preference, exchange: exchange, addresses: vec![] }
+  }
+
+  /// the preference of this exchange, lower values are preferred
+  pub fn get_preference(&self) -> u16 { self.preference }
+  /// the host which accepts mail for the domain
+  pub fn get_exchange(&self) -> &Name { &self.exchange }
+  /// the addresses of the exchange, empty unless they were resolved
+  pub fn get_addresses(&self) -> &[IpAddr] { &self.addresses }
+
+  pub fn set_addresses(&mut self, addresses: Vec<IpAddr>) { self.addresses = addresses; }
+}
+
+impl<'a> From<&'a MX> for MailExchange {
+  fn from(mx: &'a MX) -> Self {
+    MailExchange::new(mx.get_preference(), mx.get_exchange().clone())
+  }
+}
+
+/// Sorts the exchanges by preference, exchanges of equal preference are shuffled so that load is
+///  spread across them, [RFC 5321](https://tools.ietf.org/html/rfc5321#section-5.1)
+///
+/// ```text
+/// If there are multiple destinations with the same preference and there
+/// is no clear reason to favor one (e.g., by recognition of an easily
+/// reached address), then the sender-SMTP MUST randomize them to spread
+/// the load across multiple mail exchangers for a specific organization.
+/// ```
+pub fn sort_by_preference(exchanges: &mut Vec<MailExchange>) {
+  exchanges.sort_by_key(|mx| mx.get_preference());
+
+  let mut rng = rand::thread_rng();
+  let mut start = 0;
+  while start < exchanges.len() {
+    let preference = exchanges[start].get_preference();
+    let end = exchanges[start..].iter().position(|mx| mx.get_preference() != preference)
+                                       .map_or(exchanges.len(), |len| start + len);
+
+    rng.shuffle(&mut exchanges[start..end]);
+    start = end;
+  }
+}
+
+#[test]
+fn test_sort_by_preference() {
+  let name = |s: &str| Name::with_labels(vec![s.to_string(), "example".to_string(), "com".to_string()]);
+
+  let mut exchanges = vec![MailExchange::new(20, name("c")),
+                           MailExchange::new(10, name("a")),
+                           MailExchange::new(10, name("b")),
+                           MailExchange::new(5, name("d"))];
+  sort_by_preference(&mut exchanges);
+
+  let preferences: Vec<u16> = exchanges.iter().map(|mx| mx.get_preference()).collect();
+  assert_eq!(preferences, vec![5, 10, 10, 20]);
+  assert_eq!(exchanges[0].get_exchange(), &name("d"));
+  assert_eq!(exchanges[3].get_exchange(), &name("c"));
+}
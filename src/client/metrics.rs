@@ -0,0 +1,83 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Instrumentation hooks for the `Client`, for exporting metrics of the requests it sends
+
+use std::time::Duration;
+
+use ::error::*;
+use ::op::Message;
+
+/// The transport over which a request was sent, see `ClientConnection::transport()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+  Udp,
+  Tcp,
+  Tls,
+  /// a connection which does not identify its transport, e.g. test or custom connections
+  Unknown,
+}
+
+/// Receives an event for every request sent by a `Client`, set with `Client::set_observer()`.
+///
+/// All methods default to doing nothing, implement those of interest. The observer is called
+///  synchronously on the thread making the request, so implementations should be quick, e.g.
+///  incrementing counters to be exported to Prometheus or statsd.
+pub trait QueryObserver: Send + Sync {
+  /// A request is about to be sent, this is called for every attempt including retries
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the message being sent
+  /// * `transport` - the transport of the connection
+  fn on_send(&self, request: &Message, transport: Transport) {
+    let _ = (request, transport);
+  }
+
+  /// A failed attempt will be retried
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the message being retried
+  /// * `attempt` - the number of the attempt which is about to be made, 2 for the first retry
+  fn on_retry(&self, request: &Message, attempt: usize) {
+    let _ = (request, attempt);
+  }
+
+  /// A response was received, the response code is `response.get_response_code()`
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the message which was sent
+  /// * `response` - the response from the server
+  /// * `latency` - the time from sending the request to receiving the response
+  /// * `transport` - the transport of the connection
+  fn on_response(&self, request: &Message, response: &Message, latency: Duration, transport: Transport) {
+    let _ = (request, response, latency, transport);
+  }
+
+  /// An attempt failed without a valid response, e.g. a timeout
+  ///
+  /// # Arguments
+  ///
+  /// * `request` - the message which was sent
+  /// * `error` - the reason the attempt failed
+  /// * `latency` - the time from sending the request to the failure
+  /// * `transport` - the transport of the connection
+  fn on_error(&self, request: &Message, error: &ClientError, latency: Duration, transport: Transport) {
+    let _ = (request, error, latency, transport);
+  }
+}
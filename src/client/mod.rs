@@ -16,11 +16,86 @@
 
 //! Use `Client` along with `trust_dns::udp::UdpClientConnection` or
 //! `trust_dns::tcp::TcpClientConnection`.
+//!
+//! The `Client` is synchronous, each request blocks until the response is received or the
+//! request times out. The connections drive their own event loop for the duration of each
+//! request, so there is no event loop for the caller to manage.
+//!
+//! ```no_run
+//! use trust_dns::client::Client;
+//! use trust_dns::udp::UdpClientConnection;
+//! use trust_dns::rr::{DNSClass, Name, RecordType};
+//!
+//! let address = "8.8.8.8:53".parse().unwrap();
+//! let conn = UdpClientConnection::new(address).unwrap();
+//! let client = Client::new(conn);
+//!
+//! let name = Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
+//! let response = client.query(&name, DNSClass::IN, RecordType::A).unwrap();
+//! println!("{:?}", response.get_answers());
+//! ```
 
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use ::error::*;
+
+mod cache;
+mod chaos;
 mod client;
 mod client_connection;
+mod coalescing_client;
+mod cookie_jar;
+mod dns64;
+mod interceptor;
+mod lookup_ip;
+mod mail_exchange;
+mod metrics;
+mod name_server_pool;
+mod query_flags;
+mod query_limiter;
+mod retry;
+mod service_lookup;
+mod tsig_client_connection;
 
-pub use self::client::Client;
+pub use self::cache::{CacheStats, ResponseCache, SharedCache};
+pub use self::chaos::ChaosQuery;
+pub use self::client::{Client, IncrementalTransfer, ZoneDiff, ZoneTransfer};
 pub use self::client_connection::ClientConnection;
+pub use self::coalescing_client::CoalescingClient;
+pub use self::cookie_jar::{CookieInterceptor, CookieJar};
+pub use self::dns64::Dns64;
+pub use self::interceptor::MessageInterceptor;
+pub use self::lookup_ip::{AddressOrder, LookupIpStrategy, sort_rfc6724};
+pub use self::mail_exchange::MailExchange;
+pub use self::metrics::{QueryObserver, Transport};
+pub use self::name_server_pool::{NameServerPool, NameServerStats, SelectionStrategy};
+pub use self::query_flags::QueryFlags;
+pub use self::query_limiter::{QueryLimiter, QueryPermit};
+pub use self::retry::RetryPolicy;
+pub use self::service_lookup::{ServiceLookup, ServiceSelection};
+pub use self::tsig_client_connection::TsigClientConnection;
 #[cfg(test)]
 pub use self::client_connection::test::TestClientConnection;
+
+/// converts the Duration to milliseconds, for the mio timers of the connections and the backoff
+///  and round trip times of the client
+#[doc(hidden)]
+pub fn duration_to_ms(duration: &Duration) -> u64 {
+  duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+/// connects on another thread, std has no timeout for connecting. If the connection is slower
+///  than the timeout it is abandoned. For the blocking connections of TLS and of the proxies.
+#[doc(hidden)]
+pub fn connect_timeout(address: SocketAddr, timeout: Duration) -> ClientResult<TcpStream> {
+  let (sender, receiver) = mpsc::channel();
+  thread::spawn(move || { let _ = sender.send(TcpStream::connect(address)); });
+
+  match receiver.recv_timeout(timeout) {
+    Ok(result) => Ok(try!(result)),
+    Err(..) => Err(ClientErrorKind::Msg(format!("timed out connecting to {}", address)).into()),
+  }
+}
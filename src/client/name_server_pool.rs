@@ -0,0 +1,399 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A pool of upstream name servers, usable as a single ClientConnection
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use rand;
+
+use ::error::*;
+use ::client::{ClientConnection, Transport, duration_to_ms};
+use ::op::{Message, MessageType, OpCode, Query};
+use ::rr::{DNSClass, Name, RecordType};
+use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+
+/// How the next name server is selected from the pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+  /// Always start with the first healthy server, failing over to the next in order
+  Ordered,
+  /// Rotate the starting server for each request
+  RoundRobin,
+  /// Start with the server with the lowest smoothed round trip time
+  LowestLatency,
+}
+
+/// A name server connection along with its health statistics
+struct NameServer<C: ClientConnection> {
+  connection: C,
+  consecutive_failures: usize,
+  down_since: Option<Instant>,
+  srtt_ms: Option<u64>, // smoothed round trip time, None until the first success
+  successes: u64,
+  failures: u64,
+  last_probe: Option<Instant>,
+}
+
+/// Health statistics of a name server in the pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameServerStats {
+  srtt: Option<Duration>,
+  successes: u64,
+  failures: u64,
+  down: bool,
+}
+
+impl NameServerStats {
+  /// the smoothed round trip time, None if the server has never responded
+  pub fn get_srtt(&self) -> Option<Duration> { self.srtt }
+  pub fn get_successes(&self) -> u64 { self.successes }
+  pub fn get_failures(&self) -> u64 { self.failures }
+  pub fn is_down(&self) -> bool { self.down }
+
+  /// the fraction of requests, including probes, which succeeded, 1.0 if none were sent
+  pub fn success_rate(&self) -> f64 {
+    let total = self.successes + self.failures;
+    if total == 0 { 1.0 } else { self.successes as f64 / total as f64 }
+  }
+}
+
+impl<C: ClientConnection> NameServer<C> {
+  fn new(connection: C) -> Self {
+    NameServer{ connection: connection, consecutive_failures: 0, down_since: None, srtt_ms: None,
+                successes: 0, failures: 0, last_probe: None }
+  }
+
+  fn record_success(&mut self, rtt: Duration) {
+    let rtt_ms = duration_to_ms(&rtt);
+
+    // the same 7/8 weighting as TCP's srtt, RFC 6298
+    self.srtt_ms = Some(match self.srtt_ms {
+      Some(srtt) => (srtt * 7 + rtt_ms) / 8,
+      None => rtt_ms,
+    });
+
+    if self.down_since.is_some() { info!("name server is back up: {:?}", self.connection); }
+    self.successes += 1;
+    self.consecutive_failures = 0;
+    self.down_since = None;
+  }
+
+  fn record_failure(&mut self, max_failures: usize) {
+    self.failures += 1;
+    self.consecutive_failures += 1;
+
+    if self.consecutive_failures >= max_failures {
+      if self.down_since.is_none() { warn!("marking name server down: {:?}", self.connection); }
+      // restarts the probe interval on failed probes
+      self.down_since = Some(Instant::now());
+    }
+  }
+
+  /// a server is usable if it is up, or it has been down for long enough that it should be probed
+  fn is_usable(&self, probe_interval: Duration) -> bool {
+    match self.down_since {
+      None => true,
+      Some(since) => since.elapsed() >= probe_interval,
+    }
+  }
+
+  /// sends a lightweight query, the root NS records, to check the health of the server
+  fn probe(&mut self, max_failures: usize) {
+    let mut query = Query::new();
+    query.name(Name::root()).query_class(DNSClass::IN).query_type(RecordType::NS);
+
+    let mut message = Message::new();
+    message.id(rand::random()).message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(64);
+    {
+      let mut encoder = BinEncoder::new(&mut bytes);
+      if let Err(error) = message.emit(&mut encoder) {
+        warn!("could not encode probe: {}", error);
+        return
+      }
+    }
+
+    self.last_probe = Some(Instant::now());
+    let start = Instant::now();
+    let result = self.connection.send(bytes).and_then(|response| {
+      let response = try!(Message::read(&mut BinDecoder::new(&response)));
+      if response.get_id() != message.get_id() { return Err(ClientErrorKind::IncorrectMessageId(response.get_id(), message.get_id()).into()) }
+      Ok(())
+    });
+
+    match result {
+      Ok(()) => self.record_success(start.elapsed()),
+      Err(error) => {
+        debug!("probe of name server {:?} failed: {}", self.connection, error);
+        self.record_failure(max_failures);
+      },
+    }
+  }
+
+  fn stats(&self) -> NameServerStats {
+    NameServerStats{ srtt: self.srtt_ms.map(Duration::from_millis),
+                     successes: self.successes,
+                     failures: self.failures,
+                     down: self.down_since.is_some() }
+  }
+}
+
+/// A set of name servers, each request is sent to the servers in an order determined by the
+///  SelectionStrategy until one of them responds.
+///
+/// Servers are marked down after a number of consecutive failures, and are then skipped until the
+///  probe interval elapses, at which point the next request will be used to probe them for health.
+pub struct NameServerPool<C: ClientConnection> {
+  name_servers: Vec<NameServer<C>>,
+  strategy: SelectionStrategy,
+  next: usize,
+  max_failures: usize,
+  probe_interval: Duration,
+  health_check_interval: Option<Duration>,
+}
+
+impl<C: ClientConnection> NameServerPool<C> {
+  /// Creates a new pool
+  ///
+  /// # Arguments
+  ///
+  /// * `connections` - connections to each of the name servers, must not be empty
+  /// * `strategy` - how to choose the server to send requests to first
+  pub fn new(connections: Vec<C>, strategy: SelectionStrategy) -> Self {
+    assert!(!connections.is_empty(), "at least one name server is required");
+
+    NameServerPool{ name_servers: connections.into_iter().map(NameServer::new).collect(),
+                    strategy: strategy,
+                    next: 0,
+                    max_failures: 3,
+                    probe_interval: Duration::from_secs(30),
+                    health_check_interval: None }
+  }
+
+  /// The number of consecutive failures before a server is marked down, default is 3
+  pub fn max_failures(&mut self, max_failures: usize) -> &mut Self { self.max_failures = max_failures; self }
+  /// How long a down server is skipped before it is probed again, default is 30 seconds
+  pub fn probe_interval(&mut self, interval: Duration) -> &mut Self { self.probe_interval = interval; self }
+  /// If set, each server is probed with a lightweight query once per interval, including servers
+  ///  which are not otherwise receiving requests, default is None. The connections are not shared
+  ///  between threads, so the probes are sent before the next request that falls due. Down servers
+  ///  are not probed until the probe interval has passed, so that their timeouts are not added to
+  ///  every request; use `probe()` from an application's own timer to keep probes off the request
+  ///  path entirely.
+  pub fn health_check_interval(&mut self, interval: Option<Duration>) -> &mut Self { self.health_check_interval = interval; self }
+
+  pub fn get_strategy(&self) -> SelectionStrategy { self.strategy }
+
+  /// the number of servers which are not currently marked down
+  pub fn healthy_count(&self) -> usize {
+    self.name_servers.iter().filter(|ns| ns.down_since.is_none()).count()
+  }
+
+  /// The health statistics of each server, in the order the servers were configured
+  pub fn get_stats(&self) -> Vec<NameServerStats> {
+    self.name_servers.iter().map(|ns| ns.stats()).collect()
+  }
+
+  /// Probes every server now, regardless of the health check interval, this can be used to drive
+  ///  the health checks from an application's own timer.
+  pub fn probe(&mut self) {
+    let max_failures = self.max_failures;
+    for name_server in self.name_servers.iter_mut() {
+      name_server.probe(max_failures);
+    }
+  }
+
+  /// probes the servers which have not been checked within the health check interval, down
+  ///  servers are skipped until the probe interval has passed
+  fn probe_due(&mut self) {
+    let interval = match self.health_check_interval {
+      Some(interval) => interval,
+      None => return,
+    };
+
+    let max_failures = self.max_failures;
+    let probe_interval = self.probe_interval;
+    for name_server in self.name_servers.iter_mut() {
+      let due = name_server.last_probe.map_or(true, |last| last.elapsed() >= interval);
+      if due && name_server.is_usable(probe_interval) { name_server.probe(max_failures); }
+    }
+  }
+
+  /// returns the indexes of the servers, in the order they should be tried
+  fn ordered_indexes(&mut self) -> Vec<usize> {
+    let len = self.name_servers.len();
+    let mut indexes: Vec<usize> = match self.strategy {
+      SelectionStrategy::Ordered => (0..len).collect(),
+      SelectionStrategy::RoundRobin => {
+        let start = self.next % len;
+        self.next = (start + 1) % len;
+        (start..len).chain(0..start).collect()
+      },
+      SelectionStrategy::LowestLatency => {
+        let mut indexes: Vec<usize> = (0..len).collect();
+        // servers without any measurement yet sort first, so that they are measured
+        indexes.sort_by_key(|i| self.name_servers[*i].srtt_ms.unwrap_or(0));
+        indexes
+      },
+    };
+
+    // healthy servers first, and those to be probed, down servers last as a final resort
+    let probe_interval = self.probe_interval;
+    let name_servers = &self.name_servers;
+    indexes.sort_by_key(|i| !name_servers[*i].is_usable(probe_interval));
+    indexes
+  }
+}
+
+impl<C: ClientConnection> ClientConnection for NameServerPool<C> {
+  fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+    let mut error: ClientError = ClientErrorKind::Message("no name servers available").into();
+    self.probe_due();
+
+    for index in self.ordered_indexes() {
+      let name_server = &mut self.name_servers[index];
+      let start = Instant::now();
+
+      match name_server.connection.send(bytes.clone()) {
+        Ok(response) => {
+          name_server.record_success(start.elapsed());
+          return Ok(response)
+        },
+        Err(e) => {
+          debug!("name server {:?} failed: {}", name_server.connection, e);
+          name_server.record_failure(self.max_failures);
+          error = e;
+        },
+      }
+    }
+
+    Err(error)
+  }
+
+  fn next_upstream(&mut self) {
+    self.next = (self.next + 1) % self.name_servers.len();
+  }
+
+  /// all connections in the pool are of the same type, the first is representative
+  fn transport(&self) -> Transport {
+    self.name_servers.first().map_or(Transport::Unknown, |ns| ns.connection.transport())
+  }
+}
+
+impl<C: ClientConnection> fmt::Debug for NameServerPool<C> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let connections: Vec<&C> = self.name_servers.iter().map(|ns| &ns.connection).collect();
+    write!(f, "NameServerPool {:?}: {:?}", self.strategy, connections)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fmt;
+  use std::time::Duration;
+
+  use super::*;
+  use ::client::ClientConnection;
+  use ::error::*;
+
+  /// responds with its id to empty requests, echos others, or fails if down
+  struct EchoConnection {
+    id: u8,
+    down: bool,
+  }
+
+  impl ClientConnection for EchoConnection {
+    fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+      if self.down { return Err(ClientErrorKind::Timeout.into()) }
+      if bytes.is_empty() { Ok(vec![self.id]) } else { Ok(bytes) }
+    }
+  }
+
+  impl fmt::Debug for EchoConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "EchoConnection {}", self.id) }
+  }
+
+  #[test]
+  fn test_ordered_failover() {
+    let mut pool = NameServerPool::new(vec![EchoConnection{ id: 0, down: true }, EchoConnection{ id: 1, down: false }], SelectionStrategy::Ordered);
+    pool.max_failures(2).probe_interval(Duration::from_secs(3600));
+
+    assert_eq!(pool.send(vec![]).unwrap(), vec![1]);
+    assert_eq!(pool.healthy_count(), 2);
+    assert_eq!(pool.send(vec![]).unwrap(), vec![1]);
+    assert_eq!(pool.healthy_count(), 1);
+
+    // the down server is now tried last
+    assert_eq!(pool.ordered_indexes(), vec![1, 0]);
+  }
+
+  #[test]
+  fn test_round_robin() {
+    let mut pool = NameServerPool::new(vec![EchoConnection{ id: 0, down: false }, EchoConnection{ id: 1, down: false }], SelectionStrategy::RoundRobin);
+
+    assert_eq!(pool.send(vec![]).unwrap(), vec![0]);
+    assert_eq!(pool.send(vec![]).unwrap(), vec![1]);
+    assert_eq!(pool.send(vec![]).unwrap(), vec![0]);
+  }
+
+  #[test]
+  fn test_health_check() {
+    let mut pool = NameServerPool::new(vec![EchoConnection{ id: 0, down: true }, EchoConnection{ id: 1, down: false }], SelectionStrategy::LowestLatency);
+    pool.max_failures(1).health_check_interval(Some(Duration::from_secs(3600)));
+
+    // the probes happen before the request, so the down server is known and skipped
+    assert_eq!(pool.send(vec![]).unwrap(), vec![1]);
+    let stats = pool.get_stats();
+    assert!(stats[0].is_down());
+    assert_eq!(stats[0].get_failures(), 1);
+    assert_eq!(stats[0].success_rate(), 0.0);
+    // the probe and the request
+    assert!(!stats[1].is_down());
+    assert_eq!(stats[1].get_successes(), 2);
+    assert!(stats[1].get_srtt().is_some());
+
+    // not yet due
+    pool.send(vec![]).unwrap();
+    assert_eq!(pool.get_stats()[0].get_failures(), 1);
+
+    pool.probe();
+    assert_eq!(pool.get_stats()[0].get_failures(), 2);
+  }
+
+  #[test]
+  fn test_down_server_not_probed() {
+    let mut pool = NameServerPool::new(vec![EchoConnection{ id: 0, down: true }, EchoConnection{ id: 1, down: false }], SelectionStrategy::Ordered);
+    pool.max_failures(1).probe_interval(Duration::from_secs(3600)).health_check_interval(Some(Duration::from_secs(0)));
+
+    assert_eq!(pool.send(vec![]).unwrap(), vec![1]);
+    assert!(pool.get_stats()[0].is_down());
+    assert_eq!(pool.get_stats()[0].get_failures(), 1);
+
+    // every server is due on each request, but the down server waits for the probe interval
+    for _ in 0..3 { assert_eq!(pool.send(vec![]).unwrap(), vec![1]); }
+    assert_eq!(pool.get_stats()[0].get_failures(), 1);
+    assert_eq!(pool.get_stats()[1].get_successes(), 8);
+  }
+
+  #[test]
+  fn test_all_down() {
+    let mut pool = NameServerPool::new(vec![EchoConnection{ id: 0, down: true }], SelectionStrategy::LowestLatency);
+    assert!(pool.send(vec![]).is_err());
+  }
+}
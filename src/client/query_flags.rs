@@ -0,0 +1,84 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Header flags of the queries sent by the `Client`
+
+use ::op::Message;
+
+/// The RD, CD and AD header flags of a query, the defaults differ between uses of the `Client`.
+///
+/// A stub resolver relies on the recursive server, a validating resolver checks the signatures
+///  itself and so disables checking upstream, and an iterative resolver does not ask the
+///  authoritative servers for recursion. The AD bit in a query requests that the server signal
+///  whether the response was validated, [RFC 6840](https://tools.ietf.org/html/rfc6840#section-5.7)
+///
+/// ```text
+/// 5.7.  Setting the AD Bit on Queries
+///
+///    The semantics of the Authentic Data (AD) bit in the query were
+///    previously undefined.  Section 4.6 of [RFC4035] instructed resolvers
+///    to always clear the AD bit when composing queries.
+///
+///    This document defines setting the AD bit in a query as a signal
+///    indicating that the requester understands and is interested in the
+///    value of the AD bit in the response.
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryFlags {
+  recursion_desired: bool,
+  checking_disabled: bool,
+  authentic_data: bool,
+}
+
+impl QueryFlags {
+  /// Recursion desired, for querying a recursive server, this is the default
+  pub fn stub() -> Self {
+    QueryFlags{ recursion_desired: true, checking_disabled: false, authentic_data: false }
+  }
+
+  /// Recursion desired and checking disabled, the upstream returns the data even if its
+  ///  validation fails, so that the client can validate it
+  pub fn validating() -> Self {
+    QueryFlags{ recursion_desired: true, checking_disabled: true, authentic_data: false }
+  }
+
+  /// No flags, for querying authoritative servers
+  pub fn iterative() -> Self {
+    QueryFlags{ recursion_desired: false, checking_disabled: false, authentic_data: false }
+  }
+
+  /// RD, the server should pursue the query recursively
+  pub fn recursion_desired(&mut self, recursion_desired: bool) -> &mut Self { self.recursion_desired = recursion_desired; self }
+  /// CD, the server should not perform DNSSEC validation
+  pub fn checking_disabled(&mut self, checking_disabled: bool) -> &mut Self { self.checking_disabled = checking_disabled; self }
+  /// AD, requests the server to set the AD bit in the response if the data was validated
+  pub fn authentic_data(&mut self, authentic_data: bool) -> &mut Self { self.authentic_data = authentic_data; self }
+
+  pub fn is_recursion_desired(&self) -> bool { self.recursion_desired }
+  pub fn is_checking_disabled(&self) -> bool { self.checking_disabled }
+  pub fn is_authentic_data(&self) -> bool { self.authentic_data }
+
+  /// sets the flags on the message header
+  pub fn apply(&self, message: &mut Message) {
+    message.recursion_desired(self.recursion_desired)
+           .checking_disabled(self.checking_disabled)
+           .authentic_data(self.authentic_data);
+  }
+}
+
+impl Default for QueryFlags {
+  fn default() -> Self { Self::stub() }
+}
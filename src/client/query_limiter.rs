@@ -0,0 +1,182 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Limits on the number of simultaneous upstream queries
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use ::error::*;
+
+#[derive(Debug, Default)]
+struct LimiterState {
+  in_flight: usize,
+  queued: usize,
+  rejected: u64,
+}
+
+#[derive(Debug)]
+struct Limiter {
+  max_in_flight: usize,
+  queue_timeout: Duration,
+  state: Mutex<LimiterState>,
+  released: Condvar,
+}
+
+/// Bounds the number of queries which are sent upstream at the same time, by all of the clients
+///  which share the limiter, see `Client::set_limiter()`. Queries beyond the limit wait in a queue
+///  for a slot, and fail if none is free before the queue timeout, so a burst of lookups neither
+///  opens thousands of sockets nor overwhelms the name servers.
+///
+/// Clones share the same limit.
+///
+/// ```
+/// use std::time::Duration;
+/// use trust_dns::client::QueryLimiter;
+///
+/// let limiter = QueryLimiter::new(64, Duration::from_secs(2));
+/// let permit = limiter.acquire().unwrap();
+/// assert_eq!(limiter.get_in_flight(), 1);
+/// drop(permit);
+/// assert_eq!(limiter.get_in_flight(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryLimiter {
+  limiter: Arc<Limiter>,
+}
+
+impl QueryLimiter {
+  /// Creates a limiter
+  ///
+  /// # Arguments
+  ///
+  /// * `max_in_flight` - the number of queries which may be outstanding at once, at least 1
+  /// * `queue_timeout` - how long a query waits for a slot before failing
+  pub fn new(max_in_flight: usize, queue_timeout: Duration) -> Self {
+    assert!(max_in_flight > 0, "at least one query must be allowed in flight");
+    QueryLimiter{ limiter: Arc::new(Limiter{ max_in_flight: max_in_flight,
+                                             queue_timeout: queue_timeout,
+                                             state: Mutex::new(LimiterState::default()),
+                                             released: Condvar::new() }) }
+  }
+
+  /// Waits for a slot, the query is in flight until the permit is dropped. An error is returned if
+  ///  no slot is free before the queue timeout.
+  pub fn acquire(&self) -> ClientResult<QueryPermit> {
+    let deadline = Instant::now() + self.limiter.queue_timeout;
+    let mut state = self.lock();
+
+    if state.in_flight >= self.limiter.max_in_flight {
+      state.queued += 1;
+      while state.in_flight >= self.limiter.max_in_flight {
+        let now = Instant::now();
+        if now >= deadline { break }
+
+        state = match self.limiter.released.wait_timeout(state, deadline - now) {
+          Ok((state, _)) => state,
+          Err(poisoned) => poisoned.into_inner().0,
+        };
+      }
+      state.queued -= 1;
+
+      if state.in_flight >= self.limiter.max_in_flight {
+        state.rejected += 1;
+        debug!("no query slot was free within {:?}", self.limiter.queue_timeout);
+        return Err(ClientErrorKind::Message("timed out waiting for a free query slot").into())
+      }
+    }
+
+    state.in_flight += 1;
+    Ok(QueryPermit{ limiter: self.limiter.clone() })
+  }
+
+  pub fn get_max_in_flight(&self) -> usize { self.limiter.max_in_flight }
+  pub fn get_queue_timeout(&self) -> Duration { self.limiter.queue_timeout }
+
+  /// The number of queries currently in flight
+  pub fn get_in_flight(&self) -> usize { self.lock().in_flight }
+
+  /// The number of queries currently waiting for a slot
+  pub fn get_queued(&self) -> usize { self.lock().queued }
+
+  /// The number of queries which failed waiting for a slot
+  pub fn get_rejected(&self) -> u64 { self.lock().rejected }
+
+  /// the counters remain consistent if a holder panicked
+  fn lock(&self) -> MutexGuard<LimiterState> {
+    match self.limiter.state.lock() {
+      Ok(state) => state,
+      Err(poisoned) => poisoned.into_inner(),
+    }
+  }
+}
+
+/// A slot of a `QueryLimiter`, released when dropped
+#[derive(Debug)]
+pub struct QueryPermit {
+  limiter: Arc<Limiter>,
+}
+
+impl Drop for QueryPermit {
+  fn drop(&mut self) {
+    let mut state = match self.limiter.state.lock() {
+      Ok(state) => state,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+
+    state.in_flight -= 1;
+    self.limiter.released.notify_one();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  use super::*;
+
+  #[test]
+  fn test_timeout() {
+    let limiter = QueryLimiter::new(2, Duration::from_millis(50));
+    let first = limiter.acquire().unwrap();
+    let _second = limiter.acquire().unwrap();
+
+    let start = Instant::now();
+    assert!(limiter.acquire().is_err());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(limiter.get_rejected(), 1);
+    assert_eq!(limiter.get_queued(), 0);
+
+    drop(first);
+    assert!(limiter.acquire().is_ok());
+  }
+
+  #[test]
+  fn test_queue() {
+    let limiter = QueryLimiter::new(1, Duration::from_secs(5));
+    let permit = limiter.acquire().unwrap();
+
+    let waiter = limiter.clone();
+    let handle = thread::spawn(move || waiter.acquire().map(|_| ()).is_ok());
+
+    while limiter.get_queued() == 0 { thread::sleep(Duration::from_millis(1)) }
+    drop(permit);
+
+    assert!(handle.join().unwrap());
+    assert_eq!(limiter.get_in_flight(), 0);
+  }
+}
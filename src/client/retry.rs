@@ -0,0 +1,154 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Retry policy for client requests
+
+use std::cmp;
+use std::time::Duration;
+
+use rand::Rng;
+use rand;
+
+use ::error::*;
+use ::client::duration_to_ms;
+use ::op::ResponseCode;
+
+/// Controls if and how a failed request is retried by the `Client`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+  attempts: usize,
+  initial_backoff: Duration,
+  max_backoff: Duration,
+  jitter: bool,
+  switch_upstream: bool,
+}
+
+impl RetryPolicy {
+  /// A policy of the specified total number of attempts, must be at least 1. The backoff starts
+  ///  at 100ms and doubles on each retry up to a maximum of 2 seconds, with jitter.
+  pub fn new(attempts: usize) -> Self {
+    assert!(attempts > 0, "at least one attempt is required");
+    RetryPolicy{ attempts: attempts,
+                 initial_backoff: Duration::from_millis(100),
+                 max_backoff: Duration::from_secs(2),
+                 jitter: true,
+                 switch_upstream: false }
+  }
+
+  /// A policy which never retries, this is the default.
+  pub fn none() -> Self {
+    Self::new(1)
+  }
+
+  pub fn get_attempts(&self) -> usize { self.attempts }
+  pub fn get_initial_backoff(&self) -> Duration { self.initial_backoff }
+  pub fn get_max_backoff(&self) -> Duration { self.max_backoff }
+  pub fn is_jitter(&self) -> bool { self.jitter }
+  pub fn is_switch_upstream(&self) -> bool { self.switch_upstream }
+
+  /// the backoff before the first retry, this doubles on each subsequent retry
+  pub fn initial_backoff(&mut self, backoff: Duration) -> &mut Self { self.initial_backoff = backoff; self }
+  /// the backoff will never exceed this value
+  pub fn max_backoff(&mut self, backoff: Duration) -> &mut Self { self.max_backoff = backoff; self }
+  /// if true, a random jitter of up to half the backoff is applied to each wait
+  pub fn jitter(&mut self, jitter: bool) -> &mut Self { self.jitter = jitter; self }
+  /// if true, the connection is asked to switch to its next upstream before each retry,
+  ///  see `ClientConnection::next_upstream`
+  pub fn switch_upstream(&mut self, switch: bool) -> &mut Self { self.switch_upstream = switch; self }
+
+  /// Returns the time to wait before the retry following the zero based `attempt`.
+  pub fn backoff(&self, attempt: usize) -> Duration {
+    let initial_ms = duration_to_ms(&self.initial_backoff);
+    let max_ms = duration_to_ms(&self.max_backoff);
+
+    // cap the shift, beyond this it will certainly be above max
+    let shift = cmp::min(attempt, 32) as u32;
+    let backoff_ms = cmp::min(initial_ms.saturating_mul(1 << shift), max_ms);
+
+    if self.jitter && backoff_ms > 1 {
+      let half = backoff_ms / 2;
+      Duration::from_millis(half + rand::thread_rng().gen_range(0, half + 1))
+    } else {
+      Duration::from_millis(backoff_ms)
+    }
+  }
+
+  /// Returns true if the error is transient, i.e. the request might succeed if sent again.
+  ///
+  /// Timeouts and io errors are retryable, errors in the encoding of the request or validation of
+  ///  the response are not.
+  pub fn is_retryable_error(error: &ClientError) -> bool {
+    match *error.kind() {
+      ClientErrorKind::Timeout |
+      ClientErrorKind::Io(..) |
+      ClientErrorKind::NotAllBytesSent(..) |
+      ClientErrorKind::NotAllBytesReceived(..) |
+      ClientErrorKind::IncorrectMessageId(..) => true,
+      _ => false,
+    }
+  }
+
+  /// Returns true if the response code signals a transient server side failure, e.g. SERVFAIL.
+  ///  NXDOMAIN and the other codes are authoritative answers, and are terminal.
+  pub fn is_retryable_response(response_code: ResponseCode) -> bool {
+    match response_code {
+      ResponseCode::ServFail | ResponseCode::Refused => true,
+      _ => false,
+    }
+  }
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self::none()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use super::RetryPolicy;
+  use ::error::*;
+  use ::op::ResponseCode;
+
+  #[test]
+  fn test_backoff() {
+    let mut policy = RetryPolicy::new(5);
+    policy.jitter(false).initial_backoff(Duration::from_millis(100)).max_backoff(Duration::from_millis(500));
+
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(1), Duration::from_millis(200));
+    assert_eq!(policy.backoff(2), Duration::from_millis(400));
+    assert_eq!(policy.backoff(3), Duration::from_millis(500));
+    assert_eq!(policy.backoff(100), Duration::from_millis(500));
+
+    policy.jitter(true);
+    for _ in 0..100 {
+      let backoff = policy.backoff(1);
+      assert!(backoff >= Duration::from_millis(100) && backoff <= Duration::from_millis(200));
+    }
+  }
+
+  #[test]
+  fn test_retryable() {
+    assert!(RetryPolicy::is_retryable_error(&ClientErrorKind::Timeout.into()));
+    assert!(!RetryPolicy::is_retryable_error(&ClientErrorKind::NoDNSKEY.into()));
+    assert!(RetryPolicy::is_retryable_response(ResponseCode::ServFail));
+    assert!(!RetryPolicy::is_retryable_response(ResponseCode::NXDomain));
+    assert!(!RetryPolicy::is_retryable_response(ResponseCode::NoError));
+  }
+}
@@ -0,0 +1,165 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Service records returned from `Client::srv_lookup()`
+
+use rand::{self, Rng, ThreadRng};
+
+use ::rr::Name;
+use ::rr::rdata::SRV;
+
+/// The SRV records of a service, `selection()` orders them for connection attempts according to
+///  their priority and weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceLookup {
+  name: Name,
+  records: Vec<SRV>,
+}
+
+impl ServiceLookup {
+  /// Creates the lookup result, a single record with the target `.` signifies that the service is
+  ///  not available at this domain, in which case no records are kept.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - the name which was queried, i.e. `_service._proto.name`
+  /// * `records` - the SRV records of the name
+  pub fn new(name: Name, records: Vec<SRV>) -> Self {
+    let unavailable = records.len() == 1 && records[0].get_target().is_root();
+    ServiceLookup{ name: name, records: if unavailable { vec![] } else { records } }
+  }
+
+  /// The name of the service, `_service._proto.name`
+  pub fn get_name(&self) -> &Name { &self.name }
+
+  /// The SRV records in the order returned from the server
+  pub fn get_records(&self) -> &[SRV] { &self.records }
+
+  /// True if the service is not available, there were no records or it was explicitly marked
+  ///  unavailable with a target of `.`
+  pub fn is_empty(&self) -> bool { self.records.is_empty() }
+
+  /// Returns the records in the order in which they should be attempted, each call produces a new
+  ///  randomized order for records of equal priority.
+  pub fn selection(&self) -> ServiceSelection {
+    let mut remaining = self.records.clone();
+    // zero weight records must be first within their priority for the selection
+    remaining.sort_by_key(|srv| (srv.get_priority(), srv.get_weight()));
+    ServiceSelection{ remaining: remaining, rng: rand::thread_rng() }
+  }
+}
+
+/// Iterator over the SRV records of a service, implements the selection algorithm of
+///  [RFC 2782](https://tools.ietf.org/html/rfc2782)
+///
+/// ```text
+/// To select a target to be contacted next, arrange all SRV RRs
+/// (that have not been ordered yet) in any order, except that all
+/// those with weight 0 are placed at the beginning of the list.
+///
+/// Compute the sum of the weights of those RRs, and with each RR
+/// associate the running sum in the selected order. Then choose a
+/// uniform random number between 0 and the sum computed
+/// (inclusive), and select the RR whose running sum value is the
+/// first in the selected order which is greater than or equal to
+/// the random number selected. The target host specified in the
+/// selected SRV RR is the next one to be contacted by the client.
+/// Remove this SRV RR from the set of the unordered SRV RRs and
+/// apply the described algorithm to the unordered SRV RRs to select
+/// the next target host.  Continue the ordering process until there
+/// are no unordered SRV RRs.  This process is repeated for each
+/// Priority.
+/// ```
+pub struct ServiceSelection {
+  remaining: Vec<SRV>,
+  rng: ThreadRng,
+}
+
+impl ServiceSelection {
+  /// selects the index of the next record, given the random value in 0..=sum of weights
+  fn select(&self, random: u32) -> usize {
+    let priority = self.remaining[0].get_priority();
+    let mut running_sum: u32 = 0;
+    let mut last = 0;
+
+    for (index, srv) in self.remaining.iter().enumerate().take_while(|&(_, srv)| srv.get_priority() == priority) {
+      running_sum += srv.get_weight() as u32;
+      if running_sum >= random { return index }
+      last = index;
+    }
+
+    last
+  }
+}
+
+impl Iterator for ServiceSelection {
+  type Item = SRV;
+
+  fn next(&mut self) -> Option<SRV> {
+    if self.remaining.is_empty() { return None }
+
+    let priority = self.remaining[0].get_priority();
+    let total: u32 = self.remaining.iter()
+                                   .take_while(|srv| srv.get_priority() == priority)
+                                   .fold(0, |sum, srv| sum + srv.get_weight() as u32);
+
+    let random: u32 = self.rng.gen_range(0, total + 1);
+    let index = self.select(random);
+    Some(self.remaining.remove(index))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use rand;
+
+  use ::rr::Name;
+  use ::rr::rdata::SRV;
+  use super::*;
+
+  fn srv(priority: u16, weight: u16, target: &str) -> SRV {
+    SRV::new(priority, weight, 389, Name::with_labels(vec![target.to_string(), "example".to_string(), "com".to_string()]))
+  }
+
+  #[test]
+  fn test_unavailable() {
+    let name = Name::with_labels(vec!["_ldap".to_string(), "_tcp".to_string(), "example".to_string(), "com".to_string()]);
+    let lookup = ServiceLookup::new(name, vec![SRV::new(0, 0, 0, Name::root())]);
+    assert!(lookup.is_empty());
+    assert_eq!(lookup.selection().count(), 0);
+  }
+
+  #[test]
+  fn test_select() {
+    let selection = ServiceSelection{ remaining: vec![srv(1, 0, "a"), srv(1, 10, "b"), srv(1, 30, "c"), srv(2, 50, "d")],
+                                      rng: rand::thread_rng() };
+
+    assert_eq!(selection.select(0), 0);
+    assert_eq!(selection.select(1), 1);
+    assert_eq!(selection.select(10), 1);
+    assert_eq!(selection.select(11), 2);
+    assert_eq!(selection.select(40), 2);
+  }
+
+  #[test]
+  fn test_priority_order() {
+    let name = Name::with_labels(vec!["_ldap".to_string(), "_tcp".to_string(), "example".to_string(), "com".to_string()]);
+    let lookup = ServiceLookup::new(name, vec![srv(20, 0, "c"), srv(10, 60, "a"), srv(10, 40, "b")]);
+
+    let selected: Vec<u16> = lookup.selection().map(|srv| srv.get_priority()).collect();
+    assert_eq!(selected, vec![10, 10, 20]);
+  }
+}
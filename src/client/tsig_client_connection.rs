@@ -0,0 +1,281 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A ClientConnection which signs all requests and verifies all responses with TSIG
+
+use std::fmt;
+use std::mem;
+
+use chrono::UTC;
+
+use ::error::*;
+use ::client::{ClientConnection, Transport};
+use ::rr::RData;
+use ::rr::dnssec::{TsigKey, split_tsig};
+
+/// RFC 2845, a TSIG MUST be placed on at least every 100'th envelope of a multiple message response
+const MAX_UNSIGNED_MESSAGES: usize = 99;
+
+/// Wraps a connection, every request sent over it is signed with the TSIG key, and every response
+///  must carry a valid TSIG from the server.
+///
+/// Multiple message responses, i.e. AXFR and IXFR over TCP, are verified with the MAC chaining of
+///  [RFC 2845](https://tools.ietf.org/html/rfc2845#section-4.4), where intermediary messages may be
+///  unsigned.
+pub struct TsigClientConnection<C: ClientConnection> {
+  connection: C,
+  key: TsigKey,
+  prior_mac: Option<Vec<u8>>,
+  unsigned_messages: Vec<u8>,
+  unsigned_count: usize,
+}
+
+impl<C: ClientConnection> TsigClientConnection<C> {
+  /// Creates a new signing connection
+  ///
+  /// # Arguments
+  ///
+  /// * `connection` - the connection over which to send the signed messages
+  /// * `key` - the key shared with the server
+  pub fn new(connection: C, key: TsigKey) -> Self {
+    TsigClientConnection{ connection: connection, key: key, prior_mac: None, unsigned_messages: vec![], unsigned_count: 0 }
+  }
+
+  pub fn get_key(&self) -> &TsigKey { &self.key }
+
+  /// verifies the TSIG of the response, subsequent is true for all but the first message of a
+  ///  multiple message response
+  fn verify(&mut self, response: &[u8], subsequent: bool) -> ClientResult<()> {
+    let (unsigned, record) = match try!(split_tsig(response)) {
+      Some(split) => split,
+      None => {
+        if !subsequent { return Err(ClientErrorKind::Tsig("response is not signed".to_string()).into()) }
+
+        self.unsigned_count += 1;
+        if self.unsigned_count > MAX_UNSIGNED_MESSAGES {
+          return Err(ClientErrorKind::Tsig(format!("more than {} consecutive unsigned messages", MAX_UNSIGNED_MESSAGES)).into())
+        }
+
+        self.unsigned_messages.extend_from_slice(response);
+        return Ok(())
+      },
+    };
+
+    let tsig = match *record.get_rdata() {
+      RData::TSIG(ref tsig) => tsig.clone(),
+      _ => return Err(ClientErrorKind::Tsig("record is not a tsig".to_string()).into()),
+    };
+
+    if record.get_name() != self.key.get_name() {
+      return Err(ClientErrorKind::Tsig(format!("response signed with unknown key: {}", record.get_name())).into())
+    }
+
+    // BADSIG, BADKEY and BADTIME responses are not signed with a MAC
+    if tsig.get_error() != 0 {
+      return Err(ClientErrorKind::Tsig(format!("server rejected the signature, error: {}", tsig.get_error())).into())
+    }
+
+    let mut messages = mem::replace(&mut self.unsigned_messages, vec![]);
+    messages.extend_from_slice(&unsigned);
+
+    let valid = try!(self.key.verify(self.prior_mac.as_ref().map(|mac| &mac[..]), &messages, &tsig, subsequent));
+    if !valid { return Err(ClientErrorKind::Tsig("bad signature".to_string()).into()) }
+
+    if !TsigKey::is_time_valid(&tsig, UTC::now().timestamp() as u64) {
+      return Err(ClientErrorKind::Tsig(format!("time signed is outside of the fudge: {}", tsig.get_time_signed())).into())
+    }
+
+    self.prior_mac = Some(tsig.get_mac().to_vec());
+    self.unsigned_count = 0;
+    Ok(())
+  }
+}
+
+impl<C: ClientConnection> ClientConnection for TsigClientConnection<C> {
+  fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+    let (signed, mac) = try!(self.key.sign(&bytes, None, UTC::now().timestamp() as u64));
+
+    self.prior_mac = Some(mac);
+    self.unsigned_messages.clear();
+    self.unsigned_count = 0;
+
+    let response = try!(self.connection.send(signed));
+    try!(self.verify(&response, false));
+    Ok(response)
+  }
+
+  fn receive(&mut self) -> ClientResult<Vec<u8>> {
+    let response = try!(self.connection.receive());
+    try!(self.verify(&response, true));
+    Ok(response)
+  }
+
+  fn next_upstream(&mut self) {
+    self.connection.next_upstream()
+  }
+
+  fn transport(&self) -> Transport {
+    self.connection.transport()
+  }
+}
+
+impl<C: ClientConnection> fmt::Debug for TsigClientConnection<C> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "TsigClientConnection({}, {:?})", self.key.get_name(), self.connection)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::VecDeque;
+  use std::fmt;
+
+  use chrono::UTC;
+
+  use ::client::{Client, ClientConnection};
+  use ::error::*;
+  use ::op::{Message, MessageType};
+  use ::rr::{DNSClass, Name, RData, RecordType};
+  use ::rr::dnssec::{TsigAlgorithm, TsigKey, append_tsig, split_tsig};
+  use ::rr::rdata::TSIG;
+  use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+  use super::TsigClientConnection;
+
+  /// verifies requests and responds with `messages` responses, only the first and last are signed
+  struct TsigServer {
+    key: TsigKey,
+    request_key: TsigKey,
+    messages: usize,
+    queued: VecDeque<Vec<u8>>,
+  }
+
+  impl TsigServer {
+    fn response(request: &Message) -> Vec<u8> {
+      let mut response = Message::new();
+      response.id(request.get_id()).message_type(MessageType::Response).add_all_queries(request.get_queries());
+
+      let mut bytes = Vec::new();
+      {
+        let mut encoder = BinEncoder::new(&mut bytes);
+        response.emit(&mut encoder).unwrap();
+      }
+      bytes
+    }
+  }
+
+  impl ClientConnection for TsigServer {
+    fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+      let now = UTC::now().timestamp() as u64;
+      let (unsigned, record) = split_tsig(&bytes).unwrap().expect("request not signed");
+      let tsig = if let RData::TSIG(ref tsig) = *record.get_rdata() { tsig.clone() } else { panic!("not a tsig") };
+      assert!(self.request_key.verify(None, &unsigned, &tsig, false).unwrap(), "request signature invalid");
+
+      let request = try!(Message::read(&mut BinDecoder::new(&bytes)));
+      assert!(request.get_tsig().is_some());
+      let response = Self::response(&request);
+
+      let (first, prior_mac) = try!(self.key.sign(&response, Some(tsig.get_mac()), now));
+
+      let mut unsigned_messages: Vec<u8> = vec![];
+      for i in 1..self.messages {
+        if i + 1 < self.messages {
+          unsigned_messages.extend_from_slice(&response);
+          self.queued.push_back(response.clone());
+        } else {
+          unsigned_messages.extend_from_slice(&response);
+          let timers = TSIG::new(TsigAlgorithm::HmacSha256.to_name(), now, 300, vec![], request.get_id(), 0, vec![]);
+          let mac = try!(self.key.mac(Some(&prior_mac), &unsigned_messages, &timers, true));
+          let tsig = TSIG::new(TsigAlgorithm::HmacSha256.to_name(), now, 300, mac, request.get_id(), 0, vec![]);
+          self.queued.push_back(try!(append_tsig(&response, &self.key.to_record(tsig))));
+        }
+      }
+
+      Ok(first)
+    }
+
+    fn receive(&mut self) -> ClientResult<Vec<u8>> {
+      Ok(self.queued.pop_front().expect("no more messages"))
+    }
+  }
+
+  impl fmt::Debug for TsigServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "TsigServer")
+    }
+  }
+
+  fn key(secret: &[u8]) -> TsigKey {
+    TsigKey::new(Name::new().label("tsig-key"), TsigAlgorithm::HmacSha256, secret.to_vec())
+  }
+
+  #[test]
+  fn test_tsig_query() {
+    let server = TsigServer{ key: key(b"secret"), request_key: key(b"secret"), messages: 1, queued: VecDeque::new() };
+    let client = Client::new(TsigClientConnection::new(server, key(b"secret")));
+
+    let name = Name::new().label("www").label("example").label("com");
+    let response = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+    assert!(response.get_tsig().is_some());
+  }
+
+  #[test]
+  fn test_tsig_bad_key() {
+    // the server signs its responses with a different secret
+    let server = TsigServer{ key: key(b"other"), request_key: key(b"secret"), messages: 1, queued: VecDeque::new() };
+    let client = Client::new(TsigClientConnection::new(server, key(b"secret")));
+
+    let name = Name::new().label("www").label("example").label("com");
+    let error = client.query(&name, DNSClass::IN, RecordType::A).unwrap_err();
+    match *error.kind() {
+      ClientErrorKind::Tsig(..) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_tsig_multiple_messages() {
+    let server = TsigServer{ key: key(b"secret"), request_key: key(b"secret"), messages: 4, queued: VecDeque::new() };
+    let mut connection = TsigClientConnection::new(server, key(b"secret"));
+
+    let mut request = Message::new();
+    request.id(1).message_type(MessageType::Query);
+    let mut bytes = Vec::new();
+    {
+      let mut encoder = BinEncoder::new(&mut bytes);
+      request.emit(&mut encoder).unwrap();
+    }
+
+    assert!(connection.send(bytes).is_ok());
+    // two unsigned messages, then the signed final message which covers them
+    assert!(connection.receive().is_ok());
+    assert!(connection.receive().is_ok());
+    assert!(connection.receive().is_ok());
+    assert_eq!(connection.unsigned_count, 0);
+
+    // a tampered final message fails
+    let mut bytes = Vec::new();
+    {
+      let mut encoder = BinEncoder::new(&mut bytes);
+      request.emit(&mut encoder).unwrap();
+    }
+    assert!(connection.send(bytes).is_ok());
+    connection.receive().unwrap();
+    connection.unsigned_messages.push(0);
+    connection.receive().unwrap();
+    let result = connection.receive();
+    assert!(result.is_err());
+  }
+}
@@ -15,25 +15,51 @@
  */
 
 //! Configuration module for the server binary, `named`.
+//!
+//! The listeners, zones, forwarders, logging and limits of the server are all configured in a
+//!  TOML file, see `src/config/test/example.toml`. Keys which are not known, and values which
+//!  are not valid, are rejected with an error naming the key, e.g. `zones[1].primaries[0]`.
 
+use std::fmt;
 use std::io::Read;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use log::LogLevel;
 use rustc_serialize::Decodable;
 
-use toml::{Decoder, Value};
+use data_encoding::{base64, hex};
+use toml::{Decoder, Table, Value};
 
 use ::error::{ConfigErrorKind, ConfigResult, ConfigError, ParseResult};
 use ::rr::Name;
-use ::authority::ZoneType;
+use ::rr::dnssec::{Algorithm, Nsec3HashAlgorithm, TsigAlgorithm, TsigKey};
+use ::rr::rdata::NSEC3PARAM;
+use ::authority::{Network, QueryPolicy, SerialPolicy, ZoneType};
 
 static DEFAULT_PORT: u16 = 53;
 static DEFAULT_PATH: &'static str = "/var/named"; // TODO what about windows (do I care? ;)
 
+static LOG_LEVELS: &'static [&'static str] = &["Trace", "Debug", "Info", "Warn", "Error"];
+
+// the keys of each of the tables, any other key is most likely misspelled
+static CONFIG_KEYS: &'static [&'static str] = &["listen_addrs_ipv4", "listen_addrs_ipv6", "listen_port", "log_level", "directory",
+                                                 "threads", "recursion", "allow_query", "deny_query", "allow_recursion", "listeners",
+                                                 "forwarders", "logging", "limits", "tsig_keys", "zones"];
+static LISTENER_KEYS: &'static [&'static str] = &["addr", "port", "protocol", "allow_query", "deny_query"];
+static FORWARDER_KEYS: &'static [&'static str] = &["domain", "name_servers"];
+static TSIG_KEY_KEYS: &'static [&'static str] = &["name", "algorithm", "secret"];
+static LOGGING_KEYS: &'static [&'static str] = &["query_log", "dnstap_file", "dnstap_socket"];
+static LIMITS_KEYS: &'static [&'static str] = &["max_tcp_connections", "tcp_idle_timeout_ms", "max_tcp_requests", "responses_per_second",
+                                                 "rate_limit_burst", "rate_limit_slip"];
+static ZONE_KEYS: &'static [&'static str] = &["zone", "zone_type", "file", "allow_update", "allow_update_from", "enable_dnssec", "key_file",
+                                               "algorithm", "signature_validity_days", "nsec3", "nsec3_iterations", "nsec3_salt",
+                                               "primaries", "also_notify", "allow_transfer", "transfer_keys", "update_keys", "serial_policy"];
+
+static ALGORITHMS: &'static [&'static str] = &["RSASHA1", "RSASHA256", "RSASHA1NSEC3SHA1", "RSASHA512"];
+
 #[derive(RustcDecodable, Debug)]
 pub struct Config {
   listen_addrs_ipv4: Vec<String>,
@@ -41,6 +67,16 @@ pub struct Config {
   listen_port: Option<u16>,
   log_level: Option<String>,
   directory: Option<String>,
+  threads: Option<usize>,
+  recursion: Option<bool>,
+  allow_query: Option<Vec<String>>,
+  deny_query: Option<Vec<String>>,
+  allow_recursion: Option<Vec<String>>,
+  listeners: Vec<ListenerConfig>,
+  forwarders: Vec<ForwarderConfig>,
+  logging: Option<LoggingConfig>,
+  limits: Option<LimitsConfig>,
+  tsig_keys: Vec<TsigKeyConfig>,
   zones: Vec<ZoneConfig>,
 }
 
@@ -71,7 +107,83 @@ impl Config {
     }
   }
   pub fn get_directory(&self) -> &Path { self.directory.as_ref().map_or(Path::new(DEFAULT_PATH), |s|Path::new(s)) }
+  /// the number of threads answering requests, default 1
+  pub fn get_threads(&self) -> usize { self.threads.unwrap_or(1) }
+  /// if true, names which are not in any zone, or forwarded, are resolved recursively
+  pub fn is_recursion_enabled(&self) -> bool { self.recursion.unwrap_or(false) }
+  /// the policy of all queries, from `allow_query`, `deny_query` and `allow_recursion`
+  pub fn get_query_policy(&self) -> QueryPolicy {
+    let mut policy = QueryPolicy::new();
+    for network in networks(&self.allow_query) { policy.allow_network(network); }
+    for network in networks(&self.deny_query) { policy.deny_network(network); }
+    for network in networks(&self.allow_recursion) { policy.allow_recursion_network(network); }
+    policy
+  }
+  /// the addresses to listen on, if empty the `listen_addrs_ipv4` and `listen_addrs_ipv6` are
+  ///  listened on for both UDP and TCP
+  pub fn get_listeners(&self) -> &[ListenerConfig] { &self.listeners }
+  pub fn get_forwarders(&self) -> &[ForwarderConfig] { &self.forwarders }
+  pub fn get_logging(&self) -> Option<&LoggingConfig> { self.logging.as_ref() }
+  pub fn get_limits(&self) -> Option<&LimitsConfig> { self.limits.as_ref() }
+  pub fn get_tsig_keys(&self) -> &[TsigKeyConfig] { &self.tsig_keys }
+  /// the TSIG key with the name, e.g. one of the `transfer_keys` of a zone
+  pub fn get_tsig_key(&self, name: &Name) -> Option<TsigKey> {
+    self.tsig_keys.iter().find(|key| &key.get_name() == name).map(|key| key.to_tsig_key())
+  }
   pub fn get_zones(&self) -> &[ZoneConfig] { &self.zones }
+
+  /// Checks the values which decoding does not, i.e. the addresses, networks and names, and the
+  ///  combinations of keys
+  fn validate(&self) -> ConfigResult<()> {
+    for (i, addr) in self.listen_addrs_ipv4.iter().enumerate() {
+      try!(check(format!("listen_addrs_ipv4[{}]", i), addr.parse::<Ipv4Addr>()));
+    }
+    for (i, addr) in self.listen_addrs_ipv6.iter().enumerate() {
+      try!(check(format!("listen_addrs_ipv6[{}]", i), addr.parse::<Ipv6Addr>()));
+    }
+
+    if let Some(ref level) = self.log_level {
+      if !LOG_LEVELS.contains(&(level as &str)) {
+        return Err(invalid("log_level".to_string(), format!("expected one of {}", LOG_LEVELS.join(", "))))
+      }
+    }
+
+    if self.threads == Some(0) {
+      return Err(invalid("threads".to_string(), "at least one thread is required"))
+    }
+
+    try!(check_networks("allow_query", &self.allow_query));
+    try!(check_networks("deny_query", &self.deny_query));
+    try!(check_networks("allow_recursion", &self.allow_recursion));
+
+    for (i, listener) in self.listeners.iter().enumerate() {
+      try!(listener.validate(&format!("listeners[{}]", i), self.get_threads()));
+    }
+
+    for (i, forwarder) in self.forwarders.iter().enumerate() {
+      try!(forwarder.validate(&format!("forwarders[{}]", i)));
+    }
+
+    if let Some(ref logging) = self.logging { try!(logging.validate("logging")); }
+    if let Some(ref limits) = self.limits { try!(limits.validate("limits")); }
+
+    let mut tsig_key_names: Vec<Name> = Vec::with_capacity(self.tsig_keys.len());
+    for (i, tsig_key) in self.tsig_keys.iter().enumerate() {
+      let key = format!("tsig_keys[{}]", i);
+      try!(tsig_key.validate(&key));
+
+      if tsig_key_names.contains(&tsig_key.get_name()) {
+        return Err(invalid(format!("{}.name", key), "there is already a key with this name"))
+      }
+      tsig_key_names.push(tsig_key.get_name());
+    }
+
+    for (i, zone) in self.zones.iter().enumerate() {
+      try!(zone.validate(&format!("zones[{}]", i), &tsig_key_names));
+    }
+
+    Ok(())
+  }
 }
 
 impl FromStr for Config {
@@ -79,8 +191,172 @@ impl FromStr for Config {
 
   fn from_str(toml: &str) -> ConfigResult<Config> {
     let value: Value = try!(toml.parse().map_err(|vec| ConfigErrorKind::VecParserError(vec)));
+    try!(check_keys(&value));
+
     let mut decoder: Decoder = Decoder::new(value);
-    Ok(try!(Self::decode(&mut decoder)))
+    let config = try!(Self::decode(&mut decoder));
+    try!(config.validate());
+    Ok(config)
+  }
+}
+
+/// The protocols of a listener
+#[derive(RustcDecodable, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Protocol { Udp, Tcp, Both }
+
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct ListenerConfig {
+  addr: String,
+  port: Option<u16>,
+  protocol: Option<Protocol>,
+  allow_query: Option<Vec<String>>,
+  deny_query: Option<Vec<String>>,
+}
+
+impl ListenerConfig {
+  pub fn get_addr(&self) -> IpAddr { self.addr.parse().unwrap() }
+  /// the port of the listener, None for the `listen_port`
+  pub fn get_port(&self) -> Option<u16> { self.port }
+  /// UDP, TCP or both, default both
+  pub fn get_protocol(&self) -> Protocol { self.protocol.unwrap_or(Protocol::Both) }
+  /// the policy of the queries received by this listener, in place of that of the `Catalog`,
+  ///  None if neither `allow_query` nor `deny_query` are set
+  pub fn get_query_policy(&self) -> Option<QueryPolicy> {
+    if self.allow_query.is_none() && self.deny_query.is_none() { return None }
+
+    let mut policy = QueryPolicy::new();
+    for network in networks(&self.allow_query) { policy.allow_network(network); }
+    for network in networks(&self.deny_query) { policy.deny_network(network); }
+    Some(policy)
+  }
+
+  fn validate(&self, key: &str, threads: usize) -> ConfigResult<()> {
+    try!(check(format!("{}.addr", key), self.addr.parse::<IpAddr>()));
+    try!(check_networks(&format!("{}.allow_query", key), &self.allow_query));
+    try!(check_networks(&format!("{}.deny_query", key), &self.deny_query));
+
+    // each thread registers clones of the sockets, without their policies
+    if threads > 1 && (self.allow_query.is_some() || self.deny_query.is_some()) {
+      return Err(invalid(format!("{}.allow_query", key), "listener policies require a single thread"))
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct ForwarderConfig {
+  domain: String,
+  name_servers: Vec<String>,
+}
+
+impl ForwarderConfig {
+  /// the names at and below the domain are forwarded, `.` forwards all names not in a zone
+  pub fn get_domain(&self) -> Name { Name::parse(&self.domain, Some(&Name::new())).unwrap() }
+  /// the resolvers the queries are forwarded to, in order, the port defaults to 53
+  pub fn get_name_servers(&self) -> Vec<SocketAddr> { self.name_servers.iter().map(|s| parse_socket_addr(s).unwrap()).collect() }
+
+  fn validate(&self, key: &str) -> ConfigResult<()> {
+    try!(check(format!("{}.domain", key), Name::parse(&self.domain, Some(&Name::new()))));
+    if self.name_servers.is_empty() {
+      return Err(invalid(format!("{}.name_servers", key), "at least one name server is required"))
+    }
+    check_socket_addrs(&format!("{}.name_servers", key), &self.name_servers)
+  }
+}
+
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct LoggingConfig {
+  query_log: Option<String>,
+  dnstap_file: Option<String>,
+  dnstap_socket: Option<String>,
+}
+
+impl LoggingConfig {
+  /// `stderr`, or the path of the file the query log is appended to
+  pub fn get_query_log(&self) -> Option<&str> { self.query_log.as_ref().map(|s| s as &str) }
+  /// the file dnstap frames are written to
+  pub fn get_dnstap_file(&self) -> Option<&Path> { self.dnstap_file.as_ref().map(|s| Path::new(s)) }
+  /// the unix socket of a dnstap collector, e.g. fstrm_capture
+  pub fn get_dnstap_socket(&self) -> Option<&Path> { self.dnstap_socket.as_ref().map(|s| Path::new(s)) }
+
+  fn validate(&self, key: &str) -> ConfigResult<()> {
+    if self.dnstap_file.is_some() && self.dnstap_socket.is_some() {
+      return Err(invalid(format!("{}.dnstap_socket", key), "only one of dnstap_file and dnstap_socket may be set"))
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct LimitsConfig {
+  max_tcp_connections: Option<usize>,
+  tcp_idle_timeout_ms: Option<u64>,
+  max_tcp_requests: Option<usize>,
+  responses_per_second: Option<u32>,
+  rate_limit_burst: Option<u32>,
+  rate_limit_slip: Option<u32>,
+}
+
+impl LimitsConfig {
+  pub fn get_max_tcp_connections(&self) -> Option<usize> { self.max_tcp_connections }
+  pub fn get_tcp_idle_timeout_ms(&self) -> Option<u64> { self.tcp_idle_timeout_ms }
+  pub fn get_max_tcp_requests(&self) -> Option<usize> { self.max_tcp_requests }
+  /// the UDP responses per second to each client network, None if responses are not limited
+  pub fn get_responses_per_second(&self) -> Option<u32> { self.responses_per_second }
+  pub fn get_rate_limit_burst(&self) -> Option<u32> { self.rate_limit_burst }
+  pub fn get_rate_limit_slip(&self) -> Option<u32> { self.rate_limit_slip }
+
+  fn validate(&self, key: &str) -> ConfigResult<()> {
+    if self.responses_per_second == Some(0) {
+      return Err(invalid(format!("{}.responses_per_second", key), "must be greater than 0"))
+    }
+
+    if self.responses_per_second.is_none() {
+      if self.rate_limit_burst.is_some() {
+        return Err(invalid(format!("{}.rate_limit_burst", key), "requires responses_per_second"))
+      }
+      if self.rate_limit_slip.is_some() {
+        return Err(invalid(format!("{}.rate_limit_slip", key), "requires responses_per_second"))
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct TsigKeyConfig {
+  name: String,
+  algorithm: Option<String>,
+  secret: String,
+}
+
+impl TsigKeyConfig {
+  /// the name of the key, by which the zones reference it, and which the TSIG records have
+  pub fn get_name(&self) -> Name { Name::parse(&self.name, Some(&Name::new())).unwrap() }
+  /// `hmac-md5.sig-alg.reg.int`, `hmac-sha1`, `hmac-sha256` or `hmac-sha512`, default hmac-sha256
+  pub fn get_algorithm(&self) -> TsigAlgorithm {
+    self.algorithm.as_ref().map_or(TsigAlgorithm::HmacSha256, |algorithm| parse_tsig_algorithm(algorithm).unwrap())
+  }
+  pub fn to_tsig_key(&self) -> TsigKey {
+    TsigKey::new(self.get_name(), self.get_algorithm(), base64::decode(self.secret.as_bytes()).unwrap())
+  }
+
+  fn validate(&self, key: &str) -> ConfigResult<()> {
+    try!(check(format!("{}.name", key), Name::parse(&self.name, Some(&Name::new()))));
+    if let Some(ref algorithm) = self.algorithm {
+      try!(check(format!("{}.algorithm", key), parse_tsig_algorithm(algorithm)));
+    }
+
+    // the secret is base64, as in the key files of dnssec-keygen and tsig-keygen
+    let secret: Vec<u8> = try!(check(format!("{}.secret", key), base64::decode(self.secret.as_bytes()).map_err(|_| "not base64")));
+    if secret.is_empty() {
+      return Err(invalid(format!("{}.secret", key), "the secret is empty"))
+    }
+
+    Ok(())
   }
 }
 
@@ -90,7 +366,20 @@ pub struct ZoneConfig {
   zone_type: ZoneType,
   file: String,
   allow_update: Option<bool>,
+  allow_update_from: Option<Vec<String>>,
   enable_dnssec: Option<bool>,
+  key_file: Option<String>,
+  algorithm: Option<String>,
+  signature_validity_days: Option<u32>,
+  nsec3: Option<bool>,
+  nsec3_iterations: Option<u16>,
+  nsec3_salt: Option<String>,
+  primaries: Option<Vec<String>>,
+  also_notify: Option<Vec<String>>,
+  allow_transfer: Option<Vec<String>>,
+  transfer_keys: Option<Vec<String>>,
+  update_keys: Option<Vec<String>>,
+  serial_policy: Option<SerialPolicy>,
 }
 
 impl ZoneConfig {
@@ -99,7 +388,192 @@ impl ZoneConfig {
   pub fn get_zone_type(&self) -> ZoneType { self.zone_type }
   pub fn get_file(&self) -> PathBuf { PathBuf::from(&self.file) }
   pub fn is_update_allowed(&self) -> bool { self.allow_update.unwrap_or(false) }
+  /// the networks which may update the zone, any client if empty
+  pub fn get_allow_update_from(&self) -> Vec<Network> { networks(&self.allow_update_from) }
   pub fn is_dnssec_enabled(&self) -> bool { self.enable_dnssec.unwrap_or(false) }
+  /// the private key of the zone, relative to the directory, by default `$file.key`
+  pub fn get_key_file(&self) -> PathBuf { self.key_file.as_ref().map_or(self.get_file().with_extension(".key"), |s| PathBuf::from(s)) }
+  /// the algorithm the zone is signed with, default RSASHA256
+  pub fn get_algorithm(&self) -> Algorithm {
+    match self.algorithm.as_ref().map_or("RSASHA256", |s| s as &str) {
+      "RSASHA1" => Algorithm::RSASHA1,
+      "RSASHA1NSEC3SHA1" => Algorithm::RSASHA1NSEC3SHA1,
+      "RSASHA512" => Algorithm::RSASHA512,
+      _ => Algorithm::RSASHA256,
+    }
+  }
+  /// the days from signing until the signatures expire, default 364
+  pub fn get_signature_validity_days(&self) -> u32 { self.signature_validity_days.unwrap_or(364) }
+  /// the parameters of the NSEC3 records of the zone, None if the zone is signed with NSEC
+  pub fn get_nsec3param(&self) -> Option<NSEC3PARAM> {
+    if !self.nsec3.unwrap_or(false) { return None }
+
+    let salt: Vec<u8> = self.nsec3_salt.as_ref().map_or(vec![], |salt| decode_salt(salt).unwrap());
+    Some(NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, false, self.nsec3_iterations.unwrap_or(0), salt))
+  }
+  /// the primaries of a Slave zone, the port defaults to 53
+  pub fn get_primaries(&self) -> Vec<SocketAddr> { socket_addrs(&self.primaries) }
+  /// secondaries to NOTIFY of changes, in addition to the name servers of the zone, the port
+  ///  defaults to 53
+  pub fn get_also_notify(&self) -> Vec<SocketAddr> { socket_addrs(&self.also_notify) }
+  /// the networks which may transfer the zone, any client if empty
+  pub fn get_allow_transfer(&self) -> ParseResult<Vec<Network>> {
+    self.allow_transfer.as_ref().map_or(Ok(vec![]), |networks| networks.iter().map(|s| s.parse()).collect())
+  }
+  /// the names of the `tsig_keys` which requests to transfer the zone may be signed with
+  pub fn get_transfer_keys(&self) -> Vec<Name> { key_names(&self.transfer_keys) }
+  /// the names of the `tsig_keys` which updates of the zone may be signed with
+  pub fn get_update_keys(&self) -> Vec<Name> { key_names(&self.update_keys) }
+  /// how the serial is incremented when the zone is updated or re-signed, default Increment
+  pub fn get_serial_policy(&self) -> SerialPolicy { self.serial_policy.unwrap_or_default() }
+
+  fn validate(&self, key: &str, tsig_key_names: &[Name]) -> ConfigResult<()> {
+    try!(check(format!("{}.zone", key), self.get_zone()));
+
+    if self.zone_type == ZoneType::Slave && self.primaries.as_ref().map_or(true, |primaries| primaries.is_empty()) {
+      return Err(invalid(format!("{}.primaries", key), "Slave zones require at least one primary"))
+    }
+    if let Some(ref primaries) = self.primaries { try!(check_socket_addrs(&format!("{}.primaries", key), primaries)); }
+    if let Some(ref also_notify) = self.also_notify { try!(check_socket_addrs(&format!("{}.also_notify", key), also_notify)); }
+
+    try!(check_networks(&format!("{}.allow_transfer", key), &self.allow_transfer));
+    try!(check_networks(&format!("{}.allow_update_from", key), &self.allow_update_from));
+    if self.allow_update_from.is_some() && !self.is_update_allowed() {
+      return Err(invalid(format!("{}.allow_update_from", key), "requires allow_update = true"))
+    }
+    if self.update_keys.is_some() && !self.is_update_allowed() {
+      return Err(invalid(format!("{}.update_keys", key), "requires allow_update = true"))
+    }
+
+    for &(name, key_names) in &[("transfer_keys", &self.transfer_keys), ("update_keys", &self.update_keys)] {
+      for (i, key_name) in key_names.iter().flat_map(|key_names| key_names.iter()).enumerate() {
+        let key_name: Name = try!(check(format!("{}.{}[{}]", key, name, i), Name::parse(key_name, Some(&Name::new()))));
+        if !tsig_key_names.contains(&key_name) {
+          return Err(invalid(format!("{}.{}[{}]", key, name, i), "there are no tsig_keys with this name"))
+        }
+      }
+    }
+
+    for &(name, is_set) in &[("key_file", self.key_file.is_some()), ("algorithm", self.algorithm.is_some()),
+                             ("signature_validity_days", self.signature_validity_days.is_some()), ("nsec3", self.nsec3.is_some())] {
+      if is_set && !self.is_dnssec_enabled() {
+        return Err(invalid(format!("{}.{}", key, name), "requires enable_dnssec = true"))
+      }
+    }
+
+    if let Some(ref algorithm) = self.algorithm {
+      if !ALGORITHMS.contains(&(algorithm as &str)) {
+        return Err(invalid(format!("{}.algorithm", key), format!("expected one of {}", ALGORITHMS.join(", "))))
+      }
+    }
+    if self.signature_validity_days == Some(0) {
+      return Err(invalid(format!("{}.signature_validity_days", key), "must be greater than 0"))
+    }
+
+    for &(name, is_set) in &[("nsec3_iterations", self.nsec3_iterations.is_some()), ("nsec3_salt", self.nsec3_salt.is_some())] {
+      if is_set && !self.nsec3.unwrap_or(false) {
+        return Err(invalid(format!("{}.{}", key, name), "requires nsec3 = true"))
+      }
+    }
+    if let Some(ref salt) = self.nsec3_salt {
+      try!(check(format!("{}.nsec3_salt", key), decode_salt(salt)));
+    }
+    // the NSEC3 alias of RSASHA1 tells resolvers which do not support NSEC3 that they can not validate the zone
+    if self.nsec3.unwrap_or(false) && self.get_algorithm() == Algorithm::RSASHA1 {
+      return Err(invalid(format!("{}.algorithm", key), "NSEC3 zones are signed with RSASHA1NSEC3SHA1 rather than RSASHA1"))
+    }
+
+    Ok(())
+  }
+}
+
+/// the address, or an address without a port for the default port
+fn parse_socket_addr(s: &str) -> Result<SocketAddr, AddrParseError> {
+  s.parse::<SocketAddr>().or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, DEFAULT_PORT)))
+}
+
+/// the hex salt of the NSEC3 hashes, `-` is no salt, as in zone files
+fn decode_salt(salt: &str) -> Result<Vec<u8>, String> {
+  if salt == "-" { return Ok(vec![]) }
+
+  let salt: Vec<u8> = try!(hex::decode(salt.to_uppercase().as_bytes()).map_err(|_| format!("not hex: {}", salt)));
+  if salt.len() > 255 { return Err(format!("the salt is {} bytes, at most 255 are allowed", salt.len())) }
+  Ok(salt)
+}
+
+fn parse_tsig_algorithm(algorithm: &str) -> Result<TsigAlgorithm, String> {
+  Name::parse(algorithm, Some(&Name::new())).map_err(|e| e.to_string())
+                                            .and_then(|name| TsigAlgorithm::from_name(&name).map_err(|e| e.to_string()))
+}
+
+fn key_names(key_names: &Option<Vec<String>>) -> Vec<Name> {
+  key_names.as_ref().map_or(vec![], |key_names| key_names.iter().map(|s| Name::parse(s, Some(&Name::new())).unwrap()).collect())
+}
+
+fn socket_addrs(servers: &Option<Vec<String>>) -> Vec<SocketAddr> {
+  servers.as_ref().map_or(vec![], |servers| servers.iter().map(|s| parse_socket_addr(s).unwrap()).collect())
+}
+
+fn networks(networks: &Option<Vec<String>>) -> Vec<Network> {
+  networks.as_ref().map_or(vec![], |networks| networks.iter().map(|s| s.parse().unwrap()).collect())
+}
+
+fn invalid<M: fmt::Display>(key: String, message: M) -> ConfigError {
+  ConfigErrorKind::InvalidValue(key, message.to_string()).into()
+}
+
+fn check<T, E: fmt::Display>(key: String, result: Result<T, E>) -> ConfigResult<T> {
+  result.map_err(|e| invalid(key, e))
+}
+
+fn check_socket_addrs(key: &str, servers: &[String]) -> ConfigResult<()> {
+  for (i, server) in servers.iter().enumerate() {
+    try!(check(format!("{}[{}]", key, i), parse_socket_addr(server)));
+  }
+  Ok(())
+}
+
+fn check_networks(key: &str, networks: &Option<Vec<String>>) -> ConfigResult<()> {
+  for (i, network) in networks.iter().flat_map(|networks| networks.iter()).enumerate() {
+    try!(check(format!("{}[{}]", key, i), network.parse::<Network>()));
+  }
+  Ok(())
+}
+
+/// Rejects the keys which are not fields of the config, decoding silently ignores them
+fn check_keys(value: &Value) -> ConfigResult<()> {
+  let table: &Table = match *value {
+    Value::Table(ref table) => table,
+    _ => return Ok(()),
+  };
+  try!(check_table_keys(table, "", CONFIG_KEYS));
+
+  for &(name, keys) in &[("listeners", LISTENER_KEYS), ("forwarders", FORWARDER_KEYS), ("tsig_keys", TSIG_KEY_KEYS), ("zones", ZONE_KEYS)] {
+    if let Some(&Value::Array(ref array)) = table.get(name) {
+      for (i, element) in array.iter().enumerate() {
+        if let Value::Table(ref element) = *element {
+          try!(check_table_keys(element, &format!("{}[{}].", name, i), keys));
+        }
+      }
+    }
+  }
+
+  for &(name, keys) in &[("logging", LOGGING_KEYS), ("limits", LIMITS_KEYS)] {
+    if let Some(&Value::Table(ref element)) = table.get(name) {
+      try!(check_table_keys(element, &format!("{}.", name), keys));
+    }
+  }
+
+  Ok(())
+}
+
+fn check_table_keys(table: &Table, prefix: &str, keys: &[&str]) -> ConfigResult<()> {
+  for key in table.keys() {
+    if !keys.contains(&(key as &str)) {
+      return Err(ConfigErrorKind::UnknownKey(format!("{}{}", prefix, key)).into())
+    }
+  }
+  Ok(())
 }
 
 #[cfg(test)]
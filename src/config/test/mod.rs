@@ -14,11 +14,15 @@
  * limitations under the License.
  */
 use std::path::{Path, PathBuf};
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use log::LogLevel;
 
-use ::authority::ZoneType;
+use ::authority::{Network, SerialPolicy, ZoneType};
+use ::error::ConfigErrorKind;
+use ::rr::Name;
+use ::rr::dnssec::{Algorithm, Nsec3HashAlgorithm, TsigAlgorithm};
+use ::rr::rdata::NSEC3PARAM;
 use super::*;
 
 #[test]
@@ -38,11 +42,11 @@ fn test_read_config() {
   assert_eq!(config.get_log_level(), LogLevel::Info);
   assert_eq!(config.get_directory(), Path::new("/var/named"));
   assert_eq!(config.get_zones(), [
-    ZoneConfig { zone: "localhost".into(), zone_type: ZoneType::Master, file: "default/localhost.zone".into(), allow_update: None, enable_dnssec: None },
-    ZoneConfig { zone: "0.0.127.in-addr.arpa".into(), zone_type: ZoneType::Master, file: "default/127.0.0.1.zone".into(), allow_update: None, enable_dnssec: None },
-    ZoneConfig { zone: "0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa".into(), zone_type: ZoneType::Master, file: "default/ipv6_1.zone".into(), allow_update: None, enable_dnssec: None },
-    ZoneConfig { zone: "255.in-addr.arpa".into(), zone_type: ZoneType::Master, file: "default/255.zone".into(), allow_update: None, enable_dnssec: None },
-    ZoneConfig { zone: "0.in-addr.arpa".into(), zone_type: ZoneType::Master, file: "default/0.zone".into(), allow_update: None, enable_dnssec: None }
+    ZoneConfig { zone: "localhost".into(), zone_type: ZoneType::Master, file: "default/localhost.zone".into(), allow_update: None, allow_update_from: None, enable_dnssec: None, key_file: None, algorithm: None, signature_validity_days: None, nsec3: None, nsec3_iterations: None, nsec3_salt: None, primaries: None, also_notify: None, allow_transfer: None, transfer_keys: None, update_keys: None, serial_policy: None },
+    ZoneConfig { zone: "0.0.127.in-addr.arpa".into(), zone_type: ZoneType::Master, file: "default/127.0.0.1.zone".into(), allow_update: None, allow_update_from: None, enable_dnssec: None, key_file: None, algorithm: None, signature_validity_days: None, nsec3: None, nsec3_iterations: None, nsec3_salt: None, primaries: None, also_notify: None, allow_transfer: None, transfer_keys: None, update_keys: None, serial_policy: None },
+    ZoneConfig { zone: "0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa".into(), zone_type: ZoneType::Master, file: "default/ipv6_1.zone".into(), allow_update: None, allow_update_from: None, enable_dnssec: None, key_file: None, algorithm: None, signature_validity_days: None, nsec3: None, nsec3_iterations: None, nsec3_salt: None, primaries: None, also_notify: None, allow_transfer: None, transfer_keys: None, update_keys: None, serial_policy: None },
+    ZoneConfig { zone: "255.in-addr.arpa".into(), zone_type: ZoneType::Master, file: "default/255.zone".into(), allow_update: None, allow_update_from: None, enable_dnssec: None, key_file: None, algorithm: None, signature_validity_days: None, nsec3: None, nsec3_iterations: None, nsec3_salt: None, primaries: None, also_notify: None, allow_transfer: None, transfer_keys: None, update_keys: None, serial_policy: None },
+    ZoneConfig { zone: "0.in-addr.arpa".into(), zone_type: ZoneType::Master, file: "default/0.zone".into(), allow_update: None, allow_update_from: None, enable_dnssec: None, key_file: None, algorithm: None, signature_validity_days: None, nsec3: None, nsec3_iterations: None, nsec3_salt: None, primaries: None, also_notify: None, allow_transfer: None, transfer_keys: None, update_keys: None, serial_policy: None }
   ]);
 }
 
@@ -68,4 +72,145 @@ fn test_parse_toml() {
 
   let config: Config = "directory = \"/dev/null\"".parse().unwrap();
   assert_eq!(config.get_directory(), Path::new("/dev/null"));
+
+  let config: Config = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Slave\"\nfile = \"example.com.zone\"\nprimaries = [\"192.0.2.1\", \"[2001:db8::1]:5353\"]".parse().unwrap();
+  assert_eq!(config.get_zones()[0].get_primaries(), vec!["192.0.2.1:53".parse::<SocketAddr>().unwrap(), "[2001:db8::1]:5353".parse().unwrap()]);
+  assert!(config.get_zones()[0].get_also_notify().is_empty());
+
+  let config: Config = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\nalso_notify = [\"192.0.2.2\"]".parse().unwrap();
+  assert_eq!(config.get_zones()[0].get_also_notify(), vec!["192.0.2.2:53".parse::<SocketAddr>().unwrap()]);
+
+  let config: Config = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\nallow_transfer = [\"192.0.2.0/24\", \"2001:db8::1\"]".parse().unwrap();
+  assert_eq!(config.get_zones()[0].get_allow_transfer().unwrap(), vec!["192.0.2.0/24".parse::<Network>().unwrap(), "2001:db8::1/128".parse().unwrap()]);
+  assert_eq!(config.get_zones()[0].get_serial_policy(), SerialPolicy::Increment);
+
+  let config: Config = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\nserial_policy = \"Date\"".parse().unwrap();
+  assert_eq!(config.get_zones()[0].get_serial_policy(), SerialPolicy::Date);
+}
+
+#[test]
+fn test_parse_server_config() {
+  let config: Config = "threads = 2\nrecursion = true\nallow_recursion = [\"192.0.2.0/24\"]\ndeny_query = [\"198.51.100.0/24\"]\n\
+                        [[listeners]]\naddr = \"127.0.0.1\"\nport = 5353\nprotocol = \"Udp\"\n\
+                        [[listeners]]\naddr = \"::1\"\n\
+                        [[forwarders]]\ndomain = \"example.net\"\nname_servers = [\"192.0.2.53\", \"192.0.2.54:5353\"]\n\
+                        [logging]\nquery_log = \"stderr\"\ndnstap_file = \"/var/log/named/dnstap.fstrm\"\n\
+                        [limits]\nmax_tcp_connections = 100\nresponses_per_second = 5\nrate_limit_slip = 2".parse().unwrap();
+
+  assert_eq!(config.get_threads(), 2);
+  assert!(config.is_recursion_enabled());
+  let policy = config.get_query_policy();
+  assert!(policy.is_recursion_allowed(Some(&"192.0.2.1".parse().unwrap())));
+  assert!(!policy.is_query_allowed(Some(&"198.51.100.1".parse().unwrap())));
+
+  let listeners = config.get_listeners();
+  assert_eq!(listeners.len(), 2);
+  assert_eq!(listeners[0].get_addr(), "127.0.0.1".parse::<IpAddr>().unwrap());
+  assert_eq!(listeners[0].get_port(), Some(5353));
+  assert_eq!(listeners[0].get_protocol(), Protocol::Udp);
+  assert_eq!(listeners[1].get_port(), None);
+  assert_eq!(listeners[1].get_protocol(), Protocol::Both);
+  assert!(listeners[1].get_query_policy().is_none());
+
+  let forwarders = config.get_forwarders();
+  assert_eq!(forwarders[0].get_domain(), Name::parse("example.net.", None).unwrap());
+  assert_eq!(forwarders[0].get_name_servers(), vec!["192.0.2.53:53".parse::<SocketAddr>().unwrap(), "192.0.2.54:5353".parse().unwrap()]);
+
+  let logging = config.get_logging().unwrap();
+  assert_eq!(logging.get_query_log(), Some("stderr"));
+  assert_eq!(logging.get_dnstap_file(), Some(Path::new("/var/log/named/dnstap.fstrm")));
+  assert_eq!(logging.get_dnstap_socket(), None);
+
+  let limits = config.get_limits().unwrap();
+  assert_eq!(limits.get_max_tcp_connections(), Some(100));
+  assert_eq!(limits.get_tcp_idle_timeout_ms(), None);
+  assert_eq!(limits.get_responses_per_second(), Some(5));
+  assert_eq!(limits.get_rate_limit_slip(), Some(2));
+
+  let config: Config = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\nallow_update = true\n\
+                        allow_update_from = [\"192.0.2.0/24\"]\nenable_dnssec = true\nkey_file = \"keys/example.com.pem\"".parse().unwrap();
+  assert_eq!(config.get_zones()[0].get_allow_update_from(), vec!["192.0.2.0/24".parse::<Network>().unwrap()]);
+  assert_eq!(config.get_zones()[0].get_key_file(), PathBuf::from("keys/example.com.pem"));
+}
+
+#[test]
+fn test_parse_tsig_keys() {
+  let config: Config = "[[tsig_keys]]\nname = \"transfer-key\"\nsecret = \"c2VjcmV0\"\n\
+                        [[tsig_keys]]\nname = \"update-key.example.com\"\nalgorithm = \"hmac-sha512\"\nsecret = \"b3RoZXI=\"\n\
+                        [[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\n\
+                        allow_update = true\ntransfer_keys = [\"transfer-key\"]\nupdate_keys = [\"update-key.example.com\"]".parse().unwrap();
+
+  let transfer_key = Name::parse("transfer-key.", None).unwrap();
+  let update_key = Name::parse("update-key.example.com.", None).unwrap();
+  assert_eq!(config.get_zones()[0].get_transfer_keys(), vec![transfer_key.clone()]);
+  assert_eq!(config.get_zones()[0].get_update_keys(), vec![update_key.clone()]);
+
+  let key = config.get_tsig_key(&transfer_key).unwrap();
+  assert_eq!(key.get_name(), &transfer_key);
+  assert_eq!(key.get_algorithm(), TsigAlgorithm::HmacSha256);
+  assert_eq!(config.get_tsig_key(&update_key).unwrap().get_algorithm(), TsigAlgorithm::HmacSha512);
+  assert!(config.get_tsig_key(&Name::parse("other-key.", None).unwrap()).is_none());
+}
+
+#[test]
+fn test_parse_signing_config() {
+  let config: Config = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\nenable_dnssec = true".parse().unwrap();
+  let zone = &config.get_zones()[0];
+  assert_eq!(zone.get_key_file(), PathBuf::from("example.com.zone").with_extension(".key"));
+  assert_eq!(zone.get_algorithm(), Algorithm::RSASHA256);
+  assert_eq!(zone.get_signature_validity_days(), 364);
+  assert!(zone.get_nsec3param().is_none());
+
+  let config: Config = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\nenable_dnssec = true\n\
+                        algorithm = \"RSASHA512\"\nsignature_validity_days = 30\nnsec3 = true\nnsec3_iterations = 5\nnsec3_salt = \"aabbccdd\"".parse().unwrap();
+  let zone = &config.get_zones()[0];
+  assert_eq!(zone.get_algorithm(), Algorithm::RSASHA512);
+  assert_eq!(zone.get_signature_validity_days(), 30);
+  assert_eq!(zone.get_nsec3param(), Some(NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, false, 5, vec![0xAA, 0xBB, 0xCC, 0xDD])));
+}
+
+fn invalid_key(toml: &str) -> String {
+  match *toml.parse::<Config>().unwrap_err().kind() {
+    ConfigErrorKind::InvalidValue(ref key, _) => key.clone(),
+    ConfigErrorKind::UnknownKey(ref key) => format!("unknown {}", key),
+    ref kind => panic!("unexpected error: {:?}", kind),
+  }
+}
+
+#[test]
+fn test_config_errors() {
+  assert_eq!(invalid_key("listen_por = 53"), "unknown listen_por");
+  assert_eq!(invalid_key("[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\n\
+                          [[zones]]\nzone = \"example.net\"\nzsone_type = \"Master\"\nfile = \"example.net.zone\""),
+             "unknown zones[1].zsone_type");
+  assert_eq!(invalid_key("[limits]\nmax_tcp_conns = 1"), "unknown limits.max_tcp_conns");
+
+  assert_eq!(invalid_key("listen_addrs_ipv4 = [\"0.0.0.0\", \"::1\"]"), "listen_addrs_ipv4[1]");
+  assert_eq!(invalid_key("log_level = \"Loud\""), "log_level");
+  assert_eq!(invalid_key("threads = 0"), "threads");
+  assert_eq!(invalid_key("allow_query = [\"192.0.2.0/33\"]"), "allow_query[0]");
+  assert_eq!(invalid_key("threads = 2\n[[listeners]]\naddr = \"127.0.0.1\"\nallow_query = [\"127.0.0.1\"]"), "listeners[0].allow_query");
+  assert_eq!(invalid_key("[[listeners]]\naddr = \"localhost\""), "listeners[0].addr");
+  assert_eq!(invalid_key("[[forwarders]]\ndomain = \"example.net\"\nname_servers = []"), "forwarders[0].name_servers");
+  assert_eq!(invalid_key("[logging]\ndnstap_file = \"a\"\ndnstap_socket = \"b\""), "logging.dnstap_socket");
+  assert_eq!(invalid_key("[limits]\nrate_limit_slip = 2"), "limits.rate_limit_slip");
+  assert_eq!(invalid_key("[[zones]]\nzone = \"example.com\"\nzone_type = \"Slave\"\nfile = \"example.com.zone\""), "zones[0].primaries");
+  assert_eq!(invalid_key("[[zones]]\nzone = \"example.com\"\nzone_type = \"Slave\"\nfile = \"example.com.zone\"\nprimaries = [\"192.0.2.1\", \"primary\"]"),
+             "zones[0].primaries[1]");
+  assert_eq!(invalid_key("[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\nallow_update_from = [\"192.0.2.1\"]"),
+             "zones[0].allow_update_from");
+
+  let zone = "[[zones]]\nzone = \"example.com\"\nzone_type = \"Master\"\nfile = \"example.com.zone\"\n";
+  assert_eq!(invalid_key(&format!("{}nsec3 = true", zone)), "zones[0].nsec3");
+  assert_eq!(invalid_key(&format!("{}transfer_keys = [\"missing-key\"]", zone)), "zones[0].transfer_keys[0]");
+  assert_eq!(invalid_key(&format!("{}update_keys = []", zone)), "zones[0].update_keys");
+
+  let tsig_key = "[[tsig_keys]]\nname = \"transfer-key\"\nsecret = \"c2VjcmV0\"\n";
+  assert_eq!(invalid_key(&format!("{}{}", tsig_key, tsig_key)), "tsig_keys[1].name");
+  assert_eq!(invalid_key("[[tsig_keys]]\nname = \"transfer-key\"\nsecret = \"not base64!\""), "tsig_keys[0].secret");
+  assert_eq!(invalid_key("[[tsig_keys]]\nname = \"transfer-key\"\nalgorithm = \"hmac-sha3\"\nsecret = \"c2VjcmV0\""), "tsig_keys[0].algorithm");
+  assert_eq!(invalid_key(&format!("{}enable_dnssec = true\nalgorithm = \"ED25519\"", zone)), "zones[0].algorithm");
+  assert_eq!(invalid_key(&format!("{}enable_dnssec = true\nnsec3_salt = \"aa\"", zone)), "zones[0].nsec3_salt");
+  assert_eq!(invalid_key(&format!("{}enable_dnssec = true\nnsec3 = true\nnsec3_salt = \"xyz\"", zone)), "zones[0].nsec3_salt");
+  assert_eq!(invalid_key(&format!("{}enable_dnssec = true\nnsec3 = true\nalgorithm = \"RSASHA1\"", zone)), "zones[0].algorithm");
 }
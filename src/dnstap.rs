@@ -0,0 +1,693 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! [dnstap](http://dnstap.info) output of the messages sent and received by the `Server` and the
+//!  `Client`, protobuf messages in a [Frame Streams](https://github.com/farsightsec/fstrm) stream,
+//!  written to a file or to the unix socket of a collector, e.g. `dnstap -u /var/run/dnstap.sock`.
+//!
+//! ```rust,ignore
+//! let dnstap = Arc::new(try!(Dnstap::connect(Path::new("/var/run/dnstap.sock"))));
+//! server.set_dnstap(dnstap.clone());
+//! client.set_observer(Some(Rc::new(DnstapObserver::new(dnstap, MessageType::ForwarderQuery))));
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+#[cfg(unix)] use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(unix)] use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ::client::{QueryObserver, Transport};
+use ::error::*;
+use ::op::Message;
+use ::serialize::binary::{BinEncoder, BinSerializable};
+
+/// the content type of the frames, the Frame Streams control frames negotiate it
+const CONTENT_TYPE: &'static [u8] = b"protobuf:dnstap.Dnstap";
+
+// the Frame Streams control frame types, and the content type field of them
+#[cfg(unix)] const CONTROL_ACCEPT: u32 = 0x01;
+const CONTROL_START: u32 = 0x02;
+const CONTROL_STOP: u32 = 0x03;
+#[cfg(unix)] const CONTROL_READY: u32 = 0x04;
+const CONTROL_FIELD_CONTENT_TYPE: u32 = 0x01;
+
+/// control frames are small, anything larger is not from a Frame Streams receiver
+const MAX_CONTROL_FRAME: usize = 512;
+
+/// the frames queued for the writer, beyond which events are dropped
+const MAX_QUEUED_FRAMES: usize = 4096;
+/// a collector which fails is reconnected at most this often
+#[cfg(unix)] const RECONNECT_INTERVAL_SECS: u64 = 5;
+/// a collector which does not read for this long has failed
+#[cfg(unix)] const COLLECTOR_TIMEOUT_SECS: u64 = 5;
+
+/// The type of a dnstap message, which side of which exchange the message was seen at
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum MessageType {
+  /// a query received by an authoritative server
+  AuthQuery,
+  /// a response sent by an authoritative server
+  AuthResponse,
+  /// a query sent by a recursive server toward an authoritative server
+  ResolverQuery,
+  /// a response received by a recursive server from an authoritative server
+  ResolverResponse,
+  /// a query received by a recursive server from a client
+  ClientQuery,
+  /// a response sent by a recursive server to a client
+  ClientResponse,
+  /// a query forwarded to an upstream recursive server
+  ForwarderQuery,
+  /// a response received from an upstream recursive server
+  ForwarderResponse,
+  /// a query sent by a stub resolver
+  StubQuery,
+  /// a response received by a stub resolver
+  StubResponse,
+}
+
+impl MessageType {
+  /// true for the query types, whose message is the query
+  pub fn is_query(&self) -> bool {
+    self.value() % 2 == 1
+  }
+
+  /// The response type of the same exchange, e.g. `ResolverResponse` for `ResolverQuery`
+  pub fn response(&self) -> MessageType {
+    match *self {
+      MessageType::AuthQuery | MessageType::AuthResponse => MessageType::AuthResponse,
+      MessageType::ResolverQuery | MessageType::ResolverResponse => MessageType::ResolverResponse,
+      MessageType::ClientQuery | MessageType::ClientResponse => MessageType::ClientResponse,
+      MessageType::ForwarderQuery | MessageType::ForwarderResponse => MessageType::ForwarderResponse,
+      MessageType::StubQuery | MessageType::StubResponse => MessageType::StubResponse,
+    }
+  }
+
+  fn value(&self) -> u64 {
+    match *self {
+      MessageType::AuthQuery => 1,
+      MessageType::AuthResponse => 2,
+      MessageType::ResolverQuery => 3,
+      MessageType::ResolverResponse => 4,
+      MessageType::ClientQuery => 5,
+      MessageType::ClientResponse => 6,
+      MessageType::ForwarderQuery => 7,
+      MessageType::ForwarderResponse => 8,
+      MessageType::StubQuery => 9,
+      MessageType::StubResponse => 10,
+    }
+  }
+}
+
+/// The protocol the message was sent over
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum SocketProtocol {
+  Udp,
+  Tcp,
+  /// DNS over TLS
+  Dot,
+}
+
+impl SocketProtocol {
+  fn value(&self) -> u64 {
+    match *self {
+      SocketProtocol::Udp => 1,
+      SocketProtocol::Tcp => 2,
+      SocketProtocol::Dot => 3,
+    }
+  }
+}
+
+/// A single dnstap message, all but the type are optional
+#[derive(Debug, Clone)]
+pub struct DnstapEvent<'a> {
+  message_type: MessageType,
+  protocol: Option<SocketProtocol>,
+  query_address: Option<SocketAddr>,
+  response_address: Option<SocketAddr>,
+  query: Option<(SystemTime, &'a [u8])>,
+  response: Option<(SystemTime, &'a [u8])>,
+}
+
+impl<'a> DnstapEvent<'a> {
+  pub fn new(message_type: MessageType) -> Self {
+    DnstapEvent{ message_type: message_type, protocol: None, query_address: None, response_address: None, query: None,
+                 response: None }
+  }
+
+  pub fn protocol(&mut self, protocol: SocketProtocol) -> &mut Self {
+    self.protocol = Some(protocol);
+    self
+  }
+
+  /// The address of the initiator of the exchange, e.g. the client of a server
+  pub fn query_address(&mut self, address: SocketAddr) -> &mut Self {
+    self.query_address = Some(address);
+    self
+  }
+
+  /// The address of the responder of the exchange, e.g. the upstream server of a forwarder
+  pub fn response_address(&mut self, address: SocketAddr) -> &mut Self {
+    self.response_address = Some(address);
+    self
+  }
+
+  /// The query in wire format, and when it was sent or received
+  pub fn query(&mut self, time: SystemTime, message: &'a [u8]) -> &mut Self {
+    self.query = Some((time, message));
+    self
+  }
+
+  /// The response in wire format, and when it was sent or received
+  pub fn response(&mut self, time: SystemTime, message: &'a [u8]) -> &mut Self {
+    self.response = Some((time, message));
+    self
+  }
+
+  pub fn get_message_type(&self) -> MessageType { self.message_type }
+
+  /// the dnstap.Message protobuf of the event
+  fn encode(&self) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::with_capacity(64 + self.query.map_or(0, |(_, m)| m.len()) + self.response.map_or(0, |(_, m)| m.len()));
+    encode_varint_field(&mut buf, 1, self.message_type.value());
+
+    if let Some(address) = self.query_address.or(self.response_address) {
+      encode_varint_field(&mut buf, 2, if let IpAddr::V4(..) = address.ip() { 1 } else { 2 });
+    }
+    if let Some(protocol) = self.protocol {
+      encode_varint_field(&mut buf, 3, protocol.value());
+    }
+    if let Some(address) = self.query_address {
+      encode_bytes_field(&mut buf, 4, &address_bytes(&address));
+    }
+    if let Some(address) = self.response_address {
+      encode_bytes_field(&mut buf, 5, &address_bytes(&address));
+    }
+    if let Some(address) = self.query_address {
+      encode_varint_field(&mut buf, 6, address.port() as u64);
+    }
+    if let Some(address) = self.response_address {
+      encode_varint_field(&mut buf, 7, address.port() as u64);
+    }
+    if let Some((time, message)) = self.query {
+      let since_epoch = since_epoch(time);
+      encode_varint_field(&mut buf, 8, since_epoch.as_secs());
+      encode_fixed32_field(&mut buf, 9, since_epoch.subsec_nanos());
+      encode_bytes_field(&mut buf, 10, message);
+    }
+    if let Some((time, message)) = self.response {
+      let since_epoch = since_epoch(time);
+      encode_varint_field(&mut buf, 12, since_epoch.as_secs());
+      encode_fixed32_field(&mut buf, 13, since_epoch.subsec_nanos());
+      encode_bytes_field(&mut buf, 14, message);
+    }
+
+    buf
+  }
+}
+
+/// The dnstap output, the events are written as Frame Streams data frames of dnstap.Dnstap
+///  protobufs to a file or to the unix socket of a collector.
+///
+/// The frames are queued for a writer thread, so that a slow or stalled collector does not stall
+///  the server. When the queue is full the events are dropped, as are those which could not be
+///  written, see `get_dropped()`; they do not affect the queries. A collector which fails is
+///  reconnected, at most every 5 seconds.
+pub struct Dnstap {
+  sender: Mutex<Option<SyncSender<Vec<u8>>>>,
+  writer: Mutex<Option<JoinHandle<()>>>,
+  dropped: Arc<AtomicUsize>,
+  identity: Option<Vec<u8>>,
+  version: Option<Vec<u8>>,
+}
+
+impl Dnstap {
+  /// Starts a unidirectional stream, the START frame is written to the writer
+  pub fn new(writer: Box<Write + Send>) -> io::Result<Self> {
+    let mut writer = writer;
+    try!(writer.write_all(&control_frame(CONTROL_START, true)));
+    Self::spawn(Output{ writer: Some(BufWriter::new(writer)), collector: None, failed: None })
+  }
+
+  /// Writes the stream to the file, creating or truncating it
+  pub fn create(path: &Path) -> io::Result<Self> {
+    Self::new(Box::new(try!(File::create(path))))
+  }
+
+  /// Connects to the unix socket of a collector, negotiating the content type with it as a
+  ///  bidirectional stream. If the collector fails it is reconnected.
+  #[cfg(unix)]
+  pub fn connect(path: &Path) -> io::Result<Self> {
+    let stream = try!(connect_collector(path));
+    Self::spawn(Output{ writer: Some(BufWriter::new(Box::new(stream))), collector: Some(path.to_path_buf()), failed: None })
+  }
+
+  /// starts the thread which writes the queued frames to the output
+  fn spawn(output: Output) -> io::Result<Self> {
+    let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_FRAMES);
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let writer_dropped = dropped.clone();
+    let writer = try!(thread::Builder::new().name("dnstap".to_string()).spawn(move || output.run(receiver, writer_dropped)));
+
+    Ok(Dnstap{ sender: Mutex::new(Some(sender)), writer: Mutex::new(Some(writer)), dropped: dropped, identity: None, version: None })
+  }
+
+  /// The identity of the server in each message, e.g. its hostname
+  pub fn identity(&mut self, identity: Option<String>) -> &mut Self {
+    self.identity = identity.map(|identity| identity.into_bytes());
+    self
+  }
+
+  /// The version of the server in each message, e.g. `trust_dns::version()`
+  pub fn version(&mut self, version: Option<String>) -> &mut Self {
+    self.version = version.map(|version| version.into_bytes());
+    self
+  }
+
+  /// Writes the event as a data frame
+  pub fn log(&self, event: &DnstapEvent) {
+    // the Dnstap protobuf, of type MESSAGE
+    let message = event.encode();
+    let mut dnstap: Vec<u8> = Vec::with_capacity(message.len() + 32);
+    if let Some(ref identity) = self.identity { encode_bytes_field(&mut dnstap, 1, identity); }
+    if let Some(ref version) = self.version { encode_bytes_field(&mut dnstap, 2, version); }
+    encode_bytes_field(&mut dnstap, 14, &message);
+    encode_varint_field(&mut dnstap, 15, 1);
+
+    let mut frame: Vec<u8> = Vec::with_capacity(dnstap.len() + 4);
+    push_u32(&mut frame, dnstap.len() as u32);
+    frame.extend_from_slice(&dnstap);
+
+    let sender = self.sender.lock().unwrap(); // poison errors should panic...
+    let full = match *sender {
+      Some(ref sender) => match sender.try_send(frame) {
+        Ok(()) => false,
+        Err(TrySendError::Full(..)) => true,
+        Err(TrySendError::Disconnected(..)) => true,
+      },
+      None => true,
+    };
+
+    // only the first is logged, the writer is behind and there would be many more
+    if full && self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+      warn!("the dnstap queue is full, dropping the {:?} event", event.get_message_type());
+    }
+  }
+
+  /// The number of events which were dropped, because the queue was full or they could not be
+  ///  written
+  pub fn get_dropped(&self) -> usize {
+    self.dropped.load(Ordering::Relaxed)
+  }
+
+  /// Logs a request received by the server, and the responses sent to it, a zone transfer is
+  ///  sent in multiple responses. The messages are `ClientQuery` and `ClientResponse` if the
+  ///  server was recursive for the client, otherwise `AuthQuery` and `AuthResponse`.
+  pub fn log_server<'a, I>(&self, client: SocketAddr, protocol: SocketProtocol, recursive: bool, received: Instant, request: &[u8],
+                           responses: I) where I: IntoIterator<Item=&'a [u8]> {
+    let message_type = if recursive { MessageType::ClientQuery } else { MessageType::AuthQuery };
+    let received = SystemTime::now() - received.elapsed();
+    self.log(DnstapEvent::new(message_type).protocol(protocol).query_address(client).query(received, request));
+
+    for response in responses {
+      self.log(DnstapEvent::new(message_type.response()).protocol(protocol).query_address(client).query(received, request)
+                                                         .response(SystemTime::now(), response));
+    }
+  }
+}
+
+impl Drop for Dnstap {
+  /// the writer writes the queued frames and the STOP frame once the queue is closed
+  fn drop(&mut self) {
+    if let Ok(mut sender) = self.sender.lock() { sender.take(); }
+    if let Some(writer) = self.writer.lock().ok().and_then(|mut writer| writer.take()) {
+      let _ = writer.join();
+    }
+  }
+}
+
+/// where the writer thread writes the frames, a collector is reconnected after it fails, the
+///  frames in the meantime are lost
+struct Output {
+  writer: Option<BufWriter<Box<Write + Send>>>,
+  collector: Option<PathBuf>,
+  failed: Option<Instant>,
+}
+
+impl Output {
+  /// writes the frames until the queue is closed, flushing whenever it is empty
+  fn run(mut self, receiver: Receiver<Vec<u8>>, dropped: Arc<AtomicUsize>) {
+    while let Ok(frame) = receiver.recv() {
+      if !self.write(&frame) { dropped.fetch_add(1, Ordering::Relaxed); }
+
+      while let Ok(frame) = receiver.try_recv() {
+        if !self.write(&frame) { dropped.fetch_add(1, Ordering::Relaxed); }
+      }
+
+      self.flush();
+    }
+
+    if let Some(ref mut writer) = self.writer {
+      let _ = writer.write_all(&control_frame(CONTROL_STOP, false)).and_then(|_| writer.flush());
+    }
+  }
+
+  /// writes the frame, false if it was lost
+  fn write(&mut self, frame: &[u8]) -> bool {
+    self.reconnect();
+    let result = match self.writer {
+      Some(ref mut writer) => writer.write_all(frame),
+      None => return false,
+    };
+
+    match result {
+      Ok(()) => true,
+      Err(error) => { self.fail(error); false },
+    }
+  }
+
+  fn flush(&mut self) {
+    let result = match self.writer {
+      Some(ref mut writer) => writer.flush(),
+      None => return,
+    };
+
+    if let Err(error) = result { self.fail(error); }
+  }
+
+  /// a collector is disconnected, to be reconnected, a file is kept
+  fn fail(&mut self, error: io::Error) {
+    warn!("could not write to dnstap: {}", error);
+    if self.collector.is_some() {
+      self.writer = None;
+      self.failed = Some(Instant::now());
+    }
+  }
+
+  /// reconnects to the collector, if it is disconnected and failed long enough ago
+  #[cfg(unix)]
+  fn reconnect(&mut self) {
+    if self.writer.is_some() { return }
+    let path: PathBuf = if let Some(ref path) = self.collector { path.clone() } else { return };
+    if self.failed.map_or(false, |failed| failed.elapsed() < Duration::from_secs(RECONNECT_INTERVAL_SECS)) { return }
+
+    match connect_collector(&path) {
+      Ok(stream) => {
+        info!("reconnected to the dnstap collector: {:?}", path);
+        self.writer = Some(BufWriter::new(Box::new(stream)));
+        self.failed = None;
+      },
+      Err(error) => {
+        warn!("could not reconnect to the dnstap collector {:?}: {}", path, error);
+        self.failed = Some(Instant::now());
+      },
+    }
+  }
+
+  #[cfg(not(unix))]
+  fn reconnect(&mut self) {}
+}
+
+/// connects to the unix socket of a collector, negotiating the content type, and starts the stream
+#[cfg(unix)]
+fn connect_collector(path: &Path) -> io::Result<UnixStream> {
+  let mut stream = try!(UnixStream::connect(path));
+  try!(stream.set_read_timeout(Some(Duration::from_secs(COLLECTOR_TIMEOUT_SECS))));
+  try!(stream.set_write_timeout(Some(Duration::from_secs(COLLECTOR_TIMEOUT_SECS))));
+  try!(stream.write_all(&control_frame(CONTROL_READY, true)));
+
+  // the collector accepts our content type, or closes the connection
+  let accept = try!(read_control_frame(&mut stream));
+  if accept.len() < 4 || read_u32(&accept[0..4]) != CONTROL_ACCEPT {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "expected an ACCEPT frame from the dnstap collector"))
+  }
+  if !accept.windows(CONTENT_TYPE.len()).any(|window| window == CONTENT_TYPE) {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "the dnstap collector does not accept dnstap"))
+  }
+
+  try!(stream.write_all(&control_frame(CONTROL_START, true)));
+  Ok(stream)
+}
+
+/// Logs the messages of a `Client` to dnstap, set with `Client::set_observer()`. The messages are
+///  those decoded and encoded by the client, the upstream server's address is not known.
+pub struct DnstapObserver {
+  dnstap: Arc<Dnstap>,
+  query_type: MessageType,
+}
+
+impl DnstapObserver {
+  /// # Arguments
+  ///
+  /// * `dnstap` - the output, which may be shared with the `Server`
+  /// * `query_type` - the type of the queries, e.g. `ResolverQuery` for a `Recursor`, or
+  ///                  `ForwarderQuery` for a `Forwarder`, the responses are of the matching type
+  pub fn new(dnstap: Arc<Dnstap>, query_type: MessageType) -> Self {
+    DnstapObserver{ dnstap: dnstap, query_type: query_type }
+  }
+
+  fn protocol(transport: Transport) -> Option<SocketProtocol> {
+    match transport {
+      Transport::Udp => Some(SocketProtocol::Udp),
+      Transport::Tcp => Some(SocketProtocol::Tcp),
+      Transport::Tls => Some(SocketProtocol::Dot),
+      Transport::Unknown => None,
+    }
+  }
+
+  fn encode(message: &Message) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(512);
+    let encode_result = {
+      let mut encoder = BinEncoder::new(&mut bytes);
+      message.emit(&mut encoder)
+    };
+
+    match encode_result {
+      Ok(()) => Some(bytes),
+      Err(error) => { warn!("could not encode the message for dnstap: {}", error); None },
+    }
+  }
+}
+
+impl QueryObserver for DnstapObserver {
+  fn on_send(&self, request: &Message, transport: Transport) {
+    if let Some(request) = Self::encode(request) {
+      let mut event = DnstapEvent::new(self.query_type);
+      event.query(SystemTime::now(), &request);
+      if let Some(protocol) = Self::protocol(transport) { event.protocol(protocol); }
+      self.dnstap.log(&event);
+    }
+  }
+
+  fn on_response(&self, request: &Message, response: &Message, latency: Duration, transport: Transport) {
+    if let (Some(request), Some(response)) = (Self::encode(request), Self::encode(response)) {
+      let now = SystemTime::now();
+      let mut event = DnstapEvent::new(self.query_type.response());
+      event.query(now - latency, &request).response(now, &response);
+      if let Some(protocol) = Self::protocol(transport) { event.protocol(protocol); }
+      self.dnstap.log(&event);
+    }
+  }
+
+  fn on_error(&self, _: &Message, error: &ClientError, _: Duration, _: Transport) {
+    debug!("no dnstap response message, the request failed: {}", error);
+  }
+}
+
+fn since_epoch(time: SystemTime) -> Duration {
+  time.duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0))
+}
+
+fn address_bytes(address: &SocketAddr) -> Vec<u8> {
+  match address.ip() {
+    IpAddr::V4(ip) => ip.octets().to_vec(),
+    IpAddr::V6(ip) => ip.segments().iter().flat_map(|segment| vec![(*segment >> 8) as u8, *segment as u8]).collect(),
+  }
+}
+
+fn encode_varint(buf: &mut Vec<u8>, value: u64) {
+  let mut value = value;
+  while value >= 0x80 {
+    buf.push((value as u8 & 0x7F) | 0x80);
+    value >>= 7;
+  }
+  buf.push(value as u8);
+}
+
+/// a varint field, wire type 0
+fn encode_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+  encode_varint(buf, field << 3);
+  encode_varint(buf, value);
+}
+
+/// a length delimited field, wire type 2
+fn encode_bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+  encode_varint(buf, field << 3 | 2);
+  encode_varint(buf, bytes.len() as u64);
+  buf.extend_from_slice(bytes);
+}
+
+/// a little endian fixed32 field, wire type 5
+fn encode_fixed32_field(buf: &mut Vec<u8>, field: u64, value: u32) {
+  encode_varint(buf, field << 3 | 5);
+  buf.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+/// frame lengths are big endian
+fn push_u32(buf: &mut Vec<u8>, value: u32) {
+  buf.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+  (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+/// a control frame, an escape of a zero length, followed by the length and the control type, with
+///  the content type field if requested
+fn control_frame(control_type: u32, content_type: bool) -> Vec<u8> {
+  let mut control: Vec<u8> = Vec::with_capacity(MAX_CONTROL_FRAME);
+  push_u32(&mut control, control_type);
+  if content_type {
+    push_u32(&mut control, CONTROL_FIELD_CONTENT_TYPE);
+    push_u32(&mut control, CONTENT_TYPE.len() as u32);
+    control.extend_from_slice(CONTENT_TYPE);
+  }
+
+  let mut frame: Vec<u8> = Vec::with_capacity(control.len() + 8);
+  push_u32(&mut frame, 0);
+  push_u32(&mut frame, control.len() as u32);
+  frame.extend_from_slice(&control);
+  frame
+}
+
+/// reads a control frame, returning its payload, the control type and fields
+#[cfg(unix)]
+fn read_control_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+  let mut header = [0u8; 8];
+  try!(reader.read_exact(&mut header));
+  let length = read_u32(&header[4..8]) as usize;
+  if read_u32(&header[0..4]) != 0 || length > MAX_CONTROL_FRAME {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "expected a control frame from the dnstap collector"))
+  }
+
+  let mut control = vec![0u8; length];
+  try!(reader.read_exact(&mut control));
+  Ok(control)
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::{self, Write};
+  use std::sync::{Arc, Mutex};
+  use std::time::{Duration, UNIX_EPOCH};
+
+  use super::*;
+
+  /// a writer which may be inspected after it is given to the Dnstap
+  #[derive(Clone, Default)]
+  struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+  impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+  }
+
+  #[test]
+  fn test_encode_event() {
+    let time = UNIX_EPOCH + Duration::new(1_471_687_200, 300);
+    let query: &[u8] = &[0xAB, 0xCD];
+    let mut event = DnstapEvent::new(MessageType::AuthQuery);
+    event.protocol(SocketProtocol::Udp).query_address("192.0.2.1:53535".parse().unwrap()).query(time, query);
+
+    assert_eq!(event.encode(),
+               vec![0x08, 0x01, // type AUTH_QUERY
+                    0x10, 0x01, // socket_family INET
+                    0x18, 0x01, // socket_protocol UDP
+                    0x22, 0x04, 192, 0, 2, 1, // query_address
+                    0x30, 0x9F, 0xA2, 0x03, // query_port 53535
+                    0x40, 0xA0, 0xD4, 0xE0, 0xBD, 0x05, // query_time_sec
+                    0x4D, 0x2C, 0x01, 0x00, 0x00, // query_time_nsec
+                    0x52, 0x02, 0xAB, 0xCD]); // query_message
+
+    let mut event = DnstapEvent::new(MessageType::ClientResponse);
+    event.response_address("[2001:db8::1]:53".parse().unwrap());
+    assert_eq!(&event.encode()[..8], &[0x08, 0x06, 0x10, 0x02, 0x2A, 0x10, 0x20, 0x01]);
+
+    assert!(MessageType::ResolverQuery.is_query());
+    assert_eq!(MessageType::ResolverQuery.response(), MessageType::ResolverResponse);
+  }
+
+  #[test]
+  fn test_frame_stream() {
+    let buffer = SharedBuffer::default();
+    {
+      let mut dnstap = Dnstap::new(Box::new(buffer.clone())).unwrap();
+      dnstap.identity(Some("ns1".to_string()));
+
+      // the START frame, with the content type
+      let start: Vec<u8> = buffer.0.lock().unwrap().clone();
+      assert_eq!(&start[..16], &[0, 0, 0, 0, 0, 0, 0, 34, 0, 0, 0, 2, 0, 0, 0, 1]);
+      assert_eq!(&start[16..20], &[0, 0, 0, 22]);
+      assert_eq!(&start[20..], b"protobuf:dnstap.Dnstap");
+      buffer.0.lock().unwrap().clear();
+
+      dnstap.log(&DnstapEvent::new(MessageType::StubQuery));
+    }
+
+    // the queued data frame is written before the STOP frame
+    assert_eq!(*buffer.0.lock().unwrap(),
+               vec![0, 0, 0, 11, // the length of the data frame
+                    0x0A, 0x03, b'n', b's', b'1', // identity
+                    0x72, 0x02, 0x08, 0x09, // message, of type STUB_QUERY
+                    0x78, 0x01, // type MESSAGE
+                    0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 3]); // STOP
+  }
+
+  /// a writer which blocks after the START frame, until it is released
+  struct StalledWriter(Mutex<mpsc::Receiver<()>>, bool);
+
+  impl Write for StalledWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      if self.1 { let _ = self.0.lock().unwrap().recv(); }
+      self.1 = true;
+      Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+  }
+
+  #[test]
+  fn test_stalled_writer() {
+    let (release, stalled) = mpsc::channel();
+    let dnstap = Dnstap::new(Box::new(StalledWriter(Mutex::new(stalled), false))).unwrap();
+
+    // the writer is stalled, the events do not block, those beyond the queue and the buffer of
+    //  the writer are dropped
+    for _ in 0..(3 * MAX_QUEUED_FRAMES) {
+      dnstap.log(&DnstapEvent::new(MessageType::AuthQuery));
+    }
+    assert!(dnstap.get_dropped() > 0);
+
+    drop(release);
+    drop(dnstap);
+  }
+}
@@ -37,6 +37,8 @@ error_chain! {
     links {
       super::decode_error::Error, super::decode_error::ErrorKind, Decode;
       super::encode_error::Error, super::encode_error::ErrorKind, Encode;
+      super::parse_error::Error, super::parse_error::ErrorKind, Parse;
+      super::config_error::Error, super::config_error::ErrorKind, Config;
     }
 
     // Automatic conversions between this error chain and other
@@ -78,6 +80,11 @@ error_chain! {
         display("response was an error: {}", response_code.to_str())
       }
 
+      NegativeResponse(response_code: ResponseCode, negative_ttl: u32) {
+        description("the name or records do not exist")
+        display("the name or records do not exist: {}, negative ttl: {}", response_code.to_str(), negative_ttl)
+      }
+
       // TODO: add record to which this applies
       NoRRSIG {
         description("no rrsig was recieved")
@@ -101,9 +108,44 @@ error_chain! {
         display("no soa record found for zone: {}", name)
       }
 
+      Bogus(name: Name, reason: String) {
+        description("dnssec validation failed")
+        display("dnssec validation failed for {}: {}", name, reason)
+      }
+
       SecNxDomain(proof: Vec<Record>) {
         description("verified secure non-existence")
         display("verified secure non-existence: {:?}", proof)
       }
+
+      CnameLoop(name: Name) {
+        description("cname chain loops")
+        display("cname chain loops at: {}", name)
+      }
+
+      CnameDepthExceeded(depth: usize) {
+        description("cname chain exceeded the maximum depth")
+        display("cname chain exceeded the maximum depth: {}", depth)
+      }
+
+      Timeout {
+        description("request timed out")
+        display("request timed out")
+      }
+
+      Proxy(msg: String) {
+        description("proxy negotiation failed")
+        display("proxy negotiation failed: {}", msg)
+      }
+
+      Coalesced(msg: String) {
+        description("coalesced query failed")
+        display("coalesced query failed: {}", msg)
+      }
+
+      Tsig(msg: String) {
+        description("tsig verification failed")
+        display("tsig verification failed: {}", msg)
+      }
     }
 }
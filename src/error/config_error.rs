@@ -54,5 +54,15 @@ error_chain! {
         description("parser errors")
         display("parser errors: {:?}", vec)
       }
+
+      InvalidValue(key: String, message: String) {
+        description("invalid value")
+        display("invalid value for {}: {}", key, message)
+      }
+
+      UnknownKey(key: String) {
+        description("unknown key")
+        display("unknown key: {}", key)
+      }
     }
 }
@@ -107,6 +107,31 @@ error_chain! {
       display("unrecognized label code: {:b}", value)
     }
 
+    ForwardPointer(location: u16, index: usize) {
+      description("label pointer does not point to a prior label")
+      display("label pointer to {} does not point before {}", location, index)
+    }
+
+    TooManyPointers(count: usize) {
+      description("too many label pointers")
+      display("too many label pointers: {}", count)
+    }
+
+    LabelBytesTooLong(len: usize) {
+      description("label bytes exceed 63")
+      display("label bytes exceed 63: {}", len)
+    }
+
+    DomainNameTooLong(len: usize) {
+      description("domain name exceeds 255 bytes")
+      display("domain name exceeds 255 bytes: {}", len)
+    }
+
+    RDataLengthExceedsBuffer(len: usize, remaining: usize) {
+      description("rdata length exceeds the remaining bytes")
+      display("rdata length {} exceeds the remaining bytes: {}", len, remaining)
+    }
+
     IncorrectRDataLengthRead(read: usize, len: usize) {
       description("incorrect rdata length read")
       display("incorrect rdata length read: {} expected: {}", read, len)
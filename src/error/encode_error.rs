@@ -56,5 +56,10 @@ error_chain! {
         description("name label data exceed 255")
         display("name label data exceed 255: {}", len)
       }
+
+      RDataTooLong(len: usize) {
+        description("rdata length exceeds 65535")
+        display("rdata length exceeds 65535: {}", len)
+      }
     }
 }
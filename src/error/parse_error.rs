@@ -80,5 +80,10 @@ error_chain! {
         description("invalid time string")
         display("invalid time string: {}", string)
       }
+
+      InvalidIdna(label: String) {
+        description("invalid internationalized label")
+        display("invalid internationalized label: {}", label)
+      }
     }
 }
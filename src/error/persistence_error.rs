@@ -4,6 +4,8 @@
 // http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
+use std::io;
+
 use rusqlite;
 
 use super::{decode_error, encode_error};
@@ -35,6 +37,7 @@ error_chain! {
   // This section can be empty.
   foreign_links {
     rusqlite::Error, Sqlite, "sqlite error";
+    io::Error, Io, "io error";
   }
 
   // Define additional `ErrorKind` variants. The syntax here is
@@ -50,5 +53,10 @@ error_chain! {
       description("error recovering from journal")
       display("error recovering from journal: {}", msg)
     }
+
+    OutOfSync(serial: u32, last_serial: u32) {
+      description("journal is out of sync with the zone")
+      display("journal does not follow serial {} of the zone to its last serial {}", serial, last_serial)
+    }
   }
 }
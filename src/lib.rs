@@ -36,8 +36,11 @@ extern crate openssl_sys;
 extern crate rand;
 extern crate rusqlite;
 extern crate rustc_serialize;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(any(feature = "serde_json", all(test, feature = "serde")))] extern crate serde_json;
 extern crate time;
 extern crate toml;
+#[cfg(windows)] extern crate winreg;
 
 
 pub mod error;
@@ -47,10 +50,13 @@ pub mod authority;
 pub mod op;
 pub mod udp;
 pub mod tcp;
+pub mod tls;
 pub mod client;
 pub mod server;
 pub mod serialize;
 pub mod config;
+pub mod dnstap;
+pub mod resolver;
 
 /// this exposes a version function which gives access to the access
 include!(concat!(env!("OUT_DIR"), "/version.rs"));
@@ -40,10 +40,12 @@ extern crate chrono;
 
 use std::fs;
 use std::fs::File;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::collections::BTreeMap;
-use std::net::{Ipv4Addr, Ipv6Addr};
-use std::net::ToSocketAddrs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::process;
+use std::sync::Arc;
 
 use chrono::{Duration};
 use mio::tcp::TcpListener;
@@ -54,12 +56,18 @@ use openssl::crypto::pkey::PKey;
 
 use trust_dns::logger;
 use trust_dns::version;
-use trust_dns::authority::{Authority, Catalog, Journal};
-use trust_dns::config::{Config, ZoneConfig};
+use trust_dns::authority::{Authority, Catalog, Forwarder, Journal, QueryPolicy, TransferPolicy, UpdatePolicy, ZoneType};
+use trust_dns::client::{ResponseCache, SharedCache};
+use trust_dns::config::{Config, Protocol, ZoneConfig};
+use trust_dns::dnstap::Dnstap;
+use trust_dns::resolver::Recursor;
 use trust_dns::serialize::txt::Parser;
 use trust_dns::rr::Name;
-use trust_dns::server::Server;
-use trust_dns::rr::dnssec::{Algorithm, Signer};
+use trust_dns::server::{FileSink, QueryLog, RateLimiter, Server, StderrSink, ThreadedServer, ZoneCheckpointer, ZoneNotifier, ZoneRefresher};
+use trust_dns::rr::dnssec::Signer;
+
+// how often the updated zones are written back to their zone files
+const CHECKPOINT_INTERVAL_MS: u64 = 3_600_000;
 
 // the Docopt usage string.
 //  http://docopt.org
@@ -88,38 +96,17 @@ struct Args {
   pub flag_port: Option<u16>,
 }
 
-fn load_zone(zone_dir: &Path, zone: &ZoneConfig) -> Result<Authority, String> {
+fn load_zone(zone_dir: &Path, zone: &ZoneConfig, config: &Config) -> Result<Authority, String> {
   let zone_name: Name = zone.get_zone().expect("bad zone name");
   let zone_path: PathBuf = zone_dir.to_owned().join(zone.get_file());
   let journal_path: PathBuf = zone_path.with_extension(".jrnl");
-  let key_path: PathBuf = zone_path.with_extension(".key");
+  let key_path: PathBuf = zone_dir.to_owned().join(zone.get_key_file());
 
   // load the zone
-  let mut authority = if zone.is_update_allowed() && journal_path.exists() {
-    info!("recovering zone from journal: {:?}", journal_path);
-    let journal = match Journal::from_file(&journal_path) {
-      Ok(j) => j,
-      Err(e) => return Err(format!("error opening journal: {:?}: {}", journal_path, e)),
-    };
-
-    let mut authority = Authority::new(zone_name.clone(), BTreeMap::new(),  zone.get_zone_type(), zone.is_update_allowed());
-    if let Err(e) = authority.recover_with_journal(&journal) {
-      return Err(format!("error recovering from journal: {}", e))
-    }
-
-    authority.journal(journal);
-    info!("recovered zone: {}", zone_name);
-
-    authority
-  } else if zone_path.exists() {
+  let mut authority = if zone_path.exists() {
     info!("loading zone file: {:?}", zone_path);
 
-    let zone_file = match File::open(&zone_path) {
-      Ok(f) => f,
-      Err(e) => return Err(format!("error opening zone file: {:?}: {}", zone_path, e)),
-    };
-
-    let mut authority: Authority = match Parser::parse_file(zone_file, Some(zone_name.clone()), zone.get_zone_type(), zone.is_update_allowed()) {
+    let mut authority: Authority = match Parser::parse_path(&zone_path, Some(zone_name.clone()), zone.get_zone_type(), zone.is_update_allowed()) {
       Ok(a) => a,
       Err(e) => return Err(format!("error reading zone: {:?}: {}", zone_path, e)),
     };
@@ -129,23 +116,90 @@ fn load_zone(zone_dir: &Path, zone: &ZoneConfig) -> Result<Authority, String> {
       info!("enabling journal: {:?}", journal_path);
       let journal = match Journal::from_file(&journal_path) {
         Ok(j) => j,
-        Err(e) => return Err(format!("error creating journal {:?}: {}", journal_path, e)),
+        Err(e) => return Err(format!("error opening journal {:?}: {}", journal_path, e)),
       };
 
       authority.journal(journal);
 
-      // preserve to the new journal, i.e. we just loaded the zone from disk, start the journal
-      if let Err(e) = authority.persist_to_journal() {
-        return Err(format!("error persisting to journal {:?}: {}", journal_path, e))
+      // the journal holds the updates since the zone file was last checkpointed
+      if let Err(e) = authority.replay_journal() {
+        return Err(format!("error replaying journal {:?}: {}", journal_path, e))
       }
     }
 
     info!("loaded zone: {}", zone_name);
     authority
+  } else if zone.is_update_allowed() && journal_path.exists() {
+    // journals written before checkpoints hold the entire zone
+    info!("recovering zone from journal: {:?}", journal_path);
+    let journal = match Journal::from_file(&journal_path) {
+      Ok(j) => j,
+      Err(e) => return Err(format!("error opening journal: {:?}: {}", journal_path, e)),
+    };
+
+    let mut authority = Authority::new(zone_name.clone(), BTreeMap::new(),  zone.get_zone_type(), zone.is_update_allowed());
+    if let Err(e) = authority.recover_with_journal(&journal) {
+      return Err(format!("error recovering from journal: {}", e))
+    }
+
+    authority.journal(journal);
+    info!("recovered zone: {}", zone_name);
+
+    authority
+  } else if zone.get_zone_type() == ZoneType::Slave {
+    // the zone will be transferred from the primaries
+    info!("secondary zone without a zone file: {}", zone_name);
+    Authority::new(zone_name.clone(), BTreeMap::new(), ZoneType::Slave, false)
   } else {
     return Err(format!("no zone file defined at: {:?}", zone_path))
   };
 
+  // a zone which would be answered wrongly is not served, secondary zones are checked once they
+  //  are transferred
+  if !(zone.get_zone_type() == ZoneType::Slave && authority.get_records().is_empty()) {
+    let problems = authority.validate();
+    if !problems.is_empty() {
+      for problem in &problems {
+        error!("{}: {}", zone_name, problem);
+      }
+      return Err(format!("{} problems in zone", problems.len()))
+    }
+  }
+
+  authority.set_serial_policy(zone.get_serial_policy());
+  authority.set_primaries(zone.get_primaries());
+  authority.set_also_notify(zone.get_also_notify());
+
+  let allow_transfer = match zone.get_allow_transfer() {
+    Ok(networks) => networks,
+    Err(e) => return Err(format!("bad allow_transfer for {}: {}", zone_name, e)),
+  };
+  let transfer_keys = zone.get_transfer_keys();
+  if !allow_transfer.is_empty() || !transfer_keys.is_empty() {
+    let mut policy = TransferPolicy::new();
+    for network in allow_transfer {
+      policy.allow_network(network);
+    }
+    for key_name in transfer_keys {
+      // the key names were validated with the config
+      policy.tsig_key(config.get_tsig_key(&key_name).unwrap());
+    }
+    authority.set_transfer_policy(policy);
+  }
+
+  let allow_update_from = zone.get_allow_update_from();
+  let update_keys = zone.get_update_keys();
+  if !allow_update_from.is_empty() || !update_keys.is_empty() {
+    let mut policy = UpdatePolicy::new();
+    for network in allow_update_from {
+      policy.allow_network(network);
+    }
+    for key_name in update_keys {
+      policy.tsig_key(config.get_tsig_key(&key_name).unwrap());
+    }
+    authority.set_update_policy(policy);
+  }
+
   // load any keys for the Zone, if it is a dynamic update zone, then keys are required
   if zone.is_dnssec_enabled() {
     let pkey = if key_path.exists() {
@@ -182,15 +236,145 @@ fn load_zone(zone_dir: &Path, zone: &ZoneConfig) -> Result<Authority, String> {
     };
 
     // add the key to the zone
-    // TODO: allow the duration of signatutes to be customized
-    let signer = Signer::new(Algorithm::RSASHA256, pkey, authority.get_origin().clone(), Duration::weeks(52));
+    let signer = Signer::new(zone.get_algorithm(), pkey, authority.get_origin().clone(),
+                             Duration::days(zone.get_signature_validity_days() as i64));
     authority.add_secure_key(signer);
-  }
 
+    if let Some(nsec3param) = zone.get_nsec3param() {
+      authority.set_nsec3param(nsec3param);
+    }
+
+    // the zone is served signed, with the NSEC or NSEC3 records
+    info!("signing zone: {}", zone_name);
+    authority.sign_zone();
+  }
 
   Ok(authority)
 }
 
+/// The catalog of the zones, forwarders and recursion of the config
+fn new_catalog(config: &Config, zone_dir: &Path) -> Catalog {
+  let mut catalog: Catalog = Catalog::new();
+  for zone in config.get_zones() {
+    // the names were validated with the config
+    let zone_name = zone.get_zone().unwrap();
+
+    match load_zone(zone_dir, zone, config) {
+      Ok(authority) => catalog.upsert(zone_name, authority),
+      Err(error) => error!("could not load zone {}: {}", zone_name, error),
+    }
+  }
+
+  catalog.set_query_policy(config.get_query_policy());
+
+  if !config.get_forwarders().is_empty() {
+    let cache = SharedCache::new(ResponseCache::new(4096));
+    for forwarder in config.get_forwarders() {
+      match Forwarder::with_name_servers(forwarder.get_domain(), &forwarder.get_name_servers(), cache.clone()) {
+        Ok(f) => catalog.add_forwarder(f),
+        Err(e) => error!("could not forward {}: {}", forwarder.get_domain(), e),
+      }
+    }
+  }
+
+  if config.is_recursion_enabled() {
+    catalog.set_recursor(Some(Box::new(Recursor::default())));
+  }
+
+  catalog
+}
+
+/// A server answering from its own catalog, with the limits and logging of the config
+fn new_server(config: &Config, zone_dir: &Path, dnstap: Option<Arc<Dnstap>>) -> Server {
+  let mut server = Server::new(new_catalog(config, zone_dir));
+
+  if let Some(limits) = config.get_limits() {
+    server.set_max_tcp_connections(limits.get_max_tcp_connections());
+    server.set_tcp_idle_timeout_ms(limits.get_tcp_idle_timeout_ms());
+    server.set_max_tcp_requests(limits.get_max_tcp_requests());
+
+    if let Some(responses_per_second) = limits.get_responses_per_second() {
+      let mut rate_limiter = RateLimiter::new();
+      rate_limiter.responses_per_second(responses_per_second);
+      if let Some(burst) = limits.get_rate_limit_burst() { rate_limiter.burst(burst); }
+      if let Some(slip) = limits.get_rate_limit_slip() { rate_limiter.slip(slip); }
+      server.set_rate_limiter(rate_limiter);
+    }
+  }
+
+  if let Some(query_log_path) = config.get_logging().and_then(|logging| logging.get_query_log()) {
+    let mut query_log = QueryLog::new();
+    if query_log_path == "stderr" {
+      query_log.add_sink(Box::new(StderrSink));
+      server.set_query_log(query_log);
+    } else {
+      match FileSink::open(Path::new(query_log_path)) {
+        Ok(sink) => { query_log.add_sink(Box::new(sink)); server.set_query_log(query_log); },
+        Err(e) => error!("could not open the query log {}: {}", query_log_path, e),
+      }
+    }
+  }
+
+  if let Some(dnstap) = dnstap {
+    server.set_dnstap(dnstap);
+  }
+
+  // keep the secondary zones up to date with their primaries
+  if config.get_zones().iter().any(|zone| zone.get_zone_type() == ZoneType::Slave) {
+    server.set_zone_refresher(ZoneRefresher::new(), 1000);
+  }
+
+  // NOTIFY the secondaries of zones which change, or are configured to be notified
+  if config.get_zones().iter().any(|zone| zone.is_update_allowed() || !zone.get_also_notify().is_empty()) {
+    server.set_zone_notifier(ZoneNotifier::new(), 1000);
+  }
+
+  // write the updated zones back to their zone files, which bounds the size of their journals
+  if config.get_zones().iter().any(|zone| zone.is_update_allowed()) {
+    let mut checkpointer = ZoneCheckpointer::new();
+    for zone in config.get_zones().iter().filter(|zone| zone.is_update_allowed()) {
+      if let Ok(origin) = zone.get_zone() {
+        checkpointer.watch(origin, zone_dir.join(zone.get_file()));
+      }
+    }
+
+    server.set_zone_checkpointer(checkpointer, CHECKPOINT_INTERVAL_MS);
+  }
+
+  server
+}
+
+/// The addresses, protocols and policies of the listeners of the config, or of the
+///  `listen_addrs_ipv4` and `listen_addrs_ipv6` if there are none
+fn listen_addrs(config: &Config, port: Option<u16>) -> Vec<(SocketAddr, Protocol, Option<QueryPolicy>)> {
+  let listen_port: u16 = port.unwrap_or(config.get_listen_port());
+
+  if !config.get_listeners().is_empty() {
+    return config.get_listeners().iter().map(|listener| {
+      let addr = SocketAddr::new(listener.get_addr(), port.or(listener.get_port()).unwrap_or(listen_port));
+      (addr, listener.get_protocol(), listener.get_query_policy())
+    }).collect()
+  }
+
+  let mut addrs: Vec<IpAddr> = config.get_listen_addrs_ipv4().into_iter().map(IpAddr::V4).collect();
+  if addrs.is_empty() { addrs.push(IpAddr::V4(Ipv4Addr::new(0,0,0,0))); }
+  let mut addrs_v6: Vec<IpAddr> = config.get_listen_addrs_ipv6().into_iter().map(IpAddr::V6).collect();
+  if addrs_v6.is_empty() { addrs_v6.push(IpAddr::V6(Ipv6Addr::new(0,0,0,0, 0,0,0,0))); }
+  addrs.extend(addrs_v6);
+
+  addrs.into_iter().map(|addr| (SocketAddr::new(addr, listen_port), Protocol::Both, None)).collect()
+}
+
+#[cfg(unix)]
+fn connect_dnstap(path: &Path) -> io::Result<Dnstap> {
+  Dnstap::connect(path)
+}
+
+#[cfg(not(unix))]
+fn connect_dnstap(_: &Path) -> io::Result<Dnstap> {
+  Err(io::Error::new(io::ErrorKind::Other, "dnstap sockets are only supported on unix"))
+}
+
 /// Main method for running the named server.
 ///
 /// `Note`: Tries to avoid panics, in favor of always starting.
@@ -214,55 +398,85 @@ pub fn main() {
 
   let config_path = Path::new(args.flag_config.as_ref().map(|s| s as &str).unwrap_or("/etc/named.toml"));
   info!("loading configuration from: {:?}", config_path);
-  let config = Config::read_config(config_path).unwrap();
-  let zone_dir: &Path = args.flag_zonedir.as_ref().map(|s| Path::new(s)).unwrap_or(config.get_directory());
-
-  let mut catalog: Catalog = Catalog::new();
-  // configure our server based on the config_path
-  for zone in config.get_zones() {
-    let zone_name = zone.get_zone().expect(&format!("bad zone name in {:?}", config_path));
-
-    match load_zone(zone_dir, zone) {
-      Ok(authority) => catalog.upsert(zone_name, authority),
-      Err(error) => error!("could not load zone {}: {}", zone_name, error),
+  let config = match Config::read_config(config_path) {
+    Ok(config) => config,
+    Err(e) => {
+      error!("could not load the configuration {:?}: {}", config_path, e);
+      process::exit(1);
+    },
+  };
+  let zone_dir: PathBuf = args.flag_zonedir.as_ref().map(|s| PathBuf::from(s)).unwrap_or(config.get_directory().to_owned());
+
+  let mut udp_sockets: Vec<(UdpSocket, Option<QueryPolicy>)> = Vec::new();
+  let mut tcp_listeners: Vec<(TcpListener, Option<QueryPolicy>)> = Vec::new();
+  for (addr, protocol, policy) in listen_addrs(&config, args.flag_port) {
+    if protocol != Protocol::Tcp {
+      info!("listening for UDP on {}", addr);
+      udp_sockets.push((UdpSocket::bound(&addr).unwrap_or_else(|e| panic!("could not bind UDP {}: {}", addr, e)), policy.clone()));
+    }
+    if protocol != Protocol::Udp {
+      info!("listening for TCP on {}", addr);
+      tcp_listeners.push((TcpListener::bind(&addr).unwrap_or_else(|e| panic!("could not bind TCP {}: {}", addr, e)), policy));
     }
   }
 
-  // TODO support all the IPs asked to listen on...
-  let listen_addr_v4: Ipv4Addr = *config.get_listen_addrs_ipv4().first().unwrap_or(&Ipv4Addr::new(0,0,0,0));
-  let listen_addr_v6: Ipv6Addr = *config.get_listen_addrs_ipv6().first().unwrap_or(&Ipv6Addr::new(0,0,0,0, 0,0,0,0));
-  let listen_port: u16 = args.flag_port.unwrap_or(config.get_listen_port());
-
-  let addr_v4 = (listen_addr_v4, listen_port).to_socket_addrs().unwrap().next().unwrap();
-  let addr_v6 = (listen_addr_v6, listen_port).to_socket_addrs().unwrap().next().unwrap();
+  // dnstap is shared by the threads, its frames are written under a lock
+  let dnstap: Option<Arc<Dnstap>> = match config.get_logging() {
+    Some(logging) => {
+      let dnstap = if let Some(path) = logging.get_dnstap_file() {
+        Some((path, Dnstap::create(path)))
+      } else if let Some(path) = logging.get_dnstap_socket() {
+        Some((path, connect_dnstap(path)))
+      } else {
+        None
+      };
 
-  let udp_sockets: Vec<UdpSocket> = vec![UdpSocket::bound(&addr_v4).unwrap(),
-                                         UdpSocket::bound(&addr_v6).unwrap()];
-  let tcp_listeners: Vec<TcpListener> = vec![TcpListener::bind(&addr_v4).unwrap(),
-                                             TcpListener::bind(&addr_v6).unwrap()];
+      match dnstap {
+        Some((_, Ok(mut dnstap))) => { dnstap.version(Some(version().to_string())); Some(Arc::new(dnstap)) },
+        Some((path, Err(e))) => { error!("could not open dnstap {:?}: {}", path, e); None },
+        None => None,
+      }
+    },
+    None => None,
+  };
 
+  banner();
 
-  // now, run the server, based on the config
-  let mut server = Server::new(catalog);
+  let threads = config.get_threads();
+  let result = if threads == 1 {
+    let mut server = new_server(&config, &zone_dir, dnstap);
+    for (udp_socket, policy) in udp_sockets {
+      match policy {
+        Some(policy) => server.register_socket_with_policy(udp_socket, policy),
+        None => server.register_socket(udp_socket),
+      }
+    }
+    for (tcp_listener, policy) in tcp_listeners {
+      match policy {
+        Some(policy) => server.register_listener_with_policy(tcp_listener, policy),
+        None => server.register_listener(tcp_listener),
+      }
+    }
 
-  for udp_socket in udp_sockets {
-    info!("listening for UDP on {:?}", udp_socket);
-    server.register_socket(udp_socket);
-  }
+    server.listen()
+  } else {
+    if config.get_zones().iter().any(|zone| zone.is_update_allowed() || zone.get_zone_type() == ZoneType::Slave) {
+      warn!("zones which allow updates, or are Slave zones, will differ between the {} threads", threads);
+    }
 
-  for tcp_listener in tcp_listeners {
-    info!("listening for TCP on {:?}", tcp_listener);
-    server.register_listener(tcp_listener);
-  }
+    // the listener policies were rejected with the config, each thread builds its own catalog
+    let config = Arc::new(config);
+    ThreadedServer::spawn(threads,
+                          udp_sockets.into_iter().map(|(socket, _)| socket).collect(),
+                          tcp_listeners.into_iter().map(|(listener, _)| listener).collect(),
+                          move |_| new_server(&config, &zone_dir, dnstap.clone()))
+                  .and_then(|server| server.join())
+  };
 
-  banner();
-  if let Err(e) = server.listen() {
+  if let Err(e) = result {
     error!("failed to listen: {}", e);
   }
 
-  //let mut server = Server::new((listen_addr, listen_port), catalog).unwrap();
-  //server.listen().unwrap();
-
   // we're exiting for some reason...
   info!("Trust-DNS {} stopping", trust_dns::version());
 }
@@ -16,6 +16,8 @@
 
 //! Basic protocol message for DNS
 
+use std::cmp;
+use std::fmt;
 use std::fmt::Debug;
 
 use super::{MessageType, Header, Query, Edns, OpCode, ResponseCode};
@@ -23,7 +25,7 @@ use ::rr::resource::Record;
 use ::rr::domain::Name;
 use ::rr::{RData, RecordType, DNSClass};
 use ::rr::rdata::SIG;
-use ::serialize::binary::{BinEncoder, BinDecoder, BinSerializable, EncodeMode};
+use ::serialize::binary::{BinEncoder, BinDecoder, BinSerializable, DecodeMode, EncodeMode};
 use ::error::*;
 use ::rr::dnssec::Signer;
 
@@ -69,7 +71,7 @@ use ::rr::dnssec::Signer;
 ///
 /// By default Message is a Query. Use the Message::as_update() to create and update, or
 ///  Message::new_update()
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Message {
   header: Header, queries: Vec<Query>, answers: Vec<Record>, name_servers: Vec<Record>,
    additionals: Vec<Record>, sig0: Vec<Record>, edns: Option<Edns>
@@ -97,6 +99,70 @@ impl Message {
     truncated
   }
 
+  /// Truncates the message to fit in max_size bytes, e.g. the max payload of a UDP request, by
+  ///  removing whole RRSets, from the end of the additional section first, then the authority and
+  ///  the answer sections. The EDNS and SIG0 records are kept.
+  ///
+  /// Omitting records from the additional section does not set TC, removing any of the authority
+  ///  or answer sections does.
+  ///
+  /// [RFC 2181](https://tools.ietf.org/html/rfc2181), Clarifications to the DNS Specification, July 1997
+  ///
+  /// ```text
+  /// 9. The TC (truncated) header bit
+  ///
+  ///    The TC bit should be set in responses only when an RRSet is required
+  ///    as a part of the response, but could not be included in its entirety.
+  ///    The TC bit should not be set merely because some extra information
+  ///    could have been included, but there was insufficient room.  This
+  ///    includes the results of additional section processing.
+  /// ```
+  ///
+  /// # Return value
+  ///
+  /// The message, which is only larger than max_size if the header, queries, EDNS and SIG0 alone
+  ///  do not fit.
+  pub fn truncate_to(&self, max_size: usize) -> EncodeResult<Self> {
+    let mut truncated: Message = self.clone();
+
+    while try!(truncated.encoded_len()) > max_size {
+      if Self::remove_last_rr_set(&mut truncated.additionals) { continue }
+
+      truncated.truncated(true);
+      if Self::remove_last_rr_set(&mut truncated.name_servers) { continue }
+      if Self::remove_last_rr_set(&mut truncated.answers) { continue }
+      break
+    }
+
+    Ok(truncated)
+  }
+
+  /// removes the records of the last RRSet in the section, along with the RRSIGs which cover it,
+  ///  returns false if the section is empty
+  fn remove_last_rr_set(records: &mut Vec<Record>) -> bool {
+    let last = if let Some(record) = records.last() { Self::rr_set_key(record) } else { return false };
+    while records.last().map_or(false, |record| Self::rr_set_key(record) == last) {
+      records.pop();
+    }
+
+    true
+  }
+
+  /// the name, class and type of the RRSet of the record, for RRSIGs the type covered
+  fn rr_set_key(record: &Record) -> (Name, DNSClass, RecordType) {
+    let rr_type = if let &RData::SIG(ref sig) = record.get_rdata() { sig.get_type_covered() } else { record.get_rr_type() };
+    (record.get_name().clone(), record.get_dns_class(), rr_type)
+  }
+
+  fn encoded_len(&self) -> EncodeResult<usize> {
+    let mut buf: Vec<u8> = Vec::new();
+    {
+      let mut encoder = BinEncoder::new(&mut buf);
+      try!(self.emit(&mut encoder));
+    }
+    Ok(buf.len())
+  }
+
   pub fn id(&mut self, id: u16) -> &mut Self { self.header.id(id); self }
   pub fn message_type(&mut self, message_type: MessageType) -> &mut Self { self.header.message_type(message_type); self }
   pub fn op_code(&mut self, op_code: OpCode) -> &mut Self { self.header.op_code(op_code); self }
@@ -138,8 +204,9 @@ impl Message {
     self.edns = Some(edns);
   }
 
+  /// adds a SIG(0) or TSIG record, which are the final records of the message
   pub fn add_sig0(&mut self, record: Record) -> &mut Self {
-    assert_eq!(RecordType::SIG, record.get_rr_type());
+    assert!(record.get_rr_type() == RecordType::SIG || record.get_rr_type() == RecordType::TSIG);
     self.sig0.push(record);
     self
   }
@@ -244,6 +311,51 @@ impl Message {
   /// the version as defined in the EDNS record
   pub fn get_version(&self) -> u8 { self.edns.as_ref().map_or(0, |e|e.get_version()) }
 
+  /// The negative TTL of [RFC 2308](https://tools.ietf.org/html/rfc2308#section-5) for NXDOMAIN and
+  ///  NODATA responses, i.e. the minimum of the TTL and MINIMUM field of the SOA in the authority
+  ///  section. Returns None if this is not a negative response or there is no SOA.
+  pub fn get_negative_ttl(&self) -> Option<u32> {
+    match self.get_response_code() {
+      ResponseCode::NXDomain => (),
+      ResponseCode::NoError if self.answers.is_empty() => (),
+      _ => return None,
+    }
+
+    self.name_servers.iter().filter_map(|r| {
+      if let RData::SOA(ref soa) = *r.get_rdata() {
+        Some(cmp::min(r.get_ttl(), soa.get_minimum()))
+      } else {
+        None
+      }
+    }).next()
+  }
+
+  /// Strict validation that this message is a response to the `request`: the ids must match,
+  ///  the QR bit must be set, and for queries the question section must equal that of the request.
+  ///
+  /// Truncated and error responses are allowed to omit the question section.
+  pub fn is_response_to(&self, request: &Message) -> bool {
+    if self.get_id() != request.get_id() {
+      debug!("response id {} does not match request {}", self.get_id(), request.get_id());
+      return false
+    }
+
+    if self.get_message_type() != MessageType::Response {
+      debug!("message {} is not a response", self.get_id());
+      return false
+    }
+
+    if request.get_op_code() != OpCode::Query { return true }
+    if self.queries.is_empty() && (self.is_truncated() || self.get_response_code() != ResponseCode::NoError) { return true }
+
+    if self.queries != request.queries {
+      debug!("response questions {:?} do not match request {:?}", self.queries, request.queries);
+      return false
+    }
+
+    true
+  }
+
   /// [RFC 2535, Domain Name System Security Extensions, March 1999](https://tools.ietf.org/html/rfc2535#section-4)
   ///
   /// ```text
@@ -258,7 +370,15 @@ impl Message {
   /// # Return value
   ///
   /// The sig0, i.e. signed record, for verifying the sending and package integrity
-  fn get_sig0(&self) -> &[Record] { &self.sig0 }
+  pub fn get_sig0(&self) -> &[Record] { &self.sig0 }
+
+  /// [RFC 2845](https://tools.ietf.org/html/rfc2845), Secret Key Transaction Authentication for DNS, May 2000
+  ///
+  /// The TSIG record of the message, if it was signed with TSIG. Messages are signed and verified
+  ///  in their serialized form, see `TsigKey`.
+  pub fn get_tsig(&self) -> Option<&Record> {
+    self.sig0.iter().find(|record| record.get_rr_type() == RecordType::TSIG)
+  }
 
   // TODO only necessary in tests, should it be removed?
   /// this is necessary to match the counts in the header from the record sections
@@ -288,21 +408,26 @@ impl Message {
   }
 
   fn read_records(decoder: &mut BinDecoder, count: usize, is_additional: bool) -> DecodeResult<(Vec<Record>, Option<Edns>, Vec<Record>)> {
-    let mut records: Vec<Record> = Vec::with_capacity(count);
+    // the count is not trusted to allocate, each record is at least 11 bytes
+    let mut records: Vec<Record> = Vec::with_capacity(cmp::min(count, decoder.len() / 11));
     let mut edns: Option<Edns> = None;
     let mut sig0s: Vec<Record> = Vec::with_capacity(if is_additional { 1 } else { 0 });
 
     // sig0 must be last, once this is set, disable.
     let mut saw_sig0 = false;
     for _ in 0 .. count {
-      let record = try!(Record::read(decoder));
+      let record = match try!(Self::read_record(decoder)) {
+        Some(record) => record,
+        None => continue,
+      };
 
       if !is_additional {
         if saw_sig0 { return Err(DecodeErrorKind::Message("sig0 must be final resource record").into()) } // SIG0 must be last
         records.push(record)
       } else {
         match record.get_rr_type() {
-          RecordType::SIG => {
+          // TSIG, like SIG(0), must be the final record
+          RecordType::SIG | RecordType::TSIG => {
             saw_sig0 = true;
             sig0s.push(record);
           },
@@ -322,6 +447,31 @@ impl Message {
     Ok((records, edns, sig0s))
   }
 
+  /// Reads the next record, in the Lenient mode a record which can not be decoded is skipped and
+  ///  recorded as a warning, one which can not even be skipped ends the message
+  fn read_record(decoder: &mut BinDecoder) -> DecodeResult<Option<Record>> {
+    let start = decoder.index();
+    let error = match Record::read(decoder) {
+      Ok(record) => return Ok(Some(record)),
+      Err(error) => if decoder.mode() == DecodeMode::Strict { return Err(error) } else { error },
+    };
+
+    // nothing is left of the message, e.g. it was ended by a record which could not be skipped
+    if start == decoder.index() + decoder.len() { return Ok(None) }
+
+    try!(decoder.seek(start));
+    let end = start + decoder.len();
+    if Record::skip(decoder).is_ok() {
+      warn!("skipped a record which could not be decoded: {}", error);
+    } else {
+      warn!("could not decode a record, ending the message: {}", error);
+      try!(decoder.seek(end));
+    }
+
+    decoder.add_warning(error);
+    Ok(None)
+  }
+
   fn emit_records(encoder: &mut BinEncoder, records: &Vec<Record>) -> EncodeResult {
     for r in records {
       try!(r.emit(encoder));
@@ -429,7 +579,8 @@ impl BinSerializable<Message> for Message {
 
     // get the questions
     let count = header.get_query_count() as usize;
-    let mut queries = Vec::with_capacity(count);
+    // the count is not trusted to allocate, each query is at least 5 bytes
+    let mut queries = Vec::with_capacity(cmp::min(count, decoder.len() / 5));
     for _ in 0 .. count {
       queries.push(try!(Query::read(decoder)));
     }
@@ -485,6 +636,155 @@ impl BinSerializable<Message> for Message {
   }
 }
 
+/// The message as it is printed by dig, the header, the EDNS options and then each section which
+///  has records, with the records in their presentation format
+///
+/// ```text
+/// ;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 1234
+/// ;; flags: qr aa rd; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 0
+///
+/// ;; QUESTION SECTION:
+/// ;www.example.com.		IN	A
+///
+/// ;; ANSWER SECTION:
+/// www.example.com.	3600	IN	A	192.0.2.1
+/// ```
+impl fmt::Display for Message {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    try!(writeln!(f, ";; ->>HEADER<<- opcode: {}, status: {}, id: {}", self.get_op_code(), self.get_response_code(),
+                  self.get_id()));
+
+    let flags: Vec<&str> = [(self.get_message_type() == MessageType::Response, "qr"), (self.is_authoritative(), "aa"),
+                            (self.is_truncated(), "tc"), (self.is_recursion_desired(), "rd"), (self.is_recursion_available(), "ra"),
+                            (self.is_authentic_data(), "ad"), (self.is_checking_disabled(), "cd")]
+                            .iter().filter(|&&(set, _)| set).map(|&(_, flag)| flag).collect();
+
+    // an update has the zone, prerequisite and update sections, RFC 2136
+    let is_update = self.get_op_code() == OpCode::Update;
+    let names = if is_update { ["ZONE", "PREREQ", "UPDATE", "ADDITIONAL"] } else { ["QUERY", "ANSWER", "AUTHORITY", "ADDITIONAL"] };
+    let additional_count = self.additionals.len() + self.sig0.len() + if self.edns.is_some() { 1 } else { 0 };
+    try!(writeln!(f, ";; flags: {}; {}: {}, {}: {}, {}: {}, {}: {}", flags.join(" "), names[0], self.queries.len(),
+                  names[1], self.answers.len(), names[2], self.name_servers.len(), names[3], additional_count));
+
+    if let Some(edns) = self.get_edns() {
+      try!(write!(f, "\n;; OPT PSEUDOSECTION:\n; EDNS: version: {}, flags:{}; udp: {}\n", edns.get_version(),
+                  if edns.is_dnssec_ok() { " do" } else { "" }, edns.get_max_payload()));
+    }
+
+    if !self.queries.is_empty() {
+      try!(write!(f, "\n;; {} SECTION:\n", if is_update { "ZONE" } else { "QUESTION" }));
+      for query in &self.queries {
+        try!(writeln!(f, ";{}", query));
+      }
+    }
+
+    let sections = [(if is_update { "PREREQUISITE" } else { "ANSWER" }, &self.answers),
+                    (if is_update { "UPDATE" } else { "AUTHORITY" }, &self.name_servers),
+                    ("ADDITIONAL", &self.additionals)];
+    for &(name, records) in &sections {
+      // the SIG(0) and TSIG records are at the end of the additional section
+      let sig0: &[Record] = if name == "ADDITIONAL" { &self.sig0 } else { &[] };
+      if records.is_empty() && sig0.is_empty() { continue }
+
+      try!(write!(f, "\n;; {} SECTION:\n", name));
+      for record in records.iter().chain(sig0) {
+        try!(writeln!(f, "{}", record));
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[test]
+fn test_is_response_to() {
+  let mut query = Query::new();
+  query.name(Name::parse("www.example.com.", None).unwrap());
+
+  let mut request = Message::new();
+  request.id(10).message_type(MessageType::Query).add_query(query.clone());
+
+  let mut response = Message::new();
+  response.id(10).message_type(MessageType::Response).add_query(query.clone());
+  assert!(response.is_response_to(&request));
+
+  response.id(11);
+  assert!(!response.is_response_to(&request));
+
+  response.id(10).message_type(MessageType::Query);
+  assert!(!response.is_response_to(&request));
+
+  let mut other = Message::new();
+  query.name(Name::parse("example.com.", None).unwrap());
+  other.id(10).message_type(MessageType::Response).add_query(query);
+  assert!(!other.is_response_to(&request));
+}
+
+#[test]
+fn test_negative_ttl() {
+  use ::rr::rdata::SOA;
+
+  let origin = Name::parse("example.com.", None).unwrap();
+  let mut soa = Record::with(origin.clone(), RecordType::SOA, 3600);
+  soa.rdata(RData::SOA(SOA::new(origin.clone(), origin.clone(), 1, 2, 3, 4, 60)));
+
+  let mut message = Message::new();
+  message.message_type(MessageType::Response).response_code(ResponseCode::NXDomain).add_name_server(soa);
+  assert_eq!(message.get_negative_ttl(), Some(60));
+
+  message.response_code(ResponseCode::ServFail);
+  assert_eq!(message.get_negative_ttl(), None);
+}
+
+#[test]
+fn test_truncate_to() {
+  use std::net::Ipv4Addr;
+
+  let a = |name: &str, octet: u8| {
+    let mut record = Record::with(Name::parse(name, None).unwrap(), RecordType::A, 86400);
+    record.rdata(RData::A(Ipv4Addr::new(192,0,2,octet)));
+    record
+  };
+  let mut ns = Record::with(Name::parse("example.com.", None).unwrap(), RecordType::NS, 86400);
+  ns.rdata(RData::NS(Name::parse("ns1.example.com.", None).unwrap()));
+
+  let mut message = Message::new();
+  message.id(10).message_type(MessageType::Response).add_query(Query::new());
+  message.add_answer(a("www.example.com.", 1)).add_answer(a("www.example.com.", 2));
+  message.add_name_server(ns);
+  message.add_additional(a("ns1.example.com.", 3)).add_additional(a("ns1.example.com.", 4)).add_additional(a("ns2.example.com.", 5));
+
+  // it fits
+  let len = message.encoded_len().unwrap();
+  assert_eq!(message.truncate_to(len).unwrap(), message);
+
+  // the last RRSet of the additional section is removed first, without setting TC
+  let truncated = message.truncate_to(len - 1).unwrap();
+  assert_eq!(truncated.get_additional(), &message.get_additional()[..2]);
+  assert!(!truncated.is_truncated());
+
+  let mut no_additional = message.clone();
+  no_additional.additionals.clear();
+  let len = no_additional.encoded_len().unwrap();
+  let truncated = message.truncate_to(len).unwrap();
+  assert!(truncated.get_additional().is_empty());
+  assert_eq!(truncated.get_name_servers().len(), 1);
+  assert!(!truncated.is_truncated());
+
+  // then the authority, which sets TC, the answer RRSet is kept whole
+  let truncated = message.truncate_to(len - 1).unwrap();
+  assert!(truncated.get_name_servers().is_empty());
+  assert_eq!(truncated.get_answers(), message.get_answers());
+  assert!(truncated.is_truncated());
+  assert!(truncated.encoded_len().unwrap() <= len - 1);
+
+  // and finally the answers
+  let truncated = message.truncate_to(truncated.encoded_len().unwrap() - 1).unwrap();
+  assert!(truncated.get_answers().is_empty());
+  assert_eq!(truncated.get_queries().len(), 1);
+  assert!(truncated.is_truncated());
+}
+
 #[test]
 fn test_emit_and_read_header() {
   let mut message = Message::new();
@@ -572,3 +872,70 @@ fn test_legit_message() {
 
   assert_eq!(message.get_id(), 4096);
 }
+
+#[test]
+fn test_read_lenient() {
+  use std::net::Ipv4Addr;
+
+  let name = Name::parse("www.example.com.", None).unwrap();
+  let mut message = Message::new();
+  message.add_query(Query::new().name(name.clone()).query_type(RecordType::A).query_class(DNSClass::IN).clone());
+  for address in &[Ipv4Addr::new(192,0,2,1), Ipv4Addr::new(192,0,2,2)] {
+    message.add_answer(Record::new().name(name.clone()).rr_type(RecordType::A).dns_class(DNSClass::IN).ttl(60).rdata(RData::A(*address)).clone());
+  }
+
+  let mut buffer: Vec<u8> = Vec::new();
+  message.emit(&mut BinEncoder::new(&mut buffer)).unwrap();
+
+  // the first answer, after the header and the query, is a pointer to the query name and a type,
+  //  which is made unknown
+  assert_eq!(&buffer[33..37], &[0xC0, 12, 0, 1]);
+  buffer[35] = 0xFF;
+
+  assert!(Message::read(&mut BinDecoder::new(&buffer)).is_err());
+  let mut decoder = BinDecoder::with_mode(&buffer, DecodeMode::Lenient);
+  let lenient = Message::read(&mut decoder).unwrap();
+  assert_eq!(lenient.get_answers(), &message.get_answers()[1..]);
+  assert_eq!(decoder.get_warnings().len(), 1);
+
+  // a record which is cut short ends the message
+  buffer.truncate(buffer.len() - 2);
+  let mut decoder = BinDecoder::with_mode(&buffer, DecodeMode::Lenient);
+  let truncated = Message::read(&mut decoder).unwrap();
+  assert_eq!(truncated.get_queries(), message.get_queries());
+  assert!(truncated.get_answers().is_empty());
+  assert_eq!(decoder.get_warnings().len(), 2);
+}
+
+#[test]
+fn test_display() {
+  use std::net::Ipv4Addr;
+
+  let name = Name::parse("www.example.com.", None).unwrap();
+  let mut message = Message::new();
+  message.id(1234).message_type(MessageType::Response).authoritative(true).recursion_desired(true).response_code(ResponseCode::NXDomain);
+  message.add_query(Query::new().name(name.clone()).query_type(RecordType::A).query_class(DNSClass::IN).clone());
+  message.add_answer(Record::new().name(name.clone()).rr_type(RecordType::A).dns_class(DNSClass::IN).ttl(3600)
+                                  .rdata(RData::A(Ipv4Addr::new(192,0,2,1))).clone());
+
+  let mut edns = Edns::new();
+  edns.set_max_payload(4096);
+  edns.set_dnssec_ok(true);
+  message.set_edns(edns);
+
+  assert_eq!(message.to_string(), ";; ->>HEADER<<- opcode: QUERY, status: NXDOMAIN, id: 1234\n\
+                                   ;; flags: qr aa rd; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 1\n\
+                                   \n\
+                                   ;; OPT PSEUDOSECTION:\n\
+                                   ; EDNS: version: 0, flags: do; udp: 4096\n\
+                                   \n\
+                                   ;; QUESTION SECTION:\n\
+                                   ;www.example.com.\t\tIN\tA\n\
+                                   \n\
+                                   ;; ANSWER SECTION:\n\
+                                   www.example.com.\t3600\tIN\tA\t192.0.2.1\n");
+
+  // the header of an update
+  message.op_code(OpCode::Update).response_code(ResponseCode::YXRRSet);
+  assert!(message.to_string().starts_with(";; ->>HEADER<<- opcode: UPDATE, status: YXRRSET, id: 1234\n"));
+}
@@ -0,0 +1,179 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A builder of the common messages, so that the header and the sections need not be set by hand
+
+use rand;
+
+use ::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode, UpdateMessage};
+use ::rr::{DNSClass, Name, Record, RecordType};
+
+/// Builds queries, responses, updates and notifies.
+///
+/// Each message starts with the header of its kind, e.g. a query has a random id and the `Query`
+///  op code, which may be changed before the message is built.
+///
+/// ```
+/// use trust_dns::op::{Edns, MessageBuilder, OpCode};
+/// use trust_dns::rr::{Name, RecordType};
+///
+/// let mut edns = Edns::new();
+/// edns.set_max_payload(1500);
+///
+/// let message = MessageBuilder::query(Name::parse("www.example.com.", None).unwrap(), RecordType::A)
+///                              .recursion_desired(true)
+///                              .edns(edns)
+///                              .build();
+///
+/// assert_eq!(message.get_op_code(), OpCode::Query);
+/// assert!(message.is_recursion_desired());
+/// assert_eq!(message.get_max_payload(), 1500);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageBuilder {
+  message: Message,
+}
+
+impl MessageBuilder {
+  /// A query of the records of the name and type, in the IN class
+  pub fn query(name: Name, query_type: RecordType) -> Self {
+    let mut query = Query::new();
+    query.name(name).query_type(query_type).query_class(DNSClass::IN);
+
+    Self::with_op_code(OpCode::Query, query)
+  }
+
+  /// A response to the request, with its id, op code and queries. Recursion desired and checking
+  ///  disabled are copied from the request, RFC 1035 and RFC 4035 3.1.6.
+  pub fn response(request: &Message) -> Self {
+    let mut message = Message::new();
+    message.id(request.get_id())
+           .message_type(MessageType::Response)
+           .op_code(request.get_op_code())
+           .recursion_desired(request.is_recursion_desired())
+           .checking_disabled(request.is_checking_disabled())
+           .add_all_queries(request.get_queries());
+
+    MessageBuilder{ message: message }
+  }
+
+  /// An update of the zone, RFC 2136. The prerequisites and the updates are added with
+  ///  `prerequisite()` and `update()`.
+  pub fn update(zone_origin: Name, dns_class: DNSClass) -> Self {
+    let mut zone = Query::new();
+    zone.name(zone_origin).query_class(dns_class).query_type(RecordType::SOA);
+
+    Self::with_op_code(OpCode::Update, zone)
+  }
+
+  /// A NOTIFY of a change to the zone, RFC 1996. The new SOA of the zone may be added as a hint
+  ///  with `answer()`.
+  pub fn notify(zone_origin: Name, dns_class: DNSClass) -> Self {
+    let mut query = Query::new();
+    query.name(zone_origin).query_class(dns_class).query_type(RecordType::SOA);
+
+    let mut builder = Self::with_op_code(OpCode::Notify, query);
+    builder.message.authoritative(true);
+    builder
+  }
+
+  fn with_op_code(op_code: OpCode, query: Query) -> Self {
+    let mut message = Message::new();
+    message.id(rand::random()).message_type(MessageType::Query).op_code(op_code);
+    message.add_query(query);
+
+    MessageBuilder{ message: message }
+  }
+
+  /// replaces the random id, e.g. to match a request
+  pub fn id(&mut self, id: u16) -> &mut Self { self.message.id(id); self }
+  pub fn authoritative(&mut self, authoritative: bool) -> &mut Self { self.message.authoritative(authoritative); self }
+  pub fn recursion_desired(&mut self, recursion_desired: bool) -> &mut Self { self.message.recursion_desired(recursion_desired); self }
+  pub fn recursion_available(&mut self, recursion_available: bool) -> &mut Self { self.message.recursion_available(recursion_available); self }
+  pub fn authentic_data(&mut self, authentic_data: bool) -> &mut Self { self.message.authentic_data(authentic_data); self }
+  pub fn checking_disabled(&mut self, checking_disabled: bool) -> &mut Self { self.message.checking_disabled(checking_disabled); self }
+  pub fn response_code(&mut self, response_code: ResponseCode) -> &mut Self { self.message.response_code(response_code); self }
+  /// the EDNS of the message, RFC 6891, e.g. to advertise a larger payload or set DNSSEC OK
+  pub fn edns(&mut self, edns: Edns) -> &mut Self { self.message.set_edns(edns); self }
+
+  pub fn answer(&mut self, record: Record) -> &mut Self { self.message.add_answer(record); self }
+  pub fn name_server(&mut self, record: Record) -> &mut Self { self.message.add_name_server(record); self }
+  pub fn additional(&mut self, record: Record) -> &mut Self { self.message.add_additional(record); self }
+  /// a prerequisite of an update, in the answer section, RFC 2136 2.4
+  pub fn prerequisite(&mut self, record: Record) -> &mut Self { self.message.add_pre_requisite(record); self }
+  /// an update of an update, in the authority section, RFC 2136 2.5
+  pub fn update_record(&mut self, record: Record) -> &mut Self { self.message.add_update(record); self }
+
+  /// the message, the builder may be used again e.g. for another message with a new id
+  pub fn build(&self) -> Message {
+    self.message.clone()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use ::op::*;
+  use ::rr::{DNSClass, Name, Record, RecordType, RData};
+
+  #[test]
+  fn test_query_and_response() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut edns = Edns::new();
+    edns.set_dnssec_ok(true);
+
+    let query = MessageBuilder::query(name.clone(), RecordType::AAAA).recursion_desired(true).checking_disabled(true).edns(edns).build();
+    assert_eq!(query.get_message_type(), MessageType::Query);
+    assert_eq!(query.get_op_code(), OpCode::Query);
+    assert!(query.is_recursion_desired());
+    assert!(query.get_edns().unwrap().is_dnssec_ok());
+    assert_eq!(query.get_queries().len(), 1);
+    assert_eq!(query.get_queries()[0].get_name(), &name);
+    assert_eq!(query.get_queries()[0].get_query_type(), RecordType::AAAA);
+    assert_eq!(query.get_queries()[0].get_query_class(), DNSClass::IN);
+
+    let record = Record::new().name(name.clone()).rr_type(RecordType::AAAA).dns_class(DNSClass::IN).ttl(300)
+                              .rdata(RData::AAAA("::1".parse().unwrap())).clone();
+    let response = MessageBuilder::response(&query).authoritative(true).answer(record.clone()).build();
+    assert_eq!(response.get_id(), query.get_id());
+    assert_eq!(response.get_message_type(), MessageType::Response);
+    assert!(response.is_recursion_desired());
+    assert!(response.is_checking_disabled());
+    assert!(response.is_authoritative());
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_queries(), query.get_queries());
+    assert_eq!(response.get_answers(), &[record]);
+  }
+
+  #[test]
+  fn test_update_and_notify() {
+    let origin = Name::parse("example.com.", None).unwrap();
+    let record = Record::new().name(Name::parse("new.example.com.", None).unwrap()).rr_type(RecordType::A).dns_class(DNSClass::IN).ttl(300)
+                              .rdata(RData::A("10.0.0.1".parse().unwrap())).clone();
+
+    let update = MessageBuilder::update(origin.clone(), DNSClass::IN).update_record(record.clone()).build();
+    assert_eq!(update.get_op_code(), OpCode::Update);
+    assert!(!update.is_recursion_desired());
+    assert_eq!(update.get_zones()[0].get_name(), &origin);
+    assert_eq!(update.get_zones()[0].get_query_type(), RecordType::SOA);
+    assert_eq!(update.get_updates(), &[record]);
+    assert!(update.get_pre_requisites().is_empty());
+
+    let notify = MessageBuilder::notify(origin.clone(), DNSClass::CH).build();
+    assert_eq!(notify.get_op_code(), OpCode::Notify);
+    assert!(notify.is_authoritative());
+    assert_eq!(notify.get_queries()[0].get_query_class(), DNSClass::CH);
+  }
+}
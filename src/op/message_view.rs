@@ -0,0 +1,393 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Borrowed views of a binary message, which decode nothing but the header, types and classes,
+//!  the names and rdata are slices of the receive buffer
+
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::fmt;
+use std::str;
+
+use ::error::*;
+use ::op::{Header, Message};
+use ::rr::{DNSClass, Name, Record, RecordType};
+use ::rr::domain::MAX_POINTERS;
+use ::serialize::binary::{BinDecoder, BinSerializable};
+
+/// A message which borrows the buffer it was received into.
+///
+/// Reading a `Message` allocates each label of each name and each rdata, reading a `MessageView`
+///  only allocates the lists of the queries and records of each section. The names may be compared
+///  and the rdata inspected without copying, and single records decoded with
+///  `RecordView::to_record()`, e.g. a server may route a request by its query before decoding it,
+///  or drop it without decoding it at all.
+///
+/// ```rust
+/// use trust_dns::op::{Message, MessageView, Query};
+/// use trust_dns::rr::{DNSClass, Name, RecordType};
+/// use trust_dns::serialize::binary::{BinEncoder, BinSerializable};
+///
+/// let mut message = Message::new();
+/// message.add_query(Query::new().name(Name::parse("www.example.com.", None).unwrap())
+///                               .query_type(RecordType::A).query_class(DNSClass::IN).clone());
+/// let mut buffer: Vec<u8> = Vec::new();
+/// message.emit(&mut BinEncoder::new(&mut buffer)).unwrap();
+///
+/// let view = MessageView::read(&buffer).unwrap();
+/// assert!(view.get_queries()[0].get_name().eq_name(&Name::parse("WWW.example.com.", None).unwrap()));
+/// assert_eq!(view.to_message().unwrap(), message);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageView<'a> {
+  buffer: &'a [u8],
+  header: Header,
+  queries: Vec<QueryView<'a>>,
+  answers: Vec<RecordView<'a>>,
+  name_servers: Vec<RecordView<'a>>,
+  additionals: Vec<RecordView<'a>>,
+}
+
+impl<'a> MessageView<'a> {
+  /// Reads the message from the buffer, checking the structure of each name, record and section,
+  ///  but not the rdata, see `RecordView::to_record()`
+  pub fn read(buffer: &'a [u8]) -> DecodeResult<Self> {
+    let mut decoder = BinDecoder::new(buffer);
+    let header = try!(Header::read(&mut decoder));
+
+    // the counts are not trusted to allocate, each query is at least 5 bytes, each record 11
+    let mut queries: Vec<QueryView<'a>> = Vec::with_capacity(cmp::min(header.get_query_count() as usize, decoder.len() / 5));
+    for _ in 0 .. header.get_query_count() {
+      queries.push(try!(QueryView::read(buffer, &mut decoder)));
+    }
+
+    let answers = try!(Self::read_records(buffer, &mut decoder, header.get_answer_count()));
+    let name_servers = try!(Self::read_records(buffer, &mut decoder, header.get_name_server_count()));
+    let additionals = try!(Self::read_records(buffer, &mut decoder, header.get_additional_count()));
+
+    Ok(MessageView {
+      buffer: buffer,
+      header: header,
+      queries: queries,
+      answers: answers,
+      name_servers: name_servers,
+      additionals: additionals,
+    })
+  }
+
+  fn read_records(buffer: &'a [u8], decoder: &mut BinDecoder<'a>, count: u16) -> DecodeResult<Vec<RecordView<'a>>> {
+    let mut records: Vec<RecordView<'a>> = Vec::with_capacity(cmp::min(count as usize, decoder.len() / 11));
+    for _ in 0 .. count {
+      records.push(try!(RecordView::read(buffer, decoder)));
+    }
+
+    Ok(records)
+  }
+
+  pub fn get_header(&self) -> &Header { &self.header }
+  pub fn get_id(&self) -> u16 { self.header.get_id() }
+  pub fn get_queries(&self) -> &[QueryView<'a>] { &self.queries }
+  pub fn get_answers(&self) -> &[RecordView<'a>] { &self.answers }
+  pub fn get_name_servers(&self) -> &[RecordView<'a>] { &self.name_servers }
+  /// The additional records, including the EDNS OPT record and SIG(0) or TSIG, which
+  ///  `Message::read()` separates out
+  pub fn get_additionals(&self) -> &[RecordView<'a>] { &self.additionals }
+
+  /// The buffer the message was read from
+  pub fn get_buffer(&self) -> &'a [u8] { self.buffer }
+
+  /// Decodes the whole message, the same as `Message::read()` on the buffer
+  pub fn to_message(&self) -> DecodeResult<Message> {
+    Message::read(&mut BinDecoder::new(self.buffer))
+  }
+}
+
+/// A query of a `MessageView`
+#[derive(Debug, Clone, Copy)]
+pub struct QueryView<'a> {
+  name: NameView<'a>,
+  query_type: RecordType,
+  query_class: DNSClass,
+}
+
+impl<'a> QueryView<'a> {
+  fn read(buffer: &'a [u8], decoder: &mut BinDecoder<'a>) -> DecodeResult<Self> {
+    let name = try!(NameView::read(buffer, decoder));
+    let query_type = try!(RecordType::read(decoder));
+    let query_class = try!(DNSClass::read(decoder));
+
+    Ok(QueryView{ name: name, query_type: query_type, query_class: query_class })
+  }
+
+  pub fn get_name(&self) -> &NameView<'a> { &self.name }
+  pub fn get_query_type(&self) -> RecordType { self.query_type }
+  pub fn get_query_class(&self) -> DNSClass { self.query_class }
+}
+
+/// A record of a `MessageView`, the rdata is the slice of the buffer it was received in
+#[derive(Debug, Clone, Copy)]
+pub struct RecordView<'a> {
+  buffer: &'a [u8],
+  index: usize,
+  name: NameView<'a>,
+  rr_type: RecordType,
+  dns_class: DNSClass,
+  ttl: u32,
+  rdata: &'a [u8],
+}
+
+impl<'a> RecordView<'a> {
+  fn read(buffer: &'a [u8], decoder: &mut BinDecoder<'a>) -> DecodeResult<Self> {
+    let index = decoder.index();
+    let name = try!(NameView::read(buffer, decoder));
+    let rr_type = try!(RecordType::read(decoder));
+
+    //  DNS Class is overloaded for OPT records in EDNS - RFC 6891
+    let dns_class = if rr_type == RecordType::OPT {
+      DNSClass::for_opt(try!(decoder.read_u16()))
+    } else {
+      try!(DNSClass::read(decoder))
+    };
+
+    let ttl = try!(decoder.read_u32());
+    let rd_length = try!(decoder.read_u16());
+    let rdata = try!(decoder.read_slice(rd_length as usize));
+
+    Ok(RecordView{ buffer: buffer, index: index, name: name, rr_type: rr_type, dns_class: dns_class, ttl: ttl, rdata: rdata })
+  }
+
+  pub fn get_name(&self) -> &NameView<'a> { &self.name }
+  pub fn get_rr_type(&self) -> RecordType { self.rr_type }
+  pub fn get_dns_class(&self) -> DNSClass { self.dns_class }
+  pub fn get_ttl(&self) -> u32 { self.ttl }
+
+  /// The rdata as it was received, names in the rdata may be pointers into the rest of the
+  ///  message, see `to_record()`
+  pub fn get_rdata(&self) -> &'a [u8] { self.rdata }
+
+  /// Decodes the record, with its rdata
+  pub fn to_record(&self) -> DecodeResult<Record> {
+    let mut decoder = BinDecoder::new(self.buffer);
+    try!(decoder.read_slice(self.index));
+    Record::read(&mut decoder)
+  }
+}
+
+/// A name of a `MessageView`, the labels are slices of the buffer, following the pointers of
+///  compressed names
+#[derive(Clone, Copy)]
+pub struct NameView<'a> {
+  buffer: &'a [u8],
+  index: usize,
+}
+
+impl<'a> NameView<'a> {
+  /// Checks the name at the index of the decoder and reads past it, with the limits of
+  ///  `Name::read()`, pointers must point before themselves so that following them always ends.
+  fn read(buffer: &'a [u8], decoder: &mut BinDecoder<'a>) -> DecodeResult<Self> {
+    let index = decoder.index();
+    let mut length: usize = 0;
+    let mut position: usize = index;
+    let mut pointers: usize = 0;
+    let mut in_place = true;
+
+    loop {
+      let byte: u8 = match buffer.get(position) {
+        Some(byte) => *byte,
+        None => return Err(DecodeErrorKind::Message("unexpected end of input reached").into()),
+      };
+
+      match byte & 0b1100_0000 {
+        0b0000_0000 if byte == 0 => {
+          position += 1;
+          length += 1;
+          break
+        },
+        0b0000_0000 => {
+          position += 1 + byte as usize;
+          length += 1 + byte as usize;
+          if position > buffer.len() { return Err(DecodeErrorKind::Message("unexpected end of input reached").into()) }
+        },
+        0b1100_0000 => {
+          let second: u8 = match buffer.get(position + 1) {
+            Some(second) => *second,
+            None => return Err(DecodeErrorKind::Message("unexpected end of input reached").into()),
+          };
+          let location: u16 = ((byte as u16 & 0x3F) << 8) | second as u16;
+          if location as usize >= position { return Err(DecodeErrorKind::ForwardPointer(location, position).into()) }
+
+          pointers += 1;
+          if pointers > MAX_POINTERS { return Err(DecodeErrorKind::TooManyPointers(pointers).into()) }
+
+          if in_place {
+            try!(decoder.read_slice(position + 2 - index));
+            in_place = false;
+          }
+          position = location as usize;
+        },
+        _ => return Err(DecodeErrorKind::UnrecognizedLabelCode(byte).into()),
+      }
+
+      if length > 255 { return Err(DecodeErrorKind::DomainNameTooLong(length).into()) }
+    }
+
+    if in_place { try!(decoder.read_slice(position - index)); }
+    Ok(NameView{ buffer: buffer, index: index })
+  }
+
+  /// The labels of the name, without the root
+  pub fn labels(&self) -> Labels<'a> {
+    Labels{ buffer: self.buffer, position: self.index }
+  }
+
+  pub fn num_labels(&self) -> u8 {
+    self.labels().count() as u8
+  }
+
+  pub fn is_root(&self) -> bool {
+    self.labels().next().is_none()
+  }
+
+  /// Compares the labels with those of the name, ignoring ASCII case, as names are compared
+  pub fn eq_name(&self, name: &Name) -> bool {
+    let mut labels = self.labels();
+    for index in 0 .. name.num_labels() as usize {
+      match labels.next() {
+        Some(view) if view.eq_ignore_ascii_case(name[index].as_bytes()) => (),
+        _ => return false,
+      }
+    }
+
+    labels.next().is_none()
+  }
+
+  /// Decodes the name
+  pub fn to_name(&self) -> DecodeResult<Name> {
+    let mut decoder = BinDecoder::new(self.buffer);
+    try!(decoder.read_slice(self.index));
+    Name::read(&mut decoder)
+  }
+}
+
+impl<'a> fmt::Debug for NameView<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    write!(f, "NameView({})", self)
+  }
+}
+
+impl<'a> fmt::Display for NameView<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    for label in self.labels() {
+      match str::from_utf8(label) {
+        Ok(label) => try!(write!(f, "{}.", label)),
+        Err(_) => try!(write!(f, "{}.", String::from_utf8_lossy(label))),
+      }
+    }
+
+    if self.is_root() { try!(write!(f, ".")); }
+    Ok(())
+  }
+}
+
+/// The labels of a `NameView`, from the first label to the last before the root
+pub struct Labels<'a> {
+  buffer: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Iterator for Labels<'a> {
+  type Item = &'a [u8];
+
+  // the name was checked when it was read, the labels are in the buffer and the pointers end
+  fn next(&mut self) -> Option<&'a [u8]> {
+    loop {
+      let byte = self.buffer[self.position];
+      if byte == 0 { return None }
+
+      if byte & 0b1100_0000 == 0b1100_0000 {
+        self.position = ((byte as usize & 0x3F) << 8) | self.buffer[self.position + 1] as usize;
+      } else {
+        let start = self.position + 1;
+        self.position = start + byte as usize;
+        return Some(&self.buffer[start .. self.position])
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::Ipv4Addr;
+
+  use ::op::{Message, Query};
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::serialize::binary::{BinEncoder, BinSerializable};
+  use super::*;
+
+  #[test]
+  fn test_read_view() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut message = Message::new();
+    message.id(10);
+    message.add_query(Query::new().name(name.clone()).query_type(RecordType::A).query_class(DNSClass::IN).clone());
+    message.add_answer(Record::new().name(name.clone()).rr_type(RecordType::CNAME).dns_class(DNSClass::IN).ttl(86400)
+                                    .rdata(RData::CNAME(Name::parse("host.example.com.", None).unwrap())).clone());
+    message.add_answer(Record::new().name(Name::parse("host.example.com.", None).unwrap()).rr_type(RecordType::A)
+                                    .dns_class(DNSClass::IN).ttl(86400).rdata(RData::A(Ipv4Addr::new(93,184,216,34))).clone());
+
+    let mut buffer: Vec<u8> = Vec::new();
+    message.emit(&mut BinEncoder::new(&mut buffer)).unwrap();
+
+    let view = MessageView::read(&buffer).unwrap();
+    assert_eq!(view.get_id(), 10);
+    assert_eq!(view.get_queries().len(), 1);
+    assert_eq!(view.get_answers().len(), 2);
+
+    // the names of the answers are compressed
+    let answer = view.get_answers()[1];
+    assert!(answer.get_name().eq_name(&Name::parse("HOST.example.com.", None).unwrap()));
+    assert!(!answer.get_name().eq_name(&name));
+    assert_eq!(answer.get_name().num_labels(), 3);
+    assert_eq!(answer.get_name().to_string(), "host.example.com.");
+    assert_eq!(answer.get_rr_type(), RecordType::A);
+    assert_eq!(answer.get_ttl(), 86400);
+    assert_eq!(answer.get_rdata(), &[93,184,216,34]);
+
+    assert_eq!(&view.get_answers()[0].to_record().unwrap(), &message.get_answers()[0]);
+    assert_eq!(view.get_queries()[0].get_name().to_name().unwrap(), name);
+    assert_eq!(view.to_message().unwrap(), message);
+  }
+
+  #[test]
+  fn test_read_bad_names() {
+    // a header with one query, whose name points at itself
+    let mut buffer: Vec<u8> = vec![0,0, 0,0, 0,1, 0,0, 0,0, 0,0];
+    buffer.extend_from_slice(&[0xC0, 12, 0,1, 0,1]);
+    assert!(MessageView::read(&buffer).is_err());
+
+    // a label past the end of the message
+    let mut buffer: Vec<u8> = vec![0,0, 0,0, 0,1, 0,0, 0,0, 0,0];
+    buffer.extend_from_slice(&[5, b'a', 0]);
+    assert!(MessageView::read(&buffer).is_err());
+
+    // the root name
+    let mut buffer: Vec<u8> = vec![0,0, 0,0, 0,1, 0,0, 0,0, 0,0];
+    buffer.extend_from_slice(&[0, 0,1, 0,1]);
+    let view = MessageView::read(&buffer).unwrap();
+    assert!(view.get_queries()[0].get_name().is_root());
+    assert_eq!(view.get_queries()[0].get_name().to_string(), ".");
+  }
+}
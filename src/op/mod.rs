@@ -20,11 +20,15 @@
 pub mod op_code;
 pub mod response_code;
 pub mod message;
+pub mod message_builder;
 pub mod header;
 pub mod query;
+pub mod message_view;
 mod edns;
 
 pub use self::message::{Message, UpdateMessage};
+pub use self::message_builder::MessageBuilder;
+pub use self::message_view::{MessageView, QueryView, RecordView, NameView};
 pub use self::query::Query;
 pub use self::header::Header;
 pub use self::header::MessageType;
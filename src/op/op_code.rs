@@ -17,6 +17,7 @@
 //! Operation code for queries, updates, and responses
 
 use std::convert::From;
+use std::fmt;
 
 use ::error::*;
 
@@ -99,3 +100,21 @@ impl OpCode {
     }
   }
 }
+
+/// The mnemonic of the OpCode, as dig prints it in the header of a message
+///
+/// ```
+/// use trust_dns::op::op_code::OpCode;
+///
+/// assert_eq!(OpCode::Update.to_string(), "UPDATE");
+/// ```
+impl fmt::Display for OpCode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match *self {
+      OpCode::Query  => "QUERY",
+      OpCode::Status => "STATUS",
+      OpCode::Notify => "NOTIFY",
+      OpCode::Update => "UPDATE",
+    })
+  }
+}
@@ -16,9 +16,12 @@
 
 //! Query struct for looking up resource records
 
+use std::fmt;
+
 use ::rr::domain::Name;
 use ::rr::record_type::RecordType;
 use ::rr::dns_class::DNSClass;
+use ::serialize::txt::Writer;
 use ::serialize::binary::*;
 use ::error::*;
 
@@ -106,6 +109,16 @@ impl BinSerializable<Query> for Query {
   }
 }
 
+/// The query as it is in the question section of the output of dig, without the leading `;`
+impl fmt::Display for Query {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let query_class: &'static str = self.query_class.into();
+    let query_type: &'static str = self.query_type.into();
+    let name = Writer::new().relative_names(false).name_to_string(&self.name, &Name::root());
+    write!(f, "{}\t\t{}\t{}", name, query_class, query_type)
+  }
+}
+
 #[test]
 fn test_read_and_emit() {
   let expect = Query { name: Name::with_labels(vec!["WWW".to_string(),"example".to_string(),"com".to_string()]),
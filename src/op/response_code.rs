@@ -16,6 +16,8 @@
 
 //! All defined response codes in DNS
 
+use std::fmt;
+
 /// The status code of the response to a query.
 ///
 /// [RFC 1035, DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987](https://tools.ietf.org/html/rfc1035)
@@ -55,7 +57,7 @@
 ///
 ///                 6-15            Reserved for future use.
 ///  ```
-#[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Copy, Clone)]
 #[allow(dead_code)]
 pub enum ResponseCode {
   /// No Error [RFC 1035](https://tools.ietf.org/html/rfc1035)
@@ -131,8 +133,8 @@ impl ResponseCode {
   }
 
   /// returns the high 12 bits for the edns portion of the response code
-  pub fn high(&self) {
-    (u16::from(*self) & 0x0FF0) >> 4;
+  pub fn high(&self) -> u8 {
+    ((u16::from(*self) & 0x0FF0) >> 4) as u8
   }
 
   pub fn from(high: u8, low: u8) -> ResponseCode {
@@ -249,3 +251,38 @@ impl From<u16> for ResponseCode {
     }
   }
 }
+
+/// The mnemonic of the ResponseCode, as dig prints it in the header of a message, see `to_str()`
+///  for the description
+///
+/// ```
+/// use trust_dns::op::response_code::ResponseCode;
+///
+/// assert_eq!(ResponseCode::NXDomain.to_string(), "NXDOMAIN");
+/// ```
+impl fmt::Display for ResponseCode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match *self {
+      ResponseCode::NoError   => "NOERROR",
+      ResponseCode::FormErr   => "FORMERR",
+      ResponseCode::ServFail  => "SERVFAIL",
+      ResponseCode::NXDomain  => "NXDOMAIN",
+      ResponseCode::NotImp    => "NOTIMP",
+      ResponseCode::Refused   => "REFUSED",
+      ResponseCode::YXDomain  => "YXDOMAIN",
+      ResponseCode::YXRRSet   => "YXRRSET",
+      ResponseCode::NXRRSet   => "NXRRSET",
+      ResponseCode::NotAuth   => "NOTAUTH",
+      ResponseCode::NotZone   => "NOTZONE",
+      ResponseCode::BADVERS   => "BADVERS",
+      ResponseCode::BADSIG    => "BADSIG",
+      ResponseCode::BADKEY    => "BADKEY",
+      ResponseCode::BADTIME   => "BADTIME",
+      ResponseCode::BADMODE   => "BADMODE",
+      ResponseCode::BADNAME   => "BADNAME",
+      ResponseCode::BADALG    => "BADALG",
+      ResponseCode::BADTRUNC  => "BADTRUNC",
+      ResponseCode::BADCOOKIE => "BADCOOKIE",
+    })
+  }
+}
@@ -0,0 +1,310 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Configuration of the name servers and the query name expansion of a stub resolver
+
+#[cfg(not(any(windows, target_os = "macos")))]
+use std::fs::File;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use ::client::{Client, ClientConnection, NameServerPool, RetryPolicy, SelectionStrategy};
+use ::error::*;
+use ::resolver::resolv_conf;
+#[cfg(target_os = "macos")]
+use ::resolver::macos;
+#[cfg(windows)]
+use ::resolver::windows;
+use ::rr::Name;
+use ::tls::{OpportunisticConnection, TlsMode};
+use ::udp::UdpClientConnection;
+
+/// The name servers and options of a stub resolver, the defaults are those of the libc resolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolverConfig {
+  name_servers: Vec<SocketAddr>,
+  domain: Option<Name>,
+  search: Vec<Name>,
+  ndots: usize,
+  timeout: Duration,
+  attempts: usize,
+  rotate: bool,
+  validate: bool,
+  tls: TlsMode,
+  tls_name: Option<String>,
+  scoped: Vec<(Name, ResolverConfig)>,
+}
+
+impl ResolverConfig {
+  /// A configuration without any name servers, `ndots:1 timeout:5 attempts:2`
+  pub fn new() -> Self {
+    ResolverConfig{ name_servers: vec![],
+                    domain: None,
+                    search: vec![],
+                    ndots: 1,
+                    timeout: Duration::from_secs(5),
+                    attempts: 2,
+                    rotate: false,
+                    validate: false,
+                    tls: TlsMode::Off,
+                    tls_name: None,
+                    scoped: vec![] }
+  }
+
+  /// Reads the configuration in the format of `resolv.conf(5)`
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - the contents of the configuration, e.g. a `File`
+  pub fn from_resolv_conf<R: Read>(reader: R) -> ConfigResult<Self> {
+    resolv_conf::read_resolv_conf(reader)
+  }
+
+  /// Reads the configuration of the system, `/etc/resolv.conf`
+  #[cfg(not(any(windows, target_os = "macos")))]
+  pub fn from_system() -> ConfigResult<Self> {
+    Self::from_resolv_conf(try!(File::open("/etc/resolv.conf")))
+  }
+
+  /// Reads the configuration of the system, the default and the scoped resolvers from
+  ///  SystemConfiguration, which includes those of the `/etc/resolver/` files
+  #[cfg(target_os = "macos")]
+  pub fn from_system() -> ConfigResult<Self> {
+    macos::read_system_config()
+  }
+
+  /// Reads the configuration of the system, the DNS servers of the network adapters and the
+  ///  suffix search list from the registry
+  #[cfg(windows)]
+  pub fn from_system() -> ConfigResult<Self> {
+    windows::read_system_config()
+  }
+
+  pub fn add_name_server(&mut self, name_server: SocketAddr) -> &mut Self { self.name_servers.push(name_server); self }
+  /// The local domain, relative names are searched in this domain when there is no search list
+  pub fn domain(&mut self, domain: Option<Name>) -> &mut Self { self.domain = domain; self }
+  /// The search list, replaces the domain for the expansion of relative names
+  pub fn search(&mut self, search: Vec<Name>) -> &mut Self { self.search = search; self }
+  /// Names with fewer dots than this are first tried with the search list
+  pub fn ndots(&mut self, ndots: usize) -> &mut Self { self.ndots = ndots; self }
+  /// The timeout of each request to a name server
+  pub fn timeout(&mut self, timeout: Duration) -> &mut Self { self.timeout = timeout; self }
+  /// The number of times all of the name servers are tried, must be at least 1
+  pub fn attempts(&mut self, attempts: usize) -> &mut Self { self.attempts = attempts; self }
+  /// Spreads the load across the name servers, rather than always trying them in order
+  pub fn rotate(&mut self, rotate: bool) -> &mut Self { self.rotate = rotate; self }
+  /// Validates the answers with DNSSEC against the root trust anchor, see `Client::set_validating()`
+  pub fn validate(&mut self, validate: bool) -> &mut Self { self.validate = validate; self }
+  /// Upgrades the connections to the name servers to DNS over TLS, only used by `tls_client()`
+  ///  and `Resolver::with_tls()`
+  pub fn tls(&mut self, tls: TlsMode) -> &mut Self { self.tls = tls; self }
+  /// The authentication domain name of the name servers, which their certificates must be issued
+  ///  for, required by `TlsMode::Strict`, see `OpportunisticConnection::tls_name()`
+  pub fn tls_name(&mut self, tls_name: Option<String>) -> &mut Self { self.tls_name = tls_name; self }
+
+  /// Adds a configuration for the names in a domain, e.g. for the internal domain of a VPN
+  pub fn add_scoped(&mut self, domain: Name, config: ResolverConfig) -> &mut Self { self.scoped.push((domain, config)); self }
+
+  /// Routes the queries of the names in the domain to the name servers, split DNS, e.g.
+  ///  `corp.example.` to the name server of a VPN, while all other names use the name servers of
+  ///  this configuration. The most specific domain which contains a name is used. The timeout,
+  ///  attempts, rotation, validation and TLS are those of this configuration.
+  pub fn add_route(&mut self, domain: Name, name_servers: Vec<SocketAddr>) -> &mut Self {
+    let mut config = ResolverConfig::new();
+    config.timeout(self.timeout).attempts(self.attempts).rotate(self.rotate).validate(self.validate).tls(self.tls)
+          .tls_name(self.tls_name.clone());
+    config.name_servers = name_servers;
+    self.add_scoped(domain, config)
+  }
+
+  /// Adds a route in the form of a domain followed by its name servers, separated by white
+  ///  space, e.g. `*.corp.example 10.0.0.53 [fd00::53]:5353`, see `add_route()`. The `*.` of the
+  ///  domain is optional, the domain itself is always included. The port defaults to 53.
+  pub fn add_route_rule(&mut self, rule: &str) -> ConfigResult<&mut Self> {
+    let mut tokens = rule.split_whitespace();
+    let domain = match tokens.next() {
+      Some(domain) => domain.trim_left_matches("*."),
+      None => return Err(ConfigErrorKind::Msg(format!("empty route: {}", rule)).into()),
+    };
+    let domain = try!(Name::parse(domain, Some(&Name::root())).map_err(|e| ConfigErrorKind::Msg(format!("invalid route domain {}: {}", domain, e))));
+
+    let mut name_servers: Vec<SocketAddr> = vec![];
+    for token in tokens {
+      let name_server = match token.parse::<SocketAddr>() {
+        Ok(name_server) => name_server,
+        Err(..) => match token.parse::<IpAddr>() {
+          Ok(addr) => SocketAddr::new(addr, 53),
+          Err(..) => return Err(ConfigErrorKind::Msg(format!("invalid name server in route: {}", token)).into()),
+        },
+      };
+      name_servers.push(name_server);
+    }
+
+    if name_servers.is_empty() {
+      return Err(ConfigErrorKind::Msg(format!("route without name servers: {}", rule)).into())
+    }
+
+    Ok(self.add_route(domain, name_servers))
+  }
+
+  pub fn get_name_servers(&self) -> &[SocketAddr] { &self.name_servers }
+  pub fn get_domain(&self) -> Option<&Name> { self.domain.as_ref() }
+  pub fn get_ndots(&self) -> usize { self.ndots }
+  pub fn get_timeout(&self) -> Duration { self.timeout }
+  pub fn get_attempts(&self) -> usize { self.attempts }
+  pub fn is_rotate(&self) -> bool { self.rotate }
+  pub fn is_validate(&self) -> bool { self.validate }
+  pub fn get_tls(&self) -> TlsMode { self.tls }
+  pub fn get_tls_name(&self) -> Option<&str> { self.tls_name.as_ref().map(|s| s as &str) }
+
+  /// The per domain configurations, see `for_name()`
+  pub fn get_scoped(&self) -> &[(Name, ResolverConfig)] { &self.scoped }
+
+  /// The configuration of the most specific scoped domain which contains the name, or else this
+  ///  configuration
+  pub fn for_name(&self, name: &Name) -> &ResolverConfig {
+    self.scoped.iter()
+               .filter(|&&(ref domain, _)| domain.zone_of(name))
+               .max_by_key(|&&(ref domain, _)| domain.num_labels())
+               .map_or(self, |&(_, ref config)| config)
+  }
+
+  /// The names with which relative names are expanded, the search list or else the domain
+  pub fn get_search(&self) -> Vec<Name> {
+    if !self.search.is_empty() { return self.search.clone() }
+    self.domain.iter().cloned().collect()
+  }
+
+  /// The names to query for a name given by a user, in order, the first with a positive answer
+  ///  is the result, see `Client::query_first()`.
+  ///
+  /// A name ending in `.` is absolute and is never expanded. Otherwise the name is expanded with
+  ///  each of the search domains, those with at least `ndots` dots are first tried as is, and
+  ///  those with fewer are tried as is last.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - the name as given, e.g. `db01` or `www.example.com.`
+  pub fn search_names(&self, name: &str) -> ParseResult<Vec<Name>> {
+    let root = Name::root();
+    if name.ends_with('.') { return Ok(vec![try!(Name::parse(name, None))]) }
+
+    let as_is = try!(Name::parse(name, Some(&root)));
+    let mut names: Vec<Name> = Vec::with_capacity(self.search.len() + 2);
+    let dots = name.chars().filter(|c| *c == '.').count();
+
+    if dots >= self.ndots { names.push(as_is.clone()) }
+    for domain in self.get_search() {
+      let search_name = try!(Name::parse(name, Some(&domain)));
+      if !names.contains(&search_name) { names.push(search_name) }
+    }
+    if dots < self.ndots && !names.contains(&as_is) { names.push(as_is) }
+
+    Ok(names)
+  }
+
+  /// Creates a `Client` for the configured name servers, or the local host if there are none,
+  ///  with the timeout, attempts, rotation and validation of the configuration.
+  pub fn client(&self) -> ClientResult<Client<NameServerPool<UdpClientConnection>>> {
+    self.pool_client(|name_server| UdpClientConnection::with_timeout(name_server, self.timeout))
+  }
+
+  /// Like `client()`, with connections which are upgraded to DNS over TLS as configured with
+  ///  `tls()`. `TlsMode::Strict` requires the `tls_name()` of the name servers, so that their
+  ///  certificates are authenticated.
+  pub fn tls_client(&self) -> ClientResult<Client<NameServerPool<OpportunisticConnection>>> {
+    if self.tls == TlsMode::Strict && self.tls_name.is_none() {
+      return Err(ClientErrorKind::Message("strict tls requires the authentication domain name, see tls_name()").into())
+    }
+
+    self.pool_client(|name_server| {
+      let mut connection = try!(OpportunisticConnection::new(name_server, self.tls, self.timeout));
+      connection.tls_name(self.tls_name.clone());
+      Ok(connection)
+    })
+  }
+
+  fn pool_client<C, F>(&self, connect: F) -> ClientResult<Client<NameServerPool<C>>>
+    where C: ClientConnection, F: Fn(SocketAddr) -> ClientResult<C> {
+    let local = [SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127,0,0,1)), 53)];
+    let name_servers = if self.name_servers.is_empty() { &local[..] } else { &self.name_servers[..] };
+
+    let mut connections = Vec::with_capacity(name_servers.len());
+    for name_server in name_servers {
+      connections.push(try!(connect(*name_server)));
+    }
+
+    let strategy = if self.rotate { SelectionStrategy::RoundRobin } else { SelectionStrategy::Ordered };
+    let mut client = Client::new(NameServerPool::new(connections, strategy));
+    // the pool tries each server in turn, each attempt is a round of all of the servers
+    client.set_retry_policy(RetryPolicy::new(self.attempts));
+    client.set_validating(self.validate);
+    Ok(client)
+  }
+}
+
+impl Default for ResolverConfig {
+  fn default() -> Self { Self::new() }
+}
+
+#[test]
+fn test_search_names() {
+  let name = |s: &str| Name::parse(s, None).unwrap();
+
+  let mut config = ResolverConfig::new();
+  config.search(vec![name("corp.example.com."), name("example.com.")]);
+
+  assert_eq!(config.search_names("db01").unwrap(),
+             vec![name("db01.corp.example.com."), name("db01.example.com."), name("db01.")]);
+  assert_eq!(config.search_names("www.example.org").unwrap(),
+             vec![name("www.example.org."), name("www.example.org.corp.example.com."), name("www.example.org.example.com.")]);
+  assert_eq!(config.search_names("www.example.org.").unwrap(), vec![name("www.example.org.")]);
+
+  config.ndots(2);
+  assert_eq!(config.search_names("db01.corp").unwrap(),
+             vec![name("db01.corp.corp.example.com."), name("db01.corp.example.com."), name("db01.corp.")]);
+}
+
+#[test]
+fn test_routes() {
+  let name = |s: &str| Name::parse(s, None).unwrap();
+  let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+
+  let mut config = ResolverConfig::new();
+  config.add_name_server(addr("8.8.8.8:53")).timeout(Duration::from_secs(2));
+  config.add_route_rule("*.corp.example 10.0.0.53 [fd00::53]:5353").unwrap();
+  config.add_route(name("lab.corp.example."), vec![addr("10.1.0.53:53")]);
+
+  assert_eq!(config.for_name(&name("www.example.com.")).get_name_servers(), &[addr("8.8.8.8:53")]);
+  assert_eq!(config.for_name(&name("corp.example.")).get_name_servers(), &[addr("10.0.0.53:53"), addr("[fd00::53]:5353")]);
+  assert_eq!(config.for_name(&name("db.corp.example.")).get_timeout(), Duration::from_secs(2));
+  assert_eq!(config.for_name(&name("db.lab.corp.example.")).get_name_servers(), &[addr("10.1.0.53:53")]);
+
+  assert!(config.add_route_rule("corp.example").is_err());
+  assert!(config.add_route_rule("corp.example not-an-address").is_err());
+}
+
+#[test]
+fn test_strict_tls_name() {
+  let mut config = ResolverConfig::new();
+  config.add_name_server("127.0.0.1:53".parse().unwrap()).tls(TlsMode::Strict);
+  assert!(config.tls_client().is_err());
+
+  config.tls_name(Some("dns.example.com".to_string()));
+  assert!(config.tls_client().is_ok());
+  assert_eq!(config.get_tls_name(), Some("dns.example.com"));
+}
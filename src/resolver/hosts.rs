@@ -0,0 +1,212 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The hosts file, `hosts(5)`, static addresses of names which are used before querying
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use ::error::*;
+use ::rr::{Name, RecordType};
+
+/// files which are watched are checked for modification at most this often
+const WATCH_INTERVAL_SECS: u64 = 1;
+
+/// The file the hosts were read from
+#[derive(Debug, Clone)]
+struct Source {
+  path: PathBuf,
+  modified: Option<SystemTime>,
+  checked: Instant,
+}
+
+/// The addresses of the names in a hosts file, and the names of the addresses for reverse lookups
+#[derive(Debug, Clone, Default)]
+pub struct Hosts {
+  by_name: HashMap<Name, Vec<IpAddr>>,
+  by_addr: HashMap<IpAddr, Vec<Name>>,
+  source: Option<Source>,
+  watch: bool,
+}
+
+impl Hosts {
+  /// An empty set of hosts
+  pub fn new() -> Self {
+    Hosts::default()
+  }
+
+  /// Reads hosts in the format of `hosts(5)`, an address followed by its canonical name and any
+  ///  aliases on each line. Invalid lines are ignored with a warning.
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - the contents of the hosts file
+  pub fn read<R: Read>(reader: R) -> ConfigResult<Self> {
+    let mut hosts = Hosts::new();
+
+    for line in BufReader::new(reader).lines() {
+      let line = try!(line);
+      let mut fields = line.split('#').next().unwrap_or("").split_whitespace();
+
+      let addr: IpAddr = match fields.next() {
+        Some(addr) => match addr.parse() {
+          Ok(addr) => addr,
+          Err(_) => {
+            warn!("invalid address in hosts: {}", line);
+            continue
+          },
+        },
+        None => continue,
+      };
+
+      for name in fields {
+        match Name::parse(name, Some(&Name::root())) {
+          Ok(name) => hosts.insert(name, addr),
+          Err(error) => warn!("invalid name in hosts: {}: {}", name, error),
+        }
+      }
+    }
+
+    Ok(hosts)
+  }
+
+  /// Reads the hosts file at the path, the path is kept so that the file can be watched, see
+  ///  `set_watch()`
+  pub fn open(path: &Path) -> ConfigResult<Self> {
+    let modified = try!(fs::metadata(path)).modified().ok();
+    let mut hosts = try!(Self::read(try!(File::open(path))));
+    hosts.source = Some(Source{ path: path.to_path_buf(), modified: modified, checked: Instant::now() });
+    Ok(hosts)
+  }
+
+  /// Reads the hosts file of the system, `/etc/hosts`, or on Windows
+  ///  `%SystemRoot%\System32\drivers\etc\hosts`
+  pub fn from_system() -> ConfigResult<Self> {
+    Self::open(&Self::system_path())
+  }
+
+  #[cfg(not(windows))]
+  fn system_path() -> PathBuf {
+    PathBuf::from("/etc/hosts")
+  }
+
+  #[cfg(windows)]
+  fn system_path() -> PathBuf {
+    let system_root = ::std::env::var("SystemRoot").unwrap_or("C:\\Windows".to_string());
+    Path::new(&system_root).join("System32").join("drivers").join("etc").join("hosts")
+  }
+
+  /// Adds the address to the name, and the name to the names of the address
+  pub fn insert(&mut self, name: Name, addr: IpAddr) {
+    {
+      let addrs = self.by_name.entry(name.clone()).or_insert(vec![]);
+      if !addrs.contains(&addr) { addrs.push(addr); }
+    }
+
+    let names = self.by_addr.entry(addr).or_insert(vec![]);
+    if !names.contains(&name) { names.push(name); }
+  }
+
+  /// The addresses of the name for the record type, A or AAAA, None if the name is not in the
+  ///  hosts. The addresses are empty if the name only has addresses of the other type, as the
+  ///  hosts are authoritative for the names in them.
+  pub fn lookup(&self, name: &Name, record_type: RecordType) -> Option<Vec<IpAddr>> {
+    self.by_name.get(name).map(|addrs| {
+      addrs.iter().filter(|addr| match (record_type, **addr) {
+        (RecordType::A, IpAddr::V4(..)) | (RecordType::AAAA, IpAddr::V6(..)) => true,
+        _ => false,
+      }).cloned().collect()
+    })
+  }
+
+  /// The names of the address, the canonical name first
+  pub fn reverse_lookup(&self, addr: &IpAddr) -> Option<&[Name]> {
+    self.by_addr.get(addr).map(|names| &names[..])
+  }
+
+  /// The hosts of a file which is watched are reloaded by `refresh()` when the file changes,
+  ///  only hosts read with `open()` can be watched. Default is false.
+  pub fn set_watch(&mut self, watch: bool) {
+    if watch && self.source.is_none() { warn!("only hosts opened from a file can be watched") }
+    self.watch = watch && self.source.is_some();
+  }
+
+  pub fn is_watch(&self) -> bool {
+    self.watch
+  }
+
+  /// Reloads the hosts if the watched file has been modified, the file is checked at most once
+  ///  a second. If the file can not be read the current hosts are kept.
+  pub fn refresh(&mut self) {
+    if !self.watch { return }
+
+    let (path, modified) = match self.source {
+      Some(ref mut source) => {
+        if source.checked.elapsed() < Duration::from_secs(WATCH_INTERVAL_SECS) { return }
+        source.checked = Instant::now();
+
+        let modified = fs::metadata(&source.path).and_then(|metadata| metadata.modified()).ok();
+        if modified == source.modified { return }
+        (source.path.clone(), modified)
+      },
+      None => return,
+    };
+
+    match Self::open(&path) {
+      Ok(mut hosts) => {
+        info!("reloaded hosts: {:?}, modified: {:?}", path, modified);
+        hosts.watch = true;
+        *self = hosts;
+      },
+      Err(error) => warn!("could not reload hosts: {:?}: {}", path, error),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::IpAddr;
+
+  use ::rr::{Name, RecordType};
+  use super::Hosts;
+
+  #[test]
+  fn test_read_hosts() {
+    let hosts = b"
+127.0.0.1 localhost
+::1       localhost ip6-localhost # loopback
+192.0.2.10 db01.example.com db01
+not-an-address db02
+";
+    let hosts = Hosts::read(&hosts[..]).unwrap();
+
+    let localhost = Name::parse("localhost.", None).unwrap();
+    assert_eq!(hosts.lookup(&localhost, RecordType::A), Some(vec!["127.0.0.1".parse::<IpAddr>().unwrap()]));
+    assert_eq!(hosts.lookup(&localhost, RecordType::AAAA), Some(vec!["::1".parse::<IpAddr>().unwrap()]));
+
+    let db01 = Name::parse("DB01.", None).unwrap();
+    assert_eq!(hosts.lookup(&db01, RecordType::A), Some(vec!["192.0.2.10".parse::<IpAddr>().unwrap()]));
+    assert_eq!(hosts.lookup(&db01, RecordType::AAAA), Some(vec![]));
+    assert_eq!(hosts.lookup(&Name::parse("db02.", None).unwrap(), RecordType::A), None);
+
+    assert_eq!(hosts.reverse_lookup(&"192.0.2.10".parse().unwrap()),
+               Some(&[Name::parse("db01.example.com.", None).unwrap(), Name::parse("db01.", None).unwrap()][..]));
+  }
+}
@@ -0,0 +1,210 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The system DNS configuration of macOS, including the per domain resolvers of VPNs and the
+//!  `/etc/resolver/` files, see `resolver(5)`
+
+#[cfg(target_os = "macos")]
+use std::fs::{self, File};
+#[cfg(target_os = "macos")]
+use std::path::Path;
+#[cfg(target_os = "macos")]
+use std::process::Command;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+#[cfg(target_os = "macos")]
+use ::error::*;
+use ::resolver::ResolverConfig;
+use ::rr::Name;
+
+/// Reads the configuration from SystemConfiguration with `scutil --dns`, if that is not available
+///  the configuration is read from `/etc/resolv.conf` and `/etc/resolver/`.
+#[cfg(target_os = "macos")]
+pub fn read_system_config() -> ConfigResult<ResolverConfig> {
+  match Command::new("scutil").arg("--dns").output() {
+    Ok(ref output) if output.status.success() => return Ok(parse_scutil_dns(&String::from_utf8_lossy(&output.stdout))),
+    Ok(output) => warn!("scutil --dns failed: {}", output.status),
+    Err(error) => warn!("scutil --dns failed: {}", error),
+  }
+
+  let mut config = try!(ResolverConfig::from_resolv_conf(try!(File::open("/etc/resolv.conf"))));
+  for (domain, scoped) in try!(read_resolver_dir(Path::new("/etc/resolver"))) {
+    config.add_scoped(domain, scoped);
+  }
+  Ok(config)
+}
+
+/// Reads the `resolver(5)` files, each is named for the domain it configures
+#[cfg(target_os = "macos")]
+pub fn read_resolver_dir(path: &Path) -> ConfigResult<Vec<(Name, ResolverConfig)>> {
+  let mut resolvers = vec![];
+  if !path.is_dir() { return Ok(resolvers) }
+
+  for entry in try!(fs::read_dir(path)) {
+    let entry = try!(entry);
+    let file_name = entry.file_name();
+    let domain = match file_name.to_str().and_then(|name| Name::parse(name, Some(&Name::root())).ok()) {
+      Some(domain) => domain,
+      None => {
+        warn!("ignoring resolver file: {:?}", entry.path());
+        continue
+      },
+    };
+
+    let config = try!(ResolverConfig::from_resolv_conf(try!(File::open(entry.path()))));
+    resolvers.push((domain, config));
+  }
+
+  Ok(resolvers)
+}
+
+/// A resolver from the output of scutil, the options which are needed to select it
+#[derive(Default)]
+struct ScutilResolver {
+  domain: Option<Name>,
+  search: Vec<Name>,
+  name_servers: Vec<IpAddr>,
+  port: Option<u16>,
+  timeout: Option<u64>,
+  mdns: bool,
+}
+
+impl ScutilResolver {
+  fn to_config(&self) -> ResolverConfig {
+    let mut config = ResolverConfig::new();
+    for ip in self.name_servers.iter() {
+      config.add_name_server(SocketAddr::new(*ip, self.port.unwrap_or(53)));
+    }
+    if let Some(timeout) = self.timeout { config.timeout(Duration::from_secs(timeout)); }
+    config.search(self.search.clone());
+    config
+  }
+}
+
+/// Parses the output of `scutil --dns`, the first resolver without a domain is the default,
+///  those with a domain are scoped to it. Multicast DNS resolvers, e.g. for `local.`, and the
+///  resolvers for interface scoped queries are ignored.
+///
+/// ```text
+/// DNS configuration
+///
+/// resolver #1
+///   search domain[0] : example.com
+///   nameserver[0] : 192.168.1.1
+///   if_index : 6 (en0)
+///   flags    : Request A records
+///   reach    : 0x00020002 (Reachable,Directly Reachable Address)
+///
+/// resolver #2
+///   domain   : corp.example.com
+///   nameserver[0] : 10.0.0.53
+///   ...
+/// ```
+fn parse_scutil_dns(output: &str) -> ResolverConfig {
+  let mut resolvers: Vec<ScutilResolver> = vec![];
+
+  for line in output.lines() {
+    let line = line.trim();
+    if line.starts_with("DNS configuration (") { break }
+    if line.starts_with("resolver #") {
+      resolvers.push(ScutilResolver::default());
+      continue
+    }
+
+    let resolver = match resolvers.last_mut() {
+      Some(resolver) => resolver,
+      None => continue,
+    };
+
+    let mut split = line.splitn(2, " : ");
+    let key = split.next().unwrap_or("").trim();
+    let value = match split.next() {
+      Some(value) => value.trim(),
+      None => continue,
+    };
+
+    // indexed keys, e.g. nameserver[0]
+    match key.split('[').next().unwrap_or("").trim() {
+      "domain" => resolver.domain = Name::parse(value, Some(&Name::root())).ok(),
+      "search domain" => resolver.search.extend(Name::parse(value, Some(&Name::root())).ok()),
+      "nameserver" => resolver.name_servers.extend(value.parse::<IpAddr>().ok()),
+      "port" => resolver.port = value.parse().ok(),
+      "timeout" => resolver.timeout = value.parse().ok(),
+      "options" => resolver.mdns = value.split_whitespace().any(|option| option == "mdns"),
+      _ => (),
+    }
+  }
+
+  let mut config = resolvers.iter()
+                            .find(|resolver| resolver.domain.is_none() && !resolver.mdns)
+                            .map_or(ResolverConfig::new(), |resolver| resolver.to_config());
+
+  for resolver in resolvers.iter().filter(|resolver| !resolver.mdns && !resolver.name_servers.is_empty()) {
+    if let Some(ref domain) = resolver.domain {
+      config.add_scoped(domain.clone(), resolver.to_config());
+    }
+  }
+
+  config
+}
+
+#[test]
+fn test_parse_scutil_dns() {
+  let output = "
+DNS configuration
+
+resolver #1
+  search domain[0] : example.com
+  nameserver[0] : 192.168.1.1
+  nameserver[1] : fe80::1
+  if_index : 6 (en0)
+  flags    : Request A records
+  reach    : 0x00020002 (Reachable,Directly Reachable Address)
+
+resolver #2
+  domain   : local
+  options  : mdns
+  timeout  : 5
+  flags    : Request A records
+  reach    : 0x00000000 (Not Reachable)
+  order    : 300000
+
+resolver #3
+  domain   : corp.example.com
+  nameserver[0] : 10.0.0.53
+  port     : 5353
+  timeout  : 2
+
+DNS configuration (for scoped queries)
+
+resolver #1
+  nameserver[0] : 192.168.1.254
+";
+
+  let config = parse_scutil_dns(output);
+  assert_eq!(config.get_name_servers(), &["192.168.1.1:53".parse().unwrap(), "[fe80::1]:53".parse().unwrap()]);
+  assert_eq!(config.get_search(), vec![Name::parse("example.com.", None).unwrap()]);
+  assert_eq!(config.get_scoped().len(), 1);
+
+  let name = Name::parse("db.corp.example.com.", None).unwrap();
+  let scoped = config.for_name(&name);
+  assert_eq!(scoped.get_name_servers(), &["10.0.0.53:5353".parse().unwrap()]);
+  assert_eq!(scoped.get_timeout(), Duration::from_secs(2));
+
+  let name = Name::parse("www.example.com.", None).unwrap();
+  assert_eq!(config.for_name(&name), &config);
+}
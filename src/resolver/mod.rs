@@ -0,0 +1,51 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A stub resolver and its configuration, e.g. from the system `/etc/resolv.conf`,
+//! SystemConfiguration on macOS, or the registry on Windows
+//!
+//! ```no_run
+//! use trust_dns::resolver::Resolver;
+//!
+//! let resolver = Resolver::from_system().unwrap();
+//! let addresses = resolver.lookup_ip("www.example.com").unwrap();
+//! println!("{:?}", addresses);
+//! ```
+//!
+//! The `Resolver` is synchronous, as is the `Client` it is built on, each lookup blocks until the
+//! answer is received or the lookup times out.
+//!
+//! The `Recursor` resolves names without an upstream resolver, by following the delegations from
+//! the root servers, see `RootHints`.
+
+mod config;
+mod hosts;
+#[cfg(any(target_os = "macos", test))]
+mod macos;
+mod overrides;
+mod recursor;
+mod resolv_conf;
+mod resolver;
+mod root_hints;
+#[cfg(any(windows, test))]
+mod windows;
+
+pub use self::config::ResolverConfig;
+pub use self::hosts::Hosts;
+pub use self::overrides::Overrides;
+pub use self::recursor::{Connector, Recursor, UdpConnector};
+pub use self::resolver::Resolver;
+pub use self::root_hints::RootHints;
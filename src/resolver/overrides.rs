@@ -0,0 +1,130 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Static answers of names, which take precedence over the hosts and the name servers
+
+use std::collections::{HashMap, HashSet};
+
+use ::op::ResponseCode;
+use ::rr::{Name, Record, RecordType};
+
+/// Fixed records of names, and blocked domains, set programmatically, e.g. for tests, the service
+///  names of containers, or blocking of ad and malware domains. See `Client::set_overrides()`.
+///
+/// Like the hosts, the overrides are authoritative for their names, a name with records of other
+///  types only has no data for the query type.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+  records: HashMap<Name, Vec<Record>>,
+  blocked: HashSet<Name>,
+}
+
+impl Overrides {
+  /// No overrides
+  pub fn new() -> Self {
+    Overrides::default()
+  }
+
+  /// Adds the record to the answers of its name, e.g. an A record for the address of a name, or a
+  ///  CNAME which is answered for queries of all types
+  pub fn insert(&mut self, record: Record) -> &mut Self {
+    let records = self.records.entry(record.get_name().clone()).or_insert(vec![]);
+    if !records.contains(&record) { records.push(record); }
+    self
+  }
+
+  /// Removes all of the records of the name, returning them
+  pub fn remove(&mut self, name: &Name) -> Vec<Record> {
+    self.records.remove(name).unwrap_or(vec![])
+  }
+
+  /// Queries of the domain, and all names below it, are answered with NXDOMAIN. Blocking
+  ///  takes precedence over records.
+  pub fn block(&mut self, domain: Name) -> &mut Self {
+    self.blocked.insert(domain);
+    self
+  }
+
+  /// Removes the block of the domain, returning true if it was blocked
+  pub fn unblock(&mut self, domain: &Name) -> bool {
+    self.blocked.remove(domain)
+  }
+
+  /// true if the name or one of its parent domains is blocked
+  pub fn is_blocked(&self, name: &Name) -> bool {
+    (0..name.num_labels() + 1).any(|labels| self.blocked.contains(&name.trim_to(labels as usize)))
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.records.is_empty() && self.blocked.is_empty()
+  }
+
+  /// The response code and answers of the query, None if the name isn't overridden. The answers of
+  ///  a name which only has records of other types are empty, a CNAME is the answer to all types.
+  pub fn lookup(&self, name: &Name, record_type: RecordType) -> Option<(ResponseCode, Vec<Record>)> {
+    if self.is_blocked(name) { return Some((ResponseCode::NXDomain, vec![])) }
+
+    self.records.get(name).map(|records| {
+      let answers: Vec<Record> = records.iter()
+                                        .filter(|r| r.get_rr_type() == record_type || record_type == RecordType::ANY)
+                                        .cloned()
+                                        .collect();
+      if !answers.is_empty() { return (ResponseCode::NoError, answers) }
+
+      (ResponseCode::NoError, records.iter().filter(|r| r.get_rr_type() == RecordType::CNAME).cloned().collect())
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::Ipv4Addr;
+
+  use ::op::ResponseCode;
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use super::*;
+
+  fn record(name: &Name, rdata: RData) -> Record {
+    let rr_type = match rdata { RData::CNAME(..) => RecordType::CNAME, _ => RecordType::A };
+    Record::new().name(name.clone()).ttl(60).rr_type(rr_type).dns_class(DNSClass::IN).rdata(rdata).clone()
+  }
+
+  #[test]
+  fn test_lookup() {
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let alias = Name::parse("alias.example.com.", None).unwrap();
+    let ads = Name::parse("ads.example.", None).unwrap();
+    let a = record(&www, RData::A(Ipv4Addr::new(10,0,0,1)));
+    let cname = record(&alias, RData::CNAME(www.clone()));
+
+    let mut overrides = Overrides::new();
+    overrides.insert(a.clone()).insert(cname.clone()).block(ads.clone());
+
+    assert_eq!(overrides.lookup(&www, RecordType::A), Some((ResponseCode::NoError, vec![a.clone()])));
+    assert_eq!(overrides.lookup(&www, RecordType::AAAA), Some((ResponseCode::NoError, vec![])));
+    assert_eq!(overrides.lookup(&alias, RecordType::A), Some((ResponseCode::NoError, vec![cname])));
+    assert_eq!(overrides.lookup(&Name::parse("example.com.", None).unwrap(), RecordType::A), None);
+
+    assert_eq!(overrides.lookup(&ads, RecordType::A), Some((ResponseCode::NXDomain, vec![])));
+    assert_eq!(overrides.lookup(&Name::parse("tracker.ads.example.", None).unwrap(), RecordType::A), Some((ResponseCode::NXDomain, vec![])));
+    assert!(!overrides.is_blocked(&Name::parse("example.", None).unwrap()));
+
+    assert_eq!(overrides.remove(&www), vec![a]);
+    assert_eq!(overrides.lookup(&www, RecordType::A), None);
+    assert!(overrides.unblock(&ads));
+    assert!(overrides.lookup(&ads, RecordType::A).is_none());
+  }
+}
@@ -0,0 +1,646 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! An iterative resolver, which resolves names by following the delegations from the root servers
+
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ::client::{Client, ClientConnection, QueryFlags};
+use ::error::*;
+use ::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use ::resolver::RootHints;
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+use ::udp::UdpClientConnection;
+
+/// the delegations followed for a single name, from the root
+const MAX_REFERRALS: usize = 16;
+/// nested resolutions of the addresses of name servers for which there was no glue
+const MAX_NS_DEPTH: usize = 4;
+/// the default CNAME chain depth
+const MAX_CNAME_DEPTH: usize = 8;
+
+/// Creates the connections to the name servers queried by the `Recursor`
+pub trait Connector {
+  type Connection: ClientConnection;
+
+  /// Returns a connection to the name server
+  fn connect(&self, name_server: SocketAddr) -> ClientResult<Self::Connection>;
+}
+
+/// Connects to name servers over UDP
+#[derive(Debug, Clone, Copy)]
+pub struct UdpConnector {
+  timeout: Duration,
+}
+
+impl UdpConnector {
+  /// # Arguments
+  ///
+  /// * `timeout` - the time to wait for each response
+  pub fn new(timeout: Duration) -> Self {
+    UdpConnector{ timeout: timeout }
+  }
+}
+
+impl Connector for UdpConnector {
+  type Connection = UdpClientConnection;
+
+  fn connect(&self, name_server: SocketAddr) -> ClientResult<UdpClientConnection> {
+    UdpClientConnection::with_timeout(name_server, self.timeout)
+  }
+}
+
+/// the addresses of the name servers of a zone, learned from a referral
+#[derive(Debug)]
+struct Delegation {
+  name_servers: Vec<SocketAddr>,
+  expires: Instant,
+}
+
+/// A recursive resolver, which does not rely on an upstream server. Each name is resolved by
+///  querying the root servers, following the referrals to the authoritative servers of the name,
+///  along with the CNAME chain of the answer.
+///
+/// The name servers of the delegations are cached for the TTL of their NS records, so that later
+///  names in the same zones are sent directly to their servers. Referrals which do not lead closer
+///  to the name, and CNAME chains which loop, are errors.
+///
+/// ```no_run
+/// use trust_dns::resolver::Recursor;
+/// use trust_dns::rr::{DNSClass, Name, RecordType};
+///
+/// let recursor = Recursor::default();
+///
+/// let name = Name::parse("www.example.com.", None).unwrap();
+/// let response = recursor.resolve(&name, DNSClass::IN, RecordType::A).unwrap();
+/// ```
+pub struct Recursor<T: Connector> {
+  connector: T,
+  roots: Vec<SocketAddr>,
+  delegations: Mutex<HashMap<Name, Delegation>>,
+  max_cname_depth: usize,
+  qname_minimization: bool,
+}
+
+impl Recursor<UdpConnector> {
+  /// Creates a recursor which queries the name servers over UDP, with a five second timeout
+  ///
+  /// # Arguments
+  ///
+  /// * `roots` - the addresses of the root servers
+  pub fn new(roots: Vec<SocketAddr>) -> Self {
+    Self::with_connector(roots, UdpConnector::new(Duration::from_secs(5)))
+  }
+
+  /// Creates a recursor which starts from the root servers of the hints, e.g. those read from an
+  ///  operator supplied `named.root`
+  pub fn with_root_hints(root_hints: &RootHints) -> Self {
+    Self::new(root_hints.get_socket_addrs())
+  }
+}
+
+impl Default for Recursor<UdpConnector> {
+  /// A recursor starting from the compiled in root servers, see `RootHints::default()`
+  fn default() -> Self {
+    Self::with_root_hints(&RootHints::default())
+  }
+}
+
+impl<T: Connector> Recursor<T> {
+  /// # Arguments
+  ///
+  /// * `roots` - the addresses of the root servers
+  /// * `connector` - creates the connections to the name servers
+  pub fn with_connector(roots: Vec<SocketAddr>, connector: T) -> Self {
+    Recursor{ connector: connector, roots: roots, delegations: Mutex::new(HashMap::new()), max_cname_depth: MAX_CNAME_DEPTH,
+              qname_minimization: true }
+  }
+
+  /// The number of CNAMEs which will be followed for a name, defaults to 8
+  pub fn set_max_cname_depth(&mut self, max_cname_depth: usize) {
+    self.max_cname_depth = max_cname_depth;
+  }
+
+  /// When enabled, the default, each server is sent only the labels of the name which it needs to
+  ///  refer to the next zone, as NS queries, rather than the full name,
+  ///  [RFC 7816](https://tools.ietf.org/html/rfc7816). If a server fails a minimized query the
+  ///  full name is sent for the remainder of the resolution.
+  ///
+  /// ```text
+  /// 2.  Description of QNAME Minimisation
+  ///
+  ///    The idea is to minimise the amount of data sent from the DNS resolver
+  ///    to the authoritative name server.  In the example in the previous
+  ///    section, sending "QNAME=www.example.org, QTYPE=AAAA" to the root name
+  ///    servers is not necessary.  The resolver could instead send
+  ///    "QNAME=org, QTYPE=NS" to the root name servers.  The root name servers
+  ///    would then send back a referral to the .org name servers.
+  /// ```
+  pub fn set_qname_minimization(&mut self, qname_minimization: bool) {
+    self.qname_minimization = qname_minimization;
+  }
+
+  pub fn is_qname_minimization(&self) -> bool {
+    self.qname_minimization
+  }
+
+  pub fn get_roots(&self) -> &[SocketAddr] {
+    &self.roots
+  }
+
+  /// The cached addresses of the name servers of the zone, None if the delegation is not known or
+  ///  has expired
+  pub fn get_delegation(&self, zone: &Name) -> Option<Vec<SocketAddr>> {
+    let delegations = self.delegations.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    delegations.get(zone).and_then(|d| if d.expires > Instant::now() { Some(d.name_servers.clone()) } else { None })
+  }
+
+  /// Removes all of the cached delegations, the next names are resolved from the root
+  pub fn clear_delegations(&self) {
+    self.delegations.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+  }
+
+  /// Resolves the records of the name, following the CNAME chain of the answer. The response
+  ///  contains the chain and the final answers, with the response code of the final name.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - the fully qualified name to resolve
+  /// * `query_class` - most likely this should always be DNSClass::IN
+  /// * `query_type` - record type to lookup
+  pub fn resolve(&self, name: &Name, query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
+    let mut chain: Vec<Record> = vec![];
+    let mut visited: HashSet<Name> = HashSet::new();
+    let mut current = name.clone();
+    visited.insert(current.clone());
+
+    loop {
+      let response = try!(self.resolve_name(&current, query_class, query_type, 0));
+
+      let target = if query_type == RecordType::CNAME { None } else { try!(chain_target(&current, query_type, response.get_answers(), &mut visited)) };
+      chain.extend(response.get_answers().iter().cloned());
+
+      match target {
+        Some(target) => {
+          if visited.len() > self.max_cname_depth { return Err(ClientErrorKind::CnameDepthExceeded(self.max_cname_depth).into()) }
+          debug!("restarting at cname: {}", target);
+          current = target;
+        },
+        None => return Ok(Self::response(name, query_class, query_type, chain, &response)),
+      }
+    }
+  }
+
+  /// the response to the original query, with the entire chain of answers
+  fn response(name: &Name, query_class: DNSClass, query_type: RecordType, answers: Vec<Record>, last: &Message) -> Message {
+    let mut query = Query::new();
+    query.name(name.clone()).query_class(query_class).query_type(query_type);
+
+    let mut message = Message::new();
+    message.message_type(MessageType::Response)
+           .op_code(OpCode::Query)
+           .recursion_desired(true)
+           .recursion_available(true)
+           .response_code(last.get_response_code())
+           .add_query(query);
+
+    for answer in answers { message.add_answer(answer); }
+    for name_server in last.get_name_servers() { message.add_name_server(name_server.clone()); }
+    message
+  }
+
+  /// Resolves the single name, without following CNAMEs, from the closest known delegation
+  fn resolve_name(&self, name: &Name, query_class: DNSClass, query_type: RecordType, ns_depth: usize) -> ClientResult<Message> {
+    let (mut zone, mut name_servers) = self.closest_delegation(name);
+    let name_labels = name.num_labels() as usize;
+    // the labels of the name which have been revealed to the servers of the zone
+    let mut labels = zone.num_labels() as usize;
+    let mut minimize = self.qname_minimization;
+    let mut referrals = 0;
+
+    // each minimized query reveals one more label, or is followed by a referral
+    for _ in 0..(MAX_REFERRALS + name_labels) {
+      let minimized = minimize && labels + 1 < name_labels;
+      let (query_name, query_name_type) = if minimized {
+        labels += 1;
+        (name.trim_to(labels), RecordType::NS)
+      } else {
+        (name.clone(), query_type)
+      };
+
+      debug!("querying {} for {} {:?}", zone, query_name, query_name_type);
+      let response = try!(self.query_name_servers(&name_servers, &query_name, query_class, query_name_type));
+
+      match response.get_response_code() {
+        ResponseCode::NoError => (),
+        // some servers answer incorrectly for empty non-terminals, or don't support NS queries,
+        //  fall back to the full name
+        response_code if minimized => {
+          debug!("minimized query for {} failed: {:?}, sending the full name", query_name, response_code);
+          minimize = false;
+          continue
+        },
+        ResponseCode::NXDomain => return Ok(response),
+        response_code => return Err(ClientErrorKind::ErrorResponse(response_code).into()),
+      }
+
+      if !minimized && !response.get_answers().is_empty() { return Ok(response) }
+
+      // without answers, this is either a referral or NODATA
+      let referral = match referral(&zone, &query_name, &response) {
+        Ok(referral) => referral,
+        Err(error) => if minimized {
+          debug!("minimized query for {} failed: {}, sending the full name", query_name, error);
+          minimize = false;
+          continue
+        } else {
+          return Err(error)
+        },
+      };
+
+      let (child, ns_names, ttl) = match referral {
+        Some(referral) => referral,
+        // the minimized name is not a delegation from this zone, reveal the next label
+        None if minimized => continue,
+        None => return Ok(response),
+      };
+
+      referrals += 1;
+      if referrals > MAX_REFERRALS { break }
+
+      let addresses = try!(self.name_server_addresses(&child, &ns_names, &response, query_class, ns_depth));
+      if addresses.is_empty() {
+        return Err(ClientErrorKind::Message("no addresses for the name servers of the delegation").into())
+      }
+
+      self.cache_delegation(child.clone(), addresses.clone(), ttl);
+      labels = child.num_labels() as usize;
+      zone = child;
+      name_servers = addresses;
+    }
+
+    Err(ClientErrorKind::Message("too many referrals").into())
+  }
+
+  /// the deepest cached delegation which contains the name, or the root
+  fn closest_delegation(&self, name: &Name) -> (Name, Vec<SocketAddr>) {
+    let now = Instant::now();
+    let mut delegations = self.delegations.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    for num_labels in (1..(name.num_labels() as usize + 1)).rev() {
+      let zone = name.trim_to(num_labels);
+
+      let expired = match delegations.get(&zone) {
+        Some(delegation) if delegation.expires > now => return (zone, delegation.name_servers.clone()),
+        Some(_) => true,
+        None => false,
+      };
+
+      if expired { delegations.remove(&zone); }
+    }
+
+    (Name::root(), self.roots.clone())
+  }
+
+  fn cache_delegation(&self, zone: Name, name_servers: Vec<SocketAddr>, ttl: u32) {
+    let delegation = Delegation{ name_servers: name_servers, expires: Instant::now() + Duration::from_secs(ttl as u64) };
+    self.delegations.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(zone, delegation);
+  }
+
+  /// queries each of the name servers in turn, returning the first response, or the last error
+  fn query_name_servers(&self, name_servers: &[SocketAddr], name: &Name, query_class: DNSClass, query_type: RecordType) -> ClientResult<Message> {
+    let mut last_error: Option<ClientError> = None;
+
+    for name_server in name_servers {
+      let mut query = Query::new();
+      query.name(name.clone()).query_class(query_class).query_type(query_type);
+
+      let result = self.connector.connect(*name_server)
+                                 .and_then(|connection| Client::new(connection).lookup_with_flags(query, QueryFlags::iterative()));
+
+      match result {
+        Ok(response) => return Ok(response),
+        Err(error) => {
+          debug!("name server {} failed: {}", name_server, error);
+          last_error = Some(error);
+        },
+      }
+    }
+
+    Err(last_error.unwrap_or_else(|| ClientErrorKind::Message("no name servers to query").into()))
+  }
+
+  /// The addresses of the name servers of the delegation, from the glue of the referral, or else
+  ///  resolved from the names of the name servers.
+  fn name_server_addresses(&self, zone: &Name, ns_names: &[Name], referral: &Message, query_class: DNSClass, ns_depth: usize) -> ClientResult<Vec<SocketAddr>> {
+    let glue = addresses(ns_names, referral.get_additional());
+    if !glue.is_empty() { return Ok(glue) }
+
+    if ns_depth >= MAX_NS_DEPTH {
+      return Err(ClientErrorKind::Message("too many nested name server resolutions").into())
+    }
+
+    // names within the zone can't be resolved without glue, the referral would be repeated
+    for ns_name in ns_names.iter().filter(|ns_name| !zone.zone_of(ns_name)) {
+      debug!("resolving name server without glue: {}", ns_name);
+      match self.resolve_name(ns_name, query_class, RecordType::A, ns_depth + 1) {
+        Ok(response) => {
+          let resolved = addresses(&[ns_name.clone()], response.get_answers());
+          if !resolved.is_empty() { return Ok(resolved) }
+        },
+        Err(error) => debug!("could not resolve name server {}: {}", ns_name, error),
+      }
+    }
+
+    Ok(vec![])
+  }
+}
+
+/// Follows the CNAMEs at the name within the answers. Returns the name at which the resolution
+///  must be restarted, or None if the answers contain the records of the final name, or no CNAME.
+fn chain_target(name: &Name, query_type: RecordType, answers: &[Record], visited: &mut HashSet<Name>) -> ClientResult<Option<Name>> {
+  let mut current = name.clone();
+
+  loop {
+    if answers.iter().any(|r| r.get_rr_type() == query_type && r.get_name() == &current) { return Ok(None) }
+
+    let target = answers.iter().filter(|r| r.get_name() == &current).filter_map(|r| {
+      if let RData::CNAME(ref target) = *r.get_rdata() { Some(target.clone()) } else { None }
+    }).next();
+
+    match target {
+      Some(target) => {
+        if !visited.insert(target.clone()) { return Err(ClientErrorKind::CnameLoop(target).into()) }
+        current = target;
+      },
+      // no CNAME at the name itself means there was nothing to follow
+      None if current == *name => return Ok(None),
+      None => return Ok(Some(current)),
+    }
+  }
+}
+
+/// Finds the referral of the response, the zone below `zone` which contains the name, along with
+///  the names of its name servers and the minimum TTL of the NS records.
+fn referral(zone: &Name, name: &Name, response: &Message) -> ClientResult<Option<(Name, Vec<Name>, u32)>> {
+  // the NS records of an authoritative response are those of the zone itself
+  if response.is_authoritative() { return Ok(None) }
+
+  let ns_records: Vec<&Record> = response.get_name_servers().iter().filter(|r| r.get_rr_type() == RecordType::NS).collect();
+  let child = match ns_records.first() {
+    Some(record) => record.get_name().clone(),
+    None => return Ok(None),
+  };
+
+  if !child.zone_of(name) || !zone.zone_of(&child) || child.num_labels() <= zone.num_labels() {
+    return Err(ClientErrorKind::Message("referral does not lead closer to the name").into())
+  }
+
+  let mut ttl = u32::max_value();
+  let mut ns_names = vec![];
+  for record in ns_records.iter().filter(|r| r.get_name() == &child) {
+    if let RData::NS(ref ns_name) = *record.get_rdata() {
+      ttl = cmp::min(ttl, record.get_ttl());
+      ns_names.push(ns_name.clone());
+    }
+  }
+
+  Ok(Some((child, ns_names, ttl)))
+}
+
+/// the addresses of the A and AAAA records of the names, IPv4 first
+fn addresses(names: &[Name], records: &[Record]) -> Vec<SocketAddr> {
+  let mut v4 = vec![];
+  let mut v6 = vec![];
+
+  for record in records.iter().filter(|r| names.contains(r.get_name())) {
+    match *record.get_rdata() {
+      RData::A(ip) => v4.push(SocketAddr::new(IpAddr::V4(ip), 53)),
+      RData::AAAA(ip) => v6.push(SocketAddr::new(IpAddr::V6(ip), 53)),
+      _ => (),
+    }
+  }
+
+  v4.extend(v6);
+  v4
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::HashMap;
+  use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+  use std::sync::{Arc, Mutex};
+
+  use ::client::ClientConnection;
+  use ::error::*;
+  use ::op::{Message, MessageType, ResponseCode};
+  use ::rr::{Name, RData, Record, RecordType};
+  use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+  use super::{Connector, Recursor};
+
+  /// a minimal authoritative server of the zones, answering with the records, referrals to the
+  ///  delegations with in-bailiwick glue, or NXDOMAIN
+  #[derive(Debug, Clone)]
+  struct TestServer {
+    zones: Vec<Name>,
+    records: Vec<Record>,
+    queries: Arc<Mutex<Vec<(Name, RecordType)>>>,
+  }
+
+  impl TestServer {
+    fn handle(&self, request: &Message) -> Message {
+      let query = &request.get_queries()[0];
+      let name = query.get_name();
+
+      self.queries.lock().unwrap().push((name.clone(), query.get_query_type()));
+
+      let mut response = Message::new();
+      response.id(request.get_id()).message_type(MessageType::Response).add_all_queries(request.get_queries());
+
+      let delegation: Vec<&Record> = self.records.iter()
+                                         .filter(|r| r.get_rr_type() == RecordType::NS && r.get_name().zone_of(name))
+                                         .filter(|r| !self.zones.contains(r.get_name()))
+                                         .collect();
+      if !delegation.is_empty() {
+        response.add_all_name_servers(&delegation);
+        for ns in &delegation {
+          if let RData::NS(ref target) = *ns.get_rdata() {
+            if !ns.get_name().zone_of(target) { continue }
+            for glue in self.records.iter().filter(|r| r.get_name() == target && r.get_rr_type() == RecordType::A) {
+              response.add_additional(glue.clone());
+            }
+          }
+        }
+        return response
+      }
+
+      let answers: Vec<&Record> = self.records.iter()
+                                      .filter(|r| r.get_name() == name)
+                                      .filter(|r| r.get_rr_type() == query.get_query_type() || r.get_rr_type() == RecordType::CNAME)
+                                      .collect();
+      if !answers.is_empty() {
+        response.authoritative(true).add_all_answers(&answers);
+        return response
+      }
+
+      // empty non-terminals are incorrectly NXDOMAIN, as with some broken servers
+      response.authoritative(true);
+      if !self.records.iter().any(|r| r.get_name() == name) { response.response_code(ResponseCode::NXDomain); }
+      response
+    }
+  }
+
+  impl ClientConnection for TestServer {
+    fn send(&mut self, bytes: Vec<u8>) -> ClientResult<Vec<u8>> {
+      let request = try!(Message::read(&mut BinDecoder::new(&bytes)));
+      assert!(!request.is_recursion_desired());
+
+      let mut buf = Vec::new();
+      {
+        let mut encoder = BinEncoder::new(&mut buf);
+        try!(self.handle(&request).emit(&mut encoder));
+      }
+      Ok(buf)
+    }
+  }
+
+  struct TestConnector {
+    servers: HashMap<SocketAddr, TestServer>,
+  }
+
+  impl Connector for TestConnector {
+    type Connection = TestServer;
+
+    fn connect(&self, name_server: SocketAddr) -> ClientResult<TestServer> {
+      self.servers.get(&name_server).cloned().ok_or(ClientErrorKind::Message("unknown server").into())
+    }
+  }
+
+  fn name(name: &str) -> Name {
+    Name::parse(name, None).unwrap()
+  }
+
+  fn record(owner: &str, rdata: RData) -> Record {
+    let record_type = match rdata {
+      RData::A(..) => RecordType::A,
+      RData::NS(..) => RecordType::NS,
+      RData::CNAME(..) => RecordType::CNAME,
+      _ => panic!("unexpected rdata"),
+    };
+
+    let mut record = Record::with(name(owner), record_type, 3600);
+    record.rdata(rdata);
+    record
+  }
+
+  fn addr(last: u8) -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10,0,0,last)), 53)
+  }
+
+  /// root at 10.0.0.1, com. at 10.0.0.2 and example.com. and other.com. at 10.0.0.3, the referral
+  ///  to other.com. has no glue
+  fn recursor() -> (Recursor<TestConnector>, Arc<Mutex<Vec<(Name, RecordType)>>>) {
+    let queries = Arc::new(Mutex::new(vec![]));
+    let mut servers = HashMap::new();
+    servers.insert(addr(1), TestServer{ zones: vec![Name::root()], queries: queries.clone(), records: vec![
+      record("com.", RData::NS(name("a.gtld-servers.com."))),
+      record("a.gtld-servers.com.", RData::A(Ipv4Addr::new(10,0,0,2))),
+    ]});
+    servers.insert(addr(2), TestServer{ zones: vec![name("com.")], queries: queries.clone(), records: vec![
+      record("example.com.", RData::NS(name("ns.example.com."))),
+      record("ns.example.com.", RData::A(Ipv4Addr::new(10,0,0,3))),
+      record("other.com.", RData::NS(name("ns.example.com."))),
+    ]});
+    servers.insert(addr(3), TestServer{ zones: vec![name("example.com."), name("other.com.")], queries: queries.clone(), records: vec![
+      record("ns.example.com.", RData::A(Ipv4Addr::new(10,0,0,3))),
+      record("www.example.com.", RData::A(Ipv4Addr::new(93,184,216,34))),
+      record("alias.example.com.", RData::CNAME(name("www.other.com."))),
+      record("www.other.com.", RData::A(Ipv4Addr::new(192,0,2,1))),
+      record("loop1.example.com.", RData::CNAME(name("loop2.other.com."))),
+      record("loop2.other.com.", RData::CNAME(name("loop1.example.com."))),
+      record("a.empty.example.com.", RData::A(Ipv4Addr::new(192,0,2,2))),
+    ]});
+
+    (Recursor::with_connector(vec![addr(1)], TestConnector{ servers: servers }), queries)
+  }
+
+  #[test]
+  fn test_resolve() {
+    let (recursor, _) = recursor();
+
+    let response = recursor.resolve(&name("www.example.com."), ::rr::DNSClass::IN, RecordType::A).expect("resolve failed");
+    assert_eq!(response.get_answers().len(), 1);
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(93,184,216,34)));
+    assert_eq!(recursor.get_delegation(&name("example.com.")), Some(vec![addr(3)]));
+    assert_eq!(recursor.get_delegation(&name("com.")), Some(vec![addr(2)]));
+
+    let response = recursor.resolve(&name("nx.example.com."), ::rr::DNSClass::IN, RecordType::A).expect("resolve failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+  }
+
+  #[test]
+  fn test_cname_restart_without_glue() {
+    let (recursor, _) = recursor();
+
+    // www.other.com. is delegated without glue, its name server is resolved first
+    let response = recursor.resolve(&name("alias.example.com."), ::rr::DNSClass::IN, RecordType::A).expect("resolve failed");
+    assert_eq!(response.get_answers().len(), 2);
+    assert_eq!(response.get_answers()[0].get_rr_type(), RecordType::CNAME);
+    assert_eq!(response.get_answers()[1].get_rdata(), &RData::A(Ipv4Addr::new(192,0,2,1)));
+    assert_eq!(response.get_queries()[0].get_name(), &name("alias.example.com."));
+    assert_eq!(recursor.get_delegation(&name("other.com.")), Some(vec![addr(3)]));
+  }
+
+  #[test]
+  fn test_cname_loop() {
+    let (recursor, _) = recursor();
+
+    match *recursor.resolve(&name("loop1.example.com."), ::rr::DNSClass::IN, RecordType::A).unwrap_err().kind() {
+      ClientErrorKind::CnameLoop(..) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_qname_minimization() {
+    let (recursor, queries) = recursor();
+
+    recursor.resolve(&name("www.example.com."), ::rr::DNSClass::IN, RecordType::A).expect("resolve failed");
+    assert_eq!(*queries.lock().unwrap(), vec![(name("com."), RecordType::NS),
+                                              (name("example.com."), RecordType::NS),
+                                              (name("www.example.com."), RecordType::A)]);
+
+    // empty.example.com. is NXDOMAIN from the broken server, the full name is sent instead
+    queries.lock().unwrap().clear();
+    let response = recursor.resolve(&name("a.empty.example.com."), ::rr::DNSClass::IN, RecordType::A).expect("resolve failed");
+    assert_eq!(response.get_answers()[0].get_rdata(), &RData::A(Ipv4Addr::new(192,0,2,2)));
+    assert_eq!(*queries.lock().unwrap(), vec![(name("empty.example.com."), RecordType::NS),
+                                              (name("a.empty.example.com."), RecordType::A)]);
+  }
+
+  #[test]
+  fn test_without_qname_minimization() {
+    let (mut recursor, queries) = recursor();
+    recursor.set_qname_minimization(false);
+
+    recursor.resolve(&name("www.example.com."), ::rr::DNSClass::IN, RecordType::A).expect("resolve failed");
+    assert!(queries.lock().unwrap().iter().all(|&(ref query, _)| query == &name("www.example.com.")));
+  }
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for `resolv.conf(5)`
+
+use std::cmp;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use ::error::*;
+use ::resolver::ResolverConfig;
+use ::rr::Name;
+
+// the limits of the glibc resolver
+const MAX_NDOTS: usize = 15;
+const MAX_TIMEOUT: u64 = 30;
+const MAX_ATTEMPTS: usize = 5;
+
+/// Reads the `nameserver`, `domain`, `search` and `options` lines of a resolv.conf, the last of
+///  `domain` and `search` takes precedence. Malformed and unknown lines are ignored with a
+///  warning, as the libc resolver does.
+///
+/// The `port` and `timeout` lines of the macOS `resolver(5)` files are also supported.
+pub fn read_resolv_conf<R: Read>(reader: R) -> ConfigResult<ResolverConfig> {
+  let mut config = ResolverConfig::new();
+  let mut name_servers: Vec<IpAddr> = vec![];
+  let mut port: u16 = 53;
+
+  for line in BufReader::new(reader).lines() {
+    let line = try!(line);
+    let line = line.split(|c| c == '#' || c == ';').next().unwrap_or("");
+    let mut fields = line.split_whitespace();
+
+    let keyword = match fields.next() {
+      Some(keyword) => keyword,
+      None => continue,
+    };
+
+    match keyword {
+      "nameserver" => {
+        // link local addresses may have a scope, e.g. fe80::1%eth0, which is not supported
+        match fields.next().and_then(|ip| ip.parse::<IpAddr>().ok()) {
+          Some(ip) => name_servers.push(ip),
+          None => warn!("invalid nameserver in resolv.conf: {}", line),
+        }
+      },
+      "domain" => {
+        let domain = fields.next().and_then(|domain| parse_name(domain));
+        config.domain(domain).search(vec![]);
+      },
+      "search" => {
+        let search: Vec<Name> = fields.filter_map(parse_name).collect();
+        config.domain(None).search(search);
+      },
+      "port" => {
+        match fields.next().and_then(|port| port.parse::<u16>().ok()) {
+          Some(value) => port = value,
+          None => warn!("invalid port in resolv.conf: {}", line),
+        }
+      },
+      "timeout" => {
+        if let Some(timeout) = fields.next() { read_option(&mut config, &format!("timeout:{}", timeout)); }
+      },
+      "options" => {
+        for option in fields {
+          read_option(&mut config, option);
+        }
+      },
+      _ => debug!("ignoring resolv.conf line: {}", line),
+    }
+  }
+
+  for ip in name_servers {
+    config.add_name_server(SocketAddr::new(ip, port));
+  }
+
+  Ok(config)
+}
+
+fn parse_name(name: &str) -> Option<Name> {
+  match Name::parse(name, Some(&Name::root())) {
+    Ok(name) => Some(name),
+    Err(error) => {
+      warn!("invalid domain in resolv.conf: {}: {}", name, error);
+      None
+    },
+  }
+}
+
+fn read_option(config: &mut ResolverConfig, option: &str) {
+  let mut split = option.splitn(2, ':');
+  let name = split.next().unwrap_or("");
+  let value = split.next().and_then(|value| value.parse::<usize>().ok());
+
+  match (name, value) {
+    ("ndots", Some(ndots)) => { config.ndots(cmp::min(ndots, MAX_NDOTS)); },
+    ("timeout", Some(timeout)) => { config.timeout(Duration::from_secs(cmp::min(cmp::max(timeout as u64, 1), MAX_TIMEOUT))); },
+    ("attempts", Some(attempts)) => { config.attempts(cmp::min(cmp::max(attempts, 1), MAX_ATTEMPTS)); },
+    ("rotate", None) => { config.rotate(true); },
+    _ => debug!("ignoring resolv.conf option: {}", option),
+  }
+}
+
+#[test]
+fn test_read_resolv_conf() {
+  let resolv_conf = b"
+# generated by NetworkManager
+domain corp.example.com
+search example.com example.net
+nameserver 192.0.2.1
+nameserver 2001:db8::1 ; the second
+nameserver not-an-address
+options ndots:2 timeout:1 attempts:9 rotate edns0
+";
+
+  let config = read_resolv_conf(&resolv_conf[..]).unwrap();
+  assert_eq!(config.get_name_servers(), &["192.0.2.1:53".parse().unwrap(), "[2001:db8::1]:53".parse().unwrap()]);
+  assert_eq!(config.get_domain(), None);
+  assert_eq!(config.get_search(), vec![Name::parse("example.com.", None).unwrap(), Name::parse("example.net.", None).unwrap()]);
+  assert_eq!(config.get_ndots(), 2);
+  assert_eq!(config.get_timeout(), Duration::from_secs(1));
+  assert_eq!(config.get_attempts(), MAX_ATTEMPTS);
+  assert!(config.is_rotate());
+}
+
+#[test]
+fn test_resolver_file() {
+  let config = read_resolv_conf(&b"nameserver 192.0.2.53\nport 5353\ntimeout 2\n"[..]).unwrap();
+  assert_eq!(config.get_name_servers(), &["192.0.2.53:5353".parse().unwrap()]);
+  assert_eq!(config.get_timeout(), Duration::from_secs(2));
+}
+
+#[test]
+fn test_domain_search() {
+  let config = read_resolv_conf(&b"search example.net\ndomain example.com\n"[..]).unwrap();
+  assert_eq!(config.get_search(), vec![Name::parse("example.com.", None).unwrap()]);
+
+  let config = read_resolv_conf(&b""[..]).unwrap();
+  assert!(config.get_search().is_empty());
+  assert_eq!(config.get_ndots(), 1);
+}
@@ -0,0 +1,387 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The stub resolver, lookups of the names given by users with the system configuration
+
+use std::net::IpAddr;
+
+use ::client::{AddressOrder, Client, ClientConnection, Dns64, LookupIpStrategy, MailExchange, NameServerPool, QueryLimiter, ResponseCache, ServiceLookup, SharedCache};
+use ::error::*;
+use ::op::{Message, ResponseCode};
+use ::resolver::{Hosts, Overrides, ResolverConfig};
+use ::rr::{DNSClass, Name, RData, RecordType};
+use ::tls::OpportunisticConnection;
+use ::udp::UdpClientConnection;
+
+/// the number of entries in the cache of `Resolver::new()`
+const CACHE_SIZE: usize = 1024;
+/// the CNAME chain depth followed by `lookup()`
+const MAX_CNAME_DEPTH: usize = 8;
+
+/// A stub resolver, the names given to the lookups are expanded with the search list of the
+///  configuration, and each is resolved with the client of the most specific scoped domain which
+///  contains it, or the default client. Names in the hosts are answered without querying.
+///
+/// ```no_run
+/// use trust_dns::resolver::Resolver;
+///
+/// let resolver = Resolver::from_system().unwrap();
+/// let addresses = resolver.lookup_ip("www.example.com").unwrap();
+/// ```
+pub struct Resolver<C: ClientConnection> {
+  config: ResolverConfig,
+  client: Client<C>,
+  scoped: Vec<(Name, Client<C>)>,
+  address_order: AddressOrder,
+  ip_strategy: LookupIpStrategy,
+}
+
+impl Resolver<NameServerPool<UdpClientConnection>> {
+  /// Creates a resolver of the configuration, with a cache shared by the name server pools, the
+  ///  default and those of the scoped domains
+  pub fn new(config: ResolverConfig) -> ClientResult<Self> {
+    Self::with_cache(config, SharedCache::new(ResponseCache::new(CACHE_SIZE)))
+  }
+
+  /// Creates a resolver of the configuration which uses the cache, the cache may be shared with
+  ///  other resolvers in the process.
+  ///
+  /// ```no_run
+  /// use trust_dns::client::{ResponseCache, SharedCache};
+  /// use trust_dns::resolver::{Resolver, ResolverConfig};
+  ///
+  /// let mut cache = ResponseCache::new(4096);
+  /// cache.min_ttl(30).max_memory(Some(4 * 1024 * 1024));
+  /// let cache = SharedCache::new(cache);
+  ///
+  /// let system = Resolver::with_cache(ResolverConfig::from_system().unwrap(), cache.clone()).unwrap();
+  /// let other = Resolver::with_cache(ResolverConfig::from_system().unwrap(), cache.clone()).unwrap();
+  /// ```
+  pub fn with_cache(config: ResolverConfig, cache: SharedCache) -> ClientResult<Self> {
+    let mut resolver = Resolver::with_client(config.clone(), try!(config.client()));
+    for &(ref domain, ref scoped_config) in config.get_scoped() {
+      resolver.add_scoped(domain.clone(), try!(scoped_config.client()));
+    }
+
+    resolver.set_cache(Some(cache));
+    Ok(resolver)
+  }
+
+  /// Creates a resolver of the system configuration, see `ResolverConfig::from_system()`, and
+  ///  the system hosts file, which is watched for changes
+  pub fn from_system() -> ClientResult<Self> {
+    let mut resolver = try!(Self::new(try!(ResolverConfig::from_system())));
+
+    match Hosts::from_system() {
+      Ok(mut hosts) => {
+        hosts.set_watch(true);
+        resolver.set_hosts(Some(hosts));
+      },
+      Err(error) => warn!("could not read the hosts file: {}", error),
+    }
+
+    Ok(resolver)
+  }
+}
+
+impl Resolver<NameServerPool<OpportunisticConnection>> {
+  /// Creates a resolver of the configuration whose connections are upgraded to DNS over TLS, see
+  ///  `ResolverConfig::tls()`, with a cache as with `new()`
+  ///
+  /// ```no_run
+  /// use trust_dns::resolver::{Resolver, ResolverConfig};
+  /// use trust_dns::tls::TlsMode;
+  ///
+  /// let mut config = ResolverConfig::from_system().unwrap();
+  /// config.tls(TlsMode::Opportunistic);
+  /// let resolver = Resolver::with_tls(config).unwrap();
+  /// ```
+  pub fn with_tls(config: ResolverConfig) -> ClientResult<Self> {
+    let mut resolver = Resolver::with_client(config.clone(), try!(config.tls_client()));
+    for &(ref domain, ref scoped_config) in config.get_scoped() {
+      resolver.add_scoped(domain.clone(), try!(scoped_config.tls_client()));
+    }
+
+    resolver.set_cache(Some(SharedCache::new(ResponseCache::new(CACHE_SIZE))));
+    Ok(resolver)
+  }
+}
+
+impl<C: ClientConnection> Resolver<C> {
+  /// Creates a resolver which resolves all names with the client, the name servers of the
+  ///  configuration are not used, only the search list. If the configuration validates, so does
+  ///  the client, e.g. one created with `Client::with_trust_anchor()`.
+  ///
+  /// # Arguments
+  ///
+  /// * `config` - the configuration for the expansion of names
+  /// * `client` - the client for all names not in a scoped domain, see `add_scoped()`
+  pub fn with_client(config: ResolverConfig, client: Client<C>) -> Self {
+    let mut client = client;
+    client.set_max_cname_depth(MAX_CNAME_DEPTH);
+    if config.is_validate() { client.set_validating(true) }
+    Resolver{ config: config, client: client, scoped: vec![], address_order: AddressOrder::default(), ip_strategy: LookupIpStrategy::default() }
+  }
+
+  /// Adds a client for the names of a domain, e.g. the internal domain of a VPN. The client
+  ///  validates if the configuration of the resolver does.
+  pub fn add_scoped(&mut self, domain: Name, client: Client<C>) -> &mut Self {
+    let mut client = client;
+    client.set_max_cname_depth(MAX_CNAME_DEPTH);
+    if self.config.is_validate() { client.set_validating(true) }
+    self.scoped.push((domain, client));
+    self
+  }
+
+  /// Sets the hosts which are consulted before querying, for all of the clients
+  pub fn set_hosts(&mut self, hosts: Option<Hosts>) {
+    self.client.set_hosts(hosts.clone());
+    for &mut (_, ref mut client) in self.scoped.iter_mut() {
+      client.set_hosts(hosts.clone());
+    }
+  }
+
+  /// Sets the static answers of names for all of the clients, see `Client::set_overrides()`
+  pub fn set_overrides(&mut self, overrides: Option<Overrides>) {
+    self.client.set_overrides(overrides.clone());
+    for &mut (_, ref mut client) in self.scoped.iter_mut() {
+      client.set_overrides(overrides.clone());
+    }
+  }
+
+  /// Sets the limit on the requests in flight, shared by the default and scoped clients, see
+  ///  `Client::set_limiter()`
+  pub fn set_limiter(&mut self, limiter: Option<QueryLimiter>) {
+    self.client.set_limiter(limiter.clone());
+    for &mut (_, ref mut client) in self.scoped.iter_mut() {
+      client.set_limiter(limiter.clone());
+    }
+  }
+
+  /// Sets the cache of the default and scoped clients, None disables caching
+  pub fn set_cache(&mut self, cache: Option<SharedCache>) {
+    self.client.set_shared_cache(cache.clone());
+    for &mut (_, ref mut client) in self.scoped.iter_mut() {
+      client.set_shared_cache(cache.clone());
+    }
+  }
+
+  /// Sets the DNS64 synthesis of the default and scoped clients, see `Client::set_dns64()`
+  pub fn set_dns64(&mut self, dns64: Option<Dns64>) {
+    self.client.set_dns64(dns64.clone());
+    for &mut (_, ref mut client) in self.scoped.iter_mut() {
+      client.set_dns64(dns64.clone());
+    }
+  }
+
+  /// The cache of the default client, e.g. to inspect its statistics
+  pub fn get_cache(&self) -> Option<&SharedCache> {
+    self.client.get_cache()
+  }
+
+  /// Refreshes the popular entries of the cache which are about to expire, each with the client of
+  ///  its name, see `Client::prefetch()`. Returns the number of entries which were refreshed.
+  pub fn prefetch(&self) -> usize {
+    let prefetches = match self.get_cache() {
+      Some(cache) => cache.take_prefetches(),
+      None => return 0,
+    };
+
+    prefetches.iter().filter(|&&(ref name, query_class, query_type)| {
+      self.client_for(name).refresh(name, query_class, query_type).is_ok()
+    }).count()
+  }
+
+  /// The order of the IPv4 and IPv6 addresses of `lookup_ip()`, the default is IPv4 first
+  pub fn set_address_order(&mut self, address_order: AddressOrder) {
+    self.address_order = address_order;
+  }
+
+  /// The address families queried by `lookup_ip()`, the default is both IPv4 and IPv6
+  pub fn set_ip_strategy(&mut self, ip_strategy: LookupIpStrategy) {
+    self.ip_strategy = ip_strategy;
+  }
+
+  pub fn get_config(&self) -> &ResolverConfig {
+    &self.config
+  }
+
+  /// The client for the name, of the most specific scoped domain which contains the name
+  fn client_for(&self, name: &Name) -> &Client<C> {
+    self.scoped.iter()
+               .filter(|&&(ref domain, _)| domain.zone_of(name))
+               .max_by_key(|&&(ref domain, _)| domain.num_labels())
+               .map_or(&self.client, |&(_, ref client)| client)
+  }
+
+  /// Calls the lookup with each of the search names of the name, returning the first results
+  ///  which are not empty
+  fn search<T, F>(&self, name: &str, lookup: F) -> ClientResult<Vec<T>>
+    where F: Fn(&Client<C>, &Name) -> ClientResult<Vec<T>> {
+    for search_name in try!(self.config.search_names(name)) {
+      let results = try!(lookup(self.client_for(&search_name), &search_name));
+      if !results.is_empty() { return Ok(results) }
+    }
+
+    Ok(vec![])
+  }
+
+  /// Queries the records of the name, the response of the first search name with answers is
+  ///  returned, or else the response of the last search name
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - the name as given by the user, e.g. `db01`, see `ResolverConfig::search_names()`
+  /// * `record_type` - the type of the records
+  pub fn lookup(&self, name: &str, record_type: RecordType) -> ClientResult<Message> {
+    let mut last: Option<Message> = None;
+
+    for search_name in try!(self.config.search_names(name)) {
+      let response = try!(self.client_for(&search_name).query(&search_name, DNSClass::IN, record_type));
+      if response.get_response_code() == ResponseCode::NoError && !response.get_answers().is_empty() {
+        return Ok(response)
+      }
+      last = Some(response);
+    }
+
+    last.ok_or(ClientErrorKind::Message("no names to query").into())
+  }
+
+  /// The IPv4 and IPv6 addresses of the name, of the families of `set_ip_strategy()`, empty if it
+  ///  has none
+  pub fn lookup_ip(&self, name: &str) -> ClientResult<Vec<IpAddr>> {
+    let (address_order, ip_strategy) = (self.address_order, self.ip_strategy);
+    self.search(name, |client, search_name| client.lookup_ip_with_strategy(search_name, ip_strategy, address_order))
+  }
+
+  /// The names of the address, from its PTR records
+  pub fn reverse_lookup(&self, addr: IpAddr) -> ClientResult<Vec<Name>> {
+    self.client_for(&Name::from(addr)).reverse_lookup(addr)
+  }
+
+  /// The mail exchanges of the name, sorted by preference, with their addresses
+  pub fn mx_lookup(&self, name: &str) -> ClientResult<Vec<MailExchange>> {
+    self.search(name, |client, search_name| client.mx_lookup(search_name, true))
+  }
+
+  /// The text of each of the TXT records of the name
+  pub fn txt_lookup(&self, name: &str) -> ClientResult<Vec<String>> {
+    let response = try!(self.lookup(name, RecordType::TXT));
+    Ok(response.get_answers().iter().filter_map(|record| {
+      if let RData::TXT(ref txt) = *record.get_rdata() { Some(txt.get_txt_data().concat()) } else { None }
+    }).collect())
+  }
+
+  /// The SRV records of the service of the name, see `Client::srv_lookup()`
+  pub fn srv_lookup(&self, service: &str, protocol: &str, name: &str) -> ClientResult<ServiceLookup> {
+    let mut last: Option<ServiceLookup> = None;
+
+    for search_name in try!(self.config.search_names(name)) {
+      let lookup = try!(self.client_for(&search_name).srv_lookup(service, protocol, &search_name));
+      if !lookup.is_empty() { return Ok(lookup) }
+      last = Some(lookup);
+    }
+
+    last.ok_or(ClientErrorKind::Message("no names to query").into())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::{IpAddr, Ipv4Addr};
+
+  use ::authority::Catalog;
+  use ::authority::authority_tests::create_example;
+  use ::client::{Client, ResponseCache, SharedCache, TestClientConnection};
+  use ::error::*;
+  use ::resolver::{Hosts, ResolverConfig};
+  use ::rr::{Name, RecordType};
+  use super::Resolver;
+
+  #[test]
+  fn test_resolver() {
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut config = ResolverConfig::new();
+    config.search(vec![Name::parse("example.com.", None).unwrap()]);
+
+    let mut resolver = Resolver::with_client(config, Client::new(TestClientConnection::new(&catalog)));
+    let addresses = resolver.lookup_ip("www").expect("lookup failed");
+    assert_eq!(addresses[0], IpAddr::V4(Ipv4Addr::new(93,184,216,34)));
+    assert_eq!(addresses.len(), 2);
+
+    assert!(resolver.lookup_ip("nx").expect("lookup failed").is_empty());
+    assert_eq!(resolver.txt_lookup("www").expect("lookup failed"), vec!["v=spf1 -all".to_string()]);
+    assert_eq!(resolver.lookup("www", RecordType::A).expect("lookup failed").get_answers().len(), 1);
+
+    resolver.set_hosts(Some(Hosts::read(&b"192.0.2.1 db01.example.com"[..]).unwrap()));
+    assert_eq!(resolver.lookup_ip("db01").expect("lookup failed"), vec![IpAddr::V4(Ipv4Addr::new(192,0,2,1))]);
+  }
+
+  #[test]
+  fn test_scoped() {
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+    let empty = Catalog::new();
+
+    // the scoped domain is resolved with a server which does not know example.com
+    let mut resolver = Resolver::with_client(ResolverConfig::new(), Client::new(TestClientConnection::new(&catalog)));
+    resolver.add_scoped(Name::parse("www.example.com.", None).unwrap(), Client::new(TestClientConnection::new(&empty)));
+
+    assert!(resolver.lookup_ip("www.example.com").expect("lookup failed").is_empty());
+    assert!(!resolver.lookup_ip("example.com").expect("lookup failed").is_empty());
+  }
+
+  #[test]
+  fn test_shared_cache() {
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+    let empty = Catalog::new();
+
+    let cache = SharedCache::new(ResponseCache::new(16));
+    let mut first = Resolver::with_client(ResolverConfig::new(), Client::new(TestClientConnection::new(&catalog)));
+    first.set_cache(Some(cache.clone()));
+
+    // the second resolver's server knows nothing, its answer comes from the shared cache
+    let mut second = Resolver::with_client(ResolverConfig::new(), Client::new(TestClientConnection::new(&empty)));
+    second.set_cache(Some(cache.clone()));
+
+    assert!(!first.lookup_ip("www.example.com").expect("lookup failed").is_empty());
+    assert!(!second.lookup_ip("www.example.com").expect("lookup failed").is_empty());
+    assert!(cache.get_stats().get_hits() > 0);
+  }
+
+  #[test]
+  fn test_validating() {
+    let authority = create_example();
+    let catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    // the example zone is not signed
+    let mut config = ResolverConfig::new();
+    config.validate(true);
+    let resolver = Resolver::with_client(config, Client::new(TestClientConnection::new(&catalog)));
+
+    match *resolver.lookup_ip("www.example.com").unwrap_err().kind() {
+      ClientErrorKind::Bogus(..) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+  }
+}
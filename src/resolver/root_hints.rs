@@ -0,0 +1,186 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The names and addresses of the root servers, from which the `Recursor` starts
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+use ::error::*;
+use ::rr::Name;
+
+/// The root servers of [named.root](https://www.internic.net/domain/named.root), last updated
+///  November 2023 for the renumbering of b.root-servers.net
+const ROOT_SERVERS: &'static [(&'static str, &'static str, &'static str)] = &[
+  ("a.root-servers.net.", "198.41.0.4", "2001:503:ba3e::2:30"),
+  ("b.root-servers.net.", "170.247.170.2", "2801:1b8:10::b"),
+  ("c.root-servers.net.", "192.33.4.12", "2001:500:2::c"),
+  ("d.root-servers.net.", "199.7.91.13", "2001:500:2d::d"),
+  ("e.root-servers.net.", "192.203.230.10", "2001:500:a8::e"),
+  ("f.root-servers.net.", "192.5.5.241", "2001:500:2f::f"),
+  ("g.root-servers.net.", "192.112.36.4", "2001:500:12::d0d"),
+  ("h.root-servers.net.", "198.97.190.53", "2001:500:1::53"),
+  ("i.root-servers.net.", "192.36.148.17", "2001:7fe::53"),
+  ("j.root-servers.net.", "192.58.128.30", "2001:503:c27::2:30"),
+  ("k.root-servers.net.", "193.0.14.129", "2001:7fd::1"),
+  ("l.root-servers.net.", "199.7.83.42", "2001:500:9f::42"),
+  ("m.root-servers.net.", "202.12.27.33", "2001:dc3::35"),
+];
+
+/// The root servers and their addresses. The default is compiled in, operators may supply their
+///  own in the format of BIND's `named.root`, e.g. for a private root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootHints {
+  name_servers: Vec<(Name, Vec<IpAddr>)>,
+}
+
+impl RootHints {
+  /// Reads the root hints in the master file format of `named.root`, the NS records of the root
+  ///  along with the A and AAAA records of those names. Other records are ignored, as are
+  ///  malformed lines, with a warning.
+  ///
+  /// ```text
+  /// .                        3600000      NS    A.ROOT-SERVERS.NET.
+  /// A.ROOT-SERVERS.NET.      3600000      A     198.41.0.4
+  /// A.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:ba3e::2:30
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - the contents of the root hints file
+  pub fn read<R: Read>(reader: R) -> ConfigResult<Self> {
+    let mut names: Vec<Name> = vec![];
+    let mut addresses: HashMap<Name, Vec<IpAddr>> = HashMap::new();
+
+    for line in BufReader::new(reader).lines() {
+      let line = try!(line);
+      let fields: Vec<&str> = line.split(';').next().unwrap_or("").split_whitespace().collect();
+      if fields.is_empty() || fields[0].starts_with('$') { continue }
+
+      // the owner, then the optional TTL and class, then the type and data
+      let mut index = 1;
+      while index < fields.len() && (fields[index].parse::<u32>().is_ok() || fields[index].to_uppercase() == "IN") {
+        index += 1;
+      }
+
+      if index + 1 >= fields.len() {
+        warn!("invalid line in root hints: {}", line);
+        continue
+      }
+
+      let owner = match Name::parse(fields[0], Some(&Name::root())) {
+        Ok(owner) => owner,
+        Err(error) => {
+          warn!("invalid name in root hints: {}: {}", fields[0], error);
+          continue
+        },
+      };
+
+      let data = fields[index + 1];
+      match &fields[index].to_uppercase() as &str {
+        "NS" if owner.is_root() => match Name::parse(data, Some(&Name::root())) {
+          Ok(name) => if !names.contains(&name) { names.push(name) },
+          Err(error) => warn!("invalid name in root hints: {}: {}", data, error),
+        },
+        "A" | "AAAA" => match data.parse::<IpAddr>() {
+          Ok(addr) => addresses.entry(owner).or_insert_with(Vec::new).push(addr),
+          Err(_) => warn!("invalid address in root hints: {}", line),
+        },
+        _ => debug!("ignoring record in root hints: {}", line),
+      }
+    }
+
+    let name_servers: Vec<(Name, Vec<IpAddr>)> = names.into_iter().filter_map(|name| {
+      let addrs = addresses.remove(&name);
+      addrs.map(|addrs| (name, addrs))
+    }).collect();
+
+    if name_servers.is_empty() {
+      return Err(ConfigErrorKind::Msg("no root servers with addresses in the root hints".to_string()).into())
+    }
+
+    Ok(RootHints{ name_servers: name_servers })
+  }
+
+  /// Reads the root hints file at the path, see `read()`
+  pub fn open(path: &Path) -> ConfigResult<Self> {
+    Self::read(try!(File::open(path)))
+  }
+
+  /// The root servers, with their addresses, in the order of the hints
+  pub fn get_name_servers(&self) -> &[(Name, Vec<IpAddr>)] {
+    &self.name_servers
+  }
+
+  /// The addresses of all of the root servers on port 53, the IPv4 addresses first
+  pub fn get_socket_addrs(&self) -> Vec<SocketAddr> {
+    let addrs = || self.name_servers.iter().flat_map(|&(_, ref addrs)| addrs.iter());
+    addrs().filter(|addr| addr.is_ipv4())
+           .chain(addrs().filter(|addr| addr.is_ipv6()))
+           .map(|addr| SocketAddr::new(*addr, 53))
+           .collect()
+  }
+}
+
+impl Default for RootHints {
+  fn default() -> Self {
+    let name_servers = ROOT_SERVERS.iter().map(|&(name, v4, v6)| {
+      (Name::parse(name, None).expect("invalid root server name"),
+       vec![v4.parse().expect("invalid root server address"), v6.parse().expect("invalid root server address")])
+    }).collect();
+
+    RootHints{ name_servers: name_servers }
+  }
+}
+
+#[test]
+fn test_default() {
+  let hints = RootHints::default();
+  assert_eq!(hints.get_name_servers().len(), 13);
+
+  let addrs = hints.get_socket_addrs();
+  assert_eq!(addrs.len(), 26);
+  assert!(addrs[0].ip().is_ipv4());
+  assert!(addrs[25].ip().is_ipv6());
+}
+
+#[test]
+fn test_read() {
+  let named_root = b";       This file holds the information on root name servers
+;
+.                        3600000      NS    A.ROOT-SERVERS.NET.
+A.ROOT-SERVERS.NET.      3600000      A     198.41.0.4
+A.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:ba3e::2:30
+;
+; OPERATED BY EXAMPLE
+;
+.                        3600000 IN   NS    NS.EXAMPLE.
+NS.EXAMPLE.              3600000 IN   A     192.0.2.53
+; no addresses
+.                        3600000      NS    MISSING.EXAMPLE.
+; END OF FILE
+";
+
+  let hints = RootHints::read(&named_root[..]).expect("failed to read hints");
+  let names: Vec<Name> = hints.get_name_servers().iter().map(|&(ref name, _)| name.clone()).collect();
+  assert_eq!(names, vec![Name::parse("a.root-servers.net.", None).unwrap(), Name::parse("ns.example.", None).unwrap()]);
+  assert_eq!(hints.get_socket_addrs(), vec!["198.41.0.4:53".parse().unwrap(), "192.0.2.53:53".parse().unwrap(), "[2001:503:ba3e::2:30]:53".parse().unwrap()]);
+
+  assert!(RootHints::read(&b"; empty\n"[..]).is_err());
+}
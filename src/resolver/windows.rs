@@ -0,0 +1,102 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The system DNS configuration of Windows, read from the registry
+
+#[cfg(windows)]
+use std::net::SocketAddr;
+use std::net::IpAddr;
+
+#[cfg(windows)]
+use winreg::RegKey;
+#[cfg(windows)]
+use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ};
+
+#[cfg(windows)]
+use ::error::*;
+#[cfg(windows)]
+use ::resolver::ResolverConfig;
+use ::rr::Name;
+
+#[cfg(windows)]
+const TCPIP_PARAMETERS: &'static str = "SYSTEM\\CurrentControlSet\\Services\\Tcpip\\Parameters";
+#[cfg(windows)]
+const TCPIP6_PARAMETERS: &'static str = "SYSTEM\\CurrentControlSet\\Services\\Tcpip6\\Parameters";
+
+/// Reads the DNS servers of each network adapter, statically configured servers take precedence
+///  over those from DHCP, and the suffix search list, or else the primary DNS suffix.
+#[cfg(windows)]
+pub fn read_system_config() -> ConfigResult<ResolverConfig> {
+  let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+  let mut config = ResolverConfig::new();
+
+  for parameters_path in &[TCPIP_PARAMETERS, TCPIP6_PARAMETERS] {
+    let interfaces = match hklm.open_subkey_with_flags(format!("{}\\Interfaces", parameters_path), KEY_READ) {
+      Ok(interfaces) => interfaces,
+      Err(error) => {
+        debug!("no interfaces in {}: {}", parameters_path, error);
+        continue
+      },
+    };
+
+    for interface_name in interfaces.enum_keys() {
+      let interface = try!(interfaces.open_subkey_with_flags(try!(interface_name), KEY_READ));
+      let static_servers: String = interface.get_value("NameServer").unwrap_or(String::new());
+      let dhcp_servers: String = interface.get_value("DhcpNameServer").unwrap_or(String::new());
+
+      let servers = if static_servers.trim().is_empty() { dhcp_servers } else { static_servers };
+      for ip in parse_address_list(&servers) {
+        let name_server = SocketAddr::new(ip, 53);
+        if !config.get_name_servers().contains(&name_server) { config.add_name_server(name_server); }
+      }
+    }
+  }
+
+  let parameters = try!(hklm.open_subkey_with_flags(TCPIP_PARAMETERS, KEY_READ));
+  let search_list: String = parameters.get_value("SearchList").unwrap_or(String::new());
+  let domain: String = parameters.get_value("Domain").unwrap_or(String::new());
+  let dhcp_domain: String = parameters.get_value("DhcpDomain").unwrap_or(String::new());
+
+  let domain = if domain.trim().is_empty() { dhcp_domain } else { domain };
+  config.domain(parse_name_list(&domain).into_iter().next()).search(parse_name_list(&search_list));
+
+  Ok(config)
+}
+
+/// the registry lists addresses separated by commas, or by spaces for DHCP
+fn parse_address_list(addresses: &str) -> Vec<IpAddr> {
+  addresses.split(|c: char| c == ',' || c.is_whitespace())
+           .filter(|address| !address.is_empty())
+           .filter_map(|address| address.parse().ok())
+           .collect()
+}
+
+fn parse_name_list(names: &str) -> Vec<Name> {
+  names.split(|c: char| c == ',' || c.is_whitespace())
+       .filter(|name| !name.is_empty())
+       .filter_map(|name| Name::parse(name, Some(&Name::root())).ok())
+       .collect()
+}
+
+#[test]
+fn test_parse_lists() {
+  assert_eq!(parse_address_list("192.0.2.1,192.0.2.2"), vec!["192.0.2.1".parse::<IpAddr>().unwrap(), "192.0.2.2".parse().unwrap()]);
+  assert_eq!(parse_address_list("192.0.2.1 2001:db8::1 "), vec!["192.0.2.1".parse::<IpAddr>().unwrap(), "2001:db8::1".parse().unwrap()]);
+  assert!(parse_address_list("").is_empty());
+
+  assert_eq!(parse_name_list("corp.example.com,example.com"),
+             vec![Name::parse("corp.example.com.", None).unwrap(), Name::parse("example.com.", None).unwrap()]);
+}
@@ -22,6 +22,7 @@ mod nsec3;
 mod signer;
 mod supported_algorithm;
 mod trust_anchor;
+mod tsig_key;
 
 pub use self::algorithm::Algorithm;
 pub use self::digest_type::DigestType;
@@ -29,3 +30,4 @@ pub use self::nsec3::Nsec3HashAlgorithm;
 pub use self::signer::Signer;
 pub use self::supported_algorithm::SupportedAlgorithms;
 pub use self::trust_anchor::TrustAnchor;
+pub use self::tsig_key::{TsigAlgorithm, TsigKey, append_tsig, split_tsig};
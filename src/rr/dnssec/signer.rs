@@ -476,18 +476,14 @@ impl Signer {
         //                OrigTTL is the value from the RRSIG Original TTL field
         assert!(encoder.emit_u32(original_ttl).is_ok());
         //
-        //                RDATA length
-        // TODO: add support to the encoder to set a marker to go back and write the length
-        let mut rdata_buf = Vec::new();
-        {
-          let mut rdata_encoder = BinEncoder::new(&mut rdata_buf);
-          rdata_encoder.set_canonical_names(true);
-          assert!(record.get_rdata().emit(&mut rdata_encoder).is_ok());
-        }
-        assert!(encoder.emit_u16(rdata_buf.len() as u16).is_ok());
+        //                RDATA length, filled in once the RDATA has been written
+        let length_index = encoder.len();
+        assert!(encoder.emit_u16(0).is_ok());
         //
         //                All names in the RDATA field are in canonical form (set above)
-        assert!(encoder.emit_vec(&rdata_buf).is_ok());
+        assert!(record.get_rdata().emit(&mut encoder).is_ok());
+        let length = encoder.len() - length_index - 2;
+        encoder.set_u16_at(length_index, length as u16);
       }
     }
 
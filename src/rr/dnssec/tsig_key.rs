@@ -0,0 +1,342 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! shared secret keys for TSIG, RFC 2845, transaction signatures
+
+use std::cmp::Ordering;
+
+use openssl::crypto::hash;
+use openssl::crypto::hmac;
+
+use ::error::*;
+use ::op::Header;
+use ::op::Query;
+use ::rr::{DNSClass, Name, Record, RecordType, RData};
+use ::rr::rdata::TSIG;
+use ::rr::rdata::tsig::emit_u48;
+use ::serialize::binary::*;
+
+/// The HMAC algorithms for TSIG, [RFC 4635](https://tools.ietf.org/html/rfc4635)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TsigAlgorithm {
+  HmacMd5,    // hmac-md5.sig-alg.reg.int.  RFC 2845
+  HmacSha1,   // hmac-sha1.                 RFC 4635
+  HmacSha256, // hmac-sha256.               RFC 4635
+  HmacSha512, // hmac-sha512.               RFC 4635
+}
+
+impl TsigAlgorithm {
+  /// the name of the algorithm as it appears in the TSIG record
+  pub fn to_name(&self) -> Name {
+    match *self {
+      TsigAlgorithm::HmacMd5 => Name::new().label("hmac-md5").label("sig-alg").label("reg").label("int"),
+      TsigAlgorithm::HmacSha1 => Name::new().label("hmac-sha1"),
+      TsigAlgorithm::HmacSha256 => Name::new().label("hmac-sha256"),
+      TsigAlgorithm::HmacSha512 => Name::new().label("hmac-sha512"),
+    }
+  }
+
+  pub fn from_name(name: &Name) -> DecodeResult<Self> {
+    for algorithm in &[TsigAlgorithm::HmacMd5, TsigAlgorithm::HmacSha1, TsigAlgorithm::HmacSha256, TsigAlgorithm::HmacSha512] {
+      if algorithm.to_name().cmp_with_case(name, true) == Ordering::Equal { return Ok(*algorithm) }
+    }
+
+    Err(DecodeErrorKind::Msg(format!("unknown tsig algorithm: {}", name)).into())
+  }
+
+  pub fn to_hash(&self) -> hash::Type {
+    match *self {
+      TsigAlgorithm::HmacMd5 => hash::Type::MD5,
+      TsigAlgorithm::HmacSha1 => hash::Type::SHA1,
+      TsigAlgorithm::HmacSha256 => hash::Type::SHA256,
+      TsigAlgorithm::HmacSha512 => hash::Type::SHA512,
+    }
+  }
+}
+
+/// A shared secret used to sign and verify messages with TSIG.
+///
+/// The signing operates on the serialized message, the TSIG record is appended to the message
+///  bytes, this way the MAC covers exactly the bytes which were sent, including any name
+///  compression.
+#[derive(Clone)]
+pub struct TsigKey {
+  name: Name,
+  algorithm: TsigAlgorithm,
+  secret: Vec<u8>,
+  fudge: u16,
+}
+
+impl TsigKey {
+  /// Creates a new key, the fudge defaults to 300 seconds as recommended by RFC 2845
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - the name of the key, this must match the name configured on the server
+  /// * `algorithm` - the HMAC algorithm
+  /// * `secret` - the shared secret, already decoded from base64
+  pub fn new(name: Name, algorithm: TsigAlgorithm, secret: Vec<u8>) -> Self {
+    TsigKey{ name: name, algorithm: algorithm, secret: secret, fudge: 300 }
+  }
+
+  pub fn get_name(&self) -> &Name { &self.name }
+  pub fn get_algorithm(&self) -> TsigAlgorithm { self.algorithm }
+  /// seconds of error permitted between the time signed and the time verified
+  pub fn get_fudge(&self) -> u16 { self.fudge }
+
+  pub fn set_fudge(&mut self, fudge: u16) { self.fudge = fudge; }
+
+  /// Signs the serialized message, returning the message with the TSIG record appended and the
+  ///  MAC, which is needed to verify the response.
+  ///
+  /// # Arguments
+  ///
+  /// * `message` - the serialized message, without a TSIG record
+  /// * `request_mac` - when signing a response, the MAC of the request
+  /// * `time_signed` - the current time, in seconds since the epoch
+  pub fn sign(&self, message: &[u8], request_mac: Option<&[u8]>, time_signed: u64) -> EncodeResult<(Vec<u8>, Vec<u8>)> {
+    if message.len() < 12 { return Err(EncodeErrorKind::Msg("message is too short to sign".to_string()).into()) }
+    let id: u16 = (message[0] as u16) << 8 | message[1] as u16;
+
+    let unsigned = TSIG::new(self.algorithm.to_name(), time_signed, self.fudge, vec![], id, 0, vec![]);
+    let mac = try!(self.mac(request_mac, message, &unsigned, false));
+    let tsig = TSIG::new(self.algorithm.to_name(), time_signed, self.fudge, mac.clone(), id, 0, vec![]);
+
+    let signed = try!(append_tsig(message, &self.to_record(tsig)));
+    Ok((signed, mac))
+  }
+
+  /// Signs a subsequent message of a multiple message response, e.g. a zone transfer, returning
+  ///  the message with the TSIG record appended and its MAC, which is the prior MAC of the next.
+  ///
+  /// # Arguments
+  ///
+  /// * `message` - the serialized message, without a TSIG record
+  /// * `prior_mac` - the MAC of the previous message of the response
+  /// * `time_signed` - the current time, in seconds since the epoch
+  pub fn sign_subsequent(&self, message: &[u8], prior_mac: &[u8], time_signed: u64) -> EncodeResult<(Vec<u8>, Vec<u8>)> {
+    if message.len() < 12 { return Err(EncodeErrorKind::Msg("message is too short to sign".to_string()).into()) }
+    let id: u16 = (message[0] as u16) << 8 | message[1] as u16;
+
+    let unsigned = TSIG::new(self.algorithm.to_name(), time_signed, self.fudge, vec![], id, 0, vec![]);
+    let mac = try!(self.mac(Some(prior_mac), message, &unsigned, true));
+    let tsig = TSIG::new(self.algorithm.to_name(), time_signed, self.fudge, mac.clone(), id, 0, vec![]);
+
+    let signed = try!(append_tsig(message, &self.to_record(tsig)));
+    Ok((signed, mac))
+  }
+
+  /// Computes the MAC, [RFC 2845](https://tools.ietf.org/html/rfc2845#section-3.4)
+  ///
+  /// ```text
+  /// 4.4. TSIG on TCP connection
+  ///
+  ///    A DNS TCP session can include multiple DNS envelopes.  This is, for
+  ///    example, commonly used by zone transfer.  Using TSIG on such a
+  ///    connection can protect the connection from hijacking and provide data
+  ///    integrity.  The TSIG MUST be included on the first and last DNS
+  ///    envelopes.  It can be optionally placed on any intermediary
+  ///    envelopes.  It is expensive to include it on every envelopes, but it
+  ///    MUST be placed on at least every 100'th envelope.  The first envelope
+  ///    is processed as a standard answer, and subsequent messages have the
+  ///    following digest components:
+  ///
+  ///    *   Prior Digest (running)
+  ///    *   DNS Messages (any unsigned messages since the last TSIG)
+  ///    *   TSIG Timers (current message)
+  /// ```
+  ///
+  /// # Arguments
+  ///
+  /// * `prior_mac` - the MAC of the request, or of the prior message in a multiple message response
+  /// * `messages` - the serialized messages without their TSIG records, and the original id
+  /// * `tsig` - the TSIG of the message, the MAC itself is ignored
+  /// * `timers_only` - true for all but the first message of a multiple message response
+  pub fn mac(&self, prior_mac: Option<&[u8]>, messages: &[u8], tsig: &TSIG, timers_only: bool) -> EncodeResult<Vec<u8>> {
+    let mut digest: Vec<u8> = Vec::with_capacity(messages.len() + 128);
+    {
+      let mut encoder = BinEncoder::new(&mut digest);
+      if let Some(prior_mac) = prior_mac {
+        try!(encoder.emit_u16(prior_mac.len() as u16));
+        try!(encoder.emit_vec(prior_mac));
+      }
+
+      try!(encoder.emit_vec(messages));
+
+      if !timers_only {
+        try!(self.name.to_lowercase().emit_as_canonical(&mut encoder, true));
+        try!(DNSClass::ANY.emit(&mut encoder));
+        try!(encoder.emit_u32(0)); // ttl
+        try!(tsig.get_algorithm().to_lowercase().emit_as_canonical(&mut encoder, true));
+      }
+
+      try!(emit_u48(&mut encoder, tsig.get_time_signed()));
+      try!(encoder.emit_u16(tsig.get_fudge()));
+
+      if !timers_only {
+        try!(encoder.emit_u16(tsig.get_error()));
+        try!(encoder.emit_u16(tsig.get_other().len() as u16));
+        try!(encoder.emit_vec(tsig.get_other()));
+      }
+    }
+
+    Ok(hmac::hmac(self.algorithm.to_hash(), &self.secret, &digest))
+  }
+
+  /// Verifies the MAC of the TSIG, see `mac()` for the arguments. The comparison is constant time.
+  pub fn verify(&self, prior_mac: Option<&[u8]>, messages: &[u8], tsig: &TSIG, timers_only: bool) -> EncodeResult<bool> {
+    let mac = try!(self.mac(prior_mac, messages, tsig, timers_only));
+    Ok(constant_time_eq(&mac, tsig.get_mac()))
+  }
+
+  /// true if the time signed is within the fudge of the current time
+  pub fn is_time_valid(tsig: &TSIG, now: u64) -> bool {
+    let signed = tsig.get_time_signed();
+    let fudge = tsig.get_fudge() as u64;
+    signed <= now + fudge && now <= signed + fudge
+  }
+
+  /// creates the TSIG record for this key
+  pub fn to_record(&self, tsig: TSIG) -> Record {
+    let mut record = Record::new();
+    record.name(self.name.clone()).rr_type(RecordType::TSIG).dns_class(DNSClass::ANY).ttl(0).rdata(RData::TSIG(tsig));
+    record
+  }
+}
+
+/// true if the MACs are equal, every byte is compared whether or not an earlier one differed, so
+///  that the time taken does not reveal how much of a forged MAC was correct
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() { return false }
+
+  a.iter().zip(b.iter()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Appends the TSIG record to the serialized message, incrementing the additional count
+pub fn append_tsig(message: &[u8], record: &Record) -> EncodeResult<Vec<u8>> {
+  if message.len() < 12 { return Err(EncodeErrorKind::Msg("message is too short to sign".to_string()).into()) }
+
+  let mut signed: Vec<u8> = message.to_vec();
+  let additional_count: u16 = ((signed[10] as u16) << 8 | signed[11] as u16) + 1;
+  signed[10] = (additional_count >> 8) as u8;
+  signed[11] = additional_count as u8;
+
+  // the record is encoded separately, it must not be compressed against the message
+  let mut tsig_bytes: Vec<u8> = Vec::with_capacity(128);
+  {
+    let mut encoder = BinEncoder::new(&mut tsig_bytes);
+    try!(record.emit(&mut encoder));
+  }
+
+  signed.extend_from_slice(&tsig_bytes);
+  Ok(signed)
+}
+
+/// Splits the TSIG record from the end of the serialized message. The returned message bytes have
+///  the TSIG removed, the additional count decremented and the id replaced with the original id,
+///  as is required for verifying the MAC.
+///
+/// Returns None if the message is not signed with TSIG.
+pub fn split_tsig(message: &[u8]) -> DecodeResult<Option<(Vec<u8>, Record)>> {
+  let mut decoder = BinDecoder::new(message);
+  let header = try!(Header::read(&mut decoder));
+  if header.get_additional_count() == 0 { return Ok(None) }
+
+  for _ in 0..header.get_query_count() {
+    try!(Query::read(&mut decoder));
+  }
+
+  let record_count = header.get_answer_count() as usize + header.get_name_server_count() as usize + header.get_additional_count() as usize;
+  for _ in 0..(record_count - 1) {
+    try!(Record::read(&mut decoder));
+  }
+
+  let tsig_start = decoder.index();
+  let record = try!(Record::read(&mut decoder));
+  let original_id = match *record.get_rdata() {
+    RData::TSIG(ref tsig) => tsig.get_original_id(),
+    _ => return Ok(None),
+  };
+
+  let mut unsigned: Vec<u8> = message[..tsig_start].to_vec();
+  let additional_count = header.get_additional_count() - 1;
+  unsigned[0] = (original_id >> 8) as u8;
+  unsigned[1] = original_id as u8;
+  unsigned[10] = (additional_count >> 8) as u8;
+  unsigned[11] = additional_count as u8;
+
+  Ok(Some((unsigned, record)))
+}
+
+#[cfg(test)]
+mod test {
+  use ::op::{Message, MessageType, OpCode, Query};
+  use ::rr::{DNSClass, Name, RData, RecordType};
+  use ::serialize::binary::{BinEncoder, BinSerializable};
+  use super::*;
+
+  fn message_bytes() -> Vec<u8> {
+    let mut query = Query::new();
+    query.name(Name::new().label("www").label("example").label("com")).query_type(RecordType::A).query_class(DNSClass::IN);
+
+    let mut message = Message::new();
+    message.id(1234).message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    let mut bytes = Vec::new();
+    {
+      let mut encoder = BinEncoder::new(&mut bytes);
+      message.emit(&mut encoder).unwrap();
+    }
+    bytes
+  }
+
+  #[test]
+  fn test_sign_and_verify() {
+    let key = TsigKey::new(Name::new().label("tsig-key"), TsigAlgorithm::HmacSha256, b"secret".to_vec());
+    let message = message_bytes();
+
+    let (signed, mac) = key.sign(&message, None, 1471010000).unwrap();
+    let (unsigned, record) = split_tsig(&signed).unwrap().expect("not signed");
+    assert_eq!(unsigned, message);
+
+    let tsig = if let RData::TSIG(ref tsig) = *record.get_rdata() { tsig.clone() } else { panic!("not a tsig") };
+    assert_eq!(tsig.get_mac(), &mac[..]);
+    assert!(key.verify(None, &unsigned, &tsig, false).unwrap());
+    assert!(TsigKey::is_time_valid(&tsig, 1471010100));
+    assert!(!TsigKey::is_time_valid(&tsig, 1471010301));
+
+    let other = TsigKey::new(Name::new().label("tsig-key"), TsigAlgorithm::HmacSha256, b"other".to_vec());
+    assert!(!other.verify(None, &unsigned, &tsig, false).unwrap());
+
+    assert!(split_tsig(&message).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_constant_time_eq() {
+    assert!(constant_time_eq(&[], &[]));
+    assert!(constant_time_eq(&[1, 2, 3], &[1, 2, 3]));
+    assert!(!constant_time_eq(&[1, 2, 3], &[1, 2, 4]));
+    assert!(!constant_time_eq(&[0, 2, 3], &[1, 2, 3]));
+    assert!(!constant_time_eq(&[1, 2, 3], &[1, 2]));
+  }
+
+  #[test]
+  fn test_algorithm_names() {
+    assert_eq!(TsigAlgorithm::from_name(&Name::parse("HMAC-SHA256.", None).unwrap()).unwrap(), TsigAlgorithm::HmacSha256);
+    assert_eq!(TsigAlgorithm::from_name(&Name::parse("hmac-md5.sig-alg.reg.int.", None).unwrap()).unwrap(), TsigAlgorithm::HmacMd5);
+    assert!(TsigAlgorithm::from_name(&Name::parse("hmac-bogus.", None).unwrap()).is_err());
+  }
+}
@@ -16,19 +16,42 @@
 
 //! domain name, aka labels, implementaton
 
+use std::ascii::AsciiExt;
 use std::ops::Index;
 use std::sync::Arc as Rc;
 use std::fmt;
 use std::cmp::{Ordering, PartialEq};
 use std::char;
 use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use rand::Rng;
+use rand;
 
 use ::serialize::binary::*;
 use ::error::*;
-
-/// TODO: all Names should be stored in a global "intern" space, and then everything that uses
-///  them should be through references. As a workaround the Strings are all Rc as well as the array
-/// TODO: Currently this probably doesn't support binary names, it would be nice to do that.
+use super::punycode::{self, ACE_PREFIX};
+
+// TODO: all Names should be stored in a global "intern" space, and then everything that uses
+//  them should be through references. As a workaround the Strings are all Rc as well as the array
+// TODO: Currently this probably doesn't support binary names, it would be nice to do that.
+
+/// A domain name, a list of labels from the first to the last before the root.
+///
+/// The case of the labels is preserved, e.g. for display and the verification of 0x20 encoding,
+///  but names are compared, hashed and ordered ignoring the case of ASCII letters, as in
+///  [RFC 4343](https://tools.ietf.org/html/rfc4343). Other octets are compared as they are.
+///  `to_lowercase()` is the canonical form of RFC 4034, `eq_case()` compares with case.
+///
+/// ```
+/// use trust_dns::rr::domain::Name;
+///
+/// let name = Name::parse("WWW.Example.com.", None).unwrap();
+/// assert_eq!(name, Name::parse("www.example.com.", None).unwrap());
+/// assert!(!name.eq_case(&Name::parse("www.example.com.", None).unwrap()));
+/// assert_eq!(name.to_string(), "WWW.Example.com.");
+/// ```
 #[derive(Debug, Eq, Clone)]
 pub struct Name {
   labels: Rc<Vec<Rc<String>>>
@@ -87,7 +110,7 @@ impl Name {
     self
   }
 
-  /// appends the other to this name
+  /// appends the other to this name, e.g. a relative name to its origin, or a search domain
   pub fn append(&mut self, other: &Self) -> &mut Self {
     for rcs in &*other.labels {
       self.add_label(rcs.clone());
@@ -96,7 +119,8 @@ impl Name {
     self
   }
 
-  /// Creates a new Name with all labels lowercased
+  /// Creates a new Name with the ASCII letters of all labels lowercased, the canonical form of
+  ///  names, RFC 4034
   ///
   /// ```
   /// use trust_dns::rr::domain::Name;
@@ -108,7 +132,30 @@ impl Name {
   pub fn to_lowercase(&self) -> Name {
     let mut new_labels = Vec::with_capacity(self.labels.len());
     for label in self.labels.iter() {
-      new_labels.push(label.to_lowercase());
+      new_labels.push(label.to_ascii_lowercase());
+    }
+
+    Self::with_labels(new_labels)
+  }
+
+  /// Creates a new Name with the case of each ASCII letter randomized, for use with 0x20
+  ///  encoding, see [draft-vixie-dnsext-dns0x20](https://tools.ietf.org/html/draft-vixie-dnsext-dns0x20-00)
+  ///
+  /// ```
+  /// use trust_dns::rr::domain::Name;
+  ///
+  /// let example_com = Name::new().label("example").label("com");
+  /// assert_eq!(example_com.randomize_label_case(), example_com);
+  /// ```
+  pub fn randomize_label_case(&self) -> Name {
+    let mut rand = rand::thread_rng();
+    let mut new_labels = Vec::with_capacity(self.labels.len());
+    for label in self.labels.iter() {
+      new_labels.push(label.chars().map(|c| match c {
+        'a' ... 'z' if rand.gen() => ((c as u8) - 32) as char,
+        'A' ... 'Z' if rand.gen() => ((c as u8) + 32) as char,
+        _ => c,
+      }).collect());
     }
 
     Self::with_labels(new_labels)
@@ -154,21 +201,51 @@ impl Name {
 
   /// returns true if the name components of self are all present at the end of name
   pub fn zone_of(&self, name: &Self) -> bool {
-    let self_len = self.labels.len();
-    let name_len = name.labels.len();
-    let self_lower = self.to_lowercase();
-    let name_lower = name.to_lowercase();
-
-    // TODO: there's probably a better way using iterators directly, but it wasn't obvious
-    for i in 1..(self_len+1) {
-      if self_lower.labels.get(self_len - i) != name_lower.labels.get(name_len - i) {
-        return false;
-      }
-    }
+    if self.labels.len() > name.labels.len() { return false }
 
-    return true;
+    self.labels.iter().rev().zip(name.labels.iter().rev())
+                      .all(|(l, r)| cmp_label(l, r, true) == Ordering::Equal)
   }
 
+  /// returns true if the name is in the zone, or is the zone, the reverse of `zone_of()`
+  ///
+  /// ```
+  /// use trust_dns::rr::domain::Name;
+  ///
+  /// let zone = Name::parse("example.com.", None).unwrap();
+  /// assert!(Name::parse("www.Example.com.", None).unwrap().is_subdomain_of(&zone));
+  /// assert!(zone.is_subdomain_of(&zone));
+  /// assert!(!Name::parse("example.net.", None).unwrap().is_subdomain_of(&zone));
+  /// ```
+  pub fn is_subdomain_of(&self, zone: &Self) -> bool {
+    zone.zone_of(self)
+  }
+
+  /// The name of the parent, the name without its first label, none for the root
+  ///
+  /// ```
+  /// use trust_dns::rr::domain::Name;
+  ///
+  /// let www = Name::parse("www.example.com.", None).unwrap();
+  /// assert_eq!(www.parent(), Some(Name::parse("example.com.", None).unwrap()));
+  /// assert_eq!(Name::root().parent(), None);
+  /// ```
+  pub fn parent(&self) -> Option<Name> {
+    if self.is_root() { None } else { Some(self.base_name()) }
+  }
+
+  /// returns true if the first label is the wildcard, `*`
+  pub fn is_wildcard(&self) -> bool {
+    self.labels.first().map_or(false, |label| label.as_str() == "*")
+  }
+
+  /// The number of labels, including a wildcard label, the root is not counted
+  pub fn label_count(&self) -> usize {
+    self.labels.len()
+  }
+
+  /// The number of labels as in the labels field of an RRSIG, RFC 4034, which does not count a
+  ///  wildcard label or the root, see `label_count()` for all of the labels
   pub fn num_labels(&self) -> u8 {
     // it is illegal to have more than 256 labels.
     let num = self.labels.len() as u8;
@@ -186,6 +263,8 @@ impl Name {
   }
 
   pub fn parse(local: &str, origin: Option<&Self>) -> ParseResult<Self> {
+    if local == "." { return Ok(Self::root()) }
+
     let mut name = Name::new();
     let mut label = String::new();
     // split the local part
@@ -220,7 +299,7 @@ impl Name {
         },
         ParseState::Escape3(i, ii) => {
           if ch.is_numeric() {
-            let val: u32 = (i << 16) + (ii << 8) + try!(ch.to_digit(10).ok_or(ParseError::from(ParseErrorKind::Msg(format!("illegal char: {}", ch)))));
+            let val: u32 = i * 100 + ii * 10 + try!(ch.to_digit(10).ok_or(ParseError::from(ParseErrorKind::Msg(format!("illegal char: {}", ch)))));
             let new: char = try!(char::from_u32(val).ok_or(ParseError::from(ParseErrorKind::Msg(format!("illegal char: {}", ch)))));
             label.push(new);
             state = ParseState::Label;
@@ -245,6 +324,118 @@ impl Name {
     Ok(name)
   }
 
+  /// Parses an internationalized name, converting the labels which are not ASCII to punycode
+  ///  with the ACE prefix, see [RFC 3490](https://tools.ietf.org/html/rfc3490). The name is fully
+  ///  qualified, with or without the final dot, the labels may also be separated by the ideographic
+  ///  full stops of RFC 3490.
+  ///
+  /// Labels which are not ASCII are lowercased before they are encoded, Unicode normalization, as
+  ///  in nameprep, is not performed, the name should be in NFC. ASCII labels are kept as they are.
+  ///
+  /// ```
+  /// use trust_dns::rr::domain::Name;
+  ///
+  /// let name = Name::from_utf8("Bücher.example").unwrap();
+  /// assert_eq!(name, Name::parse("xn--bcher-kva.example.", None).unwrap());
+  /// assert_eq!(name.to_utf8(), "bücher.example.");
+  /// ```
+  pub fn from_utf8(name: &str) -> ParseResult<Name> {
+    fn is_full_stop(c: char) -> bool {
+      match c { '.' | '\u{3002}' | '\u{FF0E}' | '\u{FF61}' => true, _ => false }
+    }
+
+    let name = match name.char_indices().last() {
+      Some((index, c)) if is_full_stop(c) => &name[..index],
+      _ => name,
+    };
+    if name.is_empty() { return Ok(Self::root()) }
+
+    let mut labels: Vec<String> = Vec::new();
+    for label in name.split(is_full_stop) {
+      if label.is_empty() { return Err(ParseErrorKind::Message("empty label").into()) }
+
+      if label.is_ascii() {
+        labels.push(label.to_string());
+      } else {
+        let ascii = format!("{}{}", ACE_PREFIX, try!(punycode::encode(&label.to_lowercase())));
+        if ascii.len() > 63 { return Err(ParseErrorKind::InvalidIdna(label.to_string()).into()) }
+        labels.push(ascii);
+      }
+    }
+
+    Ok(Self::with_labels(labels))
+  }
+
+  /// The name for display, with the punycode labels decoded to Unicode, labels which are not
+  ///  valid punycode are kept as they are
+  pub fn to_utf8(&self) -> String {
+    let mut utf8 = String::new();
+    for label in self.labels.iter() {
+      let prefix_len = ACE_PREFIX.len();
+      let is_ace = label.len() > prefix_len && label.as_bytes()[..prefix_len].eq_ignore_ascii_case(ACE_PREFIX.as_bytes());
+
+      match if is_ace { punycode::decode(&label[prefix_len..]).ok() } else { None } {
+        Some(unicode) => utf8.push_str(&unicode),
+        None => utf8.push_str(label),
+      }
+      utf8.push('.');
+    }
+
+    if self.is_root() { utf8.push('.'); }
+    utf8
+  }
+
+  /// Reads the labels at the decoder, up to the root or a pointer, returning the location of the
+  ///  pointer, which must be before it
+  fn read_labels(decoder: &mut BinDecoder, labels: &mut Vec<Rc<String>>, length: &mut usize) -> DecodeResult<Option<u16>> {
+    let mut state: LabelParseState = LabelParseState::LabelLengthOrPointer;
+
+    // assume all chars are utf-8. We're doing byte-by-byte operations, no endianess issues...
+    // reserved: (1000 0000 aka 0800) && (0100 0000 aka 0400)
+    // pointer: (slice == 1100 0000 aka C0) & C0 == true, then 03FF & slice = offset
+    // label: 03FF & slice = length; slice.next(length) = label
+    // root: 0000
+    loop {
+      state = match state {
+        LabelParseState::LabelLengthOrPointer => {
+          // determine what the next label is
+          match decoder.peek() {
+            Some(0) | None => LabelParseState::Root,
+            Some(byte) if byte & 0b1100_0000 == 0b1100_0000 => LabelParseState::Pointer,
+            Some(byte) if byte & 0b1100_0000 == 0b0000_0000 => LabelParseState::Label,
+            Some(byte) => return Err(DecodeErrorKind::UnrecognizedLabelCode(byte).into()),
+          }
+        },
+        LabelParseState::Label => {
+          let label = try!(decoder.read_character_data());
+          if label.len() > 63 { return Err(DecodeErrorKind::LabelBytesTooLong(label.len()).into()) }
+
+          *length += label.len() + 1;
+          if *length > 255 { return Err(DecodeErrorKind::DomainNameTooLong(*length).into()) }
+          labels.push(Rc::new(label));
+
+          // reset to collect more data
+          LabelParseState::LabelLengthOrPointer
+        },
+        LabelParseState::Pointer => {
+          let index = decoder.index();
+          let location = try!(decoder.read_u16()) & 0x3FFF; // get rid of the two high order bits
+
+          // pointers to themselves, or after themselves, could loop
+          if location as usize >= index { return Err(DecodeErrorKind::ForwardPointer(location, index).into()) }
+
+          // Pointers always finish the labels, break like Root.
+          return Ok(Some(location))
+        },
+        LabelParseState::Root => {
+          // need to pop() the 0 off the stack...
+          try!(decoder.pop());
+          return Ok(None)
+        }
+      }
+    }
+  }
+
   pub fn emit_as_canonical(&self, encoder: &mut BinEncoder, canonical: bool) -> EncodeResult {
     let buf_len = encoder.len(); // lazily assert the size is less than 255...
     // lookup the label in the BinEncoder
@@ -300,38 +491,52 @@ impl Name {
     }
   }
 
-  /// compares with the other label, ignoring case
-  pub fn cmp_with_case(&self, other: &Self, ignore_case: bool) -> Ordering {
-    if self.labels.is_empty() && other.labels.is_empty() { return Ordering::Equal }
-
-    let mut self_labels: Vec<_> = (*self.labels).clone();
-    let mut other_labels: Vec<_> = (*other.labels).clone();
-
-    self_labels.reverse();
-    other_labels.reverse();
+  /// Writes the name without compression, as the names in the rdata of types defined after
+  ///  RFC 1035 must be written, see RFC 3597, in lowercase if the encoder writes canonical names
+  pub fn emit_uncompressed(&self, encoder: &mut BinEncoder) -> EncodeResult {
+    if encoder.is_canonical_names() {
+      self.to_lowercase().emit_as_canonical(encoder, true)
+    } else {
+      self.emit_as_canonical(encoder, true)
+    }
+  }
 
-    for (l, r) in self_labels.iter().zip(other_labels.iter()) {
-      if ignore_case {
-        match (*l).to_lowercase().cmp(&(*r).to_lowercase()) {
-          o @ Ordering::Less | o @ Ordering::Greater => return o,
-          Ordering::Equal => continue,
-        }
-      } else {
-        match l.cmp(r) {
-          o @ Ordering::Less | o @ Ordering::Greater => return o,
-          Ordering::Equal => continue,
-        }
+  /// Compares the names in canonical order, from the last label to the first, ignoring the case
+  ///  of ASCII letters if ignore_case
+  pub fn cmp_with_case(&self, other: &Self, ignore_case: bool) -> Ordering {
+    for (l, r) in self.labels.iter().rev().zip(other.labels.iter().rev()) {
+      match cmp_label(l, r, ignore_case) {
+        o @ Ordering::Less | o @ Ordering::Greater => return o,
+        Ordering::Equal => continue,
       }
     }
 
     self.labels.len().cmp(&other.labels.len())
   }
+
+  /// Compares the names with case, e.g. to verify that the 0x20 encoding of a query was echoed
+  pub fn eq_case(&self, other: &Self) -> bool {
+    self.cmp_with_case(other, false) == Ordering::Equal
+  }
+}
+
+/// compares the labels as octet strings, the uppercase ASCII letters as lowercase if ignore_case
+fn cmp_label(left: &str, right: &str, ignore_case: bool) -> Ordering {
+  if ignore_case {
+    left.bytes().map(|b| b.to_ascii_lowercase()).cmp(right.bytes().map(|b| b.to_ascii_lowercase()))
+  } else {
+    left.cmp(right)
+  }
 }
 
 impl Hash for Name {
+  /// consistent with eq, the ASCII letters are hashed as lowercase
   fn hash<H>(&self, state: &mut H) where H: Hasher {
     for label in self.labels.iter() {
-      state.write(label.to_lowercase().as_bytes());
+      state.write_usize(label.len());
+      for byte in label.bytes() {
+        state.write_u8(byte.to_ascii_lowercase());
+      }
     }
   }
 }
@@ -352,73 +557,47 @@ enum ParseState {
 impl BinSerializable<Name> for Name {
   /// parses the chain of labels
   ///  this has a max of 255 octets, with each label being less than 63.
-  ///  all names will be stored lowercase internally.
+  ///  the case of the labels is preserved.
   /// This will consume the portions of the Vec which it is reading...
+  ///
+  /// Crafted names are rejected, rather than looping or recursing without end: pointers must point
+  ///  before themselves, at most 127 pointers are followed, and the name may not be longer than
+  ///  255 octets. Labels are at most 63 octets, as the length octets of longer labels are the
+  ///  reserved label types, which are unrecognized label codes.
   fn read(decoder: &mut BinDecoder) -> DecodeResult<Name> {
-    let mut state: LabelParseState = LabelParseState::LabelLengthOrPointer;
     let mut labels: Vec<Rc<String>> = Vec::with_capacity(3); // most labels will be around three, e.g. www.example.com
-
-    // assume all chars are utf-8. We're doing byte-by-byte operations, no endianess issues...
-    // reserved: (1000 0000 aka 0800) && (0100 0000 aka 0400)
-    // pointer: (slice == 1100 0000 aka C0) & C0 == true, then 03FF & slice = offset
-    // label: 03FF & slice = length; slice.next(length) = label
-    // root: 0000
-    loop {
-      state = match state {
-        LabelParseState::LabelLengthOrPointer => {
-          // determine what the next label is
-          match decoder.peek() {
-            Some(0) | None => LabelParseState::Root,
-            Some(byte) if byte & 0b1100_0000 == 0b1100_0000 => LabelParseState::Pointer,
-            Some(byte) if byte & 0b1100_0000 == 0b0000_0000 => LabelParseState::Label,
-            Some(byte) => return Err(DecodeErrorKind::UnrecognizedLabelCode(byte).into()),
-          }
-        },
-        LabelParseState::Label => {
-          labels.push(Rc::new(try!(decoder.read_character_data())));
-
-          // reset to collect more data
-          LabelParseState::LabelLengthOrPointer
-        },
-        //         4.1.4. Message compression
-        //
-        // In order to reduce the size of messages, the domain system utilizes a
-        // compression scheme which eliminates the repetition of domain names in a
-        // message.  In this scheme, an entire domain name or a list of labels at
-        // the end of a domain name is replaced with a pointer to a prior occurance
-        // of the same name.
-        //
-        // The pointer takes the form of a two octet sequence:
-        //
-        //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-        //     | 1  1|                OFFSET                   |
-        //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-        //
-        // The first two bits are ones.  This allows a pointer to be distinguished
-        // from a label, since the label must begin with two zero bits because
-        // labels are restricted to 63 octets or less.  (The 10 and 01 combinations
-        // are reserved for future use.)  The OFFSET field specifies an offset from
-        // the start of the message (i.e., the first octet of the ID field in the
-        // domain header).  A zero offset specifies the first byte of the ID field,
-        // etc.
-        LabelParseState::Pointer => {
-          let location = try!(decoder.read_u16()) & 0x3FFF; // get rid of the two high order bits
-          let mut pointer = decoder.clone(location);
-          let pointed = try!(Name::read(&mut pointer));
-
-          for l in &*pointed.labels {
-            labels.push(l.clone());
-          }
-
-          // Pointers always finish the name, break like Root.
-          break;
-        },
-        LabelParseState::Root => {
-          // need to pop() the 0 off the stack...
-          try!(decoder.pop());
-          break;
-        }
-      }
+    let mut length: usize = 1; // the length of the name as it is written uncompressed, with the root
+
+    //         4.1.4. Message compression
+    //
+    // In order to reduce the size of messages, the domain system utilizes a
+    // compression scheme which eliminates the repetition of domain names in a
+    // message.  In this scheme, an entire domain name or a list of labels at
+    // the end of a domain name is replaced with a pointer to a prior occurance
+    // of the same name.
+    //
+    // The pointer takes the form of a two octet sequence:
+    //
+    //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //     | 1  1|                OFFSET                   |
+    //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //
+    // The first two bits are ones.  This allows a pointer to be distinguished
+    // from a label, since the label must begin with two zero bits because
+    // labels are restricted to 63 octets or less.  (The 10 and 01 combinations
+    // are reserved for future use.)  The OFFSET field specifies an offset from
+    // the start of the message (i.e., the first octet of the ID field in the
+    // domain header).  A zero offset specifies the first byte of the ID field,
+    // etc.
+    let mut pointer = try!(Self::read_labels(decoder, &mut labels, &mut length));
+    let mut pointers: usize = 0;
+    while let Some(location) = pointer {
+      pointers += 1;
+      if pointers > MAX_POINTERS { return Err(DecodeErrorKind::TooManyPointers(pointers).into()) }
+
+      // the original decoder ends after the first pointer
+      let mut pointed = decoder.clone(location);
+      pointer = try!(Self::read_labels(&mut pointed, &mut labels, &mut length));
     }
 
     Ok(Name { labels: Rc::new(labels) })
@@ -430,6 +609,16 @@ impl BinSerializable<Name> for Name {
   }
 }
 
+impl FromStr for Name {
+  type Err = ParseError;
+
+  /// Parses a fully qualified name, the final dot is optional, see `Name::parse()` for names
+  ///  relative to an origin
+  fn from_str(s: &str) -> ParseResult<Name> {
+    Name::parse(s, Some(&Name::root()))
+  }
+}
+
 impl fmt::Display for Name {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     for label in &*self.labels {
@@ -440,6 +629,52 @@ impl fmt::Display for Name {
   }
 }
 
+impl From<IpAddr> for Name {
+  /// Creates the reverse lookup name for the address, in-addr.arpa. for IPv4 and ip6.arpa. for
+  ///  IPv6, [RFC 3596](https://tools.ietf.org/html/rfc3596#section-2.5)
+  ///
+  /// ```text
+  /// 2.5 IP6.ARPA Domain
+  ///
+  ///    An IPv6 address is represented as a name in the IP6.ARPA domain by a
+  ///    sequence of nibbles separated by dots with the suffix ".IP6.ARPA".
+  ///    The sequence of nibbles is encoded in reverse order, i.e., the
+  ///    low-order nibble is encoded first, followed by the next low-order
+  ///    nibble and so on.
+  /// ```
+  ///
+  /// ```
+  /// use std::net::{IpAddr, Ipv4Addr};
+  /// use trust_dns::rr::domain::Name;
+  ///
+  /// let name = Name::from(IpAddr::V4(Ipv4Addr::new(192,0,2,1)));
+  /// assert_eq!(name, Name::parse("1.2.0.192.in-addr.arpa.", None).unwrap());
+  /// ```
+  fn from(addr: IpAddr) -> Name {
+    let mut labels: Vec<String> = Vec::with_capacity(34);
+
+    match addr {
+      IpAddr::V4(ip) => {
+        for octet in ip.octets().iter().rev() {
+          labels.push(octet.to_string());
+        }
+        labels.push("in-addr".to_string());
+      },
+      IpAddr::V6(ip) => {
+        for segment in ip.segments().iter().rev() {
+          for shift in &[0, 4, 8, 12] {
+            labels.push(format!("{:x}", (segment >> *shift) & 0xF));
+          }
+        }
+        labels.push("ip6".to_string());
+      },
+    }
+
+    labels.push("arpa".to_string());
+    Name::with_labels(labels)
+  }
+}
+
 impl Index<usize> for Name {
     type Output = String;
 
@@ -493,6 +728,9 @@ impl Ord for Name {
   }
 }
 
+/// the most pointers followed while reading a name, a name has at most 127 labels
+pub const MAX_POINTERS: usize = 127;
+
 /// This is the list of states for the label parsing state machine
 enum LabelParseState {
   LabelLengthOrPointer, // basically the start of the FSM
@@ -519,6 +757,15 @@ mod tests {
     ]
   }
 
+  #[test]
+  fn test_from_ip() {
+    use std::net::{IpAddr, Ipv6Addr};
+
+    let name = Name::from(IpAddr::V6(Ipv6Addr::new(0x2001,0xdb8,0,0,0,0,0,0x1)));
+    assert_eq!(name, Name::parse("1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.", None).unwrap());
+    assert_eq!(name.num_labels(), 34);
+  }
+
   #[test]
   fn num_labels() {
     assert_eq!(Name::new().label("*").num_labels(), 0);
@@ -581,6 +828,51 @@ mod tests {
     assert_eq!(fourth, r_test);
   }
 
+  #[test]
+  fn test_read_crafted() {
+    // a pointer to itself
+    let bytes: Vec<u8> = vec![1,b'a', 0xC0,2];
+    match *Name::read(&mut BinDecoder::new(&bytes)).unwrap_err().kind() {
+      ::error::DecodeErrorKind::ForwardPointer(2, 2) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    // a pointer after itself
+    let bytes: Vec<u8> = vec![0xC0,3, 0, 1,b'a',0];
+    match *Name::read(&mut BinDecoder::new(&bytes)).unwrap_err().kind() {
+      ::error::DecodeErrorKind::ForwardPointer(3, 0) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    // a chain of pointers to pointers, each before the last
+    let mut bytes: Vec<u8> = vec![0];
+    for _ in 0..200 {
+      let location = bytes.len() as u16 - if bytes.len() == 1 { 1 } else { 2 };
+      bytes.extend_from_slice(&[0xC0 | (location >> 8) as u8, location as u8]);
+    }
+    let mut decoder = BinDecoder::new(&bytes).clone(bytes.len() as u16 - 2);
+    match *Name::read(&mut decoder).unwrap_err().kind() {
+      ::error::DecodeErrorKind::TooManyPointers(128) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    // longer than 255 octets, 5 labels of 63
+    let mut bytes: Vec<u8> = Vec::new();
+    for _ in 0..5 { bytes.push(63); bytes.extend_from_slice(&[b'a'; 63]); }
+    bytes.push(0);
+    match *Name::read(&mut BinDecoder::new(&bytes)).unwrap_err().kind() {
+      ::error::DecodeErrorKind::DomainNameTooLong(257) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    // a label longer than 63 octets is a reserved label type
+    let bytes: Vec<u8> = vec![64];
+    match *Name::read(&mut BinDecoder::new(&bytes)).unwrap_err().kind() {
+      ::error::DecodeErrorKind::UnrecognizedLabelCode(64) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+  }
+
   #[test]
   fn test_base_name() {
     let zone = Name::new().label("example").label("com");
@@ -590,6 +882,22 @@ mod tests {
     assert!(zone.base_name().base_name().base_name().is_root());
   }
 
+  #[test]
+  fn test_parent_and_wildcard() {
+    let wildcard: Name = "*.Example.com".parse().unwrap();
+    assert!(wildcard.is_wildcard());
+    assert_eq!(wildcard.label_count(), 3);
+    assert_eq!(wildcard.num_labels(), 2);
+    assert_eq!(wildcard.parent().unwrap(), "example.com.".parse().unwrap());
+    assert!(!wildcard.parent().unwrap().is_wildcard());
+    assert!(wildcard.is_subdomain_of(&wildcard.parent().unwrap().parent().unwrap()));
+
+    let root: Name = ".".parse().unwrap();
+    assert!(root.is_root());
+    assert_eq!(root.label_count(), 0);
+    assert_eq!(root.parent(), None);
+  }
+
   #[test]
   fn test_prepend() {
     let zone = Name::new().label("example").label("com");
@@ -666,4 +974,43 @@ mod tests {
       assert_eq!(left, right);
      }
   }
+
+  #[test]
+  fn test_case_preserving() {
+    use std::collections::HashSet;
+
+    let upper = Name::parse("WWW.Example.COM.", None).unwrap();
+    let lower = Name::parse("www.example.com.", None).unwrap();
+
+    assert_eq!(upper, lower);
+    assert!(!upper.eq_case(&lower));
+    assert!(upper.to_lowercase().eq_case(&lower));
+    assert_eq!(upper.cmp(&lower), Ordering::Equal);
+    assert_eq!(upper.to_string(), "WWW.Example.COM.");
+
+    let mut names: HashSet<Name> = HashSet::new();
+    names.insert(upper.clone());
+    assert!(names.contains(&lower));
+
+    // only ASCII letters are compared without case, RFC 4343
+    assert!(Name::parse("É.example.com.", None).unwrap() != Name::parse("é.example.com.", None).unwrap());
+
+    assert!(lower.base_name().zone_of(&upper));
+    assert!(!upper.zone_of(&lower.base_name()));
+  }
+
+  #[test]
+  fn test_from_utf8() {
+    let name = Name::from_utf8("www.他们为什么不说中文。Example").unwrap();
+    assert!(name.eq_case(&Name::parse("www.xn--ihqwcrb4cv8a8dqg056pqjye.Example.", None).unwrap()));
+    assert_eq!(name.to_utf8(), "www.他们为什么不说中文.Example.");
+
+    assert_eq!(Name::from_utf8("MÜNCHEN.de.").unwrap().to_utf8(), "münchen.de.");
+    assert!(Name::from_utf8(".").unwrap().is_root());
+    assert_eq!(Name::root().to_utf8(), ".");
+    assert!(Name::from_utf8("www..example").is_err());
+
+    // invalid punycode is displayed as it is
+    assert_eq!(Name::parse("xn--bcher-kv!.example.", None).unwrap().to_utf8(), "xn--bcher-kv!.example.");
+  }
 }
@@ -23,6 +23,7 @@ pub mod record_data;
 pub mod domain;
 pub mod rdata;
 pub mod dnssec;
+mod punycode;
 
 pub use self::record_type::RecordType;
 pub use self::resource::Record;
@@ -0,0 +1,186 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! [RFC 3492](https://tools.ietf.org/html/rfc3492), Punycode, the encoding of the Unicode labels
+//!  of internationalized names in ASCII, see `Name::from_utf8()` and `Name::to_utf8()`
+
+use std::char;
+
+use ::error::*;
+
+// 5.  Parameter values for Punycode
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// the ACE prefix of the labels which are punycode, RFC 3490
+pub const ACE_PREFIX: &'static str = "xn--";
+
+/// 6.1 Bias adaptation function
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+  let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+  delta += delta / num_points;
+
+  let mut k = 0;
+  while delta > ((BASE - TMIN) * TMAX) / 2 {
+    delta /= BASE - TMIN;
+    k += BASE;
+  }
+
+  k + (((BASE - TMIN + 1) * delta) / (delta + SKEW))
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+  if k <= bias { TMIN } else if k >= bias + TMAX { TMAX } else { k - bias }
+}
+
+fn encode_digit(digit: u32) -> char {
+  match digit {
+    0 ... 25 => (b'a' + digit as u8) as char,
+    _ => (b'0' + (digit - 26) as u8) as char,
+  }
+}
+
+fn decode_digit(byte: u8) -> Option<u32> {
+  match byte {
+    b'a' ... b'z' => Some((byte - b'a') as u32),
+    b'A' ... b'Z' => Some((byte - b'A') as u32),
+    b'0' ... b'9' => Some((byte - b'0') as u32 + 26),
+    _ => None,
+  }
+}
+
+fn overflow(label: &str) -> ParseError {
+  ParseErrorKind::InvalidIdna(label.to_string()).into()
+}
+
+/// 6.3 Encoding procedure, the label without the ACE prefix
+pub fn encode(label: &str) -> ParseResult<String> {
+  let input: Vec<u32> = label.chars().map(|c| c as u32).collect();
+  // longer labels could not be encoded in 63 octets, and could overflow
+  if input.len() > 63 { return Err(overflow(label)) }
+
+  let mut output: String = label.chars().filter(|c| (*c as u32) < INITIAL_N).collect();
+  let basic = output.len() as u32;
+  let mut handled = basic;
+  if basic > 0 { output.push('-'); }
+
+  let mut n = INITIAL_N;
+  let mut delta: u32 = 0;
+  let mut bias = INITIAL_BIAS;
+
+  while (handled as usize) < input.len() {
+    let m = input.iter().cloned().filter(|c| *c >= n).min().expect("a code point was not handled");
+    delta = try!(((m - n) * (handled + 1)).checked_add(delta).ok_or(overflow(label)));
+    n = m;
+
+    for c in &input {
+      if *c < n { delta += 1; }
+      if *c == n {
+        let mut q = delta;
+        let mut k = BASE;
+        loop {
+          let t = threshold(k, bias);
+          if q < t { break }
+          output.push(encode_digit(t + (q - t) % (BASE - t)));
+          q = (q - t) / (BASE - t);
+          k += BASE;
+        }
+
+        output.push(encode_digit(q));
+        bias = adapt(delta, handled + 1, handled == basic);
+        delta = 0;
+        handled += 1;
+      }
+    }
+
+    delta += 1;
+    n += 1;
+  }
+
+  Ok(output)
+}
+
+/// 6.2 Decoding procedure, the label without the ACE prefix
+pub fn decode(label: &str) -> ParseResult<String> {
+  let (basic, extended) = match label.rfind('-') {
+    Some(index) => (&label[..index], &label[index + 1..]),
+    None => ("", label),
+  };
+  if basic.chars().any(|c| (c as u32) >= INITIAL_N) { return Err(overflow(label)) }
+
+  let mut output: Vec<char> = basic.chars().collect();
+  let mut n = INITIAL_N;
+  let mut i: u32 = 0;
+  let mut bias = INITIAL_BIAS;
+  let mut bytes = extended.bytes().peekable();
+
+  while bytes.peek().is_some() {
+    let old_i = i;
+    let mut w: u32 = 1;
+    let mut k = BASE;
+    loop {
+      let digit = try!(bytes.next().and_then(decode_digit).ok_or(overflow(label)));
+      i = try!(digit.checked_mul(w).and_then(|dw| i.checked_add(dw)).ok_or(overflow(label)));
+
+      let t = threshold(k, bias);
+      if digit < t { break }
+      w = try!(w.checked_mul(BASE - t).ok_or(overflow(label)));
+      k += BASE;
+    }
+
+    let length = output.len() as u32 + 1;
+    bias = adapt(i - old_i, length, old_i == 0);
+    n = try!(n.checked_add(i / length).ok_or(overflow(label)));
+    i %= length;
+
+    output.insert(i as usize, try!(char::from_u32(n).ok_or(overflow(label))));
+    i += 1;
+  }
+
+  Ok(output.into_iter().collect())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_encode_and_decode() {
+    // samples of RFC 3492, and others
+    let samples = vec![("bücher", "bcher-kva"),
+                       ("münchen", "mnchen-3ya"),
+                       ("他们为什么不说中文", "ihqwcrb4cv8a8dqg056pqjye"),
+                       ("3年b組金八先生", "3b-ww4c5e180e575a65lsy2b"),
+                       ("ascii", "ascii-")];
+
+    for (unicode, ascii) in samples {
+      assert_eq!(encode(unicode).unwrap(), ascii);
+      assert_eq!(decode(ascii).unwrap(), unicode);
+    }
+  }
+
+  #[test]
+  fn test_decode_invalid() {
+    assert!(decode("bcher-kv!").is_err());
+    assert!(decode("99999999999").is_err());
+    assert!(decode("ü-kva").is_err());
+  }
+}
@@ -150,6 +150,7 @@ impl DNSKEY {
 }
 
 pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<DNSKEY> {
+  let start_idx = decoder.index();
   let flags: u16 = try!(decoder.read_u16());
 
   //    Bits 0-6 and 8-14 are reserved: these bits MUST have value 0 upon
@@ -174,7 +175,8 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<DNSKEY>
 
   // the public key is the left-over bytes minus 4 for the first fields
   // TODO: decode the key here?
-  let public_key: Vec<u8> = try!(decoder.read_vec((rdata_length - 4) as usize));
+  let left: usize = try!(super::remaining_rdata(decoder, start_idx, rdata_length));
+  let public_key: Vec<u8> = try!(decoder.read_vec(left));
 
   Ok(DNSKEY::new(zone_key, secure_entry_point, revoke, algorithm, public_key))
 }
@@ -141,7 +141,7 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<DS> {
   let algorithm: Algorithm = try!(Algorithm::read(decoder));
   let digest_type: DigestType = try!(DigestType::from_u8(try!(decoder.read_u8())));
 
-  let left: usize = rdata_length as usize - (decoder.index() - start_idx);;
+  let left: usize = try!(super::remaining_rdata(decoder, start_idx, rdata_length));
   let digest = try!(decoder.read_vec(left));
 
   Ok(DS::new(key_tag, algorithm, digest_type, digest))
@@ -33,6 +33,7 @@ pub mod opt;
 pub mod sig;
 pub mod soa;
 pub mod srv;
+pub mod tsig;
 pub mod txt;
 
 pub use self::dnskey::DNSKEY;
@@ -45,5 +46,20 @@ pub use self::null::NULL;
 pub use self::opt::OPT;
 pub use self::sig::SIG;
 pub use self::srv::SRV;
-pub use self::soa::SOA;
+pub use self::soa::{SOA, compare_serial};
+pub use self::tsig::TSIG;
 pub use self::txt::TXT;
+
+use ::error::*;
+use ::serialize::binary::BinDecoder;
+
+/// The length of the rdata left to read, after the fields read since start_idx, an error rather
+///  than an underflow if more was read than the rdata_length
+fn remaining_rdata(decoder: &BinDecoder, start_idx: usize, rdata_length: u16) -> DecodeResult<usize> {
+  let read = decoder.index() - start_idx;
+  if read > rdata_length as usize {
+    return Err(DecodeErrorKind::IncorrectRDataLengthRead(read, rdata_length as usize).into())
+  }
+
+  Ok(rdata_length as usize - read)
+}
@@ -95,7 +95,7 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<NSEC> {
 
   let next_domain_name = try!(Name::read(decoder));
 
-  let bit_map_len = rdata_length as usize - (decoder.index() - start_idx);
+  let bit_map_len = try!(super::remaining_rdata(decoder, start_idx, rdata_length));
   let record_types = try!(nsec3::decode_type_bit_maps(decoder, bit_map_len));
 
   Ok(NSEC::new(next_domain_name, record_types))
@@ -222,7 +222,7 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<NSEC3>
   let hash_len: u8 = try!(decoder.read_u8());
   let next_hashed_owner_name: Vec<u8> = try!(decoder.read_vec(hash_len as usize));
 
-  let bit_map_len = rdata_length as usize - (decoder.index() - start_idx);
+  let bit_map_len = try!(super::remaining_rdata(decoder, start_idx, rdata_length));
   let record_types = try!(decode_type_bit_maps(decoder, bit_map_len));
 
   Ok(NSEC3::new(hash_algorithm, opt_out, iterations, salt, next_hashed_owner_name, record_types))
@@ -283,7 +283,13 @@ pub fn decode_type_bit_maps(decoder: &mut BinDecoder, bit_map_len: usize) -> Dec
 
     state = match state {
       BitMapState::ReadWindow => BitMapState::ReadLen{ window: current_byte },
-      BitMapState::ReadLen{ window } => BitMapState::ReadType{ window: window, len: current_byte, left: current_byte },
+      BitMapState::ReadLen{ window } => {
+        // a bitmap of more than 32 octets would overflow the types of the window
+        if current_byte == 0 || current_byte > 32 {
+          return Err(DecodeErrorKind::Message("type bit map length must be from 1 to 32").into())
+        }
+        BitMapState::ReadType{ window: window, len: current_byte, left: current_byte }
+      },
       BitMapState::ReadType{ window, len, left } => {
         // window is the Window Block # from above
         // len is the Bitmap Length
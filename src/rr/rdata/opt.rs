@@ -200,7 +200,13 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<OPT> {
       },
       OptReadState::Code{code} => {
         let length: usize = try!(decoder.read_u16()) as usize;
-        state = OptReadState::Data{code:code, length: length, collected: Vec::<u8>::with_capacity(length) };
+        if length == 0 {
+          // an option without data, e.g. an NSID request
+          options.insert(code, (code, &[] as &[u8]).into());
+          state = OptReadState::ReadCode;
+        } else {
+          state = OptReadState::Data{code:code, length: length, collected: Vec::<u8>::with_capacity(length) };
+        }
       },
       OptReadState::Data{code, length, mut collected } => {
         collected.push(try!(decoder.pop()));
@@ -394,7 +400,8 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
       EdnsOption::DAU(..) => EdnsCode::DAU,
       EdnsOption::DHU(..) => EdnsCode::DHU,
       EdnsOption::N3U(..)=> EdnsCode::N3U,
-      EdnsOption::Unknown(code, _) => EdnsCode::Unknown(code),
+      // the same key as is used when reading the option, e.g. EdnsCode::Cookie
+      EdnsOption::Unknown(code, _) => code.into(),
     }
   }
 }
@@ -416,3 +423,18 @@ pub fn test() {
   assert!(read_rdata.is_ok(), format!("error decoding: {:?}", read_rdata.unwrap_err()));
   assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+pub fn test_empty_option() {
+  let mut rdata = OPT::default();
+  rdata.insert(EdnsOption::Unknown(u16::from(EdnsCode::NSID), vec![]));
+
+  let mut bytes = Vec::new();
+  let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+  assert!(emit(&mut encoder, &rdata).is_ok());
+  let bytes = encoder.as_bytes();
+
+  let mut decoder: BinDecoder = BinDecoder::new(bytes);
+  let read_rdata = read(&mut decoder, bytes.len() as u16).unwrap();
+  assert_eq!(read_rdata.get(&EdnsCode::NSID), Some(&EdnsOption::Unknown(u16::from(EdnsCode::NSID), vec![])));
+}
@@ -398,8 +398,8 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<SIG> {
   let signer_name = try!(Name::read(decoder));
 
   // read the signature, this will vary buy key size
-  let bytes_read = decoder.index() - start_idx;
-  let sig = try!(decoder.read_vec(rdata_length as usize - bytes_read));
+  let left = try!(super::remaining_rdata(decoder, start_idx, rdata_length));
+  let sig = try!(decoder.read_vec(left));
 
   Ok(SIG::new(type_covered, algorithm, num_labels, original_ttl, sig_expiration,
               sig_inception, key_tag, signer_name, sig))
@@ -424,8 +424,6 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<SIG> {
 ///        by the corresponding lowercase US-ASCII letters;
 /// ```
 pub fn emit(encoder: &mut BinEncoder, sig: &SIG) -> EncodeResult {
-  let is_canonical_names = encoder.is_canonical_names();
-  
   try!(sig.get_type_covered().emit(encoder));
   try!(sig.get_algorithm().emit(encoder));
   try!(encoder.emit(sig.get_num_labels()));
@@ -433,7 +431,8 @@ pub fn emit(encoder: &mut BinEncoder, sig: &SIG) -> EncodeResult {
   try!(encoder.emit_u32(sig.get_sig_expiration()));
   try!(encoder.emit_u32(sig.get_sig_inception()));
   try!(encoder.emit_u16(sig.get_key_tag()));
-  try!(sig.get_signer_name().emit_with_lowercase(encoder, is_canonical_names));
+  // the signer name is not compressed, RFC 4034 section 3.1.7
+  try!(sig.get_signer_name().emit_uncompressed(encoder));
   try!(encoder.emit_vec(sig.get_sig()));
   Ok(())
 }
@@ -16,6 +16,8 @@
 
 //! start of authority record defining ownership and defaults for the zone
 
+use std::cmp::Ordering;
+
 use ::serialize::txt::*;
 use ::serialize::binary::*;
 use ::error::*;
@@ -94,9 +96,14 @@ impl SOA {
           minimum: minimum, }
   }
 
-  /// Increments the serial number by one
+  /// Increments the serial number by one, wrapping around to 0 after `u32::MAX`
   pub fn increment_serial(&mut self) {
-    self.serial += 1; // TODO: what to do on overflow?
+    self.serial = self.serial.wrapping_add(1);
+  }
+
+  /// Replaces the serial number, e.g. with the next serial of a `SerialPolicy`
+  pub fn set_serial(&mut self, serial: u32) {
+    self.serial = serial;
   }
 
   /// ```text
@@ -178,6 +185,22 @@ impl SOA {
   pub fn get_minimum(&self) -> u32 { self.minimum }
 }
 
+/// Compares serial numbers with the sequence space arithmetic of
+///  [RFC 1982](https://tools.ietf.org/html/rfc1982), where a serial is greater than those up to
+///  2^31 - 1 before it, wrapping around from 0 to `u32::MAX`.
+///
+/// # Return value
+///
+/// The ordering of `s1` to `s2`, None if they are exactly 2^31 apart, which is undefined
+pub fn compare_serial(s1: u32, s2: u32) -> Option<Ordering> {
+  match s2.wrapping_sub(s1) {
+    0 => Some(Ordering::Equal),
+    0x8000_0000 => None,
+    distance if distance < 0x8000_0000 => Some(Ordering::Less),
+    _ => Some(Ordering::Greater),
+  }
+}
+
 // SOA { mname: Name, rname: Name, serial: u32, refresh: i32, retry: i32, expire: i32, minimum: u32, },
 pub fn read(decoder: &mut BinDecoder) -> DecodeResult<SOA> {
   Ok(SOA{
@@ -229,24 +252,27 @@ pub fn emit(encoder: &mut BinEncoder, soa: &SOA) -> EncodeResult {
 //                                 3600000; EXPIRE
 //                                 60)    ; MINIMUM
 pub fn parse(tokens: &Vec<Token>, origin: Option<&Name>) -> ParseResult<SOA> {
-  let mut token = tokens.iter();
-
-  let mname: Name = try!(token.next().ok_or(ParseErrorKind::MissingToken("mname".to_string()).into()).and_then(|t| if let &Token::CharData(ref s) = t {Name::parse(s, origin)} else {Err(ParseErrorKind::UnexpectedToken(t.clone()).into())} ));
-  let rname: Name = try!(token.next().ok_or(ParseErrorKind::MissingToken("rname".to_string()).into()).and_then(|t| if let &Token::CharData(ref s) = t {Name::parse(s, origin)} else {Err(ParseErrorKind::UnexpectedToken(t.clone()).into())} ));
-  let mut list = try!(token.next().ok_or(ParseError::from(ParseErrorKind::MissingToken("List".to_string()))).and_then(|t| if let &Token::List(ref v) = t {Ok(v)} else {Err(ParseErrorKind::UnexpectedToken(t.clone()).into())} )).iter();
-
-  let serial: u32 = try!(list.next().ok_or(ParseError::from(ParseErrorKind::MissingToken("serial".to_string()))).and_then(|s| Ok(try!(s.parse()))));
-  let refresh: i32 = try!(list.next().ok_or(ParseError::from(ParseErrorKind::MissingToken("refresh".to_string()))).and_then(|s| Ok(try!(s.parse()))));
-  let retry: i32 = try!(list.next().ok_or(ParseError::from(ParseErrorKind::MissingToken("retry".to_string()))).and_then(|s| Ok(try!(s.parse()))));
-  let expire: i32 = try!(list.next().ok_or(ParseError::from(ParseErrorKind::MissingToken("expire".to_string()))).and_then(|s| Ok(try!(s.parse()))));
-  let minimum: u32 = try!(list.next().ok_or(ParseError::from(ParseErrorKind::MissingToken("minimum".to_string()))).and_then(|s| Ok(try!(s.parse()))));
+  // the fields may be on one line, or any of them grouped in parentheses
+  let mut fields: Vec<&String> = Vec::with_capacity(7);
+  for t in tokens {
+    match *t {
+      Token::CharData(ref s) => fields.push(s),
+      Token::List(ref v) => fields.extend(v.iter()),
+      _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+    }
+  }
 
+  let mut field = fields.into_iter();
+  let mut next = |name: &str| field.next().ok_or(ParseError::from(ParseErrorKind::MissingToken(name.to_string())));
 
-  // let serial: u32 = try!(token.next().ok_or(ParseError::MissingToken("serial".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-  // let refresh: i32 = try!(token.next().ok_or(ParseError::MissingToken("refresh".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-  // let retry: i32 = try!(token.next().ok_or(ParseError::MissingToken("retry".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-  // let expire: i32 = try!(token.next().ok_or(ParseError::MissingToken("expire".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-  // let minimum: u32 = try!(token.next().ok_or(ParseError::MissingToken("minimum".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
+  let mname: Name = try!(Name::parse(try!(next("mname")), origin));
+  let rname: Name = try!(Name::parse(try!(next("rname")), origin));
+  let serial: u32 = try!(try!(next("serial")).parse());
+  // the timers may have units, e.g. 1h
+  let refresh: i32 = try!(Parser::parse_time(try!(next("refresh")))) as i32;
+  let retry: i32 = try!(Parser::parse_time(try!(next("retry")))) as i32;
+  let expire: i32 = try!(Parser::parse_time(try!(next("expire")))) as i32;
+  let minimum: u32 = try!(Parser::parse_time(try!(next("minimum"))));
 
   Ok(SOA::new(mname, rname, serial, refresh, retry, expire, minimum))
 }
@@ -269,3 +295,24 @@ fn test() {
   assert!(read_rdata.is_ok(), format!("error decoding: {:?}", read_rdata.unwrap_err()));
   assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+fn test_compare_serial() {
+  assert_eq!(compare_serial(1, 1), Some(Ordering::Equal));
+  assert_eq!(compare_serial(1, 2), Some(Ordering::Less));
+  assert_eq!(compare_serial(2, 1), Some(Ordering::Greater));
+
+  // wrapping around
+  assert_eq!(compare_serial(u32::max_value(), 0), Some(Ordering::Less));
+  assert_eq!(compare_serial(0, u32::max_value()), Some(Ordering::Greater));
+  assert_eq!(compare_serial(0x8000_0000, 1), Some(Ordering::Greater));
+  assert_eq!(compare_serial(0x8000_0001, 1), Some(Ordering::Less));
+  assert_eq!(compare_serial(0, 0x8000_0000), None);
+}
+
+#[test]
+fn test_increment_serial() {
+  let mut soa = SOA::new(Name::new(), Name::new(), u32::max_value(), 2, 3, 4, 5);
+  soa.increment_serial();
+  assert_eq!(soa.get_serial(), 0);
+}
@@ -202,12 +202,11 @@ pub fn read(decoder: &mut BinDecoder) -> DecodeResult<SRV> {
 ///        by the corresponding lowercase US-ASCII letters;
 /// ```
 pub fn emit(encoder: &mut BinEncoder, srv: &SRV) -> EncodeResult {
-  let is_canonical_names = encoder.is_canonical_names();
-  
   try!(encoder.emit_u16(srv.get_priority()));
   try!(encoder.emit_u16(srv.get_weight()));
   try!(encoder.emit_u16(srv.get_port()));
-  try!(srv.get_target().emit_with_lowercase(encoder, is_canonical_names));
+  // the target is not compressed, RFC 2782
+  try!(srv.get_target().emit_uncompressed(encoder));
   Ok(())
 }
 
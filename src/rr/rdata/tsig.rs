@@ -0,0 +1,143 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! transaction signature for authenticating messages
+
+use ::serialize::binary::*;
+use ::error::*;
+use ::rr::domain::Name;
+
+/// [RFC 2845, Secret Key Transaction Authentication for DNS, May 2000](https://tools.ietf.org/html/rfc2845)
+///
+/// ```text
+/// 2.3. Record Format
+///
+///   NAME The name of the key used in domain name syntax.  The name
+///        should reflect the names of the hosts and uniquely identify
+///        the key among a set of keys these two hosts may share at any
+///        given time.
+///
+///   TYPE TSIG (250: Transaction SIGnature)
+///
+///   CLASS ANY
+///
+///   TTL  0
+///
+///   RdLen (variable)
+///
+///   RDATA
+///
+///     Field Name       Data Type      Notes
+///     --------------------------------------------------------------
+///     Algorithm Name   domain-name    Name of the algorithm
+///                                     in domain name syntax.
+///     Time Signed      u_int48_t      seconds since 1-Jan-70 UTC.
+///     Fudge            u_int16_t      seconds of error permitted
+///                                     in Time Signed.
+///     MAC Size         u_int16_t      number of octets in MAC.
+///     MAC              octet stream   defined by Algorithm Name.
+///     Original ID      u_int16_t      original message ID
+///     Error            u_int16_t      expanded RCODE covering
+///                                     TSIG processing.
+///     Other Len        u_int16_t      length, in octets, of
+///                                     Other Data.
+///     Other Data       octet stream   empty unless Error == BADTIME
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TSIG {
+  algorithm: Name,
+  time_signed: u64,
+  fudge: u16,
+  mac: Vec<u8>,
+  original_id: u16,
+  error: u16,
+  other: Vec<u8>,
+}
+
+impl TSIG {
+  pub fn new(algorithm: Name, time_signed: u64, fudge: u16, mac: Vec<u8>, original_id: u16, error: u16, other: Vec<u8>) -> TSIG {
+    TSIG{ algorithm: algorithm, time_signed: time_signed, fudge: fudge, mac: mac, original_id: original_id, error: error, other: other }
+  }
+
+  /// the name of the HMAC algorithm, e.g. hmac-sha256.
+  pub fn get_algorithm(&self) -> &Name { &self.algorithm }
+  /// seconds since 1-Jan-70 UTC, only the low 48 bits are significant
+  pub fn get_time_signed(&self) -> u64 { self.time_signed }
+  /// seconds of error permitted in the time signed
+  pub fn get_fudge(&self) -> u16 { self.fudge }
+  /// the message authentication code
+  pub fn get_mac(&self) -> &[u8] { &self.mac }
+  /// the id of the message when it was signed
+  pub fn get_original_id(&self) -> u16 { self.original_id }
+  /// the extended RCODE for TSIG processing, 16 BADSIG, 17 BADKEY or 18 BADTIME
+  pub fn get_error(&self) -> u16 { self.error }
+  /// other data, the server time when the error is BADTIME
+  pub fn get_other(&self) -> &[u8] { &self.other }
+}
+
+pub fn read(decoder: &mut BinDecoder) -> DecodeResult<TSIG> {
+  let algorithm = try!(Name::read(decoder));
+  let time_signed = try!(read_u48(decoder));
+  let fudge = try!(decoder.read_u16());
+  let mac_size = try!(decoder.read_u16());
+  let mac = try!(decoder.read_vec(mac_size as usize));
+  let original_id = try!(decoder.read_u16());
+  let error = try!(decoder.read_u16());
+  let other_len = try!(decoder.read_u16());
+  let other = try!(decoder.read_vec(other_len as usize));
+
+  Ok(TSIG::new(algorithm, time_signed, fudge, mac, original_id, error, other))
+}
+
+/// the algorithm name is never compressed
+pub fn emit(encoder: &mut BinEncoder, tsig: &TSIG) -> EncodeResult {
+  try!(tsig.get_algorithm().emit_as_canonical(encoder, true));
+  try!(emit_u48(encoder, tsig.get_time_signed()));
+  try!(encoder.emit_u16(tsig.get_fudge()));
+  try!(encoder.emit_u16(tsig.get_mac().len() as u16));
+  try!(encoder.emit_vec(tsig.get_mac()));
+  try!(encoder.emit_u16(tsig.get_original_id()));
+  try!(encoder.emit_u16(tsig.get_error()));
+  try!(encoder.emit_u16(tsig.get_other().len() as u16));
+  try!(encoder.emit_vec(tsig.get_other()));
+  Ok(())
+}
+
+pub fn read_u48(decoder: &mut BinDecoder) -> DecodeResult<u64> {
+  let high = try!(decoder.read_u16()) as u64;
+  let low = try!(decoder.read_u32()) as u64;
+  Ok(high << 32 | low)
+}
+
+pub fn emit_u48(encoder: &mut BinEncoder, value: u64) -> EncodeResult {
+  try!(encoder.emit_u16((value >> 32) as u16));
+  encoder.emit_u32(value as u32)
+}
+
+#[test]
+pub fn test() {
+  let rdata = TSIG::new(Name::new().label("hmac-sha256"), 1471010000, 300, vec![0,1,2,3,4,5,6,7], 1234, 0, vec![]);
+
+  let mut bytes = Vec::new();
+  let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+  assert!(emit(&mut encoder, &rdata).is_ok());
+  let bytes = encoder.as_bytes();
+
+  let mut decoder: BinDecoder = BinDecoder::new(bytes);
+  let read_rdata = read(&mut decoder);
+  assert!(read_rdata.is_ok(), format!("error decoding: {:?}", read_rdata.unwrap_err()));
+  assert_eq!(rdata, read_rdata.unwrap());
+}
@@ -27,7 +27,7 @@ use ::serialize::txt::*;
 use super::domain::Name;
 use super::record_type::RecordType;
 use super::rdata;
-use super::rdata::{ DNSKEY, DS, MX, NSEC, NSEC3, NSEC3PARAM, NULL, OPT, SIG, SOA, SRV, TXT };
+use super::rdata::{ DNSKEY, DS, MX, NSEC, NSEC3, NSEC3PARAM, NULL, OPT, SIG, SOA, SRV, TSIG, TXT };
 
 /// Record data enum variants
 ///
@@ -621,6 +621,11 @@ pub enum RData {
   //  _Service._Proto.Name TTL Class SRV Priority Weight Port Target
   SRV(SRV),
 
+  // RFC 2845       Secret Key Transaction Authentication for DNS       May 2000
+  //
+  // Transaction signature, only valid as the final record of the additional section
+  TSIG(TSIG),
+
   // 3.3.14. TXT RDATA format
   //
   //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
@@ -643,32 +648,44 @@ impl RData {
     let rdata = match record_type {
       RecordType::A => RData::A(try!(rdata::a::parse(tokens))),
       RecordType::AAAA => RData::AAAA(try!(rdata::aaaa::parse(tokens))),
-      RecordType::ANY => panic!("parsing ANY doesn't make sense"), // valid panic, never should happen
-      RecordType::AXFR => panic!("parsing AXFR doesn't make sense"), // valid panic, never should happen
       RecordType::CNAME => RData::CNAME(try!(rdata::name::parse(tokens, origin))),
-      RecordType::KEY => panic!("KEY should be dynamically generated"), // valid panic, never should happen
-      RecordType::DNSKEY => panic!("DNSKEY should be dynamically generated"), // valid panic, never should happen
-      RecordType::DS => panic!("DS should be dynamically generated"), // valid panic, never should happen
-      RecordType::IXFR => panic!("parsing IXFR doesn't make sense"), // valid panic, never should happen
       RecordType::MX => RData::MX(try!(rdata::mx::parse(tokens, origin))),
       RecordType::NULL =>  RData::NULL(try!(rdata::null::parse(tokens))),
       RecordType::NS => RData::NS(try!(rdata::name::parse(tokens, origin))),
-      RecordType::NSEC => panic!("NSEC should be dynamically generated"), // valid panic, never should happen
-      RecordType::NSEC3 => panic!("NSEC3 should be dynamically generated"), // valid panic, never should happen
-      RecordType::NSEC3PARAM => panic!("NSEC3PARAM should be dynamically generated"), // valid panic, never should happen
-      RecordType::OPT => panic!("parsing OPT doesn't make sense"), // valid panic, never should happen
       RecordType::PTR => RData::PTR(try!(rdata::name::parse(tokens, origin))),
-      RecordType::RRSIG => panic!("RRSIG should be dynamically generated"), // valid panic, never should happen
-      RecordType::SIG => panic!("parsing SIG doesn't make sense"), // valid panic, never should happen
       RecordType::SOA => RData::SOA(try!(rdata::soa::parse(tokens, origin))),
       RecordType::SRV => RData::SRV(try!(rdata::srv::parse(tokens, origin))),
       RecordType::TXT => RData::TXT(try!(rdata::txt::parse(tokens))),
+      // DNSSEC records are generated when the zone is signed, the others only exist in messages
+      RecordType::ANY | RecordType::AXFR | RecordType::KEY | RecordType::DNSKEY
+      | RecordType::DS | RecordType::IXFR | RecordType::NSEC | RecordType::NSEC3 | RecordType::NSEC3PARAM
+      | RecordType::OPT | RecordType::RRSIG | RecordType::SIG | RecordType::TSIG => {
+        return Err(ParseErrorKind::Msg(format!("{:?} records can not be parsed from text", record_type)).into())
+      },
     };
 
     Ok(rdata)
   }
 
-  fn to_bytes(&self) -> Vec<u8> {
+  /// Parses the rdata of the type from its presentation format, as in a master file but with
+  ///  absolute names, or the generic format of RFC 3597, see `serialize::txt::rdata_from_string()`
+  ///
+  /// ```
+  /// use trust_dns::rr::{RData, RecordType};
+  ///
+  /// let mx = RData::try_from_str(RecordType::MX, "10 mail.example.com.").unwrap();
+  /// let a = RData::try_from_str(RecordType::A, "\\# 4 C0000201").unwrap();
+  ///
+  /// assert_eq!(a, RData::A("192.0.2.1".parse().unwrap()));
+  /// assert_eq!(RecordType::from(&mx), RecordType::MX);
+  /// ```
+  pub fn try_from_str(record_type: RecordType, text: &str) -> ParseResult<Self> {
+    rdata_from_string(record_type, text)
+  }
+
+  /// The rdata as it is sent, without its length, if it can not be encoded a warning is logged
+  ///  and it is empty
+  pub fn to_bytes(&self) -> Vec<u8> {
     let mut buf: Vec<u8> = Vec::new();
     {
       let mut encoder: BinEncoder = BinEncoder::new(&mut buf);
@@ -702,6 +719,7 @@ impl RData {
       RecordType::SIG => {debug!("reading SIG"); RData::SIG(try!(rdata::sig::read(decoder, rdata_length))) },
       RecordType::SOA => {debug!("reading SOA"); RData::SOA(try!(rdata::soa::read(decoder))) },
       RecordType::SRV => {debug!("reading SRV"); RData::SRV(try!(rdata::srv::read(decoder))) },
+      RecordType::TSIG => {debug!("reading TSIG"); RData::TSIG(try!(rdata::tsig::read(decoder))) },
       RecordType::TXT => {debug!("reading TXT"); RData::TXT(try!(rdata::txt::read(decoder, rdata_length))) },
     };
 
@@ -755,6 +773,7 @@ impl RData {
       RData::SOA(ref soa) => rdata::soa::emit(encoder, soa),
       // to_lowercase for rfc4034 and rfc6840
       RData::SRV(ref srv) => rdata::srv::emit(encoder, srv),
+      RData::TSIG(ref tsig) => rdata::tsig::emit(encoder, tsig),
       RData::TXT(ref txt) => rdata::txt::emit(encoder, txt),
     }
   }
@@ -782,6 +801,7 @@ impl<'a> From<&'a RData> for RecordType {
       RData::SIG(..) => RecordType::SIG,
       RData::SOA(..) => RecordType::SOA,
       RData::SRV(..) => RecordType::SRV,
+      RData::TSIG(..) => RecordType::TSIG,
       RData::TXT(..) => RecordType::TXT,
     }
   }
@@ -67,7 +67,7 @@ pub enum RecordType {
     //  TA,         //	32768	N/A	DNSSEC Trust Authorities
     //  TKEY,       //	249	RFC 2930	Secret key record
     //  TLSA,       //	52	RFC 6698	TLSA certificate association
-    //  TSIG,       //	250	RFC 2845	Transaction Signature
+    TSIG,       //	250	RFC 2845	Transaction Signature
     TXT,        //	16	RFC 1035[1]	Text record
 }
 
@@ -94,6 +94,17 @@ impl RecordType {
       "TXT" => Ok(RecordType::TXT),
       "ANY" | "*" => Ok(RecordType::ANY),
       "AXFR" => Ok(RecordType::AXFR),
+      "DNSKEY" => Ok(RecordType::DNSKEY),
+      "DS" => Ok(RecordType::DS),
+      "IXFR" => Ok(RecordType::IXFR),
+      "KEY" => Ok(RecordType::KEY),
+      "NSEC" => Ok(RecordType::NSEC),
+      "NSEC3" => Ok(RecordType::NSEC3),
+      "NSEC3PARAM" => Ok(RecordType::NSEC3PARAM),
+      "OPT" => Ok(RecordType::OPT),
+      "RRSIG" => Ok(RecordType::RRSIG),
+      "SIG" => Ok(RecordType::SIG),
+      "TSIG" => Ok(RecordType::TSIG),
       _ => Err(DecodeErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
     }
   }
@@ -128,6 +139,7 @@ impl RecordType {
       24 => Ok(RecordType::SIG),
       6 => Ok(RecordType::SOA),
       33 => Ok(RecordType::SRV),
+      250 => Ok(RecordType::TSIG),
       16 => Ok(RecordType::TXT),
       // TODO: this should probably return a generic value wrapper.
       _ => Err(DecodeErrorKind::UnknownRecordTypeValue(value).into()),
@@ -185,6 +197,7 @@ impl From<RecordType> for &'static str {
       RecordType::SIG => "SIG",
       RecordType::SOA => "SOA",
       RecordType::SRV => "SRV",
+      RecordType::TSIG => "TSIG",
       RecordType::TXT => "TXT",
     }
   }
@@ -223,6 +236,7 @@ impl From<RecordType> for u16 {
       RecordType::SIG => 24,
       RecordType::SOA => 6,
       RecordType::SRV => 33,
+      RecordType::TSIG => 250,
       RecordType::TXT => 16,
     }
   }
@@ -18,14 +18,17 @@
 
 use std::sync::Arc as Rc;
 use std::cmp::Ordering;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 use ::serialize::binary::*;
 use ::error::*;
-use ::rr::rdata::NULL;
+use ::rr::rdata::{MX, NULL, SRV, TXT};
 use super::record_data::RData;
 use super::record_type::RecordType;
 use super::dns_class::DNSClass;
 use super::domain;
+use ::serialize::txt::Writer;
 
 
 /// Resource records are storage value in DNS, into which all key/value pair data is stored.
@@ -103,6 +106,75 @@ impl Record {
     }
   }
 
+  /// Creates a record of the rdata in the IN class, the type is that of the rdata
+  pub fn from_rdata(name: domain::Name, ttl: u32, rdata: RData) -> Record {
+    Record {
+      name_labels: name,
+      rr_type: RecordType::from(&rdata),
+      dns_class: DNSClass::IN,
+      ttl: ttl,
+      rdata: rdata,
+    }
+  }
+
+  /// An A record of the address
+  pub fn a(name: domain::Name, ttl: u32, address: Ipv4Addr) -> Record {
+    Self::from_rdata(name, ttl, RData::A(address))
+  }
+
+  /// An AAAA record of the address
+  pub fn aaaa(name: domain::Name, ttl: u32, address: Ipv6Addr) -> Record {
+    Self::from_rdata(name, ttl, RData::AAAA(address))
+  }
+
+  /// A CNAME record, `name` is an alias of `target`
+  pub fn cname(name: domain::Name, ttl: u32, target: domain::Name) -> Record {
+    Self::from_rdata(name, ttl, RData::CNAME(target))
+  }
+
+  /// An NS record, `name_server` is authoritative for the zone `name`
+  pub fn ns(name: domain::Name, ttl: u32, name_server: domain::Name) -> Record {
+    Self::from_rdata(name, ttl, RData::NS(name_server))
+  }
+
+  /// A PTR record, e.g. of the name in `in-addr.arpa.` of an address
+  pub fn ptr(name: domain::Name, ttl: u32, target: domain::Name) -> Record {
+    Self::from_rdata(name, ttl, RData::PTR(target))
+  }
+
+  /// An MX record, lower preferences are preferred
+  pub fn mx(name: domain::Name, ttl: u32, preference: u16, exchange: domain::Name) -> Record {
+    Self::from_rdata(name, ttl, RData::MX(MX::new(preference, exchange)))
+  }
+
+  /// An SRV record, `name` is of the form `_service._proto.example.com.`, see `SRV`
+  pub fn srv(name: domain::Name, ttl: u32, priority: u16, weight: u16, port: u16, target: domain::Name) -> Record {
+    Self::from_rdata(name, ttl, RData::SRV(SRV::new(priority, weight, port, target)))
+  }
+
+  /// A TXT record of the strings
+  pub fn txt(name: domain::Name, ttl: u32, txt_data: Vec<String>) -> Record {
+    Self::from_rdata(name, ttl, RData::TXT(TXT::new(txt_data)))
+  }
+
+  /// A record of the type, with the rdata parsed from its presentation format, see
+  ///  `RData::try_from_str()`
+  ///
+  /// ```
+  /// use trust_dns::rr::{Name, Record, RecordType};
+  ///
+  /// let name = Name::parse("example.com.", None).unwrap();
+  /// let record = Record::try_from_str(name, 3600, RecordType::MX, "10 mail.example.com.").unwrap();
+  ///
+  /// assert_eq!(record.to_string(), "example.com.\t3600\tIN\tMX\t10 mail.example.com.");
+  /// ```
+  pub fn try_from_str(name: domain::Name, ttl: u32, rr_type: RecordType, rdata: &str) -> ParseResult<Record> {
+    // the type is kept for the empty rdata of the generic format, which is NULL
+    let mut record = Self::with(name, rr_type, ttl);
+    record.rdata(try!(RData::try_from_str(rr_type, rdata)));
+    Ok(record)
+  }
+
   /// ```text
   /// NAME            a domain name to which this resource record pertains.
   /// ```
@@ -148,6 +220,20 @@ impl Record {
   pub fn get_rdata_mut(&mut self) -> &mut RData { &mut self.rdata }
 }
 
+impl Record {
+  /// Reads past the record by its rdata length, without decoding its type, class or rdata, e.g.
+  ///  to skip a record which could not be decoded, see `DecodeMode::Lenient`
+  pub fn skip(decoder: &mut BinDecoder) -> DecodeResult<()> {
+    try!(domain::Name::read(decoder));
+    try!(decoder.read_u16()); // TYPE
+    try!(decoder.read_u16()); // CLASS
+    try!(decoder.read_u32()); // TTL
+    let rd_length: u16 = try!(decoder.read_u16());
+    try!(decoder.read_slice(rd_length as usize));
+    Ok(())
+  }
+}
+
 impl BinSerializable<Record> for Record {
   /// parse a resource record line example:
   ///  WARNING: the record_bytes is 100% consumed and destroyed in this parsing process
@@ -187,6 +273,9 @@ impl BinSerializable<Record> for Record {
     // RDLENGTH        an unsigned 16 bit integer that specifies the length in
     //                octets of the RDATA field.
     let rd_length: u16 = try!(decoder.read_u16());
+    if rd_length as usize > decoder.len() {
+      return Err(DecodeErrorKind::RDataLengthExceedsBuffer(rd_length as usize, decoder.len()).into())
+    }
 
     // this is to handle updates, RFC 2136, which uses 0 to indicate certain aspects of
     //  pre-requisites
@@ -208,23 +297,15 @@ impl BinSerializable<Record> for Record {
     try!(self.dns_class.emit(encoder));
     try!(encoder.emit_u32(self.ttl));
 
-    // gah... need to write rdata before we know the size of rdata...
-    // TODO: should we skip the fixed size header and write the rdata first? then write the header?
-    let mut tmp_buf: Vec<u8> = Vec::with_capacity(512);
-    {
-      let mut tmp_encoder: BinEncoder = BinEncoder::with_offset(&mut tmp_buf, encoder.offset() + 2 /*for u16 len*/, EncodeMode::Normal);
-      try!(self.rdata.emit(&mut tmp_encoder));
-    }
-
-    assert!(tmp_buf.len() <= u16::max_value() as usize);
-
-    try!(encoder.emit_u16(tmp_buf.len() as u16));
-    encoder.reserve(tmp_buf.len());
+    // the rdata is written in place, so that its names are compressed against the names of the
+    //  message before it, or written in canonical form, and its length is filled in after
+    let length_index = encoder.len();
+    try!(encoder.emit_u16(0));
+    try!(self.rdata.emit(encoder));
 
-    tmp_buf.reverse();
-    while let Some(byte) = tmp_buf.pop() {
-      try!(encoder.emit(byte));
-    }
+    let length = encoder.len() - length_index - 2;
+    if length > u16::max_value() as usize { return Err(EncodeErrorKind::RDataTooLong(length).into()) }
+    encoder.set_u16_at(length_index, length as u16);
 
     Ok(())
   }
@@ -313,6 +394,22 @@ impl PartialOrd<Record> for Record {
   }
 }
 
+/// The record as it is in a master file, or in the output of dig, with absolute names
+///
+/// ```text
+/// www.example.com.	3600	IN	A	192.0.2.1
+/// ```
+impl fmt::Display for Record {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut writer = Writer::new();
+    writer.relative_names(false);
+    let dns_class: &'static str = self.dns_class.into();
+    let rr_type: &'static str = self.rr_type.into();
+
+    write!(f, "{}\t{}\t{}\t{}\t{}", writer.name_to_string(&self.name_labels, &domain::Name::root()), self.ttl, dns_class, rr_type,
+           writer.rdata_to_string(&self.rdata, &domain::Name::root()))
+  }
+}
 
 #[cfg(test)]
 mod tests {
@@ -348,6 +445,49 @@ mod tests {
     assert_eq!(got, record);
   }
 
+  #[test]
+  fn test_emit_compressed_rdata() {
+    let mut record = Record::new();
+    record.name(Name::parse("www.example.com.", None).unwrap())
+    .rr_type(RecordType::CNAME).dns_class(DNSClass::IN).ttl(5)
+    .rdata(RData::CNAME(Name::parse("example.com.", None).unwrap()));
+
+    let mut vec_bytes: Vec<u8> = Vec::with_capacity(512);
+    {
+      let mut encoder = BinEncoder::new(&mut vec_bytes);
+      record.emit(&mut encoder).unwrap();
+    }
+
+    // the target is a pointer to example.com. in the name of the record
+    assert_eq!(&vec_bytes[17 + 8..], &[0x00, 0x02, 0xC0, 0x04]);
+    assert_eq!(Record::read(&mut BinDecoder::new(&vec_bytes)).unwrap(), record);
+
+    // canonical names are not compressed
+    let mut canonical_bytes: Vec<u8> = Vec::with_capacity(512);
+    {
+      let mut encoder = BinEncoder::new(&mut canonical_bytes);
+      encoder.set_canonical_names(true);
+      record.emit(&mut encoder).unwrap();
+    }
+
+    assert_eq!(canonical_bytes.len(), 17 + 10 + 13);
+    assert_eq!(&canonical_bytes[17 + 8..17 + 10], &[0x00, 13]);
+  }
+
+  #[test]
+  fn test_read_rdata_length() {
+    // an A record, with an rdata length past the end of the bytes
+    let bytes: Vec<u8> = vec![0, 0,1, 0,1, 0,0,0,5, 0xFF,0xFF, 192,168,0,1];
+    match *Record::read(&mut BinDecoder::new(&bytes)).unwrap_err().kind() {
+      ::error::DecodeErrorKind::RDataLengthExceedsBuffer(65535, 4) => (),
+      ref kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    // a DNSKEY shorter than its fixed fields
+    let bytes: Vec<u8> = vec![0, 0,48, 0,1, 0,0,0,5, 0,2, 1,0, 3,8];
+    assert!(Record::read(&mut BinDecoder::new(&bytes)).is_err());
+  }
+
   #[test]
   fn test_order() {
     let mut record = Record::new();
@@ -378,4 +518,28 @@ mod tests {
       assert_eq!(r.cmp(g), Ordering::Less);
     }
   }
+
+  #[test]
+  fn test_constructors() {
+    let name = Name::parse("www.example.com.", None).unwrap();
+
+    let mut record = Record::new();
+    record.name(name.clone()).rr_type(RecordType::A).dns_class(DNSClass::IN).ttl(5)
+    .rdata(RData::A(Ipv4Addr::new(192, 168, 0, 1)));
+    assert_eq!(Record::a(name.clone(), 5, Ipv4Addr::new(192, 168, 0, 1)), record);
+    assert_eq!(Record::try_from_str(name.clone(), 5, RecordType::A, "192.168.0.1").unwrap(), record);
+
+    let target = Name::parse("server.example.com.", None).unwrap();
+    let srv = Record::srv(Name::parse("_ldap._tcp.example.com.", None).unwrap(), 60, 1, 2, 389, target.clone());
+    assert_eq!(srv.get_rr_type(), RecordType::SRV);
+    assert_eq!(srv.to_string(), "_ldap._tcp.example.com.\t60\tIN\tSRV\t1 2 389 server.example.com.");
+
+    let mx = Record::mx(name.clone(), 60, 10, target.clone());
+    assert_eq!(Record::try_from_str(name.clone(), 60, RecordType::MX, "10 server.example.com").unwrap(), mx);
+
+    let txt = Record::txt(name.clone(), 60, vec!["v=spf1 -all".to_string()]);
+    assert_eq!(Record::try_from_str(name.clone(), 60, RecordType::TXT, "\"v=spf1 -all\"").unwrap(), txt);
+
+    assert!(Record::try_from_str(name.clone(), 60, RecordType::A, "not an address").is_err());
+  }
 }
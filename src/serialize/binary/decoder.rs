@@ -13,7 +13,7 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use ::error::{DecodeErrorKind, DecodeResult};
+use ::error::{DecodeError, DecodeErrorKind, DecodeResult};
 
 /// This is non-destructive to the inner buffer, b/c for pointer types we need to perform a reverse
 ///  seek to lookup names
@@ -25,11 +25,42 @@ use ::error::{DecodeErrorKind, DecodeResult};
 pub struct BinDecoder<'a> {
   buffer: &'a [u8],
   index: usize,
+  mode: DecodeMode,
+  warnings: Vec<DecodeError>,
 }
 
 impl<'a> BinDecoder<'a> {
   pub fn new(buffer: &'a [u8]) -> Self {
-    BinDecoder { buffer: buffer, index: 0, }
+    Self::with_mode(buffer, DecodeMode::Strict)
+  }
+
+  pub fn with_mode(buffer: &'a [u8], mode: DecodeMode) -> Self {
+    BinDecoder { buffer: buffer, index: 0, mode: mode, warnings: Vec::new() }
+  }
+
+  pub fn mode(&self) -> DecodeMode {
+    self.mode
+  }
+
+  /// Records an error which was recovered from in the Lenient mode, e.g. a record which was
+  ///  skipped
+  pub fn add_warning(&mut self, warning: DecodeError) {
+    self.warnings.push(warning);
+  }
+
+  /// The errors which were recovered from, in the order they occurred
+  pub fn get_warnings(&self) -> &[DecodeError] {
+    &self.warnings
+  }
+
+  /// Moves to the index of the buffer, e.g. past a record which was skipped
+  pub fn seek(&mut self, index: usize) -> DecodeResult<()> {
+    if index > self.buffer.len() {
+      return Err(DecodeErrorKind::Message("unexpected end of input reached").into())
+    }
+
+    self.index = index;
+    Ok(())
   }
 
   pub fn pop(&mut self) -> DecodeResult<u8> {
@@ -59,11 +90,13 @@ impl<'a> BinDecoder<'a> {
   }
 
   /// This is a pretty efficient clone, as the buffer is never cloned, and only the index is set
-  ///  to the value passed in
-  pub fn clone(&self, index_at: u16) -> BinDecoder {
+  ///  to the value passed in, the mode is kept and the warnings are not
+  pub fn clone(&self, index_at: u16) -> BinDecoder<'a> {
     BinDecoder {
       buffer: self.buffer,
       index: index_at as usize,
+      mode: self.mode,
+      warnings: Vec::new(),
     }
   }
 
@@ -86,13 +119,19 @@ impl<'a> BinDecoder<'a> {
   }
 
   pub fn read_vec(&mut self, len: usize) -> DecodeResult<Vec<u8>> {
-    // TODO once Drain stabalizes on Vec, this should be replaced...
-    let mut vec: Vec<u8> = Vec::with_capacity(len);
-    for _ in 0..len as usize {
-      vec.push(try!(self.pop()))
+    self.read_slice(len).map(|slice| slice.to_vec())
+  }
+
+  /// Borrows the next len bytes of the buffer, without copying them, the slice lives as long as
+  ///  the buffer rather than the decoder
+  pub fn read_slice(&mut self, len: usize) -> DecodeResult<&'a [u8]> {
+    if len > self.len() {
+      return Err(DecodeErrorKind::Message("unexpected end of input reached").into())
     }
 
-    Ok(vec)
+    let slice: &'a [u8] = &self.buffer[self.index..self.index + len];
+    self.index += len;
+    Ok(slice)
   }
 
   pub fn read_u8(&mut self) -> DecodeResult<u8> {
@@ -141,3 +180,11 @@ impl<'a> BinDecoder<'a> {
     Ok(((b1 as u32) << 24) + ((b2 as u32) << 16) + ((b3 as u32) << 8) + (b4 as u32))
   }
 }
+
+/// In the Strict mode, the default, a message which can not be decoded is an error. In the Lenient
+///  mode a record which can not be decoded, e.g. with mangled rdata or of an unknown type, is
+///  skipped by its rdata length and recorded as a warning of the decoder, a record which can not
+///  even be skipped ends the message, so that the records before it are kept. Forwarders and
+///  diagnostic tools may then use the rest of a broken response.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DecodeMode { Strict, Lenient }
@@ -166,6 +166,13 @@ impl<'a> BinEncoder<'a> {
     Ok(())
   }
 
+  /// Overwrites a u16 which was already emitted at the index of the buffer, e.g. the length of
+  ///  rdata, which is only known once the rdata following it has been emitted
+  pub fn set_u16_at(&mut self, index: usize, data: u16) {
+    self.buffer[index] = (data >> 8 & 0xFF) as u8;
+    self.buffer[index + 1] = (data & 0xFF) as u8;
+  }
+
   pub fn emit_vec(&mut self, data: &[u8]) -> EncodeResult {
     self.buffer.reserve(data.len());
 
@@ -17,6 +17,7 @@ mod decoder;
 mod encoder;
 
 pub use self::decoder::BinDecoder;
+pub use self::decoder::DecodeMode;
 pub use self::encoder::BinEncoder;
 pub use self::encoder::EncodeMode;
 
@@ -0,0 +1,329 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The JSON representation of messages, [RFC 8427](https://tools.ietf.org/html/rfc8427),
+//!  Representing DNS Messages in JSON
+//!
+//! ```text
+//! {
+//!   "ID": 1234, "QR": true, "Opcode": 0, "AA": true, "TC": false, "RD": true, "RA": false,
+//!   "AD": false, "CD": false, "RCODE": 0,
+//!   "QDCOUNT": 1, "ANCOUNT": 1, "NSCOUNT": 0, "ARCOUNT": 0,
+//!   "questionRRs": [ { "NAME": "www.example.com.", "TYPE": 1, "TYPEname": "A", "CLASS": 1, "CLASSname": "IN" } ],
+//!   "answerRRs": [ { "NAME": "www.example.com.", "TYPE": 1, "TYPEname": "A", "CLASS": 1, "CLASSname": "IN",
+//!                    "TTL": 3600, "rdataA": "192.0.2.1" } ]
+//! }
+//! ```
+//!
+//! The rdata is in its presentation format, as the member `rdata` followed by the name of the
+//!  type, or hex encoded as `RDATAHEX` for the types without a presentation format which can be
+//!  parsed, e.g. DNSKEY or OPT. The EDNS and SIG(0) records are at the end of the additional
+//!  section, as they are sent.
+//!
+//! The representation is a `serde_json::Value`, and requires the `json` feature.
+//!
+//! ```ignore
+//! let text = serde_json::to_string(&json::to_json(&message)).unwrap();
+//! let message = json::from_json(&serde_json::from_str(&text).unwrap()).unwrap();
+//! ```
+
+use data_encoding::hex;
+use serde_json::{Map, Value};
+
+use ::error::*;
+use ::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+use ::serialize::txt::{has_presentation_format, rdata_from_hex, rdata_from_string, Writer};
+
+fn name_to_json(name: &Name) -> Value {
+  Value::String(Writer::new().relative_names(false).name_to_string(name, &Name::root()))
+}
+
+/// the type and class as numbers and names, the class of an OPT record is its max payload
+fn type_and_class_to_json(object: &mut Map<String, Value>, record_type: RecordType, dns_class: DNSClass) {
+  let type_name: &'static str = record_type.into();
+  object.insert("TYPE".to_string(), Value::U64(u16::from(record_type) as u64));
+  object.insert("TYPEname".to_string(), Value::String(type_name.to_string()));
+  object.insert("CLASS".to_string(), Value::U64(u16::from(dns_class) as u64));
+  if let DNSClass::OPT(..) = dns_class { return }
+
+  let class_name: &'static str = dns_class.into();
+  object.insert("CLASSname".to_string(), Value::String(class_name.to_string()));
+}
+
+/// The JSON representation of the question
+pub fn query_to_json(query: &Query) -> Value {
+  let mut object: Map<String, Value> = Map::new();
+  object.insert("NAME".to_string(), name_to_json(query.get_name()));
+  type_and_class_to_json(&mut object, query.get_query_type(), query.get_query_class());
+  Value::Object(object)
+}
+
+/// The JSON representation of the record
+pub fn record_to_json(record: &Record) -> Value {
+  let mut object: Map<String, Value> = Map::new();
+  object.insert("NAME".to_string(), name_to_json(record.get_name()));
+  type_and_class_to_json(&mut object, record.get_rr_type(), record.get_dns_class());
+  object.insert("TTL".to_string(), Value::U64(record.get_ttl() as u64));
+
+  let rdata: &RData = record.get_rdata();
+  if has_presentation_format(rdata) {
+    let type_name: &'static str = RecordType::from(rdata).into();
+    let text = Writer::new().relative_names(false).rdata_to_string(rdata, &Name::root());
+    object.insert(format!("rdata{}", type_name), Value::String(text));
+  } else {
+    let bytes = rdata.to_bytes();
+    object.insert("RDLENGTH".to_string(), Value::U64(bytes.len() as u64));
+    object.insert("RDATAHEX".to_string(), Value::String(hex::encode(&bytes)));
+  }
+
+  Value::Object(object)
+}
+
+fn records_to_json(records: &[Record]) -> Value {
+  Value::Array(records.iter().map(record_to_json).collect())
+}
+
+/// The JSON representation of the message, the inverse of `from_json()`
+pub fn to_json(message: &Message) -> Value {
+  let mut additionals: Vec<Record> = message.get_additional().to_vec();
+  if let Some(edns) = message.get_edns() { additionals.push(Record::from(edns)); }
+  additionals.extend_from_slice(message.get_sig0());
+
+  let mut object: Map<String, Value> = Map::new();
+  object.insert("ID".to_string(), Value::U64(message.get_id() as u64));
+  object.insert("QR".to_string(), Value::Bool(message.get_message_type() == MessageType::Response));
+  object.insert("Opcode".to_string(), Value::U64(u8::from(message.get_op_code()) as u64));
+  object.insert("AA".to_string(), Value::Bool(message.is_authoritative()));
+  object.insert("TC".to_string(), Value::Bool(message.is_truncated()));
+  object.insert("RD".to_string(), Value::Bool(message.is_recursion_desired()));
+  object.insert("RA".to_string(), Value::Bool(message.is_recursion_available()));
+  object.insert("AD".to_string(), Value::Bool(message.is_authentic_data()));
+  object.insert("CD".to_string(), Value::Bool(message.is_checking_disabled()));
+  object.insert("RCODE".to_string(), Value::U64(u16::from(message.get_response_code()) as u64));
+  object.insert("QDCOUNT".to_string(), Value::U64(message.get_queries().len() as u64));
+  object.insert("ANCOUNT".to_string(), Value::U64(message.get_answers().len() as u64));
+  object.insert("NSCOUNT".to_string(), Value::U64(message.get_name_servers().len() as u64));
+  object.insert("ARCOUNT".to_string(), Value::U64(additionals.len() as u64));
+  object.insert("questionRRs".to_string(), Value::Array(message.get_queries().iter().map(query_to_json).collect()));
+  object.insert("answerRRs".to_string(), records_to_json(message.get_answers()));
+  object.insert("authorityRRs".to_string(), records_to_json(message.get_name_servers()));
+  object.insert("additionalRRs".to_string(), records_to_json(&additionals));
+  Value::Object(object)
+}
+
+fn invalid(member: &str) -> ParseError {
+  ParseErrorKind::Msg(format!("invalid or missing member: {}", member)).into()
+}
+
+fn get_u64(object: &Map<String, Value>, member: &str) -> ParseResult<Option<u64>> {
+  match object.get(member) {
+    None => Ok(None),
+    Some(value) => value.as_u64().map(Some).ok_or(invalid(member)),
+  }
+}
+
+fn get_u16(object: &Map<String, Value>, member: &str) -> ParseResult<Option<u16>> {
+  match try!(get_u64(object, member)) {
+    Some(value) if value > u16::max_value() as u64 => Err(invalid(member)),
+    value => Ok(value.map(|value| value as u16)),
+  }
+}
+
+fn get_bool(object: &Map<String, Value>, member: &str) -> ParseResult<bool> {
+  match object.get(member) {
+    None => Ok(false),
+    Some(value) => value.as_bool().ok_or(invalid(member)),
+  }
+}
+
+fn get_str<'a>(object: &'a Map<String, Value>, member: &str) -> ParseResult<Option<&'a str>> {
+  match object.get(member) {
+    None => Ok(None),
+    Some(value) => value.as_str().map(Some).ok_or(invalid(member)),
+  }
+}
+
+/// the type and class from their numbers, or their names, the class is IN if neither is present
+fn type_and_class_from_json(object: &Map<String, Value>) -> ParseResult<(RecordType, DNSClass)> {
+  let record_type = match (try!(get_u16(object, "TYPE")), try!(get_str(object, "TYPEname"))) {
+    (Some(value), _) => try!(RecordType::from_u16(value)),
+    (None, Some(name)) => try!(RecordType::from_str(name)),
+    (None, None) => return Err(invalid("TYPE")),
+  };
+
+  let dns_class = match (try!(get_u16(object, "CLASS")), try!(get_str(object, "CLASSname"))) {
+    (Some(value), _) if record_type == RecordType::OPT => DNSClass::for_opt(value),
+    (Some(value), _) => try!(DNSClass::from_u16(value)),
+    (None, Some(name)) => try!(DNSClass::from_str(name)),
+    (None, None) => DNSClass::IN,
+  };
+
+  Ok((record_type, dns_class))
+}
+
+fn name_from_json(object: &Map<String, Value>, member: &str) -> ParseResult<Name> {
+  let name = try!(try!(get_str(object, member)).ok_or(invalid(member)));
+  name.parse()
+}
+
+fn query_from_json(json: &Value) -> ParseResult<Query> {
+  let object = try!(json.as_object().ok_or(invalid("questionRRs")));
+  let (query_type, query_class) = try!(type_and_class_from_json(object));
+
+  let mut query = Query::new();
+  query.name(try!(name_from_json(object, "NAME"))).query_type(query_type).query_class(query_class);
+  Ok(query)
+}
+
+/// Parses a record from its JSON representation, the rdata is read from the presentation format
+///  member, e.g. `rdataMX`, or else from `RDATAHEX`, and is empty if neither is present.
+pub fn record_from_json(json: &Value) -> ParseResult<Record> {
+  let object = try!(json.as_object().ok_or(invalid("RR")));
+  let (record_type, dns_class) = try!(type_and_class_from_json(object));
+
+  let type_name: &'static str = record_type.into();
+  let presentation_member = format!("rdata{}", type_name);
+  let rdata = match (try!(get_str(object, &presentation_member)), try!(get_str(object, "RDATAHEX"))) {
+    (Some(text), _) => try!(rdata_from_string(record_type, text)),
+    (None, Some(data)) => try!(rdata_from_hex(record_type, (data.len() / 2) as u16, data)),
+    (None, None) => try!(rdata_from_hex(record_type, 0, "")),
+  };
+
+  let mut record = Record::new();
+  record.name(try!(name_from_json(object, "NAME")))
+        .rr_type(record_type)
+        .dns_class(dns_class)
+        .ttl(try!(get_u64(object, "TTL")).unwrap_or(0) as u32)
+        .rdata(rdata);
+  Ok(record)
+}
+
+fn records_from_json(object: &Map<String, Value>, member: &str) -> ParseResult<Vec<Record>> {
+  match object.get(member) {
+    None => Ok(vec![]),
+    Some(records) => {
+      let records = try!(records.as_array().ok_or(invalid(member)));
+      records.iter().map(record_from_json).collect()
+    },
+  }
+}
+
+/// Parses a message from its JSON representation, the counts are ignored, and a single question
+///  may be the `QNAME`, `QTYPE` and `QCLASS` members of the message. Missing flags are false.
+pub fn from_json(json: &Value) -> ParseResult<Message> {
+  let object = try!(json.as_object().ok_or(ParseError::from(ParseErrorKind::Message("the message is not an object"))));
+
+  let mut message = Message::new();
+  message.id(try!(get_u16(object, "ID")).unwrap_or(0))
+         .message_type(if try!(get_bool(object, "QR")) { MessageType::Response } else { MessageType::Query })
+         .op_code(try!(OpCode::from_u8(try!(get_u16(object, "Opcode")).unwrap_or(0) as u8)))
+         .authoritative(try!(get_bool(object, "AA")))
+         .truncated(try!(get_bool(object, "TC")))
+         .recursion_desired(try!(get_bool(object, "RD")))
+         .recursion_available(try!(get_bool(object, "RA")))
+         .authentic_data(try!(get_bool(object, "AD")))
+         .checking_disabled(try!(get_bool(object, "CD")))
+         .response_code(ResponseCode::from(try!(get_u16(object, "RCODE")).unwrap_or(0)));
+
+  match object.get("questionRRs") {
+    Some(queries) => {
+      for query in try!(queries.as_array().ok_or(invalid("questionRRs"))) {
+        message.add_query(try!(query_from_json(query)));
+      }
+    },
+    None => if object.contains_key("QNAME") {
+      let mut query = Query::new();
+      query.name(try!(name_from_json(object, "QNAME")))
+           .query_type(try!(RecordType::from_u16(try!(get_u16(object, "QTYPE")).unwrap_or(1))))
+           .query_class(try!(DNSClass::from_u16(try!(get_u16(object, "QCLASS")).unwrap_or(1))));
+      message.add_query(query);
+    },
+  }
+
+  for record in try!(records_from_json(object, "answerRRs")) { message.add_answer(record); }
+  for record in try!(records_from_json(object, "authorityRRs")) { message.add_name_server(record); }
+  for record in try!(records_from_json(object, "additionalRRs")) {
+    match record.get_rr_type() {
+      RecordType::OPT => {
+        if message.get_edns().is_some() { return Err(ParseErrorKind::Message("more than one edns record present").into()) }
+        message.set_edns(Edns::from(&record));
+      },
+      RecordType::SIG | RecordType::TSIG => { message.add_sig0(record); },
+      _ => { message.add_additional(record); },
+    }
+  }
+
+  Ok(message)
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::Ipv4Addr;
+
+  use serde_json::{self, Value};
+
+  use ::op::{Edns, Message, MessageType, Query};
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::rr::rdata::NULL;
+  use super::*;
+
+  #[test]
+  fn test_to_and_from_json() {
+    let mut query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+
+    let mut edns = Edns::new();
+    edns.set_max_payload(4096);
+
+    let mut message = Message::new();
+    message.id(1234).message_type(MessageType::Response).authoritative(true).recursion_desired(true);
+    message.add_query(query);
+    message.add_answer(Record::new().name(Name::parse("www.example.com.", None).unwrap()).ttl(3600).rr_type(RecordType::A)
+                                    .dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(192, 0, 2, 1))).clone());
+    message.add_additional(Record::new().name(Name::parse("example.com.", None).unwrap()).ttl(60).rr_type(RecordType::NULL)
+                                        .dns_class(DNSClass::IN).rdata(RData::NULL(NULL::with(vec![1, 2, 0xAB]))).clone());
+    message.set_edns(edns);
+
+    let text = serde_json::to_string(&to_json(&message)).unwrap();
+    assert!(text.contains(r#""ID":1234"#), "{}", text);
+    assert!(text.contains(r#""QR":true"#), "{}", text);
+    assert!(text.contains(r#""ARCOUNT":2"#), "{}", text);
+    assert!(text.contains(r#""rdataA":"192.0.2.1""#), "{}", text);
+    assert!(text.contains(r#""RDATAHEX":"0102AB""#), "{}", text);
+    assert!(text.contains(r#""CLASS":4096"#), "{}", text);
+
+    assert_eq!(from_json(&serde_json::from_str(&text).unwrap()).unwrap(), message);
+  }
+
+  #[test]
+  fn test_from_json() {
+    let json: Value = serde_json::from_str(r#"{ "ID": 7, "RD": true, "QNAME": "example.com", "QTYPE": 15,
+                                          "answerRRs": [ { "NAME": "example.com.", "TYPEname": "MX", "TTL": 300, "rdataMX": "10 mail.example.com." } ] }"#).unwrap();
+    let message = from_json(&json).unwrap();
+
+    assert_eq!(message.get_id(), 7);
+    assert_eq!(message.get_message_type(), MessageType::Query);
+    assert!(message.is_recursion_desired());
+    assert_eq!(message.get_queries()[0].get_name(), &Name::parse("example.com.", None).unwrap());
+    assert_eq!(message.get_queries()[0].get_query_type(), RecordType::MX);
+    assert_eq!(message.get_answers()[0].get_dns_class(), DNSClass::IN);
+    assert_eq!(message.get_answers()[0].get_ttl(), 300);
+
+    assert!(from_json(&serde_json::from_str(r#"{ "ID": 70000 }"#).unwrap()).is_err());
+    assert!(from_json(&serde_json::from_str(r#"{ "answerRRs": [ { "NAME": "example.com.", "TYPE": 1, "rdataA": "example" } ] }"#).unwrap()).is_err());
+  }
+}
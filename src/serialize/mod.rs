@@ -14,7 +14,13 @@
  * limitations under the License.
  */
 
-//! Contains serialization libraries for `binary` and text, `txt`.
+//! Contains serialization libraries for `binary` and text, `txt`, with the `serde` feature the
+//!  `Serialize` and `Deserialize` implementations of the messages and records, and with the `json`
+//!  feature their JSON representation, `json`.
 
 pub mod binary;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod txt;
+#[cfg(feature = "serde")]
+mod serde_impls;
@@ -0,0 +1,468 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `Serialize` and `Deserialize` of serde for `Message`, `Query`, `Record`, `RData`, `Name`,
+//!  `RecordType` and `DNSClass`, with the `serde` feature.
+//!
+//! The names, types and classes are strings, and the rdata is in its presentation format, e.g.
+//!  `10 mail.example.com.` for an MX, so that serialized messages are readable, e.g. as JSON.
+//!  The rdata of the types which have no presentation format which can be parsed, e.g. DNSKEY
+//!  or OPT, is in the generic format of RFC 3597, `\# 4 C0000201`. The EDNS and SIG(0) records
+//!  of a message are serialized as they are sent, at the end of the additional section.
+
+use std::num::ParseIntError;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, MapVisitor, SeqVisitor, Visitor};
+use serde::de::impls::IgnoredAny;
+
+use ::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+use ::serialize::txt::{rdata_from_string, rdata_to_string, Writer};
+
+const QUERY_FIELDS: &'static [&'static str] = &["name", "type", "class"];
+const RECORD_FIELDS: &'static [&'static str] = &["name", "type", "class", "ttl", "rdata"];
+const RDATA_FIELDS: &'static [&'static str] = &["type", "data"];
+const MESSAGE_FIELDS: &'static [&'static str] = &["id", "message_type", "op_code", "authoritative", "truncated", "recursion_desired",
+                                                  "recursion_available", "authentic_data", "checking_disabled", "response_code",
+                                                  "queries", "answers", "name_servers", "additionals"];
+
+/// visits a string, which is converted by the function
+struct FromStrVisitor<T>(fn(&str) -> Result<T, String>);
+
+impl<T: Deserialize> Visitor for FromStrVisitor<T> {
+  type Value = T;
+
+  fn visit_str<E: de::Error>(&mut self, value: &str) -> Result<T, E> {
+    (self.0)(value).map_err(E::custom)
+  }
+}
+
+/// the next field of a struct which was serialized as a sequence, e.g. by bincode
+fn next_field<T: Deserialize, V: SeqVisitor>(visitor: &mut V, index: usize) -> Result<T, V::Error> {
+  match try!(visitor.visit::<T>()) {
+    Some(value) => Ok(value),
+    None => Err(de::Error::invalid_length(index)),
+  }
+}
+
+impl Serialize for Name {
+  fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+    serializer.serialize_str(&Writer::new().relative_names(false).name_to_string(self, &Name::root()))
+  }
+}
+
+impl Deserialize for Name {
+  fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Self, D::Error> {
+    deserializer.deserialize_str(FromStrVisitor(name_from_str))
+  }
+}
+
+fn name_from_str(value: &str) -> Result<Name, String> {
+  value.parse::<Name>().map_err(|e| e.to_string())
+}
+
+impl Serialize for RecordType {
+  fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+    let rr_type: &'static str = (*self).into();
+    serializer.serialize_str(rr_type)
+  }
+}
+
+impl Deserialize for RecordType {
+  fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Self, D::Error> {
+    deserializer.deserialize_str(FromStrVisitor(record_type_from_str))
+  }
+}
+
+fn record_type_from_str(value: &str) -> Result<RecordType, String> {
+  RecordType::from_str(value).map_err(|e| e.to_string())
+}
+
+/// the class of an OPT record is the max payload, it is written as an unknown class, RFC 3597
+impl Serialize for DNSClass {
+  fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+    match *self {
+      DNSClass::OPT(max_payload) => serializer.serialize_str(&format!("CLASS{}", max_payload)),
+      dns_class => {
+        let dns_class: &'static str = dns_class.into();
+        serializer.serialize_str(dns_class)
+      },
+    }
+  }
+}
+
+impl Deserialize for DNSClass {
+  fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Self, D::Error> {
+    deserializer.deserialize_str(FromStrVisitor(dns_class_from_str))
+  }
+}
+
+fn dns_class_from_str(value: &str) -> Result<DNSClass, String> {
+  if value.starts_with("CLASS") {
+    let value: u16 = try!(value[5..].parse().map_err(|e: ParseIntError| e.to_string()));
+    return Ok(DNSClass::from_u16(value).unwrap_or(DNSClass::for_opt(value)))
+  }
+
+  DNSClass::from_str(value).map_err(|e| e.to_string())
+}
+
+/// The rdata on its own is its type and data, `{ "type": "A", "data": "192.0.2.1" }`
+impl Serialize for RData {
+  fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+    let mut state = try!(serializer.serialize_struct("RData", RDATA_FIELDS.len()));
+    try!(serializer.serialize_struct_elt(&mut state, "type", RecordType::from(self)));
+    try!(serializer.serialize_struct_elt(&mut state, "data", rdata_to_string(self)));
+    serializer.serialize_struct_end(state)
+  }
+}
+
+impl Deserialize for RData {
+  fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Self, D::Error> {
+    deserializer.deserialize_struct("RData", RDATA_FIELDS, RDataVisitor)
+  }
+}
+
+struct RDataVisitor;
+
+impl Visitor for RDataVisitor {
+  type Value = RData;
+
+  fn visit_map<V: MapVisitor>(&mut self, mut visitor: V) -> Result<RData, V::Error> {
+    let mut record_type: Option<RecordType> = None;
+    let mut data: Option<String> = None;
+    while let Some(key) = try!(visitor.visit_key::<String>()) {
+      match &key as &str {
+        "type" => record_type = Some(try!(visitor.visit_value())),
+        "data" => data = Some(try!(visitor.visit_value())),
+        _ => { try!(visitor.visit_value::<IgnoredAny>()); },
+      }
+    }
+    try!(visitor.end());
+
+    let record_type = match record_type { Some(record_type) => record_type, None => try!(visitor.missing_field("type")) };
+    let data: String = match data { Some(data) => data, None => try!(visitor.missing_field("data")) };
+    rdata_from_string(record_type, &data).map_err(|e| de::Error::custom(e.to_string()))
+  }
+
+  fn visit_seq<V: SeqVisitor>(&mut self, mut visitor: V) -> Result<RData, V::Error> {
+    let record_type: RecordType = try!(next_field(&mut visitor, 0));
+    let data: String = try!(next_field(&mut visitor, 1));
+    try!(visitor.end());
+
+    rdata_from_string(record_type, &data).map_err(|e| de::Error::custom(e.to_string()))
+  }
+}
+
+impl Serialize for Query {
+  fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+    let mut state = try!(serializer.serialize_struct("Query", QUERY_FIELDS.len()));
+    try!(serializer.serialize_struct_elt(&mut state, "name", self.get_name()));
+    try!(serializer.serialize_struct_elt(&mut state, "type", self.get_query_type()));
+    try!(serializer.serialize_struct_elt(&mut state, "class", self.get_query_class()));
+    serializer.serialize_struct_end(state)
+  }
+}
+
+impl Deserialize for Query {
+  fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Self, D::Error> {
+    deserializer.deserialize_struct("Query", QUERY_FIELDS, QueryVisitor)
+  }
+}
+
+struct QueryVisitor;
+
+impl Visitor for QueryVisitor {
+  type Value = Query;
+
+  fn visit_map<V: MapVisitor>(&mut self, mut visitor: V) -> Result<Query, V::Error> {
+    let mut query = Query::new();
+    while let Some(key) = try!(visitor.visit_key::<String>()) {
+      match &key as &str {
+        "name" => { query.name(try!(visitor.visit_value())); },
+        "type" => { query.query_type(try!(visitor.visit_value())); },
+        "class" => { query.query_class(try!(visitor.visit_value())); },
+        _ => { try!(visitor.visit_value::<IgnoredAny>()); },
+      }
+    }
+    try!(visitor.end());
+
+    Ok(query)
+  }
+
+  fn visit_seq<V: SeqVisitor>(&mut self, mut visitor: V) -> Result<Query, V::Error> {
+    let mut query = Query::new();
+    query.name(try!(next_field(&mut visitor, 0)))
+         .query_type(try!(next_field(&mut visitor, 1)))
+         .query_class(try!(next_field(&mut visitor, 2)));
+    try!(visitor.end());
+
+    Ok(query)
+  }
+}
+
+impl Serialize for Record {
+  fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+    let mut state = try!(serializer.serialize_struct("Record", RECORD_FIELDS.len()));
+    try!(serializer.serialize_struct_elt(&mut state, "name", self.get_name()));
+    try!(serializer.serialize_struct_elt(&mut state, "type", self.get_rr_type()));
+    try!(serializer.serialize_struct_elt(&mut state, "class", self.get_dns_class()));
+    try!(serializer.serialize_struct_elt(&mut state, "ttl", self.get_ttl()));
+    try!(serializer.serialize_struct_elt(&mut state, "rdata", rdata_to_string(self.get_rdata())));
+    serializer.serialize_struct_end(state)
+  }
+}
+
+impl Deserialize for Record {
+  fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Self, D::Error> {
+    deserializer.deserialize_struct("Record", RECORD_FIELDS, RecordVisitor)
+  }
+}
+
+/// the rdata is parsed once the type of the record is known
+fn finish_record(mut record: Record, rdata: Option<String>) -> Result<Record, String> {
+  if record.get_rr_type() == RecordType::OPT {
+    let max_payload: u16 = record.get_dns_class().into();
+    record.dns_class(DNSClass::for_opt(max_payload));
+  }
+
+  if let Some(rdata) = rdata {
+    let rdata = try!(rdata_from_string(record.get_rr_type(), &rdata).map_err(|e| e.to_string()));
+    record.rdata(rdata);
+  }
+
+  Ok(record)
+}
+
+struct RecordVisitor;
+
+impl Visitor for RecordVisitor {
+  type Value = Record;
+
+  fn visit_map<V: MapVisitor>(&mut self, mut visitor: V) -> Result<Record, V::Error> {
+    let mut record = Record::new();
+    let mut rdata: Option<String> = None;
+    while let Some(key) = try!(visitor.visit_key::<String>()) {
+      match &key as &str {
+        "name" => { record.name(try!(visitor.visit_value())); },
+        "type" => { record.rr_type(try!(visitor.visit_value())); },
+        "class" => { record.dns_class(try!(visitor.visit_value())); },
+        "ttl" => { record.ttl(try!(visitor.visit_value())); },
+        "rdata" => rdata = Some(try!(visitor.visit_value())),
+        _ => { try!(visitor.visit_value::<IgnoredAny>()); },
+      }
+    }
+    try!(visitor.end());
+
+    finish_record(record, rdata).map_err(de::Error::custom)
+  }
+
+  fn visit_seq<V: SeqVisitor>(&mut self, mut visitor: V) -> Result<Record, V::Error> {
+    let mut record = Record::new();
+    record.name(try!(next_field(&mut visitor, 0)))
+          .rr_type(try!(next_field(&mut visitor, 1)))
+          .dns_class(try!(next_field(&mut visitor, 2)))
+          .ttl(try!(next_field(&mut visitor, 3)));
+    let rdata: String = try!(next_field(&mut visitor, 4));
+    try!(visitor.end());
+
+    finish_record(record, Some(rdata)).map_err(de::Error::custom)
+  }
+}
+
+fn message_type_to_str(message_type: MessageType) -> &'static str {
+  match message_type {
+    MessageType::Query => "Query",
+    MessageType::Response => "Response",
+  }
+}
+
+fn message_type_from_str(value: &str) -> Result<MessageType, String> {
+  match value {
+    "Query" => Ok(MessageType::Query),
+    "Response" => Ok(MessageType::Response),
+    _ => Err(format!("unknown message type: {}", value)),
+  }
+}
+
+/// the records at the end of the additional section, the EDNS record and the SIG(0) or TSIG
+fn add_additionals(message: &mut Message, additionals: Vec<Record>) -> Result<(), String> {
+  for record in additionals {
+    match record.get_rr_type() {
+      RecordType::OPT => {
+        if message.get_edns().is_some() { return Err("more than one edns record present".to_string()) }
+        message.set_edns(Edns::from(&record));
+      },
+      RecordType::SIG | RecordType::TSIG => { message.add_sig0(record); },
+      _ => { message.add_additional(record); },
+    }
+  }
+
+  Ok(())
+}
+
+impl Serialize for Message {
+  fn serialize<S: Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+    let mut additionals: Vec<Record> = self.get_additional().to_vec();
+    if let Some(edns) = self.get_edns() { additionals.push(Record::from(edns)); }
+    additionals.extend_from_slice(self.get_sig0());
+
+    let mut state = try!(serializer.serialize_struct("Message", MESSAGE_FIELDS.len()));
+    try!(serializer.serialize_struct_elt(&mut state, "id", self.get_id()));
+    try!(serializer.serialize_struct_elt(&mut state, "message_type", message_type_to_str(self.get_message_type())));
+    try!(serializer.serialize_struct_elt(&mut state, "op_code", u8::from(self.get_op_code())));
+    try!(serializer.serialize_struct_elt(&mut state, "authoritative", self.is_authoritative()));
+    try!(serializer.serialize_struct_elt(&mut state, "truncated", self.is_truncated()));
+    try!(serializer.serialize_struct_elt(&mut state, "recursion_desired", self.is_recursion_desired()));
+    try!(serializer.serialize_struct_elt(&mut state, "recursion_available", self.is_recursion_available()));
+    try!(serializer.serialize_struct_elt(&mut state, "authentic_data", self.is_authentic_data()));
+    try!(serializer.serialize_struct_elt(&mut state, "checking_disabled", self.is_checking_disabled()));
+    try!(serializer.serialize_struct_elt(&mut state, "response_code", u16::from(self.get_response_code())));
+    try!(serializer.serialize_struct_elt(&mut state, "queries", self.get_queries()));
+    try!(serializer.serialize_struct_elt(&mut state, "answers", self.get_answers()));
+    try!(serializer.serialize_struct_elt(&mut state, "name_servers", self.get_name_servers()));
+    try!(serializer.serialize_struct_elt(&mut state, "additionals", additionals));
+    serializer.serialize_struct_end(state)
+  }
+}
+
+impl Deserialize for Message {
+  fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Self, D::Error> {
+    deserializer.deserialize_struct("Message", MESSAGE_FIELDS, MessageVisitor)
+  }
+}
+
+struct MessageVisitor;
+
+impl Visitor for MessageVisitor {
+  type Value = Message;
+
+  /// missing fields are those of `Message::new()`
+  fn visit_map<V: MapVisitor>(&mut self, mut visitor: V) -> Result<Message, V::Error> {
+    let mut message = Message::new();
+    let mut additionals: Vec<Record> = Vec::new();
+    while let Some(key) = try!(visitor.visit_key::<String>()) {
+      match &key as &str {
+        "id" => { message.id(try!(visitor.visit_value())); },
+        "message_type" => {
+          let message_type: String = try!(visitor.visit_value());
+          message.message_type(try!(message_type_from_str(&message_type).map_err(<V::Error as de::Error>::custom)));
+        },
+        "op_code" => {
+          let op_code: u8 = try!(visitor.visit_value());
+          message.op_code(try!(OpCode::from_u8(op_code).map_err(|e| <V::Error as de::Error>::custom(e.to_string()))));
+        },
+        "authoritative" => { message.authoritative(try!(visitor.visit_value())); },
+        "truncated" => { message.truncated(try!(visitor.visit_value())); },
+        "recursion_desired" => { message.recursion_desired(try!(visitor.visit_value())); },
+        "recursion_available" => { message.recursion_available(try!(visitor.visit_value())); },
+        "authentic_data" => { message.authentic_data(try!(visitor.visit_value())); },
+        "checking_disabled" => { message.checking_disabled(try!(visitor.visit_value())); },
+        "response_code" => { message.response_code(ResponseCode::from(try!(visitor.visit_value::<u16>()))); },
+        "queries" => { for query in try!(visitor.visit_value::<Vec<Query>>()) { message.add_query(query); } },
+        "answers" => { for record in try!(visitor.visit_value::<Vec<Record>>()) { message.add_answer(record); } },
+        "name_servers" => { for record in try!(visitor.visit_value::<Vec<Record>>()) { message.add_name_server(record); } },
+        "additionals" => additionals = try!(visitor.visit_value()),
+        _ => { try!(visitor.visit_value::<IgnoredAny>()); },
+      }
+    }
+    try!(visitor.end());
+
+    try!(add_additionals(&mut message, additionals).map_err(<V::Error as de::Error>::custom));
+    Ok(message)
+  }
+
+  fn visit_seq<V: SeqVisitor>(&mut self, mut visitor: V) -> Result<Message, V::Error> {
+    let mut message = Message::new();
+    message.id(try!(next_field(&mut visitor, 0)));
+    let message_type: String = try!(next_field(&mut visitor, 1));
+    message.message_type(try!(message_type_from_str(&message_type).map_err(<V::Error as de::Error>::custom)));
+    let op_code: u8 = try!(next_field(&mut visitor, 2));
+    message.op_code(try!(OpCode::from_u8(op_code).map_err(|e| <V::Error as de::Error>::custom(e.to_string()))));
+    message.authoritative(try!(next_field(&mut visitor, 3)))
+           .truncated(try!(next_field(&mut visitor, 4)))
+           .recursion_desired(try!(next_field(&mut visitor, 5)))
+           .recursion_available(try!(next_field(&mut visitor, 6)))
+           .authentic_data(try!(next_field(&mut visitor, 7)))
+           .checking_disabled(try!(next_field(&mut visitor, 8)))
+           .response_code(ResponseCode::from(try!(next_field::<u16, V>(&mut visitor, 9))));
+
+    for query in try!(next_field::<Vec<Query>, V>(&mut visitor, 10)) { message.add_query(query); }
+    for record in try!(next_field::<Vec<Record>, V>(&mut visitor, 11)) { message.add_answer(record); }
+    for record in try!(next_field::<Vec<Record>, V>(&mut visitor, 12)) { message.add_name_server(record); }
+    let additionals: Vec<Record> = try!(next_field(&mut visitor, 13));
+    try!(visitor.end());
+
+    try!(add_additionals(&mut message, additionals).map_err(<V::Error as de::Error>::custom));
+    Ok(message)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::Ipv4Addr;
+
+  use serde_json;
+
+  use ::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::rr::rdata::{MX, NULL};
+
+  fn record(name: &str, rr_type: RecordType, rdata: RData) -> Record {
+    Record::new().name(Name::parse(name, None).unwrap()).ttl(3600).rr_type(rr_type).dns_class(DNSClass::IN).rdata(rdata).clone()
+  }
+
+  #[test]
+  fn test_record() {
+    let mx = record("example.com.", RecordType::MX, RData::MX(MX::new(10, Name::parse("mail.example.com.", None).unwrap())));
+    let json = serde_json::to_string(&mx).unwrap();
+    assert_eq!(json, r#"{"name":"example.com.","type":"MX","class":"IN","ttl":3600,"rdata":"10 mail.example.com."}"#);
+    assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), mx);
+
+    // the generic format of types without a presentation format
+    let null = record("example.com.", RecordType::NULL, RData::NULL(NULL::with(vec![1, 2, 0xAB])));
+    let json = serde_json::to_string(&null).unwrap();
+    assert!(json.contains(r#""rdata":"\\# 3 0102AB""#), "{}", json);
+    assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), null);
+
+    let rdata = RData::A(Ipv4Addr::new(192, 0, 2, 1));
+    let json = serde_json::to_string(&rdata).unwrap();
+    assert_eq!(json, r#"{"type":"A","data":"192.0.2.1"}"#);
+    assert_eq!(serde_json::from_str::<RData>(&json).unwrap(), rdata);
+
+    assert!(serde_json::from_str::<Record>(r#"{"name":"example.com.","type":"A","rdata":"not an address"}"#).is_err());
+  }
+
+  #[test]
+  fn test_message() {
+    let mut query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+
+    let mut edns = Edns::new();
+    edns.set_max_payload(4096);
+
+    let mut message = Message::new();
+    message.id(1234).message_type(MessageType::Response).op_code(OpCode::Query).authoritative(true)
+           .recursion_desired(true).response_code(ResponseCode::NXDomain);
+    message.add_query(query);
+    message.add_answer(record("www.example.com.", RecordType::A, RData::A(Ipv4Addr::new(192, 0, 2, 1))));
+    message.set_edns(edns);
+
+    let json = serde_json::to_string(&message).unwrap();
+    assert!(json.contains(r#""type":"OPT","class":"CLASS4096""#), "{}", json);
+    assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+  }
+}
@@ -16,6 +16,7 @@
 use std::collections::BTreeMap;
 use std::io::Read;
 use std::fs::File;
+use std::path::{Path, PathBuf};
 
 use ::error::*;
 use ::rr::{ Name, RecordType, Record, DNSClass, RData};
@@ -123,11 +124,55 @@ use super::master_lex::{Lexer, Token};
 /// ;               Semicolon is used to start a comment; the remainder of
 ///                 the line is ignored.
 /// ```
-pub struct Parser;
+pub struct Parser {
+  include_dir: Option<PathBuf>,
+  max_include_depth: usize,
+  depth: usize,
+}
+
+/// The state which carries over from one entry to the next
+struct Context {
+  origin: Option<Name>,
+  current_name: Option<Name>,
+  class: Option<DNSClass>,
+  // the last explicitly stated TTL, RFC 1035
+  ttl: Option<u32>,
+  // the $TTL, RFC 2308
+  default_ttl: Option<u32>,
+}
+
+/// the default nesting of `$INCLUDE`s, beyond which a file is assumed to include itself
+const DEFAULT_MAX_INCLUDE_DEPTH: usize = 8;
+/// the most records a single `$GENERATE` may expand to, the range is otherwise unbounded
+const MAX_GENERATE_COUNT: u32 = 65536;
 
 impl Parser {
   pub fn new() -> Self {
-    Parser
+    Parser{ include_dir: None, max_include_depth: DEFAULT_MAX_INCLUDE_DEPTH, depth: 0 }
+  }
+
+  /// The directory of the files of `$INCLUDE`s with relative paths, as with the `directory` of
+  ///  BIND this is also used for files included by included files. The default is the working
+  ///  directory, `parse_path()` uses the directory of the zone file.
+  pub fn include_dir(&mut self, include_dir: Option<PathBuf>) -> &mut Self {
+    self.include_dir = include_dir;
+    self
+  }
+
+  /// The number of `$INCLUDE`s which may be nested, default is 8
+  pub fn max_include_depth(&mut self, max_include_depth: usize) -> &mut Self {
+    self.max_include_depth = max_include_depth;
+    self
+  }
+
+  /// Parses the zone file, the paths of its `$INCLUDE`s are relative to its directory
+  pub fn parse_path(path: &Path, origin: Option<Name>, zone_type: ZoneType, allow_update: bool) -> ParseResult<Authority> {
+    let mut buf = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut buf));
+
+    let mut parser = Self::new();
+    parser.include_dir(path.parent().map(|dir| dir.to_path_buf()));
+    parser.parse(Lexer::new(&buf), origin, zone_type, allow_update)
   }
 
   pub fn parse_file(file: File, origin: Option<Name>, zone_type: ZoneType, allow_update: bool) -> ParseResult<Authority> {
@@ -141,16 +186,31 @@ impl Parser {
     Self::new().parse(lexer, origin, zone_type, allow_update)
   }
 
-  // TODO: change this function to load into an Authority, using the update_records() method
+  /// Parses the master file into an Authority. The origin of the zone is the owner of the SOA
+  ///  record, or else the last `$ORIGIN`.
+  ///
+  /// Omitted owners, classes and TTLs are those of the previous entry. A record without a TTL has
+  ///  the `$TTL` of RFC 2308 if it was set, otherwise the last stated TTL, or else the minimum of
+  ///  the SOA. The class defaults to IN.
   pub fn parse(&mut self, lexer: Lexer, origin: Option<Name>, zone_type: ZoneType, allow_update: bool) -> ParseResult<Authority> {
-    let mut lexer = lexer;
     let mut records: BTreeMap<RrKey, RRSet> = BTreeMap::new();
+    let mut context = Context{ origin: origin, current_name: None, class: None, ttl: None, default_ttl: None };
+
+    try!(self.read(lexer, &mut context, &mut records));
+
+    let soa_name = records.values().find(|rrset| rrset.get_record_type() == RecordType::SOA).map(|rrset| rrset.get_name().clone());
+    let origin = try!(soa_name.or(context.origin).ok_or(ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))));
+
+    //
+    // build the Authority and return.
+    Ok(Authority::new(origin, records, zone_type, allow_update))
+  }
 
-    let mut origin: Option<Name> = origin;
-    let mut current_name: Option<Name> = None;
+  /// reads the entries of the lexer into the records
+  fn read(&mut self, lexer: Lexer, context: &mut Context, records: &mut BTreeMap<RrKey, RRSet>) -> ParseResult<()> {
+    let mut lexer = lexer;
     let mut rtype: Option<RecordType> = None;
-    let mut ttl: Option<u32> = None;
-    let mut class: Option<DNSClass> = None;
+    let mut record_ttl: Option<u32> = None;
     let mut state = State::StartLine;
     let mut tokens: Vec<Token> = Vec::new();
 
@@ -159,23 +219,25 @@ impl Parser {
         State::StartLine => {
           // current_name is not reset on the next line b/c it might be needed from the previous
           rtype = None;
+          record_ttl = None;
           tokens.clear();
 
           match t {
             // if Dollar, then $INCLUDE or $ORIGIN
-            Token::Include => unimplemented!(),
+            Token::Include => State::Include,
             Token::Origin => State::Origin,
             Token::Ttl => State::Ttl,
+            Token::Generate => State::Generate,
 
             // if CharData, then Name then ttl_class_type
             Token::CharData(ref data) => {
-              current_name = Some(try!(Name::parse(data, origin.as_ref())));
+              context.current_name = Some(try!(Name::parse(data, context.origin.as_ref())));
               State::TtlClassType
             },
 
             // @ is a placeholder for specifying the current origin
             Token::At => {
-              current_name = origin.clone(); // TODO a COW or RC would reduce copies...
+              context.current_name = context.origin.clone(); // TODO a COW or RC would reduce copies...
               State::TtlClassType
             }
 
@@ -190,7 +252,7 @@ impl Parser {
         State::Ttl => {
           match t {
             Token::CharData(ref data) => {
-              ttl = Some(try!(Self::parse_time(data)));
+              context.default_ttl = Some(try!(Self::parse_time(data)));
               State::StartLine
             }
             _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
@@ -199,37 +261,53 @@ impl Parser {
         State::Origin => {
           match t {
             Token::CharData(ref data) => {
-              // TODO an origin was specified, should this be legal? definitely confusing...
-              origin = Some(try!(Name::parse(data, None)));
+              // a relative origin is relative to the current origin
+              context.origin = Some(try!(Name::parse(data, context.origin.as_ref())));
               State::StartLine
             }
             _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
           }
         }
-        State::Include => unimplemented!(),
+        State::Generate => {
+          // <range> <lhs> [<ttl>] [<class>] <type> <rhs>
+          match t {
+            Token::EOL => {
+              try!(self.generate(&tokens, context, records));
+              State::StartLine
+            },
+            Token::CharData(..) => { tokens.push(t); State::Generate },
+            Token::At => { tokens.push(Token::CharData("@".to_string())); State::Generate },
+            _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+          }
+        },
+        State::Include => {
+          // <file-name> [<domain-name>]
+          match t {
+            Token::EOL => {
+              try!(self.include(&tokens, context, records));
+              State::StartLine
+            },
+            Token::CharData(..) => { tokens.push(t); State::Include },
+            _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+          }
+        },
         State::TtlClassType => {
           match t {
-            // if number, TTL
-            // Token::Number(ref num) => ttl = Some(*num),
-            // One of Class or Type (these cannot be overlapping!)
+            // One of TTL, Class or Type (these cannot be overlapping!)
             Token::CharData(ref data) => {
-              // if it's a number it's a ttl
-              let result: ParseResult<u32> = Self::parse_time(data);
-              if result.is_ok() {
-                ttl = result.ok();
-                State::TtlClassType // hm, should this go to just ClassType?
+              // if it starts with a digit it's a ttl, e.g. 3600 or 1h
+              if data.starts_with(|c: char| c.is_digit(10)) {
+                let ttl = try!(Self::parse_time(data));
+                record_ttl = Some(ttl);
+                context.ttl = Some(ttl);
+                State::TtlClassType
+              } else if let Ok(class) = DNSClass::from_str(data) {
+                context.class = Some(class);
+                State::TtlClassType
               } else {
-                // if can parse DNSClass, then class
-                let result = DNSClass::from_str(data);
-                if result.is_ok() {
-                  class = result.ok();
-                  State::TtlClassType
-                } else {
-
-                  // if can parse RecordType, then RecordType
-                  rtype = Some(try!(RecordType::from_str(data)));
-                  State::Record
-                }
+                // if can parse RecordType, then RecordType
+                rtype = Some(try!(RecordType::from_str(data)));
+                State::Record
               }
             }
             // could be nothing if started with blank and is a comment, i.e. EOL
@@ -244,69 +322,244 @@ impl Parser {
           //  tokens to pass into the processor
           match t {
             Token::EOL => {
-              // call out to parsers for difference record types
-              let rdata = try!(RData::parse(try!(rtype.ok_or(ParseError::from(ParseErrorKind::Message("record type not specified")))), &tokens, origin.as_ref()));
-
-              // verify that we have everything we need for the record
-              let mut record = Record::new();
-              // TODO COW or RC would reduce mem usage, perhaps Name should have an intern()...
-              //  might want to wait until RC.weak() stabilizes, as that would be needed for global
-              //  memory where you want
-              record.name(try!(current_name.clone().ok_or(ParseError::from(ParseErrorKind::Message("record name not specified")))));
-              record.rr_type(rtype.unwrap());
-              record.dns_class(try!(class.ok_or(ParseError::from(ParseErrorKind::Message("record class not specified")))));
-
-              // slightly annoying, need to grab the TTL, then move rdata into the record,
-              //  then check the Type again and have custom add logic.
-              match rtype.unwrap() {
-                RecordType::SOA => {
-                  // TTL for the SOA is set internally...
-                  // expire is for the SOA, minimum is default for records
-                  if let RData::SOA(ref soa) = rdata {
-                    // TODO, this looks wrong, get_expire() should be get_minimum(), right?
-                    record.ttl(soa.get_expire() as u32); // the spec seems a little inaccurate with u32 and i32
-                    if ttl.is_none() { ttl = Some(soa.get_minimum()); } // TODO: should this only set it if it's not set?
-                  } else { assert!(false, "Invalid RData here, expected SOA: {:?}", rdata); }
-                },
-                _ => {
-                  record.ttl(try!(ttl.ok_or(ParseError::from(ParseErrorKind::Message("record ttl not specified")))));
-                },
-              }
+              try!(Self::add_record(context, rtype, record_ttl, &tokens, records));
+              State::StartLine
+            },
+            // parentheses only continue the record across lines
+            Token::List(list) => { tokens.extend(list.into_iter().map(Token::CharData)); State::Record },
+            Token::At => {
+              let origin = try!(context.origin.as_ref().ok_or(ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))));
+              tokens.push(Token::CharData(origin.to_string()));
+              State::Record
+            },
+            _ => { tokens.push(t); State::Record },
+          }
+        },
+      }
+    }
+
+    // the last record may not be terminated by a new line
+    match state {
+      State::Record => try!(Self::add_record(context, rtype, record_ttl, &tokens, records)),
+      State::Include => try!(self.include(&tokens, context, records)),
+      State::Generate => try!(self.generate(&tokens, context, records)),
+      _ => (),
+    }
+
+    Ok(())
+  }
 
-              // TODO validate record, e.g. the name of SRV record allows _ but others do not.
-
-              // move the rdata into record...
-              record.rdata(rdata);
-
-              // add to the map
-              let key = RrKey::new(record.get_name(), record.get_rr_type());
-
-              match rtype.unwrap() {
-                RecordType::SOA => {
-                  let mut set = RRSet::new(record.get_name(), record.get_rr_type(), 0);
-                  set.insert(record, 0);
-                  if records.insert(key, set).is_some() {
-                    return Err(ParseErrorKind::Message("SOA is already specified").into());
-                  }
-                },
-                _ => {
-                  // add a Vec if it's not there, then add the record to the list
-                  let mut set = records.entry(key).or_insert(RRSet::new(record.get_name(), record.get_rr_type(), 0));
-                  set.insert(record, 0);
-                },
+  /// reads the records of the included file, the origin and owner of the including file are
+  ///  restored afterwards
+  fn include(&mut self, tokens: &[Token], context: &mut Context, records: &mut BTreeMap<RrKey, RRSet>) -> ParseResult<()> {
+    let file_name = match tokens.first() {
+      Some(&Token::CharData(ref file_name)) => file_name,
+      _ => return Err(ParseErrorKind::Message("$INCLUDE without a file name").into()),
+    };
+    if tokens.len() > 2 { return Err(ParseErrorKind::UnexpectedToken(tokens[2].clone()).into()) }
+
+    if self.depth >= self.max_include_depth {
+      return Err(ParseErrorKind::Msg(format!("$INCLUDE of {} is nested deeper than {}", file_name, self.max_include_depth)).into())
+    }
+
+    let path = match self.include_dir {
+      Some(ref dir) => dir.join(file_name),
+      None => PathBuf::from(file_name),
+    };
+
+    let mut buf = String::new();
+    try!(File::open(&path).and_then(|mut file| file.read_to_string(&mut buf))
+                          .map_err(|e| ParseErrorKind::Msg(format!("error reading $INCLUDE {:?}: {}", path, e))));
+
+    let origin = context.origin.clone();
+    let current_name = context.current_name.clone();
+    if let Some(&Token::CharData(ref include_origin)) = tokens.get(1) {
+      context.origin = Some(try!(Name::parse(include_origin, origin.as_ref())));
+    }
+
+    debug!("including: {:?}", path);
+    self.depth += 1;
+    let result = self.read(Lexer::new(&buf), context, records);
+    self.depth -= 1;
+
+    context.origin = origin;
+    context.current_name = current_name;
+    result.map_err(|e| ParseErrorKind::Msg(format!("{:?}: {}", path, e)).into())
+  }
+
+  /// expands the `$GENERATE` template over the range, each line is read as a record of the zone
+  fn generate(&mut self, tokens: &[Token], context: &mut Context, records: &mut BTreeMap<RrKey, RRSet>) -> ParseResult<()> {
+    let mut template: Vec<&str> = Vec::with_capacity(tokens.len());
+    for t in tokens {
+      match *t {
+        Token::CharData(ref data) => template.push(data),
+        _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+      }
+    }
+
+    // range, lhs, type and rhs are required
+    if template.len() < 4 { return Err(ParseErrorKind::Message("$GENERATE requires a range, owner, type and rdata").into()) }
+    let (start, stop, step) = try!(Self::generate_range(template[0]));
+
+    let mut lines = String::new();
+    let mut value = start;
+    while value <= stop {
+      for part in &template[1..] {
+        lines.push_str(&try!(Self::generate_substitute(part, value)));
+        lines.push(' ');
+      }
+      lines.push('\n');
+
+      value = match value.checked_add(step) {
+        Some(value) => value,
+        None => break,
+      };
+    }
+
+    let current_name = context.current_name.clone();
+    let result = self.read(Lexer::new(&lines), context, records);
+    context.current_name = current_name;
+    result
+  }
+
+  /// parses the `start-stop[/step]` range of a `$GENERATE`, which may expand to at most 65536
+  ///  records
+  fn generate_range(range: &str) -> ParseResult<(u32, u32, u32)> {
+    let (bounds, step) = match range.find('/') {
+      Some(i) => (&range[..i], try!(range[i+1..].parse::<u32>())),
+      None => (range, 1),
+    };
+
+    let i = try!(bounds.find('-').ok_or(ParseError::from(ParseErrorKind::Msg(format!("bad $GENERATE range: {}", range)))));
+    let start = try!(bounds[..i].parse::<u32>());
+    let stop = try!(bounds[i+1..].parse::<u32>());
+
+    if start > stop || step == 0 { return Err(ParseErrorKind::Msg(format!("bad $GENERATE range: {}", range)).into()) }
+    if (stop - start) / step >= MAX_GENERATE_COUNT {
+      return Err(ParseErrorKind::Msg(format!("$GENERATE range exceeds {} records: {}", MAX_GENERATE_COUNT, range)).into())
+    }
+    Ok((start, stop, step))
+  }
+
+  /// replaces the `$` and `${offset[,width[,radix]]}` of the template with the value, `\$` and
+  ///  `$$` are a literal `$`
+  fn generate_substitute(template: &str, value: u32) -> ParseResult<String> {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+      match ch {
+        '\\' if chars.peek() == Some(&'$') => { chars.next(); result.push('$'); },
+        '$' => {
+          match chars.peek().cloned() {
+            Some('$') => { chars.next(); result.push('$'); },
+            Some('{') => {
+              chars.next();
+              let mut modifier = String::new();
+              loop {
+                match chars.next() {
+                  Some('}') => break,
+                  Some(ch) => modifier.push(ch),
+                  None => return Err(ParseErrorKind::Msg(format!("unclosed ${{ in $GENERATE: {}", template)).into()),
+                }
               }
 
-              State::StartLine
+              result.push_str(&try!(Self::generate_modifier(&modifier, value)));
             },
-            _ => { tokens.push(t); State::Record },
+            _ => result.push_str(&value.to_string()),
           }
         },
+        ch => result.push(ch),
       }
     }
 
-    //
-    // build the Authority and return.
-    Ok(Authority::new(try!(origin.ok_or(ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified")))), records, zone_type, allow_update))
+    Ok(result)
+  }
+
+  /// formats the value with the `offset[,width[,radix]]` modifier, radix is one of d, o, x, X or n
+  ///  and N for reversed nibbles, where the width is the number of nibbles
+  fn generate_modifier(modifier: &str, value: u32) -> ParseResult<String> {
+    let mut parts = modifier.split(',');
+
+    let offset = match parts.next().map(|o| o.trim_left_matches('+')) {
+      Some(o) if !o.is_empty() => try!(o.parse::<i64>()),
+      _ => 0,
+    };
+    let width = match parts.next() {
+      Some(w) => try!(w.parse::<usize>()),
+      None => 0,
+    };
+    let radix = parts.next().unwrap_or("d");
+
+    let value = value as i64 + offset;
+    if value < 0 { return Err(ParseErrorKind::Msg(format!("negative $GENERATE value: ${{{}}}", modifier)).into()) }
+
+    match radix {
+      "d" => Ok(format!("{:0w$}", value, w = width)),
+      "o" => Ok(format!("{:0w$o}", value, w = width)),
+      "x" => Ok(format!("{:0w$x}", value, w = width)),
+      "X" => Ok(format!("{:0w$X}", value, w = width)),
+      "n" | "N" => {
+        let nibbles = if radix == "n" { format!("{:0w$x}", value, w = width) } else { format!("{:0w$X}", value, w = width) };
+        let reversed: Vec<String> = nibbles.chars().rev().map(|c| c.to_string()).collect();
+        Ok(reversed.join("."))
+      },
+      _ => Err(ParseErrorKind::Msg(format!("bad $GENERATE radix: ${{{}}}", modifier)).into()),
+    }
+  }
+
+  /// builds the record from the tokens of its RDATA and the context, adding it to the records
+  fn add_record(context: &mut Context, rtype: Option<RecordType>, record_ttl: Option<u32>, tokens: &Vec<Token>, records: &mut BTreeMap<RrKey, RRSet>) -> ParseResult<()> {
+    // call out to parsers for difference record types
+    let rtype = try!(rtype.ok_or(ParseError::from(ParseErrorKind::Message("record type not specified"))));
+    let rdata = try!(RData::parse(rtype, tokens, context.origin.as_ref()));
+
+    // verify that we have everything we need for the record
+    let mut record = Record::new();
+    // TODO COW or RC would reduce mem usage, perhaps Name should have an intern()...
+    //  might want to wait until RC.weak() stabilizes, as that would be needed for global
+    //  memory where you want
+    record.name(try!(context.current_name.clone().ok_or(ParseError::from(ParseErrorKind::Message("record name not specified")))));
+    record.rr_type(rtype);
+    // the class of the first record may be omitted, as BIND does
+    record.dns_class(context.class.unwrap_or(DNSClass::IN));
+
+    let ttl = record_ttl.or(context.default_ttl);
+    match rdata {
+      RData::SOA(ref soa) => {
+        // without a TTL the SOA keeps the expire, and the minimum is the default for the records
+        record.ttl(ttl.unwrap_or(soa.get_expire() as u32)); // the spec seems a little inaccurate with u32 and i32
+        if context.ttl.is_none() { context.ttl = Some(soa.get_minimum()); }
+      },
+      _ => {
+        record.ttl(try!(ttl.or(context.ttl).ok_or(ParseError::from(ParseErrorKind::Message("record ttl not specified")))));
+      },
+    }
+
+    // TODO validate record, e.g. the name of SRV record allows _ but others do not.
+
+    // move the rdata into record...
+    record.rdata(rdata);
+
+    // add to the map
+    let key = RrKey::new(record.get_name(), record.get_rr_type());
+
+    match rtype {
+      RecordType::SOA => {
+        let mut set = RRSet::new(record.get_name(), record.get_rr_type(), 0);
+        set.insert(record, 0);
+        if records.insert(key, set).is_some() {
+          return Err(ParseErrorKind::Message("SOA is already specified").into());
+        }
+      },
+      _ => {
+        // add a Vec if it's not there, then add the record to the list
+        let mut set = records.entry(key).or_insert(RRSet::new(record.get_name(), record.get_rr_type(), 0));
+        set.insert(record, 0);
+      },
+    }
+
+    Ok(())
   }
 
   /// parses the string following the rules from:
@@ -371,5 +624,6 @@ enum State {
   Ttl,             // $TTL <time>
   Record,
   Include,         // $INCLUDE <filename>
+  Generate,        // $GENERATE <range> <lhs> [<ttl>] [<class>] <type> <rhs>
   Origin,
 }
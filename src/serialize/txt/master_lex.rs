@@ -54,7 +54,7 @@ impl<'a> Lexer<'a> {
               Some(')') => { return Err(LexerErrorKind::IllegalCharacter(ch.unwrap_or(')')).into()) },
               Some('$') => { self.txt.next(); char_data = Some(String::new()); self.state = State::Dollar; },
               Some('\r') | Some('\n') => { self.state = State::EOL; },
-              Some('"') => { self.txt.next(); char_data = Some(String::new()); self.state = State::Quote; },
+              Some('"') => { self.txt.next(); char_data = Some(String::new()); self.state = State::Quote{ is_list: false }; },
               Some(';') => { self.state = State::Comment{ is_list: false } },
               Some(ch) if ch.is_whitespace() => { self.txt.next(); }, // gobble other whitespace
               Some(ch) if !ch.is_control() && !ch.is_whitespace() => { char_data = Some(String::new()); self.state = State::CharData{ is_list: false }; },
@@ -78,9 +78,14 @@ impl<'a> Lexer<'a> {
               None => { self.state = State::EOF; },
             }
           },
-          State::Quote => {
+          State::Quote{ is_list } => {
             match ch {
-              // end and gobble the '"'
+              // end and gobble the '"', strings in a list are collected with the rest of the list
+              Some('"') if is_list => {
+                self.txt.next();
+                self.state = State::List;
+                try!(char_data_vec.as_mut().ok_or(LexerError::from(LexerErrorKind::IllegalState("char_data_vec is None"))).and_then(|v|Ok(v.push(char_data.take().unwrap_or("".into())))));
+              },
               Some('"') => { self.state = State::RestOfLine; self.txt.next() ; return Ok(Some(Token::CharData(char_data.take().unwrap_or("".into())))) },
               Some('\\') => { try!(Self::push_to_str(&mut char_data, try!(self.escape_seq()))); },
               Some(ch) => { self.txt.next(); try!(Self::push_to_str(&mut char_data, ch)); },
@@ -91,21 +96,28 @@ impl<'a> Lexer<'a> {
             match ch {
               // even this is a little broad for what's actually possible in a dollar...
               Some('A' ... 'Z') => { self.txt.next(); try!(Self::push_to_str(&mut char_data, ch.unwrap())); },
+              // a lone $ is the iterator of a $GENERATE template, e.g. `$ PTR host-$`
+              Some(_) | None if char_data.as_ref().map_or(false, |d| d.is_empty()) => {
+                char_data = Some("$".to_string());
+                self.state = State::CharData{ is_list: false };
+              },
               // finishes the Dollar...
               Some(_) | None => {
                 self.state = State::RestOfLine;
                 let dollar: String = try!(char_data.take().ok_or(LexerError::from(LexerErrorKind::IllegalState("char_data is None"))));
 
-                if     "INCLUDE" == dollar { return Ok(Some(Token::Include)) }
-                else if "ORIGIN" == dollar { return Ok(Some(Token::Origin)) }
-                else if "TTL"    == dollar { return Ok(Some(Token::Ttl)) }
-                else { return Err(LexerErrorKind::UnrecognizedDollar(char_data.take().unwrap_or("".into())).into()) }
+                if     "INCLUDE"  == dollar { return Ok(Some(Token::Include)) }
+                else if "ORIGIN"   == dollar { return Ok(Some(Token::Origin)) }
+                else if "TTL"      == dollar { return Ok(Some(Token::Ttl)) }
+                else if "GENERATE" == dollar { return Ok(Some(Token::Generate)) }
+                else { return Err(LexerErrorKind::UnrecognizedDollar(dollar).into()) }
               },
             }
           },
           State::List => {
             match ch {
               Some(';') => { self.txt.next(); self.state = State::Comment{ is_list: true } },
+              Some('"') => { self.txt.next(); char_data = Some(String::new()); self.state = State::Quote{ is_list: true } },
               Some(')') => { self.txt.next(); self.state = State::RestOfLine; return char_data_vec.take().ok_or(LexerErrorKind::IllegalState("char_data_vec is None").into()).map(|v|Some(Token::List(v))); }
               Some(ch) if ch.is_whitespace() => { self.txt.next(); },
               Some(ch) if !ch.is_control() && !ch.is_whitespace() => { char_data = Some(String::new()); self.state = State::CharData{ is_list: true } },
@@ -175,7 +187,7 @@ impl<'a> Lexer<'a> {
         let d2: u32 = try!(try!(self.txt.next().ok_or(LexerError::from(LexerErrorKind::EOF)).map(|c|c.to_digit(10).ok_or(LexerError::from(LexerErrorKind::IllegalCharacter(c)))))); // gobble
         let d3: u32 = try!(try!(self.txt.next().ok_or(LexerError::from(LexerErrorKind::EOF)).map(|c|c.to_digit(10).ok_or(LexerError::from(LexerErrorKind::IllegalCharacter(c)))))); // gobble
 
-        let val: u32 = (d1 << 16) + (d2 << 8) + d3;
+        let val: u32 = d1 * 100 + d2 * 10 + d3;
         let ch: char = try!(char::from_u32(val).ok_or(LexerError::from(LexerErrorKind::UnrecognizedOctet(val))));
 
         return Ok(ch);
@@ -205,7 +217,7 @@ pub enum State {
   //  Name,              // CharData + '.' + CharData
   Comment{ is_list: bool }, // ;.*
   At,                // @
-  Quote,             // ".*"
+  Quote{ is_list: bool }, // ".*"
   Dollar,            // $
   EOL,               // \n or \r\n
   EOF,
@@ -220,6 +232,7 @@ pub enum Token {
   Include,           // $INCLUDE
   Origin,            // $ORIGIN
   Ttl,               // $TTL
+  Generate,          // $GENERATE
   EOL,               // \n or \r\n
 }
 
@@ -279,7 +292,7 @@ mod lex_test {
     assert_eq!(Lexer::new("\"some \\A\"").next_token().unwrap().unwrap(), Token::CharData("some A".to_string()));
     assert_eq!(Lexer::new("\"a\\Aa\"").next_token().unwrap().unwrap(), Token::CharData("aAa".to_string()));
     assert_eq!(Lexer::new("\"a\\$\"").next_token().unwrap().unwrap(), Token::CharData("a$".to_string()));
-    assert_eq!(Lexer::new("\"a\\077\"").next_token().unwrap().unwrap(), Token::CharData("a\u{707}".to_string()));
+    assert_eq!(Lexer::new("\"a\\077\"").next_token().unwrap().unwrap(), Token::CharData("aM".to_string()));
 
     assert!(Lexer::new("\"a\\\"").next_token().is_err());
     assert!(Lexer::new("\"a\\0\"").next_token().is_err());
@@ -318,10 +331,22 @@ mod lex_test {
     assert_eq!(next_token(&mut Lexer::new("$INCLUDE")).unwrap(), Token::Include);
     assert_eq!(next_token(&mut Lexer::new("$ORIGIN")).unwrap(), Token::Origin);
     assert_eq!(next_token(&mut Lexer::new("$TTL")).unwrap(), Token::Ttl);
+    assert_eq!(next_token(&mut Lexer::new("$GENERATE")).unwrap(), Token::Generate);
     assert_eq!(next_token(&mut Lexer::new("\n")), Some(Token::EOL));
     assert_eq!(next_token(&mut Lexer::new("\r\n")), Some(Token::EOL));
   }
 
+  #[test]
+  fn generate() {
+    let mut lexer = Lexer::new("$GENERATE 1-10 $ PTR ${0,3,d}.example.");
+    assert_eq!(next_token(&mut lexer).unwrap(), Token::Generate);
+    assert_eq!(next_token(&mut lexer).unwrap(), Token::CharData("1-10".to_string()));
+    assert_eq!(next_token(&mut lexer).unwrap(), Token::CharData("$".to_string()));
+    assert_eq!(next_token(&mut lexer).unwrap(), Token::CharData("PTR".to_string()));
+    assert_eq!(next_token(&mut lexer).unwrap(), Token::CharData("${0,3,d}.example.".to_string()));
+    assert_eq!(next_token(&mut lexer), None);
+  }
+
   #[test]
   fn list() {
     let mut lexer = Lexer::new("(");
@@ -348,6 +373,10 @@ mod lex_test {
     let mut lexer = Lexer::new("(\nabc;comment\n)");
     assert_eq!(next_token(&mut lexer).unwrap(), Token::List(vec!["abc".to_string()]));
     assert_eq!(next_token(&mut lexer), None);
+
+    let mut lexer = Lexer::new("(\"a b\" \"c;d\"\n)");
+    assert_eq!(next_token(&mut lexer).unwrap(), Token::List(vec!["a b".to_string(), "c;d".to_string()]));
+    assert_eq!(next_token(&mut lexer), None);
   }
 
   #[test]
@@ -0,0 +1,245 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use data_encoding::{base32hex, base64, hex};
+
+use ::authority::{Authority, RrKey};
+use ::rr::{Name, Record, RecordType, RData};
+use ::serialize::binary::BinEncoder;
+
+/// Writes an `Authority` as master file text, the inverse of the `Parser`
+///
+/// The zone starts with `$ORIGIN` and `$TTL`, the most common TTL of the zone, followed by the
+///  SOA and then the other records ordered by name and type. Names in the zone are written
+///  relative to the origin, repeated owners are left blank, and the columns are aligned.
+pub struct Writer {
+  relative_names: bool,
+  signatures: bool,
+}
+
+impl Writer {
+  pub fn new() -> Self {
+    Writer{ relative_names: true, signatures: true }
+  }
+
+  /// If true, the default, names in the zone are written relative to the origin
+  pub fn relative_names(&mut self, relative_names: bool) -> &mut Self {
+    self.relative_names = relative_names;
+    self
+  }
+
+  /// If true, the default, the RRSIG, NSEC and NSEC3 records, which are generated by signing the
+  ///  zone, are written
+  pub fn signatures(&mut self, signatures: bool) -> &mut Self {
+    self.signatures = signatures;
+    self
+  }
+
+  /// Writes the zone to the writer, e.g. a `File`
+  pub fn write<W: Write>(&self, authority: &Authority, mut writer: W) -> io::Result<()> {
+    let origin = authority.get_origin();
+
+    // the SOA is first, with its signatures
+    let soa_key = RrKey::new(origin, RecordType::SOA);
+    let mut records: Vec<&Record> = Vec::new();
+    if let Some(rr_set) = authority.get_records().get(&soa_key) {
+      records.extend(rr_set.get_records(true));
+    }
+    for (key, rr_set) in authority.get_records() {
+      if *key == soa_key { continue }
+      records.extend(rr_set.get_records(true));
+    }
+
+    if !self.signatures {
+      records.retain(|record| match record.get_rr_type() {
+        RecordType::RRSIG | RecordType::SIG | RecordType::NSEC | RecordType::NSEC3 => false,
+        _ => true,
+      });
+    }
+
+    let default_ttl = Self::most_common_ttl(&records);
+
+    // owner, ttl, class, type, rdata
+    let mut rows: Vec<[String; 5]> = Vec::with_capacity(records.len());
+    let mut last_name: Option<&Name> = None;
+    for record in records {
+      let owner = if last_name == Some(record.get_name()) { String::new() } else { self.name_to_string(record.get_name(), origin) };
+      let ttl = if Some(record.get_ttl()) == default_ttl { String::new() } else { record.get_ttl().to_string() };
+      let class: &'static str = record.get_dns_class().into();
+      let rr_type: &'static str = record.get_rr_type().into();
+
+      rows.push([owner, ttl, class.to_string(), rr_type.to_string(), self.rdata_to_string(record.get_rdata(), origin)]);
+      last_name = Some(record.get_name());
+    }
+
+    let mut widths = [0_usize; 4];
+    for row in &rows {
+      for i in 0..widths.len() {
+        if row[i].len() > widths[i] { widths[i] = row[i].len(); }
+      }
+    }
+
+    try!(writeln!(writer, "$ORIGIN {}", absolute_name(origin)));
+    if let Some(ttl) = default_ttl {
+      try!(writeln!(writer, "$TTL {}", ttl));
+    }
+
+    for row in &rows {
+      try!(writeln!(writer, "{:<ow$} {:>tw$} {:<cw$} {:<rw$} {}", row[0], row[1], row[2], row[3], row[4],
+                    ow = widths[0], tw = widths[1], cw = widths[2], rw = widths[3]));
+    }
+
+    Ok(())
+  }
+
+  /// Returns the zone as master file text
+  pub fn to_string(&self, authority: &Authority) -> String {
+    let mut buf: Vec<u8> = Vec::new();
+    self.write(authority, &mut buf).expect("writes to a Vec do not fail");
+    String::from_utf8(buf).expect("the zone text is utf8")
+  }
+
+  fn most_common_ttl(records: &[&Record]) -> Option<u32> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for record in records {
+      *counts.entry(record.get_ttl()).or_insert(0) += 1;
+    }
+
+    // ties go to the lower TTL, to be stable
+    counts.into_iter().max_by_key(|&(ttl, count)| (count, !ttl)).map(|(ttl, _)| ttl)
+  }
+
+  /// `@` for the origin, relative names for names in the zone and absolute names otherwise, with
+  ///  the special characters of the master file escaped
+  pub fn name_to_string(&self, name: &Name, origin: &Name) -> String {
+    let num_labels = name.num_labels() as usize;
+    let origin_labels = origin.num_labels() as usize;
+
+    if self.relative_names && origin.zone_of(name) && num_labels >= origin_labels {
+      if num_labels == origin_labels { return "@".to_string() }
+
+      let labels: Vec<String> = (0..(num_labels - origin_labels)).map(|i| escape_label(&name[i])).collect();
+      labels.join(".")
+    } else {
+      absolute_name(name)
+    }
+  }
+
+  /// The presentation format of the rdata, as it is written in the master file, the names in it
+  ///  are written as by `name_to_string()`
+  pub fn rdata_to_string(&self, rdata: &RData, origin: &Name) -> String {
+    match *rdata {
+      RData::A(ref address) => address.to_string(),
+      RData::AAAA(ref address) => address.to_string(),
+      RData::CNAME(ref name) | RData::NS(ref name) | RData::PTR(ref name) => self.name_to_string(name, origin),
+      RData::DNSKEY(ref key) | RData::KEY(ref key) => {
+        let flags: u16 = (if key.is_zone_key() { 0x0100 } else { 0 })
+                       | (if key.is_revoke() { 0x0080 } else { 0 })
+                       | (if key.is_secure_entry_point() { 0x0001 } else { 0 });
+        format!("{} 3 {} {}", flags, u8::from(*key.get_algorithm()), base64::encode(key.get_public_key()))
+      },
+      RData::DS(ref ds) => {
+        format!("{} {} {} {}", ds.get_key_tag(), u8::from(*ds.get_algorithm()), u8::from(ds.get_digest_type()), hex::encode(ds.get_digest()))
+      },
+      RData::MX(ref mx) => format!("{} {}", mx.get_preference(), self.name_to_string(mx.get_exchange(), origin)),
+      RData::NSEC(ref nsec) => {
+        format!("{} {}", self.name_to_string(nsec.get_next_domain_name(), origin), types_to_string(nsec.get_type_bit_maps()))
+      },
+      RData::NSEC3(ref nsec3) => {
+        format!("{} {} {} {} {} {}", u8::from(nsec3.get_hash_algorithm()), if nsec3.is_opt_out() { 1 } else { 0 },
+                nsec3.get_iterations(), salt_to_string(nsec3.get_salt()),
+                base32hex::encode(nsec3.get_next_hashed_owner_name()).to_lowercase(), types_to_string(nsec3.get_type_bit_maps()))
+      },
+      RData::NSEC3PARAM(ref param) => {
+        format!("{} {} {} {}", u8::from(param.get_hash_algorithm()), if param.is_opt_out() { 1 } else { 0 },
+                param.get_iterations(), salt_to_string(param.get_salt()))
+      },
+      RData::SIG(ref sig) => {
+        let type_covered: &'static str = sig.get_type_covered().into();
+        format!("{} {} {} {} {} {} {} {} {}", type_covered, u8::from(sig.get_algorithm()), sig.get_num_labels(),
+                sig.get_original_ttl(), sig.get_sig_expiration(), sig.get_sig_inception(), sig.get_key_tag(),
+                self.name_to_string(sig.get_signer_name(), origin), base64::encode(sig.get_sig()))
+      },
+      RData::SOA(ref soa) => {
+        format!("{} {} {} {} {} {} {}", self.name_to_string(soa.get_mname(), origin), self.name_to_string(soa.get_rname(), origin),
+                soa.get_serial(), soa.get_refresh(), soa.get_retry(), soa.get_expire(), soa.get_minimum())
+      },
+      RData::SRV(ref srv) => {
+        format!("{} {} {} {}", srv.get_priority(), srv.get_weight(), srv.get_port(), self.name_to_string(srv.get_target(), origin))
+      },
+      RData::TXT(ref txt) => {
+        let strings: Vec<String> = txt.get_txt_data().iter().map(|s| quote(s)).collect();
+        strings.join(" ")
+      },
+      // no presentation format, the generic format of RFC 3597
+      RData::NULL(..) | RData::OPT(..) | RData::TSIG(..) => {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+          let mut encoder = BinEncoder::new(&mut buf);
+          rdata.emit(&mut encoder).unwrap_or_else(|_| warn!("could not encode RDATA: {:?}", rdata));
+        }
+        format!("\\# {} {}", buf.len(), hex::encode(&buf))
+      },
+    }
+  }
+}
+
+fn absolute_name(name: &Name) -> String {
+  if name.is_root() { return ".".to_string() }
+
+  let labels: Vec<String> = (0..name.num_labels() as usize).map(|i| escape_label(&name[i])).collect();
+  labels.join(".") + "."
+}
+
+/// escapes the special characters of the master file, unprintable characters are `\DDD`
+fn escape_label(label: &str) -> String {
+  let mut escaped = String::with_capacity(label.len());
+  for ch in label.chars() {
+    match ch {
+      '.' | '\\' | '"' | ';' | '(' | ')' | '@' | '$' => { escaped.push('\\'); escaped.push(ch); },
+      ch if ch.is_whitespace() || ch.is_control() => escaped.push_str(&format!("\\{:03}", ch as u32)),
+      ch => escaped.push(ch),
+    }
+  }
+  escaped
+}
+
+/// a quoted character string, with `"` and `\` escaped
+fn quote(data: &str) -> String {
+  let mut quoted = String::with_capacity(data.len() + 2);
+  quoted.push('"');
+  for ch in data.chars() {
+    match ch {
+      '"' | '\\' => { quoted.push('\\'); quoted.push(ch); },
+      ch if ch.is_control() => quoted.push_str(&format!("\\{:03}", ch as u32)),
+      ch => quoted.push(ch),
+    }
+  }
+  quoted.push('"');
+  quoted
+}
+
+/// an empty salt is `-`, RFC 5155
+fn salt_to_string(salt: &[u8]) -> String {
+  if salt.is_empty() { "-".to_string() } else { hex::encode(salt) }
+}
+
+fn types_to_string(types: &[RecordType]) -> String {
+  let types: Vec<&'static str> = types.iter().map(|t| (*t).into()).collect();
+  types.join(" ")
+}
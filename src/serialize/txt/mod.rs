@@ -15,10 +15,14 @@
  */
 mod master_lex;
 mod master;
+mod master_writer;
+mod presentation;
 
 pub use self::master::Parser;
+pub use self::master_writer::Writer;
 pub use self::master_lex::Lexer;
 pub use self::master_lex::Token;
+pub use self::presentation::{has_presentation_format, rdata_from_hex, rdata_from_string, rdata_to_string};
 
 #[cfg(test)]
 mod txt_tests;
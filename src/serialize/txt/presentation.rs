@@ -0,0 +1,108 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The presentation format of rdata on its own, outside of a master file, e.g. for the JSON and
+//!  serde representations of records. Names are absolute.
+
+use data_encoding::hex;
+
+use ::error::*;
+use ::rr::{Name, RData, RecordType};
+use ::rr::rdata::NULL;
+use ::serialize::binary::BinDecoder;
+use ::serialize::txt::{Lexer, Token, Writer};
+
+/// true if the presentation format of the rdata can be parsed, see `RData::parse()`
+pub fn has_presentation_format(rdata: &RData) -> bool {
+  match *rdata {
+    RData::A(..) | RData::AAAA(..) | RData::CNAME(..) | RData::MX(..) | RData::NS(..) | RData::PTR(..)
+    | RData::SOA(..) | RData::SRV(..) | RData::TXT(..) => true,
+    _ => false,
+  }
+}
+
+/// The presentation format of the rdata, or the generic format of RFC 3597, `\# 4 C0000201`, if
+///  it has none which can be parsed, e.g. for DNSKEY or OPT
+pub fn rdata_to_string(rdata: &RData) -> String {
+  if has_presentation_format(rdata) {
+    Writer::new().relative_names(false).rdata_to_string(rdata, &Name::root())
+  } else {
+    let bytes = rdata.to_bytes();
+    format!("\\# {} {}", bytes.len(), hex::encode(&bytes))
+  }
+}
+
+/// Parses the rdata of the type from its presentation format, or the generic format of RFC 3597,
+///  the inverse of `rdata_to_string()`. Names are absolute, the final dot is optional.
+pub fn rdata_from_string(record_type: RecordType, text: &str) -> ParseResult<RData> {
+  let mut fields = text.split_whitespace();
+  if fields.next() == Some("\\#") {
+    let length: u16 = try!(try!(fields.next().ok_or(ParseError::from(ParseErrorKind::Message("the rdata length is missing")))).parse());
+    let data: String = fields.collect();
+    return rdata_from_hex(record_type, length, &data)
+  }
+
+  let mut lexer = Lexer::new(text);
+  let mut tokens: Vec<Token> = Vec::new();
+  while let Some(token) = try!(lexer.next_token()) {
+    match token {
+      Token::List(list) => tokens.extend(list.into_iter().map(Token::CharData)),
+      Token::Blank | Token::EOL => (),
+      token => tokens.push(token),
+    }
+  }
+
+  RData::parse(record_type, &tokens, Some(&Name::root()))
+}
+
+/// Decodes the rdata of the type from its hex encoded bytes
+pub fn rdata_from_hex(record_type: RecordType, length: u16, data: &str) -> ParseResult<RData> {
+  let data: Vec<u8> = try!(hex::decode(data.to_uppercase().as_bytes()).map_err(|e| ParseError::from(ParseErrorKind::Msg(format!("rdata is not hex: {}", e)))));
+  if data.len() != length as usize {
+    return Err(ParseErrorKind::Msg(format!("the rdata is {} bytes, not: {}", data.len(), length)).into())
+  }
+
+  // as for Record::read(), empty rdata is NULL, e.g. the prerequisites of updates
+  if data.is_empty() { return Ok(RData::NULL(NULL::new())) }
+  let mut decoder = BinDecoder::new(&data);
+  Ok(try!(RData::read(&mut decoder, record_type, length)))
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::Ipv4Addr;
+
+  use ::rr::{Name, RData, RecordType};
+  use ::rr::rdata::{MX, NULL};
+  use super::*;
+
+  #[test]
+  fn test_rdata_to_and_from_string() {
+    let mx = RData::MX(MX::new(10, Name::parse("mail.example.com.", None).unwrap()));
+    assert_eq!(rdata_to_string(&mx), "10 mail.example.com.");
+    assert_eq!(rdata_from_string(RecordType::MX, "10 mail.example.com.").unwrap(), mx);
+    assert_eq!(rdata_from_string(RecordType::MX, "10 mail.example.com").unwrap(), mx);
+
+    let null = RData::NULL(NULL::with(vec![1, 2, 0xAB]));
+    assert_eq!(rdata_to_string(&null), "\\# 3 0102AB");
+    assert_eq!(rdata_from_string(RecordType::NULL, "\\# 3 0102ab").unwrap(), null);
+
+    // the generic format is accepted for any type
+    assert_eq!(rdata_from_string(RecordType::A, "\\# 4 C0000201").unwrap(), RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+    assert!(rdata_from_string(RecordType::A, "\\# 5 C0000201").is_err());
+    assert!(rdata_from_string(RecordType::A, "not an address").is_err());
+  }
+}
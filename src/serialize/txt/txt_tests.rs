@@ -188,3 +188,161 @@ venera  A       10.1.0.52
     panic!("Not an SRV record!!!") // valid panic, test code
   }
 }
+
+#[test]
+fn test_master_file() {
+  let lexer = Lexer::new("$ORIGIN example.com.
+$TTL 1h
+@   3600 IN SOA ns1 hostmaster 2016100101 1h 15m 1w 5m
+         NS     ns1
+ns1      A      192.0.2.1
+www  300 A      192.0.2.2
+txt      TXT    ( \"multi word\" ; a comment
+                  \"second\" )
+alias    CNAME  @
+$ORIGIN sub
+host     A      192.0.2.4");
+
+  let authority = Parser::new().parse(lexer, None, ZoneType::Master, false).expect("failed to parse");
+  let origin = Name::parse("example.com.", None).unwrap();
+  assert_eq!(authority.get_origin(), &origin);
+
+  let soa_record = authority.get_soa().unwrap();
+  assert_eq!(soa_record.get_ttl(), 3600);
+  if let RData::SOA(ref soa) = *soa_record.get_rdata() {
+    assert_eq!(soa.get_mname(), &Name::parse("ns1.example.com.", None).unwrap());
+    assert_eq!(soa.get_serial(), 2016100101);
+    assert_eq!(soa.get_retry(), 900);
+    assert_eq!(soa.get_expire(), 604800);
+    assert_eq!(soa.get_minimum(), 300);
+  } else {
+    panic!("Not an SOA record!!!") // valid panic, test code
+  }
+
+  // the owner and class are inherited, the TTL is the $TTL
+  let ns_record = authority.lookup(&origin, RecordType::NS, false).first().cloned().unwrap();
+  assert_eq!(ns_record.get_dns_class(), DNSClass::IN);
+  assert_eq!(ns_record.get_ttl(), 3600);
+
+  let www_record = authority.lookup(&Name::parse("www.example.com.", None).unwrap(), RecordType::A, false).first().cloned().unwrap();
+  assert_eq!(www_record.get_ttl(), 300);
+
+  let txt_record = authority.lookup(&Name::parse("txt.example.com.", None).unwrap(), RecordType::TXT, false).first().cloned().unwrap();
+  if let RData::TXT(ref txt) = *txt_record.get_rdata() {
+    assert_eq!(txt.get_txt_data(), &["multi word".to_string(), "second".to_string()]);
+  } else {
+    panic!("Not a TXT record!!!") // valid panic, test code
+  }
+
+  let cname_record = authority.lookup(&Name::parse("alias.example.com.", None).unwrap(), RecordType::CNAME, false).first().cloned().unwrap();
+  assert_eq!(cname_record.get_rdata(), &RData::CNAME(origin.clone()));
+
+  // the last record has no new line, and is relative to the relative $ORIGIN
+  let host_record = authority.lookup(&Name::parse("host.sub.example.com.", None).unwrap(), RecordType::A, false).first().cloned().unwrap();
+  assert_eq!(host_record.get_rdata(), &RData::A(Ipv4Addr::new(192,0,2,4)));
+
+  // signed records aren't parsed, rather than panicking
+  assert!(Parser::new().parse(Lexer::new("$ORIGIN example.com.\n@ 3600 IN DNSKEY 257 3 8 AwEAAQ==\n"), None, ZoneType::Master, false).is_err());
+}
+
+#[test]
+fn test_include() {
+  use std::env;
+  use std::fs::{self, File};
+  use std::io::Write;
+
+  let dir = env::temp_dir().join("trust_dns_test_include");
+  fs::create_dir_all(&dir).unwrap();
+  File::create(dir.join("hosts.inc")).unwrap().write_all(b"host A 192.0.2.2\n").unwrap();
+  File::create(dir.join("loop.inc")).unwrap().write_all(b"$INCLUDE loop.inc\n").unwrap();
+  File::create(dir.join("example.com.zone")).unwrap().write_all(b"$ORIGIN example.com.
+@   3600 IN SOA ns1 hostmaster 2016100101 1h 15m 1w 5m
+$INCLUDE hosts.inc sub
+www      A      192.0.2.1
+$INCLUDE hosts.inc").unwrap();
+
+  let authority = Parser::parse_path(&dir.join("example.com.zone"), None, ZoneType::Master, false).expect("failed to parse");
+
+  // the origin of the include does not change the origin of the including file
+  assert!(!authority.lookup(&Name::parse("host.sub.example.com.", None).unwrap(), RecordType::A, false).is_empty());
+  assert!(!authority.lookup(&Name::parse("www.example.com.", None).unwrap(), RecordType::A, false).is_empty());
+  assert!(!authority.lookup(&Name::parse("host.example.com.", None).unwrap(), RecordType::A, false).is_empty());
+
+  // a file which includes itself is stopped by the depth limit
+  File::create(dir.join("loop.zone")).unwrap().write_all(b"$ORIGIN example.com.
+@   3600 IN SOA ns1 hostmaster 2016100101 1h 15m 1w 5m
+$INCLUDE loop.inc
+").unwrap();
+  assert!(Parser::parse_path(&dir.join("loop.zone"), None, ZoneType::Master, false).is_err());
+
+  // missing files are errors
+  let lexer = Lexer::new("@ 3600 IN SOA ns1 hostmaster 2016100101 1h 15m 1w 5m\n$INCLUDE missing.inc\n");
+  assert!(Parser::new().include_dir(Some(dir.clone())).parse(lexer, Name::parse("example.com.", None).ok(), ZoneType::Master, false).is_err());
+}
+
+#[test]
+fn test_generate() {
+  let lexer = Lexer::new("$ORIGIN 2.0.192.in-addr.arpa.
+@   3600 IN SOA ns1.example.com. hostmaster.example.com. 2016100101 1h 15m 1w 5m
+$GENERATE 1-4/2 $ PTR host-${10,3,d}.example.com.
+$GENERATE 10-11 ${0,2,x} 300 IN CNAME ${0,2,n}.\\$.example.com.
+after A 192.0.2.9");
+
+  let authority = Parser::new().parse(lexer, None, ZoneType::Master, false).expect("failed to parse");
+
+  let ptr = authority.lookup(&Name::parse("3.2.0.192.in-addr.arpa.", None).unwrap(), RecordType::PTR, false).first().cloned().unwrap();
+  if let RData::PTR(ref name) = *ptr.get_rdata() {
+    assert_eq!(name, &Name::parse("host-013.example.com.", None).unwrap());
+  } else {
+    panic!("Not a PTR record!!!") // valid panic, test code
+  }
+  assert!(authority.lookup(&Name::parse("1.2.0.192.in-addr.arpa.", None).unwrap(), RecordType::PTR, false).len() == 1);
+  assert!(authority.lookup(&Name::parse("2.2.0.192.in-addr.arpa.", None).unwrap(), RecordType::PTR, false).is_empty());
+
+  let cname = authority.lookup(&Name::parse("0b.2.0.192.in-addr.arpa.", None).unwrap(), RecordType::CNAME, false).first().cloned().unwrap();
+  assert_eq!(cname.get_ttl(), 300);
+  if let RData::CNAME(ref name) = *cname.get_rdata() {
+    assert_eq!(name, &Name::parse("b.0.$.example.com.", None).unwrap());
+  } else {
+    panic!("Not a CNAME record!!!") // valid panic, test code
+  }
+
+  assert!(!authority.lookup(&Name::parse("after.2.0.192.in-addr.arpa.", None).unwrap(), RecordType::A, false).is_empty());
+
+  // the range is limited, rather than building every line of it
+  let lexer = Lexer::new("$ORIGIN example.com.
+@   3600 IN SOA ns1 hostmaster 2016100101 1h 15m 1w 5m
+$GENERATE 0-4294967295 host-$ A 192.0.2.1");
+  assert!(Parser::new().parse(lexer, None, ZoneType::Master, false).is_err());
+
+  let lexer = Lexer::new("$ORIGIN example.com.
+@   3600 IN SOA ns1 hostmaster 2016100101 1h 15m 1w 5m
+$GENERATE 0-4294967295/1000000000 host-$ A 192.0.2.1");
+  assert!(Parser::new().parse(lexer, None, ZoneType::Master, false).is_ok());
+}
+
+#[test]
+fn test_write_round_trip() {
+  let lexer = Lexer::new("$ORIGIN example.com.
+@     3600 IN SOA  ns1 hostmaster 2016100101 3600 900 604800 300
+      3600 IN NS   ns1
+      3600 IN NS   ns.example.net.
+      3600 IN MX   10 mail
+ns1   3600 IN A    192.0.2.1
+www    300 IN AAAA 2001:db8::1
+_sip._tcp 3600 IN SRV 0 5 5060 sip
+txt   3600 IN TXT  \"quote \\\" and \\\\ slash\" second
+");
+
+  let authority = Parser::new().parse(lexer, None, ZoneType::Master, false).expect("failed to parse");
+  let text = Writer::new().to_string(&authority);
+
+  assert!(text.starts_with("$ORIGIN example.com.\n$TTL 3600\n@ "), "{}", text);
+  assert!(text.contains(" ns.example.net.\n"), "{}", text);
+  assert!(text.contains("\nwww       300 IN AAAA 2001:db8::1\n"), "{}", text);
+  assert!(text.contains("\"quote \\\" and \\\\ slash\" \"second\""), "{}", text);
+
+  let reparsed = Parser::new().parse(Lexer::new(&text), None, ZoneType::Master, false).expect("failed to parse written zone");
+  assert_eq!(reparsed.get_origin(), authority.get_origin());
+  assert_eq!(reparsed.get_records(), authority.get_records());
+}
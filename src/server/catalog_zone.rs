@@ -0,0 +1,230 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+use ::authority::{Authority, Catalog, ZoneType};
+use ::rr::{Name, RData, RecordType};
+
+/// the schema versions of catalog zones which are understood
+const SUPPORTED_VERSIONS: &'static [&'static str] = &["1", "2"];
+
+/// A catalog zone, a zone in the `Catalog` which lists the zones to serve as secondaries, so that
+///  they are provisioned by changing the catalog zone at its primary.
+///
+/// [RFC 9432](https://tools.ietf.org/html/rfc9432), DNS Catalog Zones, July 2023
+///
+/// ```text
+/// version.$CATZ 0 IN TXT "2"
+/// <unique-N>.zones.$CATZ 0 IN PTR example.com.
+/// ```
+///
+/// Each time the serial of the catalog zone changes, the member zones, the targets of the PTR
+///  records of `zones`, which are not in the `Catalog` are added as empty secondary zones, and
+///  transferred by the `ZoneRefresher`. The member zones which were added and are no longer listed
+///  are removed. Zones which were in the `Catalog` already are left alone.
+///
+/// ```rust,ignore
+/// let mut refresher = ZoneRefresher::new();
+/// refresher.add_catalog_zone(CatalogZone::new(Name::parse("catalog.example.", None).unwrap()));
+/// server.set_zone_refresher(refresher, 1000);
+/// ```
+pub struct CatalogZone {
+  origin: Name,
+  primaries: Option<Vec<SocketAddr>>,
+  members: HashSet<Name>,
+  serial: Option<u32>,
+}
+
+impl CatalogZone {
+  /// # Arguments
+  ///
+  /// * `origin` - the name of the catalog zone, which must be in the `Catalog`
+  pub fn new(origin: Name) -> Self {
+    CatalogZone{ origin: origin, primaries: None, members: HashSet::new(), serial: None }
+  }
+
+  /// The primaries of the member zones, by default those of the catalog zone
+  pub fn set_primaries(&mut self, primaries: Vec<SocketAddr>) -> &mut Self {
+    self.primaries = Some(primaries);
+    self
+  }
+
+  pub fn get_origin(&self) -> &Name { &self.origin }
+
+  /// The member zones which were added to the `Catalog`
+  pub fn get_members(&self) -> Vec<Name> {
+    self.members.iter().cloned().collect()
+  }
+
+  /// Adds the newly listed member zones to the catalog, and removes those no longer listed, if the
+  ///  serial of the catalog zone has changed
+  ///
+  /// Returns the zones which were added or removed.
+  pub fn sync(&mut self, catalog: &Catalog) -> Vec<Name> {
+    let (listed, primaries) = {
+      let authority = match catalog.get(&self.origin) {
+        Some(authority) => authority,
+        None => return Vec::new(),
+      };
+      let authority = authority.read().unwrap(); // poison errors should panic...
+
+      // the zone has not been transferred yet
+      let serial = match authority.get_soa().map(|soa| soa.get_rdata()) {
+        Some(&RData::SOA(ref soa)) => soa.get_serial(),
+        _ => return Vec::new(),
+      };
+      if self.serial == Some(serial) { return Vec::new() }
+      self.serial = Some(serial);
+
+      let listed = match self.listed(&authority) {
+        Some(listed) => listed,
+        None => return Vec::new(),
+      };
+
+      let primaries = self.primaries.clone().unwrap_or_else(|| authority.get_primaries().to_vec());
+      (listed, primaries)
+    };
+
+    let mut changed: Vec<Name> = Vec::new();
+
+    let removed: Vec<Name> = self.members.iter().filter(|member| !listed.contains(*member)).cloned().collect();
+    for member in removed {
+      self.members.remove(&member);
+      if catalog.remove(&member) {
+        info!("removed member zone {} of catalog zone {}", member, self.origin);
+        changed.push(member);
+      }
+    }
+
+    for member in listed {
+      if self.members.contains(&member) { continue }
+      if member == self.origin || catalog.get(&member).is_some() {
+        warn!("member zone {} of catalog zone {} is already in the catalog", member, self.origin);
+        continue
+      }
+
+      let mut authority = Authority::new(member.clone(), BTreeMap::new(), ZoneType::Slave, false);
+      authority.set_primaries(primaries.clone());
+
+      catalog.upsert(member.clone(), authority);
+      info!("added member zone {} of catalog zone {}", member, self.origin);
+      self.members.insert(member.clone());
+      changed.push(member);
+    }
+
+    changed
+  }
+
+  /// the member zones listed in the catalog zone, None if its schema version is not supported
+  fn listed(&self, authority: &Authority) -> Option<HashSet<Name>> {
+    let version = self.origin.prepend_label(Rc::new("version".to_string()));
+    let supported = authority.lookup(&version, RecordType::TXT, false).iter().any(|record| {
+      if let &RData::TXT(ref txt) = record.get_rdata() {
+        txt.get_txt_data().iter().any(|data| SUPPORTED_VERSIONS.contains(&data.as_str()))
+      } else {
+        false
+      }
+    });
+
+    if !supported {
+      warn!("catalog zone {} does not have a supported version, it is ignored", self.origin);
+      return None
+    }
+
+    // each member is a single PTR record, at a unique name below zones
+    let zones = self.origin.prepend_label(Rc::new("zones".to_string()));
+    let mut listed: HashSet<Name> = HashSet::new();
+    for rr_set in authority.get_records().values() {
+      if rr_set.get_record_type() != RecordType::PTR || rr_set.get_name().base_name() != zones { continue }
+
+      let records = rr_set.get_records(false);
+      if records.len() != 1 {
+        warn!("{} in catalog zone {} has more than one PTR, it is ignored", rr_set.get_name(), self.origin);
+        continue
+      }
+
+      if let &RData::PTR(ref member) = records[0].get_rdata() {
+        listed.insert(member.clone());
+      }
+    }
+
+    Some(listed)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+  use std::net::SocketAddr;
+
+  use ::authority::{Authority, Catalog, ZoneType};
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use ::rr::rdata::{SOA, TXT};
+  use super::*;
+
+  fn create_catalog_zone(serial: u32, members: &[&str]) -> Authority {
+    let origin = Name::parse("catalog.example.", None).unwrap();
+    let mut authority = Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Slave, false);
+    authority.set_primaries(vec!["192.0.2.1:53".parse().unwrap()]);
+    authority.upsert(Record::new().name(origin.clone()).ttl(0).rr_type(RecordType::SOA).dns_class(DNSClass::IN).rdata(RData::SOA(SOA::new(Name::parse("invalid.", None).unwrap(), Name::parse("invalid.", None).unwrap(), serial, 3600, 600, 86400, 0))).clone(), serial);
+    authority.upsert(Record::new().name(Name::parse("version", Some(&origin)).unwrap()).ttl(0).rr_type(RecordType::TXT).dns_class(DNSClass::IN).rdata(RData::TXT(TXT::new(vec!["2".to_string()]))).clone(), serial);
+
+    for (i, member) in members.iter().enumerate() {
+      let name = Name::parse(&format!("m{}.zones", i), Some(&origin)).unwrap();
+      authority.upsert(Record::new().name(name).ttl(0).rr_type(RecordType::PTR).dns_class(DNSClass::IN).rdata(RData::PTR(Name::parse(member, None).unwrap())).clone(), serial);
+    }
+
+    authority
+  }
+
+  #[test]
+  fn test_sync() {
+    let origin = Name::parse("catalog.example.", None).unwrap();
+    let configured = Name::parse("configured.example.", None).unwrap();
+    let catalog = Catalog::new();
+    catalog.upsert(configured.clone(), Authority::new(configured.clone(), BTreeMap::new(), ZoneType::Master, false));
+
+    let mut catalog_zone = CatalogZone::new(origin.clone());
+
+    // not transferred yet
+    catalog.upsert(origin.clone(), Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Slave, false));
+    assert!(catalog_zone.sync(&catalog).is_empty());
+
+    catalog.upsert(origin.clone(), create_catalog_zone(1, &["a.example.", "b.example.", "configured.example."]));
+    assert_eq!(catalog_zone.sync(&catalog).len(), 2);
+    let a = catalog.get(&Name::parse("a.example.", None).unwrap()).unwrap();
+    assert_eq!(a.read().unwrap().get_zone_type(), ZoneType::Slave);
+    assert_eq!(a.read().unwrap().get_primaries().to_vec(), vec!["192.0.2.1:53".parse::<SocketAddr>().unwrap()]);
+
+    // the configured zone is not a member
+    assert_eq!(catalog_zone.get_members().len(), 2);
+    assert!(!catalog_zone.get_members().contains(&configured));
+
+    // nothing changes until the serial does
+    catalog.upsert(origin.clone(), create_catalog_zone(1, &["a.example."]));
+    assert!(catalog_zone.sync(&catalog).is_empty());
+
+    catalog.upsert(origin.clone(), create_catalog_zone(2, &["a.example.", "c.example."]));
+    let changed = catalog_zone.sync(&catalog);
+    assert_eq!(changed.len(), 2);
+    assert!(changed.contains(&Name::parse("b.example.", None).unwrap()));
+    assert!(catalog.get(&Name::parse("b.example.", None).unwrap()).is_none());
+    assert!(catalog.get(&Name::parse("c.example.", None).unwrap()).is_some());
+    assert!(catalog.get(&configured).is_some());
+  }
+}
@@ -0,0 +1,279 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use ::client::SharedCache;
+use ::op::{OpCode, ResponseCode};
+use ::rr::RecordType;
+use super::{QueryLogEntry, QueryLogSink, Transport};
+
+/// the upper bounds of the buckets of the latency histogram, in microseconds, the final bucket is
+///  for all greater latencies
+const LATENCY_BUCKETS_US: &'static [u64] = &[100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000,
+                                             500_000, 1_000_000];
+
+/// counters of keys which are only known as they are seen, the keys are added under the write
+///  lock, after which they are incremented under the read lock
+struct KeyedCounters<K: Eq + Hash> {
+  counters: RwLock<HashMap<K, AtomicUsize>>,
+}
+
+impl<K: Eq + Hash + Copy> KeyedCounters<K> {
+  fn new() -> Self {
+    KeyedCounters{ counters: RwLock::new(HashMap::new()) }
+  }
+
+  fn increment(&self, key: K) {
+    {
+      let counters = self.counters.read().unwrap(); // poison errors should panic...
+      if let Some(counter) = counters.get(&key) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        return
+      }
+    }
+
+    let mut counters = self.counters.write().unwrap(); // poison errors should panic...
+    counters.entry(key).or_insert_with(|| AtomicUsize::new(0)).fetch_add(1, Ordering::Relaxed);
+  }
+
+  fn snapshot(&self) -> HashMap<K, u64> {
+    let counters = self.counters.read().unwrap(); // poison errors should panic...
+    counters.iter().map(|(key, counter)| (*key, counter.load(Ordering::Relaxed) as u64)).collect()
+  }
+}
+
+struct Counters {
+  queries_by_type: KeyedCounters<RecordType>,
+  responses_by_code: KeyedCounters<ResponseCode>,
+  udp: AtomicUsize,
+  tcp: AtomicUsize,
+  transfers: AtomicUsize,
+  updates: AtomicUsize,
+  latency: Vec<AtomicUsize>,
+  caches: Mutex<Vec<SharedCache>>,
+}
+
+/// Counters of the requests answered by the `Server`, a snapshot of them is taken with
+///  `snapshot()` for exporting to a monitoring system, e.g. Prometheus or statsd.
+///
+/// The counters are updated from the entries of the `QueryLog`, the metrics are one of its sinks.
+///  Clones share the same counters, so that a clone may be kept to take snapshots.
+///
+/// ```rust,ignore
+/// let metrics = ServerMetrics::new();
+/// metrics.add_cache(cache.clone());
+///
+/// let mut query_log = QueryLog::new();
+/// query_log.add_sink(Box::new(metrics.clone()));
+/// server.set_query_log(query_log);
+///
+/// // in the exporter
+/// let snapshot = metrics.snapshot();
+/// ```
+#[derive(Clone)]
+pub struct ServerMetrics {
+  counters: Arc<Counters>,
+}
+
+impl ServerMetrics {
+  pub fn new() -> Self {
+    ServerMetrics{
+      counters: Arc::new(Counters{
+        queries_by_type: KeyedCounters::new(),
+        responses_by_code: KeyedCounters::new(),
+        udp: AtomicUsize::new(0),
+        tcp: AtomicUsize::new(0),
+        transfers: AtomicUsize::new(0),
+        updates: AtomicUsize::new(0),
+        latency: (0..LATENCY_BUCKETS_US.len() + 1).map(|_| AtomicUsize::new(0)).collect(),
+        caches: Mutex::new(Vec::new()),
+      })
+    }
+  }
+
+  /// The hits and misses of the cache, e.g. that of a `Forwarder`, are included in the snapshots
+  pub fn add_cache(&self, cache: SharedCache) {
+    self.counters.caches.lock().unwrap().push(cache); // poison errors should panic...
+  }
+
+  /// Counts the request of the entry
+  pub fn record(&self, entry: &QueryLogEntry) {
+    let counters = &self.counters;
+
+    match entry.get_transport() {
+      Transport::Udp => counters.udp.fetch_add(1, Ordering::Relaxed),
+      Transport::Tcp => counters.tcp.fetch_add(1, Ordering::Relaxed),
+    };
+
+    counters.responses_by_code.increment(entry.get_response_code());
+
+    match entry.get_op_code() {
+      OpCode::Update => { counters.updates.fetch_add(1, Ordering::Relaxed); },
+      OpCode::Query => if let Some(&(_, query_type, _)) = entry.get_query() {
+        counters.queries_by_type.increment(query_type);
+        if query_type == RecordType::AXFR || query_type == RecordType::IXFR {
+          counters.transfers.fetch_add(1, Ordering::Relaxed);
+        }
+      },
+      _ => (),
+    }
+
+    let latency = entry.get_latency();
+    let latency_us = latency.as_secs() * 1_000_000 + (latency.subsec_nanos() / 1_000) as u64;
+    let bucket = LATENCY_BUCKETS_US.iter().position(|bound| latency_us <= *bound).unwrap_or(LATENCY_BUCKETS_US.len());
+    counters.latency[bucket].fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// The current values of the counters
+  pub fn snapshot(&self) -> MetricsSnapshot {
+    let counters = &self.counters;
+
+    let (mut cache_hits, mut cache_misses) = (0, 0);
+    for cache in counters.caches.lock().unwrap().iter() { // poison errors should panic...
+      let stats = cache.get_stats();
+      cache_hits += stats.get_hits() + stats.get_negative_hits();
+      cache_misses += stats.get_misses();
+    }
+
+    let latency = counters.latency.iter().enumerate().map(|(i, count)| {
+      (LATENCY_BUCKETS_US.get(i).map(|bound| Duration::new(*bound / 1_000_000, (*bound % 1_000_000 * 1_000) as u32)), count.load(Ordering::Relaxed) as u64)
+    }).collect();
+
+    MetricsSnapshot{
+      queries_by_type: counters.queries_by_type.snapshot(),
+      responses_by_code: counters.responses_by_code.snapshot(),
+      udp: counters.udp.load(Ordering::Relaxed) as u64,
+      tcp: counters.tcp.load(Ordering::Relaxed) as u64,
+      transfers: counters.transfers.load(Ordering::Relaxed) as u64,
+      updates: counters.updates.load(Ordering::Relaxed) as u64,
+      cache_hits: cache_hits,
+      cache_misses: cache_misses,
+      latency: latency,
+    }
+  }
+}
+
+impl QueryLogSink for ServerMetrics {
+  fn log(&self, entry: &QueryLogEntry) {
+    self.record(entry);
+  }
+}
+
+/// The values of the `ServerMetrics` counters at the time of the snapshot, all of the counters
+///  only increase
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+  queries_by_type: HashMap<RecordType, u64>,
+  responses_by_code: HashMap<ResponseCode, u64>,
+  udp: u64,
+  tcp: u64,
+  transfers: u64,
+  updates: u64,
+  cache_hits: u64,
+  cache_misses: u64,
+  latency: Vec<(Option<Duration>, u64)>,
+}
+
+impl MetricsSnapshot {
+  /// The queries by their type, of the first query of the request
+  pub fn get_queries_by_type(&self) -> &HashMap<RecordType, u64> { &self.queries_by_type }
+  /// The responses by their response code, including those to updates and notifies
+  pub fn get_responses_by_code(&self) -> &HashMap<ResponseCode, u64> { &self.responses_by_code }
+  /// The requests received over UDP
+  pub fn get_udp_requests(&self) -> u64 { self.udp }
+  /// The requests received over TCP
+  pub fn get_tcp_requests(&self) -> u64 { self.tcp }
+  /// The AXFR and IXFR requests, whether or not they were allowed
+  pub fn get_transfers(&self) -> u64 { self.transfers }
+  /// The dynamic update requests, whether or not they were allowed
+  pub fn get_updates(&self) -> u64 { self.updates }
+  /// The positive and negative hits of the caches, see `ServerMetrics::add_cache()`
+  pub fn get_cache_hits(&self) -> u64 { self.cache_hits }
+  pub fn get_cache_misses(&self) -> u64 { self.cache_misses }
+
+  /// The histogram of the latencies from receiving the requests to sending the responses, the
+  ///  count of each bucket with its upper bound, the final bucket, without a bound, counts the
+  ///  latencies greater than all of the bounds. The counts are not cumulative.
+  pub fn get_latency(&self) -> &[(Option<Duration>, u64)] { &self.latency }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::{Duration, Instant};
+
+  use ::client::{ResponseCache, SharedCache};
+  use ::op::{Message, MessageType, OpCode, Query, ResponseCode};
+  use ::rr::{DNSClass, Name, RecordType};
+  use ::server::{QueryLog, QueryLogEntry, Transport};
+  use super::*;
+
+  fn exchange(op_code: OpCode, query_type: RecordType, response_code: ResponseCode) -> (Message, Message) {
+    let mut query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(query_type);
+    let mut request = Message::new();
+    request.id(7).message_type(MessageType::Query).op_code(op_code).add_query(query.clone());
+
+    let mut response = Message::new();
+    response.id(7).message_type(MessageType::Response).op_code(op_code).response_code(response_code).add_query(query);
+    (request, response)
+  }
+
+  #[test]
+  fn test_snapshot() {
+    let metrics = ServerMetrics::new();
+    let cache = SharedCache::new(ResponseCache::new(16));
+    metrics.add_cache(cache.clone());
+
+    let mut query_log = QueryLog::new();
+    query_log.add_sink(Box::new(metrics.clone()));
+
+    let client = "192.0.2.1:53535".parse().unwrap();
+    for &(transport, op_code, query_type, response_code) in &[(Transport::Udp, OpCode::Query, RecordType::A, ResponseCode::NoError),
+                                                              (Transport::Udp, OpCode::Query, RecordType::A, ResponseCode::NXDomain),
+                                                              (Transport::Udp, OpCode::Query, RecordType::MX, ResponseCode::NoError),
+                                                              (Transport::Tcp, OpCode::Query, RecordType::AXFR, ResponseCode::Refused),
+                                                              (Transport::Udp, OpCode::Update, RecordType::SOA, ResponseCode::NoError)] {
+      let (request, response) = exchange(op_code, query_type, response_code);
+      query_log.log(&QueryLogEntry::new(client, transport, Some(&request), &response, 64, Instant::now()));
+    }
+
+    cache.get(&Name::parse("www.example.com.", None).unwrap(), DNSClass::IN, RecordType::A);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.get_udp_requests(), 4);
+    assert_eq!(snapshot.get_tcp_requests(), 1);
+    assert_eq!(snapshot.get_queries_by_type().get(&RecordType::A), Some(&2));
+    assert_eq!(snapshot.get_queries_by_type().get(&RecordType::MX), Some(&1));
+    assert_eq!(snapshot.get_queries_by_type().get(&RecordType::SOA), None);
+    assert_eq!(snapshot.get_responses_by_code().get(&ResponseCode::NoError), Some(&3));
+    assert_eq!(snapshot.get_responses_by_code().get(&ResponseCode::Refused), Some(&1));
+    assert_eq!(snapshot.get_transfers(), 1);
+    assert_eq!(snapshot.get_updates(), 1);
+    assert_eq!(snapshot.get_cache_hits(), 0);
+    assert_eq!(snapshot.get_cache_misses(), 1);
+
+    // every request is in one of the buckets, the last of which is unbounded
+    let latency = snapshot.get_latency();
+    assert_eq!(latency.iter().map(|&(_, count)| count).sum::<u64>(), 5);
+    assert_eq!(latency[0].0, Some(Duration::new(0, 100_000)));
+    assert_eq!(latency[latency.len() - 2].0, Some(Duration::new(1, 0)));
+    assert_eq!(latency[latency.len() - 1].0, None);
+  }
+}
@@ -16,6 +16,24 @@
 
 //! `Server` component for hosting a domain name servers operations.
  
+mod catalog_zone;
+mod metrics;
+mod query_log;
+mod rate_limiter;
 mod server;
+mod threaded_server;
+mod zone_checkpointer;
+mod zone_notifier;
+mod zone_refresher;
+mod zone_reloader;
 
-pub use self::server::Server;
+pub use self::catalog_zone::CatalogZone;
+pub use self::metrics::{MetricsSnapshot, ServerMetrics};
+pub use self::query_log::{FileSink, QueryLog, QueryLogEntry, QueryLogSink, StderrSink, Transport};
+pub use self::rate_limiter::{RateLimitAction, RateLimiter};
+pub use self::server::{Server, ShutdownHandle};
+pub use self::threaded_server::ThreadedServer;
+pub use self::zone_checkpointer::ZoneCheckpointer;
+pub use self::zone_notifier::ZoneNotifier;
+pub use self::zone_refresher::ZoneRefresher;
+pub use self::zone_reloader::{ReloadHandle, ZoneReloader};
@@ -0,0 +1,236 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, UTC};
+
+use ::op::{Message, OpCode, ResponseCode};
+use ::rr::{DNSClass, Name, RecordType};
+
+/// The transport a request was received on
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum Transport {
+  Udp,
+  Tcp,
+}
+
+impl fmt::Display for Transport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      Transport::Udp => write!(f, "udp"),
+      Transport::Tcp => write!(f, "tcp"),
+    }
+  }
+}
+
+/// A request and the response it was answered with, see `QueryLog`
+#[derive(Debug, Clone)]
+pub struct QueryLogEntry {
+  timestamp: DateTime<UTC>,
+  client: SocketAddr,
+  transport: Transport,
+  id: u16,
+  op_code: OpCode,
+  query: Option<(Name, RecordType, DNSClass)>,
+  response_code: ResponseCode,
+  flags: Vec<&'static str>,
+  response_size: usize,
+  latency: Duration,
+}
+
+impl QueryLogEntry {
+  /// # Arguments
+  ///
+  /// * `client` - the address the request was received from
+  /// * `transport` - the transport the request was received on
+  /// * `request` - the request, None if it could not be decoded
+  /// * `response` - the response which was sent
+  /// * `response_size` - the length of the sent response, 0 if it was dropped
+  /// * `received` - when the request was received, for the latency
+  pub fn new(client: SocketAddr, transport: Transport, request: Option<&Message>, response: &Message, response_size: usize,
+             received: Instant) -> Self {
+    let query = request.and_then(|request| request.get_queries().first())
+                       .map(|query| (query.get_name().clone(), query.get_query_type(), query.get_query_class()));
+
+    let mut flags: Vec<&'static str> = vec!["qr"];
+    if response.is_authoritative() { flags.push("aa"); }
+    if response.is_truncated() { flags.push("tc"); }
+    if response.is_recursion_desired() { flags.push("rd"); }
+    if response.is_recursion_available() { flags.push("ra"); }
+    if response.is_authentic_data() { flags.push("ad"); }
+    if response.is_checking_disabled() { flags.push("cd"); }
+    if request.and_then(|request| request.get_edns()).map_or(false, |edns| edns.is_dnssec_ok()) { flags.push("do"); }
+
+    QueryLogEntry{ timestamp: UTC::now(), client: client, transport: transport, id: response.get_id(), op_code: response.get_op_code(),
+                   query: query, response_code: response.get_response_code(), flags: flags, response_size: response_size,
+                   latency: received.elapsed() }
+  }
+
+  pub fn get_timestamp(&self) -> &DateTime<UTC> { &self.timestamp }
+  pub fn get_client(&self) -> SocketAddr { self.client }
+  pub fn get_transport(&self) -> Transport { self.transport }
+  pub fn get_id(&self) -> u16 { self.id }
+  pub fn get_op_code(&self) -> OpCode { self.op_code }
+  /// The name, type and class of the first query, None if the request could not be decoded
+  pub fn get_query(&self) -> Option<&(Name, RecordType, DNSClass)> { self.query.as_ref() }
+  pub fn get_response_code(&self) -> ResponseCode { self.response_code }
+  /// The flags of the response, and `do` if the request had the DNSSEC OK bit set
+  pub fn get_flags(&self) -> &[&'static str] { &self.flags }
+  pub fn get_response_size(&self) -> usize { self.response_size }
+  pub fn get_latency(&self) -> Duration { self.latency }
+}
+
+/// a single line of space separated key=value pairs
+impl fmt::Display for QueryLogEntry {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    try!(write!(f, "{} client={} transport={} id={}", self.timestamp.to_rfc3339(), self.client, self.transport, self.id));
+    match self.query {
+      Some((ref name, query_type, query_class)) => try!(write!(f, " qname={} qtype={:?} qclass={:?}", name, query_type, query_class)),
+      None => try!(write!(f, " qname=- qtype=- qclass=-")),
+    }
+
+    let latency_us = self.latency.as_secs() * 1_000_000 + (self.latency.subsec_nanos() / 1_000) as u64;
+    write!(f, " rcode={:?} flags={} size={} latency_us={}", self.response_code, self.flags.join(","), self.response_size, latency_us)
+  }
+}
+
+/// Where the entries of the `QueryLog` are written, any `Fn(&QueryLogEntry)` is a sink
+pub trait QueryLogSink {
+  fn log(&self, entry: &QueryLogEntry);
+}
+
+impl<F: Fn(&QueryLogEntry)> QueryLogSink for F {
+  fn log(&self, entry: &QueryLogEntry) {
+    self(entry)
+  }
+}
+
+/// Writes each entry as a line to stderr
+pub struct StderrSink;
+
+impl QueryLogSink for StderrSink {
+  fn log(&self, entry: &QueryLogEntry) {
+    let stderr = io::stderr();
+    let _ = writeln!(stderr.lock(), "{}", entry);
+  }
+}
+
+/// Appends each entry as a line to a file
+pub struct FileSink {
+  file: Mutex<LineWriter<File>>,
+}
+
+impl FileSink {
+  /// Opens the file for appending, creating it if it does not exist
+  pub fn open(path: &Path) -> io::Result<Self> {
+    let file = try!(OpenOptions::new().append(true).create(true).open(path));
+    Ok(FileSink{ file: Mutex::new(LineWriter::new(file)) })
+  }
+}
+
+impl QueryLogSink for FileSink {
+  fn log(&self, entry: &QueryLogEntry) {
+    let mut file = self.file.lock().unwrap(); // poison errors should panic...
+    if let Err(error) = writeln!(file, "{}", entry) {
+      warn!("could not write to the query log: {}", error);
+    }
+  }
+}
+
+/// The query log, one line per request with the client, query, response code, flags, response
+///  size and latency, written to each of the sinks. It is separate from the debug logging of the
+///  `log` crate.
+///
+/// ```text
+/// 2016-08-20T10:00:00.000123+00:00 client=192.0.2.1:53535 transport=udp id=4321 qname=www.example.com. qtype=A qclass=IN rcode=NoError flags=qr,aa,rd size=84 latency_us=120
+/// ```
+///
+/// ```rust,ignore
+/// let mut query_log = QueryLog::new();
+/// query_log.add_sink(Box::new(try!(FileSink::open(Path::new("/var/log/named/queries.log")))))
+///          .add_sink(Box::new(|entry: &QueryLogEntry| if entry.get_latency() > slow { warn!("slow: {}", entry) }));
+/// server.set_query_log(query_log);
+/// ```
+pub struct QueryLog {
+  sinks: Vec<Box<QueryLogSink>>,
+}
+
+impl QueryLog {
+  pub fn new() -> Self {
+    QueryLog{ sinks: Vec::new() }
+  }
+
+  pub fn add_sink(&mut self, sink: Box<QueryLogSink>) -> &mut Self {
+    self.sinks.push(sink);
+    self
+  }
+
+  /// Writes the entry to each of the sinks
+  pub fn log(&self, entry: &QueryLogEntry) {
+    for sink in &self.sinks {
+      sink.log(entry);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::cell::RefCell;
+  use std::rc::Rc;
+  use std::time::Instant;
+
+  use ::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+  use ::rr::{Name, RecordType};
+  use super::*;
+
+  #[test]
+  fn test_log() {
+    let mut query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::AAAA);
+    let mut request = Message::new();
+    request.id(7).message_type(MessageType::Query).op_code(OpCode::Query).recursion_desired(true).add_query(query.clone());
+    let mut edns = Edns::new();
+    edns.set_dnssec_ok(true);
+    request.set_edns(edns);
+
+    let mut response = Message::new();
+    response.id(7).message_type(MessageType::Response).response_code(ResponseCode::NXDomain).authoritative(true)
+            .recursion_desired(true).add_query(query);
+
+    let lines: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+    let sink_lines = lines.clone();
+    let mut query_log = QueryLog::new();
+    query_log.add_sink(Box::new(move |entry: &QueryLogEntry| sink_lines.borrow_mut().push(entry.to_string())));
+
+    let entry = QueryLogEntry::new("192.0.2.1:53535".parse().unwrap(), Transport::Udp, Some(&request), &response, 84, Instant::now());
+    assert_eq!(entry.get_flags(), &["qr", "aa", "rd", "do"]);
+    query_log.log(&entry);
+
+    let line = lines.borrow()[0].clone();
+    assert!(line.contains(" client=192.0.2.1:53535 transport=udp id=7 qname=www.example.com. qtype=AAAA qclass=IN rcode=NXDomain flags=qr,aa,rd,do size=84 latency_us="), "{}", line);
+    assert_eq!(line.lines().count(), 1);
+
+    // requests which could not be decoded
+    let entry = QueryLogEntry::new("192.0.2.1:53535".parse().unwrap(), Transport::Tcp, None, &response, 0, Instant::now());
+    assert!(entry.to_string().contains(" qname=- qtype=- qclass=- "));
+  }
+}
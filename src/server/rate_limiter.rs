@@ -0,0 +1,348 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use ::op::{Message, OpCode, ResponseCode};
+use ::rr::{Name, RecordType};
+
+/// the full buckets are discarded at most this often, when the table is full
+const PRUNE_INTERVAL_SECS: u64 = 1;
+
+/// What to do with a UDP response, see `RateLimiter::check()`
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RateLimitAction {
+  /// the response is within the limit
+  Send,
+  /// over the limit, send an empty truncated response, so that a real client retries over TCP
+  Slip,
+  /// over the limit, send nothing
+  Drop,
+}
+
+/// Response Rate Limiting (RRL) of UDP responses, so that the server is not useful for reflection
+///  and amplification attacks, where requests are sent with the spoofed address of the victim.
+///
+/// Each client network, the address masked to the prefix length, has a token bucket for each name
+///  and type it queries. As in the RRL of BIND, the NXDOMAIN responses of a zone share a bucket, as
+///  do the answers synthesized from a wildcard, so that queries of random names are limited
+///  together. A response takes a token, the tokens are refilled at the rate of
+///  responses per second, up to the burst. Responses over the limit are dropped, except that every
+///  slip-th is sent as an empty truncated response; a real client receiving it retries over TCP,
+///  which can not be spoofed. A slip of 0 drops all of them, a slip of 1 truncates all of them.
+///
+/// At most `max_buckets()` are tracked. When the table is full, the buckets which have refilled
+///  are discarded, at most once a second, and the responses which would need a new bucket are
+///  limited until there is room.
+///
+/// TCP responses are not limited.
+///
+/// ```rust,ignore
+/// let mut rate_limiter = RateLimiter::new();
+/// rate_limiter.responses_per_second(10).slip(2);
+/// server.set_rate_limiter(rate_limiter);
+/// ```
+pub struct RateLimiter {
+  buckets: HashMap<BucketKey, Bucket>,
+  responses_per_second: u32,
+  burst: u32,
+  slip: u32,
+  ipv4_prefix_len: u8,
+  ipv6_prefix_len: u8,
+  max_buckets: usize,
+  last_prune: Option<Instant>,
+  // the responses limited without a bucket, for the slip
+  untracked: u32,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct BucketKey {
+  client_prefix: Vec<u8>,
+  name: Name,
+  record_type: Option<RecordType>, // None for NXDOMAIN, which applies to all types
+}
+
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+  limited: u32,
+}
+
+impl RateLimiter {
+  pub fn new() -> Self {
+    RateLimiter{ buckets: HashMap::new(), responses_per_second: 5, burst: 10, slip: 2,
+                 ipv4_prefix_len: 24, ipv6_prefix_len: 56, max_buckets: 65536, last_prune: None, untracked: 0 }
+  }
+
+  /// The rate at which responses are permitted, default 5
+  pub fn responses_per_second(&mut self, responses_per_second: u32) -> &mut Self {
+    self.responses_per_second = responses_per_second;
+    self
+  }
+
+  /// The number of responses which may be sent at once, before being limited, default 10
+  pub fn burst(&mut self, burst: u32) -> &mut Self {
+    self.burst = burst;
+    self
+  }
+
+  /// Every slip-th limited response is sent truncated, the others are dropped, default 2
+  pub fn slip(&mut self, slip: u32) -> &mut Self {
+    self.slip = slip;
+    self
+  }
+
+  /// The prefix lengths of the client networks which are limited together, default 24 for IPv4
+  ///  and 56 for IPv6
+  pub fn prefix_lens(&mut self, ipv4_prefix_len: u8, ipv6_prefix_len: u8) -> &mut Self {
+    self.ipv4_prefix_len = ipv4_prefix_len;
+    self.ipv6_prefix_len = ipv6_prefix_len;
+    self
+  }
+
+  /// The number of buckets tracked, when there are this many the full ones are discarded, and
+  ///  responses which need a new bucket are limited, default 65536
+  pub fn max_buckets(&mut self, max_buckets: usize) -> &mut Self {
+    self.max_buckets = max_buckets;
+    self
+  }
+
+  /// Takes a token for the response to the client, the name and type are those of the first
+  ///  query of the response. NXDOMAIN responses take it from the bucket of the zone, the owner of
+  ///  the SOA in the authority section. Only responses to queries are limited.
+  ///
+  /// # Arguments
+  ///
+  /// * `src` - the address of the client
+  /// * `response` - the response to the client
+  /// * `wildcard` - the wildcard the answers were synthesized from, if they were, whose bucket is
+  ///                used instead of that of the name, see `Catalog::find_wildcard()`
+  pub fn check(&mut self, src: &IpAddr, response: &Message, wildcard: Option<&Name>) -> RateLimitAction {
+    let query = match response.get_queries().first() {
+      Some(query) if response.get_op_code() == OpCode::Query => query,
+      _ => return RateLimitAction::Send,
+    };
+
+    let now = Instant::now();
+    if response.get_response_code() == ResponseCode::NXDomain {
+      // without an SOA, all of the NXDOMAINs to the client share the bucket of the root
+      let root = Name::root();
+      let zone = response.get_name_servers().iter().find(|r| r.get_rr_type() == RecordType::SOA).map_or(&root, |soa| soa.get_name());
+      self.check_at(src, zone, None, now)
+    } else {
+      self.check_at(src, wildcard.unwrap_or(query.get_name()), Some(query.get_query_type()), now)
+    }
+  }
+
+  fn check_at(&mut self, src: &IpAddr, name: &Name, record_type: Option<RecordType>, now: Instant) -> RateLimitAction {
+    let key = BucketKey{ client_prefix: self.client_prefix(src), name: name.to_lowercase(), record_type: record_type };
+    let rate = self.responses_per_second as f64;
+    let burst = self.burst as f64;
+    let slip = self.slip;
+
+    if !self.buckets.contains_key(&key) && self.buckets.len() >= self.max_buckets {
+      if self.last_prune.map_or(true, |last_prune| now.duration_since(last_prune) >= Duration::from_secs(PRUNE_INTERVAL_SECS)) {
+        self.prune(now);
+      }
+
+      // still full, the response is limited without tracking it
+      if self.buckets.len() >= self.max_buckets {
+        self.untracked = self.untracked.wrapping_add(1);
+        return if slip > 0 && self.untracked % slip == 0 { RateLimitAction::Slip } else { RateLimitAction::Drop }
+      }
+    }
+
+    let bucket = self.buckets.entry(key).or_insert(Bucket{ tokens: burst, updated: now, limited: 0 });
+    bucket.tokens = Self::refill(bucket, rate, burst, now);
+    bucket.updated = now;
+
+    if bucket.tokens >= 1.0 {
+      bucket.tokens -= 1.0;
+      return RateLimitAction::Send
+    }
+
+    bucket.limited = bucket.limited.wrapping_add(1);
+    if slip > 0 && bucket.limited % slip == 0 { RateLimitAction::Slip } else { RateLimitAction::Drop }
+  }
+
+  /// the tokens of the bucket at the time
+  fn refill(bucket: &Bucket, rate: f64, burst: f64, now: Instant) -> f64 {
+    let elapsed: Duration = now.duration_since(bucket.updated);
+    let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+    let tokens = bucket.tokens + elapsed * rate;
+    if tokens > burst { burst } else { tokens }
+  }
+
+  /// discards the buckets which have refilled, they are the same as new ones
+  fn prune(&mut self, now: Instant) {
+    let rate = self.responses_per_second as f64;
+    let burst = self.burst as f64;
+    self.last_prune = Some(now);
+
+    let full: Vec<BucketKey> = self.buckets.iter()
+                                           .filter(|&(_, bucket)| Self::refill(bucket, rate, burst, now) >= burst)
+                                           .map(|(key, _)| key.clone())
+                                           .collect();
+    for key in full {
+      self.buckets.remove(&key);
+    }
+
+    if self.buckets.len() >= self.max_buckets { warn!("rate limiting {} clients, more than the max of {}", self.buckets.len(), self.max_buckets); }
+  }
+
+  /// the octets of the address, masked to the prefix length
+  fn client_prefix(&self, src: &IpAddr) -> Vec<u8> {
+    let (mut octets, prefix_len): (Vec<u8>, u8) = match *src {
+      IpAddr::V4(address) => (address.octets().to_vec(), self.ipv4_prefix_len),
+      IpAddr::V6(address) => (address.octets().to_vec(), self.ipv6_prefix_len),
+    };
+
+    for (i, octet) in octets.iter_mut().enumerate() {
+      let bits = prefix_len as usize;
+      if bits >= (i + 1) * 8 { continue }
+      *octet = if bits <= i * 8 { 0 } else { *octet & (0xFF << (8 - (bits - i * 8))) };
+    }
+
+    octets
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+  use std::time::{Duration, Instant};
+
+  use ::rr::{Name, RecordType};
+  use super::*;
+
+  #[test]
+  fn test_check() {
+    let mut limiter = RateLimiter::new();
+    limiter.responses_per_second(2).burst(2).slip(2);
+
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let client = IpAddr::V4(Ipv4Addr::new(192,0,2,1));
+    let now = Instant::now();
+
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), now), RateLimitAction::Send);
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), now), RateLimitAction::Send);
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), now), RateLimitAction::Drop);
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), now), RateLimitAction::Slip);
+
+    // the same network, and name regardless of case, share the bucket
+    let neighbor = IpAddr::V4(Ipv4Addr::new(192,0,2,200));
+    assert_eq!(limiter.check_at(&neighbor, &Name::parse("WWW.example.com.", None).unwrap(), Some(RecordType::A), now), RateLimitAction::Drop);
+
+    // other types, names and networks do not
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::AAAA), now), RateLimitAction::Send);
+    assert_eq!(limiter.check_at(&IpAddr::V4(Ipv4Addr::new(192,0,3,1)), &name, Some(RecordType::A), now), RateLimitAction::Send);
+
+    // refilled at the rate
+    let later = now + Duration::from_millis(500);
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), later), RateLimitAction::Send);
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), later), RateLimitAction::Slip);
+  }
+
+  #[test]
+  fn test_client_prefix() {
+    let mut limiter = RateLimiter::new();
+    assert_eq!(limiter.client_prefix(&IpAddr::V4(Ipv4Addr::new(192,0,2,1))), vec![192,0,2,0]);
+
+    limiter.prefix_lens(20, 56);
+    assert_eq!(limiter.client_prefix(&IpAddr::V4(Ipv4Addr::new(192,0,255,1))), vec![192,0,240,0]);
+    assert_eq!(limiter.client_prefix(&IpAddr::V6(Ipv6Addr::new(0x2001,0xdb8,0x1234,0x5678,1,2,3,4))),
+               vec![0x20,0x01,0x0d,0xb8,0x12,0x34,0x56,0,0,0,0,0,0,0,0,0]);
+  }
+
+  #[test]
+  fn test_prune() {
+    let mut limiter = RateLimiter::new();
+    limiter.max_buckets(2);
+
+    let now = Instant::now();
+    for octet in 1..4 {
+      let name = Name::parse(&format!("host{}.example.com.", octet), None).unwrap();
+      limiter.check_at(&IpAddr::V4(Ipv4Addr::new(192,0,2,1)), &name, Some(RecordType::A), now);
+    }
+
+    // the buckets have refilled a minute later
+    let name = Name::parse("www.example.com.", None).unwrap();
+    limiter.check_at(&IpAddr::V4(Ipv4Addr::new(192,0,2,1)), &name, Some(RecordType::A), now + Duration::from_secs(60));
+    assert_eq!(limiter.buckets.len(), 1);
+  }
+
+  #[test]
+  fn test_max_buckets() {
+    let mut limiter = RateLimiter::new();
+    limiter.max_buckets(10).slip(0);
+
+    let client = IpAddr::V4(Ipv4Addr::new(192,0,2,1));
+    let now = Instant::now();
+    let mut sent = 0;
+    for i in 0..100 {
+      let name = Name::parse(&format!("random{}.example.com.", i), None).unwrap();
+      if limiter.check_at(&client, &name, Some(RecordType::A), now) == RateLimitAction::Send { sent += 1; }
+    }
+
+    // the names beyond the max are limited, and not tracked
+    assert_eq!(sent, 10);
+    assert_eq!(limiter.buckets.len(), 10);
+
+    // the known names still have their buckets
+    let name = Name::parse("random0.example.com.", None).unwrap();
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), now), RateLimitAction::Send);
+
+    // once they have refilled there is room again
+    let name = Name::parse("random100.example.com.", None).unwrap();
+    assert_eq!(limiter.check_at(&client, &name, Some(RecordType::A), now + Duration::from_secs(60)), RateLimitAction::Send);
+    assert_eq!(limiter.buckets.len(), 1);
+  }
+
+  #[test]
+  fn test_nxdomain_and_wildcard() {
+    use ::op::{Message, MessageType, Query, ResponseCode};
+    use ::rr::{Record, RData};
+    use ::rr::rdata::SOA;
+
+    let mut limiter = RateLimiter::new();
+    limiter.burst(1).slip(0);
+
+    let client = IpAddr::V4(Ipv4Addr::new(192,0,2,1));
+    let origin = Name::parse("example.com.", None).unwrap();
+    let response = |name: &str, response_code: ResponseCode| {
+      let mut query = Query::new();
+      query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+      let mut message = Message::new();
+      message.message_type(MessageType::Response).response_code(response_code).add_query(query);
+      if response_code == ResponseCode::NXDomain {
+        message.add_name_server(Record::new().name(origin.clone()).ttl(3600).rr_type(RecordType::SOA).dns_class(::rr::DNSClass::IN)
+                                             .rdata(RData::SOA(SOA::new(origin.clone(), origin.clone(), 1, 2, 3, 4, 5))).clone());
+      }
+      message
+    };
+
+    // the random names of the zone share its bucket
+    assert_eq!(limiter.check(&client, &response("random1.example.com.", ResponseCode::NXDomain), None), RateLimitAction::Send);
+    assert_eq!(limiter.check(&client, &response("random2.example.com.", ResponseCode::NXDomain), None), RateLimitAction::Drop);
+
+    // as do those synthesized from the same wildcard
+    let wildcard = Name::parse("*.example.com.", None).unwrap();
+    assert_eq!(limiter.check(&client, &response("random3.example.com.", ResponseCode::NoError), Some(&wildcard)), RateLimitAction::Send);
+    assert_eq!(limiter.check(&client, &response("random4.example.com.", ResponseCode::NoError), Some(&wildcard)), RateLimitAction::Drop);
+    assert_eq!(limiter.buckets.len(), 2);
+  }
+}
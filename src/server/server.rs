@@ -16,24 +16,53 @@
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::io;
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, Mutex};
 use std::cell::Cell;
+use std::net::Shutdown;
+use std::time::{Duration, Instant};
 
-use mio::{Token, Evented, EventLoop, Handler, EventSet, PollOpt};
+use mio::{Token, Evented, EventLoop, Handler, EventSet, PollOpt, Sender, Timeout};
 use mio::tcp::{TcpListener, TcpStream};
 use mio::udp::UdpSocket;
 
-use ::authority::Catalog;
+use ::authority::{Catalog, MAX_TRANSFER_MESSAGE_SIZE, QueryPolicy};
+use ::dnstap::{Dnstap, SocketProtocol};
 use ::op::{Message, OpCode, ResponseCode};
 use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
 use ::tcp::{TcpHandler, TcpState};
 use ::udp::{UdpHandler, UdpState};
+use super::{QueryLog, QueryLogEntry, RateLimiter, Transport, ZoneCheckpointer, ZoneNotifier, ZoneRefresher, ZoneReloader};
+
+/// the timeout tokens of the zone reloader, refresher, notifier and checkpointer, handler tokens
+///  count up from 1
+const RELOAD_TOKEN: Token = Token(::std::usize::MAX);
+const REFRESH_TOKEN: Token = Token(::std::usize::MAX - 1);
+const NOTIFY_TOKEN: Token = Token(::std::usize::MAX - 2);
+const SHUTDOWN_TOKEN: Token = Token(::std::usize::MAX - 3);
+const CHECKPOINT_TOKEN: Token = Token(::std::usize::MAX - 4);
 
 // TODO, might be cool to store buffers for later usage...
 pub struct Server {
   handlers: HashMap<Token, DnsHandlerType>,
   next_token: Cell<usize>,
   catalog: Arc<Catalog>, // should the catalog just be static?
+  tcp_timeouts: HashMap<Token, Timeout>,
+  max_tcp_connections: Option<usize>,
+  tcp_idle_timeout_ms: Option<u64>,
+  max_tcp_requests: Option<usize>,
+  max_tcp_message_length: Option<usize>,
+  zone_reloader: Option<(ZoneReloader, u64)>,
+  zone_refresher: Option<(ZoneRefresher, u64)>,
+  zone_notifier: Option<(ZoneNotifier, u64)>,
+  zone_checkpointer: Option<(ZoneCheckpointer, u64)>,
+  rate_limiter: Option<RateLimiter>,
+  query_log: Option<QueryLog>,
+  dnstap: Option<Arc<Dnstap>>,
+  // the policies of the listeners, and of the connections accepted by them
+  listener_policies: HashMap<Token, QueryPolicy>,
+  shutdown: ShutdownHandle,
+  // true once the shutdown has been requested, until the in-flight requests are done
+  draining: bool,
 }
 
 impl Server {
@@ -42,6 +71,196 @@ impl Server {
       handlers: HashMap::new(),
       next_token: Cell::new(0),
       catalog: Arc::new(catalog),
+      tcp_timeouts: HashMap::new(),
+      max_tcp_connections: None,
+      tcp_idle_timeout_ms: None,
+      max_tcp_requests: None,
+      max_tcp_message_length: None,
+      zone_reloader: None,
+      zone_refresher: None,
+      zone_notifier: None,
+      zone_checkpointer: None,
+      rate_limiter: None,
+      query_log: None,
+      dnstap: None,
+      listener_policies: HashMap::new(),
+      shutdown: ShutdownHandle::new(),
+      draining: false,
+    }
+  }
+
+  /// The maximum number of concurrent inbound TCP connections, new connections beyond this limit
+  ///  are closed immediately after being accepted. `None`, the default, is unlimited.
+  pub fn set_max_tcp_connections(&mut self, max: Option<usize>) {
+    self.max_tcp_connections = max;
+  }
+
+  /// The number of milliseconds a TCP connection may remain idle, i.e. without any read or write
+  ///  activity, before it is closed. `None`, the default, never times out idle connections.
+  pub fn set_tcp_idle_timeout_ms(&mut self, timeout_ms: Option<u64>) {
+    self.tcp_idle_timeout_ms = timeout_ms;
+  }
+
+  /// The maximum number of requests which will be answered on a single TCP connection, the
+  ///  connection is closed after the response to the final request has been written.
+  pub fn set_max_tcp_requests(&mut self, max: Option<usize>) {
+    self.max_tcp_requests = max;
+  }
+
+  /// The maximum length of the requests read from TCP connections, the connection is closed if a
+  ///  longer request is sent. `None`, the default, accepts any request which can be framed. The
+  ///  responses are not limited, e.g. the messages of zone transfers.
+  pub fn set_max_tcp_message_length(&mut self, max: Option<usize>) {
+    self.max_tcp_message_length = max;
+  }
+
+  /// Checks the zone files watched by the reloader for changes, and handles its reload requests,
+  ///  every poll_ms milliseconds while listening.
+  pub fn set_zone_reloader(&mut self, reloader: ZoneReloader, poll_ms: u64) {
+    self.zone_reloader = Some((reloader, poll_ms));
+  }
+
+  /// Refreshes the secondary zones which have been notified of changes, or are due for refresh,
+  ///  checking every poll_ms milliseconds while listening.
+  pub fn set_zone_refresher(&mut self, refresher: ZoneRefresher, poll_ms: u64) {
+    self.zone_refresher = Some((refresher, poll_ms));
+  }
+
+  /// NOTIFYs the secondaries of zones whose serials have changed, e.g. by dynamic updates,
+  ///  checking every poll_ms milliseconds while listening.
+  pub fn set_zone_notifier(&mut self, notifier: ZoneNotifier, poll_ms: u64) {
+    self.zone_notifier = Some((notifier, poll_ms));
+  }
+
+  /// Checkpoints the zones which have been updated to their zone files, truncating their
+  ///  journals, every poll_ms milliseconds while listening.
+  pub fn set_zone_checkpointer(&mut self, checkpointer: ZoneCheckpointer, poll_ms: u64) {
+    self.zone_checkpointer = Some((checkpointer, poll_ms));
+  }
+
+  /// Limits the rate of UDP responses to each client network, see `RateLimiter`. By default
+  ///  responses are not limited.
+  pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+    self.rate_limiter = Some(rate_limiter);
+  }
+
+  /// Logs each request and its response, see `QueryLog`. By default there is no query log.
+  pub fn set_query_log(&mut self, query_log: QueryLog) {
+    self.query_log = Some(query_log);
+  }
+
+  /// Writes each request and its responses to dnstap, see `Dnstap`. By default there is no
+  ///  dnstap output.
+  pub fn set_dnstap(&mut self, dnstap: Arc<Dnstap>) {
+    self.dnstap = Some(dnstap);
+  }
+
+  /// A handle to shut the server down gracefully from another thread, see `ShutdownHandle`
+  pub fn shutdown_handle(&self) -> ShutdownHandle {
+    self.shutdown.clone()
+  }
+
+  /// The catalog being served, zones can be added to and removed from it while the server is
+  ///  running, see `Catalog::upsert()` and `Catalog::remove()`
+  pub fn get_catalog(&self) -> Arc<Catalog> {
+    self.catalog.clone()
+  }
+
+  /// arms the timer for the next poll of the zone reloader, if there is one
+  fn schedule_reload(&self, event_loop: &mut EventLoop<Self>) {
+    if let Some(poll_ms) = self.zone_reloader.as_ref().map(|&(_, poll_ms)| poll_ms) {
+      event_loop.timeout_ms(RELOAD_TOKEN, poll_ms).map(|_| ()).unwrap_or_else(|e| warn!("could not schedule zone reload: {:?}", e));
+    }
+  }
+
+  /// arms the timer for the next poll of the zone refresher, if there is one
+  fn schedule_refresh(&self, event_loop: &mut EventLoop<Self>) {
+    if let Some(poll_ms) = self.zone_refresher.as_ref().map(|&(_, poll_ms)| poll_ms) {
+      event_loop.timeout_ms(REFRESH_TOKEN, poll_ms).map(|_| ()).unwrap_or_else(|e| warn!("could not schedule zone refresh: {:?}", e));
+    }
+  }
+
+  /// arms the timer for the next poll of the zone notifier, if there is one
+  fn schedule_notify(&self, event_loop: &mut EventLoop<Self>) {
+    if let Some(poll_ms) = self.zone_notifier.as_ref().map(|&(_, poll_ms)| poll_ms) {
+      event_loop.timeout_ms(NOTIFY_TOKEN, poll_ms).map(|_| ()).unwrap_or_else(|e| warn!("could not schedule zone notify: {:?}", e));
+    }
+  }
+
+  /// arms the timer for the next poll of the zone checkpointer, if there is one
+  fn schedule_checkpoint(&self, event_loop: &mut EventLoop<Self>) {
+    if let Some(poll_ms) = self.zone_checkpointer.as_ref().map(|&(_, poll_ms)| poll_ms) {
+      event_loop.timeout_ms(CHECKPOINT_TOKEN, poll_ms).map(|_| ()).unwrap_or_else(|e| warn!("could not schedule zone checkpoint: {:?}", e));
+    }
+  }
+
+  /// stops accepting requests, the listeners, idle connections and UDP sockets without responses
+  ///  to send are closed, the remaining connections have until the grace period passes
+  fn begin_shutdown(&mut self, event_loop: &mut EventLoop<Self>, grace: Duration) {
+    if self.draining { return }
+    self.draining = true;
+    info!("shutting down, draining in-flight requests for up to {:?}", grace);
+
+    let idle: Vec<Token> = self.handlers.iter().filter(|&(_, handler)| Self::is_drained(handler)).map(|(token, _)| *token).collect();
+    for token in idle {
+      self.remove_handler(event_loop, token);
+    }
+
+    let grace_ms = grace.as_secs() * 1000 + (grace.subsec_nanos() / 1_000_000) as u64;
+    event_loop.timeout_ms(SHUTDOWN_TOKEN, grace_ms).map(|_| ()).unwrap_or_else(|e| warn!("could not schedule the shutdown deadline: {:?}", e));
+    self.stop_if_drained(event_loop);
+  }
+
+  /// true if the handler has nothing left to do while shutting down
+  fn is_drained(handler: &DnsHandlerType) -> bool {
+    match *handler {
+      DnsHandlerType::UdpSocket((_, ref requests)) => requests.is_empty(),
+      DnsHandlerType::TcpListener(_) => true,
+      DnsHandlerType::TcpHandler(ref tcp_handler) => tcp_handler.is_idle(),
+    }
+  }
+
+  /// once the in-flight requests are done, the journals are flushed and the event loop stopped
+  fn stop_if_drained(&mut self, event_loop: &mut EventLoop<Self>) {
+    if !self.draining || !self.handlers.is_empty() { return }
+
+    info!("in-flight requests are done, flushing journals");
+    self.catalog.flush_journals();
+    event_loop.shutdown();
+  }
+
+  /// the number of currently open inbound TCP connections
+  fn tcp_connection_count(&self) -> usize {
+    self.handlers.values().filter(|h| if let DnsHandlerType::TcpHandler(_) = **h { true } else { false }).count()
+  }
+
+  /// (re)arms the idle timer for the TCP connection at token, if idle timeouts are configured
+  fn reset_idle_timeout(&mut self, event_loop: &mut EventLoop<Self>, token: Token) {
+    if let Some(timeout) = self.tcp_timeouts.remove(&token) {
+      event_loop.clear_timeout(timeout);
+    }
+
+    if let Some(timeout_ms) = self.tcp_idle_timeout_ms {
+      match event_loop.timeout_ms(token, timeout_ms) {
+        Ok(timeout) => { self.tcp_timeouts.insert(token, timeout); },
+        Err(e) => warn!("could not set idle timeout for {:?}: {:?}", token, e),
+      }
+    }
+  }
+
+  /// deregisters and closes the handler at token
+  fn remove_handler(&mut self, event_loop: &mut EventLoop<Self>, token: Token) {
+    if let Some(timeout) = self.tcp_timeouts.remove(&token) {
+      event_loop.clear_timeout(timeout);
+    }
+
+    self.listener_policies.remove(&token);
+    if let Some(handler) = self.handlers.remove(&token) {
+      event_loop.deregister(handler.get_socket()).unwrap_or_else(|e| debug!("error deregistering: {}", e));
+
+      if let DnsHandlerType::TcpHandler(ref tcp_handler) = handler {
+        tcp_handler.get_stream().shutdown(Shutdown::Both).unwrap_or_else(|e| debug!("error shutting down stream: {}", e));
+      }
     }
   }
 
@@ -71,10 +290,35 @@ impl Server {
     self.handlers.insert(token, DnsHandlerType::TcpListener(listener));
   }
 
+  /// register a UDP socket, requests received on it from clients which the policy does not permit
+  ///  to query are refused.
+  pub fn register_socket_with_policy(&mut self, socket: UdpSocket, policy: QueryPolicy) {
+    let token = self.next_token();
+    self.handlers.insert(token, DnsHandlerType::UdpSocket((socket, VecDeque::new())));
+    self.listener_policies.insert(token, policy);
+  }
+
+  /// register a TcpListener, requests on the connections it accepts from clients which the policy
+  ///  does not permit to query are refused.
+  pub fn register_listener_with_policy(&mut self, listener: TcpListener, policy: QueryPolicy) {
+    let token = self.next_token();
+    self.handlers.insert(token, DnsHandlerType::TcpListener(listener));
+    self.listener_policies.insert(token, policy);
+  }
+
   /// TODO how to do threads? should we do a bunch of listener threads and then query threads?
   /// Ideally the processing would be n-threads for recieving, which hand off to m-threads for
   ///  request handling. It would generally be the case that n <= m.
+  ///
+  /// Returns Ok once the server has been shut down with its `ShutdownHandle`, the handle's
+  ///  `wait()` returns once this does, whether or not the server was shut down.
   pub fn listen(&mut self) -> io::Result<()> {
+    let result = self.run_event_loop();
+    self.shutdown.set_stopped();
+    result
+  }
+
+  fn run_event_loop(&mut self) -> io::Result<()> {
     info!("Server starting up");
     let mut event_loop: EventLoop<Self> = try!(EventLoop::new());
 
@@ -87,27 +331,89 @@ impl Server {
       }
     }
 
+    self.schedule_reload(&mut event_loop);
+    self.schedule_refresh(&mut event_loop);
+    self.schedule_notify(&mut event_loop);
+    self.schedule_checkpoint(&mut event_loop);
+
+    // a shutdown may have been requested before the event loop existed
+    if let Some(grace) = self.shutdown.set_sender(event_loop.channel()) {
+      self.begin_shutdown(&mut event_loop, grace);
+    }
     try!(event_loop.run(self));
 
+    if self.draining {
+      info!("Server shut down");
+      return Ok(())
+    }
     Err(io::Error::new(io::ErrorKind::Interrupted, "Server stopping due to interruption"))
   }
 
-  /// given a set of bytes, decode and process the request, producing a response to send
-  fn process_request(bytes: &[u8], stream: &TcpStream, catalog: &Catalog) -> Message {
+  /// given a set of bytes, decode and process the request, producing the responses to send, a
+  ///  zone transfer is sent as multiple messages
+  fn process_request(bytes: &[u8], stream: &TcpStream, catalog: &Catalog, policy: Option<&QueryPolicy>, query_log: Option<&QueryLog>,
+                     dnstap: Option<&Dnstap>) -> io::Result<Vec<Vec<u8>>> {
+    let received = Instant::now();
     let mut decoder = BinDecoder::new(bytes);
     let request = Message::read(&mut decoder);
 
-    match request {
+    let log_query = |response: &Message, buffers: &[Vec<u8>]| {
+      if let (Some(query_log), Ok(src)) = (query_log, stream.peer_addr()) {
+        query_log.log(&QueryLogEntry::new(src, Transport::Tcp, request.as_ref().ok(), response, buffers.iter().map(|buffer| buffer.len()).sum(), received));
+      }
+      if let (Some(dnstap), Ok(src)) = (dnstap, stream.peer_addr()) {
+        dnstap.log_server(src, SocketProtocol::Tcp, response.is_recursion_available(), received, bytes, buffers.iter().map(|buffer| &buffer[..]));
+      }
+    };
+
+    let response = match request {
       Err(ref decode_error) => {
         warn!("unable to decode request from client: {:?}: {}", stream, decode_error);
         Catalog::error_msg(0/* id is in the message... */, OpCode::Query/* right default? */, ResponseCode::FormErr)
       },
-      Ok(ref req) => catalog.handle_request(req),
-    }
+      Ok(ref req) => {
+        let src = match stream.peer_addr() {
+          Ok(addr) => Some(addr),
+          Err(error) => {
+            warn!("unable to get the address of the client: {:?}: {}", stream, error);
+            None
+          },
+        };
+
+        if !policy.map_or(true, |policy| policy.is_query_allowed(src.map(|addr| addr.ip()).as_ref())) {
+          info!("refusing request from: {:?}", src);
+          Catalog::error_msg(req.get_id(), req.get_op_code(), ResponseCode::Refused)
+        } else if Catalog::is_transfer(req) {
+          let transfer = catalog.transfer(req, src.map(|addr| addr.ip()).as_ref(), Some(bytes));
+          match transfer.to_bytes(MAX_TRANSFER_MESSAGE_SIZE) {
+            Ok(buffers) => {
+              debug!("transferring in {} messages to: {:?}", buffers.len(), src);
+              log_query(transfer.get_response(), &buffers);
+              return Ok(buffers)
+            },
+            Err(encode_error) => {
+              error!("error encoding transfer to client: {}", encode_error);
+              Catalog::error_msg(req.get_id(), req.get_op_code(), ResponseCode::ServFail)
+            },
+          }
+        } else {
+          match src {
+            Some(addr) => catalog.handle_request_from(req, addr, bytes),
+            None => catalog.handle_request(req),
+          }
+        }
+      },
+    };
+
+    let mut buffer: Vec<u8> = Vec::with_capacity(512);
+    try!(Self::encode_message(&response, &mut buffer));
+    let buffers = vec![buffer];
+    log_query(&response, &buffers);
+    Ok(buffers)
   }
 
   /// encodes a message to the specified buffer
-  fn encode_message(response: Message, buffer: &mut Vec<u8>) -> io::Result<()> {
+  fn encode_message(response: &Message, buffer: &mut Vec<u8>) -> io::Result<()> {
     // all responses need these fields set:
     buffer.clear();
     let encode_result = {
@@ -153,6 +459,10 @@ trait DnsHandler {
   /// # Arguments
   /// * `events` - the set of events that that woke this socket up
   /// * `catalog` - the local catalog for lookups
+  /// * `rate_limiter` - limits the rate of UDP responses, if configured
+  /// * `policy` - the query policy of the listener, if it has one
+  /// * `query_log` - logs each request, if configured
+  /// * `dnstap` - writes each request and its responses to dnstap, if configured
   ///
   /// # Return
   ///
@@ -160,18 +470,20 @@ trait DnsHandler {
   ///  the event_loop. If the first of the tuple is None, self will be removed from the event_loop.
   ///  If the second is None, nothing will happen, otherwise the new handler will be added to the
   ///  event_loop.
-  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>);
+  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>, rate_limiter: Option<&mut RateLimiter>, policy: Option<&QueryPolicy>, query_log: Option<&QueryLog>,
+            dnstap: Option<&Dnstap>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>);
 
   /// returns the Evented which self wraps.
   fn get_socket(&self) -> &Evented;
 }
 
 impl DnsHandler for DnsHandlerType {
-  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
+  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>, rate_limiter: Option<&mut RateLimiter>, policy: Option<&QueryPolicy>, query_log: Option<&QueryLog>,
+            dnstap: Option<&Dnstap>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
     match *self {
-      DnsHandlerType::UdpSocket(ref mut udp_handler) => udp_handler.handle(events, catalog),
-      DnsHandlerType::TcpListener(ref mut tcp_listener) => tcp_listener.handle(events, catalog),
-      DnsHandlerType::TcpHandler(ref mut tcp_handler) => tcp_handler.handle(events, catalog),
+      DnsHandlerType::UdpSocket(ref mut udp_handler) => udp_handler.handle(events, catalog, rate_limiter, policy, query_log, dnstap),
+      DnsHandlerType::TcpListener(ref mut tcp_listener) => tcp_listener.handle(events, catalog, rate_limiter, policy, query_log, dnstap),
+      DnsHandlerType::TcpHandler(ref mut tcp_handler) => tcp_handler.handle(events, catalog, rate_limiter, policy, query_log, dnstap),
     }
   }
 
@@ -185,7 +497,8 @@ impl DnsHandler for DnsHandlerType {
 }
 
 impl DnsHandler for TcpListener {
-  fn handle(&mut self, events: EventSet, _: &Arc<Catalog>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
+  fn handle(&mut self, events: EventSet, _: &Arc<Catalog>, _: Option<&mut RateLimiter>, _: Option<&QueryPolicy>, _: Option<&QueryLog>,
+            _: Option<&Dnstap>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
     if events.is_error() { panic!("unexpected error state on: {:?}", self) }
     else if events.is_hup() { panic!("listening socket hungup: {:?}", self) }
     else if events.is_readable() || events.is_writable() {
@@ -217,9 +530,11 @@ impl DnsHandler for TcpListener {
 }
 
 impl DnsHandler for (UdpSocket, VecDeque<UdpHandler>) {
-  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
+  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>, rate_limiter: Option<&mut RateLimiter>, policy: Option<&QueryPolicy>, query_log: Option<&QueryLog>,
+            dnstap: Option<&Dnstap>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
     let ref socket = self.0;
     let ref mut requests = self.1;
+    let mut rate_limiter = rate_limiter;
 
     if events.is_error() {
       panic!("unexpected socket error: {:?}", socket)
@@ -272,7 +587,10 @@ impl DnsHandler for (UdpSocket, VecDeque<UdpHandler>) {
         // collect new requests
         // TODO: could a ton of inbound requests starve the server
         for _ in 0..100 {
-          if let Some(handler) = UdpHandler::new_server(&socket, catalog.clone()) {
+          if let Some(handler) = UdpHandler::new_server(&socket, catalog.clone(), rate_limiter.as_mut().map(|limiter| &mut **limiter), policy, query_log, dnstap) {
+            // the response was dropped by the rate limiter
+            if handler.is_done() { continue }
+
             // this is a new request for a UDP transaction
             // let the handler read, etc.
             requests.push_back(handler);
@@ -296,7 +614,8 @@ impl DnsHandler for (UdpSocket, VecDeque<UdpHandler>) {
 }
 
 impl DnsHandler for TcpHandler {
-  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
+  fn handle(&mut self, events: EventSet, catalog: &Arc<Catalog>, _: Option<&mut RateLimiter>, policy: Option<&QueryPolicy>, query_log: Option<&QueryLog>,
+            dnstap: Option<&Dnstap>) -> (Option<EventSet>, Option<(DnsHandlerType, EventSet)>) {
     if events.is_error() {
       warn!("closing, error from: {:?}", self.get_stream());
       // TODO: do we need to shutdown the stream?
@@ -339,10 +658,12 @@ impl DnsHandler for TcpHandler {
 
       // need to process the response
       if process_resquest {
-        let response = Server::process_request(self.get_buffer(), self.get_stream(), catalog.as_ref());
-        if Server::encode_message(response, self.get_buffer_mut()).is_err() {
-          warn!("could not encode message to: {:?}", self.get_stream());
-          return (None, None)
+        match Server::process_request(self.get_buffer(), self.get_stream(), catalog.as_ref(), policy, query_log, dnstap) {
+          Ok(buffers) => self.set_buffers(buffers),
+          Err(..) => {
+            warn!("could not encode message to: {:?}", self.get_stream());
+            return (None, None)
+          },
         }
       }
     }
@@ -363,18 +684,41 @@ impl Handler for Server {
   fn ready(&mut self, event_loop: &mut EventLoop<Self>, token: Token, events: EventSet) {
     let mut remove_token: Option<Token> = None;
     let mut add_handler: Option<(DnsHandlerType, EventSet)> = None;
+    let mut is_tcp = false;
+
+    // no new requests are read from the UDP sockets while shutting down
+    let events = match self.handlers.get(&token) {
+      Some(&DnsHandlerType::UdpSocket(_)) if self.draining => events & !EventSet::readable(),
+      _ => events,
+    };
 
     // The token should always exist
     if let Some(mut handler) = self.handlers.get_mut(&token) {
       // the handler will perform the lookup or other actions.
       //  if none is returned for event_set_opt, the handler will be revmoed
-      let (event_set_opt, add) = handler.handle(events, &self.catalog);
+      let (event_set_opt, add) = handler.handle(events, &self.catalog, self.rate_limiter.as_mut(), self.listener_policies.get(&token), self.query_log.as_ref(),
+                                                 self.dnstap.as_ref().map(|dnstap| &**dnstap));
 
       // this represents a new handler to watch
       add_handler = add;
 
+      // close connections which have served their quota of requests
+      let mut exhausted = false;
+      if let DnsHandlerType::TcpHandler(ref tcp_handler) = *handler {
+        is_tcp = true;
+        if let Some(max) = self.max_tcp_requests {
+          exhausted = tcp_handler.get_request_count() >= max;
+        }
+      }
+
       // given the new event_set option, reregister the socket
-      if let Some(event_set) = event_set_opt {
+      if exhausted {
+        info!("max requests served, closing {:?}", token);
+        remove_token = Some(token);
+      } else if self.draining && Self::is_drained(handler) {
+        debug!("drained, closing {:?}", token);
+        remove_token = Some(token);
+      } else if let Some(event_set) = event_set_opt {
         let socket: &Evented = handler.get_socket();
         if let Err(err) = event_loop.reregister(socket, token, event_set, PollOpt::all()) {
           // removing the socket in case of an error
@@ -387,15 +731,28 @@ impl Handler for Server {
     }
 
     // unregister the token
-    remove_token.and_then(|token| {
-      self.handlers.remove(&token)
-    }).and_then(|handler| {
-      event_loop.deregister(handler.get_socket()).unwrap_or_else(|e| debug!("error deregistering: {}", e));
-      Some(())
-    });
+    if let Some(token) = remove_token {
+      self.remove_handler(event_loop, token);
+      self.stop_if_drained(event_loop);
+    } else if is_tcp {
+      // there was activity, push back the idle timeout
+      self.reset_idle_timeout(event_loop, token);
+    }
 
     // need to register a new handler if there was one.
-    if let Some((handler, event_set)) = add_handler {
+    if let Some((mut handler, event_set)) = add_handler {
+      if let DnsHandlerType::TcpHandler(ref mut tcp_handler) = handler {
+        if let Some(max) = self.max_tcp_connections {
+          if self.tcp_connection_count() >= max {
+            warn!("max tcp connections reached, closing: {:?}", tcp_handler.get_stream());
+            tcp_handler.get_stream().shutdown(Shutdown::Both).unwrap_or_else(|e| debug!("error shutting down stream: {}", e));
+            return
+          }
+        }
+
+        if let Some(max) = self.max_tcp_message_length { tcp_handler.set_max_read_length(max); }
+      }
+
       let register_res: io::Result<Token> = {
         let socket: &Evented = handler.get_socket();
 
@@ -404,12 +761,87 @@ impl Handler for Server {
       };
 
       match register_res {
-        Ok(token) => { self.handlers.insert(token, handler); },
+        Ok(new_token) => {
+          let is_tcp_handler = if let DnsHandlerType::TcpHandler(_) = handler { true } else { false };
+          self.handlers.insert(new_token, handler);
+          if is_tcp_handler { self.reset_idle_timeout(event_loop, new_token); }
+
+          // connections are subject to the policy of the listener which accepted them
+          if let Some(policy) = self.listener_policies.get(&token).cloned() {
+            self.listener_policies.insert(new_token, policy);
+          }
+        },
         Err(err) => warn!("error registering handler: {}", err),
       }
     }
   }
 
+  fn timeout(&mut self, event_loop: &mut EventLoop<Self>, token: Token) {
+    if token == SHUTDOWN_TOKEN {
+      warn!("shutdown grace period passed, closing {} unfinished connections", self.handlers.len());
+      let tokens: Vec<Token> = self.handlers.keys().cloned().collect();
+      for token in tokens {
+        self.remove_handler(event_loop, token);
+      }
+
+      self.stop_if_drained(event_loop);
+      return
+    }
+
+    // the zones are left alone while shutting down, the timers are not rearmed
+    if self.draining && (token == RELOAD_TOKEN || token == REFRESH_TOKEN || token == NOTIFY_TOKEN || token == CHECKPOINT_TOKEN) { return }
+
+    if token == RELOAD_TOKEN {
+      if let Some((ref mut reloader, _)) = self.zone_reloader {
+        reloader.poll(&self.catalog);
+      }
+
+      self.schedule_reload(event_loop);
+      return
+    }
+
+    if token == REFRESH_TOKEN {
+      if let Some((ref mut refresher, _)) = self.zone_refresher {
+        refresher.poll(&self.catalog);
+      }
+
+      self.schedule_refresh(event_loop);
+      return
+    }
+
+    if token == NOTIFY_TOKEN {
+      if let Some((ref mut notifier, _)) = self.zone_notifier {
+        notifier.poll(&self.catalog);
+      }
+
+      self.schedule_notify(event_loop);
+      return
+    }
+
+    if token == CHECKPOINT_TOKEN {
+      if let Some((ref mut checkpointer, _)) = self.zone_checkpointer {
+        checkpointer.poll(&self.catalog);
+      }
+
+      self.schedule_checkpoint(event_loop);
+      return
+    }
+
+    // the timeout fired, so it no longer needs to be cleared
+    self.tcp_timeouts.remove(&token);
+
+    info!("idle timeout, closing {:?}", token);
+    self.remove_handler(event_loop, token);
+    self.stop_if_drained(event_loop);
+  }
+
+  /// the only message is the request to shut down, from the `ShutdownHandle`
+  fn notify(&mut self, event_loop: &mut EventLoop<Self>, _: ()) {
+    if let Some(grace) = self.shutdown.get_grace() {
+      self.begin_shutdown(event_loop, grace);
+    }
+  }
+
   fn interrupted(&mut self, event_loop: &mut EventLoop<Self>) {
     warn!("server interrupted, shutting down");
     event_loop.shutdown();
@@ -417,6 +849,94 @@ impl Handler for Server {
   }
 }
 
+struct ShutdownState {
+  // the grace period, once shutdown has been requested
+  grace: Option<Duration>,
+  sender: Option<Sender<()>>,
+  stopped: bool,
+}
+
+/// Shuts down a `Server` which is listening, from another thread, see `Server::shutdown_handle()`
+///
+/// The server stops accepting connections and reading requests, the requests which are being
+///  answered have until the grace period passes to be sent, after which the remaining connections
+///  are closed. The journals of the zones are flushed, and `Server::listen()` returns Ok.
+///
+/// ```rust,ignore
+/// let shutdown = server.shutdown_handle();
+/// thread::spawn(move || server.listen());
+///
+/// shutdown.shutdown(Duration::from_secs(5));
+/// shutdown.wait();
+/// ```
+#[derive(Clone)]
+pub struct ShutdownHandle {
+  state: Arc<(Mutex<ShutdownState>, Condvar)>,
+}
+
+impl ShutdownHandle {
+  fn new() -> Self {
+    ShutdownHandle{ state: Arc::new((Mutex::new(ShutdownState{ grace: None, sender: None, stopped: false }), Condvar::new())) }
+  }
+
+  /// Requests the server to shut down, the in-flight requests have until the grace period passes.
+  ///  If the server is not listening yet, it shuts down as soon as it starts.
+  pub fn shutdown(&self, grace: Duration) {
+    let mut state = self.state.0.lock().unwrap(); // poison errors should panic...
+    if state.grace.is_some() { return }
+    state.grace = Some(grace);
+
+    if let Some(ref sender) = state.sender {
+      if sender.send(()).is_err() { warn!("could not notify the server to shut down"); }
+    }
+  }
+
+  /// true once `Server::listen()` has returned
+  pub fn is_stopped(&self) -> bool {
+    self.state.0.lock().unwrap().stopped // poison errors should panic...
+  }
+
+  /// Blocks until `Server::listen()` has returned
+  pub fn wait(&self) {
+    let mut state = self.state.0.lock().unwrap(); // poison errors should panic...
+    while !state.stopped {
+      state = self.state.1.wait(state).unwrap(); // poison errors should panic...
+    }
+  }
+
+  /// Blocks until `Server::listen()` has returned, or the timeout passes, returns true if it has
+  pub fn wait_timeout(&self, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut state = self.state.0.lock().unwrap(); // poison errors should panic...
+
+    while !state.stopped {
+      let now = Instant::now();
+      if now >= deadline { return false }
+      state = self.state.1.wait_timeout(state, deadline - now).unwrap().0; // poison errors should panic...
+    }
+
+    true
+  }
+
+  fn get_grace(&self) -> Option<Duration> {
+    self.state.0.lock().unwrap().grace // poison errors should panic...
+  }
+
+  /// the channel of the event loop, returns the grace period if shutdown was already requested
+  fn set_sender(&self, sender: Sender<()>) -> Option<Duration> {
+    let mut state = self.state.0.lock().unwrap(); // poison errors should panic...
+    state.sender = Some(sender);
+    state.grace
+  }
+
+  fn set_stopped(&self) {
+    let mut state = self.state.0.lock().unwrap(); // poison errors should panic...
+    state.sender = None;
+    state.stopped = true;
+    self.state.1.notify_all();
+  }
+}
+
 #[cfg(test)]
 mod server_tests {
   use std::thread;
@@ -452,6 +972,29 @@ mod server_tests {
     //    assert!(server_result.is_ok(), "server failed: {:?}", server_result);
   }
 
+  #[test]
+  fn test_server_axfr_udp() {
+    use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127,0,0,1), 0));
+    let udp_socket = UdpSocket::bound(&addr).unwrap();
+    let ipaddr = udp_socket.local_addr().unwrap();
+
+    thread::Builder::new().name("test_server:axfr_udp:server".to_string()).spawn(move || server_thread_udp(udp_socket)).unwrap();
+
+    let request = MessageBuilder::query(Name::parse("example.com.", None).unwrap(), RecordType::AXFR).build();
+    let mut bytes: Vec<u8> = Vec::new();
+    request.emit(&mut BinEncoder::new(&mut bytes)).unwrap();
+
+    // the zone is not sent, the client should retry over TCP
+    let mut client_conn = UdpClientConnection::new(ipaddr).unwrap();
+    let response = Message::read(&mut BinDecoder::new(&client_conn.send(bytes).unwrap())).unwrap();
+    assert_eq!(response.get_id(), request.get_id());
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_truncated());
+    assert!(response.get_answers().is_empty());
+  }
+
   #[test]
   #[ignore]
   fn test_server_www_tcp() {
@@ -475,6 +1018,54 @@ mod server_tests {
     //    assert!(server_result.is_ok(), "server failed: {:?}", server_result);
   }
 
+  #[test]
+  #[ignore]
+  fn test_server_tcp_max_requests() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127,0,0,1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let ipaddr = tcp_listener.local_addr().unwrap();
+
+    thread::Builder::new().name("test_server:tcp_max:server".to_string()).spawn(move || {
+      let mut server = Server::new(new_catalog());
+      server.set_max_tcp_requests(Some(1));
+      server.register_listener(tcp_listener);
+      server.listen().unwrap();
+    }).unwrap();
+
+    let name = Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
+    let client = Client::new(TcpClientConnection::new(ipaddr).unwrap());
+
+    assert!(client.query(&name, DNSClass::IN, RecordType::A).is_ok());
+    // the server will have closed the connection after the first response
+    assert!(client.query(&name, DNSClass::IN, RecordType::A).is_err());
+  }
+
+  #[test]
+  fn test_server_shutdown() {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127,0,0,1), 0));
+    let udp_socket = UdpSocket::bound(&addr).unwrap();
+    let ipaddr = udp_socket.local_addr().unwrap();
+
+    let (handle_sender, handle_receiver) = mpsc::channel();
+    let server_thread = thread::Builder::new().name("test_server:shutdown:server".to_string()).spawn(move || {
+      let mut server = Server::new(new_catalog());
+      server.register_socket(udp_socket);
+      handle_sender.send(server.shutdown_handle()).unwrap();
+      server.listen()
+    }).unwrap();
+    let shutdown = handle_receiver.recv().unwrap();
+
+    client_thread_www(UdpClientConnection::new(ipaddr).unwrap());
+    assert!(!shutdown.is_stopped());
+
+    shutdown.shutdown(Duration::from_secs(1));
+    assert!(shutdown.wait_timeout(Duration::from_secs(5)));
+    assert!(server_thread.join().unwrap().is_ok());
+  }
+
   #[allow(dead_code)]
   fn client_thread_www<C: ClientConnection>(conn: C) {
     let name = Name::with_labels(vec!["www".to_string(), "example".to_string(), "com".to_string()]);
@@ -510,7 +1101,7 @@ mod server_tests {
     let example = create_example();
     let origin = example.get_origin().clone();
 
-    let mut catalog: Catalog = Catalog::new();
+    let catalog: Catalog = Catalog::new();
     catalog.upsert(origin.clone(), example);
     catalog
   }
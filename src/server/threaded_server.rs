@@ -0,0 +1,179 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::io;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use mio::tcp::TcpListener;
+use mio::udp::UdpSocket;
+
+use super::{Server, ShutdownHandle};
+
+/// Runs a `Server` on each of a number of threads, e.g. one per core, as a single event loop is
+///  limited to a single core.
+///
+/// The catalog is sharded, each thread answers from its own `Catalog`, built on that thread by
+///  the function which creates its `Server`, as the zones may have journals and forwarders which
+///  can not be shared between threads. Each thread listens on a clone of each of the sockets, the
+///  kernel hands each request to one of them.
+///
+/// Dynamic updates and zone transfers into secondary zones only change the catalog of the thread
+///  which performed them, so zones which allow updates, and secondary zones, should be served by
+///  a single threaded `Server`, e.g. on a separate address.
+///
+/// ```rust,ignore
+/// let server = try!(ThreadedServer::spawn(4, vec![udp_socket], vec![tcp_listener], |_| {
+///   Server::new(load_catalog())
+/// }));
+///
+/// server.shutdown(Duration::from_secs(5));
+/// try!(server.join());
+/// ```
+pub struct ThreadedServer {
+  threads: Vec<JoinHandle<io::Result<()>>>,
+  shutdown_handles: Vec<ShutdownHandle>,
+}
+
+impl ThreadedServer {
+  /// Spawns the threads, each of which listens with the `Server` created for it
+  ///
+  /// # Arguments
+  ///
+  /// * `threads` - the number of threads, at least one is spawned
+  /// * `sockets` - the UDP sockets, a clone of each is registered with each `Server`
+  /// * `listeners` - the TCP listeners, a clone of each is registered with each `Server`
+  /// * `new_server` - creates the `Server` of each thread, on that thread, given the index of the
+  ///                  thread
+  pub fn spawn<F>(threads: usize, sockets: Vec<UdpSocket>, listeners: Vec<TcpListener>, new_server: F) -> io::Result<Self>
+  where F: Fn(usize) -> Server + Send + Sync + 'static {
+    let new_server = Arc::new(new_server);
+    let threads = if threads == 0 { 1 } else { threads };
+
+    let mut handles: Vec<JoinHandle<io::Result<()>>> = Vec::with_capacity(threads);
+    let mut shutdown_handles: Vec<ShutdownHandle> = Vec::with_capacity(threads);
+
+    for index in 0..threads {
+      match Self::spawn_thread(index, &sockets, &listeners, new_server.clone()) {
+        Ok((thread, shutdown_handle)) => {
+          handles.push(thread);
+          shutdown_handles.push(shutdown_handle);
+        },
+        Err(error) => {
+          // stop the threads which did start
+          for shutdown_handle in &shutdown_handles { shutdown_handle.shutdown(Duration::from_secs(0)); }
+          return Err(error)
+        },
+      }
+    }
+
+    info!("listening on {} threads", threads);
+    Ok(ThreadedServer{ threads: handles, shutdown_handles: shutdown_handles })
+  }
+
+  fn spawn_thread<F>(index: usize, sockets: &[UdpSocket], listeners: &[TcpListener], new_server: Arc<F>)
+                     -> io::Result<(JoinHandle<io::Result<()>>, ShutdownHandle)> where F: Fn(usize) -> Server + Send + Sync + 'static {
+    let mut thread_sockets: Vec<UdpSocket> = Vec::with_capacity(sockets.len());
+    for socket in sockets { thread_sockets.push(try!(socket.try_clone())); }
+    let mut thread_listeners: Vec<TcpListener> = Vec::with_capacity(listeners.len());
+    for listener in listeners { thread_listeners.push(try!(listener.try_clone())); }
+
+    let (handle_sender, handle_receiver) = mpsc::channel();
+    let thread = try!(thread::Builder::new().name(format!("trust-dns:server:{}", index)).spawn(move || {
+      let mut server = new_server(index);
+      for socket in thread_sockets { server.register_socket(socket); }
+      for listener in thread_listeners { server.register_listener(listener); }
+
+      handle_sender.send(server.shutdown_handle()).unwrap_or_else(|_| warn!("server {} was dropped while starting", index));
+      server.listen()
+    }));
+
+    // the server is created on its thread, the handle is only missing if creating it panicked
+    match handle_receiver.recv() {
+      Ok(shutdown_handle) => Ok((thread, shutdown_handle)),
+      Err(_) => Err(io::Error::new(io::ErrorKind::Other, format!("server thread {} failed to start", index))),
+    }
+  }
+
+  /// The number of threads
+  pub fn get_threads(&self) -> usize {
+    self.threads.len()
+  }
+
+  /// Shuts down each of the servers, see `ShutdownHandle::shutdown()`
+  pub fn shutdown(&self, grace: Duration) {
+    for shutdown_handle in &self.shutdown_handles {
+      shutdown_handle.shutdown(grace);
+    }
+  }
+
+  /// Waits for each of the threads to finish, returning the first error of any of the servers
+  pub fn join(self) -> io::Result<()> {
+    let mut result: io::Result<()> = Ok(());
+
+    for thread in self.threads {
+      let thread_result = thread.join().unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "server thread panicked")));
+      if let Err(error) = thread_result {
+        if result.is_ok() { result = Err(error); }
+      }
+    }
+
+    result
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+  use std::time::Duration;
+
+  use mio::udp::UdpSocket;
+
+  use ::authority::Catalog;
+  use ::authority::authority_tests::create_example;
+  use ::client::Client;
+  use ::op::ResponseCode;
+  use ::rr::{DNSClass, Name, RecordType};
+  use ::server::Server;
+  use ::udp::UdpClientConnection;
+  use super::*;
+
+  #[test]
+  fn test_threaded_server() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127,0,0,1), 0));
+    let udp_socket = UdpSocket::bound(&addr).unwrap();
+    let ipaddr = udp_socket.local_addr().unwrap();
+
+    let server = ThreadedServer::spawn(2, vec![udp_socket], vec![], |_| {
+      let example = create_example();
+      let catalog = Catalog::new();
+      catalog.upsert(example.get_origin().clone(), example);
+      Server::new(catalog)
+    }).unwrap();
+    assert_eq!(server.get_threads(), 2);
+
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let client = Client::new(UdpClientConnection::new(ipaddr).unwrap());
+    for _ in 0..4 {
+      let response = client.query(&name, DNSClass::IN, RecordType::A).unwrap();
+      assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    }
+
+    server.shutdown(Duration::from_secs(1));
+    assert!(server.join().is_ok());
+  }
+}
@@ -0,0 +1,153 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::path::PathBuf;
+
+use ::authority::Catalog;
+use ::error::*;
+use ::rr::Name;
+
+/// Writes the zones of the `Catalog` which have been updated back to their zone files, and
+///  truncates their journals, see `Authority::checkpoint()`, so that the journals only grow with
+///  the updates between checkpoints.
+///
+/// Once the server restarts the zone files are loaded, and the updates since the last checkpoint
+///  are replayed from the journals, see `Authority::replay_journal()`.
+///
+/// ```rust,ignore
+/// let mut checkpointer = ZoneCheckpointer::new();
+/// checkpointer.watch(origin, PathBuf::from("/var/named/example.com.zone"));
+///
+/// // the updated zones are checkpointed every hour
+/// server.set_zone_checkpointer(checkpointer, 3_600_000);
+/// ```
+pub struct ZoneCheckpointer {
+  zones: Vec<(Name, PathBuf)>,
+}
+
+impl ZoneCheckpointer {
+  pub fn new() -> Self {
+    ZoneCheckpointer{ zones: Vec::new() }
+  }
+
+  /// Checkpoints a zone of the catalog, which has a journal, to its zone file
+  ///
+  /// # Arguments
+  ///
+  /// * `origin` - the name of the zone in the catalog
+  /// * `path` - the zone file the zone was loaded from
+  pub fn watch(&mut self, origin: Name, path: PathBuf) -> &mut Self {
+    self.zones.push((origin, path));
+    self
+  }
+
+  /// Checkpoints the zone if it has been updated since its last checkpoint
+  ///
+  /// Returns false if the zone has not been updated, or is not watched or not in the catalog.
+  pub fn checkpoint(&self, catalog: &Catalog, origin: &Name) -> PersistenceResult<bool> {
+    let path: &PathBuf = match self.zones.iter().find(|&&(ref zone, _)| zone == origin) {
+      Some(&(_, ref path)) => path,
+      None => return Ok(false),
+    };
+
+    let authority = match catalog.get(origin) {
+      Some(authority) => authority,
+      None => return Ok(false),
+    };
+
+    // updates wait for the zone to be written
+    let authority = authority.read().unwrap(); // poison errors should panic...
+    match authority.get_journal() {
+      Some(journal) => if try!(journal.select_last_serial()).is_none() { return Ok(false) },
+      None => return Ok(false),
+    }
+
+    try!(authority.checkpoint(path));
+    Ok(true)
+  }
+
+  /// Checkpoints each of the zones which has been updated, returns the zones which were
+  ///  checkpointed, failures are logged
+  pub fn poll(&mut self, catalog: &Catalog) -> Vec<Name> {
+    let origins: Vec<Name> = self.zones.iter().map(|&(ref origin, _)| origin.clone()).collect();
+    origins.into_iter().filter(|origin| {
+      match self.checkpoint(catalog, origin) {
+        Ok(checkpointed) => checkpointed,
+        Err(e) => { warn!("could not checkpoint zone {}: {}", origin, e); false },
+      }
+    }).collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::env;
+  use std::fs::{self, File};
+  use std::io::Read;
+  use std::net::Ipv4Addr;
+
+  use rusqlite::Connection;
+
+  use ::authority::{Catalog, Journal, UpdatePolicy};
+  use ::authority::authority_tests::create_example;
+  use ::op::Message;
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use super::*;
+
+  #[test]
+  fn test_poll() {
+    let dir = env::temp_dir().join("trust_dns_test_zone_checkpointer");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("example.com.zone");
+    fs::remove_file(&path).ok();
+
+    let mut journal = Journal::new(Connection::open_in_memory().unwrap()).unwrap();
+    journal.schema_up().unwrap();
+
+    let mut authority = create_example();
+    authority.set_allow_update(true);
+    let mut policy = UpdatePolicy::new();
+    policy.allow_unsigned(true);
+    authority.set_update_policy(policy);
+    authority.journal(journal);
+
+    let origin = authority.get_origin().clone();
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), authority);
+
+    let mut checkpointer = ZoneCheckpointer::new();
+    checkpointer.watch(origin.clone(), path.clone());
+
+    // nothing has been updated
+    assert!(checkpointer.poll(&catalog).is_empty());
+    assert!(!path.exists());
+
+    let mut message = Message::new();
+    message.add_update(Record::new().name(Name::parse("new.example.com.", None).unwrap()).ttl(86400).rr_type(RecordType::A)
+                                    .dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(10,11,12,13))).clone());
+    assert!(catalog.get(&origin).unwrap().write().unwrap().update(&message).unwrap());
+
+    assert_eq!(checkpointer.poll(&catalog), vec![origin.clone()]);
+    let mut text = String::new();
+    File::open(&path).unwrap().read_to_string(&mut text).unwrap();
+    assert!(text.contains("\nnew "), "{}", text);
+
+    // the journal was truncated
+    assert!(checkpointer.poll(&catalog).is_empty());
+    assert!(!checkpointer.checkpoint(&catalog, &Name::parse("example.net.", None).unwrap()).unwrap());
+
+    fs::remove_file(&path).ok();
+  }
+}
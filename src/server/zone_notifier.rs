@@ -0,0 +1,251 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime};
+
+use ::authority::{Authority, Catalog, ZoneType};
+use ::client::Client;
+use ::error::*;
+use ::op::ResponseCode;
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+use ::udp::UdpClientConnection;
+
+/// NOTIFYs the secondaries of the zones in the `Catalog` when the serial of a zone changes, e.g.
+///  after a dynamic update, a reload or re-signing, so that they refresh the zone without waiting
+///  for its refresh interval.
+///
+/// The secondaries are the name servers of the zone, other than the primary named in the SOA,
+///  whose addresses are in the zone, and the `also_notify` servers of the zone. Zones are notified
+///  when first seen, i.e. after the server starts. A NOTIFY which fails is retried, the interval
+///  doubling after each attempt, up to the maximum number of attempts.
+///
+/// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+///
+/// ```text
+/// 3.6. If no response is received, the master should retransmit the NOTIFY
+///    request, with an interval which increases with each retry.  A
+///    NOTIFY request is considered complete when a NOTIFY response is
+///    received, or the retry count is exhausted.
+/// ```
+///
+/// ```rust,ignore
+/// // the server checks for changed zones every second
+/// server.set_zone_notifier(ZoneNotifier::new(), 1000);
+/// ```
+pub struct ZoneNotifier {
+  serials: HashMap<Name, u32>,
+  pending: Vec<PendingNotify>,
+  retry_interval: Duration,
+  max_attempts: usize,
+  timeout: Duration,
+}
+
+struct PendingNotify {
+  origin: Name,
+  dns_class: DNSClass,
+  soa: Record,
+  secondary: SocketAddr,
+  attempts: usize,
+  next_attempt: SystemTime,
+}
+
+impl ZoneNotifier {
+  pub fn new() -> Self {
+    ZoneNotifier{ serials: HashMap::new(), pending: Vec::new(), retry_interval: Duration::from_secs(5),
+                  max_attempts: 5, timeout: Duration::from_secs(2) }
+  }
+
+  /// The interval before the first retry of a failed NOTIFY, it doubles with each retry. The
+  ///  default is 5 seconds.
+  pub fn retry_interval(&mut self, retry_interval: Duration) -> &mut Self {
+    self.retry_interval = retry_interval;
+    self
+  }
+
+  /// The number of times a NOTIFY is sent before the secondary is given up on, default 5
+  pub fn max_attempts(&mut self, max_attempts: usize) -> &mut Self {
+    self.max_attempts = max_attempts;
+    self
+  }
+
+  /// How long to wait for the response of a secondary, default 2 seconds
+  pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Queues NOTIFYs for the zones which have changed, and sends those which are due, returns the
+  ///  number of NOTIFYs which were answered
+  pub fn poll(&mut self, catalog: &Catalog) -> usize {
+    let now = SystemTime::now();
+    self.queue_changed(catalog, now);
+    self.send_due(now)
+  }
+
+  /// queues a NOTIFY to each secondary of the zones whose serials have changed
+  fn queue_changed(&mut self, catalog: &Catalog, now: SystemTime) {
+    for origin in catalog.origins() {
+      let authority = match catalog.get(&origin) {
+        Some(authority) => authority,
+        None => continue,
+      };
+      let authority = authority.read().unwrap(); // poison errors should panic...
+
+      match authority.get_zone_type() {
+        ZoneType::Master | ZoneType::Slave => (),
+        _ => continue,
+      }
+
+      let soa: Record = if let Some(soa) = authority.get_soa() { soa.clone() } else { continue };
+      let serial = if let &RData::SOA(ref soa) = soa.get_rdata() { soa.get_serial() } else { continue };
+      if self.serials.get(&origin) == Some(&serial) { continue }
+
+      self.serials.insert(origin.clone(), serial);
+
+      // a newer serial supersedes the NOTIFYs of the older one
+      self.pending.retain(|pending| pending.origin != origin);
+
+      let secondaries = Self::secondaries(&authority);
+      info!("notifying {} secondaries of zone {} at serial {}", secondaries.len(), origin, serial);
+      for secondary in secondaries {
+        self.pending.push(PendingNotify{ origin: origin.clone(), dns_class: authority.get_class(), soa: soa.clone(),
+                                         secondary: secondary, attempts: 0, next_attempt: now });
+      }
+    }
+  }
+
+  /// sends the NOTIFYs which are due, rescheduling those which fail
+  fn send_due(&mut self, now: SystemTime) -> usize {
+    let mut answered = 0;
+    let pending = ::std::mem::replace(&mut self.pending, Vec::new());
+
+    for mut notify in pending {
+      if notify.next_attempt > now {
+        self.pending.push(notify);
+        continue
+      }
+
+      notify.attempts += 1;
+      match self.send(&notify) {
+        Ok(()) => {
+          debug!("notified {} of zone {}", notify.secondary, notify.origin);
+          answered += 1;
+        },
+        Err(e) if notify.attempts < self.max_attempts => {
+          debug!("notify of {} to {} failed, attempt {}: {}", notify.origin, notify.secondary, notify.attempts, e);
+          notify.next_attempt = now + self.retry_interval * (1 << (notify.attempts - 1)) as u32;
+          self.pending.push(notify);
+        },
+        Err(e) => warn!("giving up notifying {} of zone {} after {} attempts: {}", notify.secondary, notify.origin, notify.attempts, e),
+      }
+    }
+
+    answered
+  }
+
+  fn send(&self, notify: &PendingNotify) -> ClientResult<()> {
+    let client = Client::new(try!(UdpClientConnection::with_timeout(notify.secondary, self.timeout)));
+    let response = try!(client.notify(&notify.origin, notify.dns_class, Some(notify.soa.clone())));
+
+    match response.get_response_code() {
+      ResponseCode::NoError => Ok(()),
+      code => Err(ClientErrorKind::Msg(format!("notify was answered with {:?}", code)).into()),
+    }
+  }
+
+  /// the addresses of the name servers of the zone, other than the primary in the SOA, which are
+  ///  in the zone, and the also_notify servers
+  fn secondaries(authority: &Authority) -> Vec<SocketAddr> {
+    let mname: Option<&Name> = authority.get_soa().and_then(|soa| if let &RData::SOA(ref soa) = soa.get_rdata() { Some(soa.get_mname()) } else { None });
+
+    let mut secondaries: Vec<SocketAddr> = Vec::new();
+    for ns in authority.get_ns(false) {
+      let name: &Name = if let &RData::NS(ref name) = ns.get_rdata() { name } else { continue };
+      if Some(name) == mname { continue }
+
+      let addresses: Vec<IpAddr> = authority.lookup(name, RecordType::A, false).into_iter()
+                                            .chain(authority.lookup(name, RecordType::AAAA, false).into_iter())
+                                            .filter_map(|rr| match *rr.get_rdata() {
+                                              RData::A(address) => Some(IpAddr::V4(address)),
+                                              RData::AAAA(address) => Some(IpAddr::V6(address)),
+                                              _ => None,
+                                            })
+                                            .collect();
+
+      if addresses.is_empty() { debug!("no address in the zone {} for the name server: {}", authority.get_origin(), name) }
+      secondaries.extend(addresses.into_iter().map(|address| SocketAddr::new(address, 53)));
+    }
+
+    for secondary in authority.get_also_notify() {
+      if !secondaries.contains(secondary) { secondaries.push(*secondary); }
+    }
+
+    secondaries
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::{Ipv4Addr, SocketAddr};
+  use std::time::{Duration, SystemTime};
+
+  use ::authority::Catalog;
+  use ::authority::authority_tests::create_example;
+  use ::rr::{DNSClass, Name, RData, Record, RecordType};
+  use super::*;
+
+  #[test]
+  fn test_queue_changed() {
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+
+    // ns1 is a name server in the zone, the others of the example are not
+    let ns1 = Name::parse("ns1.example.com.", None).unwrap();
+    example.upsert(Record::new().name(origin.clone()).ttl(86400).rr_type(RecordType::NS).dns_class(DNSClass::IN).rdata(RData::NS(ns1.clone())).clone(), 0);
+    example.upsert(Record::new().name(ns1.clone()).ttl(86400).rr_type(RecordType::A).dns_class(DNSClass::IN).rdata(RData::A(Ipv4Addr::new(192,0,2,53))).clone(), 0);
+
+    let also_notify: SocketAddr = "192.0.2.54:5353".parse().unwrap();
+    example.set_also_notify(vec![also_notify]);
+
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let now = SystemTime::now();
+    let mut notifier = ZoneNotifier::new();
+    notifier.queue_changed(&catalog, now);
+
+    let secondaries: Vec<SocketAddr> = notifier.pending.iter().map(|pending| pending.secondary).collect();
+    assert_eq!(secondaries, vec!["192.0.2.53:53".parse().unwrap(), also_notify]);
+
+    // unchanged zones are not notified again
+    notifier.pending.clear();
+    notifier.queue_changed(&catalog, now);
+    assert!(notifier.pending.is_empty());
+
+    // the serial changed
+    catalog.get(&origin).unwrap().write().unwrap().secure_zone();
+    notifier.queue_changed(&catalog, now);
+    assert_eq!(notifier.pending.len(), 2);
+
+    // nothing is sent before it is due
+    for pending in notifier.pending.iter_mut() {
+      pending.next_attempt = now + Duration::from_secs(60);
+    }
+    assert_eq!(notifier.send_due(now), 0);
+    assert_eq!(notifier.pending.len(), 2);
+  }
+}
@@ -0,0 +1,216 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use ::authority::{Authority, Catalog, ZoneType};
+use ::client::{Client, IncrementalTransfer};
+use ::error::*;
+use ::rr::{DNSClass, Name, RData, Record};
+use ::server::CatalogZone;
+use ::tcp::TcpClientConnection;
+
+/// the refresh and retry intervals of a zone which does not yet have an SOA, in seconds
+const DEFAULT_REFRESH: u64 = 3600;
+const DEFAULT_RETRY: u64 = 600;
+
+/// Keeps the secondary zones of the `Catalog`, those of `ZoneType::Slave`, up to date with their
+///  primaries, see `Authority::set_primaries()`.
+///
+/// A zone is refreshed when the refresh interval of its SOA has passed, or as soon as one of its
+///  primaries sends a NOTIFY. The zone is transferred from the first primary which answers, with
+///  IXFR where the primary supports it. After a failure the zone is retried after the retry
+///  interval of its SOA. The transfers happen on the thread of `poll()`.
+///
+/// The secondary zones listed by catalog zones are added and removed after the catalog zones are
+///  refreshed, see `CatalogZone`.
+///
+/// ```rust,ignore
+/// // the server checks for notified and due zones every second
+/// server.set_zone_refresher(ZoneRefresher::new(), 1000);
+/// ```
+pub struct ZoneRefresher {
+  next_refresh: HashMap<Name, SystemTime>,
+  catalog_zones: Vec<CatalogZone>,
+}
+
+impl ZoneRefresher {
+  pub fn new() -> Self {
+    ZoneRefresher{ next_refresh: HashMap::new(), catalog_zones: Vec::new() }
+  }
+
+  /// Provisions the member zones of the catalog zone, which must be a secondary zone of the
+  ///  `Catalog`
+  pub fn add_catalog_zone(&mut self, catalog_zone: CatalogZone) -> &mut Self {
+    self.catalog_zones.push(catalog_zone);
+    self
+  }
+
+  /// Refreshes the secondary zones which were notified of a change, or whose refresh interval has
+  ///  passed, returns the zones which were updated, failures are logged
+  pub fn poll(&mut self, catalog: &Catalog) -> Vec<Name> {
+    let now = SystemTime::now();
+    let mut updated: Vec<Name> = Vec::new();
+
+    for origin in catalog.origins() {
+      let (refresh, retry) = {
+        let authority = match catalog.get(&origin) {
+          Some(authority) => authority,
+          None => continue,
+        };
+        let mut authority = authority.write().unwrap(); // poison errors should panic...
+
+        if authority.get_zone_type() != ZoneType::Slave { continue }
+
+        let notified = authority.take_notified();
+        if !notified && self.next_refresh.get(&origin).map_or(false, |next| *next > now) { continue }
+
+        Self::intervals(&authority)
+      };
+
+      match Self::refresh(catalog, &origin) {
+        Ok(true) => {
+          self.next_refresh.insert(origin.clone(), now + refresh);
+          updated.push(origin);
+        },
+        Ok(false) => { self.next_refresh.insert(origin, now + refresh); },
+        Err(e) => {
+          warn!("could not refresh zone {}: {}", origin, e);
+          self.next_refresh.insert(origin, now + retry);
+        },
+      }
+    }
+
+    // the added member zones are transferred on the next poll
+    for catalog_zone in &mut self.catalog_zones {
+      for member in catalog_zone.sync(catalog) {
+        self.next_refresh.remove(&member);
+      }
+    }
+
+    updated
+  }
+
+  /// Transfers the zone from the first of its primaries which answers, if its serial has changed
+  ///
+  /// Returns true if the zone was updated.
+  pub fn refresh(catalog: &Catalog, origin: &Name) -> ClientResult<bool> {
+    let (primaries, dns_class, soa): (Vec<SocketAddr>, DNSClass, Option<Record>) = {
+      let authority = match catalog.get(origin) {
+        Some(authority) => authority,
+        None => return Err(ClientErrorKind::Msg(format!("{} is not in the catalog", origin)).into()),
+      };
+      let authority = authority.read().unwrap(); // poison errors should panic...
+
+      (authority.get_primaries().to_vec(), authority.get_class(), authority.get_soa().cloned())
+    };
+
+    let mut error: ClientError = ClientErrorKind::Msg(format!("{} has no primaries", origin)).into();
+    for primary in primaries {
+      match Self::transfer(primary, origin, dns_class, soa.clone()) {
+        Ok(transfer) => return Self::apply(catalog, origin, transfer),
+        Err(e) => {
+          debug!("could not transfer {} from {}: {}", origin, primary, e);
+          error = e;
+        },
+      }
+    }
+
+    Err(error)
+  }
+
+  fn transfer(primary: SocketAddr, origin: &Name, dns_class: DNSClass, soa: Option<Record>) -> ClientResult<IncrementalTransfer> {
+    let client = Client::new(try!(TcpClientConnection::new(primary)));
+
+    match soa {
+      // the primary answers with only its SOA if the zone has not changed
+      Some(soa) => client.incremental_transfer(origin, dns_class, soa),
+      None => {
+        let mut records: Vec<Record> = try!(try!(client.zone_transfer(origin, dns_class)).collect());
+        if records.is_empty() { return Err(ClientErrorKind::Message("zone transfer contained no records").into()) }
+
+        let soa = records.remove(0);
+        Ok(IncrementalTransfer::Full{ soa: soa, records: records })
+      },
+    }
+  }
+
+  fn apply(catalog: &Catalog, origin: &Name, transfer: IncrementalTransfer) -> ClientResult<bool> {
+    let authority = match catalog.get(origin) {
+      Some(authority) => authority,
+      None => return Err(ClientErrorKind::Msg(format!("{} is not in the catalog", origin)).into()),
+    };
+
+    let mut authority = authority.write().unwrap(); // poison errors should panic...
+    match transfer {
+      IncrementalTransfer::UpToDate(..) => {
+        debug!("zone is up to date: {}", origin);
+        Ok(false)
+      },
+      IncrementalTransfer::Incremental{ diffs, .. } => {
+        if authority.apply_diffs(&diffs) {
+          info!("transferred {} diffs of zone: {}", diffs.len(), origin);
+          Ok(true)
+        } else {
+          Err(ClientErrorKind::Msg(format!("diffs of {} could not be applied", origin)).into())
+        }
+      },
+      IncrementalTransfer::Full{ soa, records } => {
+        let mut zone = Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Slave, false);
+        let serial = if let &RData::SOA(ref soa_data) = soa.get_rdata() { soa_data.get_serial() } else { 0 };
+        zone.upsert(soa, serial);
+        for record in records {
+          zone.upsert(record, serial);
+        }
+
+        try!(authority.reload(zone).map_err(|e| ClientErrorKind::Msg(format!("error replacing zone {}: {}", origin, e))));
+        info!("transferred zone: {}", origin);
+        Ok(true)
+      },
+    }
+  }
+
+  /// the refresh and retry intervals of the SOA of the zone
+  fn intervals(authority: &Authority) -> (Duration, Duration) {
+    match authority.get_soa().map(|soa| soa.get_rdata()) {
+      Some(&RData::SOA(ref soa)) => (Duration::from_secs(soa.get_refresh() as u64), Duration::from_secs(soa.get_retry() as u64)),
+      _ => (Duration::from_secs(DEFAULT_REFRESH), Duration::from_secs(DEFAULT_RETRY)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+
+  use ::authority::{Authority, Catalog, ZoneType};
+  use ::rr::Name;
+  use super::*;
+
+  #[test]
+  fn test_poll_without_primaries() {
+    let origin = Name::parse("example.com.", None).unwrap();
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Slave, false));
+
+    // the failure is retried later, not on every poll
+    let mut refresher = ZoneRefresher::new();
+    assert!(refresher.poll(&catalog).is_empty());
+    assert!(refresher.next_refresh.get(&origin).is_some());
+    assert!(ZoneRefresher::refresh(&catalog, &origin).is_err());
+  }
+}
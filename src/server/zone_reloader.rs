@@ -0,0 +1,226 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::SystemTime;
+
+use ::authority::{Catalog, ZoneType};
+use ::error::*;
+use ::rr::Name;
+use ::serialize::txt::Parser;
+
+/// Re-reads zone files and swaps the new records into the `Catalog`
+///
+/// Reloads happen on request, or when the modification time of a watched zone file changes.
+///  Queries in flight are answered from the previous records, a zone file which fails to parse
+///  leaves the served zone untouched.
+///
+/// ```rust,ignore
+/// let mut reloader = ZoneReloader::new();
+/// reloader.watch(origin, PathBuf::from("/var/named/example.com.zone"));
+///
+/// // e.g. for a SIGHUP handler on another thread
+/// let handle = reloader.handle();
+///
+/// // the server checks the zone files and the handle every 5 seconds
+/// server.set_zone_reloader(reloader, 5000);
+/// handle.reload_all();
+/// ```
+pub struct ZoneReloader {
+  zones: Vec<WatchedZone>,
+  sender: Sender<Option<Name>>,
+  receiver: Receiver<Option<Name>>,
+}
+
+struct WatchedZone {
+  origin: Name,
+  path: PathBuf,
+  modified: Option<SystemTime>,
+}
+
+impl ZoneReloader {
+  pub fn new() -> Self {
+    let (sender, receiver) = mpsc::channel();
+    ZoneReloader{ zones: Vec::new(), sender: sender, receiver: receiver }
+  }
+
+  /// Watches the zone file of a zone in the catalog, the current modification time of the file
+  ///  is taken as that of the loaded zone
+  ///
+  /// # Arguments
+  ///
+  /// * `origin` - the name of the zone in the catalog
+  /// * `path` - the zone file, `$INCLUDE`s are relative to its directory
+  pub fn watch(&mut self, origin: Name, path: PathBuf) -> &mut Self {
+    let modified = modified(&path);
+    self.zones.push(WatchedZone{ origin: origin, path: path, modified: modified });
+    self
+  }
+
+  /// A handle to request reloads from other threads, requests are handled on the next `poll()`
+  pub fn handle(&self) -> ReloadHandle {
+    ReloadHandle{ sender: self.sender.clone() }
+  }
+
+  /// Re-reads the zone file of the zone and swaps it into the catalog
+  ///
+  /// Returns false if the zone is not watched or not in the catalog.
+  pub fn reload(&mut self, catalog: &Catalog, origin: &Name) -> ParseResult<bool> {
+    let zone = match self.zones.iter_mut().find(|z| &z.origin == origin) {
+      Some(zone) => zone,
+      None => return Ok(false),
+    };
+
+    // a file which fails to parse is retried on its next change
+    zone.modified = modified(&zone.path);
+
+    let authority = try!(Parser::parse_path(&zone.path, Some(zone.origin.clone()), ZoneType::Master, false));
+    if authority.get_origin() != &zone.origin {
+      return Err(ParseErrorKind::Msg(format!("{:?} is the zone {}, not {}", zone.path, authority.get_origin(), zone.origin)).into())
+    }
+
+    catalog.reload(authority).map_err(|e| ParseErrorKind::Msg(format!("error reloading {}: {}", zone.origin, e)).into())
+  }
+
+  /// Reloads every watched zone, returns the zones which were reloaded, failures are logged
+  pub fn reload_all(&mut self, catalog: &Catalog) -> Vec<Name> {
+    let origins: Vec<Name> = self.zones.iter().map(|z| z.origin.clone()).collect();
+    self.reload_each(catalog, origins)
+  }
+
+  /// Reloads the zones whose files have been modified since they were last loaded, returns the
+  ///  zones which were reloaded, failures are logged
+  pub fn reload_changed(&mut self, catalog: &Catalog) -> Vec<Name> {
+    let origins: Vec<Name> = self.zones.iter()
+                                       .filter(|z| modified(&z.path) != z.modified)
+                                       .map(|z| z.origin.clone())
+                                       .collect();
+    self.reload_each(catalog, origins)
+  }
+
+  /// Handles the requests of the handles, then reloads the changed zones, returns the zones which
+  ///  were reloaded
+  pub fn poll(&mut self, catalog: &Catalog) -> Vec<Name> {
+    let mut reloaded: Vec<Name> = Vec::new();
+    while let Ok(request) = self.receiver.try_recv() {
+      match request {
+        Some(origin) => reloaded.extend(self.reload_each(catalog, vec![origin])),
+        None => reloaded.extend(self.reload_all(catalog)),
+      }
+    }
+
+    reloaded.extend(self.reload_changed(catalog));
+    reloaded
+  }
+
+  fn reload_each(&mut self, catalog: &Catalog, origins: Vec<Name>) -> Vec<Name> {
+    origins.into_iter().filter(|origin| {
+      match self.reload(catalog, origin) {
+        Ok(reloaded) => reloaded,
+        Err(e) => { warn!("could not reload zone {}: {}", origin, e); false },
+      }
+    }).collect()
+  }
+}
+
+/// Requests reloads from a `ZoneReloader`, e.g. from a signal handler, see `ZoneReloader::handle()`
+#[derive(Clone)]
+pub struct ReloadHandle {
+  sender: Sender<Option<Name>>,
+}
+
+impl ReloadHandle {
+  /// Requests that the zone be reloaded from its file
+  pub fn reload(&self, origin: Name) {
+    self.sender.send(Some(origin)).unwrap_or_else(|_| warn!("zone reloader has been dropped"));
+  }
+
+  /// Requests that all watched zones be reloaded from their files
+  pub fn reload_all(&self) {
+    self.sender.send(None).unwrap_or_else(|_| warn!("zone reloader has been dropped"));
+  }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod test {
+  use std::env;
+  use std::fs::{self, File};
+  use std::io::Write;
+  use std::net::Ipv4Addr;
+  use std::path::Path;
+
+  use ::authority::{Catalog, ZoneType};
+  use ::op::{Message, Query};
+  use ::rr::{Name, RData, RecordType};
+  use ::serialize::txt::Parser;
+  use super::*;
+
+  fn write_zone(path: &Path, address: &str) {
+    File::create(path).unwrap().write_all(format!("$ORIGIN example.com.
+@   3600 IN SOA ns1 hostmaster 2016100101 1h 15m 1w 5m
+www      A      {}
+", address).as_bytes()).unwrap();
+  }
+
+  fn lookup_www(catalog: &Catalog) -> RData {
+    let mut query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+    let mut question = Message::new();
+    question.add_query(query);
+
+    catalog.lookup(&question).get_answers()[0].get_rdata().clone()
+  }
+
+  #[test]
+  fn test_reload() {
+    let dir = env::temp_dir().join("trust_dns_test_reload");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("example.com.zone");
+    write_zone(&path, "192.0.2.1");
+
+    let origin = Name::parse("example.com.", None).unwrap();
+    let catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Parser::parse_path(&path, None, ZoneType::Master, false).unwrap());
+
+    let mut reloader = ZoneReloader::new();
+    reloader.watch(origin.clone(), path.clone());
+    assert!(reloader.poll(&catalog).is_empty());
+
+    write_zone(&path, "192.0.2.2");
+    // the modification time may not have changed within its resolution
+    reloader.zones[0].modified = None;
+    assert_eq!(reloader.reload_changed(&catalog), vec![origin.clone()]);
+    assert_eq!(lookup_www(&catalog), RData::A(Ipv4Addr::new(192,0,2,2)));
+
+    // requested through the handle
+    write_zone(&path, "192.0.2.3");
+    reloader.handle().reload(origin.clone());
+    assert_eq!(reloader.poll(&catalog)[0], origin);
+    assert_eq!(lookup_www(&catalog), RData::A(Ipv4Addr::new(192,0,2,3)));
+
+    // a broken zone file leaves the zone in place
+    File::create(&path).unwrap().write_all(b"www A not-an-address\n").unwrap();
+    assert!(reloader.reload(&catalog, &origin).is_err());
+    assert_eq!(lookup_www(&catalog), RData::A(Ipv4Addr::new(192,0,2,3)));
+
+    assert!(!reloader.reload(&catalog, &Name::parse("example.net.", None).unwrap()).unwrap());
+  }
+}
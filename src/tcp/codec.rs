@@ -0,0 +1,224 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::io;
+use std::io::Read;
+use std::mem;
+
+/// The largest message which can be framed by the two byte length
+pub const MAX_MESSAGE_LENGTH: usize = 65535;
+
+/// The framing of DNS messages over streams, RFC 1035 4.2.2, each message is prefixed by its
+///  length as two bytes in network order. Used by the TCP and TLS connections of the client, and
+///  the TCP listeners of the server.
+///
+/// A message may arrive over many reads, the state of a message which has been partially read is
+///  kept until the rest of it arrives, e.g. after a read on a non-blocking stream returned
+///  `WouldBlock`.
+#[derive(Debug)]
+pub struct DnsMessageCodec {
+  max_read_length: usize,
+  max_write_length: usize,
+  length: [u8; 2],
+  length_read: usize,
+  buffer: Vec<u8>,
+}
+
+impl DnsMessageCodec {
+  /// A codec which accepts messages of any length which can be framed
+  pub fn new() -> Self {
+    Self::with_max_length(MAX_MESSAGE_LENGTH)
+  }
+
+  /// A codec which rejects messages longer than `max_length`, in either direction
+  pub fn with_max_length(max_length: usize) -> Self {
+    DnsMessageCodec{ max_read_length: max_length, max_write_length: max_length, length: [0u8; 2], length_read: 0, buffer: Vec::new() }
+  }
+
+  pub fn get_max_read_length(&self) -> usize {
+    self.max_read_length
+  }
+
+  /// Limits the messages which are read, e.g. the requests of a server, without limiting the
+  ///  messages which are written, e.g. the responses of zone transfers
+  pub fn set_max_read_length(&mut self, max_length: usize) {
+    self.max_read_length = max_length;
+  }
+
+  pub fn get_max_write_length(&self) -> usize {
+    self.max_write_length
+  }
+
+  pub fn set_max_write_length(&mut self, max_length: usize) {
+    self.max_write_length = max_length;
+  }
+
+  /// The length of the message being read, if its length prefix has been read
+  pub fn get_pending_length(&self) -> Option<u16> {
+    if self.length_read < 2 { return None }
+    Some((self.length[0] as u16) << 8 | self.length[1] as u16)
+  }
+
+  /// true if nothing of the next message has been read
+  pub fn is_idle(&self) -> bool {
+    self.length_read == 0
+  }
+
+  /// discards any part of a message which has been read
+  pub fn reset(&mut self) {
+    self.length_read = 0;
+    self.buffer.clear();
+  }
+
+  /// Reads the next message from the stream
+  ///
+  /// # Return value
+  ///
+  /// The message without its length prefix. Errors of the reader are returned as is, if it is
+  ///  `WouldBlock` the read should be retried once the stream is readable, and continues where
+  ///  it left off. A message longer than the maximum read length is `InvalidData`, and the stream
+  ///  ending before the message is complete is `UnexpectedEof`.
+  pub fn read_message<R: Read>(&mut self, reader: &mut R) -> io::Result<Vec<u8>> {
+    while self.length_read < 2 {
+      let read = try!(read_some(reader, &mut self.length[self.length_read..]));
+      self.length_read += read;
+    }
+
+    let length = self.get_pending_length().expect("the length was read") as usize;
+    if length > self.max_read_length {
+      self.reset();
+      return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {} bytes exceeds the max length: {}", length, self.max_read_length)));
+    }
+
+    while self.buffer.len() < length {
+      let start = self.buffer.len();
+      self.buffer.resize(length, 0);
+      match read_some(reader, &mut self.buffer[start..]) {
+        Ok(read) => self.buffer.truncate(start + read),
+        Err(e) => {
+          self.buffer.truncate(start);
+          return Err(e)
+        },
+      }
+    }
+
+    self.length_read = 0;
+    Ok(mem::replace(&mut self.buffer, Vec::new()))
+  }
+
+  /// Frames the message with its length prefix, fails with `InvalidInput` if the message is
+  ///  longer than the maximum write length.
+  pub fn encode(&self, message: &[u8]) -> io::Result<Vec<u8>> {
+    if message.len() > self.max_write_length {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("message of {} bytes exceeds the max length: {}", message.len(), self.max_write_length)));
+    }
+
+    let mut framed = Vec::with_capacity(message.len() + 2);
+    framed.push((message.len() >> 8) as u8);
+    framed.push(message.len() as u8);
+    framed.extend_from_slice(message);
+    Ok(framed)
+  }
+}
+
+/// reads at least one byte, retrying if interrupted
+fn read_some<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+  loop {
+    match reader.read(buf) {
+      Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the stream ended within a message")),
+      Ok(read) => return Ok(read),
+      Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(e),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::VecDeque;
+  use std::io;
+  use std::io::Read;
+
+  use super::*;
+
+  /// returns each chunk on its own read, an empty chunk would block
+  struct Chunks(VecDeque<Vec<u8>>);
+
+  impl Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      let chunk = match self.0.pop_front() {
+        Some(chunk) => chunk,
+        None => return Ok(0),
+      };
+      if chunk.is_empty() { return Err(io::Error::new(io::ErrorKind::WouldBlock, "would block")) }
+
+      let read = ::std::cmp::min(buf.len(), chunk.len());
+      buf[..read].copy_from_slice(&chunk[..read]);
+      if read < chunk.len() { self.0.push_front(chunk[read..].to_vec()); }
+      Ok(read)
+    }
+  }
+
+  #[test]
+  fn test_read_partial() {
+    let mut codec = DnsMessageCodec::new();
+    let framed = codec.encode(&[1, 2, 3, 4, 5]).unwrap();
+    assert_eq!(framed, vec![0, 5, 1, 2, 3, 4, 5]);
+
+    // split within the length and within the message, with a second message behind it
+    let mut reader = Chunks(vec![vec![0], vec![], vec![5, 1, 2], vec![], vec![3, 4, 5, 0, 1, 9]].into_iter().collect());
+
+    assert_eq!(codec.read_message(&mut reader).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    assert!(!codec.is_idle());
+    assert_eq!(codec.get_pending_length(), None);
+
+    assert_eq!(codec.read_message(&mut reader).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(codec.get_pending_length(), Some(5));
+
+    assert_eq!(codec.read_message(&mut reader).unwrap(), vec![1, 2, 3, 4, 5]);
+    assert!(codec.is_idle());
+    assert_eq!(codec.read_message(&mut reader).unwrap(), vec![9]);
+    assert_eq!(codec.read_message(&mut reader).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+  }
+
+  #[test]
+  fn test_max_length() {
+    let mut codec = DnsMessageCodec::with_max_length(4);
+    assert_eq!(codec.encode(&[0; 5]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    let mut reader = io::Cursor::new(vec![0, 5, 1, 2, 3, 4, 5]);
+    assert_eq!(codec.read_message(&mut reader).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert!(codec.is_idle());
+
+    assert!(DnsMessageCodec::new().encode(&vec![0; MAX_MESSAGE_LENGTH + 1]).is_err());
+  }
+
+  #[test]
+  fn test_max_read_length() {
+    // a server limiting its requests still writes large responses, e.g. the messages of an AXFR
+    let mut codec = DnsMessageCodec::new();
+    codec.set_max_read_length(512);
+    assert_eq!(codec.get_max_write_length(), MAX_MESSAGE_LENGTH);
+
+    let framed = codec.encode(&vec![0; 16384]).unwrap();
+    assert_eq!(framed.len(), 16384 + 2);
+
+    let mut reader = io::Cursor::new(framed);
+    assert_eq!(codec.read_message(&mut reader).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    let mut reader = io::Cursor::new(codec.encode(&[0; 512]).unwrap());
+    assert_eq!(codec.read_message(&mut reader).unwrap().len(), 512);
+  }
+}
@@ -13,18 +13,24 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use std::collections::VecDeque;
 use std::io;
-use std::io::{Write, Read};
+use std::io::Write;
 use std::mem;
 
 use mio::tcp::TcpStream;
 use mio::EventSet; // not * b/c don't want confusion with std::net
 
+use ::tcp::DnsMessageCodec;
+
 pub struct TcpHandler {
   tcp_type: TcpType,
   state: TcpState,   // current state of the handler and stream, i.e. are we reading from the client? or writing back to it?
   buffer: Vec<u8>, // current location and buffer we are reading into or writing from
   stream: TcpStream,
+  codec: DnsMessageCodec, // the length framing, holds a message which has been partially read
+  request_count: usize, // number of completed request/response exchanges on this stream
+  pending: VecDeque<Vec<u8>>, // further messages of a multiple message response, e.g. AXFR
 }
 
 impl TcpHandler {
@@ -39,13 +45,25 @@ impl TcpHandler {
   }
 
   fn new(tcp_type: TcpType, state: TcpState, buffer: Vec<u8>, stream: TcpStream) -> Self {
-    TcpHandler{ tcp_type: tcp_type, state: state, buffer: buffer, stream: stream }
+    TcpHandler{ tcp_type: tcp_type, state: state, buffer: buffer, stream: stream, codec: DnsMessageCodec::new(), request_count: 0,
+                pending: VecDeque::new() }
   }
 
   pub fn get_stream(&self) -> &TcpStream {
     &self.stream
   }
 
+  /// messages read which are longer than this are rejected, and the stream should be closed, the
+  ///  messages written are not limited, e.g. the responses of a server
+  pub fn set_max_read_length(&mut self, max_length: usize) {
+    self.codec.set_max_read_length(max_length);
+  }
+
+  /// the number of request/response exchanges which have completed on this stream
+  pub fn get_request_count(&self) -> usize {
+    self.request_count
+  }
+
   pub fn get_events(&self) -> EventSet {
     Self::get_events_recurse(self.state, self.tcp_type)
   }
@@ -61,6 +79,14 @@ impl TcpHandler {
     return buf;
   }
 
+  /// for servers, responds with multiple messages, e.g. a zone transfer, each is written with its
+  ///  length before the exchange is done
+  pub fn set_buffers(&mut self, buffers: Vec<Vec<u8>>) {
+    let mut buffers: VecDeque<Vec<u8>> = buffers.into_iter().collect();
+    self.buffer = buffers.pop_front().unwrap_or(vec![]);
+    self.pending = buffers;
+  }
+
   pub fn get_buffer(&self) -> &[u8] {
     &self.buffer
   }
@@ -86,36 +112,19 @@ impl TcpHandler {
     // This will loop forever, or until the transaction is done.
     loop {
       self.state = match self.state {
-        TcpState::WillReadLength => {
+        TcpState::WillReadLength | TcpState::WillRead{ .. } => {
           if events.is_readable() {
-            // assuming we will always be able to read two bytes.
-            let mut len_bytes: [u8;2] = [0u8;2];
-            let len_read = try!((&mut self.stream).take(2).read(&mut len_bytes)) as u16;
-            if len_read < 2 {
-              debug!("did not read all len_bytes expected: 2 got: {:?} bytes from: {:?}", len_read, self.stream);
-              return Err(io::Error::new(io::ErrorKind::InvalidData, "did not receive the length"));
-            }
-
-            let length = (len_bytes[0] as u16) << 8 & 0xFF00 | len_bytes[1] as u16 & 0x00FF;
-            self.buffer.reserve(length as usize);
-            self.buffer.clear();
-            TcpState::WillRead{ length: length } // TODO clean up state change with param...
-          } else {
-            return Ok(self.state); // wrong socket state...
-          }
-        },
-        TcpState::WillRead{ length } => {
-          if events.is_readable() {
-            // this will return if it would block with ErrKind::WouldBlock
-            // TODO: add a TryTake to mio for doing the same thing as TryRead?
-            try!((&mut self.stream).take((length as usize - self.buffer.len()) as u64).read_to_end(&mut self.buffer));
-
-            // if we got all the bits...
-            if self.buffer.len() == length as usize {
-              self.state.next_state(self.tcp_type)
-            } else {
-              // still waiting on some more
-              TcpState::WillRead { length: length }
+            // this will return if it would block with ErrKind::WouldBlock, the codec keeps what
+            //  was read of the message until the stream is readable again
+            match self.codec.read_message(&mut self.stream) {
+              Ok(message) => {
+                self.buffer = message;
+                self.state.next_state(self.tcp_type)
+              },
+              Err(e) => {
+                if let Some(length) = self.codec.get_pending_length() { self.state = TcpState::WillRead{ length: length }; }
+                return Err(e)
+              },
             }
           } else {
             return Ok(self.state); // wrong socket state...
@@ -123,13 +132,8 @@ impl TcpHandler {
         },
         TcpState::WillWriteLength => {
           if events.is_writable() {
-            let len: [u8; 2] = [(self.buffer.len() >> 8 & 0xFF) as u8, (self.buffer.len() & 0xFF) as u8];
-            let wrote: usize = try!(self.stream.write(&len));
-
-            if wrote != 2 {
-              return Err(io::Error::new(io::ErrorKind::InvalidData, "did not write the length"));
-            }
-
+            // the length is written with the message, the buffer is written until it is empty
+            self.buffer = try!(self.codec.encode(&self.buffer));
             self.state.next_state(self.tcp_type)
           } else {
             return Ok(self.state); // wrong socket state...
@@ -138,9 +142,13 @@ impl TcpHandler {
         TcpState::WillWrite => {
           if events.is_writable() {
             let wrote: usize = try!(self.stream.write(&self.buffer));
-            for _ in 0..wrote { self.buffer.pop(); } // adnvance the current position in the buffer
-            if self.buffer.is_empty() { self.state.next_state(self.tcp_type) }
-            else { TcpState::WillWrite }
+            self.buffer.drain(..wrote); // advance the current position in the buffer
+            if !self.buffer.is_empty() { TcpState::WillWrite }
+            else if let Some(next) = self.pending.pop_front() {
+              // the next message of a multiple message response
+              self.buffer = next;
+              TcpState::WillWriteLength
+            } else { self.state.next_state(self.tcp_type) }
           } else {
             return Ok(self.state); // wrong socket state...
           }
@@ -152,9 +160,23 @@ impl TcpHandler {
     }
   }
 
+  /// for clients, skips the write of a request to read the next message from the stream, e.g.
+  ///  the subsequent messages of a zone transfer.
+  pub fn expect_read(&mut self) {
+    self.buffer.clear();
+    self.codec.reset();
+    self.state = TcpState::WillReadLength;
+  }
+
+  /// true if the handler is waiting for the next message, none of which has been read
+  pub fn is_idle(&self) -> bool {
+    self.state == TcpState::WillReadLength && self.codec.is_idle()
+  }
+
   /// resets the state of the handler to perform more requests if desired.
   ///  clears the buffers and sets the state back to the initial state
   pub fn reset(&mut self) {
+    if self.state == TcpState::Done { self.request_count += 1; }
     self.state = TcpState::initial_state(self.tcp_type);
   }
 }
@@ -178,8 +200,7 @@ impl TcpState {
 
   pub fn next_state(&self, tcp_type: TcpType) -> Self {
     match *self {
-      TcpState::WillReadLength => panic!("just use the enum constructor"),
-      TcpState::WillRead {..} => match tcp_type {
+      TcpState::WillReadLength | TcpState::WillRead {..} => match tcp_type {
         TcpType::Client => TcpState::Done, // Write (request) then Read (Response) then done
         TcpType::Server => TcpState::WillWriteLength, // Read (request) then Write (Response) then done
       },
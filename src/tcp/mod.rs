@@ -16,9 +16,13 @@
 
 //! TCP protocol related components for DNS.
 
+mod codec;
 mod handler;
+mod proxy;
 mod tcp_client_connection;
 
+pub use self::codec::{DnsMessageCodec, MAX_MESSAGE_LENGTH};
 pub use self::handler::TcpHandler;
 pub use self::handler::TcpState;
+pub use self::proxy::Proxy;
 pub use self::tcp_client_connection::TcpClientConnection;
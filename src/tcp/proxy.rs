@@ -0,0 +1,236 @@
+// Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proxy tunneling for TCP based DNS clients, SOCKS5 and HTTP CONNECT.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use ::error::*;
+use ::client::connect_timeout;
+
+/// A proxy through which a TCP connection to the name server is tunneled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proxy {
+  /// [RFC 1928](https://tools.ietf.org/html/rfc1928) SOCKS5 proxy, with optional
+  ///  [RFC 1929](https://tools.ietf.org/html/rfc1929) username/password authentication
+  Socks5 { proxy: SocketAddr, credentials: Option<(String, String)> },
+  /// HTTP proxy which supports the CONNECT method
+  HttpConnect { proxy: SocketAddr },
+}
+
+impl Proxy {
+  /// Returns the address of the proxy server
+  pub fn get_proxy(&self) -> SocketAddr {
+    match *self {
+      Proxy::Socks5{ proxy, .. } => proxy,
+      Proxy::HttpConnect{ proxy } => proxy,
+    }
+  }
+
+  /// Connects to the proxy and establishes the tunnel to the target, the returned stream is
+  ///  blocking and ready to carry DNS messages to `target`.
+  ///
+  /// # Arguments
+  ///
+  /// * `target` - address of the name server to tunnel to
+  /// * `timeout` - how long to wait for the connection to the proxy, and for each read or write
+  ///               of the handshake, before failing
+  pub fn connect(&self, target: &SocketAddr, timeout: Duration) -> ClientResult<TcpStream> {
+    debug!("connecting to {:?} via proxy {:?}", target, self.get_proxy());
+    let mut stream = try!(connect_timeout(self.get_proxy(), timeout));
+    try!(stream.set_read_timeout(Some(timeout)));
+    try!(stream.set_write_timeout(Some(timeout)));
+
+    match *self {
+      Proxy::Socks5{ ref credentials, .. } => try!(socks5_handshake(&mut stream, target, credentials.as_ref())),
+      Proxy::HttpConnect{ .. } => try!(http_connect_handshake(&mut stream, target)),
+    }
+
+    // the queries are timed by the connection from here on
+    try!(stream.set_read_timeout(None));
+    try!(stream.set_write_timeout(None));
+    Ok(stream)
+  }
+}
+
+fn socks5_handshake<S: Read + Write>(stream: &mut S, target: &SocketAddr, credentials: Option<&(String, String)>) -> ClientResult<()> {
+  // version 5, one method offered; 0x00 no auth, 0x02 username/password
+  let method: u8 = if credentials.is_some() { 0x02 } else { 0x00 };
+  try!(stream.write_all(&[0x05, 0x01, method]));
+
+  let mut reply = [0u8; 2];
+  try!(stream.read_exact(&mut reply));
+  if reply[0] != 0x05 { return Err(ClientErrorKind::Proxy(format!("bad socks version: {}", reply[0])).into()) }
+  if reply[1] != method { return Err(ClientErrorKind::Proxy(format!("socks method rejected: {}", reply[1])).into()) }
+
+  if let Some(&(ref user, ref pass)) = credentials {
+    if user.len() > 255 || pass.len() > 255 {
+      return Err(ClientErrorKind::Proxy("socks credentials exceed 255 bytes".to_string()).into())
+    }
+
+    let mut auth: Vec<u8> = Vec::with_capacity(3 + user.len() + pass.len());
+    auth.push(0x01);
+    auth.push(user.len() as u8);
+    auth.extend_from_slice(user.as_bytes());
+    auth.push(pass.len() as u8);
+    auth.extend_from_slice(pass.as_bytes());
+    try!(stream.write_all(&auth));
+
+    try!(stream.read_exact(&mut reply));
+    if reply[0] != 0x01 { return Err(ClientErrorKind::Proxy(format!("bad socks auth version: {}", reply[0])).into()) }
+    if reply[1] != 0x00 { return Err(ClientErrorKind::Proxy("socks authentication failed".to_string()).into()) }
+  }
+
+  // CONNECT request
+  let mut request: Vec<u8> = vec![0x05, 0x01, 0x00];
+  match *target {
+    SocketAddr::V4(ref addr) => {
+      request.push(0x01);
+      request.extend_from_slice(&addr.ip().octets());
+    },
+    SocketAddr::V6(ref addr) => {
+      request.push(0x04);
+      for segment in addr.ip().segments().iter() {
+        request.push((*segment >> 8) as u8);
+        request.push(*segment as u8);
+      }
+    },
+  }
+  request.push((target.port() >> 8) as u8);
+  request.push(target.port() as u8);
+  try!(stream.write_all(&request));
+
+  let mut header = [0u8; 4];
+  try!(stream.read_exact(&mut header));
+  if header[1] != 0x00 { return Err(ClientErrorKind::Proxy(format!("socks connect failed: {}", header[1])).into()) }
+
+  // drain the bound address, it's not interesting to us
+  let remaining = match header[3] {
+    0x01 => 4 + 2,
+    0x04 => 16 + 2,
+    0x03 => {
+      let mut len = [0u8; 1];
+      try!(stream.read_exact(&mut len));
+      len[0] as usize + 2
+    },
+    atyp => return Err(ClientErrorKind::Proxy(format!("unknown socks address type: {}", atyp)).into()),
+  };
+  let mut bound = vec![0u8; remaining];
+  try!(stream.read_exact(&mut bound));
+
+  Ok(())
+}
+
+fn http_connect_handshake<S: Read + Write>(stream: &mut S, target: &SocketAddr) -> ClientResult<()> {
+  let authority = match *target {
+    SocketAddr::V4(ref addr) => format!("{}:{}", addr.ip(), addr.port()),
+    SocketAddr::V6(ref addr) => format!("[{}]:{}", addr.ip(), addr.port()),
+  };
+  let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", authority);
+  try!(stream.write_all(request.as_bytes()));
+
+  // read the response headers a byte at a time, we must not consume any of the tunneled data
+  let mut response: Vec<u8> = Vec::with_capacity(128);
+  let mut byte = [0u8; 1];
+  while !response.ends_with(b"\r\n\r\n") {
+    if response.len() > 8192 { return Err(ClientErrorKind::Proxy("http proxy response too large".to_string()).into()) }
+    try!(stream.read_exact(&mut byte));
+    response.push(byte[0]);
+  }
+
+  let response = String::from_utf8_lossy(&response);
+  let status_line = response.lines().next().unwrap_or("");
+  let status = status_line.split_whitespace().nth(1).unwrap_or("");
+  if !status.starts_with('2') {
+    return Err(ClientErrorKind::Proxy(format!("http proxy refused: {}", status_line)).into())
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::{self, Read, Write, Cursor};
+  use std::net::{SocketAddr, TcpListener};
+  use std::str::FromStr;
+  use std::time::{Duration, Instant};
+
+  use super::{Proxy, socks5_handshake, http_connect_handshake};
+
+  /// a fake stream, reads are served from a canned response, writes are recorded
+  struct MockStream {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+  }
+
+  impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.input.read(buf) }
+  }
+
+  impl Write for MockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+  }
+
+  #[test]
+  fn test_socks5_no_auth() {
+    let target = SocketAddr::from_str("8.8.8.8:53").unwrap();
+    let mut stream = MockStream{ input: Cursor::new(vec![0x05, 0x00, 0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]), output: vec![] };
+    socks5_handshake(&mut stream, &target, None).expect("handshake failed");
+    assert_eq!(stream.output, vec![0x05, 0x01, 0x00, 0x05, 0x01, 0x00, 0x01, 8, 8, 8, 8, 0, 53]);
+  }
+
+  #[test]
+  fn test_socks5_auth_rejected() {
+    let target = SocketAddr::from_str("8.8.8.8:53").unwrap();
+    let creds = ("user".to_string(), "pass".to_string());
+    let mut stream = MockStream{ input: Cursor::new(vec![0x05, 0x02, 0x01, 0x01]), output: vec![] };
+    assert!(socks5_handshake(&mut stream, &target, Some(&creds)).is_err());
+  }
+
+  #[test]
+  fn test_socks5_auth_version() {
+    let target = SocketAddr::from_str("8.8.8.8:53").unwrap();
+    let creds = ("user".to_string(), "pass".to_string());
+    let mut stream = MockStream{ input: Cursor::new(vec![0x05, 0x02, 0x05, 0x00, 0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]), output: vec![] };
+    assert!(socks5_handshake(&mut stream, &target, Some(&creds)).is_err());
+  }
+
+  #[test]
+  fn test_proxy_timeout() {
+    // the connection is accepted by the backlog of the listener, but nothing is ever answered
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = Proxy::Socks5{ proxy: listener.local_addr().unwrap(), credentials: None };
+    let target = SocketAddr::from_str("8.8.8.8:53").unwrap();
+
+    let start = Instant::now();
+    assert!(proxy.connect(&target, Duration::from_millis(100)).is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+  }
+
+  #[test]
+  fn test_http_connect() {
+    let target = SocketAddr::from_str("[::1]:53").unwrap();
+    let mut stream = MockStream{ input: Cursor::new(b"HTTP/1.1 200 Connection established\r\n\r\n\x00\x01".to_vec()), output: vec![] };
+    http_connect_handshake(&mut stream, &target).expect("handshake failed");
+    assert_eq!(&stream.output[..], &b"CONNECT [::1]:53 HTTP/1.1\r\nHost: [::1]:53\r\n\r\n"[..]);
+    // the tunneled data must not have been consumed
+    assert_eq!(stream.input.position(), 39);
+
+    let mut stream = MockStream{ input: Cursor::new(b"HTTP/1.1 403 Forbidden\r\n\r\n".to_vec()), output: vec![] };
+    assert!(http_connect_handshake(&mut stream, &target).is_err());
+  }
+}
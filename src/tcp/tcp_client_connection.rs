@@ -18,13 +18,14 @@ use std::net::SocketAddr;
 use std::io;
 use std::mem;
 use std::fmt;
+use std::time::Duration;
 
 use mio::tcp::TcpStream;
 use mio::{Token, EventLoop, Handler, EventSet, PollOpt}; // not * b/c don't want confusion with std::net
 
 use ::error::*;
-use ::client::ClientConnection;
-use ::tcp::{TcpHandler, TcpState};
+use ::client::{ClientConnection, Transport, duration_to_ms};
+use ::tcp::{Proxy, TcpHandler, TcpState};
 
 const RESPONSE: Token = Token(0);
 
@@ -33,6 +34,7 @@ pub struct TcpClientConnection {
   handler: Option<TcpHandler>,
   event_loop: EventLoop<ClientHandler>,
   error: Option<ClientError>,
+  timeout: Duration,
 }
 
 impl TcpClientConnection {
@@ -45,36 +47,104 @@ impl TcpClientConnection {
   ///
   /// * `name_server` - address of the name server to use for queries
   pub fn new(name_server: SocketAddr) -> ClientResult<Self> {
+    // 5 seconds is the dig default
+    Self::with_timeout(name_server, Duration::from_secs(5))
+  }
+
+  /// Creates a new client connection, with a specified timeout for each query.
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - address of the name server to use for queries
+  /// * `timeout` - how long to wait for a response before failing with a Timeout error
+  pub fn with_timeout(name_server: SocketAddr, timeout: Duration) -> ClientResult<Self> {
     // TODO: randomize local port binding issue #23
     //  probably not necessary for TCP...
     debug!("connecting to {:?}", name_server);
     let stream = try!(TcpStream::connect(&name_server));
+    Self::from_stream(stream, timeout)
+  }
+
+  /// Creates a new client connection tunneled through the specified proxy.
+  ///
+  /// The proxy handshake is performed synchronously before the stream is handed to the
+  ///  event_loop, all DNS messages then flow over the established tunnel.
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - address of the name server to use for queries
+  /// * `proxy` - SOCKS5 or HTTP CONNECT proxy to tunnel through
+  pub fn with_proxy(name_server: SocketAddr, proxy: &Proxy) -> ClientResult<Self> {
+    // 5 seconds is the dig default
+    Self::with_proxy_and_timeout(name_server, proxy, Duration::from_secs(5))
+  }
+
+  /// Creates a new client connection tunneled through the specified proxy, with a specified
+  ///  timeout for the proxy handshake and for each query.
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - address of the name server to use for queries
+  /// * `proxy` - SOCKS5 or HTTP CONNECT proxy to tunnel through
+  /// * `timeout` - how long to wait for the proxy or for a response before failing
+  pub fn with_proxy_and_timeout(name_server: SocketAddr, proxy: &Proxy, timeout: Duration) -> ClientResult<Self> {
+    let std_stream = try!(proxy.connect(&name_server, timeout));
+    try!(std_stream.set_nonblocking(true));
+    let stream = try!(TcpStream::from_stream(std_stream));
+    Self::from_stream(stream, timeout)
+  }
 
+  fn from_stream(stream: TcpStream, timeout: Duration) -> ClientResult<Self> {
     let mut event_loop: EventLoop<ClientHandler> = try!(EventLoop::new());
-    // TODO make the timeout configurable, 5 seconds is the dig default
-    // TODO the error is private to mio, which makes this awkward...
-    if event_loop.timeout_ms((), 5000).is_err() { return Err(ClientErrorKind::Message("error setting timer").into()) };
     // TODO: Linux requires a register before a reregister, reregister is needed b/c of OSX later
     //  ideally this would not be added to the event loop until the client connection request.
     try!(event_loop.register(&stream, RESPONSE, EventSet::all(), PollOpt::all()));
 
-    Ok(TcpClientConnection{ handler: Some(TcpHandler::new_client_handler(stream)), event_loop: event_loop, error: None })
+    Ok(TcpClientConnection{ handler: Some(TcpHandler::new_client_handler(stream)), event_loop: event_loop, error: None, timeout: timeout })
   }
 }
 
 impl ClientConnection for TcpClientConnection {
   fn send(&mut self, buffer: Vec<u8> ) -> ClientResult<Vec<u8>> {
+    // this is the request message, needs to be set each time
+    // TODO: it would be cool to reuse this buffer.
+    self.handler.as_mut().expect("never none").set_buffer(buffer);
+    self.run()
+  }
+
+  fn receive(&mut self) -> ClientResult<Vec<u8>> {
+    // skip the write, and wait for the next message from the server
+    self.handler.as_mut().expect("never none").expect_read();
+    self.run()
+  }
+
+  fn transport(&self) -> Transport { Transport::Tcp }
+}
+
+impl TcpClientConnection {
+  /// runs the event_loop until the handler has completed its current exchange
+  fn run(&mut self) -> ClientResult<Vec<u8>> {
     self.error = None;
     // TODO: b/c of OSX this needs to be a reregister (since deregister is not working)
     // ideally it should be a register with the later deregister...
     try!(self.event_loop.reregister(self.handler.as_ref().expect("never none").get_stream(), RESPONSE, EventSet::all(), PollOpt::all()));
-    // this is the request message, needs to be set each time
-    // TODO: it would be cool to reuse this buffer.
-    let mut handler = mem::replace(&mut self.handler, None).expect("never none");
-    handler.set_buffer(buffer);
+    let handler = mem::replace(&mut self.handler, None).expect("never none");
     let mut client_handler = ClientHandler{ handler: handler, error: None };
+
+    // each query gets its own timer, cleared once the response arrives
+    // TODO the error is private to mio, which makes this awkward...
+    let timeout = match self.event_loop.timeout_ms((), duration_to_ms(&self.timeout)) {
+      Ok(timeout) => timeout,
+      Err(_) => {
+        self.handler = Some(client_handler.handler);
+        return Err(ClientErrorKind::Message("error setting timer").into())
+      },
+    };
+
     let result = self.event_loop.run(&mut client_handler);
+    self.event_loop.clear_timeout(timeout);
     self.handler = Some(client_handler.handler);
+    self.error = client_handler.error;
 
     try!(result);
 
@@ -160,7 +230,7 @@ impl Handler for ClientHandler {
   }
 
   fn timeout(&mut self, event_loop: &mut EventLoop<Self>, _: ()) {
-    self.error = Some(ClientErrorKind::Message("timed out awaiting response from server(s)").into());
+    self.error = Some(ClientErrorKind::Timeout.into());
     event_loop.shutdown();
   }
 }
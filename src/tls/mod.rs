@@ -0,0 +1,24 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! DNS over TLS, [RFC 7858](https://tools.ietf.org/html/rfc7858), and the opportunistic upgrade
+//! of plaintext name servers to TLS.
+
+mod opportunistic_connection;
+mod tls_client_connection;
+
+pub use self::opportunistic_connection::{OpportunisticConnection, TlsMode};
+pub use self::tls_client_connection::TlsClientConnection;
@@ -0,0 +1,228 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use ::error::*;
+use ::client::{ClientConnection, Transport};
+use ::tls::TlsClientConnection;
+use ::tls::tls_client_connection::DOT_PORT;
+use ::udp::UdpClientConnection;
+
+/// after TLS to a name server fails, plaintext is used for this long before TLS is tried again
+const PROBE_INTERVAL_SECS: u64 = 15 * 60;
+
+/// How the `OpportunisticConnection` uses TLS, see `ResolverConfig::tls()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+  /// Plaintext UDP only
+  Off,
+  /// TLS to port 853 of the name server is preferred, without authentication of the server. If
+  ///  it is not available the queries fall back to plaintext UDP, and TLS is retried later.
+  Opportunistic,
+  /// TLS only, the certificate of the name server must be trusted and issued for its
+  ///  authentication domain name, see `OpportunisticConnection::tls_name()`, or else its address.
+  ///  Queries fail rather than fall back to plaintext.
+  Strict,
+}
+
+impl Default for TlsMode {
+  fn default() -> Self { TlsMode::Off }
+}
+
+/// A connection to a name server which is upgraded to DNS over TLS when the server supports it,
+///  the usage profiles of [RFC 8310](https://tools.ietf.org/html/rfc8310#section-5).
+///
+/// The TLS connection is established with the first query, and kept for those which follow. If
+///  it fails, it is reconnected once, after which failures are remembered and plaintext is used
+///  for the following 15 minutes, unless the mode is `TlsMode::Strict`.
+#[derive(Debug)]
+pub struct OpportunisticConnection {
+  name_server: SocketAddr,
+  mode: TlsMode,
+  timeout: Duration,
+  tls_name: Option<String>,
+  tls: Option<TlsClientConnection>,
+  plain: Option<UdpClientConnection>,
+  failed: Option<Instant>,
+}
+
+impl OpportunisticConnection {
+  /// Creates a new client connection, nothing is sent before the first query.
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - the plaintext address of the name server, TLS uses port 853 of the address
+  /// * `mode` - if and how TLS is used
+  /// * `timeout` - how long to wait for a connection or a response before failing
+  pub fn new(name_server: SocketAddr, mode: TlsMode, timeout: Duration) -> ClientResult<Self> {
+    let plain = if mode == TlsMode::Strict { None } else { Some(try!(UdpClientConnection::with_timeout(name_server, timeout))) };
+
+    Ok(OpportunisticConnection{ name_server: name_server,
+                                mode: mode,
+                                timeout: timeout,
+                                tls_name: None,
+                                tls: None,
+                                plain: plain,
+                                failed: None })
+  }
+
+  /// The authentication domain name of the name server, which its certificate must be issued for
+  ///  in the `TlsMode::Strict` mode, [RFC 8310](https://tools.ietf.org/html/rfc8310#section-2).
+  ///  Without one the certificate must be issued for the address of the name server.
+  pub fn tls_name(&mut self, tls_name: Option<String>) -> &mut Self {
+    self.tls_name = tls_name;
+    self
+  }
+
+  pub fn get_mode(&self) -> TlsMode {
+    self.mode
+  }
+
+  pub fn get_tls_name(&self) -> Option<&str> {
+    self.tls_name.as_ref().map(|s| s as &str)
+  }
+
+  /// true if the queries are currently sent over TLS
+  pub fn is_tls(&self) -> bool {
+    self.tls.is_some()
+  }
+
+  /// true if TLS should be tried, strict connections always retry
+  fn should_probe(&self) -> bool {
+    match self.mode {
+      TlsMode::Off => false,
+      TlsMode::Strict => true,
+      TlsMode::Opportunistic => self.failed.map_or(true, |failed| failed.elapsed() >= Duration::from_secs(PROBE_INTERVAL_SECS)),
+    }
+  }
+
+  /// connects and sends the request over a new TLS connection
+  fn send_tls(&mut self, buffer: Vec<u8>) -> ClientResult<Vec<u8>> {
+    let tls_name_server = SocketAddr::new(self.name_server.ip(), DOT_PORT);
+    let mut tls = match (self.mode, self.tls_name.as_ref()) {
+      (TlsMode::Strict, Some(tls_name)) => try!(TlsClientConnection::with_tls_name(tls_name_server, self.timeout, tls_name)),
+      (mode, _) => try!(TlsClientConnection::with_timeout(tls_name_server, self.timeout, mode == TlsMode::Strict)),
+    };
+    let response = try!(tls.send(buffer));
+    self.tls = Some(tls);
+    Ok(response)
+  }
+}
+
+impl ClientConnection for OpportunisticConnection {
+  fn send(&mut self, buffer: Vec<u8>) -> ClientResult<Vec<u8>> {
+    // the server may have closed an idle connection, which is reconnected before giving up on TLS
+    if let Some(mut tls) = self.tls.take() {
+      match tls.send(buffer.clone()) {
+        Ok(response) => {
+          self.tls = Some(tls);
+          return Ok(response)
+        },
+        Err(error) => debug!("tls connection to {} failed, reconnecting: {}", self.name_server, error),
+      }
+    }
+
+    if self.should_probe() {
+      match self.send_tls(buffer.clone()) {
+        Ok(response) => {
+          self.failed = None;
+          return Ok(response)
+        },
+        Err(error) => {
+          self.failed = Some(Instant::now());
+          if self.mode == TlsMode::Strict { return Err(error) }
+          info!("tls to {} is unavailable, falling back to plaintext: {}", self.name_server, error);
+        },
+      }
+    }
+
+    match self.plain {
+      Some(ref mut plain) => plain.send(buffer),
+      None => Err(ClientErrorKind::Message("tls to the name server is unavailable").into()),
+    }
+  }
+
+  fn receive(&mut self) -> ClientResult<Vec<u8>> {
+    match self.tls {
+      Some(ref mut tls) => tls.receive(),
+      None => Err(ClientErrorKind::Message("multiple message responses are not supported by this connection").into()),
+    }
+  }
+
+  fn transport(&self) -> Transport {
+    if self.tls.is_some() { Transport::Tls } else { Transport::Udp }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::net::{SocketAddr, UdpSocket};
+  use std::thread;
+  use std::time::Duration;
+
+  use ::client::ClientConnection;
+  use ::op::{Message, MessageType, Query};
+  use ::rr::{domain, RecordType};
+  use ::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+  use super::*;
+
+  fn request() -> Vec<u8> {
+    let mut query = Query::new();
+    query.name(domain::Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+    let mut message = Message::new();
+    message.id(1).add_query(query);
+
+    let mut buffer = Vec::new();
+    message.emit(&mut BinEncoder::new(&mut buffer)).unwrap();
+    buffer
+  }
+
+  /// a plaintext server answering each request with an empty response
+  fn udp_server() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    thread::spawn(move || {
+      let mut buffer = [0u8; 512];
+      while let Ok((len, src)) = socket.recv_from(&mut buffer) {
+        let mut message = Message::read(&mut BinDecoder::new(&buffer[..len])).unwrap();
+        message.message_type(MessageType::Response);
+        let mut response = Vec::new();
+        message.emit(&mut BinEncoder::new(&mut response)).unwrap();
+        socket.send_to(&response, src).unwrap();
+      }
+    });
+    addr
+  }
+
+  // nothing listens on port 853 of the loopback address, so TLS is refused
+  #[test]
+  fn test_fallback() {
+    let mut connection = OpportunisticConnection::new(udp_server(), TlsMode::Opportunistic, Duration::from_secs(1)).unwrap();
+    assert!(connection.send(request()).is_ok());
+    assert!(!connection.is_tls());
+    assert!(connection.failed.is_some());
+    assert!(!connection.should_probe());
+  }
+
+  #[test]
+  fn test_strict() {
+    let mut connection = OpportunisticConnection::new(udp_server(), TlsMode::Strict, Duration::from_secs(1)).unwrap();
+    assert!(connection.send(request()).is_err());
+    assert!(connection.should_probe());
+  }
+}
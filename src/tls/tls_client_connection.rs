@@ -0,0 +1,223 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use openssl::ssl::{Ssl, SslContext, SslMethod, SslStream, SSL_VERIFY_NONE, SSL_VERIFY_PEER};
+use openssl::x509::X509;
+
+use ::error::*;
+use ::client::{ClientConnection, Transport, connect_timeout};
+use ::tcp::DnsMessageCodec;
+
+/// The port of DNS over TLS
+pub const DOT_PORT: u16 = 853;
+
+/// DNS over TLS based DNS client, the messages are framed as with TCP, each with a two byte length
+///  prefix. The connection is blocking, and is kept open for all of the queries.
+pub struct TlsClientConnection {
+  name_server: SocketAddr,
+  stream: SslStream<TcpStream>,
+  codec: DnsMessageCodec,
+}
+
+impl TlsClientConnection {
+  /// Creates a new client connection, the certificate of the name server is verified, it must be
+  ///  issued for the address of the name server.
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - address of the name server to use for queries, usually port 853
+  pub fn new(name_server: SocketAddr) -> ClientResult<Self> {
+    // 5 seconds is the dig default
+    Self::with_timeout(name_server, Duration::from_secs(5), true)
+  }
+
+  /// Creates a new client connection, with a specified timeout for connecting and for each query.
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - address of the name server to use for queries, usually port 853
+  /// * `timeout` - how long to wait for the connection or a response before failing
+  /// * `verify` - if the certificate chain of the name server must be trusted by the system
+  ///              roots, and the certificate issued for the address of the name server, see
+  ///              `with_tls_name()` to verify a name instead. Without verification the
+  ///              connection is only private from passive observers, the opportunistic privacy
+  ///              profile of RFC 7858.
+  pub fn with_timeout(name_server: SocketAddr, timeout: Duration, verify: bool) -> ClientResult<Self> {
+    let address = name_server.ip().to_string();
+    Self::connect(name_server, timeout, if verify { Some(&address) } else { None })
+  }
+
+  /// Creates a new client connection, the certificate chain of the name server must be trusted by
+  ///  the system roots, and the certificate issued for the authentication domain name, the strict
+  ///  privacy profile of [RFC 8310](https://tools.ietf.org/html/rfc8310#section-5.1).
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - address of the name server to use for queries, usually port 853
+  /// * `timeout` - how long to wait for the connection or a response before failing
+  /// * `tls_name` - the authentication domain name, e.g. `dns.example.com`, which must be one of
+  ///                the subject alternative names of the certificate. An IP address is matched
+  ///                against the IP address names.
+  pub fn with_tls_name(name_server: SocketAddr, timeout: Duration, tls_name: &str) -> ClientResult<Self> {
+    Self::connect(name_server, timeout, Some(tls_name))
+  }
+
+  /// connects, verifying the certificate against the name if there is one
+  fn connect(name_server: SocketAddr, timeout: Duration, tls_name: Option<&str>) -> ClientResult<Self> {
+    debug!("connecting with tls to {:?}", name_server);
+    let stream = try!(connect_timeout(name_server, timeout));
+    try!(stream.set_read_timeout(Some(timeout)));
+    try!(stream.set_write_timeout(Some(timeout)));
+
+    let mut context = try!(SslContext::new(SslMethod::Sslv23).map_err(ssl_error));
+    if tls_name.is_some() {
+      try!(context.set_default_verify_paths().map_err(ssl_error));
+      context.set_verify(SSL_VERIFY_PEER, None);
+    } else {
+      context.set_verify(SSL_VERIFY_NONE, None);
+    }
+
+    let ssl = try!(Ssl::new(&context).map_err(ssl_error));
+    if let Some(tls_name) = tls_name {
+      // SNI is only for host names
+      if tls_name.parse::<IpAddr>().is_err() { try!(ssl.set_hostname(tls_name).map_err(ssl_error)); }
+    }
+
+    let stream = try!(SslStream::connect(ssl, stream).map_err(ssl_error));
+
+    // the chain is verified by openssl, but not that the certificate is that of the name server
+    if let Some(tls_name) = tls_name {
+      if !stream.ssl().peer_certificate().map_or(false, |cert| verify_name(&cert, tls_name)) {
+        return Err(ClientErrorKind::Msg(format!("the certificate of {} is not issued for {}", name_server, tls_name)).into())
+      }
+    }
+
+    Ok(TlsClientConnection{ name_server: name_server, stream: stream, codec: DnsMessageCodec::new() })
+  }
+
+  /// reads the next length prefixed message
+  fn read_message(&mut self) -> ClientResult<Vec<u8>> {
+    // the stream is blocking, the message is read in full or the read timed out
+    self.codec.reset();
+    Ok(try!(self.codec.read_message(&mut self.stream)))
+  }
+}
+
+impl ClientConnection for TlsClientConnection {
+  fn send(&mut self, buffer: Vec<u8>) -> ClientResult<Vec<u8>> {
+    let framed = try!(self.codec.encode(&buffer));
+
+    // a single write, so that the length and message are sent in one TLS record
+    try!(self.stream.write_all(&framed));
+    try!(self.stream.flush());
+    self.read_message()
+  }
+
+  fn receive(&mut self) -> ClientResult<Vec<u8>> {
+    self.read_message()
+  }
+
+  fn transport(&self) -> Transport { Transport::Tls }
+}
+
+impl fmt::Debug for TlsClientConnection {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "TlsClientConnection: {:?}", self.name_server)
+  }
+}
+
+fn ssl_error<E: ::std::error::Error>(error: E) -> ClientError {
+  ClientErrorKind::Msg(format!("tls error: {}", error)).into()
+}
+
+/// true if the certificate is issued for the name, a host name or an IP address, which must be
+///  one of the subject alternative names, [RFC 6125](https://tools.ietf.org/html/rfc6125#section-6).
+///  The common name is not used.
+fn verify_name(cert: &X509, tls_name: &str) -> bool {
+  let names = match cert.subject_alt_names() {
+    Some(names) => names,
+    None => return false,
+  };
+
+  match tls_name.parse::<IpAddr>() {
+    Ok(address) => names.iter().filter_map(|name| name.ipaddress()).any(|ip| matches_ip(&address, ip)),
+    Err(..) => names.iter().filter_map(|name| name.dnsname()).any(|pattern| matches_dns(pattern, tls_name)),
+  }
+}
+
+fn matches_ip(address: &IpAddr, ip: &[u8]) -> bool {
+  match *address {
+    IpAddr::V4(ref address) => address.octets()[..] == *ip,
+    IpAddr::V6(ref address) => address.octets()[..] == *ip,
+  }
+}
+
+/// the names are compared without case, a wildcard may only be the whole of the leftmost label,
+///  and matches exactly one label
+fn matches_dns(pattern: &str, name: &str) -> bool {
+  let pattern = pattern.trim_right_matches('.').to_lowercase();
+  let name = name.trim_right_matches('.').to_lowercase();
+
+  if pattern.starts_with("*.") {
+    match name.find('.') {
+      Some(dot) => dot > 0 && name[dot..] == pattern[1..],
+      None => false,
+    }
+  } else {
+    pattern == name
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use openssl::x509::X509;
+
+  use super::{matches_dns, verify_name};
+
+  // a self-signed certificate for dns.example.com, *.dns.example.net and 192.0.2.53
+  const CERT: &'static str = include_str!("test_cert.pem");
+
+  #[test]
+  fn test_verify_name() {
+    let cert = X509::from_pem(&mut CERT.as_bytes()).expect("bad test certificate");
+
+    assert!(verify_name(&cert, "dns.example.com"));
+    assert!(verify_name(&cert, "DNS.Example.com."));
+    assert!(verify_name(&cert, "a.dns.example.net"));
+    assert!(verify_name(&cert, "192.0.2.53"));
+
+    // a certificate for a different name is rejected
+    assert!(!verify_name(&cert, "dns.example.org"));
+    assert!(!verify_name(&cert, "example.com"));
+    assert!(!verify_name(&cert, "a.b.dns.example.net"));
+    assert!(!verify_name(&cert, "dns.example.net"));
+    assert!(!verify_name(&cert, "192.0.2.54"));
+    assert!(!verify_name(&cert, "::1"));
+  }
+
+  #[test]
+  fn test_matches_dns() {
+    assert!(matches_dns("*.example.com", "www.example.com"));
+    assert!(!matches_dns("*.example.com", ".example.com"));
+    assert!(!matches_dns("*.example.com", "wwwexample.com"));
+    assert!(!matches_dns("www.*.com", "www.example.com"));
+  }
+}
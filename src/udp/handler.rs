@@ -13,15 +13,19 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use std::cmp;
 use std::net::SocketAddr;
 use std::io;
 use std::sync::Arc;
+use std::time::Instant;
 
 use mio::udp::UdpSocket;
 use mio::EventSet; // not * b/c don't want confusion with std::net
 
-use ::authority::Catalog;
+use ::authority::{Catalog, QueryPolicy};
+use ::dnstap::{Dnstap, SocketProtocol};
 use ::op::*;
+use ::server::{QueryLog, QueryLogEntry, RateLimitAction, RateLimiter, Transport};
 use ::serialize::binary::*;
 
 pub struct UdpHandler {
@@ -42,7 +46,11 @@ impl UdpHandler {
     UdpHandler{ state: UdpState::Writing, addr: server_addr, message: request, buffer: bytes}
   }
 
-  pub fn new_server(socket: &UdpSocket, catalog: Arc<Catalog>) -> Option<Self> {
+  /// Reads a request from the socket and prepares the response to it. If the rate limiter drops
+  ///  the response, the handler is already done and has nothing to send. The request is logged to
+  ///  the query log and dnstap, if there are.
+  pub fn new_server(socket: &UdpSocket, catalog: Arc<Catalog>, rate_limiter: Option<&mut RateLimiter>, policy: Option<&QueryPolicy>,
+                    query_log: Option<&QueryLog>, dnstap: Option<&Dnstap>) -> Option<Self> {
     //let mut buf: Vec<u8> = Vec::with_capacity(512);
     let mut buf: [u8; 4096] = [0u8; 4096];
     let recv_result = socket.recv_from(&mut buf);
@@ -50,6 +58,8 @@ impl UdpHandler {
     match recv_result {
       Ok(Some((length, addr))) => {
         debug!("revieved {} bytes from {:?}", length, addr);
+        let received = Instant::now();
+        let request_bytes: &[u8] = &buf[..length];
         let request = {
           let mut decoder = BinDecoder::new(&buf);
           Message::read(&mut decoder)
@@ -60,7 +70,42 @@ impl UdpHandler {
             warn!("unable to decode request from client: {:?}: {}", addr, decode_error);
             Catalog::error_msg(0/* id is in the message... */, OpCode::Query/* right default? */, ResponseCode::FormErr)
           },
-          Ok(ref req) => catalog.handle_request(req), // this is a buf if the unwrap() fails
+          Ok(ref req) if !policy.map_or(true, |policy| policy.is_query_allowed(Some(&addr.ip()))) => {
+            info!("refusing request from: {}", addr);
+            Catalog::error_msg(req.get_id(), req.get_op_code(), ResponseCode::Refused)
+          },
+          // AXFR is not defined over UDP, RFC 5936 4.2, the transfer would be neither split into
+          //  messages nor signed, the truncated response has the client retry over TCP
+          Ok(ref req) if Catalog::is_transfer(req) => {
+            debug!("truncating transfer over UDP to: {}", addr);
+            let mut response = Catalog::error_msg(req.get_id(), req.get_op_code(), ResponseCode::NoError);
+            response.add_all_queries(req.get_queries()).truncated(true);
+            response
+          },
+          Ok(ref req) => catalog.handle_request_from(req, addr, request_bytes), // this is a buf if the unwrap() fails
+        };
+
+        // limit the responses to spoofed sources, a slipped response is empty, with TC set. The
+        //  answers synthesized from a wildcard are limited together.
+        let action = match rate_limiter {
+          Some(limiter) => {
+            let wildcard = match response.get_queries().first() {
+              Some(query) if !response.get_answers().is_empty() => catalog.find_wildcard(query.get_name(), Some(&addr.ip())),
+              _ => None,
+            };
+            limiter.check(&addr.ip(), &response, wildcard.as_ref())
+          },
+          None => RateLimitAction::Send,
+        };
+        let response = match action {
+          RateLimitAction::Send => response,
+          RateLimitAction::Slip => { debug!("rate limited, truncating response to: {}", addr); response.truncate() },
+          RateLimitAction::Drop => {
+            debug!("rate limited, dropping response to: {}", addr);
+            if let Some(query_log) = query_log { query_log.log(&QueryLogEntry::new(addr, Transport::Udp, request.as_ref().ok(), &response, 0, received)); }
+            if let Some(dnstap) = dnstap { dnstap.log_server(addr, SocketProtocol::Udp, response.is_recursion_available(), received, request_bytes, None::<&[u8]>); }
+            return Some(UdpHandler{ state: UdpState::Done, addr: addr, message: response, buffer: vec![] })
+          },
         };
 
         // serialize the data for the response
@@ -68,12 +113,27 @@ impl UdpHandler {
 
         // TODO: this is the easiest spot to do this, but is least useful to shorten
         //  also, it's not clear how useful a truncated response is for secure operations
-        if buf.len() > request.unwrap().get_max_payload() as usize {
-          // we must truncate the response
-          let truncated_response = response.truncate();
+        // EDNS clients are sent up to their payload size, if it is not more than ours, others 512
+        let max_payload = match request {
+          Ok(ref req) if req.get_edns().is_some() => cmp::min(req.get_max_payload(), catalog.get_max_payload()),
+          _ => 512,
+        } as usize;
+        if buf.len() > max_payload {
+          // we must truncate the response, dropping whole RRSets until it fits
+          let truncated_response = match response.truncate_to(max_payload) {
+            Ok(truncated_response) => truncated_response,
+            Err(encode_error) => {
+              error!("error truncating response to client: {}", encode_error);
+              response.truncate()
+            },
+          };
           let buf = Self::serialize_msg(buf, &truncated_response);
+          if let Some(query_log) = query_log { query_log.log(&QueryLogEntry::new(addr, Transport::Udp, request.as_ref().ok(), &truncated_response, buf.len(), received)); }
+          if let Some(dnstap) = dnstap { dnstap.log_server(addr, SocketProtocol::Udp, response.is_recursion_available(), received, request_bytes, Some(&buf[..])); }
           Some(UdpHandler{ state: UdpState::Writing, addr: addr, message: response, buffer: buf})
         } else {
+          if let Some(query_log) = query_log { query_log.log(&QueryLogEntry::new(addr, Transport::Udp, request.as_ref().ok(), &response, buf.len(), received)); }
+          if let Some(dnstap) = dnstap { dnstap.log_server(addr, SocketProtocol::Udp, response.is_recursion_available(), received, request_bytes, Some(&buf[..])); }
           Some(UdpHandler{ state: UdpState::Writing, addr: addr, message: response, buffer: buf})
         }
       },
@@ -85,6 +145,11 @@ impl UdpHandler {
     }
   }
 
+  /// true if there is nothing left to send
+  pub fn is_done(&self) -> bool {
+    if let UdpState::Done = self.state { true } else { false }
+  }
+
   pub fn remote_addr(&self) -> SocketAddr {
     self.addr
   }
@@ -17,6 +17,7 @@
 use std::mem;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::fmt;
+use std::time::Duration;
 
 use mio::udp::UdpSocket;
 use mio::{Token, EventLoop, Handler, EventSet, PollOpt}; // not * b/c don't want confusion with std::net
@@ -24,7 +25,9 @@ use rand::Rng;
 use rand;
 
 use ::error::*;
-use client::ClientConnection;
+use ::client::{ClientConnection, Transport, duration_to_ms};
+use ::op::Message;
+use ::serialize::binary::{BinDecoder, BinSerializable};
 
 const RESPONSE: Token = Token(0);
 
@@ -33,6 +36,7 @@ pub struct UdpClientConnection {
   name_server: SocketAddr,
   socket: Option<UdpSocket>,
   event_loop: EventLoop<Response>,
+  timeout: Duration,
 }
 
 impl UdpClientConnection {
@@ -62,20 +66,28 @@ impl UdpClientConnection {
   ///
   /// * `name_server` - address of the name server to use for queries
   pub fn new(name_server: SocketAddr) -> ClientResult<Self> {
+    // 5 seconds is the dig default
+    Self::with_timeout(name_server, Duration::from_secs(5))
+  }
+
+  /// Creates a new client connection, with a specified timeout for each query.
+  ///
+  /// # Arguments
+  ///
+  /// * `name_server` - address of the name server to use for queries
+  /// * `timeout` - how long to wait for a response before failing with a Timeout error
+  pub fn with_timeout(name_server: SocketAddr, timeout: Duration) -> ClientResult<Self> {
     // TODO: allow the bind address to be specified...
     // client binds to all addresses... this shouldn't ever fail
     let socket = try!(Self::next_bound_local_address());
     let mut event_loop: EventLoop<Response> = try!(EventLoop::new());
-    // TODO make the timeout configurable, 5 seconds is the dig default
-    // TODO the error is private to mio, which makes this awkward...
-    if event_loop.timeout_ms((), 5000).is_err() { return Err(ClientErrorKind::Message("error setting timer").into()) };
     // TODO: Linux requires a register before a reregister, reregister is needed b/c of OSX later
     //  ideally this would not be added to the event loop until the client connection request.
     try!(event_loop.register(&socket, RESPONSE, EventSet::readable(), PollOpt::all()));
 
     debug!("client event_loop created");
 
-    Ok(UdpClientConnection{name_server: name_server, socket: Some(socket), event_loop: event_loop})
+    Ok(UdpClientConnection{name_server: name_server, socket: Some(socket), event_loop: event_loop, timeout: timeout})
   }
 }
 
@@ -84,26 +96,46 @@ impl ClientConnection for UdpClientConnection {
     debug!("client reregistering");
     // TODO: b/c of OSX this needs to be a reregister (since deregister is not working)
     try!(self.event_loop.reregister(self.socket.as_ref().expect("never none"), RESPONSE, EventSet::readable(), PollOpt::all()));
+    // the request is decoded to validate that responses match it
+    let request = try!(Message::read(&mut BinDecoder::new(&buffer)));
+
     debug!("client sending");
     try!(self.socket.as_ref().expect("never none").send_to(&buffer, &self.name_server));
     debug!("client sent data");
 
-    let mut response: Response = Response::new(mem::replace(&mut self.socket, None).expect("never none"));
+    let mut response: Response = Response::new(mem::replace(&mut self.socket, None).expect("never none"), self.name_server, request);
+
+    // each query gets its own timer, cleared once the response arrives
+    // TODO the error is private to mio, which makes this awkward...
+    let timeout = match self.event_loop.timeout_ms((), duration_to_ms(&self.timeout)) {
+      Ok(timeout) => timeout,
+      Err(_) => {
+        self.socket = Some(response.socket);
+        return Err(ClientErrorKind::Message("error setting timer").into())
+      },
+    };
 
     // run_once should be enough, if something else nepharious hits the socket, what?
-    try!(self.event_loop.run(&mut response));
+    let run_result = self.event_loop.run(&mut response);
+    self.event_loop.clear_timeout(timeout);
+    if let Err(e) = run_result {
+      self.socket = Some(response.socket);
+      return Err(e.into())
+    }
     debug!("client event_loop running");
 
 
+    // the socket must be returned before any error, otherwise the connection is unusable
+    self.socket = Some(response.socket);
     if response.error.is_some() { return Err(response.error.unwrap()) }
     if response.buf.is_none() { return Err(ClientErrorKind::Message("no data was received from the remote").into()) }
-    let result = Ok(response.buf.unwrap());
     //debug!("client deregistering");
     // TODO: when this line is added OSX starts failing, but we should have it...
     // try!(self.event_loop.deregister(&response.socket));
-    self.socket = Some(response.socket);
-    result
+    Ok(response.buf.unwrap())
   }
+
+  fn transport(&self) -> Transport { Transport::Udp }
 }
 
 impl fmt::Debug for UdpClientConnection {
@@ -117,11 +149,33 @@ struct Response {
   pub addr: Option<SocketAddr>,
   pub error: Option<ClientError>,
   pub socket: UdpSocket,
+  name_server: SocketAddr,
+  request: Message,
 }
 
 impl Response {
-  pub fn new(socket: UdpSocket) -> Self {
-    Response{ buf: None, addr: None, error: None, socket: socket }
+  pub fn new(socket: UdpSocket, name_server: SocketAddr, request: Message) -> Self {
+    Response{ buf: None, addr: None, error: None, socket: socket, name_server: name_server, request: request }
+  }
+
+  /// validates that the packet is a response to our request, from the server we sent it to
+  fn is_valid_response(&self, buf: &[u8], addr: &SocketAddr) -> bool {
+    if *addr != self.name_server {
+      warn!("dropping response from unexpected address: {}, expected: {}", addr, self.name_server);
+      return false
+    }
+
+    match Message::read(&mut BinDecoder::new(buf)) {
+      Ok(ref response) if response.is_response_to(&self.request) => true,
+      Ok(..) => {
+        warn!("dropping response which does not match the request from: {}", addr);
+        false
+      },
+      Err(e) => {
+        warn!("dropping malformed response from: {}: {}", addr, e);
+        false
+      },
+    }
   }
 }
 
@@ -155,16 +209,18 @@ impl Handler for Response {
           return
         }
 
-        // TODO: ignore if not from the IP that we requested
         let (length, addr) = recv_result.unwrap().unwrap();
         debug!("bytes: {:?} from: {:?}", length, addr);
-        self.addr = Some(addr);
 
         if length == 0 {
           debug!("0 bytes recieved from: {}", addr);
           return
         }
 
+        // anything which doesn't match is dropped, and we continue to wait for the real response
+        if !self.is_valid_response(&buf[..length], &addr) { return }
+        self.addr = Some(addr);
+
         // we got our response, shutdown.
         event_loop.shutdown();
 
@@ -179,9 +235,29 @@ impl Handler for Response {
   }
 
   fn timeout(&mut self, event_loop: &mut EventLoop<Self>, _: ()) {
-    self.error = Some(ClientErrorKind::Message("timed out awaiting response from server(s)").into());
+    self.error = Some(ClientErrorKind::Timeout.into());
     event_loop.shutdown();
   }
 }
 
 // TODO: should test this independently of the client code
+
+#[test]
+fn test_timeout() {
+  use std::net::UdpSocket as StdUdpSocket;
+
+  // nothing will ever be read from this socket
+  let silent = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+  let mut conn = UdpClientConnection::with_timeout(silent.local_addr().unwrap(), Duration::from_millis(100)).unwrap();
+
+  match conn.send(vec![0u8; 12]) {
+    Err(ref e) => match *e.kind() {
+      ClientErrorKind::Timeout => (),
+      ref kind => panic!("expected timeout: {:?}", kind),
+    },
+    Ok(..) => panic!("expected timeout"),
+  }
+
+  // the connection should remain usable after a timeout
+  assert!(conn.send(vec![0u8; 12]).is_err());
+}